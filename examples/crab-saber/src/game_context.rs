@@ -1,8 +1,4 @@
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    time::{Duration, Instant},
-};
+use std::{collections::HashMap, fmt::Debug, time::Duration};
 
 use hotham::{
     asset_importer::{self, add_model_to_world},
@@ -10,22 +6,26 @@ use hotham::{
         hand::Handedness,
         physics::{ActiveCollisionTypes, BodyType, SharedShape},
         ui_panel::add_ui_panel_to_world,
-        Collider, GlobalTransform, LocalTransform, Pointer, RigidBody, SoundEmitter, Visible,
+        Collider, GlobalTransform, LocalTransform, Pointer, RigidBody, SoundEmitter,
     },
     contexts::{audio_context::MusicTrack, physics_context::DEFAULT_COLLISION_GROUP, AudioContext},
+    entity_pool::EntityPool,
+    gameplay::{BeatClock, BeatSpawner, Score, StateMachine},
     hecs::{Entity, World},
     vk, Engine,
 };
-use rand::prelude::*;
 
 use crate::{
     components::{Color, Cube},
     systems::sabers::add_saber,
 };
 
+/// How many cubes of each color can be in play at once
+const CUBES_PER_COLOR: usize = 10;
+
 pub struct GameContext {
-    pub current_score: i32,
-    pub state: GameState,
+    pub score: Score,
+    pub state: StateMachine<GameState>,
     pub pointer: Entity,
     pub main_menu_panel: Entity,
     pub score_panel: Entity,
@@ -34,14 +34,17 @@ pub struct GameContext {
     pub backstop: Entity,
     pub songs: HashMap<String, Song>,
     pub models: HashMap<String, World>,
-    pub last_spawn_time: Instant,
+    pub beat_clock: BeatClock,
+    pub cube_spawner: BeatSpawner,
+    pub red_cubes: EntityPool,
+    pub blue_cubes: EntityPool,
     pub sound_effects: HashMap<String, SoundEmitter>,
 }
 
 impl Debug for GameContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("GameContext")
-            .field("current_score", &self.current_score)
+            .field("score", &self.score)
             .field("state", &self.state)
             .field("music_tracks", &self.songs)
             .finish()
@@ -67,9 +70,11 @@ impl GameContext {
         let sabers = [Color::Blue, Color::Red].map(|color| add_saber(color, &models, world));
 
         // Spawn cubes
-        for _ in 0..20 {
-            pre_spawn_cube(world, &models);
-        }
+        let [red_cubes, blue_cubes] = [Color::Red, Color::Blue].map(|color| {
+            EntityPool::new(world, CUBES_PER_COLOR, |world| {
+                pre_spawn_cube(color, world, &models)
+            })
+        });
 
         // Add a pointer to let the player interact with the UI
         let pointer = add_pointer(&models, world);
@@ -115,17 +120,27 @@ impl GameContext {
             backstop,
             main_menu_panel,
             score_panel,
-            current_score: 0,
-            state: GameState::Init,
+            score: Default::default(),
+            state: StateMachine::new(GameState::Init),
             blue_saber: sabers[0],
             red_saber: sabers[1],
             songs: Default::default(),
             models,
-            last_spawn_time: Instant::now() - Duration::new(100, 0),
+            beat_clock: BeatClock::new(Duration::ZERO),
+            cube_spawner: BeatSpawner::new(1.),
+            red_cubes,
+            blue_cubes,
             sound_effects: Default::default(),
         }
     }
 
+    pub fn cube_pool(&mut self, color: Color) -> &mut EntityPool {
+        match color {
+            Color::Red => &mut self.red_cubes,
+            Color::Blue => &mut self.blue_cubes,
+        }
+    }
+
     pub fn add_songs(&mut self, audio_context: &mut AudioContext) {
         let main_menu_mp3 = include_bytes!("../assets/TrackTribe - Cloud Echo.mp3").to_vec();
         self.songs.insert(
@@ -215,9 +230,7 @@ fn add_environment(models: &std::collections::HashMap<String, World>, world: &mu
     add_model_to_world("Ramp", models, world, None);
 }
 
-pub fn pre_spawn_cube(world: &mut World, models: &HashMap<String, World>) {
-    // Set the color randomly
-    let color = if random() { Color::Red } else { Color::Blue };
+pub fn pre_spawn_cube(color: Color, world: &mut World, models: &HashMap<String, World>) -> Entity {
     let model_name = match color {
         Color::Red => "Red Cube",
         Color::Blue => "Blue Cube",
@@ -229,7 +242,6 @@ pub fn pre_spawn_cube(world: &mut World, models: &HashMap<String, World>) {
         ..Default::default()
     };
 
-    world.remove_one::<Visible>(cube).unwrap();
     world
         .insert(
             cube,
@@ -254,6 +266,8 @@ pub fn pre_spawn_cube(world: &mut World, models: &HashMap<String, World>) {
             ),
         )
         .unwrap();
+
+    cube
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
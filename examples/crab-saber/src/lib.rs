@@ -70,7 +70,8 @@ fn handle_state_change(tick_data: &TickData, engine: &mut Engine, game_context:
     match (tick_data.previous_state, tick_data.current_state) {
         (SessionState::VISIBLE, SessionState::FOCUSED) => {
             audio_context.resume_music_track();
-            match game_context.state {
+            game_context.beat_clock.resume();
+            match game_context.state.current() {
                 GameState::Init => {}
                 GameState::MainMenu | GameState::GameOver => {
                     show(world, game_context.pointer);
@@ -83,7 +84,8 @@ fn handle_state_change(tick_data: &TickData, engine: &mut Engine, game_context:
         }
         (SessionState::FOCUSED, SessionState::VISIBLE) => {
             audio_context.pause_music_track();
-            match game_context.state {
+            game_context.beat_clock.pause();
+            match game_context.state.current() {
                 GameState::Init => {}
                 GameState::MainMenu | GameState::GameOver => {
                     hide(world, game_context.pointer);
@@ -1,5 +1,3 @@
-use std::time::{Duration, Instant};
-
 use crate::{
     components::{Color, Cube},
     game_context::{GameContext, GameState, Song},
@@ -11,8 +9,9 @@ use hotham::{
         Collider, LocalTransform, RigidBody, UIPanel, Visible,
     },
     contexts::{AudioContext, HapticContext},
+    gameplay::BeatClock,
     glam,
-    hecs::{Entity, With, World},
+    hecs::{Entity, World},
     Engine,
 };
 use rand::prelude::*;
@@ -49,7 +48,7 @@ fn transition(
     audio_context: &mut AudioContext,
     next_state: GameState,
 ) {
-    let current_state = &game_context.state;
+    let current_state = game_context.state.current();
     match (current_state, &next_state) {
         (GameState::Init | GameState::GameOver, GameState::MainMenu) => {
             // Make visible
@@ -84,13 +83,12 @@ fn transition(
                     }
                 })
                 .collect();
-
-            // Reset spawn time
-            game_context.last_spawn_time -= Duration::new(100, 0);
         }
         (GameState::MainMenu, GameState::Playing(song)) => {
-            // Reset score
-            game_context.current_score = 0;
+            // Reset score, and start counting beats from the start of the song
+            game_context.score.reset();
+            game_context.beat_clock = BeatClock::new(song.beat_length);
+            game_context.cube_spawner.reset();
 
             // Make visible
             world
@@ -123,19 +121,18 @@ fn transition(
             let _ = world.remove_one::<Visible>(game_context.red_saber);
 
             // Destroy all cubes
-            let live_cubes = world
-                .query::<With<(&Color, &RigidBody, &Collider), (&Visible, &Cube)>>()
+            let live_cubes = [&game_context.red_cubes, &game_context.blue_cubes]
                 .iter()
-                .map(|(e, _)| e)
-                .collect::<Vec<_>>();
-            dispose_of_cubes(live_cubes, world);
+                .flat_map(|pool| pool.active().to_vec())
+                .collect();
+            dispose_of_cubes(live_cubes, world, game_context);
 
             // Switch tracks
             let song = game_context.songs.get("Game Over").unwrap();
             audio_context.play_music_track(song.track);
 
             // Set panel text and add "OK" button
-            let message = if game_context.current_score > 0 {
+            let message = if game_context.score.points > 0 {
                 "You did adequately!"
             } else {
                 "YOU FAILED!"
@@ -144,7 +141,7 @@ fn transition(
                 .get::<&mut UIPanel>(game_context.main_menu_panel)
                 .unwrap();
 
-            panel.text = format!("Game Over\n{}", message);
+            panel.text = format!("Game Over\n{}\n{}", message, game_context.score.result());
             panel.buttons = vec![UIPanelButton::new("Back to main menu")];
         }
         _ => panic!(
@@ -153,7 +150,7 @@ fn transition(
         ),
     }
 
-    game_context.state = next_state;
+    game_context.state.set(next_state);
 }
 
 fn run(
@@ -162,7 +159,7 @@ fn run(
     audio_context: &mut AudioContext,
     haptic_context: &mut HapticContext,
 ) -> Option<GameState> {
-    match &mut game_context.state {
+    match game_context.state.current() {
         GameState::Init => return Some(GameState::MainMenu),
         GameState::MainMenu => {
             let panel = world.get::<&UIPanel>(game_context.main_menu_panel).unwrap();
@@ -172,12 +169,16 @@ fn run(
             }
         }
         GameState::Playing(song) => {
-            spawn_cube(world, song, &mut game_context.last_spawn_time);
+            game_context.beat_clock.update();
+            if game_context.cube_spawner.due(&game_context.beat_clock) {
+                let song = song.clone();
+                spawn_cube(world, game_context, &song);
+            }
 
             check_for_hits(world, game_context, haptic_context);
             update_panel_text(world, game_context);
 
-            if game_context.current_score < 0
+            if game_context.score.points < 0
                 || audio_context.music_track_status() == SoundState::Stopped
             {
                 return Some(GameState::GameOver);
@@ -198,28 +199,27 @@ fn run(
     None
 }
 
-fn spawn_cube(world: &mut World, song: &mut Song, last_spawn_time: &mut Instant) {
-    if !should_spawn_cube(*last_spawn_time, song.beat_length) {
-        return;
-    }
-
+fn spawn_cube(world: &mut World, game_context: &mut GameContext, song: &Song) {
     let color = if random() { Color::Red } else { Color::Blue };
-    let dead_cube = world
-        .query_mut::<&Color>()
-        .with::<&Cube>()
-        .without::<&Visible>()
-        .into_iter()
-        .find_map(|(e, c)| if c == &color { Some(e) } else { None })
-        .unwrap();
-    revive_cube(dead_cube, world, song);
-    *last_spawn_time = Instant::now();
+    let pool = game_context.cube_pool(color);
+    match pool.acquire(world) {
+        Some(cube) => revive_cube(cube, world, song),
+        None => println!(
+            "All {:?} cubes are in play, skipping this beat: {:?}",
+            color,
+            pool.stats()
+        ),
+    }
 }
 
 fn update_panel_text(world: &mut World, game_context: &mut GameContext) {
     world
         .get::<&mut UIPanel>(game_context.score_panel)
         .unwrap()
-        .text = format!("Score: {}", game_context.current_score);
+        .text = format!(
+        "Score: {}\nCombo: {}",
+        game_context.score.points, game_context.score.combo
+    );
 }
 
 fn check_for_hits(
@@ -240,11 +240,11 @@ fn check_for_hits(
             if let Some(color) = e.get::<&Color>() {
                 match *color {
                     Color::Red => {
-                        game_context.current_score -= 1;
+                        game_context.score.miss(1);
                         pending_sound_effects.push((*c, "Miss"));
                     }
                     Color::Blue => {
-                        game_context.current_score += 1;
+                        game_context.score.hit(1);
                         pending_sound_effects.push((*c, "Hit"));
                     }
                 }
@@ -265,11 +265,11 @@ fn check_for_hits(
             if let Some(color) = e.get::<&Color>() {
                 match *color {
                     Color::Red => {
-                        game_context.current_score += 1;
+                        game_context.score.hit(1);
                         pending_sound_effects.push((*c, "Hit"));
                     }
                     Color::Blue => {
-                        game_context.current_score -= 1;
+                        game_context.score.miss(1);
                         pending_sound_effects.push((*c, "Miss"));
                     }
                 }
@@ -288,7 +288,7 @@ fn check_for_hits(
                 continue;
             };
             if e.get::<&Cube>().is_some() {
-                game_context.current_score -= 1;
+                game_context.score.miss(1);
                 pending_sound_effects.push((*c, "Miss"));
                 println!("MISSED: Adding cube to dispose list: {:?}", c);
                 cubes_to_dispose.push(*c);
@@ -297,18 +297,23 @@ fn check_for_hits(
     }
 
     play_sound_effects(pending_sound_effects, world, game_context);
-    dispose_of_cubes(cubes_to_dispose, world);
+    dispose_of_cubes(cubes_to_dispose, world, game_context);
 }
 
 fn is_cube(e: hotham::hecs::EntityRef) -> bool {
     e.has::<Cube>() && e.has::<Visible>() && e.has::<Collider>() && e.has::<RigidBody>()
 }
 
-fn dispose_of_cubes(cubes_to_dispose: Vec<Entity>, world: &mut World) {
+fn dispose_of_cubes(
+    cubes_to_dispose: Vec<Entity>,
+    world: &mut World,
+    game_context: &mut GameContext,
+) {
     for e in cubes_to_dispose.into_iter() {
-        println!("Removing visibilty of cube: {:?}", e);
-        world.remove_one::<Visible>(e).unwrap();
+        println!("Returning cube to its pool: {:?}", e);
         world.get::<&mut RigidBody>(e).unwrap().linear_velocity = glam::Vec3::ZERO;
+        let color = *world.get::<&Color>(e).unwrap();
+        game_context.cube_pool(color).release(world, e);
     }
 }
 
@@ -324,11 +329,6 @@ fn play_sound_effects(
     }
 }
 
-fn should_spawn_cube(last_spawn_time: Instant, beat_length: Duration) -> bool {
-    let delta = Instant::now() - last_spawn_time;
-    delta > beat_length
-}
-
 fn revive_cube(cube_entity: Entity, world: &mut World, song: &Song) {
     // Update its position and velocity
     {
@@ -346,9 +346,7 @@ fn revive_cube(cube_entity: Entity, world: &mut World, song: &Song) {
         rigid_body.linear_velocity.z = -CUBE_Z / (song.beat_length.as_secs_f32() * 4.);
     }
 
-    world
-        .insert(cube_entity, (Visible {}, Teleport {}))
-        .unwrap();
+    world.insert_one(cube_entity, Teleport {}).unwrap();
 }
 
 #[cfg(target_os = "windows")]
@@ -414,7 +412,7 @@ mod tests {
 
         // INIT -> MAIN_MENU
         game_system_inner(game_context, world, audio_context, haptic_context);
-        assert_eq!(game_context.state, GameState::MainMenu);
+        assert_eq!(game_context.state.current(), &GameState::MainMenu);
         assert!(is_visible(world, game_context.pointer));
         assert!(is_visible(world, game_context.main_menu_panel));
         assert!(!is_visible(world, game_context.blue_saber));
@@ -433,7 +431,10 @@ mod tests {
             panel.buttons[0].clicked_this_frame = true;
         }
         game_system_inner(game_context, world, audio_context, haptic_context);
-        assert_eq!(game_context.state, GameState::Playing(beside_you.clone()));
+        assert_eq!(
+            game_context.state.current(),
+            &GameState::Playing(beside_you.clone())
+        );
         assert_eq!(audio_context.current_music_track, Some(beside_you.track));
         assert!(!is_visible(world, game_context.pointer));
         assert!(!is_visible(world, game_context.main_menu_panel));
@@ -477,7 +478,7 @@ mod tests {
             assert_score_is(world, game_context, 0);
 
             // Simulate blue saber hitting blue cube - increase score
-            hit_cube(game_context.blue_saber, Color::Blue, world);
+            hit_cube(game_context.blue_saber, Color::Blue, game_context, world);
         }
 
         // PLAYING - TICK THREE
@@ -487,10 +488,9 @@ mod tests {
             reset(world, game_context, haptic_context);
             assert_score_is(world, game_context, 1);
             // Simulate blue saber hitting red cube - decrease score
-            hit_cube(game_context.blue_saber, Color::Red, world);
-            // Reset spawn timer.
-            game_context.last_spawn_time =
-                Instant::now() - beside_you.beat_length - Duration::from_millis(1);
+            hit_cube(game_context.blue_saber, Color::Red, game_context, world);
+            // Move on to the next beat.
+            game_context.beat_clock.advance(beside_you.beat_length);
         }
 
         // PLAYING - TICK FOUR
@@ -502,7 +502,7 @@ mod tests {
             assert_eq!(num_cubes(world), 2);
 
             // Simulate blue saber hitting blue cube - increase score
-            hit_cube(game_context.blue_saber, Color::Blue, world);
+            hit_cube(game_context.blue_saber, Color::Blue, game_context, world);
 
             // Make the sabers collide
             collide_sabers(game_context, world);
@@ -515,7 +515,7 @@ mod tests {
             reset(world, game_context, haptic_context);
             assert_score_is(world, game_context, 1);
             // Simulate blue cube hitting the backstop - decrease score
-            hit_cube(game_context.backstop, Color::Blue, world);
+            hit_cube(game_context.backstop, Color::Blue, game_context, world);
         }
 
         // PLAYING - TICK SIX
@@ -526,7 +526,7 @@ mod tests {
             assert_score_is(world, game_context, 0);
 
             // Add a red cube to the red saber - increase score
-            hit_cube(game_context.red_saber, Color::Red, world);
+            hit_cube(game_context.red_saber, Color::Red, game_context, world);
         }
 
         // PLAYING - TICK SEVEN
@@ -536,7 +536,7 @@ mod tests {
             reset(world, game_context, haptic_context);
             assert_score_is(world, game_context, 1);
             // Add a blue cube to the red saber - decrease score
-            hit_cube(game_context.red_saber, Color::Blue, world);
+            hit_cube(game_context.red_saber, Color::Blue, game_context, world);
         }
 
         // PLAYING - TICK EIGHT
@@ -546,13 +546,13 @@ mod tests {
             reset(world, game_context, haptic_context);
             assert_score_is(world, game_context, 0);
             // Add a blue cube to the red saber - decrease score
-            hit_cube(game_context.red_saber, Color::Blue, world);
+            hit_cube(game_context.red_saber, Color::Blue, game_context, world);
         }
 
         // PLAYING - TICK NINE -> GAME OVER
         game_system_inner(game_context, world, audio_context, haptic_context);
         {
-            assert_eq!(game_context.state, GameState::GameOver);
+            assert_eq!(game_context.state.current(), &GameState::GameOver);
             assert!(is_visible(world, game_context.pointer));
             assert!(is_visible(world, game_context.main_menu_panel));
             assert!(!is_visible(world, game_context.blue_saber));
@@ -567,7 +567,10 @@ mod tests {
             let mut panel = world
                 .get::<&mut UIPanel>(game_context.main_menu_panel)
                 .unwrap();
-            assert_eq!(panel.text, "Game Over\nYOU FAILED!",);
+            assert_eq!(
+                panel.text,
+                "Game Over\nYOU FAILED!\nScore: -1\nBest combo: 1\nAccuracy: 43%",
+            );
             assert_eq!(panel.buttons[0].text, "Back to main menu",);
             panel.buttons[0].clicked_this_frame = true;
         }
@@ -575,7 +578,7 @@ mod tests {
         // GAME_OVER -> MAIN_MENU
         game_system_inner(game_context, world, audio_context, haptic_context);
         {
-            assert_eq!(game_context.state, GameState::MainMenu);
+            assert_eq!(game_context.state.current(), &GameState::MainMenu);
             assert!(is_visible(world, game_context.pointer));
             assert!(is_visible(world, game_context.main_menu_panel));
             assert!(!is_visible(world, game_context.blue_saber));
@@ -611,8 +614,11 @@ mod tests {
         }
         game_system_inner(game_context, world, audio_context, haptic_context);
         reset(world, game_context, haptic_context);
-        assert_eq!(game_context.current_score, 0);
-        assert_eq!(game_context.state, GameState::Playing(beside_you.clone()));
+        assert_eq!(game_context.score.points, 0);
+        assert_eq!(
+            game_context.state.current(),
+            &GameState::Playing(beside_you.clone())
+        );
         assert_eq!(audio_context.current_music_track, Some(beside_you.track));
         assert!(!is_visible(world, game_context.pointer));
         assert!(!is_visible(world, game_context.main_menu_panel));
@@ -645,14 +651,8 @@ mod tests {
             .len()
     }
 
-    fn hit_cube(saber: Entity, color: Color, world: &mut World) {
-        let cube = world.spawn((
-            color,
-            Cube {},
-            Visible {},
-            RigidBody::default(),
-            Collider::default(),
-        ));
+    fn hit_cube(saber: Entity, color: Color, game_context: &mut GameContext, world: &mut World) {
+        let cube = game_context.cube_pool(color).acquire(world).unwrap();
         world
             .get::<&mut Collider>(saber)
             .unwrap()
@@ -706,13 +706,13 @@ mod tests {
     }
 
     pub fn assert_score_is(world: &mut World, game_context: &mut GameContext, score: i32) {
-        assert_eq!(game_context.current_score, score);
+        assert_eq!(game_context.score.points, score);
         assert_eq!(
             world
                 .get::<&UIPanel>(game_context.score_panel)
                 .unwrap()
                 .text,
-            format!("Score: {}", score)
+            format!("Score: {}\nCombo: {}", score, game_context.score.combo)
         );
     }
 }
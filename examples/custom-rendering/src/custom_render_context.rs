@@ -1,7 +1,10 @@
 use hotham::{
     anyhow::Result,
     ash,
-    contexts::{render_context::create_shader, VulkanContext},
+    contexts::{
+        render_context::{create_shader, RENDER_PASS_DYNAMIC_STATES},
+        VulkanContext,
+    },
     glam::{Affine3A, Mat4, Vec4},
     rendering::{buffer::Buffer, primitive::Primitive, vertex::Vertex},
     vk,
@@ -63,6 +66,8 @@ impl CustomRenderContext {
     pub fn new(engine: &mut Engine) -> Self {
         let render_context = &mut engine.render_context;
         let vulkan_context = &engine.vulkan_context;
+        // The quadric shader writes its own depth, and assumes it's reversed.
+        assert!(render_context.reversed_z());
         let device = &vulkan_context.device;
         let quadrics_descriptor_set_layout = create_quadrics_descriptor_set_layout(device);
         let layouts = [
@@ -238,6 +243,9 @@ fn create_quadrics_pipeline(
         .logic_op_enable(false)
         .attachments(&color_blend_attachments);
 
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
     let create_info = vk::GraphicsPipelineCreateInfo::builder()
         .stages(&stages)
         .vertex_input_state(&vertex_input_state)
@@ -247,6 +255,7 @@ fn create_quadrics_pipeline(
         .multisample_state(&multisample_state)
         .depth_stencil_state(&depth_stencil_state)
         .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
         .layout(pipeline_layout)
         .render_pass(render_pass)
         .subpass(0)
@@ -8,13 +8,10 @@ use crate::{
 };
 use hotham::{
     components::{skin::NO_SKIN, stage, GlobalTransform, Mesh, Skin, Visible},
-    contexts::{
-        render_context::{Instance, InstancedPrimitive},
-        RenderContext, VulkanContext,
-    },
+    contexts::{render_context::InstancedPrimitive, RenderContext, VulkanContext},
     glam::{Affine3A, Mat4},
     hecs::{With, World},
-    rendering::resources::{DrawData, PrimitiveCullData},
+    rendering::resources::PrimitiveCullData,
     vk, xr, Engine,
 };
 
@@ -124,16 +121,8 @@ pub unsafe fn begin(
             render_context
                 .primitive_map
                 .entry(key)
-                .or_insert(InstancedPrimitive {
-                    primitive: primitive.clone(),
-                    instances: Default::default(),
-                })
-                .instances
-                .push(Instance {
-                    gos_from_local,
-                    bounding_sphere: primitive.get_bounding_sphere_in_gos(&gos_from_local),
-                    skin_id,
-                });
+                .or_insert_with(|| InstancedPrimitive::new(primitive.clone()))
+                .push_instance(gos_from_local, skin_id);
         }
     }
 
@@ -187,9 +176,9 @@ pub unsafe fn begin(
 
     for instanced_primitive in render_context.primitive_map.values() {
         let primitive = &instanced_primitive.primitive;
-        for (instance, i) in instanced_primitive.instances.iter().zip(0u32..) {
+        for (bounding_sphere, i) in instanced_primitive.bounding_spheres.iter().zip(0u32..) {
             cull_data.push(&PrimitiveCullData {
-                bounding_sphere: instance.bounding_sphere,
+                bounding_sphere: *bounding_sphere,
                 index_instance: i,
                 primitive_id: primitive.index_buffer_offset,
                 visible: false,
@@ -337,14 +326,8 @@ pub unsafe fn draw_world(
                         .primitive_map
                         .get(&cull_result.primitive_id)
                         .unwrap();
-                    let instance =
-                        &instanced_primitive.instances[cull_result.index_instance as usize];
-                    let draw_data = DrawData {
-                        gos_from_local: instance.gos_from_local.into(),
-                        local_from_gos: instance.gos_from_local.inverse().into(),
-                        material_id: instanced_primitive.primitive.material_id,
-                        skin_id: instance.skin_id,
-                    };
+                    let draw_data =
+                        instanced_primitive.draw_data(cull_result.index_instance as usize);
                     draw_data_buffer.push(&draw_data);
                     instance_count += 1;
                 }
@@ -5,8 +5,8 @@
 #include "../../../../hotham/src/shaders/brdf.glsl"
 
 // Textures
-layout (set = 0, binding = 4) uniform sampler2D textures[];
-layout (set = 0, binding = 5) uniform samplerCube cubeTextures[];
+layout (set = 0, binding = 4) uniform samplerCube cubeTextures[];
+layout (set = 0, binding = 5) uniform sampler2D textures[];
 
 #include "pbr.glsl"
 
@@ -0,0 +1,246 @@
+use hotham::{
+    asset_importer::{self, add_model_to_world},
+    components::{
+        hand::Handedness, physics::SharedShape, projectile::add_tracer_to_world, stage,
+        AngularVelocity, Collider, Damage, DamageOnContact, GlobalTransform, Hand, Health,
+        LocalTransform, Projectile, Sprite, Visible,
+    },
+    gameplay::Timer,
+    glam::{Affine3A, Vec2, Vec3, Vec4},
+    hecs::{Entity, World},
+    systems::{
+        animation_system, grabbing_system, hands::add_hand, hands_system, haptics_system,
+        health_system, motion_system, physics_system, projectile_system,
+        rendering::rendering_system, skinning::skinning_system, update_global_transform_system,
+        update_global_transform_with_parent_system,
+    },
+    xr, Engine, HothamResult, TickData,
+};
+
+/// How fast projectiles leave the controller, in metres per second
+const PROJECTILE_SPEED: f32 = 20.;
+/// How far hitscan shots reach, in metres
+const HITSCAN_RANGE: f32 = 50.;
+/// How long destroyed targets take to come back, in seconds
+const RESPAWN_TIME: f32 = 2.;
+/// How long a target swells for after it's hit, in seconds
+const HIT_REACTION_TIME: f32 = 0.2;
+/// How fast targets spin, in radians per second
+const TARGET_SPIN_SPEED: f32 = 0.5;
+
+/// Something to shoot at
+#[derive(Debug, Clone, Copy)]
+struct Target {
+    /// Where the target sits when it's alive
+    translation: Vec3,
+    /// How big the target is when it isn't reacting to a hit
+    scale: Vec3,
+    /// How long the target has left to react to its last hit
+    hit_reaction: f32,
+    /// Counts down until the target comes back, once it's been destroyed
+    respawn: Timer,
+}
+
+#[cfg_attr(target_os = "android", ndk_glue::main(backtrace = "on"))]
+pub fn main() {
+    println!("[HOTHAM_TARGET_PRACTICE] MAIN!");
+    real_main().expect("Error running app!");
+    println!("[HOTHAM_TARGET_PRACTICE] FINISHED! Goodbye!");
+}
+
+pub fn real_main() -> HothamResult<()> {
+    let mut engine = Engine::new();
+    init(&mut engine)?;
+
+    while let Ok(tick_data) = engine.update() {
+        tick(tick_data, &mut engine);
+        engine.finish()?;
+    }
+
+    Ok(())
+}
+
+fn tick(tick_data: TickData, engine: &mut Engine) {
+    if tick_data.current_state == xr::SessionState::FOCUSED {
+        hands_system(engine);
+        grabbing_system(engine);
+        physics_system(engine);
+        projectile_system(engine);
+        health_system(engine);
+        shooting_system(engine);
+        targets_system(engine);
+        motion_system(engine);
+        animation_system(engine);
+        update_global_transform_system(engine);
+        update_global_transform_with_parent_system(engine);
+        skinning_system(engine);
+        haptics_system(engine);
+    }
+
+    rendering_system(engine, tick_data.swapchain_image_index);
+}
+
+fn init(engine: &mut Engine) -> Result<(), hotham::HothamError> {
+    let render_context = &mut engine.render_context;
+    let vulkan_context = &mut engine.vulkan_context;
+    let world = &mut engine.world;
+
+    let mut glb_buffers: Vec<&[u8]> = vec![
+        include_bytes!("../../../test_assets/left_hand.glb"),
+        include_bytes!("../../../test_assets/right_hand.glb"),
+    ];
+
+    #[cfg(target_os = "android")]
+    glb_buffers.push(include_bytes!(
+        "../../../test_assets/damaged_helmet_squished.glb"
+    ));
+
+    #[cfg(not(target_os = "android"))]
+    glb_buffers.push(include_bytes!("../../../test_assets/damaged_helmet.glb"));
+
+    let models =
+        asset_importer::load_models_from_glb(&glb_buffers, vulkan_context, render_context)?;
+    add_hand(&models, Handedness::Left, world);
+    add_hand(&models, Handedness::Right, world);
+
+    for x in [-1., 0., 1.] {
+        add_target(&models, Vec3::new(x, 1.4, -3.), world);
+    }
+
+    Ok(())
+}
+
+fn add_target(
+    models: &std::collections::HashMap<String, World>,
+    translation: Vec3,
+    world: &mut World,
+) {
+    let target = add_model_to_world("Damaged Helmet", models, world, None)
+        .expect("Could not find Damaged Helmet");
+
+    let scale = Vec3::splat(0.3);
+    {
+        let mut local_transform = world.get::<&mut LocalTransform>(target).unwrap();
+        local_transform.translation = translation;
+        local_transform.scale = scale;
+    }
+
+    world
+        .insert(
+            target,
+            (
+                Collider::new(SharedShape::ball(0.2)),
+                Health::new(3.),
+                AngularVelocity(Vec3::Y * TARGET_SPIN_SPEED),
+                Target {
+                    translation,
+                    scale,
+                    hit_reaction: 0.,
+                    respawn: Timer::new(RESPAWN_TIME),
+                },
+            ),
+        )
+        .unwrap();
+}
+
+/// Fire a projectile from the right controller, or a hitscan shot from the left
+fn shooting_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let input_context = &engine.input_context;
+    let physics_context = &engine.physics_context;
+    let global_from_stage = stage::get_global_from_stage(world);
+
+    if input_context.right.trigger_button_just_pressed() {
+        let global_from_aim = global_from_stage * input_context.right.stage_from_aim();
+        let direction = global_from_aim.transform_vector3(-Vec3::Z).normalize();
+        let origin = Vec3::from(global_from_aim.translation);
+        let shooter = find_hand(world, Handedness::Right);
+
+        world.spawn((
+            Projectile {
+                radius: 0.02,
+                shooter,
+                ..Projectile::new(direction * PROJECTILE_SPEED)
+            },
+            DamageOnContact::new(1.),
+            Sprite {
+                color: Vec4::new(1., 0.8, 0.2, 1.),
+                size: Vec2::splat(0.04),
+                ..Default::default()
+            },
+            LocalTransform {
+                translation: origin,
+                ..Default::default()
+            },
+            GlobalTransform(Affine3A::from_translation(origin)),
+            Visible {},
+        ));
+    }
+
+    if input_context.left.trigger_button_just_pressed() {
+        let global_from_aim = global_from_stage * input_context.left.stage_from_aim();
+        let direction = global_from_aim.transform_vector3(-Vec3::Z).normalize();
+        let origin = Vec3::from(global_from_aim.translation);
+        let shooter = find_hand(world, Handedness::Left);
+
+        let hit = physics_context.hitscan(world, origin, direction, HITSCAN_RANGE, shooter);
+        let end = hit.map_or(origin + direction * HITSCAN_RANGE, |hit| hit.point);
+        add_tracer_to_world(origin, end, Vec4::new(0.2, 0.8, 1., 1.), 0.1, world);
+
+        // Hitscan shots aren't projectiles, so they do their damage here.
+        if let Some(hit) = hit {
+            if let Ok(mut health) = world.get::<&mut Health>(hit.entity) {
+                health.apply_damage(Damage {
+                    amount: 1.,
+                    source: shooter,
+                    point: Some(hit.point),
+                });
+            }
+        }
+    }
+}
+
+/// React to targets being hit, and bring destroyed targets back
+fn targets_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let haptic_context = &mut engine.haptic_context;
+    let delta_time = engine.time_context.delta_time();
+
+    for (_, (target, health, local_transform)) in
+        world.query_mut::<(&mut Target, &mut Health, &mut LocalTransform)>()
+    {
+        if health.just_died() {
+            println!("[HOTHAM_TARGET_PRACTICE] Target destroyed!");
+            target.respawn.reset();
+            // Move the target out of the way, so it can't be seen or hit.
+            local_transform.translation = target.translation - Vec3::Y * 100.;
+        }
+
+        if health.is_dead() {
+            if target.respawn.tick(delta_time) {
+                health.reset();
+                local_transform.translation = target.translation;
+            }
+            continue;
+        }
+
+        if !health.damage_this_frame.is_empty() {
+            target.hit_reaction = HIT_REACTION_TIME;
+            haptic_context.request_haptic_feedback(0.5, Handedness::Right);
+            haptic_context.request_haptic_feedback(0.5, Handedness::Left);
+        }
+
+        // Swell up when hit, then shrink back down.
+        target.hit_reaction = (target.hit_reaction - delta_time).max(0.);
+        let swell = 1. + 0.3 * target.hit_reaction / HIT_REACTION_TIME;
+        local_transform.scale = target.scale * swell;
+    }
+}
+
+fn find_hand(world: &World, handedness: Handedness) -> Option<Entity> {
+    world
+        .query::<&Hand>()
+        .iter()
+        .find(|(_, hand)| hand.handedness == handedness)
+        .map(|(entity, _)| entity)
+}
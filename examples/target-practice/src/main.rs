@@ -0,0 +1,5 @@
+use hotham::HothamResult;
+
+fn main() -> HothamResult<()> {
+    target_practice_example::real_main()
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{project::Project, BuildOptions, Builder, LogOptions};
+
+/// How long to wait for the app to start before giving up on finding its logs.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often `push_assets` checks for changed files.
+const ASSET_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a file has to go unchanged before it's pushed, so we don't push one that's still being exported.
+const ASSET_SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// Build an APK for `project`, returning its path.
+pub fn build(project: &Project, build_options: &BuildOptions) -> Result<PathBuf> {
+    let profile = if build_options.release {
+        "release"
+    } else {
+        "debug"
+    };
+
+    let (mut command, apk_dir) = match build_options.builder {
+        Builder::CargoApk => {
+            let mut command = Command::new("cargo");
+            command.args(["apk", "build"]);
+            (command, project.target_dir.join(profile).join("apk"))
+        }
+        Builder::XBuild => {
+            let mut command = Command::new("x");
+            command.args([
+                "build",
+                "--platform",
+                "android",
+                "--arch",
+                "arm64",
+                "--format",
+                "apk",
+            ]);
+            (
+                command,
+                project.target_dir.join("x").join(profile).join("android"),
+            )
+        }
+    };
+
+    if build_options.release {
+        command.arg("--release");
+    }
+    command.current_dir(&project.manifest_dir);
+
+    println!("[HOTHAM_CLI] Building {}..", project.name);
+    run_command(&mut command)?;
+
+    newest_apk(&apk_dir)
+        .with_context(|| format!("Unable to find the APK that was built in {:?}", apk_dir))
+}
+
+/// Install `apk` on the connected device, replacing any existing version.
+pub fn install(apk: &Path) -> Result<()> {
+    println!("[HOTHAM_CLI] Installing {}..", apk.display());
+    run_command(Command::new("adb").arg("install").arg("-r").arg(apk))
+}
+
+/// Start the app.
+pub fn launch(package: &str) -> Result<()> {
+    println!("[HOTHAM_CLI] Starting {}..", package);
+    let activity = format!("{}/android.app.NativeActivity", package);
+    run_command(Command::new("adb").args(["shell", "am", "start", "-n", activity.as_str()]))
+}
+
+/// Stop the app, if it's running.
+pub fn stop(package: &str) -> Result<()> {
+    run_command(Command::new("adb").args(["shell", "am", "force-stop", package]))
+}
+
+/// Stream the app's logs until it exits or the user presses Ctrl-C.
+pub fn logcat(package: &str, log_options: &LogOptions) -> Result<()> {
+    let mut command = Command::new("adb");
+    command.arg("logcat");
+
+    if !log_options.all {
+        let pid = wait_for_pid(package)?;
+        println!("[HOTHAM_CLI] {} is running with PID {}", package, pid);
+        command.arg(format!("--pid={}", pid));
+    }
+
+    let mut child = command.stdout(Stdio::piped()).spawn().context(
+        "Unable to run adb - is the Android SDK's platform-tools directory in your PATH?",
+    )?;
+    let stdout = child.stdout.take().unwrap();
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        if is_shown(&line, log_options.filter.as_deref()) {
+            println!("{}", line);
+        }
+    }
+
+    child.wait()?;
+    Ok(())
+}
+
+/// Stream the logs of an app that was built with `EngineBuilder::remote_log_port`, over ADB.
+pub fn remote_log(port: u16, filter: Option<&str>) -> Result<()> {
+    let forward = format!("tcp:{}", port);
+    run_command(Command::new("adb").args(["forward", forward.as_str(), forward.as_str()]))?;
+
+    let stream = TcpStream::connect(("127.0.0.1", port))
+        .with_context(|| format!("Unable to connect to port {} - is the app running?", port))?;
+    println!("[HOTHAM_CLI] Connected to port {}", port);
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if is_shown(&line, filter) {
+            println!("{}", line);
+        }
+    }
+
+    println!("[HOTHAM_CLI] The app has stopped streaming its logs");
+    Ok(())
+}
+
+/// Send `paths` to an app with an `AssetWatcher` listening on `port`, over ADB, then send each file again whenever it
+/// changes. Runs until the app stops listening.
+pub fn push_assets(paths: &[PathBuf], port: u16) -> Result<()> {
+    let forward = format!("tcp:{}", port);
+    run_command(Command::new("adb").args(["forward", forward.as_str(), forward.as_str()]))?;
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port))
+        .with_context(|| format!("Unable to connect to port {} - is the app running?", port))?;
+    println!("[HOTHAM_CLI] Connected to port {}", port);
+
+    let mut pushed: HashMap<&Path, SystemTime> = HashMap::new();
+    loop {
+        for path in paths {
+            let modified = match std::fs::metadata(path).and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                // The file may briefly disappear while it's being exported.
+                Err(_) => continue,
+            };
+            let has_settled = modified
+                .elapsed()
+                .map_or(true, |age| age >= ASSET_SETTLE_TIME);
+            if !has_settled || pushed.get(path.as_path()) == Some(&modified) {
+                continue;
+            }
+
+            let bytes =
+                std::fs::read(path).with_context(|| format!("Unable to read {:?}", path))?;
+            write_asset(&mut stream, &path.display().to_string(), &bytes)
+                .context("The app has stopped listening for assets")?;
+            println!("[HOTHAM_CLI] Pushed {}", path.display());
+            pushed.insert(path, modified);
+        }
+        sleep(ASSET_POLL_INTERVAL);
+    }
+}
+
+/// Write a file the way `AssetWatcher` expects: the length of its name and the name, then the length of the file and
+/// the file, with lengths as little endian `u32`s and `u64`s.
+fn write_asset(stream: &mut impl Write, name: &str, bytes: &[u8]) -> std::io::Result<()> {
+    stream.write_all(&(name.len() as u32).to_le_bytes())?;
+    stream.write_all(name.as_bytes())?;
+    stream.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    stream.write_all(bytes)?;
+    stream.flush()
+}
+
+fn is_shown(line: &str, filter: Option<&str>) -> bool {
+    match filter {
+        Some(filter) => line.contains(filter),
+        None => true,
+    }
+}
+
+fn wait_for_pid(package: &str) -> Result<String> {
+    let start = Instant::now();
+    loop {
+        let output = Command::new("adb")
+            .args(["shell", "pidof", package])
+            .output()
+            .context(
+                "Unable to run adb - is the Android SDK's platform-tools directory in your PATH?",
+            )?;
+        let pid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !pid.is_empty() {
+            return Ok(pid);
+        }
+
+        if start.elapsed() > LAUNCH_TIMEOUT {
+            bail!("{} didn't start - is the headset awake?", package);
+        }
+        println!("[HOTHAM_CLI] Waiting for {} to start..", package);
+        sleep(Duration::from_secs(1));
+    }
+}
+
+fn newest_apk(dir: &Path) -> Result<PathBuf> {
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("apk")) {
+            continue;
+        }
+        let modified = path.metadata()?.modified()?;
+        let is_newer = match &newest {
+            Some((newest_modified, _)) => modified > *newest_modified,
+            None => true,
+        };
+        if is_newer {
+            newest = Some((modified, path));
+        }
+    }
+
+    match newest {
+        Some((_, path)) => Ok(path),
+        None => bail!("No APKs in {:?}", dir),
+    }
+}
+
+/// Run `command`, failing if it can't be started or exits unsuccessfully.
+pub fn run_command(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let status = command
+        .status()
+        .with_context(|| format!("Unable to run {} - is it installed?", program))?;
+    if !status.success() {
+        bail!("{} failed with {}", program, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_is_shown() {
+        let line = "I RustStdoutStderr: [HOTHAM_ENGINE] Hotham is now exiting!";
+        assert!(is_shown(line, None));
+        assert!(is_shown(line, Some("HOTHAM")));
+        assert!(!is_shown(line, Some("VrApi")));
+    }
+
+    #[test]
+    pub fn test_newest_apk() {
+        let dir = std::env::temp_dir().join(format!("hotham_cli_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(newest_apk(&dir).is_err());
+
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::write(dir.join("my_game.apk"), "").unwrap();
+        assert_eq!(newest_apk(&dir).unwrap(), dir.join("my_game.apk"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
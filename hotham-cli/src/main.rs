@@ -0,0 +1,365 @@
+//! `hotham` - create Hotham apps and run them on a Quest.
+//!
+//! Turns the usual "build the APK, install it, start it, find its PID, tail logcat" dance into a single command:
+//!
+//! ```text
+//! hotham new my-game
+//! cd my-game
+//! hotham run --device quest
+//! ```
+mod android;
+mod project;
+mod scaffold;
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+
+/// The port `hotham::logging` streams logs on by default.
+const DEFAULT_REMOTE_LOG_PORT: u16 = 7878;
+
+/// The port `hotham::asset_importer::watcher::AssetWatcher::listen` is usually given.
+const DEFAULT_ASSET_PORT: u16 = 7879;
+
+const USAGE: &str = "\
+hotham - create Hotham apps and run them on a Quest
+
+USAGE:
+    hotham new <PATH> [--name <NAME>] [--permission <PERMISSION>]...
+    hotham build [--release] [--builder <cargo-apk|xbuild>]
+    hotham install [--release] [--builder <cargo-apk|xbuild>]
+    hotham run [--device <quest|desktop>] [--release] [--builder <cargo-apk|xbuild>] [--filter <TEXT>]
+    hotham logcat [--filter <TEXT>] [--all]
+    hotham remote-log [--port <PORT>] [--filter <TEXT>]
+    hotham push-assets <PATH>... [--port <PORT>]
+
+COMMANDS:
+    new        Create a new Hotham app in PATH
+    build      Build an APK for the app in the current directory
+    install    Build the APK and install it on the connected Quest
+    run        Build, install and start the app, then stream its logs
+    logcat     Stream the logs of the app in the current directory, if it's running
+    remote-log Stream the logs of an app started with EngineBuilder::remote_log_port over ADB
+    push-assets
+               Send GLB files to an app with an AssetWatcher listening over ADB, and again whenever they change
+
+OPTIONS:
+    --name <NAME>         The name of the new app's crate. Defaults to the last part of PATH
+    --permission <PERMISSION>
+                          Add a runtime permission to the new app's manifest: microphone, hand-tracking or scene
+    --release             Build with optimisations
+    --builder <BUILDER>   The tool used to build the APK. Defaults to cargo-apk
+    --device <DEVICE>     Where to run the app. Defaults to quest
+    --filter <TEXT>       Only show log lines containing TEXT
+    --all                 Show logs from every process, not just the app
+    --port <PORT>         The port the app is listening on. Defaults to 7878 for remote-log and 7879 for push-assets
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builder {
+    CargoApk,
+    XBuild,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Quest,
+    Desktop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildOptions {
+    pub release: bool,
+    pub builder: Builder,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            release: false,
+            builder: Builder::CargoApk,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogOptions {
+    pub filter: Option<String>,
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    New {
+        path: PathBuf,
+        name: Option<String>,
+        permissions: Vec<String>,
+    },
+    Build(BuildOptions),
+    Install(BuildOptions),
+    Run {
+        device: Device,
+        build_options: BuildOptions,
+        log_options: LogOptions,
+    },
+    Logcat(LogOptions),
+    RemoteLog {
+        port: u16,
+        filter: Option<String>,
+    },
+    PushAssets {
+        paths: Vec<PathBuf>,
+        port: u16,
+    },
+    Help,
+}
+
+fn main() {
+    if let Err(e) = parse_args(std::env::args().skip(1)).and_then(run) {
+        eprintln!("[HOTHAM_CLI] Error: {:?}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::New {
+            path,
+            name,
+            permissions,
+        } => scaffold::create(&path, name.as_deref(), &permissions),
+        Command::Build(build_options) => {
+            let project = project::Project::current()?;
+            let apk = android::build(&project, &build_options)?;
+            println!("[HOTHAM_CLI] Built {}", apk.display());
+            Ok(())
+        }
+        Command::Install(build_options) => {
+            let project = project::Project::current()?;
+            let apk = android::build(&project, &build_options)?;
+            android::install(&apk)
+        }
+        Command::Run {
+            device: Device::Desktop,
+            build_options,
+            ..
+        } => {
+            let project = project::Project::current()?;
+            project.run_on_desktop(build_options.release)
+        }
+        Command::Run {
+            device: Device::Quest,
+            build_options,
+            log_options,
+        } => {
+            let project = project::Project::current()?;
+            let package = project.android_package();
+            let apk = android::build(&project, &build_options)?;
+            android::stop(&package)?;
+            android::install(&apk)?;
+            android::launch(&package)?;
+            android::logcat(&package, &log_options)
+        }
+        Command::Logcat(log_options) => {
+            let project = project::Project::current()?;
+            android::logcat(&project.android_package(), &log_options)
+        }
+        Command::RemoteLog { port, filter } => android::remote_log(port, filter.as_deref()),
+        Command::PushAssets { paths, port } => android::push_assets(&paths, port),
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(Command::Help),
+    };
+
+    let mut positional = Vec::new();
+    let mut name = None;
+    let mut permissions = Vec::new();
+    let mut build_options = BuildOptions::default();
+    let mut device = Device::Quest;
+    let mut port = None;
+    let mut log_options = LogOptions {
+        filter: None,
+        all: false,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("{} needs a value\n\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "--name" => name = Some(value()?),
+            "--permission" => permissions.push(value()?),
+            "--release" => build_options.release = true,
+            "--builder" => {
+                build_options.builder = match value()?.as_str() {
+                    "cargo-apk" => Builder::CargoApk,
+                    "xbuild" => Builder::XBuild,
+                    other => bail!("Unknown builder {:?} - expected cargo-apk or xbuild", other),
+                }
+            }
+            "--device" => {
+                device = match value()?.as_str() {
+                    "quest" => Device::Quest,
+                    "desktop" => Device::Desktop,
+                    other => bail!("Unknown device {:?} - expected quest or desktop", other),
+                }
+            }
+            "--port" => {
+                let value = value()?;
+                port = Some(
+                    value
+                        .parse()
+                        .map_err(|_| anyhow!("{:?} isn't a valid port", value))?,
+                )
+            }
+            "--filter" => log_options.filter = Some(value()?),
+            "--all" => log_options.all = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            flag if flag.starts_with('-') => bail!("Unknown option {}\n\n{}", flag, USAGE),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match command.as_str() {
+        "new" => {
+            let path = match positional.as_slice() {
+                [path] => PathBuf::from(path),
+                _ => bail!("hotham new needs a path\n\n{}", USAGE),
+            };
+            return Ok(Command::New {
+                path,
+                name,
+                permissions,
+            });
+        }
+        "push-assets" => {
+            if positional.is_empty() {
+                bail!("hotham push-assets needs at least one file\n\n{}", USAGE);
+            }
+            return Ok(Command::PushAssets {
+                paths: positional.into_iter().map(PathBuf::from).collect(),
+                port: port.unwrap_or(DEFAULT_ASSET_PORT),
+            });
+        }
+        "build" => Command::Build(build_options),
+        "install" => Command::Install(build_options),
+        "run" => Command::Run {
+            device,
+            build_options,
+            log_options,
+        },
+        "logcat" => Command::Logcat(log_options),
+        "remote-log" => Command::RemoteLog {
+            port: port.unwrap_or(DEFAULT_REMOTE_LOG_PORT),
+            filter: log_options.filter,
+        },
+        "help" | "-h" | "--help" => Command::Help,
+        other => bail!("Unknown command {:?}\n\n{}", other, USAGE),
+    };
+
+    if let Some(extra) = positional.first() {
+        bail!("Unexpected argument {:?}\n\n{}", extra, USAGE);
+    }
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    pub fn test_parse_run() {
+        assert_eq!(
+            parse("run --device quest --release --filter HOTHAM").unwrap(),
+            Command::Run {
+                device: Device::Quest,
+                build_options: BuildOptions {
+                    release: true,
+                    builder: Builder::CargoApk,
+                },
+                log_options: LogOptions {
+                    filter: Some("HOTHAM".to_string()),
+                    all: false,
+                },
+            }
+        );
+        assert!(parse("run --device vive").is_err());
+        assert!(parse("run --filter").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_new() {
+        assert_eq!(
+            parse("new games/my-game --name crab_game").unwrap(),
+            Command::New {
+                path: "games/my-game".into(),
+                name: Some("crab_game".to_string()),
+                permissions: vec![],
+            }
+        );
+        assert_eq!(
+            parse("new my-game --permission microphone --permission scene").unwrap(),
+            Command::New {
+                path: "my-game".into(),
+                name: None,
+                permissions: vec!["microphone".to_string(), "scene".to_string()],
+            }
+        );
+        assert!(parse("new").is_err());
+        assert!(parse("build extra").is_err());
+        assert_eq!(parse("").unwrap(), Command::Help);
+    }
+
+    #[test]
+    pub fn test_parse_remote_log() {
+        assert_eq!(
+            parse("remote-log").unwrap(),
+            Command::RemoteLog {
+                port: DEFAULT_REMOTE_LOG_PORT,
+                filter: None,
+            }
+        );
+        assert_eq!(
+            parse("remote-log --port 9000 --filter WARN").unwrap(),
+            Command::RemoteLog {
+                port: 9000,
+                filter: Some("WARN".to_string()),
+            }
+        );
+        assert!(parse("remote-log --port quest").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_push_assets() {
+        assert_eq!(
+            parse("push-assets assets/crab.glb assets/level.glb").unwrap(),
+            Command::PushAssets {
+                paths: vec!["assets/crab.glb".into(), "assets/level.glb".into()],
+                port: DEFAULT_ASSET_PORT,
+            }
+        );
+        assert_eq!(
+            parse("push-assets crab.glb --port 9000").unwrap(),
+            Command::PushAssets {
+                paths: vec!["crab.glb".into()],
+                port: 9000,
+            }
+        );
+        assert!(parse("push-assets").is_err());
+    }
+}
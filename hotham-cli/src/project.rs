@@ -0,0 +1,131 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+
+use crate::android::run_command;
+
+/// The Hotham app in the current directory, as described by `cargo metadata`.
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// The crate's name
+    pub name: String,
+    /// The directory containing the crate's `Cargo.toml`
+    pub manifest_dir: PathBuf,
+    /// Where cargo puts build output. This may be outside the crate if it's part of a workspace.
+    pub target_dir: PathBuf,
+    /// The Android package name, if it's been set in `[package.metadata.android]`
+    android_package: Option<String>,
+}
+
+impl Project {
+    /// Find the project in the current directory.
+    pub fn current() -> Result<Project> {
+        let output = Command::new("cargo")
+            .args(["metadata", "--no-deps", "--format-version", "1"])
+            .output()
+            .context("Unable to run cargo - is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "cargo metadata failed - is this a Rust project?\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let metadata: Value = serde_json::from_slice(&output.stdout)?;
+        let current_dir = std::env::current_dir()?;
+        Self::from_metadata(&metadata, &current_dir)
+    }
+
+    fn from_metadata(metadata: &Value, current_dir: &Path) -> Result<Project> {
+        let target_dir = metadata["target_directory"]
+            .as_str()
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow!("cargo metadata has no target directory"))?;
+
+        // In a workspace, pick the package we're standing in.
+        let packages = metadata["packages"]
+            .as_array()
+            .ok_or_else(|| anyhow!("cargo metadata has no packages"))?;
+        let package = packages
+            .iter()
+            .find(|p| {
+                p["manifest_path"]
+                    .as_str()
+                    .and_then(|m| Path::new(m).parent())
+                    == Some(current_dir)
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "There's no package in this directory - run hotham from your app's directory"
+                )
+            })?;
+
+        let name = package["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Package has no name"))?
+            .to_string();
+        let android_package = package["metadata"]["android"]["package"]
+            .as_str()
+            .map(String::from);
+
+        Ok(Project {
+            name,
+            manifest_dir: current_dir.to_path_buf(),
+            target_dir,
+            android_package,
+        })
+    }
+
+    /// The name the app is installed under on Android. `cargo-apk` defaults to `rust.` followed by the crate name.
+    pub fn android_package(&self) -> String {
+        self.android_package
+            .clone()
+            .unwrap_or_else(|| format!("rust.{}", self.name.replace('-', "_")))
+    }
+
+    /// Run the app on the desktop, using whichever OpenXR runtime is active, eg. the Hotham simulator.
+    pub fn run_on_desktop(&self, release: bool) -> Result<()> {
+        let mut command = Command::new("cargo");
+        command.arg("run").current_dir(&self.manifest_dir);
+        if release {
+            command.arg("--release");
+        }
+        run_command(&mut command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_from_metadata() {
+        let metadata = json!({
+            "target_directory": "/code/target",
+            "packages": [
+                { "name": "hotham", "manifest_path": "/code/hotham/Cargo.toml", "metadata": null },
+                { "name": "my-game", "manifest_path": "/code/my-game/Cargo.toml", "metadata": null },
+                {
+                    "name": "other-game",
+                    "manifest_path": "/code/other-game/Cargo.toml",
+                    "metadata": { "android": { "package": "com.example.other" } }
+                },
+            ]
+        });
+
+        let project = Project::from_metadata(&metadata, Path::new("/code/my-game")).unwrap();
+        assert_eq!(project.name, "my-game");
+        assert_eq!(project.target_dir, PathBuf::from("/code/target"));
+        assert_eq!(project.android_package(), "rust.my_game");
+
+        let project = Project::from_metadata(&metadata, Path::new("/code/other-game")).unwrap();
+        assert_eq!(project.android_package(), "com.example.other");
+
+        assert!(Project::from_metadata(&metadata, Path::new("/code")).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+
+const CARGO_TOML: &str = include_str!("../templates/Cargo.toml.template");
+const LIB_RS: &str = include_str!("../templates/lib.rs.template");
+const MAIN_RS: &str = include_str!("../templates/main.rs.template");
+const GITIGNORE: &str = include_str!("../templates/gitignore.template");
+const RUNTIME_LIBS_README: &str = include_str!("../templates/runtime_libs_README.md.template");
+
+/// The runtime permissions `--permission` knows about, and their entries in `Cargo.toml`. These match
+/// `hotham::contexts::permissions_context::Permission::manifest_entry`.
+const PERMISSIONS: [(&str, &str); 3] = [
+    (
+        "microphone",
+        "[[package.metadata.android.uses_permission]]\nname = \"android.permission.RECORD_AUDIO\"\n",
+    ),
+    (
+        "hand-tracking",
+        "[[package.metadata.android.uses_permission]]\nname = \"com.oculus.permission.HAND_TRACKING\"\n\n\
+         [[package.metadata.android.uses_feature]]\nname = \"oculus.software.handtracking\"\nrequired = false\n",
+    ),
+    (
+        "scene",
+        "[[package.metadata.android.uses_permission]]\nname = \"com.oculus.permission.USE_SCENE\"\n",
+    ),
+];
+
+/// The values substituted into the templates.
+struct Variables {
+    name: String,
+    lib_name: String,
+    label: String,
+    log_tag: String,
+    hotham_version: String,
+    permissions: String,
+}
+
+impl Variables {
+    fn new(name: &str, permissions: &[String]) -> Result<Self> {
+        let lib_name = name.replace('-', "_");
+        let label = lib_name
+            .split('_')
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // New apps should depend on the same version of Hotham as this tool.
+        let version = env!("CARGO_PKG_VERSION");
+        let hotham_version = version
+            .rsplit_once('.')
+            .map_or(version, |(major_minor, _)| major_minor);
+
+        Ok(Self {
+            log_tag: lib_name.to_uppercase(),
+            name: name.to_string(),
+            lib_name,
+            label,
+            hotham_version: hotham_version.to_string(),
+            permissions: permissions_section(permissions)?,
+        })
+    }
+
+    fn render(&self, template: &str) -> String {
+        template
+            .replace("{{name}}", &self.name)
+            .replace("{{lib_name}}", &self.lib_name)
+            .replace("{{label}}", &self.label)
+            .replace("{{log_tag}}", &self.log_tag)
+            .replace("{{hotham_version}}", &self.hotham_version)
+            .replace("{{permissions}}", &self.permissions)
+    }
+}
+
+/// The manifest entries for the runtime permissions in `selected`. The ones that weren't selected are left commented
+/// out, so it's clear how to add them later.
+fn permissions_section(selected: &[String]) -> Result<String> {
+    if let Some(unknown) = selected
+        .iter()
+        .find(|s| !PERMISSIONS.iter().any(|(name, _)| name == s))
+    {
+        let known = PERMISSIONS.map(|(name, _)| name).join(", ");
+        bail!(
+            "Unknown permission {:?} - expected one of {}",
+            unknown,
+            known
+        );
+    }
+
+    let entries = PERMISSIONS.iter().map(|(name, entry)| {
+        if selected.iter().any(|s| s == name) {
+            entry.to_string()
+        } else {
+            entry
+                .lines()
+                .map(|line| match line {
+                    "" => "\n".to_string(),
+                    line => format!("# {}\n", line),
+                })
+                .collect()
+        }
+    });
+    Ok(entries.collect::<Vec<_>>().join("\n"))
+}
+
+/// Create a new Hotham app in `path`. The crate is named after the last part of `path` unless `name` is given. Each
+/// of `permissions` is added to the app's manifest.
+pub fn create(path: &Path, name: Option<&str>, permissions: &[String]) -> Result<()> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow!("Unable to work out a name from {:?} - try --name", path))?
+            .to_string(),
+    };
+    validate_name(&name)?;
+
+    if path.exists() && fs::read_dir(path)?.next().is_some() {
+        bail!("{:?} already exists and isn't empty", path);
+    }
+
+    let variables = Variables::new(&name, permissions)?;
+    let files = [
+        ("Cargo.toml", CARGO_TOML),
+        ("src/lib.rs", LIB_RS),
+        ("src/main.rs", MAIN_RS),
+        (".gitignore", GITIGNORE),
+        ("runtime_libs/README.md", RUNTIME_LIBS_README),
+    ];
+
+    fs::create_dir_all(path.join("src"))?;
+    fs::create_dir_all(path.join("runtime_libs").join("arm64-v8a"))?;
+    for (file, template) in files {
+        fs::write(path.join(file), variables.render(template))?;
+    }
+
+    println!("[HOTHAM_CLI] Created {} in {}", name, path.display());
+    println!("[HOTHAM_CLI] Next, copy libopenxr_loader.so into runtime_libs/arm64-v8a, then run:");
+    println!("    cd {}", path.display());
+    println!("    hotham run --device quest");
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let is_valid = matches!(name.chars().next(), Some(c) if c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        bail!(
+            "{:?} isn't a valid crate name - use letters, numbers, - and _, starting with a letter",
+            name
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_render() {
+        let variables = Variables::new("crab-saber", &[]).unwrap();
+        assert_eq!(variables.lib_name, "crab_saber");
+        assert_eq!(variables.label, "Crab Saber");
+        assert_eq!(variables.log_tag, "CRAB_SABER");
+        assert_eq!(variables.hotham_version, "0.2");
+
+        let main_rs = variables.render(MAIN_RS);
+        assert!(main_rs.contains("crab_saber::real_main()"));
+        assert!(!variables.render(CARGO_TOML).contains("{{"));
+        assert!(!variables.render(LIB_RS).contains("{{"));
+    }
+
+    #[test]
+    pub fn test_create() {
+        let path = std::env::temp_dir()
+            .join(format!("hotham_cli_scaffold_{}", std::process::id()))
+            .join("my-game");
+        create(&path, None, &["microphone".to_string()]).unwrap();
+
+        let cargo_toml = fs::read_to_string(path.join("Cargo.toml")).unwrap();
+        assert!(cargo_toml.contains("name = \"my-game\""));
+        assert!(cargo_toml.contains("\nname = \"android.permission.RECORD_AUDIO\""));
+        assert!(cargo_toml.contains("# name = \"com.oculus.permission.USE_SCENE\""));
+        assert!(path.join("src/lib.rs").exists());
+        assert!(path.join("runtime_libs/arm64-v8a").is_dir());
+
+        // Refuse to overwrite an existing app.
+        assert!(create(&path, None, &[]).is_err());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    pub fn test_permissions_section() {
+        let section = permissions_section(&["hand-tracking".to_string()]).unwrap();
+        assert!(section.contains("\nname = \"oculus.software.handtracking\""));
+        assert!(section.contains("# name = \"android.permission.RECORD_AUDIO\""));
+        assert!(permissions_section(&["camera".to_string()]).is_err());
+    }
+
+    #[test]
+    pub fn test_validate_name() {
+        assert!(validate_name("my-game").is_ok());
+        assert!(validate_name("my_game2").is_ok());
+        assert!(validate_name("2fast").is_err());
+        assert!(validate_name("my game").is_err());
+        assert!(validate_name("").is_err());
+    }
+}
@@ -7,6 +7,8 @@ use openxr_sys::{Action, Path, FALSE};
 // A bit yuck to use u64 instead of Action, but it doesn't support Hash.. but whatever.
 pub struct ActionState {
     boolean_actions: HashMap<u64, bool>,
+    // Float actions are usually shared by both hands, so keep them per action and subaction path.
+    float_actions: HashMap<(u64, Path), f32>,
     bindings: HashMap<Path, u64>,
 }
 impl ActionState {
@@ -17,6 +19,16 @@ impl ActionState {
             .unwrap_or(FALSE)
     }
 
+    pub(crate) fn get_float(&self, action: Action, subaction_path: Path) -> f32 {
+        self.float_actions
+            .iter()
+            .filter(|((a, p), _)| {
+                *a == action.into_raw() && (subaction_path.into_raw() == 0 || *p == subaction_path)
+            })
+            .map(|(_, v)| *v)
+            .fold(0., f32::max)
+    }
+
     pub(crate) fn add_binding(&mut self, path: Path, action: Action) {
         self.bindings.insert(path, action.into_raw());
     }
@@ -25,10 +37,18 @@ impl ActionState {
     pub(crate) fn clear(&mut self) {
         // Set all the booleans to false.
         self.boolean_actions.values_mut().for_each(|v| *v = false);
+        self.float_actions.clear();
     }
 
     pub(crate) fn set_boolean(&mut self, path: &Path, value: bool) {
         let action = self.bindings.get(path).unwrap();
         self.boolean_actions.insert(*action, value);
     }
+
+    pub(crate) fn set_float(&mut self, path: &Path, subaction_path: Path, value: f32) {
+        // The app may not have bound anything to this input.
+        if let Some(action) = self.bindings.get(path) {
+            self.float_actions.insert((*action, subaction_path), value);
+        }
+    }
 }
@@ -166,6 +166,18 @@ pub unsafe extern "C" fn get_instance_proc_addr(
     Result::SUCCESS.into_raw()
 }
 
+/// Lets apps load the simulator directly, like an OpenXR loader, so it works without being installed as a runtime.
+#[allow(non_snake_case)]
+#[no_mangle]
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+pub unsafe extern "system" fn xrGetInstanceProcAddr(
+    instance: *mut XrInstance_T,
+    name: *const i8,
+    function: *mut PFN_xrVoidFunction,
+) -> XrResult {
+    get_instance_proc_addr(instance, name, function)
+}
+
 #[cfg(any(target_os = "windows", target_os = "linux"))]
 static GET_INSTANCE_PROC_ADDR: PFN_xrGetInstanceProcAddr = Some(get_instance_proc_addr);
 
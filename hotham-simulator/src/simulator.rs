@@ -134,14 +134,9 @@ pub unsafe extern "system" fn create_vulkan_instance(
         transmute(get_instance_proc_addr(ptr::null(), vk_create_instance));
     let mut instance = vk::Instance::null();
 
-    let event_loop: EventLoop<()> = EventLoop::new_any_thread();
-    let window = WindowBuilder::new()
-        .with_visible(false)
-        .build(&event_loop)
-        .unwrap();
-
+    let entry = AshEntry::new().unwrap();
     let mut create_info = *vulkan_create_info;
-    let mut enabled_extensions = ash_window::enumerate_required_extensions(&window).unwrap();
+    let mut enabled_extensions = surface_extensions(&entry);
     let xr_extensions = slice::from_raw_parts(
         create_info.pp_enabled_extension_names,
         create_info.enabled_extension_count as usize,
@@ -157,7 +152,6 @@ pub unsafe extern "system" fn create_vulkan_instance(
     create_info.enabled_extension_count = enabled_extensions.len() as _;
     create_info.pp_enabled_extension_names = enabled_extensions.as_ptr();
 
-    let entry = AshEntry::new().unwrap();
     let result = create_instance(&create_info, ptr::null(), &mut instance);
     *vulkan_result = result.as_raw();
     if result != vk::Result::SUCCESS {
@@ -272,11 +266,7 @@ pub unsafe extern "system" fn create_vulkan_physical_device(
     let mut state = STATE.lock().unwrap();
     let instance = state.vulkan_instance.as_ref().unwrap();
 
-    let physical_device = instance
-        .enumerate_physical_devices()
-        .unwrap()
-        .pop()
-        .unwrap();
+    let physical_device = pick_physical_device(instance);
 
     println!(
         "[HOTHAM_SIMULATOR] Created physical device: {:?}",
@@ -301,11 +291,7 @@ pub unsafe extern "system" fn get_vulkan_physical_device(
     let ash_instance = AshInstance::load(entry.static_fn(), transmute(vk_instance));
 
     // Create the device and assign it
-    let physical_device = ash_instance
-        .enumerate_physical_devices()
-        .unwrap()
-        .pop()
-        .unwrap();
+    let physical_device = pick_physical_device(&ash_instance);
 
     println!(
         "[HOTHAM_SIMULATOR] Created physical device: {:?}",
@@ -322,6 +308,58 @@ pub unsafe extern "system" fn get_vulkan_physical_device(
     Result::SUCCESS
 }
 
+/// The surface extensions needed to show the simulator's window. Working these out from the extensions the loader has,
+/// rather than from a window, means no window needs to be created before the real one, and an instance created on
+/// Linux can show a window on either X11 or Wayland.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+unsafe fn surface_extensions(entry: &AshEntry) -> Vec<&'static CStr> {
+    let mut extensions = vec![khr::Surface::name()];
+
+    #[cfg(target_os = "windows")]
+    extensions.push(khr::Win32Surface::name());
+
+    #[cfg(target_os = "linux")]
+    {
+        let available = entry.enumerate_instance_extension_properties().unwrap();
+        let is_available = |name: &CStr| {
+            available
+                .iter()
+                .any(|e| CStr::from_ptr(e.extension_name.as_ptr()) == name)
+        };
+        extensions.extend(
+            [
+                khr::XlibSurface::name(),
+                khr::XcbSurface::name(),
+                khr::WaylandSurface::name(),
+            ]
+            .iter()
+            .copied()
+            .filter(|name| is_available(name)),
+        );
+    }
+
+    extensions
+}
+
+/// Pick the GPU to simulate with. Linux machines often have a software renderer (eg. lavapipe) installed alongside
+/// the real GPU, so prefer real hardware over whatever happens to be listed last.
+unsafe fn pick_physical_device(instance: &AshInstance) -> vk::PhysicalDevice {
+    let rank = |device_type| match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 0,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 4,
+        _ => 3,
+    };
+
+    instance
+        .enumerate_physical_devices()
+        .unwrap()
+        .into_iter()
+        .min_by_key(|d| rank(instance.get_physical_device_properties(*d).device_type))
+        .expect("No Vulkan devices found!")
+}
+
 pub unsafe extern "system" fn get_vulkan_graphics_requirements(
     _instance: Instance,
     _system_id: SystemId,
@@ -1094,7 +1132,7 @@ unsafe fn build_swapchain(state: &mut MutexGuard<State>) -> vk::SwapchainKHR {
             .queue_family_indices(&[])
             .pre_transform(swapchain_support_details.capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-            .present_mode(vk::PresentModeKHR::IMMEDIATE)
+            .present_mode(pick_present_mode(&swapchain_support_details.present_modes))
             .clipped(true)
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT);
 
@@ -1532,6 +1570,7 @@ pub unsafe extern "system" fn sync_actions(
     let mut state = STATE.lock().unwrap();
     state.update_camera_rotation();
     state.update_camera_position();
+    state.update_hand_poses();
     state.update_action_state();
 
     Result::SUCCESS
@@ -1689,11 +1728,18 @@ pub unsafe extern "system" fn destroy_instance(_instance: Instance) -> Result {
 pub unsafe extern "system" fn enumerate_view_configurations(
     _instance: Instance,
     _system_id: SystemId,
-    _view_configuration_type_capacity_input: u32,
+    view_configuration_type_capacity_input: u32,
     view_configuration_type_count_output: *mut u32,
-    _view_configuration_types: *mut ViewConfigurationType,
+    view_configuration_types: *mut ViewConfigurationType,
 ) -> Result {
-    *view_configuration_type_count_output = 0;
+    *view_configuration_type_count_output = 1;
+    if view_configuration_type_capacity_input == 0 {
+        return Result::SUCCESS;
+    }
+
+    let view_configuration_types = slice::from_raw_parts_mut(view_configuration_types, 1);
+    view_configuration_types[0] = ViewConfigurationType::PRIMARY_STEREO;
+
     Result::SUCCESS
 }
 
@@ -1740,13 +1786,19 @@ pub unsafe extern "system" fn enumerate_swapchain_formats(
 
 pub unsafe extern "system" fn get_action_state_float(
     _session: Session,
-    _get_info: *const ActionStateGetInfo,
+    get_info: *const ActionStateGetInfo,
     state: *mut ActionStateFloat,
 ) -> Result {
+    let get_info = *get_info;
+    let current_state = STATE
+        .lock()
+        .unwrap()
+        .get_action_state_float(get_info.action, get_info.subaction_path);
+
     *state = ActionStateFloat {
         ty: StructureType::ACTION_STATE_FLOAT,
         next: ptr::null_mut(),
-        current_state: 0.0,
+        current_state,
         changed_since_last_sync: FALSE,
         last_change_time: openxr_sys::Time::from_nanos(0),
         is_active: TRUE,
@@ -1799,13 +1851,8 @@ pub unsafe extern "system" fn get_vulkan_instance_extensions(
     buffer_count_output: *mut u32,
     buffer: *mut c_char,
 ) -> Result {
-    let event_loop: EventLoop<()> = EventLoop::new_any_thread();
-    let window = WindowBuilder::new()
-        // .with_drag_and_drop(false)
-        .with_visible(false)
-        .build(&event_loop)
-        .unwrap();
-    let enabled_extensions = ash_window::enumerate_required_extensions(&window).unwrap();
+    let entry = AshEntry::new().unwrap();
+    let enabled_extensions = surface_extensions(&entry);
     let extensions = enabled_extensions
         .iter()
         .map(|e| e.to_str().unwrap())
@@ -1863,6 +1910,16 @@ fn str_to_fixed_bytes(string: &str) -> [i8; 128] {
     name
 }
 
+/// Show frames as soon as they're ready if we can. Not every driver offers `IMMEDIATE` (eg. Mesa on Wayland), but
+/// every driver has `FIFO`.
+fn pick_present_mode(present_modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+    [vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX]
+        .iter()
+        .find(|m| present_modes.contains(m))
+        .copied()
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
 pub struct SwapChainSupportDetails {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
     pub surface_formats: Vec<vk::SurfaceFormatKHR>,
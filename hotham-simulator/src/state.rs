@@ -5,7 +5,7 @@ use ash::{
 };
 
 use glam::{Quat, Vec3};
-use openxr_sys::{Action, Bool32, Path, Posef, SessionState, Space, Vector3f};
+use openxr_sys::{Action, Bool32, Path, Posef, Quaternionf, SessionState, Space, Vector3f};
 use winit::event::KeyboardInput;
 
 use std::{
@@ -28,6 +28,18 @@ static A_INPUT: &str = "/user/hand/right/input/a/click";
 static B_INPUT: &str = "/user/hand/right/input/b/click";
 static X_INPUT: &str = "/user/hand/left/input/x/click";
 static Y_INPUT: &str = "/user/hand/left/input/y/click";
+static LEFT_HAND: &str = "/user/hand/left";
+static RIGHT_HAND: &str = "/user/hand/right";
+static LEFT_TRIGGER_INPUT: &str = "/user/hand/left/input/trigger/value";
+static LEFT_SQUEEZE_INPUT: &str = "/user/hand/left/input/squeeze/value";
+static RIGHT_TRIGGER_INPUT: &str = "/user/hand/right/input/trigger/value";
+static RIGHT_SQUEEZE_INPUT: &str = "/user/hand/right/input/squeeze/value";
+
+/// Where the right hand is held, relative to the head. The left hand is mirrored, and how far away they're held can be
+/// changed with the arrow keys.
+const HAND_OFFSET: Vec3 = Vec3::new(0.2, 0., -0.5);
+const MIN_HAND_REACH: f32 = 0.2;
+const MAX_HAND_REACH: f32 = 1.;
 
 pub struct State {
     pub vulkan_entry: Option<AshEntry>,
@@ -76,10 +88,20 @@ pub struct State {
     pub action_state: ActionState,
 }
 
-#[derive(Default)]
 pub struct Camera {
     yaw: f32,
     pitch: f32,
+    hand_reach: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            yaw: 0.,
+            pitch: 0.,
+            hand_reach: -HAND_OFFSET.z,
+        }
+    }
 }
 
 impl Default for State {
@@ -268,6 +290,14 @@ impl State {
                 winit::event::VirtualKeyCode::LShift => {
                     position.y -= up.y * movement_speed;
                 }
+                winit::event::VirtualKeyCode::Up => {
+                    self.camera.hand_reach =
+                        (self.camera.hand_reach + dt * 0.5).min(MAX_HAND_REACH);
+                }
+                winit::event::VirtualKeyCode::Down => {
+                    self.camera.hand_reach =
+                        (self.camera.hand_reach - dt * 0.5).max(MIN_HAND_REACH);
+                }
                 winit::event::VirtualKeyCode::Q | winit::event::VirtualKeyCode::Escape => {
                     self.session_state = SessionState::EXITING;
                     self.has_event = true;
@@ -318,6 +348,35 @@ impl State {
         Some(())
     }
 
+    /// Keep the hands in front of the head, so they move with it.
+    pub fn update_hand_poses(&mut self) {
+        let head = self.view_poses[0];
+        let head_position = Vec3::new(head.position.x, head.position.y, head.position.z);
+        let o = head.orientation;
+        let head_orientation = Quat::from_xyzw(o.x, o.y, o.z, o.w);
+        // Point the controllers forward, the way they're held.
+        let hand_orientation =
+            head_orientation * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+
+        for (space, side) in [(self.left_hand_space, -1.), (self.right_hand_space, 1.)] {
+            let offset = Vec3::new(HAND_OFFSET.x * side, HAND_OFFSET.y, -self.camera.hand_reach);
+            let position = head_position + head_orientation * offset;
+            if let Some(space_state) = self.spaces.get_mut(&space) {
+                space_state.position = Vector3f {
+                    x: position.x,
+                    y: position.y,
+                    z: position.z,
+                };
+                space_state.orientation = Quaternionf {
+                    x: hand_orientation.x,
+                    y: hand_orientation.y,
+                    z: hand_orientation.z,
+                    w: hand_orientation.w,
+                };
+            }
+        }
+    }
+
     /// Update simulated action state
     pub fn update_action_state(&mut self) {
         // Reset the state of all the inputs
@@ -338,6 +397,18 @@ impl State {
                 winit::event::VirtualKeyCode::Key4 => {
                     self.press(A_INPUT);
                 }
+                winit::event::VirtualKeyCode::Z => {
+                    self.pull(LEFT_TRIGGER_INPUT, LEFT_HAND);
+                }
+                winit::event::VirtualKeyCode::X => {
+                    self.pull(LEFT_SQUEEZE_INPUT, LEFT_HAND);
+                }
+                winit::event::VirtualKeyCode::C => {
+                    self.pull(RIGHT_TRIGGER_INPUT, RIGHT_HAND);
+                }
+                winit::event::VirtualKeyCode::V => {
+                    self.pull(RIGHT_SQUEEZE_INPUT, RIGHT_HAND);
+                }
                 _ => {}
             }
         }
@@ -348,8 +419,22 @@ impl State {
         self.action_state.get_boolean(action)
     }
 
+    /// How far a trigger or grip is pulled, for one hand or, if `subaction_path` is null, either.
+    pub fn get_action_state_float(&self, action: Action, subaction_path: Path) -> f32 {
+        self.action_state.get_float(action, subaction_path)
+    }
+
     fn press(&mut self, path_string: &str) {
         let path = self.string_path.get(path_string).unwrap();
         self.action_state.set_boolean(path, true);
     }
+
+    fn pull(&mut self, path_string: &str, hand: &str) {
+        if let (Some(path), Some(hand)) = (
+            self.string_path.get(path_string),
+            self.string_path.get(hand),
+        ) {
+            self.action_state.set_float(path, *hand, 1.);
+        }
+    }
 }
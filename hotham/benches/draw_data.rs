@@ -0,0 +1,192 @@
+//! Benchmarks for the CPU side of the renderer: gathering instances, building cull data and building
+//! draw data for 10,000 entities.
+//!
+//! The `aos` benchmarks reproduce the previous approach, which walked the world and wrote one
+//! `Instance` struct at a time, so the two layouts can be compared directly.
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hotham::{
+    components::{skin::NO_SKIN, GlobalTransform, Mesh, Visible},
+    contexts::render_context::{DrawBatch, InstancedPrimitive},
+    glam::{Affine3A, Quat, Vec3, Vec4},
+    hecs::{With, World},
+    id_arena::Arena,
+    rendering::{
+        mesh_data::MeshData,
+        primitive::Primitive,
+        resources::{DrawData, PrimitiveCullData},
+    },
+    systems::rendering::{build_cull_data, build_draw_data, gather_instances},
+};
+
+const ENTITY_COUNT: usize = 10_000;
+const MESH_COUNT: u32 = 100;
+
+struct Instance {
+    gos_from_local: Affine3A,
+    bounding_sphere: Vec4,
+    skin_id: u32,
+}
+
+struct AosInstancedPrimitive {
+    primitive: Primitive,
+    instances: Vec<Instance>,
+}
+
+fn setup() -> (World, Arena<MeshData>) {
+    let mut meshes = Arena::new();
+    let handles = (0..MESH_COUNT)
+        .map(|i| {
+            meshes.alloc(MeshData::new(vec![Primitive {
+                index_buffer_offset: i * 36,
+                indices_count: 36,
+                bounding_sphere: [0., 0., 0., 1.].into(),
+                ..Default::default()
+            }]))
+        })
+        .collect::<Vec<_>>();
+
+    let mut world = World::new();
+    for n in 0..ENTITY_COUNT {
+        let global_from_local = Affine3A::from_scale_rotation_translation(
+            Vec3::splat(0.1),
+            Quat::from_rotation_y(n as f32),
+            [n as f32 % 100., (n / 100) as f32, -10.].into(),
+        );
+        world.spawn((
+            Mesh {
+                handle: handles[n % handles.len()],
+            },
+            GlobalTransform(global_from_local),
+            Visible {},
+        ));
+    }
+
+    (world, meshes)
+}
+
+fn aos_frame(
+    world: &mut World,
+    meshes: &Arena<MeshData>,
+    gos_from_global: &Affine3A,
+    primitive_map: &mut HashMap<u32, AosInstancedPrimitive>,
+    cull_data: &mut Vec<PrimitiveCullData>,
+    draw_data: &mut Vec<DrawData>,
+) {
+    primitive_map.clear();
+    for (_, (mesh, global_transform)) in
+        world.query_mut::<With<(&Mesh, &GlobalTransform), &Visible>>()
+    {
+        let mesh = meshes.get(mesh.handle).unwrap();
+        for primitive in &mesh.primitives {
+            let gos_from_local = *gos_from_global * global_transform.0;
+            primitive_map
+                .entry(primitive.index_buffer_offset)
+                .or_insert(AosInstancedPrimitive {
+                    primitive: primitive.clone(),
+                    instances: Default::default(),
+                })
+                .instances
+                .push(Instance {
+                    gos_from_local,
+                    bounding_sphere: primitive.get_bounding_sphere_in_gos(&gos_from_local),
+                    skin_id: NO_SKIN,
+                });
+        }
+    }
+
+    cull_data.clear();
+    for instanced_primitive in primitive_map.values() {
+        let primitive = &instanced_primitive.primitive;
+        for (instance, i) in instanced_primitive.instances.iter().zip(0u32..) {
+            cull_data.push(PrimitiveCullData {
+                bounding_sphere: instance.bounding_sphere,
+                index_instance: i,
+                primitive_id: primitive.index_buffer_offset,
+                visible: i % 2 == 0,
+            });
+        }
+    }
+
+    draw_data.clear();
+    for cull_result in cull_data.iter() {
+        if cull_result.visible {
+            let instanced_primitive = primitive_map.get(&cull_result.primitive_id).unwrap();
+            let instance = &instanced_primitive.instances[cull_result.index_instance as usize];
+            draw_data.push(DrawData {
+                gos_from_local: instance.gos_from_local.into(),
+                local_from_gos: instance.gos_from_local.inverse().into(),
+                material_id: instanced_primitive.primitive.material_id,
+                skin_id: instance.skin_id,
+                entity_id: u32::MAX,
+                ..Default::default()
+            });
+        }
+    }
+}
+
+fn soa_frame(
+    world: &mut World,
+    meshes: &Arena<MeshData>,
+    gos_from_global: &Affine3A,
+    primitive_map: &mut HashMap<u32, InstancedPrimitive>,
+    cull_data: &mut Vec<PrimitiveCullData>,
+    draw_data: &mut Vec<DrawData>,
+    draw_batches: &mut Vec<DrawBatch>,
+) {
+    for instanced_primitive in primitive_map.values_mut() {
+        instanced_primitive.clear();
+    }
+    gather_instances(world, meshes, gos_from_global, None, primitive_map);
+    build_cull_data(primitive_map, cull_data);
+
+    // Pretend the culling shader has culled every other instance.
+    for cull_result in cull_data.iter_mut() {
+        cull_result.visible = cull_result.index_instance % 2 == 0;
+    }
+
+    build_draw_data(primitive_map, cull_data, draw_data, draw_batches);
+}
+
+fn draw_data_benchmark(c: &mut Criterion) {
+    let (mut world, meshes) = setup();
+    let gos_from_global = Affine3A::from_translation([0.5, 0., 0.5].into());
+    let mut cull_data = Vec::new();
+    let mut draw_data = Vec::new();
+
+    let mut aos_primitive_map = HashMap::new();
+    c.bench_function("aos draw data, 10k entities", |b| {
+        b.iter(|| {
+            aos_frame(
+                &mut world,
+                &meshes,
+                &gos_from_global,
+                &mut aos_primitive_map,
+                &mut cull_data,
+                &mut draw_data,
+            );
+            black_box(&draw_data);
+        })
+    });
+
+    let mut primitive_map = HashMap::new();
+    let mut draw_batches = Vec::new();
+    c.bench_function("soa draw data, 10k entities", |b| {
+        b.iter(|| {
+            soa_frame(
+                &mut world,
+                &meshes,
+                &gos_from_global,
+                &mut primitive_map,
+                &mut cull_data,
+                &mut draw_data,
+                &mut draw_batches,
+            );
+            black_box(&draw_data);
+        })
+    });
+}
+
+criterion_group!(benches, draw_data_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,291 @@
+//! Synthetic scenes for catching performance regressions in the CPU side of the engine.
+//!
+//! Each scene is a criterion benchmark group with one benchmark per stage of the frame, so the report shows how long
+//! each stage takes on its own:
+//!
+//! - `10k_cubes`: transform updates and draw generation for 10,000 cubes, and a physics step with 1,000 of them
+//!   falling onto the ground.
+//! - `100_skinned_characters`: transform hierarchies, joint matrices and draw generation for 100 characters with 32
+//!   joints each.
+//! - `heavy_transparency`: draw generation for 2,000 overlapping alpha masked quads, each with its own primitive and
+//!   material, so nothing can be batched. The cost of actually blending them is on the GPU and isn't measured here.
+//!
+//! Run with `cargo bench --bench scenes`. To compare against another branch, run
+//! `cargo bench --bench scenes -- --save-baseline main` there first, then `cargo bench --bench scenes -- --baseline main`.
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkGroup, Criterion};
+use hotham::{
+    components::{
+        physics::{BodyType, SharedShape},
+        Collider, GlobalTransform, LocalTransform, Mesh, Parent, RigidBody, Skin, Visible,
+    },
+    contexts::{
+        render_context::{DrawBatch, InstancedPrimitive},
+        PhysicsContext,
+    },
+    glam::{Affine3A, Mat4, Quat, Vec3},
+    hecs::World,
+    id_arena::{Arena, Id},
+    rendering::{
+        mesh_data::MeshData,
+        primitive::Primitive,
+        resources::{DrawData, PrimitiveCullData},
+    },
+    systems::{
+        physics::physics_system_inner,
+        rendering::{build_cull_data, build_draw_data, gather_instances},
+        skinning::update_joint_matrices,
+        update_global_transform::update_global_transform_system_inner,
+        update_global_transform_with_parent::update_global_transform_with_parent_system_inner,
+    },
+};
+
+const CUBE_COUNT: usize = 10_000;
+const PHYSICS_CUBE_COUNT: usize = 1_000;
+const CHARACTER_COUNT: usize = 100;
+const JOINTS_PER_CHARACTER: usize = 32;
+const QUAD_COUNT: usize = 2_000;
+
+/// Everything needed to generate draw data for a scene, reused between frames just like the renderer does.
+#[derive(Default)]
+struct DrawGeneration {
+    primitive_map: HashMap<u32, InstancedPrimitive>,
+    cull_data: Vec<PrimitiveCullData>,
+    draw_data: Vec<DrawData>,
+    draw_batches: Vec<DrawBatch>,
+}
+
+impl DrawGeneration {
+    fn gather(&mut self, world: &mut World, meshes: &Arena<MeshData>) {
+        for instanced_primitive in self.primitive_map.values_mut() {
+            instanced_primitive.clear();
+        }
+        gather_instances(
+            world,
+            meshes,
+            &Affine3A::IDENTITY,
+            None,
+            &mut self.primitive_map,
+        );
+    }
+
+    fn cull(&mut self) {
+        build_cull_data(&self.primitive_map, &mut self.cull_data);
+
+        // Pretend the culling shader has culled every other instance.
+        for cull_result in self.cull_data.iter_mut() {
+            cull_result.visible = cull_result.index_instance % 2 == 0;
+        }
+    }
+
+    fn draw(&mut self) {
+        build_draw_data(
+            &self.primitive_map,
+            &self.cull_data,
+            &mut self.draw_data,
+            &mut self.draw_batches,
+        );
+    }
+}
+
+/// Benchmark each stage of draw generation separately. Each stage is run once first so the later stages have
+/// something to work with.
+fn bench_draw_generation<M: criterion::measurement::Measurement>(
+    group: &mut BenchmarkGroup<M>,
+    world: &mut World,
+    meshes: &Arena<MeshData>,
+) {
+    let mut draw_generation = DrawGeneration::default();
+    draw_generation.gather(world, meshes);
+    draw_generation.cull();
+    draw_generation.draw();
+
+    group.bench_function("gather_instances", |b| {
+        b.iter(|| {
+            draw_generation.gather(world, meshes);
+            black_box(&draw_generation.primitive_map);
+        })
+    });
+    group.bench_function("build_cull_data", |b| {
+        b.iter(|| {
+            draw_generation.cull();
+            black_box(&draw_generation.cull_data);
+        })
+    });
+    group.bench_function("build_draw_data", |b| {
+        b.iter(|| {
+            draw_generation.draw();
+            black_box(&draw_generation.draw_data);
+        })
+    });
+}
+
+fn add_mesh(meshes: &mut Arena<MeshData>, index: u32, material_id: u32) -> Id<MeshData> {
+    meshes.alloc(MeshData::new(vec![Primitive {
+        index_buffer_offset: index * 36,
+        indices_count: 36,
+        material_id,
+        bounding_sphere: [0., 0., 0., 1.].into(),
+        ..Default::default()
+    }]))
+}
+
+fn cubes(c: &mut Criterion) {
+    let mut meshes = Arena::new();
+    let cube = add_mesh(&mut meshes, 0, 0);
+
+    let mut world = World::new();
+    for n in 0..CUBE_COUNT {
+        let local_transform = LocalTransform {
+            translation: [(n % 100) as f32, (n / 100) as f32, -10.].into(),
+            rotation: Quat::from_rotation_y(n as f32),
+            scale: Vec3::splat(0.1),
+        };
+        world.spawn((
+            Mesh { handle: cube },
+            local_transform,
+            GlobalTransform::from(local_transform),
+            Visible {},
+        ));
+    }
+
+    let mut group = c.benchmark_group("10k_cubes");
+    group.bench_function("update_global_transform", |b| {
+        b.iter(|| update_global_transform_system_inner(&mut world))
+    });
+    bench_draw_generation(&mut group, &mut world, &meshes);
+
+    // Physics gets its own world, so the draw generation numbers above aren't affected by bodies being added.
+    let mut physics_context = PhysicsContext::default();
+    let mut world = World::new();
+    let ground = LocalTransform {
+        translation: [0., -1., 0.].into(),
+        ..Default::default()
+    };
+    world.spawn((
+        Collider::new(SharedShape::cuboid(50., 1., 50.)),
+        RigidBody {
+            body_type: BodyType::Fixed,
+            ..Default::default()
+        },
+        ground,
+        GlobalTransform::from(ground),
+    ));
+    for n in 0..PHYSICS_CUBE_COUNT {
+        let local_transform = LocalTransform {
+            translation: [(n % 10) as f32, (n / 100) as f32, ((n / 10) % 10) as f32].into(),
+            ..Default::default()
+        };
+        world.spawn((
+            Collider::new(SharedShape::cuboid(0.1, 0.1, 0.1)),
+            RigidBody::default(),
+            local_transform,
+            GlobalTransform::from(local_transform),
+        ));
+    }
+
+    // Stepping the simulation is much slower than anything else, so take fewer samples.
+    group.sample_size(20);
+    group.bench_function("physics_1k_dynamic", |b| {
+        b.iter(|| physics_system_inner(&mut physics_context, &mut world))
+    });
+    group.finish();
+}
+
+fn skinned_characters(c: &mut Criterion) {
+    let mut meshes = Arena::new();
+    let body = add_mesh(&mut meshes, 0, 0);
+
+    let mut world = World::new();
+    for n in 0..CHARACTER_COUNT {
+        let root_transform = LocalTransform {
+            translation: [(n % 10) as f32, 0., -((n / 10) as f32)].into(),
+            ..Default::default()
+        };
+        let root = world.spawn((root_transform, GlobalTransform::from(root_transform)));
+
+        // A single long chain is the worst case for the hierarchy walk.
+        let mut parent = root;
+        let joints = (0..JOINTS_PER_CHARACTER)
+            .map(|j| {
+                let joint_transform = LocalTransform {
+                    translation: [0., 0.05, 0.].into(),
+                    rotation: Quat::from_rotation_z(0.01 * j as f32),
+                    ..Default::default()
+                };
+                parent = world.spawn((
+                    Parent(parent),
+                    joint_transform,
+                    GlobalTransform::from(joint_transform),
+                ));
+                parent
+            })
+            .collect::<Vec<_>>();
+
+        world.spawn((
+            Mesh { handle: body },
+            Skin {
+                joints,
+                inverse_bind_matrices: vec![Affine3A::IDENTITY; JOINTS_PER_CHARACTER],
+                id: n as _,
+            },
+            Parent(root),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+            Visible {},
+        ));
+    }
+
+    let mut group = c.benchmark_group("100_skinned_characters");
+    group.bench_function("update_global_transform", |b| {
+        b.iter(|| update_global_transform_system_inner(&mut world))
+    });
+    group.bench_function("update_global_transform_with_parent", |b| {
+        b.iter(|| {
+            // The hierarchy is applied on top of the local transforms, so reset them each time.
+            update_global_transform_system_inner(&mut world);
+            update_global_transform_with_parent_system_inner(&mut world);
+        })
+    });
+
+    let mut joint_matrices = vec![[Mat4::IDENTITY; JOINTS_PER_CHARACTER]; CHARACTER_COUNT];
+    group.bench_function("update_joint_matrices", |b| {
+        b.iter(|| {
+            for (_, (skin, global_transform)) in world.query::<(&Skin, &GlobalTransform)>().iter() {
+                let joint_matrices = &mut joint_matrices[skin.id as usize];
+                update_joint_matrices(&world, skin, global_transform, joint_matrices);
+            }
+            black_box(&joint_matrices);
+        })
+    });
+    bench_draw_generation(&mut group, &mut world, &meshes);
+    group.finish();
+}
+
+fn heavy_transparency(c: &mut Criterion) {
+    let mut meshes = Arena::new();
+    let mut world = World::new();
+    for n in 0..QUAD_COUNT {
+        // Every quad has its own primitive and material, so each one is a separate draw.
+        let quad = add_mesh(&mut meshes, n as _, n as _);
+        let local_transform = LocalTransform {
+            translation: [0., 0., -1. - 0.001 * n as f32].into(),
+            scale: [1., 1., 0.01].into(),
+            ..Default::default()
+        };
+        world.spawn((
+            Mesh { handle: quad },
+            local_transform,
+            GlobalTransform::from(local_transform),
+            Visible {},
+        ));
+    }
+
+    let mut group = c.benchmark_group("heavy_transparency");
+    bench_draw_generation(&mut group, &mut world, &meshes);
+    group.finish();
+}
+
+criterion_group!(benches, cubes, skinned_characters, heavy_transparency);
+criterion_main!(benches);
@@ -0,0 +1,416 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    net::TcpStream,
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use glam::Vec3;
+use hecs::Entity;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{components::hand::Handedness, HothamError, HothamResult};
+
+/// How many records an [`HttpSink`] collects before sending them
+const HTTP_BATCH_SIZE: usize = 32;
+/// How long an [`HttpSink`] waits for the endpoint before giving up on a batch
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something interesting the player did, sent to each [`AnalyticsSink`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnalyticsEvent {
+    /// The OpenXR session started
+    SessionStarted,
+    /// The OpenXR session ended
+    SessionEnded {
+        /// How long the session lasted, in seconds
+        duration: f64,
+    },
+    /// A scene finished loading. Hotham doesn't know when an app moves between scenes, so record this yourself.
+    SceneLoaded {
+        /// The scene's name
+        name: String,
+    },
+    /// A hand grabbed something
+    Grabbed {
+        /// Which hand it was
+        hand: Handedness,
+        /// What was grabbed, from its [`crate::components::Info`]
+        entity: Option<String>,
+    },
+    /// A hand let go of what it was holding
+    Released {
+        /// Which hand it was
+        hand: Handedness,
+        /// What was let go, from its [`crate::components::Info`]
+        entity: Option<String>,
+    },
+    /// An entity was teleported with [`crate::components::physics::Teleport`]
+    Teleported {
+        /// What was teleported, from its [`crate::components::Info`]
+        entity: Option<String>,
+        /// Where it was teleported to, from its [`crate::components::LocalTransform`]
+        position: Vec3,
+    },
+    /// A button on a [`crate::components::UIPanel`] was clicked
+    UiClicked {
+        /// The button's text
+        button: String,
+    },
+    /// An event of the app's own
+    Custom {
+        /// What happened
+        name: String,
+        /// Anything else worth knowing about it
+        properties: serde_json::Value,
+    },
+}
+
+/// An [`AnalyticsEvent`], with when and in which session it happened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnalyticsRecord {
+    /// A random ID for the session, so events from the same session can be grouped together
+    pub session_id: Uuid,
+    /// Seconds since the session started
+    pub time: f64,
+    /// What happened
+    #[serde(flatten)]
+    pub event: AnalyticsEvent,
+}
+
+/// Somewhere to send analytics records, eg. a file or a studio's analytics service.
+///
+/// Sinks are called on the main thread, so anything slow, like a network request, should be done in the background -
+/// see [`HttpSink`].
+pub trait AnalyticsSink: Send {
+    /// Send a record on.
+    fn record(&mut self, record: &AnalyticsRecord);
+
+    /// Send on any records that have been held back, eg. because the session is ending.
+    fn flush(&mut self) {}
+}
+
+/// Sends the player's interactions - grabs, teleports, UI clicks, scene loads and how long sessions last - to
+/// any number of [`AnalyticsSink`]s, so studios can wire up their own analytics without patching Hotham's systems.
+///
+/// Nothing is recorded until a sink has been added with [`Analytics::add_sink`]:
+/// ```ignore
+/// engine.analytics.add_sink(FileSink::create(engine.paths.cache().join("analytics.jsonl"))?);
+/// engine.analytics.record(AnalyticsEvent::SceneLoaded { name: "beach".into() });
+/// ```
+/// Grabs, releases, teleports and UI clicks are recorded by `analytics_system`, and the start and end of each session
+/// by the engine.
+pub struct Analytics {
+    sinks: Vec<Box<dyn AnalyticsSink>>,
+    session_id: Uuid,
+    session_start: Option<Instant>,
+    /// What each hand was holding last frame, so `analytics_system` can tell when it grabs or lets go of something
+    pub(crate) held_entities: HashMap<Entity, Entity>,
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Self {
+            sinks: Vec::new(),
+            session_id: Uuid::new_v4(),
+            session_start: None,
+            held_entities: HashMap::new(),
+        }
+    }
+}
+
+impl Analytics {
+    /// Send records to `sink` from now on.
+    pub fn add_sink(&mut self, sink: impl AnalyticsSink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Is anything listening? When nothing is, events aren't worth collecting.
+    pub fn is_enabled(&self) -> bool {
+        !self.sinks.is_empty()
+    }
+
+    /// The current session's ID
+    pub fn session_id(&self) -> Uuid {
+        self.session_id
+    }
+
+    /// Send `event` to every sink.
+    pub fn record(&mut self, event: AnalyticsEvent) {
+        if !self.is_enabled() {
+            return;
+        }
+        let record = AnalyticsRecord {
+            session_id: self.session_id,
+            time: self
+                .session_start
+                .map_or(0., |start| start.elapsed().as_secs_f64()),
+            event,
+        };
+        for sink in &mut self.sinks {
+            sink.record(&record);
+        }
+    }
+
+    /// Send on any records the sinks are holding back.
+    pub fn flush(&mut self) {
+        for sink in &mut self.sinks {
+            sink.flush();
+        }
+    }
+
+    /// Start a new session, with a new ID.
+    pub(crate) fn session_started(&mut self) {
+        self.session_id = Uuid::new_v4();
+        self.session_start = Some(Instant::now());
+        self.record(AnalyticsEvent::SessionStarted);
+    }
+
+    /// Record how long the session lasted, if one has started.
+    pub(crate) fn session_ended(&mut self) {
+        if let Some(start) = self.session_start {
+            self.record(AnalyticsEvent::SessionEnded {
+                duration: start.elapsed().as_secs_f64(),
+            });
+            self.session_start = None;
+            self.flush();
+        }
+    }
+}
+
+/// Writes each record to a file as a line of JSON.
+pub struct FileSink {
+    writer: BufWriter<File>,
+}
+
+impl FileSink {
+    /// Start writing records to `path`, adding them to the end of the file if it's already there.
+    pub fn create(path: impl AsRef<Path>) -> HothamResult<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
+impl AnalyticsSink for FileSink {
+    fn record(&mut self, record: &AnalyticsRecord) {
+        let written = serde_json::to_writer(&mut self.writer, record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+        if let Err(e) = written {
+            log::error!("[HOTHAM_ANALYTICS] Unable to write record: {:?}", e);
+        }
+    }
+
+    fn flush(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// POSTs records to an HTTP endpoint in batches, as a JSON array, from a background thread.
+///
+/// Only plain `http://` endpoints are supported. To send records to a service over HTTPS, point this at a local
+/// collector or proxy that forwards them on.
+pub struct HttpSink {
+    sender: Sender<HttpMessage>,
+}
+
+enum HttpMessage {
+    Record(AnalyticsRecord),
+    Flush,
+}
+
+impl HttpSink {
+    /// Send records to `url`, eg. `http://192.168.0.10:8080/events`.
+    pub fn new(url: &str) -> HothamResult<Self> {
+        let endpoint = HttpEndpoint::parse(url)?;
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("hotham_analytics".to_string())
+            .spawn(move || send_batches(&endpoint, receiver))?;
+        Ok(Self { sender })
+    }
+}
+
+impl AnalyticsSink for HttpSink {
+    fn record(&mut self, record: &AnalyticsRecord) {
+        let _ = self.sender.send(HttpMessage::Record(record.clone()));
+    }
+
+    fn flush(&mut self) {
+        let _ = self.sender.send(HttpMessage::Flush);
+    }
+}
+
+impl Drop for HttpSink {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Collect records into batches and POST them, until the sink is dropped.
+fn send_batches(endpoint: &HttpEndpoint, receiver: Receiver<HttpMessage>) {
+    let mut batch = Vec::with_capacity(HTTP_BATCH_SIZE);
+    for message in receiver {
+        let flush = match message {
+            HttpMessage::Record(record) => {
+                batch.push(record);
+                batch.len() >= HTTP_BATCH_SIZE
+            }
+            HttpMessage::Flush => true,
+        };
+        if flush && !batch.is_empty() {
+            if let Err(e) = endpoint.post(&batch) {
+                log::error!(
+                    "[HOTHAM_ANALYTICS] Unable to send {} records: {:?}",
+                    batch.len(),
+                    e
+                );
+            }
+            batch.clear();
+        }
+    }
+}
+
+/// Where an [`HttpSink`] sends its records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HttpEndpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl HttpEndpoint {
+    fn parse(url: &str) -> HothamResult<Self> {
+        let invalid = |reason: &str| {
+            HothamError::Other(anyhow::anyhow!(
+                "{:?} is not a valid analytics endpoint: {}",
+                url,
+                reason
+            ))
+        };
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("only http:// endpoints are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| invalid("the port is not a number"))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid("the host is missing"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn post(&self, records: &[AnalyticsRecord]) -> std::io::Result<()> {
+        let body = serde_json::to_vec(records)?;
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+        stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+
+        // We only care whether the endpoint accepted the records.
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response.split_whitespace().nth(1).unwrap_or_default();
+        if !status.starts_with('2') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("the endpoint responded with {:?}", status),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct TestSink(Arc<Mutex<Vec<AnalyticsRecord>>>);
+
+    impl AnalyticsSink for TestSink {
+        fn record(&mut self, record: &AnalyticsRecord) {
+            self.0.lock().unwrap().push(record.clone());
+        }
+    }
+
+    #[test]
+    pub fn test_analytics() {
+        let mut analytics = Analytics::default();
+        analytics.session_started();
+        assert!(!analytics.is_enabled());
+
+        let sink = TestSink::default();
+        analytics.add_sink(sink.clone());
+        analytics.record(AnalyticsEvent::Grabbed {
+            hand: Handedness::Left,
+            entity: Some("Cube".to_string()),
+        });
+        analytics.session_ended();
+        analytics.session_ended();
+
+        let records = sink.0.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(matches!(
+            records[1].event,
+            AnalyticsEvent::SessionEnded { .. }
+        ));
+        assert_eq!(
+            serde_json::to_value(&records[0]).unwrap(),
+            serde_json::json!({
+                "session_id": analytics.session_id(),
+                "time": records[0].time,
+                "type": "grabbed",
+                "hand": "Left",
+                "entity": "Cube",
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_http_endpoint() {
+        assert_eq!(
+            HttpEndpoint::parse("http://10.0.0.2:8080/events").unwrap(),
+            HttpEndpoint {
+                host: "10.0.0.2".to_string(),
+                port: 8080,
+                path: "/events".to_string(),
+            }
+        );
+        assert_eq!(HttpEndpoint::parse("http://example.com").unwrap().port, 80);
+        assert!(HttpEndpoint::parse("https://example.com").is_err());
+        assert!(HttpEndpoint::parse("http://:80/").is_err());
+    }
+}
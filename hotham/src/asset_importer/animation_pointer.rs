@@ -0,0 +1,166 @@
+// KHR_animation_pointer lets animation channels target material and light properties instead of nodes:
+// https://github.com/KhronosGroup/glTF/blob/main/extensions/2.0/Khronos/KHR_animation_pointer/README.md
+//
+// The `gltf` crate requires every channel to target a node, so channels using the extension are taken out of the JSON
+// before the document is parsed, and loaded separately.
+
+use serde_json::Value;
+
+/// An animation channel that targets a property with a JSON pointer, rather than a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PointerChannel {
+    /// The index of the animation the channel is part of
+    pub animation: usize,
+    /// The index of the channel's sampler, within its animation
+    pub sampler: usize,
+    /// The property being animated, eg. `/materials/0/emissiveFactor`
+    pub pointer: String,
+}
+
+/// The properties Hotham can animate with a pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PointerTarget {
+    /// A material's `pbrMetallicRoughness/baseColorFactor`
+    BaseColorFactor { material: usize },
+    /// A material's `emissiveFactor`
+    EmissiveFactor { material: usize },
+    /// A `KHR_lights_punctual` light's `color`
+    LightColor { light: usize },
+    /// A `KHR_lights_punctual` light's `intensity`
+    LightIntensity { light: usize },
+}
+
+impl PointerTarget {
+    /// Work out what `pointer` targets, or `None` if it's a property that can't be animated.
+    pub fn parse(pointer: &str) -> Option<Self> {
+        let parts = pointer.strip_prefix('/')?.split('/').collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["materials", material, "pbrMetallicRoughness", "baseColorFactor"] => {
+                Some(Self::BaseColorFactor {
+                    material: material.parse().ok()?,
+                })
+            }
+            ["materials", material, "emissiveFactor"] => Some(Self::EmissiveFactor {
+                material: material.parse().ok()?,
+            }),
+            ["extensions", "KHR_lights_punctual", "lights", light, "color"] => {
+                Some(Self::LightColor {
+                    light: light.parse().ok()?,
+                })
+            }
+            ["extensions", "KHR_lights_punctual", "lights", light, "intensity"] => {
+                Some(Self::LightIntensity {
+                    light: light.parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Take any channels using `KHR_animation_pointer` out of the glTF `json`, so what's left can be parsed.
+pub(crate) fn take_pointer_channels(json: &mut Value) -> Vec<PointerChannel> {
+    let mut pointer_channels = Vec::new();
+
+    if let Some(animations) = json.get_mut("animations").and_then(Value::as_array_mut) {
+        for (animation_index, animation) in animations.iter_mut().enumerate() {
+            if let Some(channels) = animation.get_mut("channels").and_then(Value::as_array_mut) {
+                channels.retain(|channel| match pointer_channel(animation_index, channel) {
+                    Some(pointer_channel) => {
+                        pointer_channels.push(pointer_channel);
+                        false
+                    }
+                    None => true,
+                });
+            }
+        }
+    }
+
+    pointer_channels
+}
+
+fn pointer_channel(animation: usize, channel: &Value) -> Option<PointerChannel> {
+    let target = channel.get("target")?;
+    if target.get("path")?.as_str()? != "pointer" {
+        return None;
+    }
+
+    Some(PointerChannel {
+        animation,
+        sampler: channel.get("sampler")?.as_u64()? as _,
+        pointer: target
+            .get("extensions")?
+            .get("KHR_animation_pointer")?
+            .get("pointer")?
+            .as_str()?
+            .to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_pointer_channels() {
+        let mut json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "animations": [{
+                "channels": [
+                    { "sampler": 0, "target": { "node": 0, "path": "translation" } },
+                    {
+                        "sampler": 1,
+                        "target": {
+                            "path": "pointer",
+                            "extensions": {
+                                "KHR_animation_pointer": { "pointer": "/materials/2/emissiveFactor" }
+                            }
+                        }
+                    }
+                ],
+                "samplers": []
+            }]
+        });
+
+        // The pointer channel is taken out, and the node channel is left behind.
+        let pointer_channels = take_pointer_channels(&mut json);
+        let root: gltf::json::Root = serde_json::from_value(json).unwrap();
+        assert_eq!(root.animations[0].channels.len(), 1);
+        assert_eq!(
+            pointer_channels,
+            [PointerChannel {
+                animation: 0,
+                sampler: 1,
+                pointer: "/materials/2/emissiveFactor".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_parse_pointer() {
+        assert_eq!(
+            PointerTarget::parse("/materials/3/pbrMetallicRoughness/baseColorFactor"),
+            Some(PointerTarget::BaseColorFactor { material: 3 })
+        );
+        assert_eq!(
+            PointerTarget::parse("/materials/0/emissiveFactor"),
+            Some(PointerTarget::EmissiveFactor { material: 0 })
+        );
+        assert_eq!(
+            PointerTarget::parse("/extensions/KHR_lights_punctual/lights/1/color"),
+            Some(PointerTarget::LightColor { light: 1 })
+        );
+        assert_eq!(
+            PointerTarget::parse("/extensions/KHR_lights_punctual/lights/1/intensity"),
+            Some(PointerTarget::LightIntensity { light: 1 })
+        );
+
+        // Properties we can't animate are ignored.
+        assert_eq!(
+            PointerTarget::parse("/materials/0/pbrMetallicRoughness/roughnessFactor"),
+            None
+        );
+        assert_eq!(PointerTarget::parse("/materials/x/emissiveFactor"), None);
+        assert_eq!(PointerTarget::parse("materials/0/emissiveFactor"), None);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::VecDeque, path::PathBuf, thread};
+
+use anyhow::{Context, Result};
+use crossbeam::channel::{Receiver, TryRecvError};
+
+use crate::contexts::{RenderContext, VulkanContext};
+
+use super::{load_models_from_glb, Models};
+
+/// How far an [`AssetLoader`] has got.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadingProgress {
+    /// How many files are being loaded
+    pub total: usize,
+    /// How many files have been read from disk
+    pub read: usize,
+    /// How many files have been imported into the renderer
+    pub imported: usize,
+}
+
+impl LoadingProgress {
+    /// How far through loading we are, from 0 to 1. Reading and importing each count for half.
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            0 => 1.,
+            total => (self.read + self.imported) as f32 / (total * 2) as f32,
+        }
+    }
+
+    /// Has everything been loaded?
+    pub fn is_finished(&self) -> bool {
+        self.imported == self.total
+    }
+}
+
+/// Loads GLB files without blocking the frame loop.
+///
+/// Files are read from disk on a background thread. Uploading to the GPU has to happen on the render thread, so each
+/// call to [`AssetLoader::update`] imports at most one file, letting the app keep rendering, eg. a
+/// [`crate::components::LoadingPanel`], in between.
+///
+/// Basic usage:
+/// ```ignore
+/// let mut loader = AssetLoader::load_models(vec!["level_1.glb", "enemies.glb"]);
+/// let panel = add_loading_panel_to_world(Vec3::new(0., 1.5, -1.), ..);
+/// let models = loop {
+///     let tick_data = engine.update()?;
+///     if let Some(models) = loader.update(&engine.vulkan_context, &mut engine.render_context)? {
+///         break models;
+///     }
+///     loading_panel_system(&mut engine, &loader.progress());
+///     draw_gui_system(&mut engine);
+///     rendering_system(&mut engine, tick_data.swapchain_image_index);
+///     engine.finish()?;
+/// };
+/// engine.world.despawn(panel)?;
+/// ```
+pub struct AssetLoader {
+    receiver: Receiver<(PathBuf, std::io::Result<Vec<u8>>)>,
+    pending: VecDeque<(PathBuf, Vec<u8>)>,
+    progress: LoadingProgress,
+    models: Models,
+}
+
+impl AssetLoader {
+    /// Start loading the models in each of the GLB files at `paths`.
+    pub fn load_models<P: Into<PathBuf>>(paths: impl IntoIterator<Item = P>) -> Self {
+        let paths = paths.into_iter().map(Into::into).collect::<Vec<PathBuf>>();
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let progress = LoadingProgress {
+            total: paths.len(),
+            ..Default::default()
+        };
+
+        thread::Builder::new()
+            .name("hotham_asset_loader".to_string())
+            .spawn(move || {
+                for path in paths {
+                    let bytes = std::fs::read(&path);
+                    if sender.send((path, bytes)).is_err() {
+                        // The loader was dropped, so nobody wants these any more.
+                        return;
+                    }
+                }
+            })
+            .expect("Unable to spawn asset loader thread");
+
+        Self {
+            receiver,
+            pending: Default::default(),
+            progress,
+            models: Default::default(),
+        }
+    }
+
+    /// How far loading has got
+    pub fn progress(&self) -> LoadingProgress {
+        self.progress
+    }
+
+    /// Import the next file that's been read, if there is one. Call this once per frame.
+    ///
+    /// Returns the loaded models once every file has been imported.
+    pub fn update(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        render_context: &mut RenderContext,
+    ) -> Result<Option<Models>> {
+        self.receive()?;
+
+        if let Some((path, bytes)) = self.pending.pop_front() {
+            let models = load_models_from_glb(&[&bytes], vulkan_context, render_context)
+                .with_context(|| format!("Unable to import {}", path.display()))?;
+            self.models.extend(models);
+            self.progress.imported += 1;
+        }
+
+        if self.progress.is_finished() {
+            return Ok(Some(std::mem::take(&mut self.models)));
+        }
+
+        Ok(None)
+    }
+
+    /// Collect any files the background thread has finished reading.
+    fn receive(&mut self) -> Result<()> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok((path, bytes)) => {
+                    let bytes =
+                        bytes.with_context(|| format!("Unable to read {}", path.display()))?;
+                    self.pending.push_back((path, bytes));
+                    self.progress.read += 1;
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_progress() {
+        let progress = LoadingProgress {
+            total: 4,
+            read: 2,
+            imported: 1,
+        };
+        assert_eq!(progress.fraction(), 0.375);
+        assert!(!progress.is_finished());
+
+        // Loading nothing should finish straight away.
+        assert_eq!(LoadingProgress::default().fraction(), 1.);
+        assert!(LoadingProgress::default().is_finished());
+    }
+
+    #[test]
+    pub fn test_read_in_background() {
+        let path = std::env::temp_dir().join(format!(
+            "hotham_asset_loader_test_{}.glb",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"not really a glb").unwrap();
+
+        let mut loader = AssetLoader::load_models(vec![path.clone()]);
+        while loader.progress().read == 0 {
+            loader.receive().unwrap();
+        }
+        assert_eq!(
+            loader.pending[0],
+            (path.clone(), b"not really a glb".to_vec())
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        // Files that can't be read should be reported.
+        let mut loader = AssetLoader::load_models(vec![path]);
+        let error = loop {
+            if let Err(e) = loader.receive() {
+                break e;
+            }
+        };
+        assert!(error.to_string().starts_with("Unable to read"));
+    }
+}
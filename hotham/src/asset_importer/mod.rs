@@ -1,10 +1,20 @@
+/// Support for the KHR_animation_pointer glTF extension
+pub(crate) mod animation_pointer;
+/// Loading GLB files without blocking the frame loop
+pub mod loader;
 /// Representation of a glTF Scene
 pub mod scene;
+/// Support for the VRMC_springBone glTF extension
+pub(crate) mod spring_bones;
+/// Support for the VRMC_vrm and VRMC_materials_mtoon glTF extensions
+pub(crate) mod vrm;
+/// Reloading GLB files when they change, for iterating on art
+pub mod watcher;
 
 use crate::{
     components::{
-        animation_controller::AnimationController, Collider, GlobalTransform, Info, LocalTransform,
-        Mesh, Parent, Root, Skin, Visible,
+        animation_controller::AnimationController, Collider, Expressions, GlobalTransform,
+        Humanoid, Info, LocalTransform, Mesh, Parent, Root, Skin, SpringBone, Visible,
     },
     contexts::{
         physics_context::{self},
@@ -19,9 +29,18 @@ use gltf::Document;
 use hecs::{Entity, World};
 use itertools::Itertools;
 use rapier3d::prelude::ActiveCollisionTypes;
-use std::{borrow::Cow, collections::HashMap, convert::TryInto};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+};
 
-use self::scene::Scene;
+use self::{
+    animation_pointer::{take_pointer_channels, PointerChannel},
+    scene::Scene,
+    spring_bones::SpringBoneExtension,
+    vrm::{mtoon_materials, VrmExtension},
+};
 
 static COLLIDER_TAG: &str = ".HOTHAM_COLLIDER";
 static WALL_COLLIDER_TAG: &str = ".HOTHAM_COLLIDER_WALL";
@@ -40,6 +59,10 @@ pub(crate) struct ImportContext<'a> {
     pub document: Document,
     pub buffer: Cow<'a, [u8]>,
     pub material_buffer_offset: u32,
+    pub pointer_channels: Vec<PointerChannel>,
+    pub spring_bone_extension: Option<SpringBoneExtension>,
+    pub vrm_extension: Option<VrmExtension>,
+    pub mtoon_materials: HashSet<usize>,
 }
 
 impl<'a> ImportContext<'a> {
@@ -49,8 +72,13 @@ impl<'a> ImportContext<'a> {
         glb_buffer: &'a [u8],
     ) -> Self {
         let glb = gltf::Glb::from_slice(glb_buffer).unwrap();
-        let json = gltf::json::Root::from_slice(&glb.json).unwrap();
-        let document = gltf::Document::from_json_without_validation(json);
+        let mut json: serde_json::Value = serde_json::from_slice(&glb.json).unwrap();
+        let pointer_channels = take_pointer_channels(&mut json);
+        let spring_bone_extension = SpringBoneExtension::from_json(&json).unwrap();
+        let vrm_extension = VrmExtension::from_json(&json).unwrap();
+        let mtoon_materials = mtoon_materials(&json);
+        let document =
+            gltf::Document::from_json_without_validation(serde_json::from_value(json).unwrap());
         let buffer = glb.bin.unwrap();
 
         let material_buffer_offset = render_context.resources.materials_buffer.len as _;
@@ -63,6 +91,10 @@ impl<'a> ImportContext<'a> {
             document,
             buffer,
             material_buffer_offset,
+            pointer_channels,
+            spring_bone_extension,
+            vrm_extension,
+            mtoon_materials,
         }
     }
 }
@@ -200,9 +232,101 @@ fn load_models_from_gltf_data(import_context: &mut ImportContext) -> Result<()>
         .insert_one(animation_controller_entity, animation_controller)
         .unwrap();
 
+    load_spring_bones(import_context);
+    load_vrm(import_context);
+
     Ok(())
 }
 
+/// VRM avatars get their `Humanoid` and `Expressions` on the root entity of the model with their hips.
+fn load_vrm(import_context: &mut ImportContext) {
+    let extension = match &import_context.vrm_extension {
+        Some(extension) => extension,
+        None => return,
+    };
+    let hips = match extension
+        .hips()
+        .and_then(|hips| import_context.node_entity_map.get(&hips))
+    {
+        Some(hips) => *hips,
+        None => {
+            println!("[HOTHAM_ASSET_IMPORTER] Ignoring VRM avatar with no hips");
+            return;
+        }
+    };
+
+    let materials_buffer = &import_context.render_context.resources.materials_buffer;
+    let materials = unsafe { materials_buffer.as_slice() };
+    let materials = &materials[import_context.material_buffer_offset as usize..];
+    let mut humanoid = extension.humanoid(&import_context.node_entity_map);
+    let mut expressions = extension.expressions(
+        &import_context.node_entity_map,
+        materials,
+        import_context.material_buffer_offset,
+    );
+
+    let world = match import_context
+        .models
+        .values_mut()
+        .find(|w| w.contains(hips))
+    {
+        Some(world) => world,
+        None => return,
+    };
+
+    // Bones and morph targets can only use entities in the same model.
+    humanoid.bones.retain(|_, entity| world.contains(*entity));
+    for expression in &mut expressions.expressions {
+        expression
+            .morph_target_binds
+            .retain(|bind| world.contains(bind.entity));
+    }
+
+    let root = world.query::<&Root>().iter().next().map(|(e, _)| e);
+    if let Some(root) = root {
+        world.insert(root, (humanoid, expressions)).unwrap();
+    }
+}
+
+/// Spring bones live on the first joint in their chain.
+fn load_spring_bones(import_context: &mut ImportContext) {
+    let spring_bones = match &import_context.spring_bone_extension {
+        Some(extension) => extension.spring_bones(&import_context.node_entity_map),
+        None => return,
+    };
+
+    for mut spring_bone in spring_bones {
+        let root_joint = spring_bone.joints[0];
+        let world = match import_context
+            .models
+            .values_mut()
+            .find(|w| w.contains(root_joint))
+        {
+            Some(world) => world,
+            None => continue,
+        };
+
+        // Chains and colliders can only use entities in the same model.
+        if !spring_bone
+            .joints
+            .iter()
+            .all(|joint| world.contains(*joint))
+        {
+            println!("[HOTHAM_ASSET_IMPORTER] Ignoring spring bone that spans more than one model");
+            continue;
+        }
+        spring_bone
+            .colliders
+            .retain(|collider| world.contains(collider.entity));
+
+        if world.get::<&SpringBone>(root_joint).is_ok() {
+            println!("[HOTHAM_ASSET_IMPORTER] Ignoring spring bone that starts at the same joint as another");
+            continue;
+        }
+        world.insert_one(root_joint, spring_bone).unwrap();
+    }
+}
+
 fn get_collider_mesh_ids(nodes: gltf::iter::Nodes) -> Vec<usize> {
     let mut mesh_ids = Vec::new();
     for node in nodes {
@@ -530,12 +654,61 @@ pub fn add_model_to_world(
                 .targets
                 .iter_mut()
                 .for_each(|t| t.target = entity_map.get(&t.target).cloned().unwrap());
+            new_animation_controller
+                .clips
+                .iter_mut()
+                .flat_map(|c| c.channels.iter_mut())
+                .for_each(|c| c.target = entity_map.get(&c.target).cloned().unwrap());
 
             destination_world
                 .insert_one(*destination_entity, new_animation_controller)
                 .unwrap();
         }
 
+        if let Some(spring_bone) = source_entity.get::<&SpringBone>() {
+            let mut new_spring_bone = (*spring_bone).clone();
+
+            // Map the chain and its colliders to their new entities, and start the simulation again.
+            new_spring_bone
+                .joints
+                .iter_mut()
+                .for_each(|e| *e = entity_map.get(e).cloned().unwrap());
+            new_spring_bone
+                .colliders
+                .iter_mut()
+                .for_each(|c| c.entity = entity_map.get(&c.entity).cloned().unwrap());
+            new_spring_bone.tails.clear();
+
+            destination_world
+                .insert_one(*destination_entity, new_spring_bone)
+                .unwrap();
+        }
+
+        if let Some(humanoid) = source_entity.get::<&Humanoid>() {
+            let mut new_humanoid = (*humanoid).clone();
+            new_humanoid
+                .bones
+                .values_mut()
+                .for_each(|e| *e = entity_map.get(e).cloned().unwrap());
+
+            destination_world
+                .insert_one(*destination_entity, new_humanoid)
+                .unwrap();
+        }
+
+        if let Some(expressions) = source_entity.get::<&Expressions>() {
+            let mut new_expressions = (*expressions).clone();
+            new_expressions
+                .expressions
+                .iter_mut()
+                .flat_map(|expression| expression.morph_target_binds.iter_mut())
+                .for_each(|b| b.entity = entity_map.get(&b.entity).cloned().unwrap());
+
+            destination_world
+                .insert_one(*destination_entity, new_expressions)
+                .unwrap();
+        }
+
         if let Some(visible) = source_entity.get::<&Visible>() {
             destination_world
                 .insert_one(*destination_entity, *visible)
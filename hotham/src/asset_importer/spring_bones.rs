@@ -0,0 +1,238 @@
+// VRMC_springBone describes chains of joints that swing under their own momentum, for hair, tails and clothes:
+// https://github.com/vrm-c/vrm-specification/tree/master/specification/VRMC_springBone-1.0
+//
+// The `gltf` crate doesn't know about the extension, so it's read straight from the JSON.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use glam::Vec3;
+use hecs::Entity;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::components::{SpringBone, SpringBoneCollider};
+
+/// The contents of a `VRMC_springBone` extension
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct SpringBoneExtension {
+    colliders: Vec<ColliderDefinition>,
+    collider_groups: Vec<ColliderGroupDefinition>,
+    springs: Vec<SpringDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ColliderDefinition {
+    node: usize,
+    shape: ShapeDefinition,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ShapeDefinition {
+    Sphere {
+        #[serde(default)]
+        offset: [f32; 3],
+        #[serde(default)]
+        radius: f32,
+    },
+    Capsule {
+        #[serde(default)]
+        offset: [f32; 3],
+        #[serde(default)]
+        radius: f32,
+        #[serde(default)]
+        tail: [f32; 3],
+    },
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ColliderGroupDefinition {
+    colliders: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SpringDefinition {
+    joints: Vec<JointDefinition>,
+    collider_groups: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct JointDefinition {
+    node: usize,
+    hit_radius: f32,
+    stiffness: f32,
+    gravity_power: f32,
+    gravity_dir: [f32; 3],
+    drag_force: f32,
+}
+
+impl Default for JointDefinition {
+    fn default() -> Self {
+        Self {
+            node: 0,
+            hit_radius: 0.,
+            stiffness: 1.,
+            gravity_power: 0.,
+            gravity_dir: [0., -1., 0.],
+            drag_force: 0.5,
+        }
+    }
+}
+
+impl SpringBoneExtension {
+    /// Read the `VRMC_springBone` extension from the glTF `json`, if it has one.
+    pub fn from_json(json: &Value) -> Result<Option<Self>> {
+        json.get("extensions")
+            .and_then(|extensions| extensions.get("VRMC_springBone"))
+            .map(|extension| Ok(serde_json::from_value(extension.clone())?))
+            .transpose()
+    }
+
+    /// Build a [`SpringBone`] for each spring, using `node_entity_map` to find the entities for its nodes.
+    ///
+    /// VRM lets each joint in a spring have its own settings, but Hotham's are per chain, so each chain takes the
+    /// settings of its first joint.
+    pub fn spring_bones(&self, node_entity_map: &HashMap<usize, Entity>) -> Vec<SpringBone> {
+        self.springs
+            .iter()
+            .filter_map(|spring| {
+                let settings = spring.joints.first()?;
+                let joints = spring
+                    .joints
+                    .iter()
+                    .map(|joint| node_entity_map.get(&joint.node).copied())
+                    .collect::<Option<Vec<_>>>()?;
+
+                let colliders = spring
+                    .collider_groups
+                    .iter()
+                    .filter_map(|group| self.collider_groups.get(*group))
+                    .flat_map(|group| group.colliders.iter())
+                    .filter_map(|collider| self.colliders.get(*collider))
+                    .filter_map(|collider| {
+                        let entity = *node_entity_map.get(&collider.node)?;
+                        Some(collider.shape.spheres(entity))
+                    })
+                    .flatten()
+                    .collect();
+
+                Some(SpringBone {
+                    joints,
+                    stiffness: settings.stiffness,
+                    damping: settings.drag_force,
+                    gravity_power: settings.gravity_power,
+                    gravity_dir: settings.gravity_dir.into(),
+                    hit_radius: settings.hit_radius,
+                    colliders,
+                    tails: Vec::new(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ShapeDefinition {
+    /// Spring bones only collide with spheres, so capsules are filled with spheres along their length.
+    fn spheres(&self, entity: Entity) -> Vec<SpringBoneCollider> {
+        match *self {
+            ShapeDefinition::Sphere { offset, radius } => vec![SpringBoneCollider {
+                entity,
+                offset: offset.into(),
+                radius,
+            }],
+            ShapeDefinition::Capsule {
+                offset,
+                radius,
+                tail,
+            } => {
+                let (offset, tail) = (Vec3::from(offset), Vec3::from(tail));
+                let length = offset.distance(tail);
+                let segments = if radius > 0. {
+                    (length / radius).ceil().max(1.) as usize
+                } else {
+                    1
+                };
+                (0..=segments)
+                    .map(|i| SpringBoneCollider {
+                        entity,
+                        offset: offset.lerp(tail, i as f32 / segments as f32),
+                        radius,
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hecs::World;
+
+    #[test]
+    pub fn test_spring_bone_extension() {
+        let json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "extensions": {
+                "VRMC_springBone": {
+                    "specVersion": "1.0",
+                    "colliders": [
+                        { "node": 0, "shape": { "sphere": { "offset": [0, 0.1, 0], "radius": 0.2 } } },
+                        { "node": 0, "shape": { "capsule": { "radius": 0.5, "tail": [0, 1, 0] } } }
+                    ],
+                    "colliderGroups": [{ "colliders": [0, 1] }],
+                    "springs": [
+                        {
+                            "joints": [{ "node": 1, "stiffness": 2, "dragForce": 0.4 }, { "node": 2 }],
+                            "colliderGroups": [0]
+                        },
+                        { "joints": [{ "node": 7 }] }
+                    ]
+                }
+            }
+        });
+
+        let mut world = World::new();
+        let node_entity_map: HashMap<_, _> = (0..3).map(|node| (node, world.spawn(()))).collect();
+        let extension = SpringBoneExtension::from_json(&json).unwrap().unwrap();
+        let spring_bones = extension.spring_bones(&node_entity_map);
+
+        // The spring with a node we don't know about should be skipped.
+        assert_eq!(spring_bones.len(), 1);
+        let spring_bone = &spring_bones[0];
+        assert_eq!(
+            spring_bone.joints,
+            [node_entity_map[&1], node_entity_map[&2]]
+        );
+
+        // Settings come from the first joint, with VRM's defaults for anything that's missing.
+        assert_eq!(spring_bone.stiffness, 2.);
+        assert_eq!(spring_bone.damping, 0.4);
+        assert_eq!(spring_bone.gravity_power, 0.);
+        assert_eq!(spring_bone.gravity_dir, -Vec3::Y);
+
+        // The sphere comes through as it is, and the capsule is filled with spheres.
+        assert_eq!(
+            spring_bone.colliders[0],
+            SpringBoneCollider {
+                entity: node_entity_map[&0],
+                offset: Vec3::new(0., 0.1, 0.),
+                radius: 0.2,
+            }
+        );
+        let capsule = &spring_bone.colliders[1..];
+        assert_eq!(capsule.len(), 3);
+        assert_eq!(capsule[0].offset, Vec3::ZERO);
+        assert_eq!(capsule[1].offset, Vec3::new(0., 0.5, 0.));
+        assert_eq!(capsule[2].offset, Vec3::Y);
+
+        // Files without the extension are fine too.
+        let json = serde_json::json!({ "asset": { "version": "2.0" } });
+        assert!(SpringBoneExtension::from_json(&json).unwrap().is_none());
+    }
+}
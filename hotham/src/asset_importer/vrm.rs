@@ -0,0 +1,278 @@
+// VRM is a set of glTF extensions for avatars: `VRMC_vrm` maps the skeleton to a humanoid and describes facial
+// expressions, `VRMC_materials_mtoon` adds a toon shader and `VRMC_springBone` (see `spring_bones`) makes hair and
+// clothes swing:
+// https://github.com/vrm-c/vrm-specification/tree/master/specification/VRMC_vrm-1.0
+//
+// The `gltf` crate doesn't know about these extensions, so they're read straight from the JSON.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use glam::Vec4;
+use hecs::Entity;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    components::{
+        expressions::{Expression, MaterialColorBind, MorphTargetBind},
+        humanoid::HumanoidBone,
+        AnimatedProperty, Expressions, Humanoid,
+    },
+    rendering::material::Material,
+};
+
+/// The contents of a `VRMC_vrm` extension
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct VrmExtension {
+    humanoid: HumanoidDefinition,
+    expressions: ExpressionsDefinition,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct HumanoidDefinition {
+    human_bones: HashMap<String, HumanBoneDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct HumanBoneDefinition {
+    node: usize,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ExpressionsDefinition {
+    preset: BTreeMap<String, ExpressionDefinition>,
+    custom: BTreeMap<String, ExpressionDefinition>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ExpressionDefinition {
+    morph_target_binds: Vec<MorphTargetBindDefinition>,
+    material_color_binds: Vec<MaterialColorBindDefinition>,
+    is_binary: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MorphTargetBindDefinition {
+    node: usize,
+    index: usize,
+    weight: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialColorBindDefinition {
+    material: usize,
+    #[serde(rename = "type")]
+    color_type: String,
+    target_value: [f32; 4],
+}
+
+impl VrmExtension {
+    /// Read the `VRMC_vrm` extension from the glTF `json`, if it has one.
+    pub fn from_json(json: &Value) -> Result<Option<Self>> {
+        json.get("extensions")
+            .and_then(|extensions| extensions.get("VRMC_vrm"))
+            .map(|extension| Ok(serde_json::from_value(extension.clone())?))
+            .transpose()
+    }
+
+    /// The node of the avatar's hips, which every VRM avatar has
+    pub fn hips(&self) -> Option<usize> {
+        self.humanoid.human_bones.get("hips").map(|bone| bone.node)
+    }
+
+    /// Map the avatar's bones to their entities, using `node_entity_map`. Bones we don't know about are skipped.
+    pub fn humanoid(&self, node_entity_map: &HashMap<usize, Entity>) -> Humanoid {
+        let bones = self
+            .humanoid
+            .human_bones
+            .iter()
+            .filter_map(|(name, bone)| {
+                let bone_name = serde_json::from_value(Value::String(name.clone())).ok()?;
+                let entity = *node_entity_map.get(&bone.node)?;
+                Some((bone_name, entity))
+            })
+            .collect::<HashMap<HumanoidBone, Entity>>();
+        Humanoid { bones }
+    }
+
+    /// Build the avatar's expressions. `materials` are the glTF file's materials, so the expressions know what
+    /// colors to return to, and `material_buffer_offset` is where they start in the materials buffer.
+    ///
+    /// Only the `color` and `emissionColor` material binds can be shown, as Hotham's materials don't have MToon's
+    /// other colors.
+    pub fn expressions(
+        &self,
+        node_entity_map: &HashMap<usize, Entity>,
+        materials: &[Material],
+        material_buffer_offset: u32,
+    ) -> Expressions {
+        let definitions = self
+            .expressions
+            .preset
+            .iter()
+            .chain(self.expressions.custom.iter());
+
+        let expressions = definitions
+            .map(|(name, definition)| Expression {
+                name: name.clone(),
+                weight: 0.,
+                is_binary: definition.is_binary,
+                morph_target_binds: definition
+                    .morph_target_binds
+                    .iter()
+                    .filter_map(|bind| {
+                        Some(MorphTargetBind {
+                            entity: *node_entity_map.get(&bind.node)?,
+                            index: bind.index,
+                            weight: bind.weight,
+                        })
+                    })
+                    .collect(),
+                material_color_binds: definition
+                    .material_color_binds
+                    .iter()
+                    .filter_map(|bind| {
+                        let material = materials.get(bind.material)?;
+                        let material_id = bind.material as u32 + material_buffer_offset;
+                        let (property, base) = match bind.color_type.as_str() {
+                            "color" => (
+                                AnimatedProperty::BaseColorFactor(material_id),
+                                material.base_color_factor,
+                            ),
+                            "emissionColor" => (
+                                AnimatedProperty::EmissiveFactor(material_id),
+                                material.emissive_factor.extend(1.),
+                            ),
+                            _ => return None,
+                        };
+                        Some(MaterialColorBind {
+                            property,
+                            base,
+                            target: Vec4::from(bind.target_value),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Expressions { expressions }
+    }
+}
+
+/// Find the materials using the `VRMC_materials_mtoon` toon shader in the glTF `json`.
+pub(crate) fn mtoon_materials(json: &Value) -> HashSet<usize> {
+    json.get("materials")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter(|(_, material)| {
+            material
+                .get("extensions")
+                .and_then(|extensions| extensions.get("VRMC_materials_mtoon"))
+                .is_some()
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Hotham doesn't have a toon shader, so MToon materials are drawn as flat, diffuse materials. MToon keeps its base
+/// color and emission in the usual glTF properties, so those are already loaded.
+pub(crate) fn approximate_mtoon(material: &mut Material) {
+    material.metallic_factor = 0.;
+    material.roughness_factor = 1.;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hecs::World;
+
+    #[test]
+    pub fn test_vrm_extension() {
+        let json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "materials": [
+                { "name": "skin" },
+                { "name": "face", "extensions": { "VRMC_materials_mtoon": { "specVersion": "1.0" } } }
+            ],
+            "extensions": {
+                "VRMC_vrm": {
+                    "specVersion": "1.0",
+                    "humanoid": {
+                        "humanBones": {
+                            "hips": { "node": 0 },
+                            "leftThumbMetacarpal": { "node": 1 },
+                            "tail": { "node": 2 }
+                        }
+                    },
+                    "expressions": {
+                        "preset": {
+                            "blink": {
+                                "isBinary": true,
+                                "morphTargetBinds": [{ "node": 2, "index": 3, "weight": 1.0 }]
+                            }
+                        },
+                        "custom": {
+                            "blush": {
+                                "materialColorBinds": [
+                                    { "material": 1, "type": "color", "targetValue": [1, 0, 0, 1] },
+                                    { "material": 1, "type": "rimColor", "targetValue": [1, 0, 0, 1] }
+                                ]
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut world = World::new();
+        let node_entity_map: HashMap<_, _> = (0..3).map(|node| (node, world.spawn(()))).collect();
+        let extension = VrmExtension::from_json(&json).unwrap().unwrap();
+        assert_eq!(extension.hips(), Some(0));
+
+        // Bones that aren't part of the humanoid are skipped.
+        let humanoid = extension.humanoid(&node_entity_map);
+        assert_eq!(humanoid.bones.len(), 2);
+        assert_eq!(humanoid.get(HumanoidBone::Hips), Some(node_entity_map[&0]));
+        assert_eq!(
+            humanoid.get(HumanoidBone::LeftThumbMetacarpal),
+            Some(node_entity_map[&1])
+        );
+        assert_eq!(humanoid.get(HumanoidBone::Head), None);
+
+        let materials = [Material::default(), Material::default()];
+        let expressions = extension.expressions(&node_entity_map, &materials, 10);
+        let blink = &expressions.expressions[0];
+        assert_eq!(blink.name, "blink");
+        assert!(blink.is_binary);
+        assert_eq!(
+            blink.morph_target_binds,
+            [MorphTargetBind {
+                entity: node_entity_map[&2],
+                index: 3,
+                weight: 1.,
+            }]
+        );
+
+        // Colors Hotham's materials don't have are skipped.
+        let blush = &expressions.expressions[1];
+        assert_eq!(blush.name, "blush");
+        assert_eq!(
+            blush.material_color_binds,
+            [MaterialColorBind {
+                property: AnimatedProperty::BaseColorFactor(11),
+                base: materials[1].base_color_factor,
+                target: Vec4::new(1., 0., 0., 1.),
+            }]
+        );
+
+        assert_eq!(mtoon_materials(&json), vec![1].into_iter().collect());
+    }
+}
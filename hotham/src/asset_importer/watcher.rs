@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    net::{Ipv4Addr, TcpListener},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use crossbeam::channel::{Receiver, Sender};
+use hecs::World;
+use id_arena::{Arena, Id};
+
+use crate::{
+    components::{Info, Mesh, Skin},
+    contexts::{RenderContext, VulkanContext},
+    rendering::mesh_data::MeshData,
+};
+
+use super::{load_models_from_glb, Models};
+
+/// The port [`AssetWatcher::listen`] is usually given. `hotham push-assets` sends to it by default.
+pub const DEFAULT_ASSET_PORT: u16 = 7879;
+
+/// How long a file has to go unchanged before it's reloaded, so we don't load one that's still being written.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// How often files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The largest file that will be accepted over the network, so a bad connection can't use up the headset's memory.
+const MAX_ASSET_SIZE: u64 = 512 * 1024 * 1024;
+
+/// The longest file name that will be accepted over the network, in bytes.
+const MAX_NAME_LENGTH: u32 = 4 * 1024;
+
+/// Reloads GLB files when they change, patching the running `World` so artists can see their changes without
+/// rebuilding the app.
+///
+/// On desktop, point the watcher at the files the app loaded:
+/// ```ignore
+/// let mut watcher = AssetWatcher::watch(vec!["assets/level.glb", "assets/crab.glb"]);
+/// while let Ok(tick_data) = engine.update() {
+///     watcher.update(&mut engine.world, &engine.vulkan_context, &mut engine.render_context);
+///     ..
+/// }
+/// ```
+///
+/// On a headset, use [`AssetWatcher::listen`] instead, then push changed files from your computer with
+/// `hotham push-assets assets/level.glb assets/crab.glb`, which forwards the port over ADB.
+///
+/// Each reloaded file is imported again, and every entity in the `World` whose [`Info`] name matches a node in the
+/// file has its [`Mesh`]'s data replaced in place, so the new geometry and materials show up everywhere the mesh is
+/// used, including models that haven't been spawned yet. [`Skin`]s get their new inverse bind matrices. Transforms
+/// and everything else are left alone.
+///
+/// This is only meant for iterating on art: every reload adds to the vertex and material buffers, and nothing is
+/// freed until the app restarts.
+pub struct AssetWatcher {
+    source: Source,
+}
+
+/// Where changed files come from
+enum Source {
+    Files(FileWatch),
+    Remote(Receiver<(String, Vec<u8>)>),
+}
+
+impl AssetWatcher {
+    /// Watch the GLB files at `paths` for changes.
+    pub fn watch<P: Into<PathBuf>>(paths: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            source: Source::Files(FileWatch::new(paths.into_iter().map(Into::into).collect())),
+        }
+    }
+
+    /// Accept changed GLB files sent to `port` by `hotham push-assets`, for iterating on a headset. Only connections
+    /// from the device itself are accepted, so files have to come through `adb forward`.
+    pub fn listen(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let (sender, receiver) = crossbeam::channel::unbounded();
+
+        thread::Builder::new()
+            .name("hotham_asset_watcher".to_string())
+            .spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    if !receive_assets(&mut stream, &sender) {
+                        // The watcher was dropped, so nobody wants these any more.
+                        return;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            source: Source::Remote(receiver),
+        })
+    }
+
+    /// Reload any files that have changed, and patch the entities in `world` that use them. Call this once per frame.
+    ///
+    /// Returns the names of the files that were reloaded. Files that can't be reloaded are logged and skipped, so a
+    /// bad export never takes the app down.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        vulkan_context: &VulkanContext,
+        render_context: &mut RenderContext,
+    ) -> Vec<String> {
+        let changed = match &mut self.source {
+            Source::Files(file_watch) => file_watch
+                .changed()
+                .into_iter()
+                .filter_map(|path| match fs::read(&path) {
+                    Ok(bytes) => Some((path.display().to_string(), bytes)),
+                    Err(e) => {
+                        log::error!(
+                            "[HOTHAM_ASSET_WATCHER] Unable to read {}: {}",
+                            path.display(),
+                            e
+                        );
+                        None
+                    }
+                })
+                .collect(),
+            Source::Remote(receiver) => receiver.try_iter().collect::<Vec<_>>(),
+        };
+
+        let mut reloaded = Vec::new();
+        for (name, bytes) in changed {
+            match reload(&bytes, world, vulkan_context, render_context) {
+                Ok(patched) => {
+                    log::info!(
+                        "[HOTHAM_ASSET_WATCHER] Reloaded {}, updating {} entities",
+                        name,
+                        patched
+                    );
+                    reloaded.push(name);
+                }
+                Err(e) => log::error!("[HOTHAM_ASSET_WATCHER] Unable to reload {}: {}", name, e),
+            }
+        }
+        reloaded
+    }
+}
+
+/// Polls files for changes
+struct FileWatch {
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+    last_checked: Instant,
+}
+
+impl FileWatch {
+    fn new(paths: Vec<PathBuf>) -> Self {
+        // Files are only reloaded once they've changed from what the app loaded to begin with.
+        let modified = paths
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect();
+        Self {
+            modified,
+            last_checked: Instant::now(),
+        }
+    }
+
+    /// The files that have changed, and settled, since the last call.
+    fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_checked.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_checked = Instant::now();
+
+        let mut changed = Vec::new();
+        for (path, last_modified) in self.modified.iter_mut() {
+            // The file may briefly disappear while it's being exported.
+            let modified = match modified(path) {
+                Some(modified) => modified,
+                None => continue,
+            };
+            let has_settled = match modified.elapsed() {
+                Ok(age) => age >= SETTLE_TIME,
+                Err(_) => true,
+            };
+            if has_settled && *last_modified != Some(modified) {
+                *last_modified = Some(modified);
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Import `glb_buffer` and patch the entities in `world` that use it. Returns how many entities were patched.
+fn reload(
+    glb_buffer: &[u8],
+    world: &mut World,
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+) -> Result<usize> {
+    // The importer expects a valid file, so catch anything that obviously isn't one before it gets there.
+    gltf::Glb::from_slice(glb_buffer).map_err(|e| anyhow!("it isn't a valid GLB file: {}", e))?;
+    let models = load_models_from_glb(&[glb_buffer], vulkan_context, render_context)?;
+    Ok(patch_world(
+        world,
+        &models,
+        &mut render_context.resources.mesh_data,
+    ))
+}
+
+/// Give the entities in `world` the meshes and skins of the nodes in `models` with the same names.
+fn patch_world(world: &mut World, models: &Models, mesh_data: &mut Arena<MeshData>) -> usize {
+    let mut meshes = HashMap::new();
+    let mut skins = HashMap::new();
+    for model in models.values() {
+        for (_, (info, mesh)) in model.query::<(&Info, &Mesh)>().iter() {
+            meshes.insert(info.name.clone(), mesh.handle);
+        }
+        for (_, (info, skin)) in model.query::<(&Info, &Skin)>().iter() {
+            skins.insert(info.name.clone(), skin.inverse_bind_matrices.clone());
+        }
+    }
+
+    let mut patched = 0;
+    for (_, (info, mesh, skin)) in world.query_mut::<(&Info, Option<&Mesh>, Option<&mut Skin>)>() {
+        let mut was_patched = false;
+        if let (Some(mesh), Some(&new_handle)) = (mesh, meshes.get(&info.name)) {
+            if mesh.handle != new_handle {
+                patch_mesh(mesh_data, mesh.handle, new_handle);
+                was_patched = true;
+            }
+        }
+        if let (Some(skin), Some(inverse_bind_matrices)) = (skin, skins.get(&info.name)) {
+            // The joints are entities in the running world, so only a skin with the same joints can be patched.
+            if skin.joints.len() == inverse_bind_matrices.len() {
+                skin.inverse_bind_matrices = inverse_bind_matrices.clone();
+                was_patched = true;
+            }
+        }
+        if was_patched {
+            patched += 1;
+        }
+    }
+    patched
+}
+
+fn patch_mesh(mesh_data: &mut Arena<MeshData>, old: Id<MeshData>, new: Id<MeshData>) {
+    let new_data = mesh_data[new].clone();
+    mesh_data[old] = new_data;
+}
+
+/// Read files sent by `hotham push-assets` from `stream` until it closes. Returns `false` if nobody is listening for
+/// them any more.
+fn receive_assets(stream: &mut impl Read, sender: &Sender<(String, Vec<u8>)>) -> bool {
+    loop {
+        match read_asset(stream) {
+            Ok(asset) => {
+                if sender.send(asset).is_err() {
+                    return false;
+                }
+            }
+            Err(e) => {
+                if e.kind() != io::ErrorKind::UnexpectedEof {
+                    log::error!("[HOTHAM_ASSET_WATCHER] Error receiving assets: {}", e);
+                }
+                return true;
+            }
+        }
+    }
+}
+
+/// Read a file written by [`write_asset`]: the length of its name and the name, then the length of the file and the
+/// file, with lengths as little endian `u32`s and `u64`s.
+fn read_asset(stream: &mut impl Read) -> io::Result<(String, Vec<u8>)> {
+    let mut name_length = [0; 4];
+    stream.read_exact(&mut name_length)?;
+    let name_length = u32::from_le_bytes(name_length);
+    if name_length > MAX_NAME_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("A file name {} bytes long is too long", name_length),
+        ));
+    }
+    let mut name = vec![0; name_length as usize];
+    stream.read_exact(&mut name)?;
+    let name =
+        String::from_utf8(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut length = [0; 8];
+    stream.read_exact(&mut length)?;
+    let length = u64::from_le_bytes(length);
+    if length > MAX_ASSET_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is too big", name),
+        ));
+    }
+    let mut bytes = vec![0; length as usize];
+    stream.read_exact(&mut bytes)?;
+    Ok((name, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::primitive::Primitive;
+    use std::io::Write;
+
+    /// Write a file the way `hotham push-assets` does.
+    fn write_asset(stream: &mut impl Write, name: &str, bytes: &[u8]) -> io::Result<()> {
+        stream.write_all(&(name.len() as u32).to_le_bytes())?;
+        stream.write_all(name.as_bytes())?;
+        stream.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        stream.write_all(bytes)
+    }
+
+    #[test]
+    pub fn test_receive_assets() {
+        let mut stream = Vec::new();
+        write_asset(&mut stream, "crab.glb", b"glTF crab").unwrap();
+        write_asset(&mut stream, "level.glb", b"glTF level").unwrap();
+
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        assert!(receive_assets(&mut stream.as_slice(), &sender));
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                ("crab.glb".to_string(), b"glTF crab".to_vec()),
+                ("level.glb".to_string(), b"glTF level".to_vec()),
+            ]
+        );
+
+        // Names that are too long are refused before anything is allocated for them.
+        let mut stream = u32::MAX.to_le_bytes().to_vec();
+        stream.extend_from_slice(b"crab.glb");
+        assert_eq!(
+            read_asset(&mut stream.as_slice()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    pub fn test_patch_world() {
+        let mut mesh_data = Arena::new();
+        let primitive = |indices_count| Primitive {
+            indices_count,
+            ..Default::default()
+        };
+        let old_crab = mesh_data.alloc(MeshData::new(vec![primitive(3)]));
+        let new_crab = mesh_data.alloc(MeshData::new(vec![primitive(6)]));
+        let rock = mesh_data.alloc(MeshData::new(vec![primitive(9)]));
+        let info = |name: &str| Info {
+            name: name.to_string(),
+            node_id: 0,
+        };
+
+        let mut world = World::new();
+        world.spawn((info("Crab"), Mesh { handle: old_crab }));
+        world.spawn((info("Rock"), Mesh { handle: rock }));
+
+        let mut model = World::new();
+        model.spawn((info("Crab"), Mesh { handle: new_crab }));
+        let models = Models::from([("Crab".to_string(), model)]);
+
+        // The crab is patched in place, and the rock isn't in the file so it's left alone.
+        assert_eq!(patch_world(&mut world, &models, &mut mesh_data), 1);
+        assert_eq!(mesh_data[old_crab].primitives[0].indices_count, 6);
+        assert_eq!(mesh_data[rock].primitives[0].indices_count, 9);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+
+/// Roughly how fast captions can be read, in characters per second
+const READING_SPEED: f32 = 15.;
+/// The shortest time a caption is shown for, however short it is
+const MIN_CAPTION_DURATION: f32 = 1.5;
+
+/// Subtitles for dialogue and other important sounds, shown one after another on a
+/// [`crate::components::CaptionPanel`].
+///
+/// Queue captions with [`Captions::push`], or give a voice over's [`crate::components::SoundEmitter`] a caption with
+/// [`crate::components::SoundEmitter::with_caption`] and `audio_system` will queue it whenever the clip starts
+/// playing:
+/// ```ignore
+/// engine.captions.push(Caption::new("Over here!", 2.).with_speaker("Guide"));
+/// ```
+///
+/// Captions are always shown for long enough to be read, even if that's longer than they were asked to be.
+pub struct Captions {
+    /// Should captions be shown? When they're turned off, queued captions are dropped.
+    pub enabled: bool,
+    queue: VecDeque<Caption>,
+    shown_for: f32,
+}
+
+/// A line of dialogue, or a description of a sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caption {
+    /// Who's speaking, if anyone
+    pub speaker: Option<String>,
+    /// What they're saying
+    pub text: String,
+    /// How long to show the caption for, in seconds
+    pub duration: f32,
+}
+
+impl Default for Captions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            queue: VecDeque::new(),
+            shown_for: 0.,
+        }
+    }
+}
+
+impl Captions {
+    /// Show `caption` after the ones already queued.
+    pub fn push(&mut self, caption: Caption) {
+        if self.enabled {
+            self.queue.push_back(caption);
+        }
+    }
+
+    /// Stop showing the current caption, and drop any that are queued.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.shown_for = 0.;
+    }
+
+    /// The caption being shown
+    pub fn current(&self) -> Option<&Caption> {
+        self.queue.front()
+    }
+
+    /// How many captions are waiting to be shown, including the current one
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Are there no captions to show?
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Move on to the next caption once the current one has been shown for long enough.
+    pub fn update(&mut self, delta_time: f32) {
+        if !self.enabled {
+            self.clear();
+            return;
+        }
+
+        self.shown_for += delta_time;
+        while let Some(caption) = self.queue.front() {
+            let duration = caption.readable_duration();
+            if self.shown_for < duration {
+                break;
+            }
+            self.shown_for -= duration;
+            self.queue.pop_front();
+        }
+        if self.queue.is_empty() {
+            self.shown_for = 0.;
+        }
+    }
+
+    /// The text to show for the current caption, with the speaker's name in front.
+    pub fn text(&self) -> Option<String> {
+        self.current().map(|caption| match &caption.speaker {
+            Some(speaker) => format!("{}: {}", speaker, caption.text),
+            None => caption.text.clone(),
+        })
+    }
+}
+
+impl Caption {
+    /// Create a caption that's shown for `duration` seconds
+    pub fn new(text: impl Into<String>, duration: f32) -> Self {
+        Self {
+            speaker: None,
+            text: text.into(),
+            duration,
+        }
+    }
+
+    /// Say who's speaking
+    pub fn with_speaker(mut self, speaker: impl Into<String>) -> Self {
+        self.speaker = Some(speaker.into());
+        self
+    }
+
+    /// How long the caption is actually shown for: its duration, or long enough to read it, whichever is longer.
+    pub fn readable_duration(&self) -> f32 {
+        let characters = self.text.chars().count() as f32;
+        self.duration
+            .max(characters / READING_SPEED)
+            .max(MIN_CAPTION_DURATION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_captions() {
+        let mut captions = Captions::default();
+        captions.push(Caption::new("Over here!", 2.).with_speaker("Guide"));
+        captions.push(Caption::new("[door creaks]", 0.1));
+        assert_eq!(captions.text().unwrap(), "Guide: Over here!");
+
+        // The first caption is shown for as long as it asked..
+        captions.update(1.9);
+        assert_eq!(captions.len(), 2);
+        captions.update(0.2);
+        assert_eq!(captions.text().unwrap(), "[door creaks]");
+
+        // ..but the second is too short to read, so it's shown for longer.
+        captions.update(1.);
+        assert_eq!(captions.len(), 1);
+        captions.update(1.);
+        assert!(captions.is_empty());
+        assert_eq!(captions.text(), None);
+
+        // Nothing is queued while captions are turned off.
+        captions.enabled = false;
+        captions.push(Caption::new("Hello?", 1.));
+        assert!(captions.is_empty());
+    }
+}
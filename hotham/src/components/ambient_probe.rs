@@ -0,0 +1,9 @@
+/// Component that lights an entity's meshes with the level's baked ambient light probes, rather than the irradiance
+/// map, so it picks up the light around it as it moves - eg. darker under a table, or tinted by a nearby red wall.
+///
+/// The probes are blended at the entity's position each frame, so this is meant for things that move. Has no effect
+/// until [`crate::contexts::RenderContext::light_probes`] has been set.
+///
+/// Requires `rendering_system`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AmbientProbe;
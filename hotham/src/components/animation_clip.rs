@@ -0,0 +1,191 @@
+use glam::{Quat, Vec3};
+use gltf::animation::{util::ReadOutputs, Interpolation};
+use hecs::Entity;
+
+use crate::asset_importer::ImportContext;
+
+/// A single animation from a glTF file, eg. "Walk" or "Wave", that can be played with an
+/// [`super::AnimationController`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    /// The animation's name, if it has one
+    pub name: Option<String>,
+    /// How long the animation lasts, in seconds
+    pub duration: f32,
+    /// The nodes the animation moves, and how
+    pub channels: Vec<AnimationChannel>,
+}
+
+/// Keyframes for one property of one node in an [`AnimationClip`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationChannel {
+    /// The entity that is affected by this channel
+    pub target: Entity,
+    /// The time of each keyframe, in seconds
+    pub times: Vec<f32>,
+    /// The value at each keyframe
+    pub values: ChannelValues,
+    /// If true, values jump from one keyframe to the next instead of being interpolated
+    pub step: bool,
+}
+
+/// The values of an [`AnimationChannel`]'s keyframes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelValues {
+    /// Translations
+    Translations(Vec<Vec3>),
+    /// Rotations
+    Rotations(Vec<Quat>),
+    /// Scales
+    Scales(Vec<Vec3>),
+}
+
+/// The value of an [`AnimationChannel`] at a particular time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelValue {
+    /// A translation
+    Translation(Vec3),
+    /// A rotation
+    Rotation(Quat),
+    /// A scale
+    Scale(Vec3),
+}
+
+impl AnimationClip {
+    pub(crate) fn load(
+        animation: gltf::Animation,
+        import_context: &ImportContext,
+    ) -> Option<AnimationClip> {
+        let node_entity_map = &import_context.node_entity_map;
+        let buffer = &import_context.buffer;
+
+        let mut channels = Vec::new();
+        for channel in animation.channels() {
+            let target = match node_entity_map.get(&channel.target().node().index()) {
+                Some(target) => *target,
+                None => continue,
+            };
+            let interpolation = channel.sampler().interpolation();
+            let reader = channel.reader(|_| Some(buffer));
+            let times = match reader.read_inputs() {
+                Some(times) => times.collect::<Vec<_>>(),
+                None => continue,
+            };
+            let values = match reader.read_outputs() {
+                Some(ReadOutputs::Translations(t)) => {
+                    ChannelValues::Translations(spline_values(t.map(Vec3::from), interpolation))
+                }
+                Some(ReadOutputs::Rotations(r)) => ChannelValues::Rotations(spline_values(
+                    r.into_f32().map(Quat::from_array),
+                    interpolation,
+                )),
+                Some(ReadOutputs::Scales(s)) => {
+                    ChannelValues::Scales(spline_values(s.map(Vec3::from), interpolation))
+                }
+                _ => continue,
+            };
+            channels.push(AnimationChannel {
+                target,
+                times,
+                values,
+                step: interpolation == Interpolation::Step,
+            });
+        }
+
+        if channels.is_empty() {
+            return None;
+        }
+
+        Some(AnimationClip {
+            name: animation.name().map(ToString::to_string),
+            duration: channels
+                .iter()
+                .filter_map(|c| c.times.last().copied())
+                .fold(0., f32::max),
+            channels,
+        })
+    }
+}
+
+/// Cubic spline samplers store an in-tangent, value and out-tangent for each keyframe, and we only want the value.
+fn spline_values<T>(values: impl Iterator<Item = T>, interpolation: Interpolation) -> Vec<T> {
+    if interpolation == Interpolation::CubicSpline {
+        values.skip(1).step_by(3).collect()
+    } else {
+        values.collect()
+    }
+}
+
+impl AnimationChannel {
+    /// Get the channel's value at `time`, in seconds. Times outside the channel's keyframes are clamped.
+    pub fn sample(&self, time: f32) -> Option<ChannelValue> {
+        let (from, to, amount) = self.keyframes_at(time)?;
+        let value = match &self.values {
+            ChannelValues::Translations(t) => {
+                ChannelValue::Translation(t.get(from)?.lerp(*t.get(to)?, amount))
+            }
+            ChannelValues::Rotations(r) => {
+                ChannelValue::Rotation(r.get(from)?.slerp(*r.get(to)?, amount))
+            }
+            ChannelValues::Scales(s) => ChannelValue::Scale(s.get(from)?.lerp(*s.get(to)?, amount)),
+        };
+        Some(value)
+    }
+
+    /// The keyframes either side of `time`, and how far between them it is.
+    fn keyframes_at(&self, time: f32) -> Option<(usize, usize, f32)> {
+        let last = self.times.len().checked_sub(1)?;
+        let next = self.times.partition_point(|t| *t <= time);
+        if next == 0 {
+            return Some((0, 0, 0.));
+        }
+        if next > last {
+            return Some((last, last, 0.));
+        }
+
+        let from = next - 1;
+        if self.step {
+            return Some((from, from, 0.));
+        }
+        let span = self.times[next] - self.times[from];
+        let amount = if span > 0. {
+            (time - self.times[from]) / span
+        } else {
+            0.
+        };
+        Some((from, next, amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_sample_channel() {
+        let mut world = hecs::World::new();
+        let mut channel = AnimationChannel {
+            target: world.spawn(()),
+            times: vec![0., 1., 3.],
+            values: ChannelValues::Translations(vec![Vec3::ZERO, Vec3::X, Vec3::Y]),
+            step: false,
+        };
+
+        let translation = |channel: &AnimationChannel, time| match channel.sample(time) {
+            Some(ChannelValue::Translation(t)) => t,
+            _ => panic!("Expected a translation"),
+        };
+
+        assert_relative_eq!(translation(&channel, 0.5), Vec3::X * 0.5);
+        assert_relative_eq!(translation(&channel, 2.), Vec3::new(0.5, 0.5, 0.));
+
+        // Times outside the keyframes are clamped..
+        assert_relative_eq!(translation(&channel, -1.), Vec3::ZERO);
+        assert_relative_eq!(translation(&channel, 10.), Vec3::Y);
+
+        // ..and step channels don't interpolate.
+        channel.step = true;
+        assert_relative_eq!(translation(&channel, 2.), Vec3::X);
+    }
+}
@@ -1,15 +1,28 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use gltf::animation::util::ReadOutputs;
+use gltf::{
+    accessor::{DataType, Dimensions, Iter},
+    animation::{util::ReadOutputs, Interpolation},
+};
+use hecs::{Entity, World};
 use itertools::Itertools;
 
-use crate::{asset_importer::ImportContext, components::AnimationTarget};
-use glam::Quat;
+use crate::{
+    asset_importer::{animation_pointer::PointerTarget, ImportContext},
+    components::{
+        AnimatedProperty, AnimationClip, AnimationTarget, Parent, PropertyAnimationTarget,
+    },
+};
+use glam::{Quat, Vec4};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 
 /// Component that controls how an `AnimationTarget` should be animated.
 /// Added by `gltf_loader` to the root node if its children contain animation data.
+///
+/// By default, `animation_system` blends between two keyframes with `blend_from`, `blend_to` and `blend_amount`.
+/// Once a clip has been started with [`AnimationController::play`], [`AnimationController::cross_fade`] or
+/// [`AnimationController::set_weight`], the controller's `clips` are played instead, in `layers`.
 pub struct AnimationController {
     /// The amount to blend from
     pub blend_from: usize,
@@ -19,6 +32,41 @@ pub struct AnimationController {
     pub blend_amount: f32,
     /// The targets to apply this animation to
     pub targets: Vec<AnimationTarget>,
+    /// The material and light properties to apply this animation to
+    pub property_targets: Vec<PropertyAnimationTarget>,
+    /// Each of the file's animations
+    pub clips: Vec<AnimationClip>,
+    /// The layers of clips being played. The first layer is the base layer, and each layer after it overrides the
+    /// ones before it.
+    pub layers: Vec<AnimationLayer>,
+}
+
+/// A layer of clips played by an [`AnimationController`], eg. waving on top of walking.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationLayer {
+    /// How much this layer overrides the layers before it, from 0 to 1
+    pub weight: f32,
+    /// The entities this layer animates, eg. from [`AnimationLayer::mask_below`]. `None` animates every entity.
+    pub mask: Option<HashSet<Entity>>,
+    /// The clips playing on this layer, which are blended by their weights
+    pub playing: Vec<ClipPlayback>,
+}
+
+/// A clip being played on an [`AnimationLayer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipPlayback {
+    /// The index of the clip in [`AnimationController::clips`]
+    pub clip: usize,
+    /// How far through the clip we are, in seconds
+    pub time: f32,
+    /// How fast the clip plays. 1 is normal speed.
+    pub speed: f32,
+    /// Whether the clip starts again when it finishes, or holds its last frame
+    pub looping: bool,
+    /// How much the clip contributes to its layer
+    pub weight: f32,
+    target_weight: f32,
+    fade_rate: f32,
 }
 
 impl AnimationController {
@@ -73,6 +121,343 @@ impl AnimationController {
             blend_to: 1,
             blend_amount: 0.,
             targets: targets.drain().map(|n| n.1).collect_vec(),
+            property_targets: load_property_targets(import_context),
+            clips: import_context
+                .document
+                .animations()
+                .filter_map(|a| AnimationClip::load(a, import_context))
+                .collect(),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Find a clip by its name.
+    pub fn clip(&self, name: &str) -> Option<usize> {
+        self.clips
+            .iter()
+            .position(|c| c.name.as_deref() == Some(name))
+    }
+
+    /// Switch straight to `clip` on the base layer.
+    pub fn play(&mut self, clip: usize) {
+        self.cross_fade(clip, 0.);
+    }
+
+    /// Fade from whatever is playing on the base layer to `clip`, over `duration` seconds.
+    pub fn cross_fade(&mut self, clip: usize, duration: f32) {
+        self.cross_fade_layer(0, clip, duration);
+    }
+
+    /// Fade from whatever is playing on `layer` to `clip`, over `duration` seconds. If `clip` is already playing,
+    /// it carries on from where it is.
+    pub fn cross_fade_layer(&mut self, layer: usize, clip: usize, duration: f32) {
+        let layer = self.layer_mut(layer);
+        if !layer.playing.iter().any(|p| p.clip == clip) {
+            layer.playing.push(ClipPlayback::new(clip, 0.));
+        }
+        for playback in &mut layer.playing {
+            let target_weight = if playback.clip == clip { 1. } else { 0. };
+            playback.fade_to(target_weight, duration);
+        }
+        layer
+            .playing
+            .retain(|p| p.weight > 0. || p.target_weight > 0.);
+    }
+
+    /// Set how much `clip` contributes to `layer`, to blend it with the layer's other clips. A weight of 0 stops it.
+    pub fn set_weight(&mut self, layer: usize, clip: usize, weight: f32) {
+        let layer = self.layer_mut(layer);
+        match layer.playing.iter_mut().find(|p| p.clip == clip) {
+            Some(playback) => playback.fade_to(weight, 0.),
+            None => layer.playing.push(ClipPlayback::new(clip, weight)),
+        }
+        layer
+            .playing
+            .retain(|p| p.weight > 0. || p.target_weight > 0.);
+    }
+
+    /// Add a layer that overrides the ones before it by `weight`, optionally only for the entities in `mask`.
+    /// Returns the layer's index.
+    pub fn add_layer(&mut self, mask: Option<HashSet<Entity>>, weight: f32) -> usize {
+        // Make sure the base layer always comes first.
+        self.layer_mut(0);
+        self.layers.push(AnimationLayer {
+            weight,
+            mask,
+            playing: Vec::new(),
+        });
+        self.layers.len() - 1
+    }
+
+    /// Stop every clip on every layer, and go back to blending between keyframes.
+    pub fn stop(&mut self) {
+        self.layers.iter_mut().for_each(|l| l.playing.clear());
+    }
+
+    /// Whether any clips are being played.
+    pub fn is_playing_clips(&self) -> bool {
+        self.layers.iter().any(|l| !l.playing.is_empty())
+    }
+
+    /// Move every clip and fade forward by `delta_time` seconds.
+    pub(crate) fn advance(&mut self, delta_time: f32) {
+        let clips = &self.clips;
+        for layer in &mut self.layers {
+            for playback in &mut layer.playing {
+                let duration = clips.get(playback.clip).map_or(0., |c| c.duration);
+                playback.advance(delta_time, duration);
+            }
+            layer
+                .playing
+                .retain(|p| p.weight > 0. || p.target_weight > 0.);
+        }
+    }
+
+    fn layer_mut(&mut self, layer: usize) -> &mut AnimationLayer {
+        if self.layers.is_empty() {
+            self.layers.push(AnimationLayer {
+                weight: 1.,
+                mask: None,
+                playing: Vec::new(),
+            });
+        }
+        &mut self.layers[layer]
+    }
+}
+
+impl AnimationLayer {
+    /// A mask with `bone` and everything below it, eg. the spine for an upper body layer.
+    pub fn mask_below(world: &World, bone: Entity) -> HashSet<Entity> {
+        let mut mask = HashSet::from([bone]);
+        let mut parents = world.query::<&Parent>();
+        let parents = parents.iter().collect_vec();
+        loop {
+            let before = mask.len();
+            for (entity, parent) in &parents {
+                if mask.contains(&parent.0) {
+                    mask.insert(*entity);
+                }
+            }
+            if mask.len() == before {
+                return mask;
+            }
+        }
+    }
+
+    /// Whether this layer animates `entity`.
+    pub fn masks(&self, entity: Entity) -> bool {
+        self.mask.as_ref().map_or(true, |m| m.contains(&entity))
+    }
+}
+
+impl ClipPlayback {
+    fn new(clip: usize, weight: f32) -> Self {
+        Self {
+            clip,
+            time: 0.,
+            speed: 1.,
+            looping: true,
+            weight,
+            target_weight: weight,
+            fade_rate: 0.,
+        }
+    }
+
+    fn fade_to(&mut self, target_weight: f32, duration: f32) {
+        self.target_weight = target_weight;
+        if duration > 0. {
+            self.fade_rate = (target_weight - self.weight).abs() / duration;
+        } else {
+            self.weight = target_weight;
+        }
+    }
+
+    fn advance(&mut self, delta_time: f32, duration: f32) {
+        self.time += delta_time * self.speed;
+        if self.looping && duration > 0. {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.clamp(0., duration);
+        }
+
+        let step = self.fade_rate * delta_time;
+        if (self.target_weight - self.weight).abs() <= step {
+            self.weight = self.target_weight;
+        } else {
+            self.weight += step.copysign(self.target_weight - self.weight);
+        }
+    }
+}
+
+/// Load the animations that target material and light properties with `KHR_animation_pointer`.
+fn load_property_targets(import_context: &ImportContext) -> Vec<PropertyAnimationTarget> {
+    let document = &import_context.document;
+    let buffer = &import_context.buffer;
+
+    // The scene's lights are numbered in the order they're found, and the same light can be used more than once.
+    let scene_lights = document
+        .default_scene()
+        .into_iter()
+        .flat_map(|scene| scene.nodes())
+        .filter_map(|node| node.light().map(|light| light.index()))
+        .collect_vec();
+    let material_id = |material: usize| material as u32 + import_context.material_buffer_offset;
+
+    let mut property_targets = Vec::new();
+    for channel in &import_context.pointer_channels {
+        let target = match PointerTarget::parse(&channel.pointer) {
+            Some(target) => target,
+            None => {
+                println!(
+                    "[HOTHAM_ANIMATION] Animating {} isn't supported, ignoring",
+                    channel.pointer
+                );
+                continue;
+            }
+        };
+        let sampler = match document
+            .animations()
+            .nth(channel.animation)
+            .and_then(|animation| animation.samplers().nth(channel.sampler))
+        {
+            Some(sampler) => sampler,
+            None => continue,
+        };
+        let values = match read_values(sampler.output(), buffer) {
+            Some(values) => values,
+            None => {
+                println!(
+                    "[HOTHAM_ANIMATION] Unable to read values for {}, ignoring",
+                    channel.pointer
+                );
+                continue;
+            }
+        };
+
+        // Cubic spline samplers store an in-tangent, value and out-tangent for each keyframe, and we only want the value.
+        let values = if sampler.interpolation() == Interpolation::CubicSpline {
+            values.into_iter().skip(1).step_by(3).collect()
+        } else {
+            values
+        };
+
+        let properties = match target {
+            PointerTarget::BaseColorFactor { material } => {
+                vec![AnimatedProperty::BaseColorFactor(material_id(material))]
+            }
+            PointerTarget::EmissiveFactor { material } => {
+                vec![AnimatedProperty::EmissiveFactor(material_id(material))]
+            }
+            PointerTarget::LightColor { light } => scene_lights
+                .iter()
+                .positions(|l| *l == light)
+                .map(AnimatedProperty::LightColor)
+                .collect(),
+            PointerTarget::LightIntensity { light } => scene_lights
+                .iter()
+                .positions(|l| *l == light)
+                .map(AnimatedProperty::LightIntensity)
+                .collect(),
+        };
+
+        property_targets.extend(
+            properties
+                .into_iter()
+                .map(|property| PropertyAnimationTarget {
+                    property,
+                    values: values.clone(),
+                }),
+        );
+    }
+
+    property_targets
+}
+
+/// Read a sampler's output as `Vec4`s. Only floating point scalars, `VEC3`s and `VEC4`s are supported.
+fn read_values(accessor: gltf::Accessor, buffer: &[u8]) -> Option<Vec<Vec4>> {
+    if accessor.data_type() != DataType::F32 {
+        return None;
+    }
+
+    let get_buffer = |_| Some(buffer);
+    let values = match accessor.dimensions() {
+        Dimensions::Scalar => Iter::<f32>::new(accessor, get_buffer)?
+            .map(|x| Vec4::new(x, 0., 0., 0.))
+            .collect(),
+        Dimensions::Vec3 => Iter::<[f32; 3]>::new(accessor, get_buffer)?
+            .map(|[x, y, z]| Vec4::new(x, y, z, 1.))
+            .collect(),
+        Dimensions::Vec4 => Iter::<[f32; 4]>::new(accessor, get_buffer)?
+            .map(Vec4::from)
+            .collect(),
+        _ => return None,
+    };
+
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn clip(duration: f32) -> AnimationClip {
+        AnimationClip {
+            name: None,
+            duration,
+            channels: Vec::new(),
         }
     }
+
+    #[test]
+    pub fn test_cross_fade() {
+        let mut controller = AnimationController {
+            clips: vec![clip(1.), clip(2.)],
+            ..Default::default()
+        };
+        assert!(!controller.is_playing_clips());
+
+        controller.play(0);
+        controller.advance(1.5);
+        assert_eq!(controller.layers[0].playing.len(), 1);
+        assert_relative_eq!(controller.layers[0].playing[0].time, 0.5);
+
+        // Halfway through the fade, both clips are playing..
+        controller.cross_fade(1, 0.5);
+        controller.advance(0.25);
+        let playing = &controller.layers[0].playing;
+        assert_eq!(playing.len(), 2);
+        assert_relative_eq!(playing[0].weight, 0.5);
+        assert_relative_eq!(playing[1].weight, 0.5);
+        assert_relative_eq!(playing[1].time, 0.25);
+
+        // ..and once it's done, the old clip is stopped.
+        controller.advance(0.25);
+        let playing = &controller.layers[0].playing;
+        assert_eq!(playing.len(), 1);
+        assert_eq!(playing[0].clip, 1);
+        assert_relative_eq!(playing[0].weight, 1.);
+
+        controller.stop();
+        assert!(!controller.is_playing_clips());
+    }
+
+    #[test]
+    pub fn test_mask_below() {
+        let mut world = World::new();
+        let hips = world.spawn(());
+        let spine = world.spawn((Parent(hips),));
+        let head = world.spawn((Parent(spine),));
+        let leg = world.spawn((Parent(hips),));
+
+        let layer = AnimationLayer {
+            weight: 1.,
+            mask: Some(AnimationLayer::mask_below(&world, spine)),
+            playing: Vec::new(),
+        };
+        assert!(layer.masks(spine));
+        assert!(layer.masks(head));
+        assert!(!layer.masks(hips));
+        assert!(!layer.masks(leg));
+    }
 }
@@ -1,4 +1,4 @@
-use glam::{Quat, Vec3};
+use glam::{Quat, Vec3, Vec4};
 use hecs::Entity;
 
 /// A component that allows an entity to be animated.
@@ -14,3 +14,28 @@ pub struct AnimationTarget {
     /// Translations for this animation
     pub translations: Vec<Vec3>,
 }
+
+/// A material or light property that can be animated.
+/// Usually added by `gltf_loader` for animations using the `KHR_animation_pointer` extension.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyAnimationTarget {
+    /// The property that is affected by this animation
+    pub property: AnimatedProperty,
+    /// Values for this animation. Colors are stored in `xyz` (and `w`, if they have alpha), and intensities in `x`.
+    pub values: Vec<Vec4>,
+}
+
+/// The material and light properties that can be animated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimatedProperty {
+    /// A material's `base_color_factor`, by its index in the materials buffer
+    BaseColorFactor(u32),
+    /// A material's `emissive_factor`, by its index in the materials buffer
+    EmissiveFactor(u32),
+    /// The color of one of the scene's lights, by its index in [`crate::asset_importer::scene::Scene`]'s `lights`.
+    /// Copy the scene's lights into `RenderContext::scene_data` in the same order to animate them.
+    LightColor(usize),
+    /// The intensity of one of the scene's lights, by its index in [`crate::asset_importer::scene::Scene`]'s
+    /// `lights`
+    LightIntensity(usize),
+}
@@ -0,0 +1,66 @@
+use ash::vk;
+use glam::{Vec2, Vec3};
+use hecs::{Entity, World};
+
+use crate::contexts::{GuiContext, RenderContext, VulkanContext};
+
+use super::ui_panel::add_ui_panel_to_world;
+
+/// A component added to a [`super::UIPanel`] to show the engine's [`crate::captions::Captions`]
+/// Used by `captions_system`
+///
+/// The panel floats in front of the player, a little below their eye line, and only catches up when they've turned
+/// far enough away from it - a panel locked rigidly to the head is uncomfortable to read. It's hidden while there are
+/// no captions to show. The panel shouldn't have a [`super::Parent`], as it's moved in global space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptionPanel {
+    /// How far in front of the player the panel floats, in metres
+    pub distance: f32,
+    /// How far below the player's eye line the panel floats, in metres
+    pub drop: f32,
+    /// How far the player can turn away from the panel before it follows them, in radians
+    pub follow_angle: f32,
+    /// How quickly the panel catches up when it follows. Higher is faster.
+    pub follow_speed: f32,
+    /// Is the panel catching up with the player?
+    pub(crate) following: bool,
+}
+
+impl Default for CaptionPanel {
+    fn default() -> Self {
+        Self {
+            distance: 1.2,
+            drop: 0.3,
+            follow_angle: 20_f32.to_radians(),
+            follow_speed: 4.,
+            following: true,
+        }
+    }
+}
+
+/// Convenience function to create a [`CaptionPanel`] and add it to a World
+pub fn add_caption_panel_to_world(
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+    gui_context: &GuiContext,
+    world: &mut World,
+) -> Entity {
+    let panel_entity = add_ui_panel_to_world(
+        "",
+        vk::Extent2D {
+            width: 800,
+            height: 160,
+        },
+        Vec2::new(0.8, 0.16),
+        Vec3::ZERO,
+        vec![],
+        vulkan_context,
+        render_context,
+        gui_context,
+        world,
+    );
+    world
+        .insert_one(panel_entity, CaptionPanel::default())
+        .unwrap();
+    panel_entity
+}
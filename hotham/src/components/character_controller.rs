@@ -0,0 +1,43 @@
+use crate::contexts::physics_context::HAND_COLLISION_GROUP;
+
+/// Component that walks the player around with the left thumbstick, in the direction they're looking, without walking
+/// through walls. Add it to the [`super::Stage`].
+/// Used by `character_controller_system`
+///
+/// The player is a capsule standing under the headset, which slides along walls, walks up slopes up to `max_slope`
+/// and steps up ledges up to `step_offset` high. When there's nothing underneath it, it falls with the physics
+/// context's gravity. The capsule isn't in the physics simulation, so it doesn't push anything out of the way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterController {
+    /// The radius of the capsule, in metres
+    pub radius: f32,
+    /// How tall the capsule is, from its feet to the top of its head, in metres
+    pub height: f32,
+    /// How fast the player walks with the thumbstick pushed all the way, in metres per second
+    pub speed: f32,
+    /// The steepest slope the player can walk up, in radians
+    pub max_slope: f32,
+    /// The highest ledge the player can step up onto, in metres
+    pub step_offset: f32,
+    /// Which collision groups the player can't walk through - by default, everything but the player's hands
+    pub collision_filter: u32,
+    /// Is the player standing on something? Set by `character_controller_system`.
+    pub grounded: bool,
+    /// How fast the player is falling, in metres per second
+    pub vertical_velocity: f32,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            radius: 0.25,
+            height: 1.7,
+            speed: 2.,
+            max_slope: std::f32::consts::FRAC_PI_4,
+            step_offset: 0.3,
+            collision_filter: !HAND_COLLISION_GROUP,
+            grounded: false,
+            vertical_velocity: 0.,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use glam::Vec3;
+
+use crate::contexts::physics_context::HAND_COLLISION_GROUP;
+
+use super::hand::Handedness;
+
+/// How the player turns with the thumbstick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurnMode {
+    /// Don't turn
+    Off,
+    /// Turn by `angle` radians each time the thumbstick is pushed to the side
+    Snap {
+        /// How far to turn, in radians
+        angle: f32,
+    },
+    /// Turn while the thumbstick is pushed to the side
+    Smooth {
+        /// How fast to turn with the thumbstick pushed all the way, in radians per second
+        speed: f32,
+    },
+}
+
+/// Component that lets the player teleport and turn with a thumbstick, which most people find much more comfortable
+/// than walking around. Add it to the [`super::Stage`].
+/// Used by `comfort_locomotion_system`
+///
+/// Pushing the thumbstick forward aims an arc from the controller, and letting go of it teleports the player to where
+/// the arc lands, as long as it's flat enough to stand on. The view fades out and back in around the teleport with
+/// [`crate::contexts::EffectsContext`], so `effects_system` has to be run too. Pushing the thumbstick to the side turns
+/// the player around their head.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortLocomotion {
+    /// Which controller's thumbstick to use
+    pub handedness: Handedness,
+    /// How the player turns
+    pub turn: TurnMode,
+    /// Can the player teleport?
+    pub teleport: bool,
+    /// How fast the arc leaves the controller, in metres per second. Faster arcs reach further.
+    pub arc_speed: f32,
+    /// The steepest slope the player can teleport onto, in radians
+    pub max_slope: f32,
+    /// How long the view takes to fade out, and back in, around a teleport, in seconds
+    pub fade_duration: f32,
+    /// Which collision groups the arc lands on - by default, everything but the player's hands
+    pub collision_filter: u32,
+    /// The arc being aimed, in global space, for drawing it. Empty when the player isn't aiming.
+    pub arc: Vec<Vec3>,
+    /// Where the player would be teleported to if they let go of the thumbstick now, in global space
+    pub target: Option<Vec3>,
+    /// A teleport that's waiting for the view to fade out
+    pub(crate) pending_teleport: Option<Vec3>,
+    /// Has the thumbstick been let go of since the last snap turn?
+    pub(crate) turn_ready: bool,
+}
+
+impl Default for ComfortLocomotion {
+    fn default() -> Self {
+        Self {
+            handedness: Handedness::Right,
+            turn: TurnMode::Snap {
+                angle: std::f32::consts::FRAC_PI_4,
+            },
+            teleport: true,
+            arc_speed: 7.,
+            max_slope: std::f32::consts::FRAC_PI_6,
+            fade_duration: 0.1,
+            collision_filter: !HAND_COLLISION_GROUP,
+            arc: Vec::new(),
+            target: None,
+            pending_teleport: None,
+            turn_ready: true,
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use super::hand::Handedness;
+
+/// A component that makes an entity follow one of the player's controllers, so the model spawned under it matches what
+/// they're holding. Use [`crate::systems::controller_models::add_controller_model`] to spawn the runtime's model of
+/// the controller, or a built-in one if the runtime doesn't have one.
+/// Requires `controller_models_system`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControllerModel {
+    /// Which controller to follow
+    pub handedness: Handedness,
+    /// Did the model come from the runtime, so it looks like the player's actual hardware? If not, it's the built-in
+    /// fallback.
+    pub from_runtime: bool,
+}
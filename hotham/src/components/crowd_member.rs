@@ -0,0 +1,33 @@
+use crate::rendering::crowd::CrowdId;
+
+/// Component that draws an entity as one member of a crowd - many copies of the same skinned mesh, animated and drawn
+/// together on the GPU. Much cheaper than giving each of them a [`super::Mesh`] and [`super::Skin`] of their own, for
+/// audiences, flocks and swarms.
+///
+/// Crowds are created with [`crate::contexts::RenderContext::add_crowd`]. Like meshes, crowd members are only drawn when
+/// their entity is [`super::Visible`].
+///
+/// Requires `rendering_system`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrowdMember {
+    /// The crowd this entity belongs to
+    pub crowd: CrowdId,
+    /// Which of the crowd's baked animations to play
+    pub animation: u32,
+    /// How far into the animation this member is, in seconds, so the crowd doesn't move in lockstep
+    pub time_offset: f32,
+    /// How fast the animation plays. 1 is normal speed.
+    pub speed: f32,
+}
+
+impl CrowdMember {
+    /// Play `animation` from `crowd`, `time_offset` seconds in, at normal speed
+    pub fn new(crowd: CrowdId, animation: u32, time_offset: f32) -> Self {
+        Self {
+            crowd,
+            animation,
+            time_offset,
+            speed: 1.,
+        }
+    }
+}
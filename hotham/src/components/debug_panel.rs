@@ -0,0 +1,39 @@
+use ash::vk;
+use glam::{Vec2, Vec3};
+use hecs::{Entity, World};
+
+use crate::contexts::{GuiContext, RenderContext, VulkanContext};
+
+use super::ui_panel::add_ui_panel_to_world;
+
+/// A component added to a [`super::UIPanel`] to show the engine's [`crate::memory_stats::MemoryStats`] and
+/// [`crate::frame_pacing::FramePacingStats`]
+/// Used by `debug_panel_system`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugPanel {}
+
+/// Convenience function to create a [`DebugPanel`] at `translation` and add it to a World
+pub fn add_debug_panel_to_world(
+    translation: Vec3,
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+    gui_context: &GuiContext,
+    world: &mut World,
+) -> Entity {
+    let panel_entity = add_ui_panel_to_world(
+        "",
+        vk::Extent2D {
+            width: 600,
+            height: 500,
+        },
+        Vec2::new(0.6, 0.5),
+        translation,
+        vec![],
+        vulkan_context,
+        render_context,
+        gui_context,
+        world,
+    );
+    world.insert_one(panel_entity, DebugPanel {}).unwrap();
+    panel_entity
+}
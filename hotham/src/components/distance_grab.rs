@@ -0,0 +1,57 @@
+use glam::Affine3A;
+use hecs::Entity;
+
+/// A component added to a [`super::Hand`] to let the player grab [`super::Grabbable`] objects that are out of reach.
+///
+/// Point at an object with the controller and it will be marked with a [`DistanceGrabTarget`]. Squeeze the grip and
+/// the object will fly to the hand, after which it is held as if it had been grabbed normally. Releasing the grip
+/// before the object arrives cancels the pull.
+///
+/// Requires `distance_grab_system`
+#[derive(Debug, Clone)]
+pub struct DistanceGrab {
+    /// How far away can objects be grabbed from, in metres?
+    pub max_distance: f32,
+    /// How long, in seconds, does it take for a pulled object to reach the hand?
+    pub pull_duration: f32,
+    /// How high, in metres, does a pulled object arc above the straight line to the hand?
+    pub arc_height: f32,
+    /// The object currently being pointed at, if any
+    pub target: Option<Entity>,
+    /// The object currently being pulled towards the hand, if any
+    pub(crate) pull: Option<Pull>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Pull {
+    pub entity: Entity,
+    pub global_from_start: Affine3A,
+    pub elapsed: f32,
+}
+
+impl Default for DistanceGrab {
+    fn default() -> Self {
+        Self {
+            max_distance: 5.0,
+            pull_duration: 0.3,
+            arc_height: 0.2,
+            target: None,
+            pull: None,
+        }
+    }
+}
+
+impl DistanceGrab {
+    /// Is an object currently being pulled towards the hand?
+    pub fn is_pulling(&self) -> bool {
+        self.pull.is_some()
+    }
+}
+
+/// A marker component added by `distance_grab_system` to the object a [`DistanceGrab`] hand is pointing at, so it can be
+/// highlighted. Removed as soon as the hand points away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DistanceGrabTarget {
+    /// The hand that is pointing at this object
+    pub hand: Entity,
+}
@@ -0,0 +1,82 @@
+use glam::Vec4;
+use hecs::Entity;
+
+use super::AnimatedProperty;
+
+/// Facial expressions and mouth shapes for an avatar, like VRM's `happy`, `blink` or `aa`. Set an expression's
+/// `weight` to show it. Usually added by `gltf_loader` to the root entity of a VRM avatar.
+///
+/// Requires `expressions_system`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expressions {
+    /// The avatar's expressions
+    pub expressions: Vec<Expression>,
+}
+
+/// A single expression, made of changes to the avatar's meshes and materials
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expression {
+    /// The expression's name, eg. `happy` or `blinkLeft`
+    pub name: String,
+    /// How much the expression is showing, from 0 to 1
+    pub weight: f32,
+    /// Should the expression snap on when `weight` is over 0.5, rather than fade in?
+    pub is_binary: bool,
+    /// Morph targets the expression blends in. Hotham doesn't draw morph targets yet, so these are only here for
+    /// applications that want them.
+    pub morph_target_binds: Vec<MorphTargetBind>,
+    /// Material colors the expression changes
+    pub material_color_binds: Vec<MaterialColorBind>,
+}
+
+/// A morph target blended in by an [`Expression`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MorphTargetBind {
+    /// The entity with the mesh
+    pub entity: Entity,
+    /// The index of the morph target in the mesh
+    pub index: usize,
+    /// The morph target's weight when the expression is fully showing
+    pub weight: f32,
+}
+
+/// A material color changed by an [`Expression`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialColorBind {
+    /// The color to change
+    pub property: AnimatedProperty,
+    /// The color when no expressions are showing
+    pub base: Vec4,
+    /// The color when the expression is fully showing
+    pub target: Vec4,
+}
+
+impl Expressions {
+    /// Get the expression called `name`, if there is one
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Expression> {
+        self.expressions.iter_mut().find(|e| e.name == name)
+    }
+
+    /// Set the weight of the expression called `name`, if there is one
+    pub fn set_weight(&mut self, name: &str, weight: f32) {
+        if let Some(expression) = self.get_mut(name) {
+            expression.weight = weight;
+        }
+    }
+}
+
+impl Expression {
+    /// How much the expression is showing, once `is_binary` is taken into account
+    pub fn effective_weight(&self) -> f32 {
+        let weight = self.weight.clamp(0., 1.);
+        if self.is_binary {
+            if weight > 0.5 {
+                1.
+            } else {
+                0.
+            }
+        } else {
+            weight
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use glam::Vec3;
+
+/// A box of fog, centred on its entity and oriented with it. Density fades in over `edge_fade` metres from the
+/// edges of the box, using its signed distance field, so volumes blend into each other and into clear air.
+///
+/// Fog is only drawn when volumetric fog has been enabled - see [`crate::EngineBuilder::volumetric_fog`].
+///
+/// Requires `rendering_system`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogVolume {
+    /// How much light the fog scatters or absorbs per metre. Values around 0.05 give a light haze; 1.0 is very thick.
+    pub density: f32,
+    /// The color of the light scattered by the fog, in linear space
+    pub albedo: Vec3,
+    /// Half the size of the box along each of its axes, in metres, before the entity's scale is applied
+    pub half_extents: Vec3,
+    /// How far inside the box, in metres, the fog reaches its full density
+    pub edge_fade: f32,
+}
+
+impl FogVolume {
+    /// Create a volume of white fog
+    pub fn new(density: f32, half_extents: Vec3) -> Self {
+        Self {
+            density,
+            half_extents,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for FogVolume {
+    fn default() -> Self {
+        Self {
+            density: 0.05,
+            albedo: Vec3::ONE,
+            half_extents: Vec3::ONE,
+            edge_fade: 0.5,
+        }
+    }
+}
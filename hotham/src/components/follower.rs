@@ -0,0 +1,69 @@
+use glam::Vec3;
+use hecs::Entity;
+
+use super::hand::Handedness;
+
+/// What a [`Follower`] follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowTarget {
+    /// The player's head
+    Head,
+    /// One of the player's controllers
+    Hand(Handedness),
+    /// Another entity
+    Entity(Entity),
+}
+
+/// A component that keeps a menu, HUD or other panel near the player, only catching up with them once they've turned
+/// far enough away from it - the "lazy follow" that's much more comfortable than something locked rigidly to the head.
+/// Used by `follow_system`
+///
+/// The entity is moved in global space, so it shouldn't have a [`super::Parent`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Follower {
+    /// What to follow
+    pub target: FollowTarget,
+    /// Where the entity should be relative to its target, in metres - eg. `[0., -0.3, -1.2]` for in front of the head
+    /// and a little below the player's eye line
+    pub offset: Vec3,
+    /// How far the entity can get from where it should be before it starts catching up, as an angle seen from the
+    /// target, in radians. Zero follows all the time.
+    pub dead_zone: f32,
+    /// How quickly the entity catches up with where it should be. Higher is faster, and infinity is instant.
+    pub speed: f32,
+    /// Ignore the target's pitch and roll, so the entity doesn't bob about as the player looks up and down
+    pub level: bool,
+    /// Turn the entity about the vertical axis so its front faces the player, rather than turning it with the target
+    pub face_player: bool,
+    /// Is the entity catching up with its target?
+    pub(crate) following: bool,
+    /// Has the entity been put in place yet?
+    pub(crate) placed: bool,
+}
+
+impl Follower {
+    /// Follow `target` lazily, keeping `offset` from it once caught up
+    pub fn lazy(target: FollowTarget, offset: Vec3) -> Self {
+        Self {
+            target,
+            offset,
+            dead_zone: 20_f32.to_radians(),
+            speed: 4.,
+            level: true,
+            face_player: true,
+            following: false,
+            placed: false,
+        }
+    }
+
+    /// Stay at `offset` from `target` at all times, eg. for a HUD
+    pub fn locked(target: FollowTarget, offset: Vec3) -> Self {
+        Self {
+            dead_zone: 0.,
+            speed: f32::INFINITY,
+            level: false,
+            face_player: false,
+            ..Self::lazy(target, offset)
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::path::Path;
+
+use glam::{Affine3A, Vec3};
+use hecs::{Entity, World};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_importer::{add_model_to_world, Models},
+    contexts::{physics_context::DELTA_TIME, InputContext},
+    input_recording::InputPlayback,
+    HothamResult,
+};
+
+use super::{hand::Handedness, AnimationController};
+
+/// A component that plays a [`HandMotion`] back on a hand model, to show the player what to do.
+/// Used by `guidance_system`
+///
+/// Ghost hands aren't [`super::Hand`]s - they don't follow the controllers or grab anything. Give the hand model a
+/// translucent material in your glTF file so it reads as a demonstration rather than the player's own hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GhostHand {
+    /// The motion to play back
+    pub motion: HandMotion,
+    /// Where the motion is played back, relative to where it was recorded, in stage space
+    pub stage_from_motion: Affine3A,
+    /// How fast to play the motion back. 1 is the speed it was recorded at.
+    pub speed: f32,
+    /// Should the motion start again from the beginning when it finishes?
+    pub looping: bool,
+    /// Is the motion being played?
+    pub playing: bool,
+    /// How far into the motion we are, in seconds
+    pub(crate) time: f32,
+}
+
+impl GhostHand {
+    /// Create a ghost hand that plays `motion` over and over, where it was recorded
+    pub fn new(motion: HandMotion) -> Self {
+        Self {
+            motion,
+            stage_from_motion: Affine3A::IDENTITY,
+            speed: 1.,
+            looping: true,
+            playing: true,
+            time: 0.,
+        }
+    }
+
+    /// Play the motion from the beginning again.
+    pub fn restart(&mut self) {
+        self.time = 0.;
+        self.playing = true;
+    }
+
+    /// Has the motion been played to the end? Looping ghost hands never finish.
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.motion.duration()
+    }
+
+    /// Move the motion on by `delta_time`, and get the pose of the hand.
+    pub(crate) fn advance(&mut self, delta_time: f32) -> Option<HandKeyframe> {
+        if self.playing {
+            self.time += delta_time * self.speed;
+            let duration = self.motion.duration();
+            if self.looping && duration > 0. {
+                self.time %= duration;
+            }
+        }
+        self.motion.sample(self.time)
+    }
+}
+
+/// A recorded motion of a hand: where it was, and how far its fingers were curled, over time.
+///
+/// Motions can be recorded live with [`HandMotion::record`], taken from an input recording made with
+/// [`crate::EngineBuilder::record_input`] with [`HandMotion::from_recording`], or built by hand with
+/// [`HandMotion::push`]. They can be saved with `serde` and shipped with the app.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HandMotion {
+    keyframes: Vec<HandKeyframe>,
+}
+
+/// The pose of a hand at a moment in a [`HandMotion`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HandKeyframe {
+    /// Seconds since the start of the motion
+    pub time: f32,
+    /// Where the hand was, in stage space
+    pub stage_from_grip: Affine3A,
+    /// How far the hand's fingers were curled, from 0 (open) to 1 (closed)
+    pub finger_curl: f32,
+}
+
+impl HandMotion {
+    /// Add a keyframe to the end of the motion. Keyframes must be added in time order.
+    pub fn push(&mut self, keyframe: HandKeyframe) {
+        debug_assert!(self
+            .keyframes
+            .last()
+            .map_or(true, |last| last.time <= keyframe.time));
+        self.keyframes.push(keyframe);
+    }
+
+    /// Add the pose of the player's hand on the given side to the end of the motion, one frame after the last
+    /// keyframe. Call this every frame while recording.
+    pub fn record(&mut self, input_context: &InputContext, handedness: Handedness) {
+        let (stage_from_grip, grip_value, trigger_value) = match handedness {
+            Handedness::Left => (
+                input_context.left.stage_from_grip(),
+                input_context.left.grip_analog(),
+                input_context.left.trigger_analog(),
+            ),
+            Handedness::Right => (
+                input_context.right.stage_from_grip(),
+                input_context.right.grip_analog(),
+                input_context.right.trigger_analog(),
+            ),
+        };
+        let time = self.keyframes.last().map_or(0., |k| k.time + DELTA_TIME);
+        self.push(HandKeyframe {
+            time,
+            stage_from_grip,
+            finger_curl: grip_value.max(trigger_value),
+        });
+    }
+
+    /// Take the motion of the hand on the given side from the input recording at `path`.
+    pub fn from_recording(path: impl AsRef<Path>, handedness: Handedness) -> HothamResult<Self> {
+        let mut playback = InputPlayback::open(path.as_ref())?;
+        let mut motion = Self::default();
+        while let Some(input_context) = playback.next_frame()? {
+            motion.record(&input_context, handedness);
+        }
+        Ok(motion)
+    }
+
+    /// How long the motion lasts, in seconds
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0., |k| k.time)
+    }
+
+    /// The keyframes in the motion
+    pub fn keyframes(&self) -> &[HandKeyframe] {
+        &self.keyframes
+    }
+
+    /// The pose of the hand `time` seconds into the motion, blended between the keyframes either side.
+    pub fn sample(&self, time: f32) -> Option<HandKeyframe> {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        let (from, to) = match (
+            self.keyframes.get(next.wrapping_sub(1)),
+            self.keyframes.get(next),
+        ) {
+            (Some(from), Some(to)) => (from, to),
+            (Some(only), None) | (None, Some(only)) => return Some(*only),
+            (None, None) => return None,
+        };
+
+        let t = ((time - from.time) / (to.time - from.time)).clamp(0., 1.);
+        let (from_scale, from_rotation, from_translation) =
+            from.stage_from_grip.to_scale_rotation_translation();
+        let (to_scale, to_rotation, to_translation) =
+            to.stage_from_grip.to_scale_rotation_translation();
+        Some(HandKeyframe {
+            time,
+            stage_from_grip: Affine3A::from_scale_rotation_translation(
+                from_scale.lerp(to_scale, t),
+                from_rotation.slerp(to_rotation, t),
+                Vec3::lerp(from_translation, to_translation, t),
+            ),
+            finger_curl: from.finger_curl + (to.finger_curl - from.finger_curl) * t,
+        })
+    }
+}
+
+/// Convenience function to add a hand model that plays `motion` back to the world
+pub fn add_ghost_hand_to_world(
+    models: &Models,
+    handedness: Handedness,
+    motion: HandMotion,
+    world: &mut World,
+) -> Option<Entity> {
+    let model_name = match handedness {
+        Handedness::Left => "Left Hand",
+        Handedness::Right => "Right Hand",
+    };
+    let hand_entity = add_model_to_world(model_name, models, world, None)?;
+
+    if let Ok(mut animation_controller) = world.get::<&mut AnimationController>(hand_entity) {
+        animation_controller.blend_from = 0;
+        animation_controller.blend_to = 1;
+    }
+    world
+        .insert_one(hand_entity, GhostHand::new(motion))
+        .unwrap();
+    Some(hand_entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_ghost_hand_playback() {
+        let mut motion = HandMotion::default();
+        assert!(motion.sample(0.).is_none());
+        motion.push(HandKeyframe {
+            time: 0.,
+            stage_from_grip: Affine3A::IDENTITY,
+            finger_curl: 0.,
+        });
+        motion.push(HandKeyframe {
+            time: 2.,
+            stage_from_grip: Affine3A::from_translation([1., 0., 0.].into()),
+            finger_curl: 1.,
+        });
+
+        // Halfway between the keyframes, the hand is halfway between them.
+        let halfway = motion.sample(1.).unwrap();
+        assert_relative_eq!(halfway.stage_from_grip.translation, [0.5, 0., 0.].into());
+        assert_relative_eq!(halfway.finger_curl, 0.5);
+
+        // Looping ghost hands start again from the beginning..
+        let mut ghost_hand = GhostHand::new(motion);
+        ghost_hand.advance(1.5);
+        let pose = ghost_hand.advance(1.).unwrap();
+        assert_relative_eq!(pose.stage_from_grip.translation, [0.25, 0., 0.].into());
+        assert!(!ghost_hand.is_finished());
+
+        // ..and the rest stop at the end.
+        ghost_hand.looping = false;
+        ghost_hand.restart();
+        let pose = ghost_hand.advance(3.).unwrap();
+        assert_relative_eq!(pose.finger_curl, 1.);
+        assert!(ghost_hand.is_finished());
+    }
+}
@@ -15,8 +15,8 @@ impl Default for GlobalTransform {
 }
 
 impl GlobalTransform {
-    /// Convenience function to convert the [`GlobalTransform`] into a [`rapier3d::na::Isometry3`]
-    pub fn to_isometry(&self) -> rapier3d::na::Isometry3<f32> {
+    /// Convert the [`GlobalTransform`] into a [`rapier3d::na::Isometry3`] for use with the physics simulation
+    pub(crate) fn to_isometry(&self) -> rapier3d::na::Isometry3<f32> {
         util::isometry_from_affine(&self.0)
     }
 
@@ -24,6 +24,47 @@ impl GlobalTransform {
     pub fn to_scale_rotation_translation(&self) -> (Vec3, Quat, Vec3) {
         self.0.to_scale_rotation_translation()
     }
+
+    /// Rotate the entity so that its forward (-Z) axis points at `target`, with its up axis as close to `up` as possible.
+    /// Scale and translation are preserved.
+    ///
+    /// If `target` is in the same place as the entity, or directly above or below it, the transform is left unchanged.
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let (scale, _, translation) = self.to_scale_rotation_translation();
+        if let Some(rotation) = util::look_at_rotation(translation, target, up) {
+            self.0 = Affine3A::from_scale_rotation_translation(scale, rotation, translation);
+        }
+    }
+
+    /// The direction the entity is facing (-Z) in global space
+    pub fn forward(&self) -> Vec3 {
+        Vec3::from(-self.0.matrix3.z_axis.normalize())
+    }
+
+    /// The entity's right (+X) axis in global space
+    pub fn right(&self) -> Vec3 {
+        Vec3::from(self.0.matrix3.x_axis.normalize())
+    }
+
+    /// The entity's up (+Y) axis in global space
+    pub fn up(&self) -> Vec3 {
+        Vec3::from(self.0.matrix3.y_axis.normalize())
+    }
+
+    /// Transform a point from the entity's local space into global space
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.0.transform_point3(point)
+    }
+
+    /// Transform a vector from the entity's local space into global space. Translation is ignored.
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.0.transform_vector3(vector)
+    }
+
+    /// Interpolate between this transform and `other`: linearly for translation and scale, spherically for rotation.
+    pub fn lerp_slerp(&self, other: &GlobalTransform, s: f32) -> GlobalTransform {
+        GlobalTransform(util::lerp_slerp(&self.0, &other.0, s))
+    }
 }
 
 impl From<LocalTransform> for GlobalTransform {
@@ -31,3 +72,10 @@ impl From<LocalTransform> for GlobalTransform {
         GlobalTransform(l.to_affine())
     }
 }
+
+impl From<&rapier3d::na::Isometry3<f32>> for GlobalTransform {
+    fn from(isometry: &rapier3d::na::Isometry3<f32>) -> Self {
+        let (rotation, translation) = util::decompose_isometry(isometry);
+        GlobalTransform(Affine3A::from_rotation_translation(rotation, translation))
+    }
+}
@@ -0,0 +1,56 @@
+use hecs::{Entity, World};
+
+use crate::asset_importer::{add_model_to_world, Models};
+
+/// A component added to an arrow model that points the player towards something they need to look at.
+/// Used by `guidance_system`
+///
+/// The arrow floats in front of the player and turns to point at its target. It's hidden once the target is in front
+/// of them, or when there's nothing to point at. The arrow model should point down -Z, with its mesh on the model's
+/// root node, and it shouldn't have a [`super::Parent`], as it's moved in global space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuidanceArrow {
+    /// The entity to point at
+    pub target: Option<Entity>,
+    /// How far in front of the player the arrow floats, in metres
+    pub distance: f32,
+    /// How far below the player's eye line the arrow floats, in metres
+    pub drop: f32,
+    /// How close to the centre of the player's view the target has to be for the arrow to be hidden, in radians
+    pub hide_angle: f32,
+}
+
+impl GuidanceArrow {
+    /// Create an arrow that points at `target`
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target: Some(target),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for GuidanceArrow {
+    fn default() -> Self {
+        Self {
+            target: None,
+            distance: 0.6,
+            drop: 0.15,
+            hide_angle: 25_f32.to_radians(),
+        }
+    }
+}
+
+/// Convenience function to add the model called `model_name` to the world as a [`GuidanceArrow`] pointing at `target`
+pub fn add_guidance_arrow_to_world(
+    models: &Models,
+    model_name: &str,
+    target: Entity,
+    world: &mut World,
+) -> Option<Entity> {
+    let arrow_entity = add_model_to_world(model_name, models, world, None)?;
+    world
+        .insert_one(arrow_entity, GuidanceArrow::new(target))
+        .unwrap();
+    Some(arrow_entity)
+}
@@ -1,8 +1,9 @@
 use hecs::Entity;
+use serde::{Deserialize, Serialize};
 
 /// A component that represents the "side" or "handedness" that an entity is on
 /// Used by components such as `Hand` and `Pointer` to identify which controller they should map to
-#[derive(Debug, PartialEq, Clone, Copy, Eq, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Handedness {
     /// Left hand side
     Left,
@@ -15,30 +16,67 @@ pub enum Handedness {
 /// Requires `hands_system`
 #[derive(Clone)]
 pub struct Hand {
-    /// How much has this hand been gripped?
+    /// How much has this hand been gripped? Curls the middle, ring and little fingers.
     pub grip_value: f32,
+    /// How much has the trigger been pulled? Curls the index finger.
+    pub trigger_value: f32,
     /// Which side is this hand on?
     pub handedness: Handedness,
     /// Have we grabbed something?
     pub grabbed_entity: Option<Entity>,
+    /// Should the hand be hidden while it's holding something? Useful when the grabbed object has its own hand pose
+    /// or would otherwise be hidden behind the hand.
+    pub hide_when_grabbing: bool,
+    /// Should the hand follow the player's tracked hand when they put their controllers down? See
+    /// [`crate::contexts::HandTrackingContext`].
+    pub tracked: bool,
+    /// Has `hands_system` hidden this hand? Kept separately from `hidden_entities`, which may be empty if nothing was
+    /// visible to begin with.
+    pub(crate) hidden: bool,
+    /// Entities that `hands_system` has made invisible, so they can be shown again once the grabbed entity is released
+    pub(crate) hidden_entities: Vec<Entity>,
 }
 
 impl Hand {
     /// Shortcut helper to create a Left hand
     pub fn left() -> Hand {
-        Hand {
-            grip_value: 0.0,
-            handedness: Handedness::Left,
-            grabbed_entity: None,
-        }
+        Hand::new(Handedness::Left)
     }
 
     /// Shortcut helper to create a right hand
     pub fn right() -> Hand {
+        Hand::new(Handedness::Right)
+    }
+
+    /// Create a hand on the given side
+    pub fn new(handedness: Handedness) -> Hand {
         Hand {
             grip_value: 0.0,
-            handedness: Handedness::Right,
+            trigger_value: 0.0,
+            handedness,
             grabbed_entity: None,
+            hide_when_grabbing: false,
+            tracked: false,
+            hidden: false,
+            hidden_entities: Vec::new(),
         }
     }
+
+    /// Create a hand that follows the player's tracked hand on the given side, and their controller when their hand
+    /// isn't being tracked. Pinching the thumb and index finger together pulls the trigger, and curling the other
+    /// fingers presses the grip.
+    pub fn tracked(handedness: Handedness) -> Hand {
+        Hand {
+            tracked: true,
+            ..Hand::new(handedness)
+        }
+    }
+
+    /// How far the hand's fingers should be curled, from 0 (open) to 1 (closed).
+    ///
+    /// The bundled hand models only have a single "fist" animation, so the whole hand closes as soon as either the
+    /// grip or trigger is pressed.
+    pub fn finger_curl(&self) -> f32 {
+        self.grip_value.max(self.trigger_value)
+    }
 }
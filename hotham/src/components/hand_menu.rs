@@ -0,0 +1,92 @@
+use hecs::Entity;
+
+use super::{hand::Handedness, Sprite};
+
+/// A component that gives the player a radial menu on their wrist - the kind of menu nearly every app ends up
+/// building for itself. Put it on its own entity.
+/// Used by `hand_menu_system`
+///
+/// The menu opens when the player looks at their palm, or presses the menu button, depending on its
+/// [`HandMenuActivation`]. Its items are laid out in a ring above the wrist, clockwise from the top, and can be picked
+/// by pointing at one with the other hand and pulling the trigger, or by flicking the menu hand's thumbstick towards
+/// one and letting it go. Each item's icon is shown by a [`Sprite`] on an entity the system looks after.
+#[derive(Debug, Clone)]
+pub struct HandMenu {
+    /// Which wrist the menu is on
+    pub handedness: Handedness,
+    /// What opens the menu
+    pub activation: HandMenuActivation,
+    /// The items in the menu, clockwise from the top
+    pub items: Vec<HandMenuItem>,
+    /// How far the middle of each icon is from the middle of the menu, in metres
+    pub radius: f32,
+    /// How much bigger the item being pointed at is drawn
+    pub hover_scale: f32,
+    /// Is the menu open? Can also be set by the app, eg. to close the menu once something's been picked.
+    pub open: bool,
+    /// The index of the item being pointed at, if any
+    pub hovered: Option<usize>,
+    /// What happened to the menu this frame
+    pub events_this_frame: Vec<HandMenuEvent>,
+    /// The entities showing each item's icon
+    pub(crate) icons: Vec<Entity>,
+    /// The item the thumbstick has been flicked towards, picked when it's let go of
+    pub(crate) flicked: Option<usize>,
+}
+
+impl HandMenu {
+    /// Create a menu on `handedness`'s wrist with `items`, opened by looking at the palm
+    pub fn new(handedness: Handedness, items: Vec<HandMenuItem>) -> Self {
+        Self {
+            handedness,
+            activation: HandMenuActivation::LookAtPalm,
+            items,
+            radius: 0.08,
+            hover_scale: 1.3,
+            open: false,
+            hovered: None,
+            events_this_frame: Vec::new(),
+            icons: Vec::new(),
+            flicked: None,
+        }
+    }
+}
+
+/// What opens a [`HandMenu`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandMenuActivation {
+    /// Turning the palm towards the face opens the menu, and turning it away closes it
+    LookAtPalm,
+    /// The menu button on the left controller opens and closes the menu
+    MenuButton,
+}
+
+/// An item in a [`HandMenu`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandMenuItem {
+    /// What the item is called, eg. for telling items apart when one's picked
+    pub name: String,
+    /// The item's icon
+    pub icon: Sprite,
+}
+
+impl HandMenuItem {
+    /// Create an item called `name`, shown by `icon`
+    pub fn new(name: &str, icon: Sprite) -> Self {
+        Self {
+            name: name.to_string(),
+            icon,
+        }
+    }
+}
+
+/// Something that happened to a [`HandMenu`] this frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandMenuEvent {
+    /// The menu was opened
+    Opened,
+    /// The menu was closed
+    Closed,
+    /// The item at this index was picked
+    Selected(usize),
+}
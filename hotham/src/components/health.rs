@@ -0,0 +1,97 @@
+use glam::Vec3;
+use hecs::Entity;
+
+/// A component that lets an entity take [`Damage`] and die. Damage comes from anything with [`DamageOnContact`]
+/// that hits the entity, or from the application calling [`Health::apply_damage`]. Hotham doesn't do anything when an
+/// entity dies - check [`Health::just_died`] and play an animation, despawn it or end the game.
+///
+/// Requires `health_system`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    /// How much health the entity has left. It's dead once this reaches zero.
+    pub current: f32,
+    /// How much health the entity starts with
+    pub max: f32,
+    /// Damage taken this frame, so the application can react to it - eg. with a sound, a flash or haptics
+    pub damage_this_frame: Vec<Damage>,
+}
+
+/// Some damage taken by an entity with [`Health`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Damage {
+    /// How much health was taken away
+    pub amount: f32,
+    /// What did the damage, if anything
+    pub source: Option<Entity>,
+    /// Where the damage was done, in global space, if it was done somewhere in particular
+    pub point: Option<Vec3>,
+}
+
+/// A component that damages anything with [`Health`] that it hits, eg. a sword, a falling rock or a
+/// [`super::Projectile`]. The entity needs a [`super::Collider`] too, unless it's a projectile.
+///
+/// Requires `health_system`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageOnContact {
+    /// How much damage to do
+    pub amount: f32,
+    /// How fast the entities need to be moving towards each other for the damage to count, in metres per second. Stops
+    /// gently resting something on an entity from hurting it.
+    pub min_speed: f32,
+}
+
+impl Health {
+    /// Create a full [`Health`] of `max`
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            damage_this_frame: Vec::new(),
+        }
+    }
+
+    /// Take `damage` away from the entity's health. Damage to entities that are already dead is ignored.
+    pub fn apply_damage(&mut self, damage: Damage) {
+        if self.is_dead() {
+            return;
+        }
+        self.current = (self.current - damage.amount).clamp(0., self.max);
+        self.damage_this_frame.push(damage);
+    }
+
+    /// Is the entity out of health?
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+
+    /// Did the entity die this frame?
+    pub fn just_died(&self) -> bool {
+        self.is_dead() && !self.damage_this_frame.is_empty()
+    }
+
+    /// Bring the entity back to full health
+    pub fn reset(&mut self) {
+        self.current = self.max;
+    }
+}
+
+impl Damage {
+    /// Create some damage of `amount`, done by nothing in particular
+    pub fn new(amount: f32) -> Self {
+        Self {
+            amount,
+            source: None,
+            point: None,
+        }
+    }
+}
+
+impl DamageOnContact {
+    /// Damage anything hit for `amount`, however gently it's hit
+    pub fn new(amount: f32) -> Self {
+        Self {
+            amount,
+            min_speed: 0.,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use crate::contexts::http_context::HttpResponse;
+
+/// A component that receives the responses to requests sent for its entity with
+/// [`crate::contexts::HttpContext::send_for`]. Added by `http_system` when the first response arrives, if the entity
+/// doesn't already have one.
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponses {
+    /// Responses that arrived this frame
+    pub responses_this_frame: Vec<HttpResponse>,
+}
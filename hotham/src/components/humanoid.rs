@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use hecs::Entity;
+use serde::{Deserialize, Serialize};
+
+/// Which joint of a model is which part of a human body, so that avatars with different skeletons can be driven the
+/// same way. Usually added by `gltf_loader` to the root entity of a VRM avatar.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Humanoid {
+    /// The entity for each of the avatar's bones. Only the hips, spine, head, legs, arms and hands are required, so
+    /// don't count on the rest being there.
+    pub bones: HashMap<HumanoidBone, Entity>,
+}
+
+impl Humanoid {
+    /// Get the entity for `bone`, if the avatar has one
+    pub fn get(&self, bone: HumanoidBone) -> Option<Entity> {
+        self.bones.get(&bone).copied()
+    }
+}
+
+/// The bones of a human skeleton, as VRM names them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HumanoidBone {
+    /// The hips
+    Hips,
+    /// The spine
+    Spine,
+    /// The chest
+    Chest,
+    /// The upper chest
+    UpperChest,
+    /// The neck
+    Neck,
+    /// The head
+    Head,
+    /// The left eye
+    LeftEye,
+    /// The right eye
+    RightEye,
+    /// The jaw
+    Jaw,
+    /// The left thigh
+    LeftUpperLeg,
+    /// The left shin
+    LeftLowerLeg,
+    /// The left foot
+    LeftFoot,
+    /// The left toes
+    LeftToes,
+    /// The right thigh
+    RightUpperLeg,
+    /// The right shin
+    RightLowerLeg,
+    /// The right foot
+    RightFoot,
+    /// The right toes
+    RightToes,
+    /// The left shoulder
+    LeftShoulder,
+    /// The left upper arm
+    LeftUpperArm,
+    /// The left forearm
+    LeftLowerArm,
+    /// The left hand
+    LeftHand,
+    /// The right shoulder
+    RightShoulder,
+    /// The right upper arm
+    RightUpperArm,
+    /// The right forearm
+    RightLowerArm,
+    /// The right hand
+    RightHand,
+    /// The left thumb's metacarpal joint
+    LeftThumbMetacarpal,
+    /// The left thumb's proximal joint
+    LeftThumbProximal,
+    /// The left thumb's distal joint
+    LeftThumbDistal,
+    /// The left index's proximal joint
+    LeftIndexProximal,
+    /// The left index's intermediate joint
+    LeftIndexIntermediate,
+    /// The left index's distal joint
+    LeftIndexDistal,
+    /// The left middle's proximal joint
+    LeftMiddleProximal,
+    /// The left middle's intermediate joint
+    LeftMiddleIntermediate,
+    /// The left middle's distal joint
+    LeftMiddleDistal,
+    /// The left ring's proximal joint
+    LeftRingProximal,
+    /// The left ring's intermediate joint
+    LeftRingIntermediate,
+    /// The left ring's distal joint
+    LeftRingDistal,
+    /// The left little's proximal joint
+    LeftLittleProximal,
+    /// The left little's intermediate joint
+    LeftLittleIntermediate,
+    /// The left little's distal joint
+    LeftLittleDistal,
+    /// The right thumb's metacarpal joint
+    RightThumbMetacarpal,
+    /// The right thumb's proximal joint
+    RightThumbProximal,
+    /// The right thumb's distal joint
+    RightThumbDistal,
+    /// The right index's proximal joint
+    RightIndexProximal,
+    /// The right index's intermediate joint
+    RightIndexIntermediate,
+    /// The right index's distal joint
+    RightIndexDistal,
+    /// The right middle's proximal joint
+    RightMiddleProximal,
+    /// The right middle's intermediate joint
+    RightMiddleIntermediate,
+    /// The right middle's distal joint
+    RightMiddleDistal,
+    /// The right ring's proximal joint
+    RightRingProximal,
+    /// The right ring's intermediate joint
+    RightRingIntermediate,
+    /// The right ring's distal joint
+    RightRingDistal,
+    /// The right little's proximal joint
+    RightLittleProximal,
+    /// The right little's intermediate joint
+    RightLittleIntermediate,
+    /// The right little's distal joint
+    RightLittleDistal,
+}
@@ -0,0 +1,23 @@
+use glam::Affine3A;
+
+/// Component that draws many copies of an entity's [`super::Mesh`], each with its own transform, using one instanced
+/// draw for each of the mesh's primitives. Much cheaper than thousands of entities with a mesh each, for forests,
+/// asteroid fields and other scenery that repeats.
+///
+/// Each transform is relative to the entity's [`super::GlobalTransform`], so the whole set of copies can be moved at
+/// once. Instances aren't culled, skinned, lit by [`super::AmbientProbe`]s or drawn into shadow maps. Like meshes,
+/// they're only drawn when their entity is [`super::Visible`].
+///
+/// Requires `rendering_system`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Instanced {
+    /// Where to draw each copy of the mesh, relative to the entity
+    pub transforms: Vec<Affine3A>,
+}
+
+impl Instanced {
+    /// Draw a copy of the entity's mesh at each of `transforms`
+    pub fn new(transforms: Vec<Affine3A>) -> Self {
+        Self { transforms }
+    }
+}
@@ -0,0 +1,48 @@
+use glam::Vec3;
+
+/// A glare sprite drawn over one of the scene's dynamic lights, so bright lights have something for the eye to latch
+/// on to. The flare is hidden when anything with a collider is between the viewer and the light, and fades in and out
+/// rather than popping.
+///
+/// Lights aren't entities, so the flare refers to its light by its index in
+/// [`crate::contexts::RenderContext::scene_data`]'s `lights`. Colliders around the light itself will hide its flare.
+///
+/// Requires `lens_flare_system`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensFlare {
+    /// Which of the scene's lights this flare belongs to
+    pub light_index: usize,
+    /// The radius of the glow, in radians. It stays the same apparent size however far away the light is.
+    pub size: f32,
+    /// Multiplied with the light's color
+    pub color: Vec3,
+    /// How bright the flare is
+    pub intensity: f32,
+    /// How long the flare takes to fade in or out, in seconds
+    pub fade_time: f32,
+    /// How visible the flare is, from 0 to 1. Updated by `lens_flare_system`.
+    pub visibility: f32,
+}
+
+impl LensFlare {
+    /// Create a flare for the light at `light_index`
+    pub fn new(light_index: usize) -> Self {
+        Self {
+            light_index,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for LensFlare {
+    fn default() -> Self {
+        Self {
+            light_index: 0,
+            size: 0.1,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            fade_time: 0.1,
+            visibility: 0.0,
+        }
+    }
+}
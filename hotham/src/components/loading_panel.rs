@@ -0,0 +1,38 @@
+use ash::vk;
+use glam::{Vec2, Vec3};
+use hecs::{Entity, World};
+
+use crate::contexts::{GuiContext, RenderContext, VulkanContext};
+
+use super::ui_panel::add_ui_panel_to_world;
+
+/// A component added to a [`super::UIPanel`] to show how far an [`crate::asset_importer::loader::AssetLoader`] has got
+/// Used by `loading_panel_system`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadingPanel {}
+
+/// Convenience function to create a [`LoadingPanel`] at `translation` and add it to a World
+pub fn add_loading_panel_to_world(
+    translation: Vec3,
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+    gui_context: &GuiContext,
+    world: &mut World,
+) -> Entity {
+    let panel_entity = add_ui_panel_to_world(
+        "Loading..",
+        vk::Extent2D {
+            width: 600,
+            height: 200,
+        },
+        Vec2::new(0.6, 0.2),
+        translation,
+        vec![],
+        vulkan_context,
+        render_context,
+        gui_context,
+        world,
+    );
+    world.insert_one(panel_entity, LoadingPanel {}).unwrap();
+    panel_entity
+}
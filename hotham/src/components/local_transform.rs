@@ -2,7 +2,7 @@ use glam::{Affine3A, Quat, Vec3};
 use gltf::scene::Transform as TransformData;
 use serde::{Deserialize, Serialize};
 
-use crate::util::{decompose_isometry, isometry_from_affine};
+use crate::util::{decompose_isometry, isometry_from_affine, look_at_rotation};
 
 /// The component's position in global space (ie. the game simulation), relative to its parent.
 ///
@@ -61,13 +61,13 @@ impl LocalTransform {
         }
     }
 
-    /// Convenience function to convert the [`LocalTransform`] into a [`rapier3d::na::Isometry3`]
-    pub fn to_isometry(&self) -> rapier3d::na::Isometry3<f32> {
+    /// Convert the [`LocalTransform`] into a [`rapier3d::na::Isometry3`] for use with the physics simulation
+    pub(crate) fn to_isometry(&self) -> rapier3d::na::Isometry3<f32> {
         isometry_from_affine(&self.to_affine())
     }
 
-    /// Update the translation and rotation from a [`rapier3d::na::Isometry3`]
-    pub fn update_from_isometry(&mut self, isometry: &rapier3d::na::Isometry3<f32>) {
+    /// Update the translation and rotation from a [`rapier3d::na::Isometry3`] in the physics simulation
+    pub(crate) fn update_from_isometry(&mut self, isometry: &rapier3d::na::Isometry3<f32>) {
         (self.rotation, self.translation) = decompose_isometry(isometry);
     }
 
@@ -85,6 +85,49 @@ impl LocalTransform {
     pub fn to_affine(&self) -> Affine3A {
         Affine3A::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
     }
+
+    /// Rotate the entity so that its forward (-Z) axis points at `target`, with its up axis as close to `up` as possible.
+    ///
+    /// If `target` is in the same place as the entity, or directly above or below it, the rotation is left unchanged.
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        if let Some(rotation) = look_at_rotation(self.translation, target, up) {
+            self.rotation = rotation;
+        }
+    }
+
+    /// The direction the entity is facing (-Z), relative to its parent
+    pub fn forward(&self) -> Vec3 {
+        self.rotation * Vec3::NEG_Z
+    }
+
+    /// The entity's right (+X) axis, relative to its parent
+    pub fn right(&self) -> Vec3 {
+        self.rotation * Vec3::X
+    }
+
+    /// The entity's up (+Y) axis, relative to its parent
+    pub fn up(&self) -> Vec3 {
+        self.rotation * Vec3::Y
+    }
+
+    /// Transform a point from the entity's local space into its parent's space
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation * (self.scale * point)
+    }
+
+    /// Transform a vector from the entity's local space into its parent's space. Translation is ignored.
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.rotation * (self.scale * vector)
+    }
+
+    /// Interpolate between this transform and `other`: linearly for translation and scale, spherically for rotation.
+    pub fn lerp_slerp(&self, other: &LocalTransform, s: f32) -> LocalTransform {
+        LocalTransform {
+            translation: self.translation.lerp(other.translation, s),
+            rotation: self.rotation.slerp(other.rotation, s),
+            scale: self.scale.lerp(other.scale, s),
+        }
+    }
 }
 
 impl From<LocalTransform> for Affine3A {
@@ -92,3 +135,73 @@ impl From<LocalTransform> for Affine3A {
         l.to_affine()
     }
 }
+
+impl From<Affine3A> for LocalTransform {
+    fn from(transform: Affine3A) -> Self {
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        LocalTransform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+}
+
+impl From<&rapier3d::na::Isometry3<f32>> for LocalTransform {
+    fn from(isometry: &rapier3d::na::Isometry3<f32>) -> Self {
+        let (rotation, translation) = decompose_isometry(isometry);
+        LocalTransform::from_rotation_translation(rotation, translation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_look_at() {
+        let mut transform = LocalTransform::from_rotation_translation(
+            Quat::from_rotation_x(1.0),
+            [1.0, 1.0, 1.0].into(),
+        );
+        transform.look_at([1.0, 1.0, 5.0].into(), Vec3::Y);
+        assert_relative_eq!(transform.forward(), Vec3::Z, epsilon = 1e-6);
+        assert_relative_eq!(transform.up(), Vec3::Y, epsilon = 1e-6);
+        assert_relative_eq!(transform.right(), Vec3::NEG_X, epsilon = 1e-6);
+
+        // Looking straight up is ambiguous, so the rotation should be left alone.
+        let rotation = transform.rotation;
+        transform.look_at([1.0, 3.0, 1.0].into(), Vec3::Y);
+        assert_eq!(transform.rotation, rotation);
+    }
+
+    #[test]
+    pub fn test_transform_point_and_vector() {
+        let transform = LocalTransform {
+            translation: [1.0, 2.0, 3.0].into(),
+            rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            scale: Vec3::splat(2.0),
+        };
+        let affine = transform.to_affine();
+        let point = Vec3::new(0.5, -1.0, 2.0);
+        assert_relative_eq!(
+            transform.transform_point(point),
+            affine.transform_point3(point),
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(
+            transform.transform_vector(point),
+            affine.transform_vector3(point),
+            epsilon = 1e-6
+        );
+
+        let round_trip: LocalTransform = affine.into();
+        assert_relative_eq!(
+            round_trip.translation,
+            transform.translation,
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(round_trip.scale, transform.scale, epsilon = 1e-6);
+    }
+}
@@ -0,0 +1,26 @@
+/// A component added to a [`super::UIPanel`] to show a string from the engine's
+/// [`crate::localization::Localization`], in whichever language the player has picked
+/// Used by `localization_system`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalizedText {
+    /// The string's key in the string tables
+    pub key: String,
+    /// Values for the string's `{name}` placeholders
+    pub args: Vec<(String, String)>,
+}
+
+impl LocalizedText {
+    /// Show the string for `key`
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Fill in the string's `{name}` placeholder with `value`
+    pub fn with_arg(mut self, name: &str, value: &str) -> Self {
+        self.args.push((name.to_string(), value.to_string()));
+        self
+    }
+}
@@ -0,0 +1,61 @@
+use ash::vk;
+use glam::{Quat, Vec2, Vec3};
+use hecs::{Entity, World};
+use log::Level;
+
+use crate::contexts::{GuiContext, RenderContext, VulkanContext};
+
+use super::{ui_panel::add_ui_panel_to_world, LocalTransform, Parent};
+
+/// A component added to a [`super::UIPanel`] to show recent entries from the engine's [`crate::logging::LogHistory`]
+/// Used by `log_panel_system`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogPanel {
+    /// Only show entries at least this important
+    pub level: Level,
+    /// How many entries to show
+    pub lines: usize,
+}
+
+impl Default for LogPanel {
+    fn default() -> Self {
+        Self {
+            level: Level::Warn,
+            lines: 8,
+        }
+    }
+}
+
+/// Convenience function to create a [`LogPanel`] strapped to the wrist of `hand` and add it to a World
+pub fn add_log_panel_to_world(
+    hand: Entity,
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+    gui_context: &GuiContext,
+    world: &mut World,
+) -> Entity {
+    let panel_entity = add_ui_panel_to_world(
+        "",
+        vk::Extent2D {
+            width: 600,
+            height: 400,
+        },
+        Vec2::new(0.15, 0.1),
+        Vec3::new(0., 0.05, 0.12),
+        vec![],
+        vulkan_context,
+        render_context,
+        gui_context,
+        world,
+    );
+
+    // Lay the panel along the top of the forearm, so it can be read by turning the wrist.
+    let mut local_transform = world.get::<&mut LocalTransform>(panel_entity).unwrap();
+    local_transform.rotation = Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2);
+    drop(local_transform);
+
+    world
+        .insert(panel_entity, (LogPanel::default(), Parent(hand)))
+        .unwrap();
+    panel_entity
+}
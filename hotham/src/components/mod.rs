@@ -1,43 +1,111 @@
 #![allow(missing_docs)]
+pub mod ambient_probe;
+pub mod animation_clip;
 pub mod animation_controller;
 pub mod animation_target;
+pub mod caption_panel;
+pub mod character_controller;
+pub mod comfort_locomotion;
+pub mod controller_model;
+pub mod crowd_member;
+pub mod debug_panel;
+pub mod distance_grab;
+pub mod expressions;
+pub mod fog_volume;
+pub mod follower;
+pub mod ghost_hand;
 pub mod global_transform;
 pub mod grabbable;
+pub mod guidance_arrow;
 pub mod hand;
+pub mod hand_menu;
+pub mod health;
 pub mod hmd;
+#[cfg(feature = "http")]
+pub mod http_responses;
+pub mod humanoid;
 pub mod info;
+pub mod instanced;
 pub mod joint;
+pub mod lens_flare;
+pub mod loading_panel;
 pub mod local_transform;
+pub mod localized_text;
+pub mod log_panel;
 pub mod mesh;
+pub mod motion;
 pub mod panel;
 pub mod parent;
+pub mod persistent;
 pub mod physics;
 pub mod pointer;
+pub mod pose_filter;
+pub mod projectile;
 pub mod root;
+#[cfg(feature = "lua-scripting")]
+pub mod script;
 pub mod skin;
+pub mod socket;
 pub mod sound_emitter;
+pub mod spring_bone;
+pub mod sprite;
 pub mod stage;
+pub mod static_geometry;
 pub mod ui_panel;
 pub mod visible;
 
-pub use animation_controller::AnimationController;
-pub use animation_target::AnimationTarget;
+pub use ambient_probe::AmbientProbe;
+pub use animation_clip::{AnimationChannel, AnimationClip, ChannelValue, ChannelValues};
+pub use animation_controller::{AnimationController, AnimationLayer, ClipPlayback};
+pub use animation_target::{AnimatedProperty, AnimationTarget, PropertyAnimationTarget};
+pub use caption_panel::CaptionPanel;
+pub use character_controller::CharacterController;
+pub use comfort_locomotion::{ComfortLocomotion, TurnMode};
+pub use controller_model::ControllerModel;
+pub use crowd_member::CrowdMember;
+pub use debug_panel::DebugPanel;
+pub use distance_grab::{DistanceGrab, DistanceGrabTarget};
+pub use expressions::Expressions;
+pub use fog_volume::FogVolume;
+pub use follower::{FollowTarget, Follower};
+pub use ghost_hand::{GhostHand, HandKeyframe, HandMotion};
 pub use global_transform::GlobalTransform;
 pub use grabbable::Grabbable;
+pub use guidance_arrow::GuidanceArrow;
 pub use hand::Hand;
+pub use hand_menu::{HandMenu, HandMenuActivation, HandMenuEvent, HandMenuItem};
+pub use health::{Damage, DamageOnContact, Health};
 pub use hmd::HMD;
+#[cfg(feature = "http")]
+pub use http_responses::HttpResponses;
+pub use humanoid::{Humanoid, HumanoidBone};
 pub use info::Info;
+pub use instanced::Instanced;
 pub use joint::Joint;
+pub use lens_flare::LensFlare;
+pub use loading_panel::LoadingPanel;
 pub use local_transform::LocalTransform;
+pub use localized_text::LocalizedText;
+pub use log_panel::LogPanel;
 pub use mesh::Mesh;
+pub use motion::{AngularVelocity, LinearAcceleration, LinearVelocity};
 pub use panel::Panel;
 pub use parent::Parent;
+pub use persistent::Persistent;
 pub use physics::collider::Collider;
 pub use physics::RigidBody;
 pub use pointer::Pointer;
+pub use pose_filter::PoseFilter;
+pub use projectile::{Projectile, Tracer};
 pub use root::Root;
+#[cfg(feature = "lua-scripting")]
+pub use script::Script;
 pub use skin::Skin;
-pub use sound_emitter::SoundEmitter;
+pub use socket::Socket;
+pub use sound_emitter::{Attenuation, AttenuationCurve, SoundEmitter};
+pub use spring_bone::{SpringBone, SpringBoneCollider};
+pub use sprite::{Sprite, SpriteLayer};
 pub use stage::Stage;
+pub use static_geometry::StaticGeometry;
 pub use ui_panel::UIPanel;
 pub use visible::Visible;
@@ -0,0 +1,18 @@
+use glam::Vec3;
+
+/// Component that moves an entity that isn't in the physics simulation, in metres per second
+/// Used by `motion_system`, which moves the entity's `LocalTransform` - so the velocity is relative to its parent, if
+/// it has one. Entities with a `RigidBody` are moved by the physics simulation instead, and are left alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinearVelocity(pub Vec3);
+
+/// Component that spins an entity that isn't in the physics simulation
+/// The axis the entity spins around, scaled by how fast it spins in radians per second. Used by `motion_system`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AngularVelocity(pub Vec3);
+
+/// Component that speeds up an entity's `LinearVelocity`, in metres per second per second - eg. `Vec3::Y * -9.81` for
+/// gravity
+/// Used by `motion_system`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinearAcceleration(pub Vec3);
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// Marks an entity whose state is kept in a [`crate::snapshot::Snapshot`], so it can be put back the way it was if
+/// the app is killed while it's paused.
+///
+/// The id is how the entity is found again when the snapshot is restored, so it must be unique, and the same every
+/// time the app runs - eg. `"player"` or `"crate_3"`, rather than something random.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Persistent(pub String);
@@ -0,0 +1,7 @@
+/// A tag component that takes an entity out of the physics simulation, without throwing away its [`super::RigidBody`]
+/// or [`super::Collider`] components. While it's there, nothing collides with the entity and rays pass straight
+/// through it. Once it's removed, the entity is added back to the simulation from its components, as if it were new.
+///
+/// Used by [`crate::entity_pool::EntityPool`] to put away entities that aren't needed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Disabled {}
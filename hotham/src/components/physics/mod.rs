@@ -1,6 +1,8 @@
 pub mod additional_mass;
 pub mod collider;
+pub mod disabled;
 pub mod impulse;
+pub mod physical_material;
 pub mod rigid_body;
 pub mod teleport;
 
@@ -8,7 +10,9 @@ pub use additional_mass::AdditionalMass;
 pub use collider::ActiveCollisionTypes;
 pub use collider::Collider;
 pub use collider::SharedShape;
+pub use disabled::Disabled;
 pub use impulse::Impulse;
+pub use physical_material::{PhysicalMaterial, Surface};
 pub use rigid_body::BodyType;
 pub use rigid_body::RigidBody;
 pub use teleport::Teleport;
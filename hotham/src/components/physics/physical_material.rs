@@ -0,0 +1,85 @@
+/// What a collider's surface is made of, so footsteps, impacts and particle effects can change with the surface.
+/// Add it alongside a [`super::Collider`]; its `friction` and `restitution` are used instead of the collider's own
+/// `restitution`.
+///
+/// Use [`crate::contexts::PhysicsContext::surface_below`] to find the surface under a point, and
+/// [`crate::contexts::PhysicsContext::surface_impacts`] to find out what hit what each frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalMaterial {
+    /// The kind of surface
+    pub surface: Surface,
+    /// How much the surface resists sliding, usually between 0 (ice) and 1 (rubber)
+    pub friction: f32,
+    /// How "bouncy" the surface is, from 0 to 1
+    pub restitution: f32,
+}
+
+/// Kinds of surface, each with sensible friction and restitution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Surface {
+    /// Any surface without a [`PhysicalMaterial`]
+    #[default]
+    Default,
+    /// Concrete, stone and tiles
+    Concrete,
+    /// Wood
+    Wood,
+    /// Metal
+    Metal,
+    /// Grass
+    Grass,
+    /// Dirt and mud
+    Dirt,
+    /// Sand and gravel
+    Sand,
+    /// Water
+    Water,
+    /// Glass
+    Glass,
+    /// Carpet and fabric
+    Carpet,
+    /// Snow
+    Snow,
+    /// Ice
+    Ice,
+    /// Rubber
+    Rubber,
+    /// Flesh, for characters and creatures
+    Flesh,
+    /// A surface defined by the application, with the default friction and restitution
+    Custom(u32),
+}
+
+impl PhysicalMaterial {
+    /// Create a material for `surface`, with its preset friction and restitution
+    pub fn new(surface: Surface) -> Self {
+        let (friction, restitution) = match surface {
+            Surface::Default | Surface::Custom(_) => (0.5, 0.),
+            Surface::Concrete => (0.8, 0.1),
+            Surface::Wood => (0.6, 0.2),
+            Surface::Metal => (0.4, 0.2),
+            Surface::Grass => (0.7, 0.05),
+            Surface::Dirt => (0.8, 0.05),
+            Surface::Sand => (0.9, 0.),
+            Surface::Water => (0.1, 0.),
+            Surface::Glass => (0.3, 0.3),
+            Surface::Carpet => (0.9, 0.05),
+            Surface::Snow => (0.4, 0.),
+            Surface::Ice => (0.05, 0.1),
+            Surface::Rubber => (1., 0.8),
+            Surface::Flesh => (0.6, 0.1),
+        };
+
+        Self {
+            surface,
+            friction,
+            restitution,
+        }
+    }
+}
+
+impl Default for PhysicalMaterial {
+    fn default() -> Self {
+        Self::new(Surface::Default)
+    }
+}
@@ -0,0 +1,181 @@
+use glam::{Affine3A, Quat, Vec3};
+
+/// A component that smooths out the pose of an entity attached to a controller, such as a [`super::Hand`] or an
+/// entity that has been grabbed by one. Tracking noise that is invisible on a hand becomes very obvious at the end
+/// of a long object, like a rifle, so grabbed entities can have their own, heavier smoothing.
+///
+/// Poses are smoothed with a [one euro filter](https://gery.casiez.net/1euro/): slow movements are heavily filtered
+/// to remove jitter, while fast movements are only lightly filtered to keep latency low. The pose can optionally be
+/// predicted forward in time using the controller's velocity to compensate for the latency the filter adds.
+///
+/// Requires `hands_system`
+#[derive(Debug, Clone)]
+pub struct PoseFilter {
+    /// The cutoff frequency in Hz used when the controller is still. Lower values remove more jitter but add lag.
+    pub min_cutoff: f32,
+    /// How quickly the cutoff frequency rises with speed. Higher values reduce lag during fast movements.
+    pub beta: f32,
+    /// The cutoff frequency in Hz used to smooth the speed estimate.
+    pub derivative_cutoff: f32,
+    /// How far ahead in seconds to predict the pose using the controller's velocity. Zero disables prediction.
+    pub prediction_time: f32,
+    previous: Option<FilterState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FilterState {
+    translation: Vec3,
+    rotation: Quat,
+    linear_speed: f32,
+    angular_speed: f32,
+}
+
+impl Default for PoseFilter {
+    fn default() -> Self {
+        Self {
+            min_cutoff: 1.0,
+            beta: 1.0,
+            derivative_cutoff: 1.0,
+            prediction_time: 0.0,
+            previous: None,
+        }
+    }
+}
+
+impl PoseFilter {
+    /// Create a new filter with the given cutoff and speed coefficient.
+    pub fn new(min_cutoff: f32, beta: f32) -> Self {
+        Self {
+            min_cutoff,
+            beta,
+            ..Default::default()
+        }
+    }
+
+    /// Forget any previous poses. The next pose will be passed through unfiltered.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Filter `pose`, given that `delta_time` seconds have passed since the last call. Scale is passed through
+    /// unfiltered.
+    pub fn filter(&mut self, pose: &Affine3A, delta_time: f32) -> Affine3A {
+        let (scale, rotation, translation) = pose.to_scale_rotation_translation();
+
+        let state = match self.previous {
+            Some(previous) if delta_time > 0. => {
+                // Estimate and smooth the speed, then use it to decide how heavily to filter.
+                let linear_speed = previous.translation.distance(translation) / delta_time;
+                let linear_speed = lerp(
+                    previous.linear_speed,
+                    linear_speed,
+                    smoothing_factor(self.derivative_cutoff, delta_time),
+                );
+                let cutoff = self.min_cutoff + self.beta * linear_speed;
+                let translation = previous
+                    .translation
+                    .lerp(translation, smoothing_factor(cutoff, delta_time));
+
+                let angular_speed = previous.rotation.angle_between(rotation) / delta_time;
+                let angular_speed = lerp(
+                    previous.angular_speed,
+                    angular_speed,
+                    smoothing_factor(self.derivative_cutoff, delta_time),
+                );
+                let cutoff = self.min_cutoff + self.beta * angular_speed;
+                let rotation = previous
+                    .rotation
+                    .slerp(rotation, smoothing_factor(cutoff, delta_time));
+
+                FilterState {
+                    translation,
+                    rotation,
+                    linear_speed,
+                    angular_speed,
+                }
+            }
+            _ => FilterState {
+                translation,
+                rotation,
+                linear_speed: 0.,
+                angular_speed: 0.,
+            },
+        };
+
+        self.previous = Some(state);
+        Affine3A::from_scale_rotation_translation(scale, state.rotation, state.translation)
+    }
+}
+
+/// Predict where `pose` will be in `time` seconds, assuming it keeps moving with the given linear and angular
+/// velocities. The velocities must be in the same space as the pose.
+pub fn predict_pose(
+    pose: &Affine3A,
+    linear_velocity: Vec3,
+    angular_velocity: Vec3,
+    time: f32,
+) -> Affine3A {
+    let (scale, rotation, translation) = pose.to_scale_rotation_translation();
+    let rotation = (Quat::from_scaled_axis(angular_velocity * time) * rotation).normalize();
+    let translation = translation + linear_velocity * time;
+    Affine3A::from_scale_rotation_translation(scale, rotation, translation)
+}
+
+fn smoothing_factor(cutoff: f32, delta_time: f32) -> f32 {
+    let tau = 1.0 / (std::f32::consts::TAU * cutoff);
+    1.0 / (1.0 + tau / delta_time)
+}
+
+fn lerp(a: f32, b: f32, s: f32) -> f32 {
+    a + (b - a) * s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_pose_filter() {
+        let mut filter = PoseFilter::default();
+        let delta_time = 1. / 72.;
+
+        // The first pose should be passed straight through.
+        let start = Affine3A::from_translation([0., 1., 0.].into());
+        assert_relative_eq!(
+            filter.filter(&start, delta_time).translation,
+            start.translation
+        );
+
+        // Small movements should be heavily smoothed..
+        let jitter = Affine3A::from_translation([0.001, 1., 0.].into());
+        let filtered = filter.filter(&jitter, delta_time);
+        assert!(filtered.translation.x > 0.);
+        assert!(filtered.translation.x < 0.0002);
+
+        // ..and resetting the filter should pass the next pose through again.
+        filter.reset();
+        assert_relative_eq!(
+            filter.filter(&jitter, delta_time).translation,
+            jitter.translation
+        );
+    }
+
+    #[test]
+    pub fn test_predict_pose() {
+        let pose = Affine3A::from_translation([0., 1., 0.].into());
+        let predicted = predict_pose(
+            &pose,
+            [1., 0., 0.].into(),
+            [0., std::f32::consts::PI, 0.].into(),
+            0.5,
+        );
+        let (_, rotation, translation) = predicted.to_scale_rotation_translation();
+        assert_relative_eq!(translation, Vec3::new(0.5, 1., 0.));
+        assert_relative_eq!(
+            rotation,
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            epsilon = 1e-6
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use glam::{Affine3A, Quat, Vec2, Vec3, Vec4};
+use hecs::{Entity, World};
+
+use crate::{
+    components::{GlobalTransform, LocalTransform, Sprite, Visible},
+    contexts::physics_context::SurfaceHit,
+};
+
+/// A component that flies an entity through the world like a bullet, arrow or grenade, until it hits something or runs
+/// out of time. Each frame the projectile is swept along its path with the physics simulation, so even very fast
+/// projectiles can't pass through thin walls.
+///
+/// The projectile moves its entity's [`LocalTransform`], so the entity shouldn't have a [`super::Parent`] or a dynamic
+/// [`super::RigidBody`].
+///
+/// Requires `projectile_system`, which should be run after `physics_system`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projectile {
+    /// How fast, and in which direction, the projectile is moving, in metres per second
+    pub velocity: Vec3,
+    /// How much the projectile's velocity changes each second - usually gravity, or zero for lasers
+    pub gravity: Vec3,
+    /// How many seconds the projectile has left before it's despawned
+    pub lifetime: f32,
+    /// The radius of the projectile, in metres. Zero is treated as a point.
+    pub radius: f32,
+    /// Whoever fired the projectile, so it doesn't hit them
+    pub shooter: Option<Entity>,
+    /// What the projectile hit. It's set by `projectile_system` for one frame so the application can react, and then
+    /// the projectile is despawned.
+    pub hit: Option<SurfaceHit>,
+}
+
+impl Projectile {
+    /// Create a projectile moving at `velocity`, with the default gravity, lifetime and radius
+    pub fn new(velocity: Vec3) -> Self {
+        Self {
+            velocity,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Projectile {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            gravity: Vec3::new(0., -9.81, 0.),
+            lifetime: 5.,
+            radius: 0.,
+            shooter: None,
+            hit: None,
+        }
+    }
+}
+
+/// A component for a debug line showing where a shot went, added by [`add_tracer_to_world`]
+///
+/// Requires `projectile_system`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tracer {
+    /// How many seconds the tracer has left before it's despawned
+    pub lifetime: f32,
+}
+
+/// The width of a tracer, in metres
+pub const TRACER_WIDTH: f32 = 0.005;
+
+/// Convenience function to draw a [`Tracer`] from `from` to `to` in global space, eg. from a gun to where
+/// [`crate::contexts::PhysicsContext::hitscan`] says the shot hit. The tracer is a flat [`Sprite`], so it's thin enough
+/// to disappear when it's seen edge on.
+pub fn add_tracer_to_world(
+    from: Vec3,
+    to: Vec3,
+    color: Vec4,
+    lifetime: f32,
+    world: &mut World,
+) -> Entity {
+    let direction = (to - from).normalize_or_zero();
+    let local_transform = LocalTransform {
+        translation: from.lerp(to, 0.5),
+        rotation: Quat::from_rotation_arc(Vec3::Y, direction),
+        scale: Vec3::ONE,
+    };
+
+    world.spawn((
+        Sprite {
+            color,
+            size: Vec2::new(TRACER_WIDTH, from.distance(to)),
+            billboard: false,
+            ..Default::default()
+        },
+        local_transform,
+        GlobalTransform(Affine3A::from_rotation_translation(
+            local_transform.rotation,
+            local_transform.translation,
+        )),
+        Visible {},
+        Tracer { lifetime },
+    ))
+}
@@ -0,0 +1,16 @@
+/// A component added to an entity to run the Lua script loaded into the `LuaContext` as `name`
+/// Used by `lua_scripting_system`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    /// The name the script was loaded with
+    pub name: String,
+}
+
+impl Script {
+    /// Convenience function to create a new `Script`
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
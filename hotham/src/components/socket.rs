@@ -0,0 +1,80 @@
+use glam::Affine3A;
+use hecs::Entity;
+use rapier3d::prelude::SharedShape;
+
+/// A component that creates a "snap zone": released [`super::Grabbable`] objects inside the socket's shape are
+/// snapped into place. Useful for holsters, inventory slots and puzzle pieces.
+///
+/// Requires `sockets_system`
+#[derive(Clone)]
+pub struct Socket {
+    /// The zone that objects must be released in to be snapped into the socket, relative to the socket
+    pub shape: SharedShape,
+    /// If set, only objects with a matching [`SocketTag`] will be accepted
+    pub filter_tag: Option<String>,
+    /// Where the object should be placed, relative to the socket
+    pub socket_from_snapped: Affine3A,
+    /// Should the object be welded to the socket so that it moves with it? If not, it will just be left in place.
+    ///
+    /// Welding doesn't create a physics joint: the object is given a [`super::Parent`] of the socket, and it's already
+    /// kinematic while it's in a socket, so it simply follows the socket's transform until it's grabbed again.
+    pub weld: bool,
+    /// The object currently held by this socket
+    pub occupant: Option<Entity>,
+    /// A compatible object that is being held inside the socket's zone and would be snapped in if it was released.
+    /// Useful for highlighting the socket.
+    pub hovering: Option<Entity>,
+    /// Objects that have been attached to or detached from this socket this frame
+    pub events_this_frame: Vec<SocketEvent>,
+}
+
+impl std::fmt::Debug for Socket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Socket")
+            .field("shape", &self.shape.shape_type())
+            .field("filter_tag", &self.filter_tag)
+            .field("socket_from_snapped", &self.socket_from_snapped)
+            .field("weld", &self.weld)
+            .field("occupant", &self.occupant)
+            .field("hovering", &self.hovering)
+            .field("events_this_frame", &self.events_this_frame)
+            .finish()
+    }
+}
+
+impl Socket {
+    /// Create a new socket that accepts any object released inside `shape`
+    pub fn new(shape: SharedShape) -> Socket {
+        Socket {
+            shape,
+            filter_tag: None,
+            socket_from_snapped: Affine3A::IDENTITY,
+            weld: false,
+            occupant: None,
+            hovering: None,
+            events_this_frame: Vec::new(),
+        }
+    }
+
+    /// Does this socket accept objects with the given tag?
+    pub fn accepts(&self, tag: Option<&SocketTag>) -> bool {
+        match (&self.filter_tag, tag) {
+            (None, _) => true,
+            (Some(filter_tag), Some(tag)) => filter_tag == &tag.0,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Something that happened to a [`Socket`] this frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketEvent {
+    /// The entity was snapped into the socket
+    Attached(Entity),
+    /// The entity was removed from the socket
+    Detached(Entity),
+}
+
+/// A tag used to decide which [`Socket`]s an object can be placed in
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SocketTag(pub String);
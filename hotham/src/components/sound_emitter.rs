@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use oddio::{Frames, Stop};
 
-type AudioHandle = oddio::Handle<oddio::SpatialBuffered<oddio::Stop<oddio::FramesSignal<f32>>>>;
+use crate::captions::Caption;
+
+type AudioHandle =
+    oddio::Handle<oddio::SpatialBuffered<oddio::Stop<oddio::Gain<oddio::FramesSignal<f32>>>>>;
 
 /// A component added to an entity to allow it to emit a sound, usually a sound effect
 /// Used by `audio_system`
@@ -13,6 +16,95 @@ pub struct SoundEmitter {
     pub handle: Option<AudioHandle>,
     /// Used to indicate that the emitter wants to change its state
     pub next_state: Option<SoundState>,
+    /// Sounds with a higher priority are always played before sounds with a lower one, however quiet they are
+    pub priority: u8,
+    /// Set while the sound is playing, but not being mixed - see [`VirtualVoice`]
+    pub virtual_voice: Option<VirtualVoice>,
+    /// Shown by `audio_system` each time the sound starts playing, eg. for a voice over
+    pub caption: Option<Caption>,
+    /// How the sound gets quieter as it gets further from the listener
+    pub attenuation: Attenuation,
+    /// How much the sound is muffled by something between it and the listener, from 0 to 1. Updated by
+    /// `audio_system` when [`crate::contexts::AudioContext::occlusion`] is turned on.
+    pub occlusion: f32,
+}
+
+/// How a sound gets quieter with distance. Sounds closer than `min_distance` are played at full volume, and sounds
+/// further away than `max_distance` can't be heard at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    /// The shape of the curve between `min_distance` and `max_distance`
+    pub curve: AttenuationCurve,
+    /// How far away the sound can be before it starts to get quieter, in metres
+    pub min_distance: f32,
+    /// How far away the sound can be heard from, in metres
+    pub max_distance: f32,
+    /// How quickly `Inverse` and `Exponential` curves get quieter. Ignored by `Linear` curves.
+    pub rolloff: f32,
+}
+
+/// The shape of an [`Attenuation`] curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttenuationCurve {
+    /// Fades out evenly, reaching silence at `max_distance`. Good for ambient sounds in a small area.
+    Linear,
+    /// Gets quieter in proportion to distance, like sounds do in the real world
+    Inverse,
+    /// Gets quieter more quickly than `Inverse` as `rolloff` goes up
+    Exponential,
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self {
+            curve: AttenuationCurve::Inverse,
+            min_distance: 1.,
+            max_distance: 100.,
+            rolloff: 1.,
+        }
+    }
+}
+
+impl Attenuation {
+    /// Create an attenuation with the given curve and distances, and a `rolloff` of 1
+    pub fn new(curve: AttenuationCurve, min_distance: f32, max_distance: f32) -> Self {
+        Self {
+            curve,
+            min_distance,
+            max_distance,
+            rolloff: 1.,
+        }
+    }
+
+    /// How loud a sound `distance` metres away from the listener is, from 0 to 1
+    pub fn volume(&self, distance: f32) -> f32 {
+        let min_distance = self.min_distance.max(f32::EPSILON);
+        if distance <= min_distance {
+            return 1.;
+        }
+        if distance >= self.max_distance {
+            return 0.;
+        }
+        match self.curve {
+            AttenuationCurve::Linear => {
+                1. - (distance - min_distance) / (self.max_distance - min_distance)
+            }
+            AttenuationCurve::Inverse => {
+                min_distance / (min_distance + self.rolloff * (distance - min_distance))
+            }
+            AttenuationCurve::Exponential => (distance / min_distance).powf(-self.rolloff),
+        }
+    }
+}
+
+/// When there are more sounds playing than `AudioContext::max_voices`, the least important ones are virtualized:
+/// they stop being mixed, but keep track of where they're up to so they can carry on when there's room for them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualVoice {
+    /// How far through the sound we are, in seconds
+    pub position: f64,
+    /// Is the sound paused?
+    pub paused: bool,
 }
 
 impl Clone for SoundEmitter {
@@ -21,6 +113,11 @@ impl Clone for SoundEmitter {
             frames: self.frames.clone(),
             handle: None,
             next_state: None,
+            priority: self.priority,
+            virtual_voice: None,
+            caption: self.caption.clone(),
+            attenuation: self.attenuation,
+            occlusion: 0.,
         }
     }
 }
@@ -43,11 +140,40 @@ impl SoundEmitter {
             frames,
             handle: None,
             next_state: None,
+            priority: 0,
+            virtual_voice: None,
+            caption: None,
+            attenuation: Default::default(),
+            occlusion: 0.,
         }
     }
 
+    /// Change how the sound gets quieter with distance
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    /// Caption the sound with what's being said, shown for as long as the sound plays
+    pub fn with_caption(mut self, speaker: Option<&str>, text: &str) -> Self {
+        let caption = Caption::new(text, self.frames.runtime() as f32);
+        self.caption = Some(match speaker {
+            Some(speaker) => caption.with_speaker(speaker),
+            None => caption,
+        });
+        self
+    }
+
     /// Convenience function to get the `SoundState` of this `SoundEmitter`
     pub fn current_state(&mut self) -> SoundState {
+        if let Some(virtual_voice) = &self.virtual_voice {
+            return if virtual_voice.paused {
+                SoundState::Paused
+            } else {
+                SoundState::Playing
+            };
+        }
+
         if let Some(handle) = self.handle.as_mut() {
             let control = handle.control::<Stop<_>, _>();
             if control.is_paused() {
@@ -81,4 +207,35 @@ impl SoundEmitter {
     pub fn resume(&mut self) {
         self.next_state = Some(SoundState::Playing);
     }
+
+    /// Is the sound playing, but not being mixed?
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_voice.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_attenuation() {
+        let linear = Attenuation::new(AttenuationCurve::Linear, 2., 12.);
+        assert_eq!(linear.volume(1.), 1.);
+        assert_relative_eq!(linear.volume(7.), 0.5);
+        assert_eq!(linear.volume(12.), 0.);
+
+        // The default matches sounds in the real world, up to 100m away..
+        let inverse = Attenuation::default();
+        assert_relative_eq!(inverse.volume(4.), 0.25);
+        assert_eq!(inverse.volume(100.), 0.);
+
+        // ..and exponential curves fall away faster, the higher their rolloff.
+        let exponential = Attenuation {
+            rolloff: 2.,
+            ..Attenuation::new(AttenuationCurve::Exponential, 1., 100.)
+        };
+        assert_relative_eq!(exponential.volume(4.), 1. / 16.);
+    }
 }
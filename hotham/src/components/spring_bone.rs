@@ -0,0 +1,74 @@
+use glam::Vec3;
+use hecs::Entity;
+
+/// A chain of joints that swings, bounces and droops under its own momentum, for hair, tails, ears and cloth.
+/// The settings match a VRM spring bone's, and chains are loaded from glTF files using the `VRMC_springBone`
+/// extension.
+///
+/// Requires `spring_bone_system`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpringBone {
+    /// The joints in the chain, from root to tip. Each joint must be the parent of the next. The last joint is the
+    /// tip of the chain: it's moved by the joint before it, but doesn't move anything itself.
+    pub joints: Vec<Entity>,
+    /// How strongly each joint is pulled back towards its pose from animation, in metres per second
+    pub stiffness: f32,
+    /// How much of each joint's velocity is lost every frame, from 0 (none) to 1 (all of it)
+    pub damping: f32,
+    /// How strongly the chain is pulled in `gravity_dir`, in metres per second
+    pub gravity_power: f32,
+    /// The direction gravity pulls the chain, in global space
+    pub gravity_dir: Vec3,
+    /// The radius of each joint when colliding with `colliders`, in metres
+    pub hit_radius: f32,
+    /// Spheres the chain can't pass through, eg. the head for a chain of hair
+    pub colliders: Vec<SpringBoneCollider>,
+    /// Where the tail of each joint is now and was last frame, in global space. Filled in by `spring_bone_system`;
+    /// clear it to snap the chain back to its pose from animation.
+    pub tails: Vec<SpringBoneTail>,
+}
+
+/// A sphere that a [`SpringBone`] can't pass through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpringBoneCollider {
+    /// The entity the sphere moves with
+    pub entity: Entity,
+    /// The centre of the sphere, relative to `entity`
+    pub offset: Vec3,
+    /// The radius of the sphere, in metres
+    pub radius: f32,
+}
+
+/// Where the tail of a joint in a [`SpringBone`] is, in global space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpringBoneTail {
+    /// Where the tail is now
+    pub current: Vec3,
+    /// Where the tail was last frame
+    pub previous: Vec3,
+}
+
+impl SpringBone {
+    /// Create a chain from `joints`, ordered from root to tip, with VRM's default settings
+    pub fn new(joints: Vec<Entity>) -> Self {
+        Self {
+            joints,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SpringBone {
+    fn default() -> Self {
+        Self {
+            joints: Vec::new(),
+            stiffness: 1.,
+            damping: 0.5,
+            gravity_power: 0.,
+            gravity_dir: -Vec3::Y,
+            hit_radius: 0.,
+            colliders: Vec::new(),
+            tails: Vec::new(),
+        }
+    }
+}
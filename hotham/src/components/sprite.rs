@@ -0,0 +1,62 @@
+use glam::{Vec2, Vec4};
+
+use crate::rendering::texture::NO_TEXTURE;
+
+/// A flat, textured quad centred on its entity - much cheaper than a mesh for things like markers, icons and damage
+/// numbers. Sprites using the same texture are drawn together, so packing several into one texture and picking them
+/// out with `uv_offset` and `uv_extent` keeps the number of draw calls down.
+///
+/// Requires `rendering_system`. Like meshes, sprites are only drawn when their entity is [`super::Visible`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    /// The index of the texture in the texture array - see [`crate::rendering::texture::Texture`]. If it's
+    /// [`NO_TEXTURE`], the sprite is filled with `color`.
+    pub texture_id: u32,
+    /// The top left corner of the region of the texture to draw, in texture coordinates
+    pub uv_offset: Vec2,
+    /// The size of the region of the texture to draw, in texture coordinates
+    pub uv_extent: Vec2,
+    /// Multiplied with the texture, in linear space. Alpha blends the sprite with what's behind it.
+    pub color: Vec4,
+    /// The width and height of the sprite, in metres, before the entity's scale is applied
+    pub size: Vec2,
+    /// Should the sprite always face the viewer? If not, it faces along the entity's Z axis.
+    pub billboard: bool,
+    /// Which layer the sprite is drawn in
+    pub layer: SpriteLayer,
+}
+
+/// Where a [`Sprite`] is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteLayer {
+    /// Part of the world, hidden behind anything in front of it
+    World,
+    /// Drawn on top of everything else, for HUD-style elements. Make the sprite's entity a child of the HMD to keep it
+    /// locked to the user's head.
+    HeadLocked,
+}
+
+impl Sprite {
+    /// Create a sprite showing all of the texture at `texture_id`, `size` metres across
+    pub fn new(texture_id: u32, size: Vec2) -> Self {
+        Self {
+            texture_id,
+            size,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Self {
+            texture_id: NO_TEXTURE,
+            uv_offset: Vec2::ZERO,
+            uv_extent: Vec2::ONE,
+            color: Vec4::ONE,
+            size: Vec2::splat(0.1),
+            billboard: true,
+            layer: SpriteLayer::World,
+        }
+    }
+}
@@ -20,7 +20,10 @@ pub struct Stage;
 use glam::Affine3A;
 use hecs::With;
 
-use crate::{components::GlobalTransform, hecs::World};
+use crate::{
+    components::{GlobalTransform, LocalTransform},
+    hecs::World,
+};
 
 /// Get the transform of the stage in global space.
 pub fn get_global_from_stage(world: &World) -> Affine3A {
@@ -32,3 +35,14 @@ pub fn get_global_from_stage(world: &World) -> Affine3A {
         .map(|(_, global_transform)| global_transform.0)
         .unwrap_or(Affine3A::IDENTITY)
 }
+
+/// Move the stage, and the player with it, to `global_from_stage`. The stage is where the runtime's tracking space is
+/// in the world, so this is how to move the player around without moving their head.
+pub fn set_global_from_stage(world: &mut World, global_from_stage: &Affine3A) {
+    for (_, (local_transform, global_transform)) in
+        world.query_mut::<With<(&mut LocalTransform, &mut GlobalTransform), &Stage>>()
+    {
+        local_transform.update_from_affine(global_from_stage);
+        global_transform.0 = *global_from_stage;
+    }
+}
@@ -0,0 +1,8 @@
+/// Component that marks an entity's meshes as static level geometry - walls, floors, furniture - that never moves or
+/// changes shape. Only static geometry is traced against for ray traced contact shadows and ambient occlusion, since
+/// its acceleration structure is built once, when ray queries are turned on.
+///
+/// See [`crate::contexts::RenderContext::enable_ray_query`]. Entities also need a [`super::Mesh`] and
+/// [`super::GlobalTransform`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StaticGeometry;
@@ -37,6 +37,10 @@ pub struct UIPanel {
     pub raw_input: egui::RawInput,
     /// A list of buttons in this panel
     pub buttons: Vec<UIPanelButton>,
+    /// A progress bar to show under the text, from 0 to 1
+    pub progress: Option<f32>,
+    /// Which of [`crate::localization::Localization`]'s fonts the panel's egui context has
+    pub(crate) fonts_version: u64,
 }
 
 /// A button for a panel
@@ -119,6 +123,8 @@ pub fn add_ui_panel_to_world(
             egui_context,
             raw_input,
             buttons,
+            progress: None,
+            fonts_version: 0,
         },
         LocalTransform {
             translation,
@@ -1,16 +1,40 @@
 use std::sync::Arc;
 
-use crate::components::{sound_emitter::SoundState, SoundEmitter};
+use crate::{
+    components::{
+        sound_emitter::{SoundState, VirtualVoice},
+        SoundEmitter,
+    },
+    HothamResult,
+};
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
     Stream,
 };
-use oddio::{Frames, FramesSignal, Handle, Mixer, SpatialBuffered, SpatialScene, Stop};
+use glam::{Quat, Vec3};
+use oddio::{Frames, FramesSignal, Gain, Handle, Mixer, SpatialBuffered, SpatialScene, Stop};
 use symphonia::core::{audio::SampleBuffer, io::MediaSourceStream, probe::Hint};
 
 type MusicTrackHandle = Handle<Stop<FramesSignal<[f32; 2]>>>;
 use generational_arena::{Arena, Index};
 
+mod music_stream;
+pub use music_stream::MusicSource;
+use music_stream::{volume_to_db, MusicStream};
+
+/// The default for [`AudioContext::max_voices`]. Quest can comfortably mix this many spatialized sounds.
+pub const DEFAULT_MAX_VOICES: usize = 24;
+
+/// The default for [`AudioContext::audibility_threshold`], about 100m away from the listener
+pub const DEFAULT_AUDIBILITY_THRESHOLD: f32 = 0.01;
+
+/// The default for [`AudioContext::occluded_volume`]
+pub const DEFAULT_OCCLUDED_VOLUME: f32 = 0.3;
+
+/// The radius of every spatialized sound, in metres. `oddio` makes sounds quieter the further they are outside it,
+/// which is undone so each sound's [`crate::components::Attenuation`] decides how loud it is.
+const SOUND_RADIUS: f32 = 1.0;
+
 /// Wrapper around `oddio` and `cpal` to represent the audio playing in an application
 /// Used by `audio_system`
 pub struct AudioContext {
@@ -22,8 +46,21 @@ pub struct AudioContext {
     pub stream: Stream,
     /// The currently playing music track
     pub current_music_track: Option<MusicTrack>,
+    /// The most sound effects that will be mixed at once. Any more are virtualized - see
+    /// [`crate::components::sound_emitter::VirtualVoice`].
+    pub max_voices: usize,
+    /// Sound effects quieter than this, from 0 to 1, are virtualized, however many voices are free
+    pub audibility_threshold: f32,
+    /// Should sounds be muffled when there's something between them and the listener? `audio_system` casts a ray
+    /// against the `PhysicsContext` for each sound playing every frame, so this is off by default.
+    pub occlusion: bool,
+    /// How loud a sound is, from 0 to 1, when it's completely occluded
+    pub occluded_volume: f32,
     music_tracks_inner: Arena<Arc<Frames<[f32; 2]>>>,
     music_track_handle: Option<MusicTrackHandle>,
+    streaming_music: Option<MusicStream>,
+    /// Streamed music that's fading out after being replaced
+    fading_music: Vec<MusicStream>,
 }
 
 /// A music track
@@ -86,7 +123,13 @@ impl Default for AudioContext {
             stream,
             music_tracks_inner: Arena::new(),
             music_track_handle: None,
+            streaming_music: None,
+            fading_music: Vec::new(),
             current_music_track: None,
+            max_voices: DEFAULT_MAX_VOICES,
+            audibility_threshold: DEFAULT_AUDIBILITY_THRESHOLD,
+            occlusion: false,
+            occluded_volume: DEFAULT_OCCLUDED_VOLUME,
         }
     }
 }
@@ -100,28 +143,81 @@ impl AudioContext {
     }
 
     /// Play a piece of audio
-    pub fn play_audio(
+    pub fn play_audio(&mut self, sound_emitter: &mut SoundEmitter, position: Vec3, velocity: Vec3) {
+        self.play_audio_from(sound_emitter, position, velocity, 0.);
+    }
+
+    fn play_audio_from(
         &mut self,
         sound_emitter: &mut SoundEmitter,
-        position: mint::Point3<f32>,
-        velocity: mint::Vector3<f32>,
+        position: Vec3,
+        velocity: Vec3,
+        start_seconds: f64,
     ) {
-        let signal: oddio::FramesSignal<_> =
-            oddio::FramesSignal::from(sound_emitter.frames.clone());
+        let signal = oddio::FramesSignal::new(sound_emitter.frames.clone(), start_seconds);
+        let gain = self.gain(sound_emitter, position);
         let handle = self.scene_handle.control().play_buffered(
-            signal,
+            Gain::new(signal, gain),
             oddio::SpatialOptions {
-                position,
-                velocity,
-                radius: 1.0, //
+                position: position.into(),
+                velocity: velocity.into(),
+                radius: SOUND_RADIUS,
             },
             1000.0,
         );
         sound_emitter.handle = Some(handle);
+        sound_emitter.virtual_voice = None;
+    }
+
+    /// Stop mixing a piece of audio, but keep track of where it's up to so it can be revived later
+    pub fn virtualize_audio(&mut self, sound_emitter: &mut SoundEmitter) {
+        if let Some(mut handle) = sound_emitter.handle.take() {
+            let position = handle.control::<FramesSignal<_>, _>().playback_position();
+            handle.control::<Stop<_>, _>().stop();
+            sound_emitter.virtual_voice = Some(VirtualVoice {
+                position,
+                paused: false,
+            });
+        }
+    }
+
+    /// Start mixing a virtualized piece of audio again, from where it's up to
+    pub fn revive_audio(
+        &mut self,
+        sound_emitter: &mut SoundEmitter,
+        position: Vec3,
+        velocity: Vec3,
+    ) {
+        if let Some(virtual_voice) = sound_emitter.virtual_voice {
+            self.play_audio_from(sound_emitter, position, velocity, virtual_voice.position);
+        }
+    }
+
+    /// Roughly how loud a sound at `position`, relative to the listener, is, from 0 to 1, if it has the default
+    /// [`crate::components::Attenuation`] and isn't occluded
+    pub fn audibility(&self, position: Vec3) -> f32 {
+        crate::components::Attenuation::default().volume(position.length())
+    }
+
+    /// How loud `sound_emitter` is at `position`, relative to the listener, from 0 to 1, taking its attenuation and
+    /// occlusion into account
+    pub fn sound_emitter_volume(&self, sound_emitter: &SoundEmitter, position: Vec3) -> f32 {
+        let occlusion = sound_emitter.occlusion.clamp(0., 1.);
+        let occluded_volume = 1. + (self.occluded_volume - 1.) * occlusion;
+        sound_emitter.attenuation.volume(position.length()) * occluded_volume
+    }
+
+    /// The gain for `sound_emitter` at `position`, in decibels, after undoing `oddio`'s own distance attenuation
+    fn gain(&self, sound_emitter: &SoundEmitter, position: Vec3) -> f32 {
+        let spatial_volume = SOUND_RADIUS / position.length().max(SOUND_RADIUS);
+        volume_to_db(self.sound_emitter_volume(sound_emitter, position) / spatial_volume)
     }
 
     /// Resume a piece of audio
     pub fn resume_audio(&mut self, sound_emitter: &mut SoundEmitter) {
+        if let Some(virtual_voice) = sound_emitter.virtual_voice.as_mut() {
+            virtual_voice.paused = false;
+        }
         if let Some(h) = sound_emitter.handle.as_mut() {
             h.control::<Stop<_>, _>().resume()
         }
@@ -129,6 +225,9 @@ impl AudioContext {
 
     /// Pause a piece of audio
     pub fn pause_audio(&mut self, sound_emitter: &mut SoundEmitter) {
+        if let Some(virtual_voice) = sound_emitter.virtual_voice.as_mut() {
+            virtual_voice.paused = true;
+        }
         if let Some(h) = sound_emitter.handle.as_mut() {
             h.control::<Stop<_>, _>().pause()
         }
@@ -136,6 +235,7 @@ impl AudioContext {
 
     /// Stop a piece of audio
     pub fn stop_audio(&mut self, sound_emitter: &mut SoundEmitter) {
+        sound_emitter.virtual_voice = None;
         if let Some(h) = sound_emitter.handle.as_mut() {
             h.control::<Stop<_>, _>().stop()
         }
@@ -144,17 +244,24 @@ impl AudioContext {
     pub(crate) fn update_motion(
         &mut self,
         audio_source: &mut SoundEmitter,
-        position: mint::Point3<f32>,
-        velocity: mint::Vector3<f32>,
+        position: Vec3,
+        velocity: Vec3,
     ) {
+        let gain = self.gain(audio_source, position);
         if let Some(h) = audio_source.handle.as_mut() {
-            h.control::<SpatialBuffered<_>, _>()
-                .set_motion(position, velocity, false)
+            h.control::<SpatialBuffered<_>, _>().set_motion(
+                position.into(),
+                velocity.into(),
+                false,
+            );
+            h.control::<Gain<_>, _>().set_gain(gain);
         };
     }
 
-    pub(crate) fn update_listener_rotation(&mut self, rotation: mint::Quaternion<f32>) {
-        self.scene_handle.control().set_listener_rotation(rotation);
+    pub(crate) fn update_listener_rotation(&mut self, rotation: Quat) {
+        self.scene_handle
+            .control()
+            .set_listener_rotation(rotation.into());
     }
 
     /// Add a music track
@@ -209,6 +316,82 @@ impl AudioContext {
         }
     }
 
+    /// Stream music from `source`, replacing any that's already streaming.
+    ///
+    /// Unlike [`AudioContext::add_music_track`], the music is decoded a little at a time on a background thread as
+    /// it plays, so only a fraction of a second of it is ever in memory. Use this for long pieces of music.
+    pub fn play_streaming_music(&mut self, source: impl Into<MusicSource>) -> HothamResult<()> {
+        self.crossfade_streaming_music(source, 0.)
+    }
+
+    /// Stream music from `source`, fading it in over `duration` seconds while any music that's already streaming
+    /// fades out.
+    pub fn crossfade_streaming_music(
+        &mut self,
+        source: impl Into<MusicSource>,
+        duration: f32,
+    ) -> HothamResult<()> {
+        let start_volume = if duration > 0. { 0. } else { 1. };
+        let mut stream =
+            MusicStream::start(&mut self.mixer_handle, source.into(), 0., start_volume)?;
+        stream.fade_to(1., duration);
+        self.stop_streaming_music(duration);
+        self.streaming_music = Some(stream);
+        Ok(())
+    }
+
+    /// Stop the streaming music, fading it out over `fade_out` seconds.
+    pub fn stop_streaming_music(&mut self, fade_out: f32) {
+        if let Some(mut stream) = self.streaming_music.take() {
+            if fade_out > 0. {
+                stream.fade_to(0., fade_out);
+                self.fading_music.push(stream);
+            } else {
+                stream.stop();
+            }
+        }
+    }
+
+    /// Pause the streaming music
+    pub fn pause_streaming_music(&mut self) {
+        if let Some(stream) = self.streaming_music.as_mut() {
+            stream.pause();
+        }
+    }
+
+    /// Resume the streaming music
+    pub fn resume_streaming_music(&mut self) {
+        if let Some(stream) = self.streaming_music.as_mut() {
+            stream.resume();
+        }
+    }
+
+    /// Jump to `seconds` into the streaming music.
+    pub fn seek_streaming_music(&mut self, seconds: f64) -> HothamResult<()> {
+        if let Some(stream) = self.streaming_music.as_mut() {
+            *stream = stream.restart_at(&mut self.mixer_handle, seconds)?;
+        }
+        Ok(())
+    }
+
+    /// Get the status of the streaming music
+    pub fn streaming_music_status(&self) -> SoundState {
+        self.streaming_music
+            .as_ref()
+            .map_or(SoundState::Stopped, MusicStream::status)
+    }
+
+    /// Move crossfades on, and let go of streams that have finished.
+    pub(crate) fn update_streaming_music(&mut self, delta_time: f32) {
+        if let Some(stream) = self.streaming_music.as_mut() {
+            if !stream.update(delta_time) {
+                self.streaming_music = None;
+            }
+        }
+        self.fading_music
+            .retain_mut(|stream| stream.update(delta_time));
+    }
+
     /// Create an empty MusicTrack. Useful for testing
     pub fn dummy_track(&mut self) -> MusicTrack {
         let frames = oddio::Frames::from_slice(0, &[]);
@@ -0,0 +1,407 @@
+use std::{
+    fs::File,
+    io::Cursor,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use oddio::{Gain, Handle, Mixer, Stop, Stream};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::Decoder,
+    formats::{FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream},
+    probe::Hint,
+    units::Time,
+};
+
+use crate::{components::sound_emitter::SoundState, HothamError, HothamResult};
+
+/// How much decoded music is kept ahead of the mixer, in seconds
+const BUFFER_SECONDS: f32 = 0.5;
+/// How long the decoding thread waits for the mixer to make room in the buffer
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How quiet a silent stream is, in decibels
+const SILENCE_DB: f32 = -60.;
+
+type StreamHandle = Handle<Stop<Gain<Stream<[f32; 2]>>>>;
+
+/// Where a piece of streamed music comes from. MP3 and Ogg Vorbis files can be streamed.
+#[derive(Debug, Clone)]
+pub enum MusicSource {
+    /// A file on disk, read a little at a time
+    File(PathBuf),
+    /// A file that's already in memory, eg. from `include_bytes!`. It's kept encoded, and only decoded as it plays.
+    Bytes(Arc<[u8]>),
+}
+
+impl From<PathBuf> for MusicSource {
+    fn from(path: PathBuf) -> Self {
+        MusicSource::File(path)
+    }
+}
+
+impl From<Vec<u8>> for MusicSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        MusicSource::Bytes(bytes.into())
+    }
+}
+
+impl MusicSource {
+    fn open(&self) -> HothamResult<(Box<dyn MediaSource>, Hint)> {
+        let mut hint = Hint::new();
+        let media_source: Box<dyn MediaSource> = match self {
+            MusicSource::File(path) => {
+                if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+                    hint.with_extension(extension);
+                }
+                Box::new(File::open(path)?)
+            }
+            MusicSource::Bytes(bytes) => Box::new(Cursor::new(bytes.clone())),
+        };
+        Ok((media_source, hint))
+    }
+}
+
+/// A piece of music being decoded a little at a time on a background thread, and fed to the mixer.
+pub(crate) struct MusicStream {
+    source: MusicSource,
+    handle: Arc<Mutex<StreamHandle>>,
+    cancelled: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    volume: f32,
+    fade: Option<Fade>,
+}
+
+impl MusicStream {
+    /// Start playing `source` from `start_seconds` in, at `volume`.
+    pub(crate) fn start(
+        mixer_handle: &mut Handle<Mixer<[f32; 2]>>,
+        source: MusicSource,
+        start_seconds: f64,
+        volume: f32,
+    ) -> HothamResult<Self> {
+        // Open the file here, rather than on the decoding thread, so any problems with it are reported straight away.
+        let mut decoder = MusicDecoder::open(&source)?;
+        if start_seconds > 0. {
+            decoder.seek(start_seconds)?;
+        }
+
+        let buffer_size = (decoder.sample_rate as f32 * BUFFER_SECONDS) as usize;
+        let stream = Stream::new(decoder.sample_rate, buffer_size);
+        let handle = mixer_handle
+            .control()
+            .play(Gain::new(stream, volume_to_db(volume)));
+        let handle = Arc::new(Mutex::new(handle));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        {
+            let handle = handle.clone();
+            let cancelled = cancelled.clone();
+            let finished = finished.clone();
+            thread::Builder::new()
+                .name("hotham-music".into())
+                .spawn(move || feed(decoder, &handle, &cancelled, &finished))?;
+        }
+
+        Ok(Self {
+            source,
+            handle,
+            cancelled,
+            finished,
+            volume,
+            fade: None,
+        })
+    }
+
+    /// Play the same music from `seconds` in, keeping the volume and whether it's paused.
+    pub(crate) fn restart_at(
+        &self,
+        mixer_handle: &mut Handle<Mixer<[f32; 2]>>,
+        seconds: f64,
+    ) -> HothamResult<Self> {
+        let mut stream = Self::start(mixer_handle, self.source.clone(), seconds, self.volume)?;
+        stream.fade = self.fade;
+        if self.status() == SoundState::Paused {
+            stream.pause();
+        }
+        Ok(stream)
+    }
+
+    pub(crate) fn pause(&mut self) {
+        self.handle.lock().unwrap().control::<Stop<_>, _>().pause();
+    }
+
+    pub(crate) fn resume(&mut self) {
+        self.handle.lock().unwrap().control::<Stop<_>, _>().resume();
+    }
+
+    pub(crate) fn status(&self) -> SoundState {
+        let mut handle = self.handle.lock().unwrap();
+        let control = handle.control::<Stop<_>, _>();
+        if control.is_stopped() || self.finished.load(Ordering::Relaxed) {
+            SoundState::Stopped
+        } else if control.is_paused() {
+            SoundState::Paused
+        } else {
+            SoundState::Playing
+        }
+    }
+
+    /// Change the volume to `volume` over `duration` seconds.
+    pub(crate) fn fade_to(&mut self, volume: f32, duration: f32) {
+        self.fade = Some(Fade {
+            from: self.volume,
+            to: volume,
+            duration,
+            elapsed: 0.,
+        });
+    }
+
+    /// Move any fade on by `delta_time`. Returns false once the music has finished, or faded out.
+    pub(crate) fn update(&mut self, delta_time: f32) -> bool {
+        let paused = self.status() == SoundState::Paused;
+        if let Some(fade) = self.fade.as_mut() {
+            if !paused {
+                fade.elapsed += delta_time;
+            }
+            self.volume = fade.volume();
+            let faded_out = fade.is_done() && fade.to <= 0.;
+            if fade.is_done() {
+                self.fade = None;
+            }
+            if faded_out {
+                self.stop();
+                return false;
+            }
+            self.handle
+                .lock()
+                .unwrap()
+                .control::<Gain<_>, _>()
+                .set_gain(volume_to_db(self.volume));
+        }
+        self.status() != SoundState::Stopped
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.handle.lock().unwrap().control::<Stop<_>, _>().stop();
+    }
+}
+
+impl Drop for MusicStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A change in volume over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Fade {
+    fn volume(&self) -> f32 {
+        if self.is_done() {
+            return self.to;
+        }
+        let t = self.elapsed / self.duration;
+        self.from + (self.to - self.from) * t
+    }
+
+    fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Turn a volume from 0 to 1 into the gain `oddio` expects.
+pub(super) fn volume_to_db(volume: f32) -> f32 {
+    if volume <= 0. {
+        return SILENCE_DB;
+    }
+    (20. * volume.log10()).max(SILENCE_DB)
+}
+
+/// Decode `decoder` into the stream a little at a time, waiting for the mixer to make room, until the music ends or
+/// the stream is cancelled.
+fn feed(
+    mut decoder: MusicDecoder,
+    handle: &Mutex<StreamHandle>,
+    cancelled: &AtomicBool,
+    finished: &AtomicBool,
+) {
+    while let Some(frames) = decoder.next_chunk() {
+        let mut written = 0;
+        while written < frames.len() {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            written += handle
+                .lock()
+                .unwrap()
+                .control::<Stream<_>, _>()
+                .write(&frames[written..]);
+            if written < frames.len() {
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    // Let the mixer play what's left in the buffer before saying we're done.
+    let mut remaining = Duration::from_secs_f32(BUFFER_SECONDS);
+    while !remaining.is_zero() {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        thread::sleep(POLL_INTERVAL);
+        if !handle.lock().unwrap().control::<Stop<_>, _>().is_paused() {
+            remaining = remaining.saturating_sub(POLL_INTERVAL);
+        }
+    }
+    finished.store(true, Ordering::Relaxed);
+}
+
+/// Reads and decodes a music file one packet at a time.
+struct MusicDecoder {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+}
+
+impl MusicDecoder {
+    fn open(source: &MusicSource) -> HothamResult<Self> {
+        let (media_source, hint) = source.open()?;
+        let mss = MediaSourceStream::new(media_source, Default::default());
+        let probed = symphonia::default::get_probe()
+            .format(&hint, mss, &Default::default(), &Default::default())
+            .map_err(invalid_audio)?;
+
+        let reader = probed.format;
+        let track = reader
+            .default_track()
+            .ok_or_else(|| invalid_audio("it has no audio tracks"))?;
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &Default::default())
+            .map_err(invalid_audio)?;
+        let sample_rate = decoder
+            .codec_params()
+            .sample_rate
+            .ok_or_else(|| invalid_audio("its sample rate is unknown"))?;
+
+        Ok(Self {
+            reader,
+            decoder,
+            track_id,
+            sample_rate,
+        })
+    }
+
+    fn seek(&mut self, seconds: f64) -> HothamResult<()> {
+        self.reader
+            .seek(
+                SeekMode::Coarse,
+                SeekTo::Time {
+                    time: Time::from(seconds),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(invalid_audio)?;
+        self.decoder.reset();
+        Ok(())
+    }
+
+    /// Decode the next packet into stereo frames, or `None` once the music has ended.
+    fn next_chunk(&mut self) -> Option<Vec<[f32; 2]>> {
+        loop {
+            let packet = match self.reader.next_packet() {
+                Ok(packet) => packet,
+                // The end of the file is reported as an error, too.
+                Err(_) => return None,
+            };
+
+            // If the packet does not belong to the music's track, skip over it.
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let channels = decoded.spec().channels.count();
+                    let mut sample_buf =
+                        SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+                    sample_buf.copy_interleaved_ref(decoded);
+                    return Some(stereo_frames(sample_buf.samples(), channels));
+                }
+                Err(err) => {
+                    eprintln!(
+                        "[HOTHAM_AUDIO_CONTEXT] Error while decoding music: {:?}",
+                        err
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Turn interleaved samples with any number of channels into stereo frames: mono is played through both speakers, and
+/// any channels past the first two are dropped.
+fn stereo_frames(samples: &[f32], channels: usize) -> Vec<[f32; 2]> {
+    match channels {
+        0 => Vec::new(),
+        1 => samples.iter().map(|s| [*s, *s]).collect(),
+        _ => samples
+            .chunks_exact(channels)
+            .map(|frame| [frame[0], frame[1]])
+            .collect(),
+    }
+}
+
+fn invalid_audio(reason: impl ToString) -> HothamError {
+    HothamError::InvalidAudio {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_music_stream_helpers() {
+        let mut fade = Fade {
+            from: 1.,
+            to: 0.,
+            duration: 2.,
+            elapsed: 0.,
+        };
+        fade.elapsed = 0.5;
+        assert_relative_eq!(fade.volume(), 0.75);
+        fade.elapsed = 3.;
+        assert!(fade.is_done());
+        assert_relative_eq!(fade.volume(), 0.);
+
+        assert_relative_eq!(volume_to_db(1.), 0.);
+        assert_relative_eq!(volume_to_db(0.1), -20.);
+        assert_relative_eq!(volume_to_db(0.), SILENCE_DB);
+
+        assert_eq!(stereo_frames(&[0.1, 0.2], 1), vec![[0.1, 0.1], [0.2, 0.2]]);
+        assert_eq!(
+            stereo_frames(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], 3),
+            vec![[0.1, 0.2], [0.4, 0.5]]
+        );
+    }
+}
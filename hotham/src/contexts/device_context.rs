@@ -0,0 +1,343 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use openxr as xr;
+
+use super::XrContext;
+
+/// How often the battery and thermal state are read. Reading them goes through Java, so not every frame.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The health of the device: its battery, how hot it's running, and the CPU and GPU performance levels.
+///
+/// Apps can raise the performance level with [`DeviceContext::set_performance_level`] for heavy scenes, and dial back
+/// when [`DeviceContext::thermal_status`] says the device is getting hot. Levels are set with
+/// `XR_EXT_performance_settings`, which on Quest controls the CPU and GPU clocks. When the runtime itself changes a
+/// level, eg. because the device is throttling, an [`crate::EngineEvent::PerformanceNotification`] is sent.
+///
+/// On desktop there's no battery or thermal information, so those read as `None` and [`ThermalStatus::Unknown`].
+#[derive(Debug, Clone, Default)]
+pub struct DeviceContext {
+    battery: Option<BatteryState>,
+    thermal_status: ThermalStatus,
+    thermal_headroom: Option<f32>,
+    cpu_level: Option<PerformanceLevel>,
+    gpu_level: Option<PerformanceLevel>,
+    last_refreshed: Option<Instant>,
+}
+
+/// The battery's charge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryState {
+    /// How full the battery is, from 0 to 1
+    pub level: f32,
+    /// Is the device plugged in and charging?
+    pub charging: bool,
+}
+
+/// How hot the device is running, from Android's `PowerManager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ThermalStatus {
+    /// There's no thermal information on this platform
+    #[default]
+    Unknown,
+    /// Not throttling
+    None,
+    /// Light throttling that shouldn't affect the experience
+    Light,
+    /// Noticeable throttling
+    Moderate,
+    /// Heavy throttling
+    Severe,
+    /// The platform is doing everything it can to cool down
+    Critical,
+    /// Key components are shutting down
+    Emergency,
+    /// The device is about to shut down
+    Shutdown,
+}
+
+impl ThermalStatus {
+    /// The status for one of Android's `PowerManager.THERMAL_STATUS_*` values.
+    pub fn from_android(status: i32) -> Self {
+        match status {
+            0 => ThermalStatus::None,
+            1 => ThermalStatus::Light,
+            2 => ThermalStatus::Moderate,
+            3 => ThermalStatus::Severe,
+            4 => ThermalStatus::Critical,
+            5 => ThermalStatus::Emergency,
+            6 => ThermalStatus::Shutdown,
+            _ => ThermalStatus::Unknown,
+        }
+    }
+
+    /// Is the device throttling enough that the app should do less work?
+    pub fn is_throttling(&self) -> bool {
+        *self >= ThermalStatus::Moderate
+    }
+}
+
+/// The part of the device a performance level applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerformanceDomain {
+    /// The CPU
+    Cpu,
+    /// The GPU
+    Gpu,
+}
+
+/// How hard the runtime should run the CPU or GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerformanceLevel {
+    /// As little power as possible, for simple scenes
+    PowerSavings,
+    /// Low power the device can keep up indefinitely
+    SustainedLow,
+    /// High power the device can keep up indefinitely - the default
+    SustainedHigh,
+    /// More power than the device can keep up for long, eg. while loading
+    Boost,
+}
+
+/// Which of the runtime's jobs a [`PerformanceNotification`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerformanceSubDomain {
+    /// Compositing frames
+    Compositing,
+    /// The app's rendering
+    Rendering,
+    /// Keeping the device cool
+    Thermal,
+}
+
+/// How well the runtime is keeping up, in a [`PerformanceNotification`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerformanceNotificationLevel {
+    /// Everything's fine
+    Normal,
+    /// Close to not keeping up
+    Warning,
+    /// Not keeping up
+    Impaired,
+}
+
+/// The runtime changed how well it's keeping up in part of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerformanceNotification {
+    /// The CPU or GPU
+    pub domain: PerformanceDomain,
+    /// Which of the runtime's jobs it's about
+    pub sub_domain: PerformanceSubDomain,
+    /// The level before
+    pub from: PerformanceNotificationLevel,
+    /// The level now
+    pub to: PerformanceNotificationLevel,
+}
+
+impl DeviceContext {
+    /// The battery's charge, if the device has a battery
+    pub fn battery(&self) -> Option<BatteryState> {
+        self.battery
+    }
+
+    /// How hot the device is running
+    pub fn thermal_status(&self) -> ThermalStatus {
+        self.thermal_status
+    }
+
+    /// How close the device is to throttling: 0 is cool, and 1 is where it starts to throttle. Only on Android 11 and
+    /// later.
+    pub fn thermal_headroom(&self) -> Option<f32> {
+        self.thermal_headroom
+    }
+
+    /// The level last set for `domain` with [`DeviceContext::set_performance_level`], if any
+    pub fn performance_level(&self, domain: PerformanceDomain) -> Option<PerformanceLevel> {
+        match domain {
+            PerformanceDomain::Cpu => self.cpu_level,
+            PerformanceDomain::Gpu => self.gpu_level,
+        }
+    }
+
+    /// Ask the runtime to run `domain` at `level`. Fails if the runtime doesn't support `XR_EXT_performance_settings`.
+    pub fn set_performance_level(
+        &mut self,
+        xr_context: &XrContext,
+        domain: PerformanceDomain,
+        level: PerformanceLevel,
+    ) -> Result<()> {
+        let performance_settings = xr_context
+            .instance
+            .exts()
+            .ext_performance_settings
+            .ok_or_else(|| anyhow!("The runtime doesn't support XR_EXT_performance_settings"))?;
+
+        let xr_domain = match domain {
+            PerformanceDomain::Cpu => xr::PerfSettingsDomainEXT::CPU,
+            PerformanceDomain::Gpu => xr::PerfSettingsDomainEXT::GPU,
+        };
+        let xr_level = match level {
+            PerformanceLevel::PowerSavings => xr::PerfSettingsLevelEXT::POWER_SAVINGS,
+            PerformanceLevel::SustainedLow => xr::PerfSettingsLevelEXT::SUSTAINED_LOW,
+            PerformanceLevel::SustainedHigh => xr::PerfSettingsLevelEXT::SUSTAINED_HIGH,
+            PerformanceLevel::Boost => xr::PerfSettingsLevelEXT::BOOST,
+        };
+        let result = unsafe {
+            (performance_settings.perf_settings_set_performance_level)(
+                xr_context.session.as_raw(),
+                xr_domain,
+                xr_level,
+            )
+        };
+        if result.into_raw() < 0 {
+            return Err(anyhow!(
+                "Unable to set {:?} to {:?} - {:?}",
+                domain,
+                level,
+                result
+            ));
+        }
+
+        match domain {
+            PerformanceDomain::Cpu => self.cpu_level = Some(level),
+            PerformanceDomain::Gpu => self.gpu_level = Some(level),
+        }
+        Ok(())
+    }
+
+    /// Read the battery and thermal state again, if it's been long enough. Called by the engine every frame.
+    pub(crate) fn update(&mut self, now: Instant) {
+        if self
+            .last_refreshed
+            .map_or(false, |last| now.duration_since(last) < REFRESH_INTERVAL)
+        {
+            return;
+        }
+        self.last_refreshed = Some(now);
+        self.refresh();
+    }
+
+    #[cfg(target_os = "android")]
+    fn refresh(&mut self) {
+        use crate::contexts::platform_context::with_activity;
+        use jni::{
+            objects::{JObject, JValue},
+            JNIEnv,
+        };
+
+        fn system_service<'a>(
+            env: &JNIEnv<'a>,
+            activity: JObject<'a>,
+            name: &str,
+        ) -> jni::errors::Result<JObject<'a>> {
+            env.call_method(
+                activity,
+                "getSystemService",
+                "(Ljava/lang/String;)Ljava/lang/Object;",
+                &[JValue::Object(env.new_string(name)?.into())],
+            )?
+            .l()
+        }
+
+        fn battery(env: &JNIEnv, activity: JObject) -> jni::errors::Result<BatteryState> {
+            let battery_manager = system_service(env, activity, "batterymanager")?;
+            // BatteryManager.BATTERY_PROPERTY_CAPACITY
+            let capacity = env
+                .call_method(battery_manager, "getIntProperty", "(I)I", &[JValue::Int(4)])?
+                .i()?;
+            let charging = env
+                .call_method(battery_manager, "isCharging", "()Z", &[])?
+                .z()?;
+            Ok(BatteryState {
+                level: capacity.clamp(0, 100) as f32 / 100.,
+                charging,
+            })
+        }
+
+        fn thermal_status(env: &JNIEnv, activity: JObject) -> jni::errors::Result<i32> {
+            let power_manager = system_service(env, activity, "power")?;
+            env.call_method(power_manager, "getCurrentThermalStatus", "()I", &[])?
+                .i()
+        }
+
+        fn thermal_headroom(env: &JNIEnv, activity: JObject) -> jni::errors::Result<f32> {
+            let power_manager = system_service(env, activity, "power")?;
+            // How close to throttling the device will be in 10 seconds.
+            env.call_method(
+                power_manager,
+                "getThermalHeadroom",
+                "(I)F",
+                &[JValue::Int(10)],
+            )?
+            .f()
+        }
+
+        // Each is read on its own, as older versions of Android don't have the thermal methods.
+        self.battery = with_activity(battery).ok();
+        self.thermal_status = with_activity(thermal_status)
+            .map_or(ThermalStatus::Unknown, ThermalStatus::from_android);
+        self.thermal_headroom = with_activity(thermal_headroom)
+            .ok()
+            .filter(|headroom| !headroom.is_nan());
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn refresh(&mut self) {}
+}
+
+impl PerformanceNotification {
+    /// Convert an `XR_EXT_performance_settings` event.
+    pub(crate) fn from_xr(event: &xr::event::PerfSettingsEXT) -> Self {
+        let level = |level| match level {
+            xr::PerfSettingsNotificationLevelEXT::WARNING => PerformanceNotificationLevel::Warning,
+            xr::PerfSettingsNotificationLevelEXT::IMPAIRED => {
+                PerformanceNotificationLevel::Impaired
+            }
+            _ => PerformanceNotificationLevel::Normal,
+        };
+        Self {
+            domain: match event.domain() {
+                xr::PerfSettingsDomainEXT::GPU => PerformanceDomain::Gpu,
+                _ => PerformanceDomain::Cpu,
+            },
+            sub_domain: match event.sub_domain() {
+                xr::PerfSettingsSubDomainEXT::COMPOSITING => PerformanceSubDomain::Compositing,
+                xr::PerfSettingsSubDomainEXT::RENDERING => PerformanceSubDomain::Rendering,
+                _ => PerformanceSubDomain::Thermal,
+            },
+            from: level(event.from_level()),
+            to: level(event.to_level()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_thermal_status() {
+        assert_eq!(ThermalStatus::from_android(0), ThermalStatus::None);
+        assert_eq!(ThermalStatus::from_android(3), ThermalStatus::Severe);
+        assert_eq!(ThermalStatus::from_android(-1), ThermalStatus::Unknown);
+        assert!(!ThermalStatus::Light.is_throttling());
+        assert!(ThermalStatus::Moderate.is_throttling());
+        assert!(!ThermalStatus::Unknown.is_throttling());
+    }
+
+    #[test]
+    pub fn test_refresh_interval() {
+        let mut device_context = DeviceContext::default();
+        let start = Instant::now();
+        device_context.update(start);
+        assert_eq!(device_context.last_refreshed, Some(start));
+
+        device_context.update(start + Duration::from_millis(500));
+        assert_eq!(device_context.last_refreshed, Some(start));
+
+        let later = start + REFRESH_INTERVAL;
+        device_context.update(later);
+        assert_eq!(device_context.last_refreshed, Some(later));
+    }
+}
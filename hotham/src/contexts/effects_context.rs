@@ -0,0 +1,429 @@
+use glam::{Vec3, Vec4};
+
+/// The most a shake can move the world, in meters. Larger movements of the whole world are a common cause of
+/// discomfort in VR, so amplitudes above this are capped.
+pub const MAX_SHAKE_AMPLITUDE: f32 = 0.03;
+
+/// The fastest the world can shake, in Hz. Faster shaking reads as vibration of the whole world rather than an impact.
+pub const MAX_SHAKE_FREQUENCY: f32 = 10.;
+
+/// The slowest a shake can fade out, in strength per second. Shakes that hang around are worse for comfort than
+/// strong ones that are over quickly, so no shake may last longer than a second.
+pub const MIN_SHAKE_DECAY: f32 = 1.;
+
+/// How a shake feels. Values outside of the comfort caps are clamped when the shake is applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShakeSettings {
+    /// How far the world moves at full strength, in meters. Capped to [`MAX_SHAKE_AMPLITUDE`].
+    pub amplitude: f32,
+    /// How quickly the world moves back and forth, in Hz. Capped to [`MAX_SHAKE_FREQUENCY`].
+    pub frequency: f32,
+    /// How quickly a shake fades out, in strength per second. At least [`MIN_SHAKE_DECAY`].
+    pub decay: f32,
+}
+
+impl Default for ShakeSettings {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.015,
+            frequency: 6.,
+            decay: 2.,
+        }
+    }
+}
+
+impl ShakeSettings {
+    /// These settings, with the comfort caps applied
+    pub fn capped(&self) -> Self {
+        Self {
+            amplitude: self.amplitude.clamp(0., MAX_SHAKE_AMPLITUDE),
+            frequency: self.frequency.clamp(0., MAX_SHAKE_FREQUENCY),
+            decay: self.decay.max(MIN_SHAKE_DECAY),
+        }
+    }
+}
+
+/// How the comfort vignette looks and responds to motion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VignetteSettings {
+    /// Show the vignette at all? Some players find it more distracting than helpful, so offer a way to turn it off.
+    pub enabled: bool,
+    /// The color the edges of the view are covered with
+    pub color: Vec3,
+    /// How much of the view is left clear at full intensity, as a distance from the middle in normalized device
+    /// coordinates. 1 reaches the middle of the edges.
+    pub min_radius: f32,
+    /// How wide the soft edge of the vignette is, in normalized device coordinates
+    pub feather: f32,
+    /// How fast the player has to be moved for the vignette to be at full intensity, in meters per second
+    pub full_linear_speed: f32,
+    /// How fast the player has to be turned for the vignette to be at full intensity, in radians per second
+    pub full_angular_speed: f32,
+    /// How quickly the vignette closes in, in intensity per second
+    pub onset: f32,
+    /// How quickly the vignette opens up again once the motion stops, in intensity per second
+    pub release: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: Vec3::ZERO,
+            min_radius: 0.6,
+            feather: 0.3,
+            full_linear_speed: 3.,
+            full_angular_speed: std::f32::consts::FRAC_PI_2,
+            onset: 4.,
+            release: 2.,
+        }
+    }
+}
+
+/// Something that happened to a fade this frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeEvent {
+    /// The view has finished fading out, and is now completely covered. This is the time to load a scene or teleport.
+    FadedOut,
+    /// The view has finished fading in, and can be seen again.
+    FadedIn,
+}
+
+/// A fade of the whole view to or from a color.
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    color: Vec3,
+    from_alpha: f32,
+    to_alpha: f32,
+    duration: f32,
+    elapsed: f32,
+    finished: bool,
+}
+
+impl Fade {
+    fn alpha(&self) -> f32 {
+        if self.duration <= 0. {
+            return self.to_alpha;
+        }
+        let s = (self.elapsed / self.duration).clamp(0., 1.);
+        self.from_alpha + (self.to_alpha - self.from_alpha) * s
+    }
+}
+
+/// Effects that are applied to the whole view, for impact feedback and transitions.
+///
+/// Shaking the view matrices in a headset is a fast way to make players sick, so instead a shake moves the world
+/// content a small amount around the player, leaving their head tracking untouched.
+///
+/// Fades cover both eyes with a color, for scene loads, teleports and comfort snaps. Wait for a
+/// [`FadeEvent::FadedOut`] before making the change, then fade back in.
+///
+/// The vignette narrows the view while the player is moved artificially, eg. with a thumbstick, which many players
+/// need to stay comfortable. Drive it each frame with how fast they're being moved; `character_controller_system` and
+/// smooth turning in `comfort_locomotion_system` already do.
+///
+/// Call `effects_system` each frame to apply them.
+///
+/// Basic usage:
+/// ```ignore
+/// if hit {
+///     engine.effects_context.shake(0.5);
+/// }
+///
+/// if teleport_requested {
+///     engine.effects_context.fade_out(0.2, Vec3::ZERO);
+/// }
+/// if engine.effects_context.fade_events_this_frame.contains(&FadeEvent::FadedOut) {
+///     teleport(&mut engine.world);
+///     engine.effects_context.fade_in(0.2);
+/// }
+///
+/// engine.effects_context.drive_vignette(vehicle.linear_velocity, vehicle.angular_velocity);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EffectsContext {
+    /// How a shake feels
+    pub shake_settings: ShakeSettings,
+    /// Fades that finished during the last update
+    pub fade_events_this_frame: Vec<FadeEvent>,
+    /// How the comfort vignette looks and responds to motion
+    pub vignette_settings: VignetteSettings,
+    shake_strength: f32,
+    time: f32,
+    offset: Vec3,
+    fade: Option<Fade>,
+    vignette: f32,
+    vignette_target: f32,
+}
+
+impl EffectsContext {
+    /// Shake the world. `strength` is from 0 to 1, and adds to any shake that's already happening, up to 1.
+    pub fn shake(&mut self, strength: f32) {
+        self.shake_strength = (self.shake_strength + strength.max(0.)).min(1.);
+    }
+
+    /// Stop shaking immediately
+    pub fn stop_shaking(&mut self) {
+        self.shake_strength = 0.;
+        self.offset = Vec3::ZERO;
+    }
+
+    /// How strongly the world is shaking right now, from 0 to 1
+    pub fn shake_strength(&self) -> f32 {
+        self.shake_strength
+    }
+
+    /// How far the world content is currently moved, in meters
+    pub fn offset(&self) -> Vec3 {
+        self.offset
+    }
+
+    /// Cover the view with `color` over `duration` seconds, starting from however faded it is now. A
+    /// [`FadeEvent::FadedOut`] is sent when it's completely covered, and it stays covered until [`Self::fade_in`].
+    pub fn fade_out(&mut self, duration: f32, color: Vec3) {
+        self.start_fade(duration, color, 1.);
+    }
+
+    /// Uncover the view over `duration` seconds, starting from however faded it is now. A [`FadeEvent::FadedIn`] is
+    /// sent when it can be completely seen again.
+    pub fn fade_in(&mut self, duration: f32) {
+        let color = self.fade.map(|f| f.color).unwrap_or_default();
+        self.start_fade(duration, color, 0.);
+    }
+
+    fn start_fade(&mut self, duration: f32, color: Vec3, to_alpha: f32) {
+        self.fade = Some(Fade {
+            color,
+            from_alpha: self.fade_color().w,
+            to_alpha,
+            duration: duration.max(0.),
+            elapsed: 0.,
+            finished: false,
+        });
+    }
+
+    /// Is a fade in or out in progress?
+    pub fn is_fading(&self) -> bool {
+        self.fade.map(|f| !f.finished).unwrap_or(false)
+    }
+
+    /// The color covering the view, with how much of the view it covers in `w`
+    pub fn fade_color(&self) -> Vec4 {
+        match self.fade {
+            Some(fade) => fade.color.extend(fade.alpha()),
+            None => Vec4::ZERO,
+        }
+    }
+
+    /// Move any fade on by `delta_time` seconds, sending a [`FadeEvent`] if it finishes.
+    pub fn update_fade(&mut self, delta_time: f32) {
+        self.fade_events_this_frame.clear();
+        let fade = match &mut self.fade {
+            Some(fade) if !fade.finished => fade,
+            _ => return,
+        };
+
+        fade.elapsed += delta_time;
+        if fade.elapsed < fade.duration {
+            return;
+        }
+
+        fade.finished = true;
+        if fade.to_alpha > 0. {
+            self.fade_events_this_frame.push(FadeEvent::FadedOut);
+        } else {
+            self.fade_events_this_frame.push(FadeEvent::FadedIn);
+            self.fade = None;
+        }
+    }
+
+    /// Narrow the view for artificial motion at `linear_velocity`, in meters per second, and `angular_velocity`, in
+    /// radians per second. Only pass the motion the app is causing, not the player's own head movement. Call it every
+    /// frame the motion lasts.
+    pub fn drive_vignette(&mut self, linear_velocity: Vec3, angular_velocity: Vec3) {
+        let settings = &self.vignette_settings;
+        let linear = linear_velocity.length() / settings.full_linear_speed.max(f32::EPSILON);
+        let angular = angular_velocity.length() / settings.full_angular_speed.max(f32::EPSILON);
+        self.request_vignette(linear.max(angular));
+    }
+
+    /// Narrow the view with the vignette at `intensity`, from 0 to 1, this frame. The strongest request each frame is
+    /// used.
+    pub fn request_vignette(&mut self, intensity: f32) {
+        self.vignette_target = self.vignette_target.max(intensity.clamp(0., 1.));
+    }
+
+    /// How much the vignette is narrowing the view right now, from 0 to 1
+    pub fn vignette_intensity(&self) -> f32 {
+        self.vignette
+    }
+
+    /// Move the vignette towards the strongest intensity asked for since the last update, over `delta_time` seconds,
+    /// returning its new intensity.
+    pub fn update_vignette(&mut self, delta_time: f32) -> f32 {
+        let settings = self.vignette_settings;
+        let target = if settings.enabled {
+            self.vignette_target
+        } else {
+            0.
+        };
+        self.vignette_target = 0.;
+
+        let rate = if target > self.vignette {
+            settings.onset
+        } else {
+            settings.release
+        };
+        let step = rate.max(0.) * delta_time;
+        self.vignette += (target - self.vignette).clamp(-step, step);
+        self.vignette
+    }
+
+    /// Move any shake on by `delta_time` seconds, returning the new offset of the world content.
+    pub fn update_shake(&mut self, delta_time: f32) -> Vec3 {
+        let settings = self.shake_settings.capped();
+        self.time += delta_time;
+        self.shake_strength = (self.shake_strength - settings.decay * delta_time).max(0.);
+
+        if self.shake_strength == 0. {
+            self.time = 0.;
+            self.offset = Vec3::ZERO;
+            return self.offset;
+        }
+
+        // Squaring the strength makes small shakes subtle and keeps the fade out smooth. Each axis gets a slightly
+        // different frequency and phase so the movement doesn't look like it's on rails.
+        let amplitude = settings.amplitude * self.shake_strength * self.shake_strength;
+        let phase = self.time * settings.frequency * std::f32::consts::TAU;
+        let wave = Vec3::new(
+            phase.sin(),
+            (phase * 1.13 + 1.7).sin(),
+            (phase * 0.87 + 3.1).sin(),
+        );
+        self.offset = wave * (amplitude / 3f32.sqrt());
+        self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_shake() {
+        let mut effects_context = EffectsContext::default();
+        assert_eq!(effects_context.update_shake(0.1), Vec3::ZERO);
+
+        // Shakes should add up, but never go above full strength..
+        effects_context.shake(0.75);
+        effects_context.shake(0.75);
+        assert_eq!(effects_context.shake_strength(), 1.);
+
+        // ..move the world, but never further than the cap..
+        effects_context.shake_settings = ShakeSettings {
+            amplitude: 10.,
+            frequency: 100.,
+            decay: 0.,
+        };
+        let mut moved = false;
+        for _ in 0..10 {
+            let offset = effects_context.update_shake(1. / 72.);
+            assert!(offset.length() <= MAX_SHAKE_AMPLITUDE);
+            moved |= offset != Vec3::ZERO;
+        }
+        assert!(moved);
+
+        // ..and fade out within a second, even if asked not to.
+        for _ in 0..72 {
+            effects_context.update_shake(1. / 72.);
+        }
+        assert_eq!(effects_context.shake_strength(), 0.);
+        assert_eq!(effects_context.offset(), Vec3::ZERO);
+    }
+
+    #[test]
+    pub fn test_fade() {
+        let mut effects_context = EffectsContext::default();
+        assert_eq!(effects_context.fade_color(), Vec4::ZERO);
+
+        // Fading out should cover the view gradually..
+        let red = Vec3::new(1., 0., 0.);
+        effects_context.fade_out(1., red);
+        effects_context.update_fade(0.5);
+        assert_eq!(effects_context.fade_color(), Vec4::new(1., 0., 0., 0.5));
+        assert!(effects_context.is_fading());
+        assert!(effects_context.fade_events_this_frame.is_empty());
+
+        // ..say when it's done, once..
+        effects_context.update_fade(0.5);
+        assert_eq!(
+            effects_context.fade_events_this_frame,
+            vec![FadeEvent::FadedOut]
+        );
+        effects_context.update_fade(0.5);
+        assert!(effects_context.fade_events_this_frame.is_empty());
+
+        // ..and stay covered until it's faded back in.
+        assert_eq!(effects_context.fade_color(), Vec4::new(1., 0., 0., 1.));
+        effects_context.fade_in(0.);
+        effects_context.update_fade(0.);
+        assert_eq!(
+            effects_context.fade_events_this_frame,
+            vec![FadeEvent::FadedIn]
+        );
+        assert_eq!(effects_context.fade_color(), Vec4::ZERO);
+        assert!(!effects_context.is_fading());
+
+        // Interrupting a fade should carry on from where it got to.
+        effects_context.fade_out(1., red);
+        effects_context.update_fade(0.25);
+        effects_context.fade_in(1.);
+        effects_context.update_fade(0.5);
+        assert_eq!(effects_context.fade_color().w, 0.125);
+    }
+
+    #[test]
+    pub fn test_vignette() {
+        let mut effects_context = EffectsContext::default();
+        assert_eq!(effects_context.update_vignette(0.1), 0.);
+
+        // Moving the player should close the vignette in gradually, as far as their speed calls for..
+        effects_context.drive_vignette(Vec3::new(0., 0., -1.5), Vec3::ZERO);
+        assert_relative_eq!(effects_context.update_vignette(0.1), 0.4);
+        effects_context.drive_vignette(Vec3::new(0., 0., -1.5), Vec3::ZERO);
+        assert_relative_eq!(effects_context.update_vignette(0.1), 0.5);
+
+        // ..with the fastest motion winning..
+        effects_context.drive_vignette(Vec3::ZERO, Vec3::new(0., 10., 0.));
+        effects_context.request_vignette(0.2);
+        assert_relative_eq!(effects_context.update_vignette(0.1), 0.9);
+
+        // ..and open it up again more slowly once they stop.
+        assert_relative_eq!(effects_context.update_vignette(0.1), 0.7);
+
+        // Turning it off should open it up, whatever's asked for.
+        effects_context.vignette_settings.enabled = false;
+        effects_context.request_vignette(1.);
+        assert_relative_eq!(effects_context.update_vignette(0.1), 0.5);
+    }
+
+    #[test]
+    pub fn test_capped() {
+        let capped = ShakeSettings {
+            amplitude: 1.,
+            frequency: 50.,
+            decay: 0.1,
+        }
+        .capped();
+        assert_eq!(
+            capped,
+            ShakeSettings {
+                amplitude: MAX_SHAKE_AMPLITUDE,
+                frequency: MAX_SHAKE_FREQUENCY,
+                decay: MIN_SHAKE_DECAY,
+            }
+        );
+        assert_eq!(ShakeSettings::default().capped(), ShakeSettings::default());
+    }
+}
@@ -271,9 +271,12 @@ impl GuiContext {
         let command_buffer = frame.command_buffer;
         let framebuffer = ui_panel.framebuffer;
         let extent = panel.resolution;
-        let (raw_input, panel_input) = handle_panel_input(ui_panel, panel);
+        // Larger text is drawn by giving egui more pixels per point, which scales the whole panel.
+        let scale = SCALE_FACTOR * render_context.accessibility.panel_text_scale();
+        let (raw_input, panel_input) = handle_panel_input(ui_panel, panel, scale);
 
         let text = ui_panel.text.clone();
+        let progress = ui_panel.progress;
         let mut updated_buttons = ui_panel.buttons.clone();
         let egui_context = &mut ui_panel.egui_context;
 
@@ -288,6 +291,10 @@ impl GuiContext {
             ui.with_layout(inner_layout, |ui| {
                 ui.heading(&text);
 
+                if let Some(progress) = progress {
+                    ui.add(egui::ProgressBar::new(progress).show_percentage());
+                }
+
                 for button in &mut updated_buttons {
                     let response = ui.button(&button.text);
 
@@ -302,8 +309,8 @@ impl GuiContext {
 
                 if let Some(panel_input) = panel_input {
                     let (x, y) = (
-                        panel_input.cursor_location.x / SCALE_FACTOR,
-                        panel_input.cursor_location.y / SCALE_FACTOR,
+                        panel_input.cursor_location.x / scale,
+                        panel_input.cursor_location.y / scale,
                     );
                     let position = ui.painter().round_pos_to_pixels((x, y).into());
                     let cursor_color = if panel_input.trigger_value > 0.9 {
@@ -372,8 +379,8 @@ impl GuiContext {
             );
 
             // Set push constants
-            let width_points = extent.width as f32 / SCALE_FACTOR;
-            let height_points = extent.height as f32 / SCALE_FACTOR;
+            let width_points = extent.width as f32 / scale;
+            let height_points = extent.height as f32 / scale;
             device.cmd_push_constants(
                 command_buffer,
                 self.pipeline_layout,
@@ -416,8 +423,8 @@ impl GuiContext {
 
                 let min = rect.min;
                 let min = egui::Pos2 {
-                    x: min.x * SCALE_FACTOR,
-                    y: min.y * SCALE_FACTOR,
+                    x: min.x * scale,
+                    y: min.y * scale,
                 };
                 let min = egui::Pos2 {
                     x: f32::clamp(min.x, 0.0, width),
@@ -425,8 +432,8 @@ impl GuiContext {
                 };
                 let max = rect.max;
                 let max = egui::Pos2 {
-                    x: max.x * SCALE_FACTOR,
-                    y: max.y * SCALE_FACTOR,
+                    x: max.x * scale,
+                    y: max.y * scale,
                 };
                 let max = egui::Pos2 {
                     x: f32::clamp(max.x, min.x, width),
@@ -464,13 +471,22 @@ impl GuiContext {
 fn handle_panel_input(
     ui_panel: &mut UIPanel,
     panel: &mut Panel,
+    scale: f32,
 ) -> (egui::RawInput, Option<PanelInput>) {
     let mut raw_input = ui_panel.raw_input.clone();
+    raw_input.pixels_per_point = Some(scale);
+    raw_input.screen_rect = Some(egui::Rect::from_min_size(
+        Default::default(),
+        egui::emath::vec2(
+            panel.resolution.width as f32,
+            panel.resolution.height as f32,
+        ) / scale,
+    ));
     let panel_input = panel.input.take();
     if let Some(input) = &panel_input {
         let pos = egui::Pos2 {
-            x: input.cursor_location.x / SCALE_FACTOR,
-            y: input.cursor_location.y / SCALE_FACTOR,
+            x: input.cursor_location.x / scale,
+            y: input.cursor_location.y / scale,
         };
         raw_input.events.push(egui::Event::PointerMoved(pos));
         if input.trigger_value >= 0. {
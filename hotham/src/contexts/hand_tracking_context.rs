@@ -0,0 +1,317 @@
+use std::f32::consts::PI;
+
+use glam::{Affine3A, Vec3};
+
+use crate::{
+    components::hand::Handedness,
+    util::affine_from_posef,
+    xr::{self, HandJoint, HAND_JOINT_COUNT},
+};
+
+use super::XrContext;
+
+/// How far apart the thumb and index finger tips are when fully pinched, in metres
+const PINCH_CLOSED_DISTANCE: f32 = 0.015;
+/// How far apart the thumb and index finger tips are when not pinching at all, in metres
+const PINCH_OPEN_DISTANCE: f32 = 0.05;
+/// How far a finger's joints bend in total when it's fully curled into a fist, in radians
+const FULL_CURL_ANGLE: f32 = 1.5 * PI;
+
+/// The player's hands, tracked by the headset's cameras rather than held controllers.
+///
+/// Hand tracking uses `XR_EXT_hand_tracking`, which Hotham enables whenever the runtime has it. If the runtime or
+/// the device doesn't support it, or the player isn't using their hands, [`HandTrackingContext::left`] and
+/// [`HandTrackingContext::right`] return `None`. To have a [`crate::components::Hand`] follow a tracked hand, create
+/// it with [`crate::components::Hand::tracked`].
+#[derive(Default)]
+pub struct HandTrackingContext {
+    trackers: Trackers,
+    left: Option<TrackedHand>,
+    right: Option<TrackedHand>,
+}
+
+#[derive(Default)]
+enum Trackers {
+    /// We haven't checked whether hand tracking is supported yet
+    #[default]
+    Unchecked,
+    /// The runtime can't track hands
+    Unsupported,
+    /// One tracker for each hand
+    Ready {
+        left: xr::HandTracker,
+        right: xr::HandTracker,
+    },
+}
+
+/// The 26 joints of a tracked hand, as described by `XR_EXT_hand_tracking`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedHand {
+    /// The transform of each joint in stage space, indexed by [`HandJoint`]
+    pub stage_from_joint: [Affine3A; HAND_JOINT_COUNT],
+    /// The radius of each joint, in metres, indexed by [`HandJoint`]
+    pub radii: [f32; HAND_JOINT_COUNT],
+}
+
+/// One of the hand's fingers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finger {
+    /// Thumb
+    Thumb,
+    /// Index finger
+    Index,
+    /// Middle finger
+    Middle,
+    /// Ring finger
+    Ring,
+    /// Little finger
+    Little,
+}
+
+impl HandTrackingContext {
+    /// The left hand, if it's being tracked
+    pub fn left(&self) -> Option<&TrackedHand> {
+        self.left.as_ref()
+    }
+
+    /// The right hand, if it's being tracked
+    pub fn right(&self) -> Option<&TrackedHand> {
+        self.right.as_ref()
+    }
+
+    /// The hand on the given side, if it's being tracked
+    pub fn hand(&self, handedness: Handedness) -> Option<&TrackedHand> {
+        match handedness {
+            Handedness::Left => self.left(),
+            Handedness::Right => self.right(),
+        }
+    }
+
+    /// Can the runtime track hands? This is only known once the engine has started running frames.
+    pub fn is_supported(&self) -> bool {
+        matches!(self.trackers, Trackers::Ready { .. })
+    }
+
+    /// Locate the joints of both hands for the frame being rendered.
+    pub(crate) fn update(&mut self, xr_context: &XrContext) {
+        if let Trackers::Unchecked = self.trackers {
+            self.trackers = create_trackers(xr_context).unwrap_or_else(|e| {
+                println!("[HOTHAM_HAND_TRACKING] Unable to track hands: {:?}", e);
+                Trackers::Unsupported
+            });
+        }
+
+        let (left, right) = match &self.trackers {
+            Trackers::Ready { left, right } => (left, right),
+            _ => return,
+        };
+        self.left = locate_hand(xr_context, left);
+        self.right = locate_hand(xr_context, right);
+    }
+}
+
+fn create_trackers(xr_context: &XrContext) -> anyhow::Result<Trackers> {
+    if !xr_context.enabled_extensions.ext_hand_tracking {
+        return Ok(Trackers::Unsupported);
+    }
+    let system = xr_context
+        .instance
+        .system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
+    if !xr_context.instance.supports_hand_tracking(system)? {
+        return Ok(Trackers::Unsupported);
+    }
+
+    println!("[HOTHAM_HAND_TRACKING] Hand tracking is supported");
+    Ok(Trackers::Ready {
+        left: xr_context.session.create_hand_tracker(xr::Hand::LEFT)?,
+        right: xr_context.session.create_hand_tracker(xr::Hand::RIGHT)?,
+    })
+}
+
+fn locate_hand(xr_context: &XrContext, tracker: &xr::HandTracker) -> Option<TrackedHand> {
+    let locations = xr_context
+        .stage_space
+        .locate_hand_joints(tracker, xr_context.frame_state.predicted_display_time)
+        .ok()
+        .flatten()?;
+
+    // The runtime either tracks the whole hand or none of it, so the palm is enough to go by.
+    if !is_joint_valid(&locations[HandJoint::PALM.into_raw() as usize]) {
+        return None;
+    }
+
+    let mut hand = TrackedHand::default();
+    for (index, location) in locations.iter().enumerate() {
+        if is_joint_valid(location) {
+            hand.stage_from_joint[index] = affine_from_posef(location.pose);
+        }
+        hand.radii[index] = location.radius;
+    }
+    Some(hand)
+}
+
+fn is_joint_valid(location: &xr::HandJointLocation) -> bool {
+    location.location_flags.contains(
+        xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID,
+    )
+}
+
+impl Default for TrackedHand {
+    fn default() -> Self {
+        Self {
+            stage_from_joint: [Affine3A::IDENTITY; HAND_JOINT_COUNT],
+            radii: [0.; HAND_JOINT_COUNT],
+        }
+    }
+}
+
+impl TrackedHand {
+    /// The transform of `joint` in stage space
+    pub fn stage_from_joint(&self, joint: HandJoint) -> Affine3A {
+        self.stage_from_joint[joint.into_raw() as usize]
+    }
+
+    /// The position of `joint` in stage space
+    pub fn joint_position(&self, joint: HandJoint) -> Vec3 {
+        self.stage_from_joint(joint).translation.into()
+    }
+
+    /// The transform of the palm in stage space. Like a controller's grip pose, -Z points along the fingers.
+    pub fn stage_from_palm(&self) -> Affine3A {
+        self.stage_from_joint(HandJoint::PALM)
+    }
+
+    /// How far `finger` is curled, from 0 (straight) to 1 (curled into a fist).
+    pub fn finger_curl(&self, finger: Finger) -> f32 {
+        let joints = finger.joints();
+        let bones: Vec<Vec3> = joints
+            .windows(2)
+            .map(|pair| self.joint_position(pair[1]) - self.joint_position(pair[0]))
+            .collect();
+        let bend: f32 = bones
+            .windows(2)
+            .map(|pair| pair[0].angle_between(pair[1]))
+            .filter(|angle| angle.is_finite())
+            .sum();
+        (bend / FULL_CURL_ANGLE).clamp(0., 1.)
+    }
+
+    /// How tightly the thumb and index finger are pinched together, from 0 (apart) to 1 (touching).
+    pub fn pinch_strength(&self) -> f32 {
+        let distance = self
+            .joint_position(HandJoint::THUMB_TIP)
+            .distance(self.joint_position(HandJoint::INDEX_TIP));
+        1. - ((distance - PINCH_CLOSED_DISTANCE) / (PINCH_OPEN_DISTANCE - PINCH_CLOSED_DISTANCE))
+            .clamp(0., 1.)
+    }
+
+    /// How tightly the hand is gripping, from the curl of the middle, ring and little fingers. The equivalent of a
+    /// controller's grip button.
+    pub fn grip_strength(&self) -> f32 {
+        [Finger::Middle, Finger::Ring, Finger::Little]
+            .iter()
+            .map(|finger| self.finger_curl(*finger))
+            .sum::<f32>()
+            / 3.
+    }
+}
+
+impl Finger {
+    /// The finger's joints, from the knuckle nearest the wrist to the tip.
+    pub fn joints(&self) -> [HandJoint; 5] {
+        match self {
+            // The thumb has no intermediate joint, so start from the wrist instead.
+            Finger::Thumb => [
+                HandJoint::WRIST,
+                HandJoint::THUMB_METACARPAL,
+                HandJoint::THUMB_PROXIMAL,
+                HandJoint::THUMB_DISTAL,
+                HandJoint::THUMB_TIP,
+            ],
+            Finger::Index => [
+                HandJoint::INDEX_METACARPAL,
+                HandJoint::INDEX_PROXIMAL,
+                HandJoint::INDEX_INTERMEDIATE,
+                HandJoint::INDEX_DISTAL,
+                HandJoint::INDEX_TIP,
+            ],
+            Finger::Middle => [
+                HandJoint::MIDDLE_METACARPAL,
+                HandJoint::MIDDLE_PROXIMAL,
+                HandJoint::MIDDLE_INTERMEDIATE,
+                HandJoint::MIDDLE_DISTAL,
+                HandJoint::MIDDLE_TIP,
+            ],
+            Finger::Ring => [
+                HandJoint::RING_METACARPAL,
+                HandJoint::RING_PROXIMAL,
+                HandJoint::RING_INTERMEDIATE,
+                HandJoint::RING_DISTAL,
+                HandJoint::RING_TIP,
+            ],
+            Finger::Little => [
+                HandJoint::LITTLE_METACARPAL,
+                HandJoint::LITTLE_PROXIMAL,
+                HandJoint::LITTLE_INTERMEDIATE,
+                HandJoint::LITTLE_DISTAL,
+                HandJoint::LITTLE_TIP,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    /// A hand with every finger laid out straight along -Z, or bent 90 degrees at each of its three joints.
+    fn hand(curled: bool) -> TrackedHand {
+        let mut hand = TrackedHand::default();
+        for (row, finger) in [Finger::Index, Finger::Middle, Finger::Ring, Finger::Little]
+            .iter()
+            .enumerate()
+        {
+            let x = row as f32 * 0.02;
+            let straight = [0., -0.04, -0.08, -0.1, -0.12].map(|z| Vec3::new(x, 0., z));
+            let fist = [
+                Vec3::new(x, 0., 0.),
+                Vec3::new(x, 0., -0.04),
+                Vec3::new(x, -0.04, -0.04),
+                Vec3::new(x, -0.04, 0.),
+                Vec3::new(x, 0., 0.),
+            ];
+            let positions = if curled { fist } else { straight };
+            for (joint, position) in finger.joints().iter().zip(positions) {
+                hand.stage_from_joint[joint.into_raw() as usize] =
+                    Affine3A::from_translation(position);
+            }
+        }
+        hand
+    }
+
+    #[test]
+    pub fn test_finger_curl() {
+        let open = hand(false);
+        assert_relative_eq!(open.finger_curl(Finger::Index), 0.);
+        assert_relative_eq!(open.grip_strength(), 0.);
+
+        let fist = hand(true);
+        assert_relative_eq!(fist.finger_curl(Finger::Index), 1., epsilon = 0.001);
+        assert_relative_eq!(fist.grip_strength(), 1., epsilon = 0.001);
+    }
+
+    #[test]
+    pub fn test_pinch_strength() {
+        let mut hand = hand(false);
+        let index_tip = hand.joint_position(HandJoint::INDEX_TIP);
+
+        hand.stage_from_joint[HandJoint::THUMB_TIP.into_raw() as usize] =
+            Affine3A::from_translation(index_tip + Vec3::X * 0.1);
+        assert_relative_eq!(hand.pinch_strength(), 0.);
+
+        hand.stage_from_joint[HandJoint::THUMB_TIP.into_raw() as usize] =
+            Affine3A::from_translation(index_tip + Vec3::X * 0.01);
+        assert_relative_eq!(hand.pinch_strength(), 1.);
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use hecs::Entity;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{HothamError, HothamResult};
+
+mod websocket;
+pub use websocket::{
+    WebSocket, WebSocketEvent, WebSocketMessage, WebSocketOptions, WebSocketState,
+};
+
+/// How many requests can be in flight at once
+const WORKER_COUNT: usize = 2;
+/// The default for [`HttpRequest::timeout`]
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The largest response body that will be read, so a misbehaving server can't use up the headset's memory
+const MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Makes HTTP requests on background threads, so fetching a leaderboard or remote config never holds up a frame.
+///
+/// Send a request for an entity with [`HttpContext::send_for`], and `http_system` will deliver the response to its
+/// [`crate::components::HttpResponses`] component, a frame or more later:
+/// ```ignore
+/// let request = HttpRequest::get("https://example.com/leaderboard.json");
+/// engine.http_context.send_for(leaderboard_panel, request);
+///
+/// // ..then, in a later frame:
+/// for response in &world.get::<&HttpResponses>(leaderboard_panel)?.responses_this_frame {
+///     let scores: Vec<Score> = response.json()?;
+/// }
+/// ```
+/// Requests sent with [`HttpContext::send`] aren't for any entity, and their responses are in
+/// [`HttpContext::responses`] instead.
+///
+/// HTTPS uses `rustls` with Mozilla's root certificates built in, so it works on Android without any setup. Remember
+/// the app needs the `android.permission.INTERNET` permission in its manifest.
+pub struct HttpContext {
+    jobs: Sender<Job>,
+    completed: Receiver<HttpResponse>,
+    next_id: u64,
+    recipients: HashMap<RequestId, Entity>,
+    in_flight: usize,
+    /// Responses that arrived this frame for requests sent with [`HttpContext::send`]
+    pub responses: Vec<HttpResponse>,
+}
+
+/// A request waiting for a worker
+struct Job {
+    id: RequestId,
+    request: HttpRequest,
+}
+
+/// Identifies a request, so its response can be matched up with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+/// An HTTP request, to be sent with [`HttpContext`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// The method, eg. `GET` or `POST`
+    pub method: String,
+    /// Where to send the request
+    pub url: String,
+    /// Headers to send with the request
+    pub headers: Vec<(String, String)>,
+    /// The body of the request, if it has one
+    pub body: Option<Vec<u8>>,
+    /// How long to wait for the whole request to finish before giving up
+    pub timeout: Duration,
+}
+
+/// The result of an [`HttpRequest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The request this is a response to
+    pub request_id: RequestId,
+    /// The HTTP status code, or `None` if the request failed before getting a response, eg. because the network is
+    /// down
+    pub status: Option<u16>,
+    /// The body of the response
+    pub body: Vec<u8>,
+    /// What went wrong, if the request failed before getting a response
+    pub error: Option<String>,
+}
+
+impl Default for HttpContext {
+    fn default() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (completed_sender, completed) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let agent = ureq::AgentBuilder::new().build();
+
+        for index in 0..WORKER_COUNT {
+            let job_receiver = job_receiver.clone();
+            let completed_sender = completed_sender.clone();
+            let agent = agent.clone();
+            spawn_worker(format!("hotham_http_{}", index), move || loop {
+                // Hold the lock just long enough to take the next job.
+                let job = match job_receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                let response = perform(&agent, job.id, &job.request);
+                if completed_sender.send(response).is_err() {
+                    return;
+                }
+            });
+        }
+
+        Self {
+            jobs,
+            completed,
+            next_id: 0,
+            recipients: HashMap::new(),
+            in_flight: 0,
+            responses: Vec::new(),
+        }
+    }
+}
+
+impl HttpContext {
+    /// Send `request`. Its response will be in [`HttpContext::responses`] for a frame once it arrives.
+    pub fn send(&mut self, request: HttpRequest) -> RequestId {
+        let id = RequestId(self.next_id);
+        self.next_id += 1;
+        self.in_flight += 1;
+        // The workers only stop once the context has been dropped.
+        let _ = self.jobs.send(Job { id, request });
+        id
+    }
+
+    /// Send `request`, delivering its response to `entity`'s [`crate::components::HttpResponses`] component. The
+    /// component is added if the entity doesn't have one.
+    pub fn send_for(&mut self, entity: Entity, request: HttpRequest) -> RequestId {
+        let id = self.send(request);
+        self.recipients.insert(id, entity);
+        id
+    }
+
+    /// How many requests haven't had a response yet
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Responses that have arrived since the last call, with the entity each one is for, if any.
+    pub(crate) fn take_completed(&mut self) -> Vec<(Option<Entity>, HttpResponse)> {
+        let completed: Vec<_> = self
+            .completed
+            .try_iter()
+            .map(|response| (self.recipients.remove(&response.request_id), response))
+            .collect();
+        self.in_flight -= completed.len();
+        completed
+    }
+}
+
+impl HttpRequest {
+    /// Create a request with the given method
+    pub fn new(method: &str, url: impl Into<String>) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Create a `GET` request
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new("GET", url)
+    }
+
+    /// Create a `POST` request with `body`
+    pub fn post(url: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            body: Some(body.into()),
+            ..Self::new("POST", url)
+        }
+    }
+
+    /// Create a `POST` request with `value` as its body, as JSON
+    pub fn post_json(url: impl Into<String>, value: &impl Serialize) -> HothamResult<Self> {
+        let body = serde_json::to_vec(value).map_err(anyhow::Error::from)?;
+        Ok(Self::post(url, body).header("Content-Type", "application/json"))
+    }
+
+    /// Add a header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Give up on the request if it hasn't finished after `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl HttpResponse {
+    /// Did the server respond with a 2xx status?
+    pub fn is_success(&self) -> bool {
+        matches!(self.status, Some(200..=299))
+    }
+
+    /// The body of the response, as text
+    pub fn text(&self) -> HothamResult<&str> {
+        std::str::from_utf8(&self.body).map_err(|e| anyhow::Error::from(e).into())
+    }
+
+    /// The body of the response, read as JSON. Fails if the request wasn't successful.
+    pub fn json<T: DeserializeOwned>(&self) -> HothamResult<T> {
+        if !self.is_success() {
+            return Err(HothamError::Other(anyhow::anyhow!(
+                "The request failed: {}",
+                self.error
+                    .clone()
+                    .unwrap_or_else(|| format!("status {:?}", self.status))
+            )));
+        }
+        serde_json::from_slice(&self.body).map_err(|e| anyhow::Error::from(e).into())
+    }
+}
+
+/// Start a networking thread. HTTP requests are shared between a few of these, and each [`WebSocket`] gets its own.
+fn spawn_worker(name: String, work: impl FnOnce() + Send + 'static) {
+    thread::Builder::new()
+        .name(name)
+        .spawn(work)
+        .expect("Unable to start networking thread");
+}
+
+/// Send `request` and wait for its response. Called on a worker thread.
+fn perform(agent: &ureq::Agent, id: RequestId, request: &HttpRequest) -> HttpResponse {
+    let mut call = agent
+        .request(&request.method, &request.url)
+        .timeout(request.timeout);
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+    let result = match &request.body {
+        Some(body) => call.send_bytes(body),
+        None => call.call(),
+    };
+
+    let failed = |error: String| HttpResponse {
+        request_id: id,
+        status: None,
+        body: Vec::new(),
+        error: Some(error),
+    };
+    // Error statuses still have a body worth reading, eg. an error message from the server.
+    let response = match result {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => return failed(e.to_string()),
+    };
+    let status = response.status();
+    let mut body = Vec::new();
+    if let Err(e) = response
+        .into_reader()
+        .take(MAX_BODY_SIZE)
+        .read_to_end(&mut body)
+    {
+        return failed(e.to_string());
+    }
+
+    HttpResponse {
+        request_id: id,
+        status: Some(status),
+        body,
+        error: None,
+    }
+}
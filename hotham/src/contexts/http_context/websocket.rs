@@ -0,0 +1,432 @@
+use std::{
+    io,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use tungstenite::{stream::MaybeTlsStream, Message};
+
+use super::spawn_worker;
+use crate::{HothamError, HothamResult};
+
+/// How long the connection waits for a message before checking if there's anything to send
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+type Socket = tungstenite::WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// A WebSocket connection, for live data like dashboards and multiplayer lobbies.
+///
+/// The connection runs on its own thread. Messages that arrive are collected into [`WebSocket::events_this_frame`]
+/// each time [`WebSocket::update`] is called - `http_system` does this for every `WebSocket` component, so the
+/// easiest way to use one is to put it on an entity:
+/// ```ignore
+/// let lobby = world.spawn((WebSocket::connect("wss://example.com/lobby"),));
+///
+/// // ..then, each frame:
+/// let mut websocket = world.get::<&mut WebSocket>(lobby)?;
+/// for event in &websocket.events_this_frame {
+///     if let WebSocketEvent::Message(WebSocketMessage::Text(text)) = event {
+///         update_lobby(text);
+///     }
+/// }
+/// websocket.send_text("ready")?;
+/// ```
+///
+/// If the connection drops it's made again, waiting a little longer after each failed attempt - see
+/// [`WebSocketOptions`]. Messages sent while disconnected are sent once the connection is back.
+pub struct WebSocket {
+    url: String,
+    state: WebSocketState,
+    max_pending_messages: usize,
+    outgoing: Sender<WebSocketMessage>,
+    incoming: Receiver<WebSocketEvent>,
+    closing: Arc<AtomicBool>,
+    /// What happened since the last call to [`WebSocket::update`]
+    pub events_this_frame: Vec<WebSocketEvent>,
+}
+
+/// Options for a [`WebSocket`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebSocketOptions {
+    /// The most messages that can be waiting to be sent, and the most received messages that can be waiting to be
+    /// delivered. Once this many received messages are waiting, the connection stops reading until they've been
+    /// delivered, which slows the server down.
+    pub max_pending_messages: usize,
+    /// Should the connection be made again if it drops?
+    pub reconnect: bool,
+    /// How long to wait before reconnecting the first time
+    pub initial_reconnect_delay: Duration,
+    /// The longest to wait between attempts to reconnect. The wait doubles after each failed attempt, up to this.
+    pub max_reconnect_delay: Duration,
+}
+
+impl Default for WebSocketOptions {
+    fn default() -> Self {
+        Self {
+            max_pending_messages: 256,
+            reconnect: true,
+            initial_reconnect_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A message sent or received on a [`WebSocket`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    /// A text message, eg. JSON
+    Text(String),
+    /// A binary message
+    Binary(Vec<u8>),
+}
+
+/// Something that happened on a [`WebSocket`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketEvent {
+    /// The connection was made, or made again
+    Connected,
+    /// A message arrived
+    Message(WebSocketMessage),
+    /// The connection dropped, or couldn't be made
+    Disconnected {
+        /// What went wrong
+        reason: String,
+        /// Will the connection be made again?
+        will_reconnect: bool,
+    },
+}
+
+/// The state of a [`WebSocket`]'s connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketState {
+    /// Connecting for the first time
+    Connecting,
+    /// Connected, and passing messages back and forth
+    Connected,
+    /// The connection dropped, and will be made again
+    Reconnecting,
+    /// The connection has been closed for good
+    Closed,
+}
+
+impl WebSocket {
+    /// Connect to `url`, eg. `wss://example.com/lobby`, with the default [`WebSocketOptions`].
+    pub fn connect(url: impl Into<String>) -> Self {
+        Self::connect_with_options(url, Default::default())
+    }
+
+    /// Connect to `url` with the given options.
+    pub fn connect_with_options(url: impl Into<String>, options: WebSocketOptions) -> Self {
+        let url = url.into();
+        let (outgoing, outgoing_receiver) = channel::bounded(options.max_pending_messages);
+        let (incoming_sender, incoming) = channel::bounded(options.max_pending_messages);
+        let closing = Arc::new(AtomicBool::new(false));
+
+        let connection = Connection {
+            url: url.clone(),
+            options,
+            outgoing: outgoing_receiver,
+            incoming: incoming_sender,
+            closing: closing.clone(),
+        };
+        spawn_worker("hotham_websocket".to_string(), move || connection.run());
+
+        Self {
+            url,
+            state: WebSocketState::Connecting,
+            max_pending_messages: options.max_pending_messages,
+            outgoing,
+            incoming,
+            closing,
+            events_this_frame: Vec::new(),
+        }
+    }
+
+    /// Where the WebSocket is connected to
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The state of the connection, as of the last call to [`WebSocket::update`]
+    pub fn state(&self) -> WebSocketState {
+        self.state
+    }
+
+    /// Send a message. Fails if the WebSocket has been closed, or if too many messages are already waiting to be
+    /// sent.
+    pub fn send(&self, message: WebSocketMessage) -> HothamResult<()> {
+        let failed = |reason: &str| HothamError::WebSocketSendFailed {
+            url: self.url.clone(),
+            reason: reason.to_string(),
+        };
+        if self.state == WebSocketState::Closed {
+            return Err(failed("the connection has been closed"));
+        }
+        self.outgoing.try_send(message).map_err(|e| match e {
+            TrySendError::Full(_) => failed("too many messages are waiting to be sent"),
+            TrySendError::Disconnected(_) => failed("the connection has been closed"),
+        })
+    }
+
+    /// Send a text message
+    pub fn send_text(&self, text: impl Into<String>) -> HothamResult<()> {
+        self.send(WebSocketMessage::Text(text.into()))
+    }
+
+    /// Send a binary message
+    pub fn send_binary(&self, bytes: impl Into<Vec<u8>>) -> HothamResult<()> {
+        self.send(WebSocketMessage::Binary(bytes.into()))
+    }
+
+    /// Close the connection for good. Dropping the `WebSocket` does this too.
+    pub fn close(&mut self) {
+        self.closing.store(true, Ordering::Relaxed);
+        self.state = WebSocketState::Closed;
+    }
+
+    /// Collect what's happened since the last call into [`WebSocket::events_this_frame`]. Called by `http_system` for
+    /// `WebSocket` components - call it once a frame for any others.
+    pub fn update(&mut self) {
+        self.events_this_frame.clear();
+        if self.state == WebSocketState::Closed {
+            return;
+        }
+
+        // A fast server could keep the queue full forever, so only take what could have been waiting.
+        for event in self.incoming.try_iter().take(self.max_pending_messages) {
+            match &event {
+                WebSocketEvent::Connected => self.state = WebSocketState::Connected,
+                WebSocketEvent::Disconnected { will_reconnect, .. } => {
+                    self.state = if *will_reconnect {
+                        WebSocketState::Reconnecting
+                    } else {
+                        WebSocketState::Closed
+                    }
+                }
+                WebSocketEvent::Message(_) => {}
+            }
+            self.events_this_frame.push(event);
+        }
+    }
+}
+
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        self.closing.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The end of a [`WebSocket`] that runs on its own thread
+struct Connection {
+    url: String,
+    options: WebSocketOptions,
+    outgoing: Receiver<WebSocketMessage>,
+    incoming: Sender<WebSocketEvent>,
+    closing: Arc<AtomicBool>,
+}
+
+impl Connection {
+    /// Connect, and keep connecting, until the WebSocket is closed.
+    fn run(self) {
+        let mut reconnect_delay = self.options.initial_reconnect_delay;
+        loop {
+            let reason = match tungstenite::connect(self.url.as_str()) {
+                Ok((mut socket, _)) => {
+                    if self.incoming.send(WebSocketEvent::Connected).is_err() {
+                        return;
+                    }
+                    reconnect_delay = self.options.initial_reconnect_delay;
+                    match self.serve(&mut socket) {
+                        Ok(()) => return,
+                        Err(reason) => reason,
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+            if self.is_closing() {
+                return;
+            }
+
+            let will_reconnect = self.options.reconnect;
+            let disconnected = WebSocketEvent::Disconnected {
+                reason,
+                will_reconnect,
+            };
+            if self.incoming.send(disconnected).is_err() || !will_reconnect {
+                return;
+            }
+
+            // Wait before trying again, but stop waiting if the WebSocket is closed.
+            let retry_at = Instant::now() + reconnect_delay;
+            while Instant::now() < retry_at {
+                if self.is_closing() {
+                    return;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            reconnect_delay = (reconnect_delay * 2).min(self.options.max_reconnect_delay);
+        }
+    }
+
+    /// Pass messages back and forth until the connection drops, returning why, or `Ok` once the WebSocket is closed.
+    fn serve(&self, socket: &mut Socket) -> Result<(), String> {
+        set_read_timeout(socket.get_ref(), POLL_INTERVAL).map_err(|e| e.to_string())?;
+
+        loop {
+            if self.is_closing() {
+                let _ = socket.close(None);
+                let _ = socket.write_pending();
+                return Ok(());
+            }
+
+            for message in self.outgoing.try_iter() {
+                let message = match message {
+                    WebSocketMessage::Text(text) => Message::Text(text),
+                    WebSocketMessage::Binary(bytes) => Message::Binary(bytes),
+                };
+                ignore_timeout(socket.write_message(message))?;
+            }
+
+            let message = match socket.read_message() {
+                Ok(Message::Text(text)) => WebSocketMessage::Text(text),
+                Ok(Message::Binary(bytes)) => WebSocketMessage::Binary(bytes),
+                Ok(Message::Close(frame)) => {
+                    return Err(match frame {
+                        Some(frame) if !frame.reason.is_empty() => {
+                            format!("the server closed the connection: {}", frame.reason)
+                        }
+                        _ => "the server closed the connection".to_string(),
+                    })
+                }
+                // Pings are answered for us.
+                Ok(_) => continue,
+                Err(e) => {
+                    // Nothing arrived in time, so finish sending anything that's been queued up.
+                    ignore_timeout(Err(e))?;
+                    ignore_timeout(socket.write_pending())?;
+                    continue;
+                }
+            };
+
+            // Blocks once too many messages are waiting, which is what slows the server down.
+            if self
+                .incoming
+                .send(WebSocketEvent::Message(message))
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::Relaxed)
+    }
+}
+
+/// Treat a read or write that timed out as a success, as the socket only has a timeout so it can be polled.
+fn ignore_timeout(result: tungstenite::Result<()>) -> Result<(), String> {
+    match result {
+        Err(tungstenite::Error::Io(e))
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+        Ok(()) => Ok(()),
+    }
+}
+
+fn set_read_timeout(stream: &MaybeTlsStream<TcpStream>, timeout: Duration) -> io::Result<()> {
+    match stream {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(timeout)),
+        MaybeTlsStream::Rustls(stream) => stream.sock.set_read_timeout(Some(timeout)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn wait_for(websocket: &mut WebSocket, condition: impl Fn(&[WebSocketEvent]) -> bool) {
+        let started = Instant::now();
+        let mut events = Vec::new();
+        while started.elapsed() < Duration::from_secs(5) {
+            websocket.update();
+            events.extend(websocket.events_this_frame.drain(..));
+            if condition(&events) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Gave up waiting - got {:?}", events);
+    }
+
+    #[test]
+    pub fn test_websocket_echo() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            while let Ok(message) = socket.read_message() {
+                if message.is_text() && socket.write_message(message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        // Messages sent before the connection is made are sent once it is.
+        let mut websocket = WebSocket::connect(format!("ws://127.0.0.1:{}", port));
+        assert_eq!(websocket.state(), WebSocketState::Connecting);
+        websocket.send_text("hello").unwrap();
+
+        wait_for(&mut websocket, |events| {
+            events.contains(&WebSocketEvent::Message(WebSocketMessage::Text(
+                "hello".to_string(),
+            )))
+        });
+        assert_eq!(websocket.state(), WebSocketState::Connected);
+
+        websocket.close();
+        assert!(websocket.send_text("goodbye").is_err());
+    }
+
+    #[test]
+    pub fn test_websocket_refused() {
+        // Find a port nothing is listening on.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let options = WebSocketOptions {
+            reconnect: false,
+            ..Default::default()
+        };
+        let mut websocket =
+            WebSocket::connect_with_options(format!("ws://127.0.0.1:{}", port), options);
+        wait_for(&mut websocket, |events| {
+            matches!(
+                events,
+                [WebSocketEvent::Disconnected {
+                    will_reconnect: false,
+                    ..
+                }]
+            )
+        });
+        assert_eq!(websocket.state(), WebSocketState::Closed);
+    }
+}
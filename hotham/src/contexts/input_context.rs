@@ -4,8 +4,9 @@ use crate::{
     xr,
 };
 use glam::{Affine3A, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LeftInputContext {
     // boolean input
     x_button: bool,
@@ -169,7 +170,7 @@ impl LeftInputContext {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RightInputContext {
     // boolean input
     a_button: bool,
@@ -322,7 +323,10 @@ impl RightInputContext {
     }
 }
 
-#[derive(Debug, Default)]
+/// Half the distance between the eyes of an average adult, in meters.
+const HALF_IPD: f32 = 0.032;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 /// Input from the Head Mounted Display (HMD, or headset)
 pub struct HmdInputContext {
     left_eye_in_stage: Affine3A,
@@ -338,13 +342,24 @@ impl HmdInputContext {
         self.right_eye_in_stage = affine_from_posef(views[1].pose);
     }
 
+    /// Put the HMD at `hmd_in_stage`, as if it were being worn by someone with an average IPD.
+    pub(crate) fn set_hmd_in_stage(&mut self, hmd_in_stage: Affine3A) {
+        self.left_eye_in_stage = hmd_in_stage * Affine3A::from_translation(Vec3::X * -HALF_IPD);
+        self.right_eye_in_stage = hmd_in_stage * Affine3A::from_translation(Vec3::X * HALF_IPD);
+    }
+
+    /// The poses of the left and right eyes in the real world (stage space)
+    pub(crate) fn eyes_in_stage(&self) -> [Affine3A; 2] {
+        [self.left_eye_in_stage, self.right_eye_in_stage]
+    }
+
     /// The pose of the HMD in the real world (stage space)
     pub(crate) fn hmd_in_stage(&self) -> Affine3A {
         lerp_slerp(&self.left_eye_in_stage, &self.right_eye_in_stage, 0.5)
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 /// Context that holds input state. Allows users to query for input events without having to
 /// worry about OpenXR internals.
 ///
@@ -573,5 +588,13 @@ pub mod tests {
 
         let (_, _, translation) = hmd_context.hmd_in_stage().to_scale_rotation_translation();
         assert_eq!(translation, expected_translation);
+
+        // Moving the HMD should move both eyes with it.
+        let mut hmd_context = HmdInputContext::default();
+        hmd_context.set_hmd_in_stage(glam::Affine3A::from_translation(expected_translation));
+        let (_, _, translation) = hmd_context.hmd_in_stage().to_scale_rotation_translation();
+        assert_eq!(translation, expected_translation);
+        let [left_eye, right_eye] = hmd_context.eyes_in_stage();
+        assert!(left_eye.translation.x < 0. && right_eye.translation.x > 0.);
     }
 }
@@ -0,0 +1,250 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use crossbeam::channel::{self, Receiver, Sender};
+
+use crate::HothamResult;
+
+/// The port the inspector listens on by default. Forward it from a Quest with `adb forward tcp:7879 tcp:7879`,
+/// then open <http://localhost:7879> in a browser.
+pub const DEFAULT_INSPECTOR_PORT: u16 = 7879;
+
+/// How long to wait for the game to answer a request. It only answers once a frame, and not at all while paused.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a browser to send or accept anything before giving up on it.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The largest request body we'll accept - edits are tiny, so anything bigger is a mistake.
+const MAX_BODY_LENGTH: usize = 64 * 1024;
+
+/// The desktop UI, served from `/`.
+const INSPECTOR_HTML: &str = include_str!("inspector.html");
+
+/// A request from the desktop UI, waiting for the game to answer it.
+#[derive(Debug)]
+pub struct InspectorRequest {
+    /// The HTTP method, eg. `GET`
+    pub method: String,
+    /// The path that was requested, eg. `/api/entities`
+    pub path: String,
+    /// The body of the request, if any
+    pub body: Vec<u8>,
+    pub(crate) respond: Sender<InspectorResponse>,
+}
+
+impl InspectorRequest {
+    /// Send `response` back to the desktop UI. It's fine if it's given up waiting.
+    pub fn respond(self, response: InspectorResponse) {
+        let _ = self.respond.send(response);
+    }
+}
+
+/// The game's answer to an [`InspectorRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InspectorResponse {
+    /// The HTTP status code
+    pub status: u16,
+    /// A JSON body
+    pub body: String,
+}
+
+impl InspectorResponse {
+    /// A successful response
+    pub fn ok(body: String) -> Self {
+        Self { status: 200, body }
+    }
+
+    /// An error response, with `message` wrapped up in a JSON object
+    pub fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: serde_json::json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+}
+
+/// Exposes the live `World` to a web UI on your desktop over HTTP, so you can see what's in it and tweak transforms,
+/// materials and lights while wearing the headset.
+///
+/// The server runs on its own thread and hands each request to the game, which answers them all in the
+/// [`crate::systems::inspector_system`] once a frame. That way nothing outside the game's own loop ever touches the
+/// `World`.
+///
+/// This is a debugging tool with no authentication - don't ship it. By default it only accepts connections from the
+/// device itself, so reach it from a desktop with `adb forward`. Anyone on the network can read and change the `World`
+/// once it's opened up with [`crate::EngineBuilder::inspector_on_lan`].
+pub struct InspectorContext {
+    requests: Receiver<InspectorRequest>,
+}
+
+impl InspectorContext {
+    /// Start listening for the desktop UI on `port`, from the device itself or, if `on_lan` is set, from anywhere on
+    /// the network.
+    pub fn new(port: u16, on_lan: bool) -> HothamResult<Self> {
+        let address = if on_lan {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let listener = TcpListener::bind((address, port))?;
+        let (request_sender, requests) = channel::unbounded();
+
+        thread::Builder::new()
+            .name("hotham_inspector".to_string())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if let Err(e) = serve(stream, &request_sender) {
+                        log::warn!("[HOTHAM_INSPECTOR] Unable to serve request: {:?}", e);
+                    }
+                }
+            })?;
+
+        log::info!("[HOTHAM_INSPECTOR] Listening on {}:{}", address, port);
+        Ok(Self { requests })
+    }
+
+    /// Get the next request that's waiting to be answered, if any.
+    pub fn next_request(&self) -> Option<InspectorRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+/// Read a request from `stream`, get an answer and send it back.
+fn serve(mut stream: TcpStream, request_sender: &Sender<InspectorRequest>) -> io::Result<()> {
+    stream.set_read_timeout(Some(STREAM_TIMEOUT))?;
+    stream.set_write_timeout(Some(STREAM_TIMEOUT))?;
+
+    let (method, path, body) = read_request(&mut BufReader::new(&mut stream))?;
+
+    // The UI doesn't need anything from the game, so don't make it wait for a frame.
+    if method == "GET" && path == "/" {
+        return write_response(&mut stream, 200, "text/html", INSPECTOR_HTML);
+    }
+
+    let (respond, response) = channel::bounded(1);
+    let request = InspectorRequest {
+        method,
+        path,
+        body,
+        respond,
+    };
+    let response = match request_sender.send(request) {
+        Ok(_) => response
+            .recv_timeout(RESPONSE_TIMEOUT)
+            .unwrap_or_else(|_| InspectorResponse::error(503, "The game isn't answering")),
+        Err(_) => InspectorResponse::error(503, "The game has stopped"),
+    };
+
+    write_response(
+        &mut stream,
+        response.status,
+        "application/json",
+        &response.body,
+    )
+}
+
+/// Parse an HTTP/1.1 request into its method, path and body.
+fn read_request(reader: &mut impl BufRead) -> io::Result<(String, String, Vec<u8>)> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(invalid_data("Malformed request line")),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid_data("Malformed Content-Length"))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LENGTH {
+        return Err(invalid_data("Request body is too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok((method, path, body))
+}
+
+fn write_response(
+    stream: &mut impl Write,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_read_request() {
+        let request = b"POST /api/lights/0 HTTP/1.1\r\nHost: localhost\r\ncontent-length: 16\r\n\r\n{\"intensity\": 2}";
+        let (method, path, body) = read_request(&mut &request[..]).unwrap();
+        assert_eq!(method, "POST");
+        assert_eq!(path, "/api/lights/0");
+        assert_eq!(body, b"{\"intensity\": 2}");
+
+        // Requests without a body are fine..
+        let (method, path, body) =
+            read_request(&mut &b"GET /api/entities HTTP/1.1\r\n\r\n"[..]).unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("GET", "/api/entities"));
+        assert!(body.is_empty());
+
+        // ..but garbage isn't.
+        assert!(read_request(&mut &b"\r\n"[..]).is_err());
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\nContent-Length: lots\r\n\r\n"[..]).is_err());
+    }
+
+    #[test]
+    pub fn test_write_response() {
+        let mut response = Vec::new();
+        write_response(&mut response, 404, "application/json", "{}").unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 2\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}"
+        );
+    }
+}
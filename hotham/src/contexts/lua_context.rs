@@ -0,0 +1,581 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use glam::{Quat, Vec3};
+use hecs::{Entity, World};
+use mlua::{Function, HookTriggers, Lua, RegistryKey, Scope, Table, ToLuaMulti, Variadic};
+
+use crate::{
+    components::{Collider, GlobalTransform, LocalTransform, Script, SoundEmitter},
+    scripting::{buttons, events},
+    util::get_asset_from_path,
+    HothamError, HothamResult,
+};
+
+/// How often, in instructions, a running script's budget is checked.
+const INSTRUCTIONS_PER_CHECK: u32 = 1000;
+
+/// How many checks a script may run for in a single call before it's stopped, so a runaway script can't hang the
+/// headset. That's ten million instructions.
+const CHECKS_PER_CALL: u32 = 10_000;
+
+/// Runs gameplay logic written in Lua - a lighter alternative to the [`super::ScriptContext`].
+///
+/// Load scripts with [`LuaContext::load`] or [`LuaContext::load_asset`], then add a [`Script`] component with the
+/// same name to each entity that should run it. A script returns a table, and every function in it is optional:
+///
+/// ```lua
+/// local spinner = {}
+///
+/// -- Called the first frame the script runs on an entity, or after it's reloaded
+/// function spinner.on_start(self) hotham.subscribe(hotham.events.INPUT) end
+/// -- Called every frame
+/// function spinner.on_update(self, delta_time) end
+/// -- Called for each collision, if subscribed to hotham.events.COLLISION
+/// function spinner.on_collision(self, other) end
+/// -- Called for each button pressed, if subscribed to hotham.events.INPUT
+/// function spinner.on_input(self, button) end
+///
+/// return spinner
+/// ```
+///
+/// `self` is a table unique to each entity, with the entity in `self.entity`; scripts can keep their own state in it.
+/// The global `hotham` table has the same host API as WASM scripts, plus the `hotham.buttons` and `hotham.events`
+/// constants:
+///
+/// - `entity()`, `spawn()` and `despawn(entity)`
+/// - `get_translation(entity)` and `get_rotation(entity)` - return the components, or nothing if the entity doesn't
+///   exist or has no `LocalTransform`
+/// - `set_translation(entity, x, y, z)`, `set_rotation(entity, x, y, z, w)` and `set_scale(entity, x, y, z)`
+/// - `play_sound(entity)` - plays the entity's `SoundEmitter`
+/// - `subscribe(event)`
+/// - `log(message)`
+///
+/// The `set_` functions and `play_sound` return `false` if the entity doesn't have the right components.
+pub struct LuaContext {
+    lua: Lua,
+    scripts: HashMap<String, LoadedScript>,
+    instances: HashMap<Entity, ScriptInstance>,
+    budget: Arc<AtomicU32>,
+    missing: HashSet<String>,
+}
+
+struct LoadedScript {
+    table: RegistryKey,
+    generation: u32,
+}
+
+struct ScriptInstance {
+    name: String,
+    generation: u32,
+    /// The `self` table passed to each of the script's functions
+    state: RegistryKey,
+    started: bool,
+    failed: bool,
+    collisions: bool,
+    input: bool,
+}
+
+/// Everything the host API needs while scripts are running this frame.
+struct Frame<'w> {
+    world: RefCell<&'w mut World>,
+    current: Cell<Option<Entity>>,
+    subscriptions: RefCell<Vec<i32>>,
+}
+
+impl Default for LuaContext {
+    fn default() -> Self {
+        let lua = Lua::new();
+        let budget = Arc::new(AtomicU32::new(0));
+
+        let hook_budget = budget.clone();
+        lua.set_hook(
+            HookTriggers {
+                every_nth_instruction: Some(INSTRUCTIONS_PER_CHECK),
+                ..Default::default()
+            },
+            move |_, _| match hook_budget
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| b.checked_sub(1))
+            {
+                Ok(_) => Ok(()),
+                Err(_) => Err(mlua::Error::RuntimeError(
+                    "the script ran for too long".to_string(),
+                )),
+            },
+        )
+        .expect("Unable to set Lua hook");
+
+        create_globals(&lua).expect("Unable to create Lua globals");
+
+        Self {
+            lua,
+            scripts: Default::default(),
+            instances: Default::default(),
+            budget,
+            missing: Default::default(),
+        }
+    }
+}
+
+impl LuaContext {
+    /// Run `source` and store the table it returns as the script `name`. If a script called `name` was already
+    /// loaded, every entity running it is restarted with the new one.
+    pub fn load(&mut self, name: &str, source: impl AsRef<[u8]>) -> HothamResult<()> {
+        self.budget.store(CHECKS_PER_CALL, Ordering::Relaxed);
+        let table = self
+            .lua
+            .load(source.as_ref())
+            .set_name(name)
+            .and_then(|chunk| chunk.eval::<Table>())
+            .and_then(|table| self.lua.create_registry_value(table))
+            .map_err(|e| script_error(name, e))?;
+
+        let generation = match self.scripts.remove(name) {
+            Some(old) => {
+                let _ = self.lua.remove_registry_value(old.table);
+                old.generation + 1
+            }
+            None => 0,
+        };
+        self.scripts
+            .insert(name.to_string(), LoadedScript { table, generation });
+        self.missing.remove(name);
+        Ok(())
+    }
+
+    /// Load the script at `path` in the app's assets and store it as `name`.
+    pub fn load_asset(&mut self, name: &str, path: &str) -> HothamResult<()> {
+        let source = get_asset_from_path(path)?;
+        self.load(name, source)
+    }
+
+    /// Run every entity's [`Script`] for this frame.
+    pub(crate) fn update(&mut self, world: &mut World, buttons_pressed: &[i32], delta_time: f32) {
+        self.sync_instances(world);
+
+        let lua = &self.lua;
+        let scripts = &self.scripts;
+        let instances = &mut self.instances;
+        let budget = &self.budget;
+        let frame = Frame {
+            world: RefCell::new(world),
+            current: Cell::new(None),
+            subscriptions: Default::default(),
+        };
+
+        let result = lua.scope(|scope| {
+            register_api(lua, scope, &frame)?;
+
+            for (entity, instance) in instances.iter_mut() {
+                if instance.failed {
+                    continue;
+                }
+                frame.current.set(Some(*entity));
+
+                let script: Table = lua.registry_value(&scripts[&instance.name].table)?;
+                let state: Table = lua.registry_value(&instance.state)?;
+                if let Err(e) = run_instance(
+                    instance,
+                    *entity,
+                    &script,
+                    state,
+                    &frame,
+                    budget,
+                    buttons_pressed,
+                    delta_time,
+                ) {
+                    log::error!(
+                        "[HOTHAM_LUA] {} failed on {:?}, disabling it: {}",
+                        instance.name,
+                        entity,
+                        e
+                    );
+                    instance.failed = true;
+                }
+            }
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            log::error!("[HOTHAM_LUA] Unable to run scripts: {}", e);
+        }
+    }
+
+    /// Start running scripts on entities that have just been given a [`Script`], and stop running them on entities
+    /// that have lost theirs or whose script has been reloaded.
+    fn sync_instances(&mut self, world: &World) {
+        let stale = self
+            .instances
+            .iter()
+            .filter(|(entity, instance)| {
+                let script = match world.get::<&Script>(**entity) {
+                    Ok(script) => script,
+                    Err(_) => return true,
+                };
+                script.name != instance.name
+                    || self.scripts.get(&script.name).map(|s| s.generation)
+                        != Some(instance.generation)
+            })
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in stale {
+            let instance = self.instances.remove(&entity).unwrap();
+            let _ = self.lua.remove_registry_value(instance.state);
+        }
+
+        for (entity, script) in world.query::<&Script>().iter() {
+            if self.instances.contains_key(&entity) {
+                continue;
+            }
+
+            let loaded = match self.scripts.get(&script.name) {
+                Some(loaded) => loaded,
+                None => {
+                    if self.missing.insert(script.name.clone()) {
+                        log::warn!(
+                            "[HOTHAM_LUA] No script called {} has been loaded",
+                            script.name
+                        );
+                    }
+                    continue;
+                }
+            };
+
+            let state = self
+                .lua
+                .create_table()
+                .and_then(|state| {
+                    state.set("entity", entity_to_lua(entity))?;
+                    self.lua.create_registry_value(state)
+                })
+                .expect("Unable to create Lua table");
+
+            self.instances.insert(
+                entity,
+                ScriptInstance {
+                    name: script.name.clone(),
+                    generation: loaded.generation,
+                    state,
+                    started: false,
+                    failed: false,
+                    collisions: false,
+                    input: false,
+                },
+            );
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_instance<'lua>(
+    instance: &mut ScriptInstance,
+    entity: Entity,
+    script: &Table<'lua>,
+    state: Table<'lua>,
+    frame: &Frame,
+    budget: &AtomicU32,
+    buttons_pressed: &[i32],
+    delta_time: f32,
+) -> mlua::Result<()> {
+    if !instance.started {
+        instance.started = true;
+        call(budget, script, "on_start", state.clone())?;
+    }
+    call(budget, script, "on_update", (state.clone(), delta_time))?;
+
+    for event in frame.subscriptions.borrow_mut().drain(..) {
+        match event {
+            events::COLLISION => instance.collisions = true,
+            events::INPUT => instance.input = true,
+            _ => log::warn!("[HOTHAM_LUA] Unknown event {}", event),
+        }
+    }
+
+    if instance.collisions {
+        let collisions = frame
+            .world
+            .borrow()
+            .get::<&Collider>(entity)
+            .map(|c| c.collisions_this_frame.clone())
+            .unwrap_or_default();
+        for other in collisions {
+            call(
+                budget,
+                script,
+                "on_collision",
+                (state.clone(), entity_to_lua(other)),
+            )?;
+        }
+    }
+
+    if instance.input {
+        for button in buttons_pressed {
+            call(budget, script, "on_input", (state.clone(), *button))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Call `function` in `script`, if it has one.
+fn call<'lua, A: ToLuaMulti<'lua>>(
+    budget: &AtomicU32,
+    script: &Table<'lua>,
+    function: &str,
+    args: A,
+) -> mlua::Result<()> {
+    if let Some(function) = script.get::<_, Option<Function>>(function)? {
+        budget.store(CHECKS_PER_CALL, Ordering::Relaxed);
+        function.call::<_, ()>(args)?;
+    }
+    Ok(())
+}
+
+/// Create the `hotham` global, with the constants scripts can use.
+fn create_globals(lua: &Lua) -> mlua::Result<()> {
+    let hotham = lua.create_table()?;
+
+    let button_table = lua.create_table()?;
+    for (name, button) in [
+        ("A", buttons::A),
+        ("B", buttons::B),
+        ("X", buttons::X),
+        ("Y", buttons::Y),
+        ("LEFT_TRIGGER", buttons::LEFT_TRIGGER),
+        ("RIGHT_TRIGGER", buttons::RIGHT_TRIGGER),
+        ("LEFT_GRIP", buttons::LEFT_GRIP),
+        ("RIGHT_GRIP", buttons::RIGHT_GRIP),
+        ("MENU", buttons::MENU),
+    ] {
+        button_table.set(name, button)?;
+    }
+    hotham.set("buttons", button_table)?;
+
+    let event_table = lua.create_table()?;
+    event_table.set("COLLISION", events::COLLISION)?;
+    event_table.set("INPUT", events::INPUT)?;
+    hotham.set("events", event_table)?;
+
+    lua.globals().set("hotham", hotham)
+}
+
+/// Add the host API to the `hotham` global. The functions borrow `frame`, so they only work for this frame.
+fn register_api<'lua, 'scope, 'w: 'scope>(
+    lua: &'lua Lua,
+    scope: &Scope<'lua, 'scope>,
+    frame: &'scope Frame<'w>,
+) -> mlua::Result<()> {
+    let hotham: Table = lua.globals().get("hotham")?;
+
+    hotham.set(
+        "entity",
+        scope.create_function(move |_, ()| Ok(frame.current.get().map(entity_to_lua)))?,
+    )?;
+    hotham.set(
+        "spawn",
+        scope.create_function(move |_, ()| {
+            let entity = frame
+                .world
+                .borrow_mut()
+                .spawn((LocalTransform::default(), GlobalTransform::default()));
+            Ok(entity_to_lua(entity))
+        })?,
+    )?;
+    hotham.set(
+        "despawn",
+        scope.create_function(move |_, entity: i64| {
+            if let Some(entity) = entity_from_lua(entity) {
+                let _ = frame.world.borrow_mut().despawn(entity);
+            }
+            Ok(())
+        })?,
+    )?;
+    hotham.set(
+        "get_translation",
+        scope.create_function(move |_, entity: i64| {
+            let translation = with_transform(frame, entity, |t| t.translation);
+            Ok(Variadic::from_iter(
+                translation.iter().flat_map(|t| t.to_array()),
+            ))
+        })?,
+    )?;
+    hotham.set(
+        "set_translation",
+        scope.create_function(move |_, (entity, x, y, z): (i64, f32, f32, f32)| {
+            Ok(with_transform(frame, entity, |t| t.translation = Vec3::new(x, y, z)).is_some())
+        })?,
+    )?;
+    hotham.set(
+        "get_rotation",
+        scope.create_function(move |_, entity: i64| {
+            let rotation = with_transform(frame, entity, |t| t.rotation);
+            Ok(Variadic::from_iter(
+                rotation.iter().flat_map(|r| r.to_array()),
+            ))
+        })?,
+    )?;
+    hotham.set(
+        "set_rotation",
+        scope.create_function(move |_, (entity, x, y, z, w): (i64, f32, f32, f32, f32)| {
+            Ok(with_transform(frame, entity, |t| {
+                t.rotation = Quat::from_xyzw(x, y, z, w).normalize()
+            })
+            .is_some())
+        })?,
+    )?;
+    hotham.set(
+        "set_scale",
+        scope.create_function(move |_, (entity, x, y, z): (i64, f32, f32, f32)| {
+            Ok(with_transform(frame, entity, |t| t.scale = Vec3::new(x, y, z)).is_some())
+        })?,
+    )?;
+    hotham.set(
+        "play_sound",
+        scope.create_function(move |_, entity: i64| {
+            let entity = match entity_from_lua(entity) {
+                Some(entity) => entity,
+                None => return Ok(false),
+            };
+            let world = frame.world.borrow();
+            let result = match world.get::<&mut SoundEmitter>(entity) {
+                Ok(mut sound_emitter) => {
+                    sound_emitter.play();
+                    true
+                }
+                Err(_) => false,
+            };
+            Ok(result)
+        })?,
+    )?;
+    hotham.set(
+        "subscribe",
+        scope.create_function(move |_, event: i32| {
+            frame.subscriptions.borrow_mut().push(event);
+            Ok(())
+        })?,
+    )?;
+    hotham.set(
+        "log",
+        scope.create_function(|_, message: String| {
+            log::info!("[HOTHAM_SCRIPT] {}", message);
+            Ok(())
+        })?,
+    )?;
+
+    Ok(())
+}
+
+/// Run `f` on the `LocalTransform` of `entity`, if it exists and has one.
+fn with_transform<T>(
+    frame: &Frame,
+    entity: i64,
+    f: impl FnOnce(&mut LocalTransform) -> T,
+) -> Option<T> {
+    let entity = entity_from_lua(entity)?;
+    let world = frame.world.borrow();
+    let mut local_transform = world.get::<&mut LocalTransform>(entity).ok()?;
+    Some(f(&mut local_transform))
+}
+
+fn entity_to_lua(entity: Entity) -> i64 {
+    entity.to_bits().get() as i64
+}
+
+fn entity_from_lua(entity: i64) -> Option<Entity> {
+    Entity::from_bits(entity as u64)
+}
+
+fn script_error(name: &str, reason: impl ToString) -> HothamError {
+    HothamError::ScriptError {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPINNER: &str = r#"
+        local spinner = {}
+
+        function spinner.on_start(self)
+            hotham.set_translation(self.entity, 0, 1, 0)
+            hotham.subscribe(hotham.events.INPUT)
+        end
+
+        function spinner.on_update(self, delta_time)
+            local x, y, z = hotham.get_translation(self.entity)
+            hotham.set_translation(self.entity, x + delta_time, y, z)
+        end
+
+        function spinner.on_input(self, button)
+            if button == hotham.buttons.A then
+                hotham.set_translation(self.entity, 0, 0, 0)
+            end
+        end
+
+        return spinner
+    "#;
+
+    #[test]
+    pub fn test_scripts() {
+        let mut world = World::new();
+        let entity = world.spawn((LocalTransform::default(), Script::new("spinner")));
+
+        let mut lua_context = LuaContext::default();
+        lua_context.load("spinner", SPINNER).unwrap();
+
+        // The first frame should call on_start, then on_update..
+        lua_context.update(&mut world, &[], 0.5);
+        let translation = world.get::<&LocalTransform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::new(0.5, 1.0, 0.0));
+
+        // ..and on_start should have subscribed to input.
+        lua_context.update(&mut world, &[buttons::A], 0.5);
+        let translation = world.get::<&LocalTransform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::ZERO);
+
+        // Reloading the script should start it again.
+        lua_context.load("spinner", SPINNER).unwrap();
+        lua_context.update(&mut world, &[], 0.25);
+        let translation = world.get::<&LocalTransform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::new(0.25, 1.0, 0.0));
+
+        // Removing the Script should stop it.
+        world.remove_one::<Script>(entity).unwrap();
+        lua_context.update(&mut world, &[], 0.25);
+        assert!(lua_context.instances.is_empty());
+    }
+
+    #[test]
+    pub fn test_runaway_script() {
+        let mut world = World::new();
+        let entity = world.spawn((LocalTransform::default(), Script::new("forever")));
+
+        let mut lua_context = LuaContext::default();
+        lua_context
+            .load(
+                "forever",
+                "return { on_update = function(self) while true do end end }",
+            )
+            .unwrap();
+
+        // The script should be stopped, rather than hanging the engine.
+        lua_context.update(&mut world, &[], 0.5);
+        assert!(lua_context.instances[&entity].failed);
+    }
+
+    #[test]
+    pub fn test_invalid_script() {
+        let mut lua_context = LuaContext::default();
+        assert!(lua_context.load("broken", "return {").is_err());
+        assert!(lua_context.load("not_a_table", "return 42").is_err());
+    }
+}
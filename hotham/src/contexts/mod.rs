@@ -1,18 +1,54 @@
 #![allow(missing_docs)]
 pub mod audio_context;
+pub mod device_context;
+pub mod effects_context;
 pub mod gui_context;
+pub mod hand_tracking_context;
 pub mod haptic_context;
+#[cfg(feature = "http")]
+pub mod http_context;
 pub mod input_context;
+#[cfg(feature = "inspector")]
+pub mod inspector_context;
+#[cfg(feature = "lua-scripting")]
+pub mod lua_context;
+pub mod permissions_context;
 pub mod physics_context;
+pub mod platform_context;
 pub mod render_context;
+#[cfg(feature = "wasm-scripting")]
+pub mod script_context;
+pub mod storage_context;
+pub mod time_context;
 pub mod vulkan_context;
 pub mod xr_context;
 
 pub use audio_context::AudioContext;
+pub use device_context::DeviceContext;
+pub use effects_context::EffectsContext;
 pub use gui_context::GuiContext;
+pub use hand_tracking_context::HandTrackingContext;
 pub use haptic_context::HapticContext;
+#[cfg(feature = "http")]
+pub use http_context::{
+    HttpContext, HttpRequest, HttpResponse, RequestId, WebSocket, WebSocketEvent, WebSocketMessage,
+    WebSocketOptions, WebSocketState,
+};
 pub use input_context::InputContext;
+#[cfg(feature = "inspector")]
+pub use inspector_context::InspectorContext;
+#[cfg(feature = "lua-scripting")]
+pub use lua_context::LuaContext;
+pub use permissions_context::PermissionsContext;
 pub use physics_context::PhysicsContext;
+pub use platform_context::PlatformContext;
 pub use render_context::RenderContext;
-pub use vulkan_context::VulkanContext;
-pub use xr_context::{XrContext, XrContextBuilder};
+#[cfg(feature = "wasm-scripting")]
+pub use script_context::ScriptContext;
+pub use storage_context::StorageContext;
+pub use time_context::TimeContext;
+pub use vulkan_context::{PhysicalDeviceSelection, PhysicalDeviceSettings, VulkanContext};
+pub use xr_context::{
+    FoveationLevel, OptionalExtensions, OverlaySettings, XrContext, XrContextBuilder, XrPlugin,
+    XrRuntime,
+};
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use super::platform_context::{Dialog, DialogResult, PlatformContext, PlatformRequest};
+
+/// Called with the outcome of a permission request.
+pub type PermissionCallback = Box<dyn FnOnce(&Permission, PermissionStatus) + Send>;
+
+/// Declares, requests and reports the Android runtime permissions the app needs, like the microphone or scene data.
+///
+/// Each permission must also be in the app's manifest, or Android will deny it without asking. `hotham new
+/// --permission <NAME>` adds the entries for a new app, and [`Permission::manifest_entry`] has them for existing
+/// ones.
+///
+/// Requests are shown and answered through the [`PlatformContext`] on the next call to [`crate::Engine::update`], and
+/// callbacks are called from there, on the main thread:
+/// ```ignore
+/// engine.permissions_context.request(Permission::Microphone, |_, status| {
+///     if status == PermissionStatus::Granted {
+///         ..
+///     }
+/// });
+/// ```
+///
+/// On desktop there are no runtime permissions, so everything is granted.
+#[derive(Default)]
+pub struct PermissionsContext {
+    statuses: HashMap<Permission, PermissionStatus>,
+    queued: Vec<(Permission, Option<PermissionCallback>)>,
+    pending: Vec<PendingRequest>,
+}
+
+/// A runtime permission.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// Record audio from the headset's microphone
+    Microphone,
+    /// Track the user's hands
+    HandTracking,
+    /// Read the room's scene data, eg. walls and furniture
+    Scene,
+    /// Any other Android permission, by name
+    Other(String),
+}
+
+/// Where a [`Permission`] is up to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionStatus {
+    /// Declared, but not checked yet - that happens on the next update
+    Unknown,
+    /// Not granted yet, and not requested
+    NotRequested,
+    /// Waiting for the user to answer
+    Requesting,
+    /// The user granted it
+    Granted,
+    /// The user denied it, or it's missing from the manifest
+    Denied,
+    /// The platform couldn't ask for it
+    Unsupported,
+}
+
+struct PendingRequest {
+    permission: Permission,
+    request: PlatformRequest<DialogResult>,
+    callbacks: Vec<PermissionCallback>,
+}
+
+impl Permission {
+    /// The permission's name on Android
+    pub fn name(&self) -> &str {
+        match self {
+            Permission::Microphone => "android.permission.RECORD_AUDIO",
+            Permission::HandTracking => "com.oculus.permission.HAND_TRACKING",
+            Permission::Scene => "com.oculus.permission.USE_SCENE",
+            Permission::Other(name) => name,
+        }
+    }
+
+    /// What to add to `Cargo.toml` for `cargo apk` to put this permission in the app's manifest.
+    pub fn manifest_entry(&self) -> String {
+        let mut entry = format!(
+            "[[package.metadata.android.uses_permission]]\nname = \"{}\"\n",
+            self.name()
+        );
+        if *self == Permission::HandTracking {
+            entry.push_str(
+                "\n[[package.metadata.android.uses_feature]]\nname = \"oculus.software.handtracking\"\nrequired = false\n",
+            );
+        }
+        entry
+    }
+}
+
+impl PermissionsContext {
+    /// Declare that the app uses `permission`, so its status is tracked and [`PermissionsContext::request_declared`]
+    /// asks for it.
+    pub fn declare(&mut self, permission: Permission) -> &mut Self {
+        self.statuses
+            .entry(permission)
+            .or_insert(PermissionStatus::Unknown);
+        self
+    }
+
+    /// Ask the user for `permission`, declaring it if it wasn't already. `callback` is called once it's been granted
+    /// or denied - straight away on the next update if it already has been.
+    pub fn request<F>(&mut self, permission: Permission, callback: F)
+    where
+        F: FnOnce(&Permission, PermissionStatus) + Send + 'static,
+    {
+        self.declare(permission.clone());
+        self.queued.push((permission, Some(Box::new(callback))));
+    }
+
+    /// Ask for every declared permission that hasn't been granted.
+    pub fn request_declared(&mut self) {
+        for (permission, status) in &self.statuses {
+            if *status != PermissionStatus::Granted {
+                self.queued.push((permission.clone(), None));
+            }
+        }
+    }
+
+    /// Where `permission` is up to. [`PermissionStatus::Unknown`] if it hasn't been declared.
+    pub fn status(&self, permission: &Permission) -> PermissionStatus {
+        self.statuses
+            .get(permission)
+            .copied()
+            .unwrap_or(PermissionStatus::Unknown)
+    }
+
+    /// Has `permission` been granted?
+    pub fn is_granted(&self, permission: &Permission) -> bool {
+        self.status(permission) == PermissionStatus::Granted
+    }
+
+    /// Check newly declared permissions, show queued requests and deliver any answers. Called by the engine every
+    /// frame.
+    pub(crate) fn update(&mut self, platform_context: &mut PlatformContext) {
+        for (permission, status) in &mut self.statuses {
+            if *status == PermissionStatus::Unknown {
+                *status = if platform_context.permission_granted(permission.name()) {
+                    PermissionStatus::Granted
+                } else {
+                    PermissionStatus::NotRequested
+                };
+            }
+        }
+
+        for (permission, callback) in std::mem::take(&mut self.queued) {
+            let status = self.status(&permission);
+            if status == PermissionStatus::Granted {
+                if let Some(callback) = callback {
+                    callback(&permission, status);
+                }
+                continue;
+            }
+
+            // Don't ask twice while the first request is still open.
+            if let Some(pending) = self.pending.iter_mut().find(|p| p.permission == permission) {
+                pending.callbacks.extend(callback);
+                continue;
+            }
+
+            let request = platform_context.show_dialog(Dialog::Permission {
+                permission: permission.name().to_string(),
+            });
+            self.statuses
+                .insert(permission.clone(), PermissionStatus::Requesting);
+            self.pending.push(PendingRequest {
+                permission,
+                request,
+                callbacks: callback.into_iter().collect(),
+            });
+        }
+
+        let mut still_pending = Vec::new();
+        for mut pending in self.pending.drain(..) {
+            let status = match pending.request.poll() {
+                Some(DialogResult::Accepted) => PermissionStatus::Granted,
+                Some(DialogResult::Declined) => PermissionStatus::Denied,
+                Some(DialogResult::Unsupported) => PermissionStatus::Unsupported,
+                None => {
+                    still_pending.push(pending);
+                    continue;
+                }
+            };
+            log::info!(
+                "[HOTHAM_PERMISSIONS] {} is now {:?}",
+                pending.permission.name(),
+                status
+            );
+            self.statuses.insert(pending.permission.clone(), status);
+            for callback in pending.callbacks {
+                callback(&pending.permission, status);
+            }
+        }
+        self.pending = still_pending;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::contexts::platform_context::{
+        KeyboardRequest, KeyboardResult, PlatformBackend, Responder,
+    };
+
+    /// Grants the microphone once asked, and denies everything else.
+    #[derive(Default)]
+    struct TestBackend {
+        microphone_granted: Arc<Mutex<bool>>,
+        responders: Arc<Mutex<Vec<(String, Responder<DialogResult>)>>>,
+    }
+
+    impl PlatformBackend for TestBackend {
+        fn show_keyboard(&mut self, _: KeyboardRequest, responder: Responder<KeyboardResult>) {
+            responder.respond(KeyboardResult::Unsupported);
+        }
+
+        fn show_dialog(&mut self, dialog: Dialog, responder: Responder<DialogResult>) {
+            if let Dialog::Permission { permission } = dialog {
+                self.responders
+                    .lock()
+                    .unwrap()
+                    .push((permission, responder));
+            }
+        }
+
+        fn permission_granted(&self, permission: &str) -> bool {
+            permission == Permission::Microphone.name() && *self.microphone_granted.lock().unwrap()
+        }
+    }
+
+    #[test]
+    pub fn test_permissions() {
+        let backend = TestBackend::default();
+        let responders = backend.responders.clone();
+        let microphone_granted = backend.microphone_granted.clone();
+        let mut platform_context = PlatformContext::with_backend(backend);
+        let mut permissions_context = PermissionsContext::default();
+        let results = Arc::new(Mutex::new(Vec::new()));
+
+        permissions_context.declare(Permission::Scene);
+        assert_eq!(
+            permissions_context.status(&Permission::Scene),
+            PermissionStatus::Unknown
+        );
+        permissions_context.update(&mut platform_context);
+        assert_eq!(
+            permissions_context.status(&Permission::Scene),
+            PermissionStatus::NotRequested
+        );
+
+        // Asking twice only shows one dialog, but both callbacks are called.
+        for _ in 0..2 {
+            let results = results.clone();
+            permissions_context.request(Permission::Microphone, move |_, status| {
+                results.lock().unwrap().push(status)
+            });
+        }
+        permissions_context.request_declared();
+        permissions_context.update(&mut platform_context);
+        assert_eq!(responders.lock().unwrap().len(), 2);
+        assert_eq!(
+            permissions_context.status(&Permission::Microphone),
+            PermissionStatus::Requesting
+        );
+
+        // Answer both dialogs.
+        for (permission, responder) in responders.lock().unwrap().drain(..) {
+            if permission == Permission::Microphone.name() {
+                *microphone_granted.lock().unwrap() = true;
+                responder.respond(DialogResult::Accepted);
+            } else {
+                responder.respond(DialogResult::Declined);
+            }
+        }
+        permissions_context.update(&mut platform_context);
+        assert!(permissions_context.is_granted(&Permission::Microphone));
+        assert_eq!(
+            permissions_context.status(&Permission::Scene),
+            PermissionStatus::Denied
+        );
+        assert_eq!(
+            *results.lock().unwrap(),
+            vec![PermissionStatus::Granted, PermissionStatus::Granted]
+        );
+
+        // Once granted, requests are answered without asking again.
+        let results_clone = results.clone();
+        permissions_context.request(Permission::Microphone, move |_, status| {
+            results_clone.lock().unwrap().push(status)
+        });
+        permissions_context.update(&mut platform_context);
+        assert!(responders.lock().unwrap().is_empty());
+        assert_eq!(results.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    pub fn test_manifest_entry() {
+        let entry = Permission::Microphone.manifest_entry();
+        assert!(entry.contains("name = \"android.permission.RECORD_AUDIO\""));
+        assert!(Permission::HandTracking
+            .manifest_entry()
+            .contains("oculus.software.handtracking"));
+    }
+}
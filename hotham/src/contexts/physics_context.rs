@@ -1,6 +1,12 @@
 use crossbeam::channel::Receiver;
-use rapier3d::na::Matrix3x1;
-use rapier3d::prelude::*;
+use glam::{Affine3A, Quat, Vec3};
+use hecs::{Entity, World};
+use rapier3d::{parry::query::TOIStatus, prelude::*};
+
+use crate::{
+    components::physics::{PhysicalMaterial, Surface},
+    util::{glam_vec_from_na, isometry_from_affine, na_vector_from_glam},
+};
 
 pub const DEFAULT_COLLISION_GROUP: u32 = 0b01;
 pub const PANEL_COLLISION_GROUP: u32 = 0b10;
@@ -13,7 +19,7 @@ pub const DELTA_TIME: f32 = 1. / 72.;
 
 pub struct PhysicsContext {
     pub physics_pipeline: PhysicsPipeline,
-    pub gravity: Matrix3x1<f32>,
+    pub gravity: Vec3,
     pub query_pipeline: QueryPipeline,
     pub colliders: ColliderSet,
     pub broad_phase: BroadPhase,
@@ -27,6 +33,186 @@ pub struct PhysicsContext {
     pub impulse_joints: ImpulseJointSet,
     pub multibody_joints: MultibodyJointSet,
     pub ccd_solver: CCDSolver,
+    /// Everything that started touching during the last physics step, and what they're made of
+    pub surface_impacts: Vec<SurfaceImpact>,
+    /// Everything that started or stopped touching or overlapping during the last physics step
+    pub collision_events: CollisionEvents,
+}
+
+/// Where a ray or projectile hit a surface - see [`PhysicsContext::cast_surface_ray`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceHit {
+    /// The entity that was hit
+    pub entity: Entity,
+    /// What the entity's surface is made of
+    pub surface: Surface,
+    /// Where the hit was, in global space
+    pub point: Vec3,
+    /// The normal of the surface where the hit was, in global space
+    pub normal: Vec3,
+    /// How far from where it started the hit was, in metres
+    pub distance: f32,
+}
+
+/// What a ray or shape hit - see [`PhysicsContext::cast_ray`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// The entity that was hit
+    pub entity: Entity,
+    /// The collider that was hit, for anything that needs rapier's view of it
+    pub collider: ColliderHandle,
+    /// Where the hit was, in global space
+    pub point: Vec3,
+    /// The normal of the surface where the hit was, in global space
+    pub normal: Vec3,
+    /// How far from where it started the hit was, in metres
+    pub distance: f32,
+}
+
+/// Which colliders a ray or shape cast can hit - see [`PhysicsContext::cast_ray`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastFilter<'a> {
+    /// Only colliders in at least one of these collision groups are hit, eg. `PANEL_COLLISION_GROUP`
+    pub groups: u32,
+    /// Whether sensors are hit
+    pub sensors: bool,
+    /// Entities that are never hit, eg. whoever cast the ray
+    pub ignore: &'a [Entity],
+}
+
+impl Default for CastFilter<'_> {
+    fn default() -> Self {
+        Self {
+            groups: u32::MAX,
+            sensors: false,
+            ignore: &[],
+        }
+    }
+}
+
+impl CastFilter<'_> {
+    /// Only hit colliders in at least one of `groups`, ignoring sensors
+    pub fn groups(groups: u32) -> Self {
+        Self {
+            groups,
+            ..Default::default()
+        }
+    }
+
+    /// Can `collider` be hit?
+    fn allows(&self, collider: &Collider) -> bool {
+        !self
+            .ignore
+            .iter()
+            .any(|entity| entity.to_bits().get() as u128 == collider.user_data)
+    }
+
+    /// The equivalent rapier filter, using `predicate` to check [`CastFilter::ignore`]
+    fn query_filter<'p>(
+        &self,
+        predicate: &'p dyn Fn(ColliderHandle, &Collider) -> bool,
+    ) -> QueryFilter<'p> {
+        let mut filter = QueryFilter::new().predicate(predicate);
+        if self.groups != u32::MAX {
+            filter = filter.groups(InteractionGroups::new(u32::MAX, self.groups));
+        }
+        if self.sensors {
+            filter
+        } else {
+            filter.exclude_sensors()
+        }
+    }
+}
+
+/// Two entities that started touching during a physics step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceImpact {
+    /// The first entity
+    pub entity_a: Entity,
+    /// What the first entity's surface is made of
+    pub surface_a: Surface,
+    /// The second entity
+    pub entity_b: Entity,
+    /// What the second entity's surface is made of
+    pub surface_b: Surface,
+    /// Where they touched, in global space
+    pub point: Vec3,
+    /// How fast they were moving towards each other, in metres per second. Handy for picking how loud a sound to play.
+    pub speed: f32,
+}
+
+/// Two entities that started or stopped touching or overlapping during a physics step - see [`CollisionEvents`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityCollision {
+    /// The first entity
+    pub entity_a: Entity,
+    /// The second entity
+    pub entity_b: Entity,
+    /// `true` if they started touching, `false` if they stopped
+    pub started: bool,
+    /// `true` if either of them is a sensor, so they overlapped rather than touched
+    pub sensor: bool,
+}
+
+impl EntityCollision {
+    /// If `entity` is one of the pair, the other one
+    pub fn other(&self, entity: Entity) -> Option<Entity> {
+        if self.entity_a == entity {
+            Some(self.entity_b)
+        } else if self.entity_b == entity {
+            Some(self.entity_a)
+        } else {
+            None
+        }
+    }
+}
+
+/// The collisions from the last physics step, with rapier's colliders resolved to the entities they belong to. It's
+/// refilled by `physics_system` every step, so read it or [`CollisionEvents::drain`] it each frame after physics has
+/// run.
+///
+/// Colliders that have been taken out of the simulation, eg. by [`crate::components::physics::Disabled`], don't get
+/// events for stopping touching, as there's no longer an entity to report them for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollisionEvents {
+    events: Vec<EntityCollision>,
+}
+
+impl CollisionEvents {
+    /// The events from the last step
+    pub fn iter(&self) -> impl Iterator<Item = &EntityCollision> {
+        self.events.iter()
+    }
+
+    /// Take the events from the last step, so they aren't seen again
+    pub fn drain(&mut self) -> impl Iterator<Item = EntityCollision> + '_ {
+        self.events.drain(..)
+    }
+
+    /// The events from the last step that `entity` was part of
+    pub fn involving(&self, entity: Entity) -> impl Iterator<Item = &EntityCollision> {
+        self.events
+            .iter()
+            .filter(move |event| event.other(entity).is_some())
+    }
+
+    /// Are there no events?
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The number of events
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub(crate) fn push(&mut self, event: EntityCollision) {
+        self.events.push(event);
+    }
 }
 
 impl Default for PhysicsContext {
@@ -46,7 +232,7 @@ impl Default for PhysicsContext {
 
         PhysicsContext {
             physics_pipeline,
-            gravity: [0., 0., 0.].into(),
+            gravity: Vec3::ZERO,
             query_pipeline: QueryPipeline::new(),
             colliders: ColliderSet::new(),
             broad_phase: BroadPhase::new(),
@@ -60,6 +246,8 @@ impl Default for PhysicsContext {
             impulse_joints,
             multibody_joints,
             ccd_solver,
+            surface_impacts: Vec::new(),
+            collision_events: Default::default(),
         }
     }
 }
@@ -67,7 +255,7 @@ impl Default for PhysicsContext {
 impl PhysicsContext {
     pub fn update(&mut self) {
         self.physics_pipeline.step(
-            &self.gravity,
+            &na_vector_from_glam(self.gravity),
             &self.integration_parameters,
             &mut self.island_manager,
             &mut self.broad_phase,
@@ -84,4 +272,285 @@ impl PhysicsContext {
         self.query_pipeline
             .update(&self.island_manager, &self.rigid_bodies, &self.colliders);
     }
+
+    /// Find the first surface hit by a ray from `origin` along `direction`, up to `max_distance` metres away. Sensors
+    /// are ignored.
+    pub fn cast_surface_ray(
+        &self,
+        world: &World,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<SurfaceHit> {
+        self.sweep(
+            world,
+            origin,
+            direction.try_normalize()? * max_distance,
+            0.,
+            &[],
+        )
+    }
+
+    /// Find what a hitscan weapon fired from `origin` along `direction` hits, up to `max_distance` metres away. Like
+    /// [`PhysicsContext::cast_surface_ray`], but `shooter` is ignored so the shot doesn't hit whoever fired it.
+    ///
+    /// Use [`crate::components::projectile::add_tracer_to_world`] to show where the shot went.
+    pub fn hitscan(
+        &self,
+        world: &World,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        shooter: Option<Entity>,
+    ) -> Option<SurfaceHit> {
+        let ignore = shooter.into_iter().collect::<Vec<_>>();
+        self.sweep(
+            world,
+            origin,
+            direction.try_normalize()? * max_distance,
+            0.,
+            &ignore,
+        )
+    }
+
+    /// Find the first collider hit by a ray from `origin` along `direction`, up to `max_distance` metres away, and the
+    /// entity it belongs to. Only colliders that `filter` allows are hit.
+    pub fn cast_ray(
+        &self,
+        world: &World,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: CastFilter,
+    ) -> Option<RayHit> {
+        let ray = Ray::new(
+            na_vector_from_glam(origin).into(),
+            na_vector_from_glam(direction.try_normalize()?),
+        );
+        let predicate = |_, collider: &Collider| filter.allows(collider);
+        let (handle, intersection) = self.query_pipeline.cast_ray_and_get_normal(
+            &self.rigid_bodies,
+            &self.colliders,
+            &ray,
+            max_distance,
+            true,
+            filter.query_filter(&predicate),
+        )?;
+        Some(self.ray_hit(world, handle, &ray, intersection))
+    }
+
+    /// Find every collider hit by a ray from `origin` along `direction`, up to `max_distance` metres away, nearest first.
+    /// Only colliders that `filter` allows are hit.
+    pub fn intersections_with_ray(
+        &self,
+        world: &World,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: CastFilter,
+    ) -> Vec<RayHit> {
+        let direction = match direction.try_normalize() {
+            Some(direction) => direction,
+            None => return Vec::new(),
+        };
+        let ray = Ray::new(
+            na_vector_from_glam(origin).into(),
+            na_vector_from_glam(direction),
+        );
+        let predicate = |_, collider: &Collider| filter.allows(collider);
+        let mut hits = Vec::new();
+        self.query_pipeline.intersections_with_ray(
+            &self.rigid_bodies,
+            &self.colliders,
+            &ray,
+            max_distance,
+            true,
+            filter.query_filter(&predicate),
+            |handle, intersection| {
+                hits.push(self.ray_hit(world, handle, &ray, intersection));
+                true
+            },
+        );
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// Move `shape` from `origin`, rotated by `rotation`, by `translation` and find the first collider it hits, and the
+    /// entity it belongs to. Only colliders that `filter` allows are hit. If the shape starts inside a collider, the hit
+    /// is at `origin`, with a distance of zero.
+    pub fn cast_shape(
+        &self,
+        world: &World,
+        shape: &dyn Shape,
+        origin: Vec3,
+        rotation: Quat,
+        translation: Vec3,
+        filter: CastFilter,
+    ) -> Option<RayHit> {
+        let max_distance = translation.length();
+        let direction = na_vector_from_glam(translation.try_normalize()?);
+        let position = isometry_from_affine(&Affine3A::from_rotation_translation(rotation, origin));
+        let predicate = |_, collider: &Collider| filter.allows(collider);
+        let (handle, toi) = self.query_pipeline.cast_shape(
+            &self.rigid_bodies,
+            &self.colliders,
+            &position,
+            &direction,
+            shape,
+            max_distance,
+            filter.query_filter(&predicate),
+        )?;
+        // If the shape starts inside something there's no witness point, so use where it started.
+        let point = if toi.status == TOIStatus::Penetrating {
+            origin
+        } else {
+            glam_vec_from_na(&toi.witness1.coords)
+        };
+        Some(RayHit {
+            entity: self.entity_for_collider(world, handle),
+            collider: handle,
+            point,
+            normal: glam_vec_from_na(&toi.normal1),
+            distance: toi.toi,
+        })
+    }
+
+    /// Move a sphere of `radius` from `origin` by `translation` and find the first surface it hits, ignoring sensors
+    /// and the entities in `ignore`. A `radius` of zero casts a ray instead.
+    pub(crate) fn sweep(
+        &self,
+        world: &World,
+        origin: Vec3,
+        translation: Vec3,
+        radius: f32,
+        ignore: &[Entity],
+    ) -> Option<SurfaceHit> {
+        let filter = CastFilter {
+            ignore,
+            ..Default::default()
+        };
+        let hit = if radius > 0. {
+            self.cast_shape(
+                world,
+                &Ball::new(radius),
+                origin,
+                Quat::IDENTITY,
+                translation,
+                filter,
+            )?
+        } else {
+            self.cast_ray(world, origin, translation, translation.length(), filter)?
+        };
+
+        Some(SurfaceHit {
+            entity: hit.entity,
+            surface: surface_of(world, hit.entity),
+            point: hit.point,
+            normal: hit.normal,
+            distance: hit.distance,
+        })
+    }
+
+    /// Find the surface directly below `point`, up to `max_distance` metres away - for footsteps, say.
+    pub fn surface_below(
+        &self,
+        world: &World,
+        point: Vec3,
+        max_distance: f32,
+    ) -> Option<SurfaceHit> {
+        self.cast_surface_ray(world, point, -Vec3::Y, max_distance)
+    }
+
+    /// Fill in `collision_events` with everything that started or stopped touching during the last step, and
+    /// `surface_impacts` with the solid things that started touching.
+    pub(crate) fn update_collision_events(&mut self, world: &World) {
+        self.surface_impacts.clear();
+        self.collision_events.clear();
+
+        while let Ok(event) = self.collision_recv.try_recv() {
+            let (handle_a, handle_b) = (event.collider1(), event.collider2());
+            let (collider_a, collider_b) =
+                match (self.colliders.get(handle_a), self.colliders.get(handle_b)) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => continue,
+                };
+            let entity_a = self.entity_for_collider(world, handle_a);
+            let entity_b = self.entity_for_collider(world, handle_b);
+            self.collision_events.push(EntityCollision {
+                entity_a,
+                entity_b,
+                started: event.started(),
+                sensor: event.sensor(),
+            });
+
+            if !event.started() || event.sensor() {
+                continue;
+            }
+
+            // Contacts are found at the end of a step, so the colliders haven't been pushed apart yet and their
+            // velocities tell us how hard they hit.
+            let contact = self
+                .narrow_phase
+                .contact_pair(handle_a, handle_b)
+                .and_then(|pair| {
+                    let (manifold, contact) = pair.find_deepest_contact()?;
+                    let (collider, normal) = if pair.collider1 == handle_a {
+                        (collider_a, manifold.data.normal)
+                    } else {
+                        (collider_b, -manifold.data.normal)
+                    };
+                    let point = collider.position() * contact.local_p1;
+                    Some((glam_vec_from_na(&point.coords), glam_vec_from_na(&normal)))
+                });
+            let (point, normal) = match contact {
+                Some(contact) => contact,
+                None => continue,
+            };
+
+            let velocity = |collider: &Collider| {
+                collider
+                    .parent()
+                    .and_then(|parent| self.rigid_bodies.get(parent))
+                    .map_or(Vec3::ZERO, |body| glam_vec_from_na(body.linvel()))
+            };
+            let speed = (velocity(collider_a) - velocity(collider_b))
+                .dot(normal)
+                .abs();
+
+            self.surface_impacts.push(SurfaceImpact {
+                entity_a,
+                surface_a: surface_of(world, entity_a),
+                entity_b,
+                surface_b: surface_of(world, entity_b),
+                point,
+                speed,
+            });
+        }
+    }
+
+    fn ray_hit(
+        &self,
+        world: &World,
+        handle: ColliderHandle,
+        ray: &Ray,
+        intersection: RayIntersection,
+    ) -> RayHit {
+        RayHit {
+            entity: self.entity_for_collider(world, handle),
+            collider: handle,
+            point: glam_vec_from_na(&ray.point_at(intersection.toi).coords),
+            normal: glam_vec_from_na(&intersection.normal),
+            distance: intersection.toi,
+        }
+    }
+
+    fn entity_for_collider(&self, world: &World, handle: ColliderHandle) -> Entity {
+        unsafe { world.find_entity_from_id(self.colliders[handle].user_data as _) }
+    }
+}
+
+fn surface_of(world: &World, entity: Entity) -> Surface {
+    world
+        .get::<&PhysicalMaterial>(entity)
+        .map_or(Surface::Default, |material| material.surface)
 }
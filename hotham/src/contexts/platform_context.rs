@@ -0,0 +1,639 @@
+use crossbeam::channel::{Receiver, Sender, TryRecvError};
+
+/// Shows the platform's own UI - the system keyboard, and dialogs like store reviews and permission requests - from
+/// engine code.
+///
+/// Each request returns a [`PlatformRequest`] straight away, which is polled each frame until the user has answered:
+/// ```ignore
+/// let mut name_request = engine.platform_context.show_keyboard(KeyboardRequest::new("Your name"));
+/// loop {
+///     engine.update()?;
+///     if let Some(KeyboardResult::Submitted(name)) = name_request.poll() {
+///         ..
+///     }
+/// }
+/// ```
+///
+/// How requests are shown depends on the [`PlatformBackend`]. On Android the system keyboard and dialogs are used. On
+/// desktop, where apps run in the simulator, requests are answered in the terminal the simulator was started from.
+pub struct PlatformContext {
+    backend: Box<dyn PlatformBackend>,
+}
+
+/// A request for a line of text from the system keyboard.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardRequest {
+    /// What the text is for, eg. "Your name"
+    pub prompt: String,
+    /// The text to start with
+    pub initial_text: String,
+    /// The most characters the user can type, if there's a limit
+    pub max_length: Option<usize>,
+}
+
+impl KeyboardRequest {
+    /// Ask for some text, with `prompt` telling the user what it's for.
+    pub fn new(prompt: &str) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// How a [`KeyboardRequest`] was answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardResult {
+    /// The user finished typing this text
+    Submitted(String),
+    /// The user closed the keyboard, or another request replaced this one
+    Cancelled,
+    /// This platform has no keyboard to show
+    Unsupported,
+}
+
+/// A dialog belonging to the platform or the runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dialog {
+    /// Ask the user to review the app in the store, by opening its store page. `app_id` is the app's store ID.
+    StoreReview {
+        /// The app's ID in the store
+        app_id: String,
+    },
+    /// Ask the user to grant a permission, eg. `android.permission.RECORD_AUDIO`. It must also be declared in the app's
+    /// manifest.
+    Permission {
+        /// The permission's name
+        permission: String,
+    },
+}
+
+/// How a [`Dialog`] was answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogResult {
+    /// The user agreed, or the permission was granted
+    Accepted,
+    /// The user said no, or closed the dialog
+    Declined,
+    /// This platform can't show the dialog
+    Unsupported,
+}
+
+/// A key typed on the system keyboard, passed to [`PlatformBackend::key_pressed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPress {
+    /// A character to add to the text
+    Char(char),
+    /// Delete the last character
+    Backspace,
+    /// Finish typing
+    Enter,
+    /// Close the keyboard without finishing
+    Back,
+}
+
+/// The answer to a request made through the [`PlatformContext`], which arrives some time later.
+pub struct PlatformRequest<T> {
+    receiver: Receiver<T>,
+    result: Option<T>,
+    if_abandoned: T,
+}
+
+impl<T: Clone> PlatformRequest<T> {
+    /// Create a request, and the [`Responder`] that answers it. If the responder is dropped without answering, the
+    /// request is answered with `if_abandoned`.
+    pub fn new(if_abandoned: T) -> (Self, Responder<T>) {
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let request = Self {
+            receiver,
+            result: None,
+            if_abandoned,
+        };
+        (request, Responder { sender })
+    }
+
+    /// The answer, once there is one. Keeps returning it after it's arrived.
+    pub fn poll(&mut self) -> Option<T> {
+        if self.result.is_none() {
+            self.result = match self.receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Disconnected) => Some(self.if_abandoned.clone()),
+                Err(TryRecvError::Empty) => None,
+            };
+        }
+        self.result.clone()
+    }
+}
+
+/// Answers a [`PlatformRequest`]. Held by a [`PlatformBackend`] until the user has answered.
+pub struct Responder<T> {
+    sender: Sender<T>,
+}
+
+impl<T> Responder<T> {
+    /// Answer the request with `result`.
+    pub fn respond(self, result: T) {
+        // The app may have dropped the request, in which case nobody's waiting for the answer.
+        let _ = self.sender.send(result);
+    }
+}
+
+/// Shows requests from the [`PlatformContext`] on a particular platform. Replace the default one with
+/// [`PlatformContext::with_backend`], eg. to draw the app's own keyboard.
+pub trait PlatformBackend: Send {
+    /// Show the keyboard for `request`, answering it with `responder` once the user is done.
+    fn show_keyboard(&mut self, request: KeyboardRequest, responder: Responder<KeyboardResult>);
+
+    /// Show `dialog`, answering it with `responder` once the user is done.
+    fn show_dialog(&mut self, dialog: Dialog, responder: Responder<DialogResult>);
+
+    /// The text typed on the keyboard so far, while it's showing.
+    fn keyboard_text(&self) -> Option<&str> {
+        None
+    }
+
+    /// Has the app been granted the Android permission `permission`? Desktop platforms don't have runtime
+    /// permissions, so by default everything is granted.
+    fn permission_granted(&self, _permission: &str) -> bool {
+        true
+    }
+
+    /// A key was typed on the system keyboard.
+    fn key_pressed(&mut self, _key: KeyPress) {}
+
+    /// Called every frame, with whether the session has focus. Platform dialogs take focus away while they're open.
+    fn update(&mut self, _focused: bool) {}
+}
+
+impl PlatformContext {
+    /// Create a `PlatformContext` with the default backend for this platform.
+    pub fn new() -> Self {
+        Self::with_backend(default_backend())
+    }
+
+    /// Create a `PlatformContext` that shows requests with `backend`.
+    pub fn with_backend(backend: impl PlatformBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+
+    /// Show the system keyboard to get a line of text. Showing it again cancels any request that's still waiting.
+    pub fn show_keyboard(&mut self, request: KeyboardRequest) -> PlatformRequest<KeyboardResult> {
+        let (platform_request, responder) = PlatformRequest::new(KeyboardResult::Cancelled);
+        self.backend.show_keyboard(request, responder);
+        platform_request
+    }
+
+    /// Show a platform dialog.
+    pub fn show_dialog(&mut self, dialog: Dialog) -> PlatformRequest<DialogResult> {
+        let (platform_request, responder) = PlatformRequest::new(DialogResult::Declined);
+        self.backend.show_dialog(dialog, responder);
+        platform_request
+    }
+
+    /// The text typed on the keyboard so far, while it's showing, for apps that draw their own text field.
+    pub fn keyboard_text(&self) -> Option<&str> {
+        self.backend.keyboard_text()
+    }
+
+    /// Has the app been granted the Android permission `permission`? See [`crate::contexts::PermissionsContext`].
+    pub fn permission_granted(&self, permission: &str) -> bool {
+        self.backend.permission_granted(permission)
+    }
+
+    /// Pass a key typed on the system keyboard to the backend. Called by the engine.
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(crate) fn key_pressed(&mut self, key: KeyPress) {
+        self.backend.key_pressed(key);
+    }
+
+    /// Called by the engine every frame.
+    pub(crate) fn update(&mut self, focused: bool) {
+        self.backend.update(focused);
+    }
+}
+
+impl Default for PlatformContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The key typed for an Android keycode, if it's one the keyboard uses.
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+pub(crate) fn key_press_from_keycode(keycode: u32, shift: bool) -> Option<KeyPress> {
+    // See https://developer.android.com/reference/android/view/KeyEvent
+    let key = match keycode {
+        4 => KeyPress::Back,
+        7..=16 => KeyPress::Char((b'0' + (keycode - 7) as u8) as char),
+        29..=54 => {
+            let c = (b'a' + (keycode - 29) as u8) as char;
+            KeyPress::Char(if shift { c.to_ascii_uppercase() } else { c })
+        }
+        55 => KeyPress::Char(','),
+        56 => KeyPress::Char('.'),
+        62 => KeyPress::Char(' '),
+        66 => KeyPress::Enter,
+        67 => KeyPress::Backspace,
+        69 => KeyPress::Char('-'),
+        77 => KeyPress::Char('@'),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Apply `key` to the text being typed for `request`. Returns the result if it finished the request.
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn type_key(request: &KeyboardRequest, text: &mut String, key: KeyPress) -> Option<KeyboardResult> {
+    match key {
+        KeyPress::Char(c) => {
+            if request
+                .max_length
+                .map_or(true, |max| text.chars().count() < max)
+            {
+                text.push(c);
+            }
+            None
+        }
+        KeyPress::Backspace => {
+            text.pop();
+            None
+        }
+        KeyPress::Enter => Some(KeyboardResult::Submitted(text.clone())),
+        KeyPress::Back => Some(KeyboardResult::Cancelled),
+    }
+}
+
+#[cfg(target_os = "android")]
+fn default_backend() -> impl PlatformBackend {
+    android::AndroidBackend::default()
+}
+
+#[cfg(not(target_os = "android"))]
+fn default_backend() -> impl PlatformBackend {
+    desktop::TerminalBackend::default()
+}
+
+#[cfg(target_os = "android")]
+pub(crate) use android::with_activity;
+
+#[cfg(target_os = "android")]
+mod android {
+    use anyhow::Result;
+    use jni::{
+        objects::{JObject, JValue},
+        JNIEnv, JavaVM,
+    };
+
+    use super::*;
+
+    /// Uses the system keyboard, and Android's own permission and store activities. The keyboard's text arrives as key
+    /// events, so the app is responsible for drawing it - see [`PlatformContext::keyboard_text`]. On Quest, the manifest
+    /// needs `<uses-feature android:name="oculus.software.overlay_keyboard" android:required="false"/>`.
+    #[derive(Default)]
+    pub(super) struct AndroidBackend {
+        keyboard: Option<(KeyboardRequest, String, Responder<KeyboardResult>)>,
+        permissions: Vec<PendingPermission>,
+    }
+
+    struct PendingPermission {
+        permission: String,
+        responder: Responder<DialogResult>,
+        lost_focus: bool,
+    }
+
+    impl PlatformBackend for AndroidBackend {
+        fn show_keyboard(
+            &mut self,
+            request: KeyboardRequest,
+            responder: Responder<KeyboardResult>,
+        ) {
+            if let Some((_, _, previous)) = self.keyboard.take() {
+                previous.respond(KeyboardResult::Cancelled);
+            }
+            ndk_glue::native_activity().show_soft_input(true);
+            let text = request.initial_text.clone();
+            self.keyboard = Some((request, text, responder));
+        }
+
+        fn show_dialog(&mut self, dialog: Dialog, responder: Responder<DialogResult>) {
+            match dialog {
+                Dialog::StoreReview { app_id } => {
+                    let url = format!("https://www.oculus.com/experiences/quest/{}/", app_id);
+                    match with_activity(|env, activity| open_url(env, activity, &url)) {
+                        Ok(()) => responder.respond(DialogResult::Accepted),
+                        Err(e) => {
+                            log::error!("[HOTHAM_PLATFORM] Unable to open store page: {:?}", e);
+                            responder.respond(DialogResult::Unsupported);
+                        }
+                    }
+                }
+                Dialog::Permission { permission } => {
+                    let requested = with_activity(|env, activity| {
+                        if is_granted(env, activity, &permission)? {
+                            return Ok(false);
+                        }
+                        request_permission(env, activity, &permission)?;
+                        Ok(true)
+                    });
+                    match requested {
+                        Ok(false) => responder.respond(DialogResult::Accepted),
+                        Ok(true) => self.permissions.push(PendingPermission {
+                            permission,
+                            responder,
+                            lost_focus: false,
+                        }),
+                        Err(e) => {
+                            log::error!(
+                                "[HOTHAM_PLATFORM] Unable to request {}: {:?}",
+                                permission,
+                                e
+                            );
+                            responder.respond(DialogResult::Unsupported);
+                        }
+                    }
+                }
+            }
+        }
+
+        fn keyboard_text(&self) -> Option<&str> {
+            self.keyboard.as_ref().map(|(_, text, _)| text.as_str())
+        }
+
+        fn permission_granted(&self, permission: &str) -> bool {
+            with_activity(|env, activity| is_granted(env, activity, permission)).unwrap_or(false)
+        }
+
+        fn key_pressed(&mut self, key: KeyPress) {
+            let (request, text, _) = match &mut self.keyboard {
+                Some(keyboard) => keyboard,
+                None => return,
+            };
+            if let Some(result) = type_key(request, text, key) {
+                let (_, _, responder) = self.keyboard.take().unwrap();
+                responder.respond(result);
+                ndk_glue::native_activity().hide_soft_input(false);
+            }
+        }
+
+        fn update(&mut self, focused: bool) {
+            // The permission dialog takes focus while it's open, so the answer is in once focus comes back.
+            let mut still_pending = Vec::new();
+            for mut pending in self.permissions.drain(..) {
+                if !focused {
+                    pending.lost_focus = true;
+                    still_pending.push(pending);
+                    continue;
+                }
+                if !pending.lost_focus {
+                    still_pending.push(pending);
+                    continue;
+                }
+                let granted =
+                    with_activity(|env, activity| is_granted(env, activity, &pending.permission))
+                        .unwrap_or(false);
+                pending.responder.respond(if granted {
+                    DialogResult::Accepted
+                } else {
+                    DialogResult::Declined
+                });
+            }
+            self.permissions = still_pending;
+        }
+    }
+
+    /// Call `f` with the JNI environment and the app's `Activity`.
+    pub(crate) fn with_activity<T>(
+        f: impl FnOnce(&JNIEnv, JObject) -> jni::errors::Result<T>,
+    ) -> Result<T> {
+        let native_activity = ndk_glue::native_activity();
+        let vm = unsafe { JavaVM::from_raw(native_activity.vm().cast())? };
+        let env = vm.attach_current_thread()?;
+        let activity = JObject::from(native_activity.activity());
+        let result = f(&env, activity);
+        if env.exception_check()? {
+            env.exception_clear()?;
+        }
+        Ok(result?)
+    }
+
+    fn is_granted(env: &JNIEnv, activity: JObject, permission: &str) -> jni::errors::Result<bool> {
+        let permission = env.new_string(permission)?;
+        let result = env
+            .call_method(
+                activity,
+                "checkSelfPermission",
+                "(Ljava/lang/String;)I",
+                &[JValue::Object(permission.into())],
+            )?
+            .i()?;
+        // PackageManager.PERMISSION_GRANTED
+        Ok(result == 0)
+    }
+
+    fn request_permission(
+        env: &JNIEnv,
+        activity: JObject,
+        permission: &str,
+    ) -> jni::errors::Result<()> {
+        let permissions = env.new_object_array(1, "java/lang/String", JObject::null())?;
+        env.set_object_array_element(permissions, 0, env.new_string(permission)?)?;
+        env.call_method(
+            activity,
+            "requestPermissions",
+            "([Ljava/lang/String;I)V",
+            &[JValue::Object(permissions.into()), JValue::Int(0)],
+        )?;
+        Ok(())
+    }
+
+    fn open_url(env: &JNIEnv, activity: JObject, url: &str) -> jni::errors::Result<()> {
+        let uri = env
+            .call_static_method(
+                "android/net/Uri",
+                "parse",
+                "(Ljava/lang/String;)Landroid/net/Uri;",
+                &[JValue::Object(env.new_string(url)?.into())],
+            )?
+            .l()?;
+        let intent = env.new_object(
+            "android/content/Intent",
+            "(Ljava/lang/String;Landroid/net/Uri;)V",
+            &[
+                JValue::Object(env.new_string("android.intent.action.VIEW")?.into()),
+                JValue::Object(uri),
+            ],
+        )?;
+        env.call_method(
+            activity,
+            "startActivity",
+            "(Landroid/content/Intent;)V",
+            &[JValue::Object(intent)],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+mod desktop {
+    use std::{
+        io::{self, BufRead, Write},
+        thread,
+    };
+
+    use super::*;
+
+    type Prompt = Box<dyn FnOnce(&mut dyn BufRead) + Send>;
+
+    /// Answers requests in the terminal, one at a time, on a background thread so the frame loop keeps running.
+    pub(super) struct TerminalBackend {
+        prompts: Sender<Prompt>,
+    }
+
+    impl Default for TerminalBackend {
+        fn default() -> Self {
+            let (prompts, receiver) = crossbeam::channel::unbounded::<Prompt>();
+            thread::Builder::new()
+                .name("hotham_platform_prompts".to_string())
+                .spawn(move || {
+                    let stdin = io::stdin();
+                    let mut stdin = stdin.lock();
+                    for prompt in receiver {
+                        prompt(&mut stdin);
+                    }
+                })
+                .expect("Unable to spawn platform prompt thread");
+            Self { prompts }
+        }
+    }
+
+    impl TerminalBackend {
+        fn prompt(&self, prompt: impl FnOnce(&mut dyn BufRead) + Send + 'static) {
+            // If the thread has gone, the responder is dropped with the prompt and the request is abandoned.
+            let _ = self.prompts.send(Box::new(prompt));
+        }
+    }
+
+    impl PlatformBackend for TerminalBackend {
+        fn show_keyboard(
+            &mut self,
+            request: KeyboardRequest,
+            responder: Responder<KeyboardResult>,
+        ) {
+            self.prompt(move |stdin| {
+                print!("[HOTHAM_PLATFORM] {}: ", request.prompt);
+                let _ = io::stdout().flush();
+                let mut text = String::new();
+                let result = match stdin.read_line(&mut text) {
+                    Ok(0) | Err(_) => KeyboardResult::Cancelled,
+                    Ok(_) => {
+                        let mut text = text.trim_end_matches(&['\r', '\n'][..]).to_string();
+                        if let Some(max_length) = request.max_length {
+                            text = text.chars().take(max_length).collect();
+                        }
+                        KeyboardResult::Submitted(text)
+                    }
+                };
+                responder.respond(result);
+            });
+        }
+
+        fn show_dialog(&mut self, dialog: Dialog, responder: Responder<DialogResult>) {
+            let question = match dialog {
+                Dialog::StoreReview { app_id } => {
+                    format!("Open the store page for {} to leave a review?", app_id)
+                }
+                Dialog::Permission { permission } => format!("Grant {}?", permission),
+            };
+            self.prompt(move |stdin| {
+                print!("[HOTHAM_PLATFORM] {} [y/n]: ", question);
+                let _ = io::stdout().flush();
+                let mut answer = String::new();
+                let result = match stdin.read_line(&mut answer) {
+                    Ok(_) if answer.trim().eq_ignore_ascii_case("y") => DialogResult::Accepted,
+                    _ => DialogResult::Declined,
+                };
+                responder.respond(result);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every request straight away.
+    struct ImmediateBackend;
+
+    impl PlatformBackend for ImmediateBackend {
+        fn show_keyboard(
+            &mut self,
+            request: KeyboardRequest,
+            responder: Responder<KeyboardResult>,
+        ) {
+            responder.respond(KeyboardResult::Submitted(request.initial_text));
+        }
+
+        fn show_dialog(&mut self, _dialog: Dialog, _responder: Responder<DialogResult>) {
+            // Dropping the responder abandons the request.
+        }
+    }
+
+    #[test]
+    pub fn test_platform_requests() {
+        let mut platform_context = PlatformContext::with_backend(ImmediateBackend);
+        let mut request = platform_context.show_keyboard(KeyboardRequest {
+            initial_text: "hello".to_string(),
+            ..KeyboardRequest::new("Greeting")
+        });
+        let expected = Some(KeyboardResult::Submitted("hello".to_string()));
+        assert_eq!(request.poll(), expected);
+        assert_eq!(request.poll(), expected);
+
+        let mut request = platform_context.show_dialog(Dialog::StoreReview {
+            app_id: "1234".to_string(),
+        });
+        assert_eq!(request.poll(), Some(DialogResult::Declined));
+
+        let (mut request, responder) = PlatformRequest::new(KeyboardResult::Cancelled);
+        assert_eq!(request.poll(), None);
+        responder.respond(KeyboardResult::Unsupported);
+        assert_eq!(request.poll(), Some(KeyboardResult::Unsupported));
+    }
+
+    #[test]
+    pub fn test_typing() {
+        let keys = [
+            (36, true),
+            (33, false),
+            (40, false),
+            (40, false),
+            (43, false),
+        ];
+        let keys = keys
+            .iter()
+            .map(|&(keycode, shift)| key_press_from_keycode(keycode, shift).unwrap());
+        let request = KeyboardRequest {
+            max_length: Some(4),
+            ..Default::default()
+        };
+        let mut text = String::new();
+        for key in keys {
+            assert_eq!(type_key(&request, &mut text, key), None);
+        }
+        assert_eq!(text, "Hell");
+
+        assert_eq!(type_key(&request, &mut text, KeyPress::Backspace), None);
+        assert_eq!(
+            type_key(
+                &request,
+                &mut text,
+                key_press_from_keycode(66, false).unwrap()
+            ),
+            Some(KeyboardResult::Submitted("Hel".to_string()))
+        );
+        assert_eq!(key_press_from_keycode(1000, false), None);
+    }
+}
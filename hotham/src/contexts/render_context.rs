@@ -1,5 +1,8 @@
-use std::{collections::HashMap, ffi::CStr, mem::size_of, slice::from_ref as slice_from_ref};
+use std::{
+    collections::HashMap, ffi::CStr, mem::size_of, slice::from_ref as slice_from_ref, time::Instant,
+};
 
+/// Clear values for the color and depth attachments when depth is reversed.
 pub static CLEAR_VALUES: [vk::ClearValue; 2] = [
     vk::ClearValue {
         color: vk::ClearColorValue {
@@ -14,36 +17,84 @@ pub static CLEAR_VALUES: [vk::ClearValue; 2] = [
     },
 ];
 
+/// Clear values for the color and depth attachments when depth isn't reversed.
+pub static CLEAR_VALUES_STANDARD_Z: [vk::ClearValue; 2] = [
+    vk::ClearValue {
+        color: vk::ClearColorValue {
+            float32: [0.0, 0.0, 0.0, 1.0],
+        },
+    },
+    vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        },
+    },
+];
+
 const CULLING_TIMEOUT: u64 = u64::MAX;
+/// The `local_size_x` of `culling.comp`
+const CULLING_WORKGROUP_SIZE: usize = 1024;
 
+#[cfg(feature = "ray-query")]
+use crate::rendering::{
+    ray_query::{RayQueryParams, RayQueryShading},
+    scene_description::SceneDescription,
+};
 use crate::{
-    contexts::{VulkanContext, XrContext},
+    components::{Mesh, SpriteLayer},
+    contexts::{xr_context::needs_srgb_encoding, FoveationLevel, VulkanContext, XrContext},
     rendering::{
-        camera::{extract_planes_from_frustum, Camera, Frustum},
+        accessibility::{AccessibilitySettings, OutlineInstance, OutlinePipeline},
+        camera::{extract_planes_from_frustum, Camera, ClipPlanes, Frustum},
+        compute::{ComputeBuffer, ComputePassId, ComputePasses, EngineBuffers},
+        crowd::{BakedAnimation, CrowdId, CrowdRenderer},
         descriptors::Descriptors,
+        fog::{Fog, FogParams, FogQuality, FogVolumeData, VolumetricFog},
         frame::Frame,
         image::Image,
+        instancing::InstancedRenderer,
+        lens_flare::{LensFlareData, LensFlarePipeline},
+        light_probes::{LightProbeGrid, ShProbe},
+        picking::{PickId, PickRay, Picking, NO_ENTITY},
         primitive::Primitive,
-        resources::Resources,
+        ray_query::RayQuerySettings,
+        readback::Readback,
+        resources::{DrawData, PrimitiveCullData, Resources},
+        ring_buffer::{RingBuffer, APP_DATA_FRAME_SIZE},
         scene_data::SceneData,
+        shadows::Shadows,
+        sprite::{SpriteBatch, SpriteData, SpritePipeline},
         swapchain::{Swapchain, SwapchainInfo},
+        timeline::{Pass, QueueTransfer, Timeline},
         vertex::Vertex,
+        vignette::{VignetteData, VignettePipeline},
     },
-    COLOR_FORMAT, DEPTH_FORMAT, VIEW_COUNT,
+    HothamResult, DEPTH_FORMAT, VIEW_COUNT,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ash::vk::{self, Handle};
-use glam::{Affine3A, Mat4, Vec4};
+use glam::{Affine3A, Mat4, Vec3, Vec4};
 use openxr as xr;
 use vk_shader_macros::include_glsl;
 
-static VERT: &[u32] = include_glsl!("src/shaders/pbr.vert", target: vulkan1_1);
-static FRAG: &[u32] = include_glsl!("src/shaders/pbr.frag", target: vulkan1_1);
+pub(crate) static VERT: &[u32] = include_glsl!("src/shaders/pbr.vert", target: vulkan1_1);
+pub(crate) static FRAG: &[u32] = include_glsl!("src/shaders/pbr.frag", target: vulkan1_1);
+/// The specialization constant in `pbr.frag` and `sprite.frag` that sizes the texture array
+pub(crate) const TEXTURE_COUNT_CONSTANT_ID: u32 = 0;
 static COMPUTE: &[u32] = include_glsl!("src/shaders/culling.comp", target: vulkan1_1);
+static FADE_VERT: &[u32] = include_glsl!("src/shaders/fade.vert", target: vulkan1_1);
+static FADE_FRAG: &[u32] = include_glsl!("src/shaders/fade.frag", target: vulkan1_1);
 
 // TODO: Is this a good idea?
 pub const PIPELINE_DEPTH: usize = 2;
 pub const SAMPLES: vk::SampleCountFlags = vk::SampleCountFlags::TYPE_4;
+/// Pipelines in the PBR render pass leave their viewport and scissor to be set when the pass begins, so the render
+/// scale can change without rebuilding them - see [`RenderContext::set_render_scale`].
+pub const RENDER_PASS_DYNAMIC_STATES: [vk::DynamicState; 2] =
+    [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+/// The lowest render scale, below which the view is too blurry to be useful
+pub const MIN_RENDER_SCALE: f32 = 0.5;
 
 pub struct RenderContext {
     pub frame_index: usize,
@@ -51,28 +102,396 @@ pub struct RenderContext {
     pub compute_pipeline: vk::Pipeline,
     pub pipeline_layout: vk::PipelineLayout,
     pub compute_pipeline_layout: vk::PipelineLayout,
+    pub fade_pipeline: vk::Pipeline,
+    pub fade_pipeline_layout: vk::PipelineLayout,
     pub render_pass: vk::RenderPass,
     pub scene_data: SceneData,
+    /// The near and far clip planes, used to build the projection matrices each frame. Change these at any time.
+    pub clip_planes: ClipPlanes,
+    /// How far to move everything that's drawn, in globally oriented stage space, without moving the cameras.
+    /// Used for effects like shakes - see [`crate::contexts::EffectsContext`].
+    pub content_offset: Vec3,
+    /// A color to cover the view with at the end of the render pass, with how much of the view it covers in `w`.
+    /// Used for fades - see [`crate::contexts::EffectsContext`].
+    pub fade_color: Vec4,
+    /// A vignette to narrow the view with, before any fade. Used for comfort during artificial motion - see
+    /// [`crate::contexts::EffectsContext`].
+    pub vignette: Option<VignetteData>,
+    /// The color the view is cleared to before anything is drawn. Overlays are cleared to transparent.
+    pub clear_color: [f32; 4],
+    /// Clear the view to transparent whatever `clear_color` is, so a layer underneath shows through wherever nothing
+    /// is drawn. Set while passthrough is showing - see [`crate::Engine::enable_passthrough`].
+    pub transparent_background: bool,
+    /// How much of the swapchain's resolution is rendered at, from [`MIN_RENDER_SCALE`] to 1.
+    pub(crate) render_scale: f32,
+    foveation_level: FoveationLevel,
     pub cameras: Vec<Camera>,
     pub views: Vec<xr::View>,
     pub resources: Resources,
     pub frames: [Frame; PIPELINE_DEPTH],
     pub swapchain: Swapchain,
     pub descriptors: Descriptors,
+    /// Orders the culling and rendering work submitted to the GPU
+    pub timeline: Timeline,
+    /// Settings for volumetric fog, drawn inside [`crate::components::FogVolume`]s. Has no effect until fog has been
+    /// turned on with [`RenderContext::enable_volumetric_fog`]; set to `None` to stop drawing it.
+    pub volumetric_fog: Option<VolumetricFog>,
+    /// Ambient light baked for the current level, used to light anything with an
+    /// [`crate::components::AmbientProbe`]. Set this when a level is loaded.
+    pub light_probes: Option<LightProbeGrid>,
+    /// The GPU resources used to draw volumetric fog
+    pub fog: Fog,
+    /// Draws [`crate::components::LensFlare`]s over their lights
+    pub lens_flare_pipeline: LensFlarePipeline,
+    /// Draws [`crate::components::Sprite`]s
+    pub sprite_pipeline: SpritePipeline,
+    /// Draws the comfort vignette
+    pub vignette_pipeline: VignettePipeline,
+    /// Animates and draws [`crate::components::CrowdMember`]s
+    pub crowds: CrowdRenderer,
+    /// Draws [`crate::components::Instanced`] meshes
+    pub instancing: InstancedRenderer,
+    /// Draws outlines around interactables, when [`AccessibilitySettings::outline_interactables`] is on
+    pub outline_pipeline: OutlinePipeline,
+    /// Settings to make the view easier to see, like color blindness filters. Change these at any time.
+    pub accessibility: AccessibilitySettings,
+    /// Settings for ray traced contact shadows and ambient occlusion. Has no effect until ray queries have been turned
+    /// on with [`RenderContext::enable_ray_query`].
+    pub ray_query_settings: RayQuerySettings,
+    /// Reads rendered images and buffers back from the GPU, a frame or two after they're asked for
+    pub readback: Readback,
+    /// Somewhere to write data for each draw of an app's own pipelines, like material parameters. What's written is
+    /// kept until the GPU has finished with the frame.
+    pub app_data: RingBuffer,
+    /// Compute shaders written by apps - create them with [`RenderContext::add_compute_pass`] and run them each frame
+    /// with [`ComputePasses::dispatch`].
+    pub compute_passes: ComputePasses,
+    /// Shadow maps for directional lights and spotlights. Pick the lights that cast shadows with [`Shadows::atlas`],
+    /// then turn them on with [`RenderContext::enable_shadows`].
+    pub shadows: Shadows,
 
     // Populated only between rendering::begin and rendering::end
     pub primitive_map: HashMap<u32, InstancedPrimitive>,
+
+    // Scratch space reused between frames so that building cull and draw data doesn't allocate.
+    pub(crate) cull_data_scratch: Vec<PrimitiveCullData>,
+    pub(crate) draw_data_scratch: Vec<DrawData>,
+    pub(crate) draw_batches: Vec<DrawBatch>,
+    pub(crate) fog_volume_scratch: Vec<FogVolumeData>,
+    pub(crate) lens_flare_scratch: Vec<LensFlareData>,
+    pub(crate) sprite_scratch: Vec<SpriteData>,
+    pub(crate) sprite_batches: Vec<SpriteBatch>,
+    pub(crate) outline_scratch: Vec<OutlineInstance>,
+
+    // Ray query settings for this frame, in the form the shader needs them.
+    #[cfg(feature = "ray-query")]
+    ray_query_params: RayQueryParams,
+    #[cfg(feature = "ray-query")]
+    ray_query: Option<RayQueryShading>,
+    // Turned on with enable_picking.
+    picking: Option<Picking>,
+
+    // Baked into the pipeline, so can't be changed once the context is created.
+    reversed_z: bool,
+    // Material animations are timed from here.
+    created_at: Instant,
+    // Kept from the last call to update_scene_data, so the cameras can be moved again when late latching.
+    gos_from_stage: Affine3A,
 }
 
 impl RenderContext {
     pub fn new(vulkan_context: &VulkanContext, xr_context: &XrContext) -> Result<Self> {
+        Self::with_reversed_z(vulkan_context, xr_context, true)
+    }
+
+    /// Create a renderer that uses reversed Z (`GREATER` depth test, cleared to 0) if `reversed_z` is set, or standard
+    /// Z (`LESS` depth test, cleared to 1) if not.
+    pub fn with_reversed_z(
+        vulkan_context: &VulkanContext,
+        xr_context: &XrContext,
+        reversed_z: bool,
+    ) -> Result<Self> {
         println!("[HOTHAM_RENDERER] Creating renderer..");
         let xr_swapchain = &xr_context.swapchain;
         let swapchain_resolution = xr_context.swapchain_resolution;
 
         // Build swapchain
-        let swapchain = SwapchainInfo::from_openxr_swapchain(xr_swapchain, swapchain_resolution)?;
-        Self::new_from_swapchain_info(vulkan_context, &swapchain)
+        let swapchain = SwapchainInfo::from_openxr_swapchain(
+            xr_swapchain,
+            swapchain_resolution,
+            xr_context.swapchain_format,
+        )?;
+        Self::new_from_swapchain_info(vulkan_context, &swapchain, reversed_z)
+    }
+
+    /// Is depth reversed, with the near plane at 1 and the far plane at 0?
+    pub fn reversed_z(&self) -> bool {
+        self.reversed_z
+    }
+
+    /// Clear values for the color and depth attachments
+    pub fn clear_values(&self) -> [vk::ClearValue; 2] {
+        let mut clear_values = if self.reversed_z {
+            CLEAR_VALUES
+        } else {
+            CLEAR_VALUES_STANDARD_Z
+        };
+        let mut clear_color = self.clear_color;
+        if self.transparent_background {
+            clear_color[3] = 0.;
+        }
+        clear_values[0].color = vk::ClearColorValue {
+            float32: clear_color,
+        };
+        clear_values
+    }
+
+    /// Turn on volumetric fog, computed at `quality`, or change its quality. Waits for the GPU to be idle.
+    pub fn enable_volumetric_fog(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        quality: FogQuality,
+    ) -> Result<()> {
+        unsafe {
+            self.fog
+                .set_quality(vulkan_context, &self.descriptors, Some(quality))?;
+        }
+        self.volumetric_fog.get_or_insert_with(Default::default);
+        Ok(())
+    }
+
+    /// Create a crowd that draws `mesh`, playing any of `animations` - see [`crate::components::CrowdMember`]. The
+    /// animations must all have been baked from the mesh's skin.
+    pub fn add_crowd(&mut self, mesh: &Mesh, animations: &[BakedAnimation]) -> Result<CrowdId> {
+        let primitives = self
+            .resources
+            .mesh_data
+            .get(mesh.handle)
+            .ok_or_else(|| anyhow!("The crowd's mesh doesn't exist"))?
+            .primitives
+            .clone();
+        self.crowds.add_crowd(primitives, animations)
+    }
+
+    /// Turn on ray traced contact shadows and ambient occlusion, building an acceleration structure from every entity in
+    /// `world` with [`crate::components::StaticGeometry`]. Call it again to rebuild it, eg. after loading a level. Waits
+    /// for the GPU to be idle.
+    ///
+    /// Returns `false`, and carries on drawing without them, if the device doesn't support ray queries or Hotham was
+    /// built without the `ray-query` feature.
+    #[cfg(feature = "ray-query")]
+    pub fn enable_ray_query(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        world: &hecs::World,
+    ) -> Result<bool> {
+        if !vulkan_context.capabilities.ray_query {
+            println!("[HOTHAM_RENDERER] Ray queries aren't supported on this device - drawing without them");
+            return Ok(false);
+        }
+
+        let description = unsafe {
+            SceneDescription::gather(
+                world,
+                &self.resources.mesh_data,
+                self.resources.vertex_buffer.as_slice(),
+                self.resources.index_buffer.as_slice(),
+            )
+        };
+        unsafe {
+            self.disable_ray_query(vulkan_context)?;
+            self.ray_query = Some(RayQueryShading::new(
+                vulkan_context,
+                &self.descriptors,
+                &self.swapchain.render_area,
+                self.render_pass,
+                self.reversed_z,
+                &description,
+            )?);
+        }
+        println!(
+            "[HOTHAM_RENDERER] Built an acceleration structure with {} instances of {} meshes, {} triangles",
+            description.instances.len(),
+            description.geometries.len(),
+            description.triangle_count()
+        );
+        Ok(true)
+    }
+
+    /// Turn on ray traced contact shadows and ambient occlusion. Hotham was built without the `ray-query` feature, so
+    /// this always returns `false`.
+    #[cfg(not(feature = "ray-query"))]
+    pub fn enable_ray_query(
+        &mut self,
+        _vulkan_context: &VulkanContext,
+        _world: &hecs::World,
+    ) -> Result<bool> {
+        println!("[HOTHAM_RENDERER] Hotham was built without the `ray-query` feature - drawing without ray queries");
+        Ok(false)
+    }
+
+    /// Turn off ray traced contact shadows and ambient occlusion, freeing the acceleration structure. Waits for the GPU
+    /// to be idle.
+    pub fn disable_ray_query(&mut self, vulkan_context: &VulkanContext) -> Result<()> {
+        #[cfg(feature = "ray-query")]
+        if let Some(ray_query) = self.ray_query.take() {
+            unsafe {
+                vulkan_context.device.device_wait_idle()?;
+                ray_query.destroy(&vulkan_context.device);
+            }
+        }
+        #[cfg(not(feature = "ray-query"))]
+        let _ = vulkan_context;
+        Ok(())
+    }
+
+    /// Are ray traced contact shadows and ambient occlusion being drawn?
+    #[cfg(feature = "ray-query")]
+    pub fn ray_query_enabled(&self) -> bool {
+        self.ray_query.is_some()
+    }
+
+    /// Are ray traced contact shadows and ambient occlusion being drawn? Never, without the `ray-query` feature.
+    #[cfg(not(feature = "ray-query"))]
+    pub fn ray_query_enabled(&self) -> bool {
+        false
+    }
+
+    /// Start drawing shadow maps for the lights picked in [`Shadows::atlas`], or start again after changing the atlas's
+    /// size. Waits for the GPU to be idle.
+    pub fn enable_shadows(&mut self, vulkan_context: &VulkanContext) -> Result<()> {
+        unsafe {
+            vulkan_context.device.device_wait_idle()?;
+            self.shadows.disable(&vulkan_context.device);
+            self.resources.resize_shadow_map(
+                vulkan_context,
+                &self.descriptors,
+                self.shadows.atlas.size(),
+            )?;
+        }
+        self.shadows.enable(
+            vulkan_context,
+            self.descriptors.graphics_layout,
+            &self.resources.shadow_map,
+        )
+    }
+
+    /// Stop drawing shadow maps, freeing the atlas. Waits for the GPU to be idle.
+    pub fn disable_shadows(&mut self, vulkan_context: &VulkanContext) -> Result<()> {
+        unsafe {
+            vulkan_context.device.device_wait_idle()?;
+            self.shadows.disable(&vulkan_context.device);
+            self.resources
+                .resize_shadow_map(vulkan_context, &self.descriptors, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Draw this frame's shadow maps, and tell the PBR shader how to sample them. Called by
+    /// [`crate::systems::rendering::begin`], after [`RenderContext::update_scene_data`] and before
+    /// [`RenderContext::begin_pbr_render_pass`].
+    pub(crate) fn draw_shadows(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        gos_from_global: &Affine3A,
+    ) {
+        if !self.shadows.is_enabled() {
+            return;
+        }
+
+        // Shadow maps are ranked, and directional lights centred, from the point between the eyes.
+        let camera_position = self.scene_data.camera_position;
+        let between_eyes = ((camera_position[0] + camera_position[1]) * 0.5).truncate();
+        let viewer = gos_from_global.inverse().transform_point3(between_eyes);
+
+        // The sun's cascades are fitted to a view from between the eyes, wide enough to see what both eyes can.
+        let mut gos_from_view = self.cameras[0].gos_from_view;
+        gos_from_view.translation = between_eyes.into();
+        let (fov_left, fov_right) = (self.views[0].fov, self.views[1].fov);
+        let frustum = Frustum {
+            left: fov_left.angle_left,
+            right: fov_right.angle_right,
+            up: fov_left.angle_up.max(fov_right.angle_up),
+            down: fov_left.angle_down.min(fov_right.angle_down),
+        };
+
+        let frame = &mut self.frames[self.frame_index];
+        unsafe {
+            let (shadows, shadow_cascades) = self.shadows.draw(
+                &vulkan_context.device,
+                frame.command_buffer,
+                self.descriptors.sets[self.frame_index],
+                &self.resources,
+                &self.primitive_map,
+                &self.scene_data.lights,
+                viewer,
+                gos_from_global,
+                &gos_from_view,
+                &frustum,
+                self.clip_planes.near,
+            );
+            let scene_data = &mut frame.scene_data_buffer.as_slice_mut()[0];
+            scene_data.shadows = shadows;
+            scene_data.shadow_cascades = shadow_cascades;
+        }
+    }
+
+    /// Turn on GPU picking, so [`RenderContext::pick`] can be used. See [`Picking`].
+    pub fn enable_picking(&mut self, vulkan_context: &VulkanContext) -> Result<&mut Picking> {
+        if self.picking.is_none() {
+            self.picking = Some(Picking::new(
+                vulkan_context,
+                self.descriptors.graphics_layout,
+            )?);
+        }
+        Ok(self.picking.as_mut().unwrap())
+    }
+
+    /// Find the entity under `ray`, which is in global space. The pick is drawn with the next frame, and its result
+    /// collected with [`RenderContext::pick_result`] a frame or two after that:
+    /// ```ignore
+    /// let pick = render_context.pick(PickRay::from_transform(&controller_transform.0))?;
+    /// // A few frames later..
+    /// if let Some(Some(entity)) = render_context.pick_result(pick, world) {
+    ///     select(entity);
+    /// }
+    /// ```
+    pub fn pick(&mut self, ray: PickRay) -> Result<PickId> {
+        self.picking
+            .as_mut()
+            .map(|picking| picking.request(ray))
+            .ok_or_else(|| anyhow!("Picking hasn't been turned on with enable_picking"))
+    }
+
+    /// The entity found by `pick`, if it's arrived: `None` while it's still being drawn or read back, then `Some(None)`
+    /// if there was nothing under the ray. Each result is only returned once.
+    ///
+    /// The entity is looked up in `world` by its [`hecs::Entity::id`], so if it's been despawned since the pick was
+    /// drawn, nothing is returned - or whatever has taken its place.
+    pub fn pick_result(
+        &mut self,
+        pick: PickId,
+        world: &hecs::World,
+    ) -> Option<Option<hecs::Entity>> {
+        let entity_id = self.picking.as_mut()?.poll(pick, &mut self.readback)?;
+        Some(entity_id.and_then(|entity_id| {
+            world
+                .iter()
+                .map(|entity_ref| entity_ref.entity())
+                .find(|entity| entity.id() == entity_id)
+        }))
+    }
+
+    /// Get the next pick ready to draw this frame. Called by [`crate::systems::rendering::begin`].
+    pub(crate) fn prepare_picking(&mut self, gos_from_global: &Affine3A) {
+        if let Some(picking) = &mut self.picking {
+            picking.prepare(gos_from_global);
+        }
+    }
+
+    /// The fog to draw this frame, if any
+    fn active_fog(&self) -> Option<VolumetricFog> {
+        self.volumetric_fog.filter(|_| self.fog.quality.is_some())
     }
 
     /// Command buffer of the current frame
@@ -84,28 +503,118 @@ impl RenderContext {
         self.swapchain.render_area
     }
 
+    /// The part of the swapchain that's rendered to, with the render scale applied. The compositor stretches it over
+    /// the whole view.
+    pub fn scaled_render_area(&self) -> vk::Rect2D {
+        scale_render_area(self.swapchain.render_area, self.render_scale)
+    }
+
+    /// The render scale: how much of the swapchain's resolution is rendered at.
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Render at `scale` times the swapchain's resolution in each direction, eg. to keep up the frame rate in a busy
+    /// scene. Clamped between [`MIN_RENDER_SCALE`] and 1.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(MIN_RENDER_SCALE, 1.);
+    }
+
+    /// The fixed foveated rendering level the swapchain was last given
+    pub fn foveation_level(&self) -> FoveationLevel {
+        self.foveation_level
+    }
+
+    /// Render the edges of the view at lower resolution. Needs `XR_FB_foveation`, which is enabled when the runtime
+    /// has it - see [`XrContext::set_foveation_level`].
+    pub fn set_foveation_level(
+        &mut self,
+        xr_context: &XrContext,
+        level: FoveationLevel,
+    ) -> HothamResult<()> {
+        xr_context.set_foveation_level(level)?;
+        self.foveation_level = level;
+        Ok(())
+    }
+
     pub(crate) fn new_from_swapchain_info(
         vulkan_context: &VulkanContext,
         swapchain_info: &SwapchainInfo,
+        reversed_z: bool,
     ) -> Result<Self> {
         let descriptors = unsafe { Descriptors::new(vulkan_context) };
         let resources = unsafe { Resources::new(vulkan_context, &descriptors) };
 
         // Pipeline, render pass
-        let render_pass = create_render_pass(vulkan_context)?;
+        let render_pass = create_render_pass(vulkan_context, swapchain_info.format)?;
         let swapchain = Swapchain::new(swapchain_info, vulkan_context, render_pass);
-        let pipeline_layout =
-            create_pipeline_layout(vulkan_context, slice_from_ref(&descriptors.graphics_layout))?;
+        let pipeline_layout = create_pipeline_layout(
+            vulkan_context,
+            &[descriptors.graphics_layout, descriptors.fog_layout],
+        )?;
         let pipeline = create_pipeline(
             vulkan_context,
             pipeline_layout,
             &swapchain.render_area,
             render_pass,
+            reversed_z,
+            descriptors.texture_capacity,
+            VERT,
+            FRAG,
         )?;
         let (compute_pipeline, compute_pipeline_layout) = create_compute_pipeline(
             &vulkan_context.device,
             slice_from_ref(&descriptors.compute_layout),
         );
+        let fade_pipeline_layout = create_fade_pipeline_layout(vulkan_context)?;
+        let fade_pipeline = create_fade_pipeline(
+            vulkan_context,
+            fade_pipeline_layout,
+            &swapchain.render_area,
+            render_pass,
+        )?;
+
+        let lens_flare_pipeline = LensFlarePipeline::new(
+            vulkan_context,
+            descriptors.graphics_layout,
+            &swapchain.render_area,
+            render_pass,
+        )?;
+        let vignette_pipeline =
+            VignettePipeline::new(vulkan_context, &swapchain.render_area, render_pass)?;
+        let sprite_pipeline = SpritePipeline::new(
+            vulkan_context,
+            descriptors.graphics_layout,
+            &swapchain.render_area,
+            render_pass,
+            reversed_z,
+            descriptors.texture_capacity,
+        )?;
+        let outline_pipeline = OutlinePipeline::new(
+            vulkan_context,
+            descriptors.graphics_layout,
+            &swapchain.render_area,
+            render_pass,
+            reversed_z,
+        )?;
+        let crowds = unsafe {
+            CrowdRenderer::new(
+                vulkan_context,
+                &descriptors,
+                &swapchain.render_area,
+                render_pass,
+                reversed_z,
+            )?
+        };
+        let instancing = unsafe {
+            InstancedRenderer::new(
+                vulkan_context,
+                &descriptors,
+                &swapchain.render_area,
+                render_pass,
+                reversed_z,
+            )?
+        };
 
         // Create all the per-frame resources we need
         let mut index = 0;
@@ -118,6 +627,8 @@ impl RenderContext {
         });
 
         let scene_data = Default::default();
+        let timeline = Timeline::new(vulkan_context)?;
+        let fog = unsafe { Fog::new(vulkan_context, &descriptors, None)? };
 
         Ok(Self {
             frames,
@@ -127,14 +638,56 @@ impl RenderContext {
             compute_pipeline,
             pipeline_layout,
             compute_pipeline_layout,
+            fade_pipeline,
+            fade_pipeline_layout,
             render_pass,
             cameras: vec![Default::default(); 2],
             views: vec![Default::default(); 2],
             scene_data,
+            clip_planes: Default::default(),
+            content_offset: Vec3::ZERO,
+            fade_color: Vec4::ZERO,
+            vignette: None,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            transparent_background: false,
+            render_scale: 1.,
+            foveation_level: FoveationLevel::None,
             descriptors,
+            timeline,
+            volumetric_fog: None,
+            light_probes: None,
+            fog,
+            lens_flare_pipeline,
+            sprite_pipeline,
+            vignette_pipeline,
+            outline_pipeline,
+            accessibility: Default::default(),
+            crowds,
+            instancing,
             resources,
 
             primitive_map: HashMap::default(),
+            cull_data_scratch: Vec::new(),
+            draw_data_scratch: Vec::new(),
+            draw_batches: Vec::new(),
+            fog_volume_scratch: Vec::new(),
+            lens_flare_scratch: Vec::new(),
+            sprite_scratch: Vec::new(),
+            sprite_batches: Vec::new(),
+            outline_scratch: Vec::new(),
+            readback: Default::default(),
+            app_data: unsafe { RingBuffer::new(vulkan_context, APP_DATA_FRAME_SIZE) },
+            compute_passes: Default::default(),
+            shadows: Default::default(),
+            ray_query_settings: Default::default(),
+            #[cfg(feature = "ray-query")]
+            ray_query_params: Default::default(),
+            #[cfg(feature = "ray-query")]
+            ray_query: None,
+            picking: None,
+            reversed_z,
+            created_at: Instant::now(),
+            gos_from_stage: Affine3A::IDENTITY,
         })
     }
 
@@ -149,7 +702,7 @@ impl RenderContext {
         // Create an image with vulkan_context
         let image = vulkan_context
             .create_image(
-                COLOR_FORMAT,
+                crate::COLOR_FORMAT,
                 &resolution,
                 vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
                 2,
@@ -163,10 +716,11 @@ impl RenderContext {
         let swapchain = SwapchainInfo {
             images: vec![image.handle],
             resolution,
+            format: crate::COLOR_FORMAT,
         };
 
         (
-            RenderContext::new_from_swapchain_info(&vulkan_context, &swapchain).unwrap(),
+            RenderContext::new_from_swapchain_info(&vulkan_context, &swapchain, true).unwrap(),
             vulkan_context,
         )
     }
@@ -182,7 +736,7 @@ impl RenderContext {
         // Create an image with vulkan_context
         let image = vulkan_context
             .create_image(
-                COLOR_FORMAT,
+                crate::COLOR_FORMAT,
                 &resolution,
                 vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
                 2,
@@ -196,10 +750,11 @@ impl RenderContext {
         let swapchain = SwapchainInfo {
             images: vec![image.handle],
             resolution,
+            format: crate::COLOR_FORMAT,
         };
 
         (
-            RenderContext::new_from_swapchain_info(&vulkan_context, &swapchain).unwrap(),
+            RenderContext::new_from_swapchain_info(&vulkan_context, &swapchain, true).unwrap(),
             vulkan_context,
             image,
         )
@@ -211,44 +766,85 @@ impl RenderContext {
         gos_from_global: &Affine3A,
         gos_from_stage: &Affine3A,
     ) {
+        self.gos_from_stage = *gos_from_stage;
+        self.update_cameras(views);
+
+        self.scene_data.time.x = self.created_at.elapsed().as_secs_f32();
+        #[cfg(feature = "ray-query")]
+        {
+            self.ray_query_params = RayQueryParams::new(&self.ray_query_settings, gos_from_global);
+        }
+
+        unsafe {
+            let scene_data = &mut self.frames[self.frame_index]
+                .scene_data_buffer
+                .as_slice_mut()[0];
+            scene_data.camera_position = self.scene_data.camera_position;
+            scene_data.view_projection = self.scene_data.view_projection;
+            scene_data.params = self.scene_data.params;
+            scene_data.params.y = self.active_fog().map_or(0., |f| f.max_distance);
+            scene_data.time = self.scene_data.time;
+            scene_data.time.y = if needs_srgb_encoding(self.swapchain.format) {
+                1.
+            } else {
+                0.
+            };
+            scene_data.lights = self.scene_data.lights;
+            // Nothing is in shadow until this frame's shadow maps are drawn.
+            scene_data.shadows = self.scene_data.shadows;
+            scene_data.color_transform = self.accessibility.color_transform();
+            for light in &mut scene_data.lights {
+                light.position = gos_from_global.transform_point3(light.position);
+                light.direction = gos_from_global.transform_vector3(light.direction);
+            }
+        }
+    }
+
+    /// Move the cameras to `views` and build the view projection matrices from them.
+    fn update_cameras(&mut self, views: &[xr::View]) {
         self.views = views.to_owned();
 
         // View (camera)
+        let gos_from_stage = self.gos_from_stage;
         let view_matrices = &self
             .cameras
             .iter_mut()
             .enumerate()
-            .map(|(n, c)| c.update(&views[n], gos_from_stage))
+            .map(|(n, c)| c.update(&views[n], &gos_from_stage))
             .collect::<Vec<_>>();
 
         // Projection
-        let near = 0.05;
-
         let fov_left = views[0].fov;
         let fov_right = views[1].fov;
 
         self.scene_data.view_projection = [
-            Frustum::from(fov_left).projection(near) * view_matrices[0],
-            Frustum::from(fov_right).projection(near) * view_matrices[1],
+            Frustum::from(fov_left).projection_with_clip_planes(&self.clip_planes, self.reversed_z)
+                * view_matrices[0],
+            Frustum::from(fov_right)
+                .projection_with_clip_planes(&self.clip_planes, self.reversed_z)
+                * view_matrices[1],
         ];
 
         self.scene_data.camera_position = [
             self.cameras[0].position_in_gos(),
             self.cameras[1].position_in_gos(),
         ];
+    }
 
+    /// Move the cameras to `views`, located again just before the frame is submitted, and patch the frame's scene data
+    /// with them. The GPU hasn't started on the frame yet, so everything already recorded is drawn from the fresher
+    /// pose. This is "late latching".
+    ///
+    /// Only the cameras move: objects were culled, and anything parented to the HMD was placed, with the pose from the
+    /// start of the frame. The same `views` must be submitted to the compositor, so it reprojects from the right pose.
+    pub fn late_latch_views(&mut self, views: &[xr::View]) {
+        self.update_cameras(views);
         unsafe {
             let scene_data = &mut self.frames[self.frame_index]
                 .scene_data_buffer
                 .as_slice_mut()[0];
             scene_data.camera_position = self.scene_data.camera_position;
             scene_data.view_projection = self.scene_data.view_projection;
-            scene_data.params = self.scene_data.params;
-            scene_data.lights = self.scene_data.lights;
-            for light in &mut scene_data.lights {
-                light.position = gos_from_global.transform_point3(light.position);
-                light.direction = gos_from_global.transform_vector3(light.direction);
-            }
         }
     }
 
@@ -258,8 +854,10 @@ impl RenderContext {
         let device = &vulkan_context.device;
         let frame = &self.frames[self.frame_index];
 
-        // Wait for the GPU to be ready.
-        self.wait(device, frame);
+        // Wait for the GPU to finish the last time this frame was rendered.
+        self.timeline
+            .wait(Pass::Render, frame.render_value, u64::MAX)
+            .expect("[HOTHAM_RENDER] Unable to wait for the previous frame!");
 
         let command_buffer = frame.command_buffer;
         unsafe {
@@ -273,13 +871,21 @@ impl RenderContext {
         }
     }
 
+    /// Cull every instance in the frame's primitive cull data against both eyes' frusta, on the GPU. Only the instances
+    /// left visible are drawn by [`crate::systems::rendering::draw_world`].
     pub fn cull_objects(&mut self, vulkan_context: &VulkanContext) {
         let device = &vulkan_context.device;
         let frame_index = self.frame_index;
         let frame = &mut self.frames[self.frame_index];
         let primitive_cull_buffer = &frame.primitive_cull_data_buffer;
+
+        // With nothing to cull, there's no need to wait for the GPU.
+        if primitive_cull_buffer.len == 0 {
+            return;
+        }
+
         let command_buffer = frame.compute_command_buffer;
-        let fence = frame.compute_fence;
+        let submit = self.timeline.submit(Pass::Cull);
 
         // Create the cull parameters to pass to the compute shader
         let cull_params =
@@ -289,7 +895,10 @@ impl RenderContext {
             frame.cull_params_buffer.overwrite(&[cull_params]);
         }
 
-        let group_count_x = (primitive_cull_buffer.len / 1024) + 1;
+        let group_count_x = primitive_cull_buffer.len.div_ceil(CULLING_WORKGROUP_SIZE);
+        let cull_buffers = frame.cull_buffers();
+        let cull_stage = vk::PipelineStageFlags::COMPUTE_SHADER;
+        let to_render = QueueTransfer::new(vulkan_context, Pass::Cull, Pass::Render);
 
         unsafe {
             device
@@ -299,6 +908,21 @@ impl RenderContext {
                         .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
                 )
                 .unwrap();
+
+            // Take the buffers back from the graphics queue. We've already waited for it to finish with this frame in
+            // `begin_frame`, so there's no need to wait for it on the GPU.
+            if frame.cull_buffers_released_to == Some(Pass::Cull) {
+                QueueTransfer::new(vulkan_context, Pass::Render, Pass::Cull).acquire(
+                    device,
+                    command_buffer,
+                    &cull_buffers,
+                    cull_stage,
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::SHADER_WRITE
+                        | vk::AccessFlags::UNIFORM_READ,
+                );
+            }
+
             device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::COMPUTE,
@@ -313,20 +937,67 @@ impl RenderContext {
                 &[],
             );
             device.cmd_dispatch(command_buffer, group_count_x as u32, 1, 1);
+
+            // The draw calls are built on the CPU from the results, so they need to be visible to the host..
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                cull_stage,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::HOST_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            // ..and the buffers are handed over to the graphics queue with the rest of the frame.
+            to_render.release(
+                device,
+                command_buffer,
+                &cull_buffers,
+                cull_stage,
+                vk::AccessFlags::SHADER_WRITE,
+            );
             device.end_command_buffer(command_buffer).unwrap();
-            let submit_info =
-                vk::SubmitInfo::builder().command_buffers(slice_from_ref(&command_buffer));
-            device
-                .queue_submit(
-                    vulkan_context.graphics_queue,
-                    slice_from_ref(&submit_info),
-                    fence,
-                )
-                .unwrap();
-            device
-                .wait_for_fences(slice_from_ref(&fence), true, CULLING_TIMEOUT)
-                .unwrap_or_else(|e| panic!("@@@ TIMEOUT WAITING FOR CULLING SHADER - {:?} @@@", e));
-            device.reset_fences(slice_from_ref(&fence)).unwrap();
+        }
+        frame.cull_buffers_released_to = to_render.is_needed().then_some(Pass::Render);
+
+        // This goes to the compute queue, so on GPUs with async compute it runs alongside the previous frame's
+        // fragment work.
+        submit.submit(vulkan_context, command_buffer).unwrap();
+
+        // The draw calls are built on the CPU from the results, so we have to wait for them here.
+        self.timeline
+            .wait(Pass::Cull, submit.signal_value(), CULLING_TIMEOUT)
+            .unwrap_or_else(|e| panic!("@@@ TIMEOUT WAITING FOR CULLING SHADER - {:?} @@@", e));
+    }
+
+    /// Record the volumetric fog passes for this frame, lighting the fog volumes in `fog_volume_scratch`. Must be called
+    /// after [`RenderContext::update_scene_data`] and before [`RenderContext::begin_pbr_render_pass`].
+    pub fn draw_fog(&mut self, vulkan_context: &VulkanContext) {
+        let settings = match self.active_fog() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        let frame = &mut self.frames[self.frame_index];
+        unsafe {
+            // The lights in the frame's scene data have already been moved into globally oriented stage space.
+            let scene_data = &frame.scene_data_buffer.as_slice()[0];
+            let fog_params = FogParams::new(
+                &settings,
+                self.fog.grid,
+                scene_data,
+                &self.fog_volume_scratch,
+            );
+            frame.fog_params_buffer.overwrite(&[fog_params]);
+            self.fog.record(
+                &vulkan_context.device,
+                frame.command_buffer,
+                self.descriptors.fog_compute_sets[self.frame_index],
+            );
         }
     }
 
@@ -342,13 +1013,23 @@ impl RenderContext {
         let frame = &self.frames[self.frame_index];
         let command_buffer = frame.command_buffer;
         let framebuffer = self.swapchain.framebuffers[swapchain_image_index];
+        let clear_values = self.clear_values();
+        let render_area = self.scaled_render_area();
 
         // Begin the renderpass.
         let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.render_pass)
             .framebuffer(framebuffer)
-            .render_area(self.swapchain.render_area)
-            .clear_values(&CLEAR_VALUES);
+            .render_area(render_area)
+            .clear_values(&clear_values);
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: render_area.extent.width as _,
+            height: render_area.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
 
         unsafe {
             device.cmd_begin_render_pass(
@@ -356,73 +1037,290 @@ impl RenderContext {
                 &render_pass_begin_info,
                 vk::SubpassContents::INLINE,
             );
+            device.cmd_set_viewport(command_buffer, 0, slice_from_ref(&viewport));
+            device.cmd_set_scissor(command_buffer, 0, slice_from_ref(&render_area));
+            self.bind_pbr_pipeline(device, command_buffer);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.resources.index_buffer.buffer,
+                0,
+                vk::IndexType::UINT32,
+            );
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                slice_from_ref(&self.resources.vertex_buffer.buffer),
+                &[0],
+            );
+        }
+    }
+
+    /// Bind the PBR pipeline and its descriptor sets, eg. after drawing with another pipeline in the PBR render pass.
+    pub(crate) unsafe fn bind_pbr_pipeline(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        #[cfg(feature = "ray-query")]
+        if let Some(ray_query) = &self.ray_query {
             device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
+                ray_query.pipeline,
             );
             device.cmd_bind_descriptor_sets(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
+                ray_query.pipeline_layout,
                 0,
-                slice_from_ref(&self.descriptors.sets[self.frame_index]),
+                &[
+                    self.descriptors.sets[self.frame_index],
+                    self.descriptors.fog_set,
+                    ray_query.descriptor_set,
+                ],
                 &[],
             );
-            device.cmd_bind_index_buffer(
+            device.cmd_push_constants(
                 command_buffer,
-                self.resources.index_buffer.buffer,
+                ray_query.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
                 0,
-                vk::IndexType::UINT32,
+                create_push_constant(&self.ray_query_params),
             );
-            device.cmd_bind_vertex_buffers(
+            return;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[
+                self.descriptors.sets[self.frame_index],
+                self.descriptors.fog_set,
+            ],
+            &[],
+        );
+    }
+
+    /// Record the crowd animation pass for this frame, for the members gathered into [`RenderContext::crowds`]. Must be
+    /// called after [`RenderContext::update_scene_data`] and before [`RenderContext::begin_pbr_render_pass`].
+    pub fn animate_crowds(&mut self, vulkan_context: &VulkanContext) {
+        let command_buffer = self.frames[self.frame_index].command_buffer;
+        unsafe {
+            self.crowds.animate(
+                &vulkan_context.device,
                 command_buffer,
-                0,
-                slice_from_ref(&self.resources.vertex_buffer.buffer),
-                &[0],
+                self.frame_index,
+                self.scene_data.time.x,
+            );
+        }
+    }
+
+    /// Create a compute pass from `spirv`, which reads and writes `bindings`, taking `push_constant_size` bytes of push
+    /// constants - see [`ComputePasses`].
+    pub fn add_compute_pass(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        spirv: &[u32],
+        bindings: &[ComputeBuffer],
+        push_constant_size: u32,
+    ) -> Result<ComputePassId> {
+        self.compute_passes.add(
+            vulkan_context,
+            &self.descriptors,
+            spirv,
+            bindings,
+            push_constant_size,
+        )
+    }
+
+    /// Record the compute passes apps have dispatched this frame. Must be called before
+    /// [`RenderContext::begin_pbr_render_pass`].
+    pub fn run_compute_passes(&mut self, vulkan_context: &VulkanContext) {
+        let frame = &self.frames[self.frame_index];
+        let buffers = EngineBuffers {
+            vertices: self.resources.vertex_buffer.buffer,
+            draw_data: frame.draw_data_buffer.buffer,
+            materials: self.resources.materials_buffer.buffer,
+        };
+        unsafe {
+            self.compute_passes.record(
+                &vulkan_context.device,
+                frame.command_buffer,
+                self.frame_index,
+                buffers,
             );
         }
     }
 
     pub fn end_pbr_render_pass(&mut self, vulkan_context: &VulkanContext) {
         let device = &vulkan_context.device;
-        let frame = &self.frames[self.frame_index];
+        let frame = &mut self.frames[self.frame_index];
         let command_buffer = frame.command_buffer;
+        let descriptor_set = self.descriptors.sets[self.frame_index];
         unsafe {
+            // Outline any interactables..
+            if self.accessibility.outline_interactables {
+                self.outline_pipeline.draw(
+                    device,
+                    command_buffer,
+                    descriptor_set,
+                    &self.resources,
+                    &self.outline_scratch,
+                    &self.accessibility,
+                );
+            }
+
+            // ..blend the sprites in the world over it..
+            frame.sprite_data_buffer.overwrite(&self.sprite_scratch);
+            self.sprite_pipeline.draw(
+                device,
+                command_buffer,
+                descriptor_set,
+                &frame.sprite_data_buffer,
+                &self.sprite_batches,
+                SpriteLayer::World,
+            );
+
+            // ..add any lens flares..
+            if !self.lens_flare_scratch.is_empty() {
+                self.lens_flare_pipeline.draw(
+                    device,
+                    command_buffer,
+                    descriptor_set,
+                    &self.lens_flare_scratch,
+                );
+            }
+
+            // ..put the head-locked sprites on top of everything..
+            self.sprite_pipeline.draw(
+                device,
+                command_buffer,
+                descriptor_set,
+                &frame.sprite_data_buffer,
+                &self.sprite_batches,
+                SpriteLayer::HeadLocked,
+            );
+
+            // ..narrow the view with the vignette, if the player's being moved..
+            if let Some(vignette) = &self.vignette {
+                self.vignette_pipeline
+                    .draw(device, command_buffer, vignette);
+            }
+
+            // ..then cover everything that's been drawn with the fade color, if there is one.
+            if self.fade_color.w > 0. {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.fade_pipeline,
+                );
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.fade_pipeline_layout,
+                    vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    create_push_constant(&self.fade_color),
+                );
+                device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            }
             device.cmd_end_render_pass(command_buffer);
+
+            // Finally, draw this frame's pick, if there is one, with the same draw data.
+            if let Some(picking) = &mut self.picking {
+                picking.draw(
+                    device,
+                    command_buffer,
+                    descriptor_set,
+                    self.resources.vertex_buffer.buffer,
+                    self.resources.index_buffer.buffer,
+                    &self.draw_batches,
+                    &self.primitive_map,
+                );
+            }
         }
     }
 
     /// Finish rendering a frame
     pub(crate) fn end_frame(&mut self, vulkan_context: &VulkanContext) {
+        self.submit_frame(vulkan_context);
+
+        // And we're done! Bump the frame index.
+        self.frame_index = (self.frame_index + 1) % PIPELINE_DEPTH;
+        self.app_data.next_frame(self.frame_index);
+    }
+
+    /// Submit the commands recorded for the current frame, without moving on to the next one.
+    pub(crate) fn submit_frame(&mut self, vulkan_context: &VulkanContext) {
         // Get the values we need to end the renderpass
         let device = &vulkan_context.device;
-        let frame = &self.frames[self.frame_index];
+        let frame = &mut self.frames[self.frame_index];
         let command_buffer = frame.command_buffer;
-        let graphics_queue = vulkan_context.graphics_queue;
+        let cull_stage = vk::PipelineStageFlags::VERTEX_SHADER;
 
-        // End the render pass and submit.
+        // End the render pass and submit, once the primitives have been culled.
         unsafe {
+            // If culling handed its buffers over, take them, and give them back for the next time this frame is culled.
+            if frame.cull_buffers_released_to == Some(Pass::Render) {
+                let cull_buffers = frame.cull_buffers();
+                QueueTransfer::new(vulkan_context, Pass::Cull, Pass::Render).acquire(
+                    device,
+                    command_buffer,
+                    &cull_buffers,
+                    cull_stage,
+                    vk::AccessFlags::empty(),
+                );
+                QueueTransfer::new(vulkan_context, Pass::Render, Pass::Cull).release(
+                    device,
+                    command_buffer,
+                    &cull_buffers,
+                    cull_stage,
+                    vk::AccessFlags::empty(),
+                );
+                frame.cull_buffers_released_to = Some(Pass::Cull);
+            }
             device.end_command_buffer(command_buffer).unwrap();
-            let fence = frame.fence;
-            let submit_info =
-                vk::SubmitInfo::builder().command_buffers(slice_from_ref(&command_buffer));
-            device
-                .queue_submit(graphics_queue, slice_from_ref(&submit_info), fence)
-                .expect("[HOTHAM_RENDER] @@ GPU CRASH DETECTED @@ - You are probably doing too much work in a compute shader!");
         }
-
-        // And we're done! Bump the frame index.
-        self.frame_index = (self.frame_index + 1) % PIPELINE_DEPTH;
+        let submit =
+            self.timeline
+                .submit(Pass::Render)
+                .wait_for(&self.timeline, Pass::Cull, cull_stage);
+        submit
+            .submit(vulkan_context, command_buffer)
+            .expect("[HOTHAM_RENDER] @@ GPU CRASH DETECTED @@ - You are probably doing too much work in a compute shader!");
+        frame.render_value = submit.signal_value();
+
+        // The pick can only be copied out once it's been drawn.
+        if let Some(picking) = &mut self.picking {
+            picking.read_back(vulkan_context, &mut self.readback);
+        }
     }
 
-    pub(crate) fn wait(&self, device: &ash::Device, frame: &Frame) {
-        let fence = frame.fence;
+    /// Throw away everything recorded this frame, eg. because a panic interrupted it, and submit an empty frame instead.
+    pub(crate) fn abandon_frame(&mut self, vulkan_context: &VulkanContext) {
+        let device = &vulkan_context.device;
+        let command_buffer = self.frames[self.frame_index].command_buffer;
 
         unsafe {
-            device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
-            device.reset_fences(&[fence]).unwrap();
+            device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                .unwrap();
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::builder()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .unwrap();
         }
+
+        self.end_frame(vulkan_context);
     }
 
     pub(crate) fn create_texture_image(
@@ -446,8 +1344,11 @@ impl RenderContext {
         }
 
         let texture_index = unsafe {
-            self.resources
-                .write_texture_to_array(vulkan_context, &self.descriptors, texture_image)
+            self.resources.write_texture_to_array(
+                vulkan_context,
+                &self.descriptors,
+                texture_image,
+            )?
         };
 
         println!(
@@ -459,25 +1360,130 @@ impl RenderContext {
     }
 }
 
+/// All the instances of a single primitive that will be considered for drawing this frame.
+///
+/// Instance data is stored as a structure of arrays so that the per-frame passes over it (building
+/// cull data, building draw data) only touch the memory they actually need.
 pub struct InstancedPrimitive {
     pub primitive: Primitive,
-    pub instances: Vec<Instance>,
+    pub gos_from_local: Vec<Affine3A>,
+    pub bounding_spheres: Vec<Vec4>,
+    pub skin_ids: Vec<u32>,
+    pub ambient_probes: Vec<Option<[Vec4; 3]>>,
+    pub entity_ids: Vec<u32>,
+}
+
+impl InstancedPrimitive {
+    pub fn new(primitive: Primitive) -> Self {
+        Self {
+            primitive,
+            gos_from_local: Default::default(),
+            bounding_spheres: Default::default(),
+            skin_ids: Default::default(),
+            ambient_probes: Default::default(),
+            entity_ids: Default::default(),
+        }
+    }
+
+    /// Add an instance of this primitive, calculating its bounding sphere in gos space.
+    pub fn push_instance(&mut self, gos_from_local: Affine3A, skin_id: u32) {
+        self.push_instance_with_ambient_probe(gos_from_local, skin_id, None);
+    }
+
+    /// Add an instance of this primitive that's lit by `ambient_probe`, rather than the irradiance map.
+    pub fn push_instance_with_ambient_probe(
+        &mut self,
+        gos_from_local: Affine3A,
+        skin_id: u32,
+        ambient_probe: Option<&ShProbe>,
+    ) {
+        self.push_entity_instance(NO_ENTITY, gos_from_local, skin_id, ambient_probe);
+    }
+
+    /// Add an instance of this primitive that belongs to the entity with [`hecs::Entity::id`] `entity_id`, so it can be
+    /// found by [`RenderContext::pick`].
+    pub fn push_entity_instance(
+        &mut self,
+        entity_id: u32,
+        gos_from_local: Affine3A,
+        skin_id: u32,
+        ambient_probe: Option<&ShProbe>,
+    ) {
+        self.bounding_spheres
+            .push(self.primitive.get_bounding_sphere_in_gos(&gos_from_local));
+        self.gos_from_local.push(gos_from_local);
+        self.skin_ids.push(skin_id);
+        self.ambient_probes.push(ambient_probe.map(ShProbe::packed));
+        self.entity_ids.push(entity_id);
+    }
+
+    /// The number of instances of this primitive.
+    pub fn len(&self) -> usize {
+        self.gos_from_local.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gos_from_local.is_empty()
+    }
+
+    /// Remove all instances, keeping the allocations around for the next frame.
+    pub fn clear(&mut self) {
+        self.gos_from_local.clear();
+        self.bounding_spheres.clear();
+        self.skin_ids.clear();
+        self.ambient_probes.clear();
+        self.entity_ids.clear();
+    }
+
+    /// Create the [`DrawData`] for the instance at `index`.
+    pub fn draw_data(&self, index: usize) -> DrawData {
+        let gos_from_local = self.gos_from_local[index];
+        DrawData {
+            gos_from_local: gos_from_local.into(),
+            local_from_gos: gos_from_local.inverse().into(),
+            material_id: self.primitive.material_id,
+            skin_id: self.skin_ids[index],
+            has_ambient_probe: self.ambient_probes[index].is_some() as u32,
+            entity_id: self.entity_ids[index],
+            ambient_probe: self.ambient_probes[index].unwrap_or_default(),
+        }
+    }
 }
 
-pub struct Instance {
-    pub gos_from_local: Affine3A,
-    pub bounding_sphere: Vec4,
-    pub skin_id: u32,
+/// A run of visible instances of a single primitive that can be drawn with one instanced draw call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawBatch {
+    /// The ID of the primitive - its index buffer offset.
+    pub primitive_id: u32,
+    /// The index of the first instance's [`DrawData`] in the draw data buffer.
+    pub instance_offset: u32,
+    /// The number of visible instances in this batch.
+    pub instance_count: u32,
 }
 
 pub fn create_push_constant<T: Sized>(p: &T) -> &[u8] {
     unsafe { std::slice::from_raw_parts(p as *const T as *const u8, size_of::<T>()) }
 }
 
-fn create_render_pass(vulkan_context: &VulkanContext) -> Result<vk::RenderPass> {
+/// `area` with its width and height scaled by `scale`, keeping at least one pixel.
+fn scale_render_area(area: vk::Rect2D, scale: f32) -> vk::Rect2D {
+    let scale_dimension = |d: u32| ((d as f32 * scale).round() as u32).clamp(1, d.max(1));
+    vk::Rect2D {
+        offset: area.offset,
+        extent: vk::Extent2D {
+            width: scale_dimension(area.extent.width),
+            height: scale_dimension(area.extent.height),
+        },
+    }
+}
+
+fn create_render_pass(
+    vulkan_context: &VulkanContext,
+    color_format: vk::Format,
+) -> Result<vk::RenderPass> {
     // Attachment used for MSAA
     let color_attachment = vk::AttachmentDescription::builder()
-        .format(COLOR_FORMAT)
+        .format(color_format)
         .samples(vk::SampleCountFlags::TYPE_4)
         .load_op(vk::AttachmentLoadOp::CLEAR)
         .store_op(vk::AttachmentStoreOp::DONT_CARE)
@@ -488,7 +1494,7 @@ fn create_render_pass(vulkan_context: &VulkanContext) -> Result<vk::RenderPass>
 
     // Final attachment to be presented
     let color_attachment_resolve = vk::AttachmentDescription::builder()
-        .format(COLOR_FORMAT)
+        .format(color_format)
         .samples(vk::SampleCountFlags::TYPE_1)
         .load_op(vk::AttachmentLoadOp::DONT_CARE)
         .store_op(vk::AttachmentStoreOp::DONT_CARE)
@@ -549,6 +1555,9 @@ fn create_render_pass(vulkan_context: &VulkanContext) -> Result<vk::RenderPass>
                 | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
         );
 
+    // Draw every view in one pass with `VK_KHR_multiview`: each draw is broadcast to a layer of the swapchain image per
+    // eye, and the shaders pick the eye's camera with `gl_ViewIndex`. Most of the scene looks much the same from both
+    // eyes, so the views are correlated too.
     let view_masks = [!(!0 << VIEW_COUNT)];
     let mut multiview = vk::RenderPassMultiviewCreateInfo::builder()
         .view_masks(&view_masks)
@@ -574,21 +1583,44 @@ fn create_render_pass(vulkan_context: &VulkanContext) -> Result<vk::RenderPass>
     Ok(render_pass)
 }
 
-fn create_pipeline(
+/// Create a pipeline that draws meshes with `vertex_shader_code` and `fragment_shader_code`. The PBR pipeline uses
+/// `pbr.vert` and `pbr.frag`; other vertex shaders must write the same outputs, and other fragment shaders must be
+/// `pbr.frag` compiled with different defines.
+pub(crate) fn create_pipeline(
     vulkan_context: &VulkanContext,
     pipeline_layout: vk::PipelineLayout,
     render_area: &vk::Rect2D,
     render_pass: vk::RenderPass,
+    reversed_z: bool,
+    texture_capacity: u32,
+    vertex_shader_code: &[u32],
+    fragment_shader_code: &[u32],
 ) -> Result<vk::Pipeline> {
     // Build up the state of the pipeline
 
     // Vertex shader stage
-    let (vertex_shader, vertex_stage) =
-        create_shader(VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
-
-    // Fragment shader stage
-    let (fragment_shader, fragment_stage) =
-        create_shader(FRAG, vk::ShaderStageFlags::FRAGMENT, vulkan_context)?;
+    let (vertex_shader, vertex_stage) = create_shader(
+        vertex_shader_code,
+        vk::ShaderStageFlags::VERTEX,
+        vulkan_context,
+    )?;
+
+    // Fragment shader stage. The size of the texture array depends on what the device supports, so it's passed in as
+    // a specialization constant.
+    let (fragment_shader, mut fragment_stage) = create_shader(
+        fragment_shader_code,
+        vk::ShaderStageFlags::FRAGMENT,
+        vulkan_context,
+    )?;
+    let texture_count_entry = vk::SpecializationMapEntry {
+        constant_id: TEXTURE_COUNT_CONSTANT_ID,
+        offset: 0,
+        size: size_of::<u32>(),
+    };
+    let fragment_specialization = vk::SpecializationInfo::builder()
+        .map_entries(slice_from_ref(&texture_count_entry))
+        .data(create_push_constant(&texture_capacity));
+    fragment_stage.p_specialization_info = &*fragment_specialization;
 
     let stages = [vertex_stage, fragment_stage];
 
@@ -648,7 +1680,11 @@ fn create_pipeline(
     let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
         .depth_test_enable(true)
         .depth_write_enable(true)
-        .depth_compare_op(vk::CompareOp::GREATER)
+        .depth_compare_op(if reversed_z {
+            vk::CompareOp::GREATER
+        } else {
+            vk::CompareOp::LESS
+        })
         .depth_bounds_test_enable(false)
         .min_depth_bounds(0.0)
         .max_depth_bounds(1.0)
@@ -671,11 +1707,15 @@ fn create_pipeline(
         .logic_op_enable(false)
         .attachments(&color_blend_attachments);
 
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
     let create_info = vk::GraphicsPipelineCreateInfo::builder()
         .stages(&stages)
         .vertex_input_state(&vertex_input_state)
         .input_assembly_state(&input_assembly_state)
         .viewport_state(&viewport_state)
+        .dynamic_state(&dynamic_state)
         .rasterization_state(&rasterization_state)
         .multisample_state(&multisample_state)
         .depth_stencil_state(&depth_stencil_state)
@@ -744,6 +1784,118 @@ fn create_pipeline_layout(
     .map_err(|e| e.into())
 }
 
+fn create_fade_pipeline_layout(vulkan_context: &VulkanContext) -> Result<vk::PipelineLayout> {
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(size_of::<Vec4>() as _)
+        .build();
+    let create_info = &vk::PipelineLayoutCreateInfo::builder()
+        .push_constant_ranges(slice_from_ref(&push_constant_range));
+    unsafe {
+        vulkan_context
+            .device
+            .create_pipeline_layout(create_info, None)
+    }
+    .map_err(|e| e.into())
+}
+
+/// A pipeline that blends a single color over the whole view, ignoring depth.
+fn create_fade_pipeline(
+    vulkan_context: &VulkanContext,
+    pipeline_layout: vk::PipelineLayout,
+    render_area: &vk::Rect2D,
+    render_pass: vk::RenderPass,
+) -> Result<vk::Pipeline> {
+    let (vertex_shader, vertex_stage) =
+        create_shader(FADE_VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
+    let (fragment_shader, fragment_stage) =
+        create_shader(FADE_FRAG, vk::ShaderStageFlags::FRAGMENT, vulkan_context)?;
+    let stages = [vertex_stage, fragment_stage];
+
+    // The triangle is generated in the vertex shader, so there are no vertex inputs.
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let viewport = vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: render_area.extent.width as _,
+        height: render_area.extent.height as _,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    };
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewports(slice_from_ref(&viewport))
+        .scissors(slice_from_ref(render_area));
+
+    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .line_width(1.0);
+    let multisample_state =
+        vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(SAMPLES);
+    let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(
+            vk::ColorComponentFlags::R
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::A,
+        )
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .build();
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .attachments(slice_from_ref(&color_blend_attachment));
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_state)
+        .viewport_state(&viewport_state)
+        .dynamic_state(&dynamic_state)
+        .rasterization_state(&rasterization_state)
+        .multisample_state(&multisample_state)
+        .depth_stencil_state(&depth_stencil_state)
+        .color_blend_state(&color_blend_state)
+        .layout(pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0)
+        .build();
+
+    let pipelines = unsafe {
+        vulkan_context.device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            slice_from_ref(&create_info),
+            None,
+        )
+    }
+    .map_err(|(_, r)| r)?;
+
+    unsafe {
+        vulkan_context
+            .device
+            .destroy_shader_module(vertex_shader, None);
+        vulkan_context
+            .device
+            .destroy_shader_module(fragment_shader, None);
+    }
+
+    Ok(pipelines[0])
+}
+
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct CullParams {
@@ -797,3 +1949,25 @@ fn create_compute_pipeline(
         (pipeline, layout)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_scale_render_area() {
+        let area = vk::Rect2D {
+            offset: Default::default(),
+            extent: vk::Extent2D {
+                width: 1832,
+                height: 1920,
+            },
+        };
+        assert_eq!(scale_render_area(area, 1.), area);
+
+        let scaled = scale_render_area(area, 0.7);
+        assert_eq!(scaled.extent.width, 1282);
+        assert_eq!(scaled.extent.height, 1344);
+        assert_eq!(scale_render_area(area, 0.).extent.width, 1);
+    }
+}
@@ -0,0 +1,545 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use glam::{Quat, Vec3};
+use hecs::{Entity, World};
+use wasmtime::{Caller, Config, Extern, Linker, Module, Store, WasmParams};
+
+use crate::{
+    components::{Collider, GlobalTransform, LocalTransform, SoundEmitter},
+    scripting::events,
+    HothamError, HothamResult,
+};
+
+/// How much work a script may do in a single call before it's stopped, so a runaway script can't hang the headset.
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// How often scripts loaded from files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs gameplay logic written in WebAssembly, so it can be changed without recompiling the app.
+///
+/// Scripts are attached to an entity. Every export is optional:
+///
+/// - `on_start()` - called the first frame after the script is attached or reloaded
+/// - `on_update(delta_time: f32)` - called every frame
+/// - `on_collision(other: i64)` - called for each collision, if subscribed to [`events::COLLISION`]
+/// - `on_input(button: i32)` - called for each of the [`crate::scripting::buttons`] pressed, if subscribed to [`events::INPUT`]
+///
+/// Scripts can import these functions from the `hotham` module. Entities are passed as `i64`s, and `_ -> i32`
+/// functions return 1 on success, or 0 if the entity doesn't exist or doesn't have the right components:
+///
+/// - `entity() -> i64` - the entity the script is attached to
+/// - `spawn() -> i64` and `despawn(entity: i64)`
+/// - `get_translation(entity: i64, out: i32) -> i32` - writes three `f32`s to memory at `out`
+/// - `set_translation(entity: i64, x: f32, y: f32, z: f32) -> i32`
+/// - `get_rotation(entity: i64, out: i32) -> i32` - writes a quaternion as four `f32`s to memory at `out`
+/// - `set_rotation(entity: i64, x: f32, y: f32, z: f32, w: f32) -> i32`
+/// - `set_scale(entity: i64, x: f32, y: f32, z: f32) -> i32`
+/// - `play_sound(entity: i64) -> i32` - plays the entity's `SoundEmitter`
+/// - `subscribe(event: i32)` - one of the [`events`]
+/// - `log(message: i32, length: i32)` - logs a UTF-8 string from memory
+///
+/// Scripts loaded with [`ScriptContext::load_file`] are reloaded whenever the file changes, so new logic can be
+/// pushed to a headset with `adb push` while the app is running.
+pub struct ScriptContext {
+    engine: wasmtime::Engine,
+    linker: Linker<ScriptHost>,
+    modules: HashMap<String, ScriptModule>,
+    instances: Vec<ScriptInstance>,
+    last_checked: Instant,
+}
+
+struct ScriptModule {
+    module: Module,
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+struct ScriptInstance {
+    name: String,
+    entity: Entity,
+    store: Store<ScriptHost>,
+    instance: wasmtime::Instance,
+    started: bool,
+    failed: bool,
+}
+
+/// The state available to host functions while a script is running.
+struct ScriptHost {
+    /// The engine's World, swapped in for the duration of each call
+    world: World,
+    entity: Entity,
+    collisions: bool,
+    input: bool,
+}
+
+impl Default for ScriptContext {
+    fn default() -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&config).expect("Unable to create WASM engine");
+        let linker = create_linker(&engine);
+
+        Self {
+            engine,
+            linker,
+            modules: Default::default(),
+            instances: Default::default(),
+            last_checked: Instant::now(),
+        }
+    }
+}
+
+impl ScriptContext {
+    /// Compile a script from WASM (or WAT) and store it as `name`. If a script called `name` was already loaded, every
+    /// entity it's attached to is switched over to the new one.
+    pub fn load(&mut self, name: &str, bytes: &[u8]) -> HothamResult<()> {
+        let module = Module::new(&self.engine, bytes).map_err(|e| script_error(name, e))?;
+        self.replace_module(
+            name,
+            ScriptModule {
+                module,
+                path: None,
+                modified: None,
+            },
+        )
+    }
+
+    /// Load a script from `path` and store it as `name`, reloading it whenever the file changes.
+    pub fn load_file(&mut self, name: &str, path: impl Into<PathBuf>) -> HothamResult<()> {
+        let path = path.into();
+        let modified = fs::metadata(&path)?.modified().ok();
+        let module = Module::from_file(&self.engine, &path).map_err(|e| script_error(name, e))?;
+        self.replace_module(
+            name,
+            ScriptModule {
+                module,
+                path: Some(path),
+                modified,
+            },
+        )
+    }
+
+    /// Attach the script called `name` to `entity`. Its `on_start` export is called next frame.
+    pub fn attach(&mut self, entity: Entity, name: &str) -> HothamResult<()> {
+        let instance = self.instantiate(entity, name)?;
+        self.instances.push(instance);
+        Ok(())
+    }
+
+    /// Remove every script attached to `entity`.
+    pub fn detach(&mut self, entity: Entity) {
+        self.instances.retain(|i| i.entity != entity);
+    }
+
+    /// Reload any scripts whose files have changed since they were loaded.
+    pub fn reload_changed_files(&mut self) {
+        if self.last_checked.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_checked = Instant::now();
+
+        let changed = self
+            .modules
+            .iter()
+            .filter_map(|(name, module)| {
+                let path = module.path.as_ref()?;
+                let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+                if Some(modified) != module.modified {
+                    Some((name.clone(), path.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (name, path) in changed {
+            match self.load_file(&name, &path) {
+                Ok(_) => log::info!(
+                    "[HOTHAM_SCRIPTING] Reloaded {} from {}",
+                    name,
+                    path.display()
+                ),
+                Err(e) => {
+                    log::error!("[HOTHAM_SCRIPTING] {} - keeping the last version", e);
+                    // Don't try the same file again until it changes.
+                    let module = self.modules.get_mut(&name).unwrap();
+                    module.modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                }
+            }
+        }
+    }
+
+    /// Run every attached script for this frame.
+    pub(crate) fn update(&mut self, world: &mut World, buttons_pressed: &[i32], delta_time: f32) {
+        // Forget scripts whose entities have been despawned.
+        self.instances.retain(|i| world.contains(i.entity));
+
+        for instance in &mut self.instances {
+            if !instance.started {
+                instance.started = true;
+                instance.call(world, "on_start", ());
+            }
+
+            instance.call(world, "on_update", delta_time);
+
+            if instance.store.data().collisions {
+                let collisions = world
+                    .get::<&Collider>(instance.entity)
+                    .map(|c| c.collisions_this_frame.clone())
+                    .unwrap_or_default();
+                for other in collisions {
+                    instance.call(world, "on_collision", other.to_bits().get());
+                }
+            }
+
+            if instance.store.data().input {
+                for button in buttons_pressed {
+                    instance.call(world, "on_input", *button);
+                }
+            }
+        }
+    }
+
+    fn replace_module(&mut self, name: &str, module: ScriptModule) -> HothamResult<()> {
+        self.modules.insert(name.to_string(), module);
+
+        // Hot swap anything that was running the old version.
+        for index in 0..self.instances.len() {
+            if self.instances[index].name == name {
+                let entity = self.instances[index].entity;
+                self.instances[index] = self.instantiate(entity, name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn instantiate(&self, entity: Entity, name: &str) -> HothamResult<ScriptInstance> {
+        let module = self
+            .modules
+            .get(name)
+            .ok_or_else(|| script_error(name, "no script has been loaded with that name"))?;
+        let mut store = Store::new(
+            &self.engine,
+            ScriptHost {
+                world: Default::default(),
+                entity,
+                collisions: false,
+                input: false,
+            },
+        );
+        store
+            .add_fuel(FUEL_PER_CALL)
+            .map_err(|e| script_error(name, e))?;
+        let instance = self
+            .linker
+            .instantiate(&mut store, &module.module)
+            .map_err(|e| script_error(name, e))?;
+
+        Ok(ScriptInstance {
+            name: name.to_string(),
+            entity,
+            store,
+            instance,
+            started: false,
+            failed: false,
+        })
+    }
+}
+
+impl ScriptInstance {
+    /// Call the export `function`, if the script has one. If the script traps, it's logged and not run again.
+    fn call<P: WasmParams>(&mut self, world: &mut World, function: &str, params: P) {
+        if self.failed {
+            return;
+        }
+
+        let func = match self.instance.get_func(&mut self.store, function) {
+            Some(func) => func,
+            None => return,
+        };
+        let result = func
+            .typed::<P, (), _>(&self.store)
+            .map_err(anyhow::Error::from)
+            .and_then(|func| {
+                refuel(&mut self.store)?;
+                std::mem::swap(world, &mut self.store.data_mut().world);
+                let result = func.call(&mut self.store, params);
+                std::mem::swap(world, &mut self.store.data_mut().world);
+                result.map_err(Into::into)
+            });
+
+        if let Err(e) = result {
+            log::error!(
+                "[HOTHAM_SCRIPTING] {} failed in {}, disabling it: {:?}",
+                self.name,
+                function,
+                e
+            );
+            self.failed = true;
+        }
+    }
+}
+
+fn refuel(store: &mut Store<ScriptHost>) -> anyhow::Result<()> {
+    let remaining = store.consume_fuel(0)?;
+    if remaining < FUEL_PER_CALL {
+        store.add_fuel(FUEL_PER_CALL - remaining)?;
+    }
+    Ok(())
+}
+
+fn script_error(name: &str, reason: impl ToString) -> HothamError {
+    HothamError::ScriptError {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn create_linker(engine: &wasmtime::Engine) -> Linker<ScriptHost> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap("hotham", "entity", |caller: Caller<'_, ScriptHost>| {
+            caller.data().entity.to_bits().get()
+        })
+        .unwrap()
+        .func_wrap("hotham", "spawn", |mut caller: Caller<'_, ScriptHost>| {
+            caller
+                .data_mut()
+                .world
+                .spawn((LocalTransform::default(), GlobalTransform::default()))
+                .to_bits()
+                .get()
+        })
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "despawn",
+            |mut caller: Caller<'_, ScriptHost>, entity: u64| {
+                if let Some(entity) = Entity::from_bits(entity) {
+                    let _ = caller.data_mut().world.despawn(entity);
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "get_translation",
+            |mut caller: Caller<'_, ScriptHost>, entity: u64, out: u32| {
+                let translation = with_transform(&mut caller, entity, |t| t.translation);
+                match translation {
+                    Some(t) => write_floats(&mut caller, out, &t.to_array()),
+                    None => 0,
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "set_translation",
+            |mut caller: Caller<'_, ScriptHost>, entity: u64, x: f32, y: f32, z: f32| {
+                with_transform(&mut caller, entity, |t| t.translation = Vec3::new(x, y, z))
+                    .is_some() as i32
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "get_rotation",
+            |mut caller: Caller<'_, ScriptHost>, entity: u64, out: u32| {
+                let rotation = with_transform(&mut caller, entity, |t| t.rotation);
+                match rotation {
+                    Some(r) => write_floats(&mut caller, out, &r.to_array()),
+                    None => 0,
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "set_rotation",
+            |mut caller: Caller<'_, ScriptHost>, entity: u64, x: f32, y: f32, z: f32, w: f32| {
+                with_transform(&mut caller, entity, |t| {
+                    t.rotation = Quat::from_xyzw(x, y, z, w).normalize()
+                })
+                .is_some() as i32
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "set_scale",
+            |mut caller: Caller<'_, ScriptHost>, entity: u64, x: f32, y: f32, z: f32| {
+                with_transform(&mut caller, entity, |t| t.scale = Vec3::new(x, y, z)).is_some()
+                    as i32
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "play_sound",
+            |caller: Caller<'_, ScriptHost>, entity: u64| {
+                let entity = match Entity::from_bits(entity) {
+                    Some(entity) => entity,
+                    None => return 0,
+                };
+                match caller.data().world.get::<&mut SoundEmitter>(entity) {
+                    Ok(mut sound_emitter) => {
+                        sound_emitter.play();
+                        1
+                    }
+                    Err(_) => 0,
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "subscribe",
+            |mut caller: Caller<'_, ScriptHost>, event: i32| {
+                let host = caller.data_mut();
+                match event {
+                    events::COLLISION => host.collisions = true,
+                    events::INPUT => host.input = true,
+                    _ => log::warn!("[HOTHAM_SCRIPTING] Unknown event {}", event),
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "hotham",
+            "log",
+            |mut caller: Caller<'_, ScriptHost>, message: u32, length: u32| {
+                let mut buffer = vec![0; length as usize];
+                if let Some(memory) = memory(&mut caller) {
+                    if memory.read(&caller, message as usize, &mut buffer).is_ok() {
+                        log::info!("[HOTHAM_SCRIPT] {}", String::from_utf8_lossy(&buffer));
+                    }
+                }
+            },
+        )
+        .unwrap();
+    linker
+}
+
+/// Run `f` on the `LocalTransform` of `entity`, if it exists and has one.
+fn with_transform<T>(
+    caller: &mut Caller<'_, ScriptHost>,
+    entity: u64,
+    f: impl FnOnce(&mut LocalTransform) -> T,
+) -> Option<T> {
+    let entity = Entity::from_bits(entity)?;
+    let mut local_transform = caller
+        .data()
+        .world
+        .get::<&mut LocalTransform>(entity)
+        .ok()?;
+    Some(f(&mut local_transform))
+}
+
+fn memory(caller: &mut Caller<'_, ScriptHost>) -> Option<wasmtime::Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+/// Write `floats` into the script's memory at `offset`, returning 1 on success.
+fn write_floats(caller: &mut Caller<'_, ScriptHost>, offset: u32, floats: &[f32]) -> i32 {
+    let bytes = floats
+        .iter()
+        .flat_map(|f| f.to_le_bytes())
+        .collect::<Vec<_>>();
+    match memory(caller) {
+        Some(memory) => memory.write(caller, offset as usize, &bytes).is_ok() as i32,
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripting::buttons;
+
+    const SPINNER: &str = r#"
+        (module
+            (import "hotham" "entity" (func $entity (result i64)))
+            (import "hotham" "get_translation" (func $get_translation (param i64 i32) (result i32)))
+            (import "hotham" "set_translation" (func $set_translation (param i64 f32 f32 f32) (result i32)))
+            (import "hotham" "subscribe" (func $subscribe (param i32)))
+            (memory (export "memory") 1)
+            (func (export "on_start")
+                (drop (call $set_translation (call $entity) (f32.const 0) (f32.const 1) (f32.const 0)))
+                (call $subscribe (i32.const 1)))
+            (func (export "on_update") (param $dt f32)
+                (drop (call $get_translation (call $entity) (i32.const 0)))
+                (drop (call $set_translation (call $entity)
+                    (f32.add (f32.load (i32.const 0)) (local.get $dt))
+                    (f32.load (i32.const 4))
+                    (f32.load (i32.const 8)))))
+            (func (export "on_input") (param $button i32)
+                (drop (call $set_translation (call $entity) (f32.const 0) (f32.const 0) (f32.const 0))))
+        )
+    "#;
+
+    #[test]
+    pub fn test_scripts() {
+        let mut world = World::new();
+        let entity = world.spawn((LocalTransform::default(), GlobalTransform::default()));
+
+        let mut script_context = ScriptContext::default();
+        script_context.load("spinner", SPINNER.as_bytes()).unwrap();
+        script_context.attach(entity, "spinner").unwrap();
+
+        // The first frame should call on_start, then on_update..
+        script_context.update(&mut world, &[], 0.5);
+        let translation = world.get::<&LocalTransform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::new(0.5, 1.0, 0.0));
+
+        // ..and on_start should have subscribed to input.
+        script_context.update(&mut world, &[buttons::A], 0.5);
+        let translation = world.get::<&LocalTransform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::ZERO);
+
+        // Reloading the script should start it again.
+        script_context.load("spinner", SPINNER.as_bytes()).unwrap();
+        script_context.update(&mut world, &[], 0.25);
+        let translation = world.get::<&LocalTransform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::new(0.25, 1.0, 0.0));
+
+        // Despawned entities should lose their scripts.
+        world.despawn(entity).unwrap();
+        script_context.update(&mut world, &[], 0.25);
+        assert!(script_context.instances.is_empty());
+    }
+
+    #[test]
+    pub fn test_runaway_script() {
+        let mut world = World::new();
+        let entity = world.spawn((LocalTransform::default(),));
+
+        let mut script_context = ScriptContext::default();
+        script_context
+            .load(
+                "forever",
+                br#"(module (func (export "on_update") (param f32) (loop (br 0))))"#,
+            )
+            .unwrap();
+        script_context.attach(entity, "forever").unwrap();
+
+        // The script should be stopped, rather than hanging the engine.
+        script_context.update(&mut world, &[], 0.5);
+        assert!(script_context.instances[0].failed);
+    }
+
+    #[test]
+    pub fn test_unknown_script() {
+        let mut world = World::new();
+        let entity = world.spawn(());
+        let mut script_context = ScriptContext::default();
+        assert!(script_context.attach(entity, "missing").is_err());
+        assert!(script_context.load("broken", b"not wasm").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{paths::Paths, HothamError, HothamResult};
+
+/// A function that upgrades a stored value from one version to the next.
+pub type Migration = Box<dyn Fn(Value) -> anyhow::Result<Value> + Send + Sync>;
+
+/// Persistent key-value storage for things like settings and save games.
+///
+/// Each key is stored as a JSON file in the application's private storage directory: internal storage on Android,
+/// or the user's config directory on desktop. Writes are atomic, so a crash or power loss part way through a save
+/// will never leave a corrupted file behind.
+///
+/// Every value is stored with a version number. When the shape of some saved data changes, register a
+/// [`Migration`] for its key with [`StorageContext::add_migration`] and old saves will be upgraded when they're
+/// next read.
+pub struct StorageContext {
+    root: PathBuf,
+    migrations: HashMap<String, Vec<Migration>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    value: T,
+}
+
+impl StorageContext {
+    /// Create a `StorageContext` in the default location for this platform - see [`Paths::internal`].
+    pub fn new(application_name: &str) -> Self {
+        Self::with_root(Paths::new(application_name).internal())
+    }
+
+    /// Create a `StorageContext` that stores its data in `root`. The directory is created when the first value is
+    /// written.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            migrations: Default::default(),
+        }
+    }
+
+    /// The directory this context stores its data in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Read the value stored for `key`, upgrading it with any registered migrations. Returns `None` if nothing has
+    /// been stored.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> HothamResult<Option<T>> {
+        let path = self.path_for(key)?;
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let envelope: Envelope<Value> =
+            serde_json::from_slice(&bytes).map_err(|e| invalid_data(key, e))?;
+        let current_version = self.current_version(key);
+        if envelope.version > current_version {
+            return Err(invalid_data(
+                key,
+                format!(
+                    "it was saved with version {} but the newest known version is {}",
+                    envelope.version, current_version
+                ),
+            ));
+        }
+
+        // Bring old data up to date, and save it so we don't have to do this again.
+        let value = if envelope.version < current_version {
+            let value = self.migrate(key, envelope.version, envelope.value)?;
+            self.write(key, &path, &value)?;
+            value
+        } else {
+            envelope.value
+        };
+
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| invalid_data(key, e))
+    }
+
+    /// Read the value stored for `key`, or `T::default()` if nothing has been stored.
+    pub fn get_or_default<T: DeserializeOwned + Default>(&self, key: &str) -> HothamResult<T> {
+        self.get(key).map(Option::unwrap_or_default)
+    }
+
+    /// Store `value` for `key`, replacing anything that was there before.
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> HothamResult<()> {
+        let path = self.path_for(key)?;
+        self.write(key, &path, value)
+    }
+
+    /// Remove the value stored for `key`, if any.
+    pub fn remove(&self, key: &str) -> HothamResult<()> {
+        match fs::remove_file(self.path_for(key)?) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Has a value been stored for `key`?
+    pub fn contains(&self, key: &str) -> bool {
+        self.path_for(key).map(|p| p.exists()).unwrap_or(false)
+    }
+
+    /// Register a migration for `key`. Migrations are applied in the order they're added: the first upgrades
+    /// values from version 0 to version 1, the second from version 1 to version 2, and so on. New values are always
+    /// written with the latest version.
+    pub fn add_migration<F>(&mut self, key: &str, migration: F) -> &mut Self
+    where
+        F: Fn(Value) -> anyhow::Result<Value> + Send + Sync + 'static,
+    {
+        self.migrations
+            .entry(key.to_string())
+            .or_default()
+            .push(Box::new(migration));
+        self
+    }
+
+    /// The version new values for `key` will be written with.
+    pub fn current_version(&self, key: &str) -> u32 {
+        self.migrations.get(key).map(|m| m.len()).unwrap_or(0) as _
+    }
+
+    fn migrate(&self, key: &str, from_version: u32, mut value: Value) -> HothamResult<Value> {
+        for (version, migration) in self.migrations[key]
+            .iter()
+            .enumerate()
+            .skip(from_version as _)
+        {
+            value = migration(value).map_err(|e| {
+                invalid_data(
+                    key,
+                    format!("migrating from version {} failed - {:?}", version, e),
+                )
+            })?;
+        }
+        Ok(value)
+    }
+
+    fn write<T: Serialize>(&self, key: &str, path: &Path, value: &T) -> HothamResult<()> {
+        let envelope = Envelope {
+            version: self.current_version(key),
+            value,
+        };
+        let bytes = serde_json::to_vec_pretty(&envelope).map_err(|e| invalid_data(key, e))?;
+
+        // Write to a temporary file first, then move it over the top of the old one. Renames are atomic, so readers
+        // will either see the old value or the new one, never half of each.
+        fs::create_dir_all(&self.root)?;
+        let temp_path = path.with_extension("json.tmp");
+        {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+
+    fn path_for(&self, key: &str) -> HothamResult<PathBuf> {
+        // Keys are used as file names, so keep them to a safe set of characters.
+        let is_valid = !key.is_empty()
+            && !key.starts_with('.')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+        if !is_valid {
+            return Err(HothamError::InvalidStorageKey {
+                key: key.to_string(),
+            });
+        }
+
+        Ok(self.root.join(format!("{}.json", key)))
+    }
+}
+
+fn invalid_data(key: &str, reason: impl ToString) -> HothamError {
+    HothamError::InvalidStorageData {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Settings {
+        volume: f32,
+        snap_turning: bool,
+    }
+
+    #[test]
+    pub fn test_get_and_set() {
+        let storage = StorageContext::with_root(temp_root());
+
+        assert_eq!(storage.get::<Settings>("settings").unwrap(), None);
+        assert!(!storage.contains("settings"));
+
+        let settings = Settings {
+            volume: 0.5,
+            snap_turning: true,
+        };
+        storage.set("settings", &settings).unwrap();
+        assert!(storage.contains("settings"));
+        assert_eq!(storage.get("settings").unwrap(), Some(settings));
+
+        // Nothing should be left behind from the atomic write.
+        assert!(!storage.root().join("settings.json.tmp").exists());
+
+        storage.remove("settings").unwrap();
+        assert_eq!(
+            storage.get_or_default::<Settings>("settings").unwrap(),
+            Settings::default()
+        );
+
+        fs::remove_dir_all(storage.root()).unwrap();
+    }
+
+    #[test]
+    pub fn test_migrations() {
+        let root = temp_root();
+
+        // Version 0 only stored a score.
+        StorageContext::with_root(&root)
+            .set("save", &json!({ "score": 10 }))
+            .unwrap();
+
+        // Version 1 added a level, and version 2 renamed score to points.
+        let mut storage = StorageContext::with_root(&root);
+        storage
+            .add_migration("save", |mut value| {
+                value["level"] = json!(1);
+                Ok(value)
+            })
+            .add_migration("save", |value| {
+                Ok(json!({ "points": value["score"], "level": value["level"] }))
+            });
+
+        let save: Value = storage.get("save").unwrap().unwrap();
+        assert_eq!(save, json!({ "points": 10, "level": 1 }));
+
+        // The upgraded save should have been written back with the latest version..
+        let envelope: Envelope<Value> =
+            serde_json::from_slice(&fs::read(root.join("save.json")).unwrap()).unwrap();
+        assert_eq!(envelope.version, 2);
+
+        // ..which an older build should refuse to read.
+        assert!(StorageContext::with_root(&root)
+            .get::<Value>("save")
+            .is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    pub fn test_invalid_keys() {
+        let storage = StorageContext::with_root(temp_root());
+        for key in ["", "../escape", ".hidden", "a/b"] {
+            assert!(storage.set(key, &1).is_err());
+        }
+    }
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir().join(format!("hotham_storage_{}", uuid::Uuid::new_v4()))
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use openxr as xr;
+
+use super::physics_context::DELTA_TIME;
+
+/// The longest a single frame is allowed to take, in seconds. Frames can take much longer than this when the app is
+/// paused or the headset is taken off, and things shouldn't jump across the world when it comes back.
+pub const MAX_DELTA_TIME: f32 = 0.1;
+
+/// Keeps track of how much time passes between frames, so that things can move at the same speed no matter the
+/// headset's refresh rate - eg. 72Hz on Quest 1, or up to 120Hz on Quest 2 and Quest Pro.
+///
+/// The time between frames is taken from the runtime's predicted display times, which are much steadier than the time
+/// it takes to run each frame.
+#[derive(Debug, Clone)]
+pub struct TimeContext {
+    delta_time: f32,
+    elapsed: Duration,
+    frame: u64,
+    last_display_time: Option<xr::Time>,
+}
+
+impl Default for TimeContext {
+    fn default() -> Self {
+        Self {
+            delta_time: DELTA_TIME,
+            elapsed: Duration::ZERO,
+            frame: 0,
+            last_display_time: None,
+        }
+    }
+}
+
+impl TimeContext {
+    /// How long the last frame took, in seconds. Before the second frame, this is [`DELTA_TIME`].
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    /// How much time has passed since the first frame
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// How many frames there have been
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Start a new frame that will be displayed at `display_time`. Called by the engine at the start of each frame.
+    pub fn update(&mut self, display_time: xr::Time) {
+        if let Some(last_display_time) = self.last_display_time {
+            let nanos = display_time.as_nanos() - last_display_time.as_nanos();
+            if nanos > 0 {
+                self.advance(nanos as f32 / 1e9);
+            }
+        }
+        self.last_display_time = Some(display_time);
+    }
+
+    /// Start a new frame, `delta_time` seconds after the last one, eg. to drive systems from a fixed time step in
+    /// tests.
+    pub fn advance(&mut self, delta_time: f32) {
+        self.delta_time = delta_time.clamp(0., MAX_DELTA_TIME);
+        self.elapsed += Duration::from_secs_f32(self.delta_time);
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_time_context() {
+        let mut time_context = TimeContext::default();
+        assert_eq!(time_context.delta_time(), DELTA_TIME);
+
+        // The first frame only sets the starting point..
+        time_context.update(xr::Time::from_nanos(1_000_000_000));
+        assert_eq!(time_context.frame(), 0);
+
+        // ..and after that, time moves on by however long the runtime says each frame takes.
+        time_context.update(xr::Time::from_nanos(1_011_111_111));
+        assert!((time_context.delta_time() - 1. / 90.).abs() < 1e-6);
+        assert_eq!(time_context.frame(), 1);
+
+        // Long pauses don't count.
+        time_context.update(xr::Time::from_nanos(5_000_000_000));
+        assert_eq!(time_context.delta_time(), MAX_DELTA_TIME);
+        let elapsed = time_context.elapsed().as_secs_f32();
+        assert!((elapsed - (1. / 90. + MAX_DELTA_TIME)).abs() < 1e-6);
+    }
+}
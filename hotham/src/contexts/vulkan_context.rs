@@ -2,19 +2,27 @@
 
 use crate::{
     hotham_error::HothamError,
-    rendering::{image::Image, texture::DEFAULT_COMPONENT_MAPPING},
-    DEPTH_FORMAT,
+    rendering::{image::Image, memory, texture::DEFAULT_COMPONENT_MAPPING},
+    DEPTH_FORMAT, VIEW_COUNT,
 };
 use anyhow::{anyhow, Result};
 use ash::{
-    extensions::ext::DebugUtils,
+    extensions::{
+        ext::DebugUtils,
+        khr::{AccelerationStructure, DeferredHostOperations, TimelineSemaphore},
+    },
     prelude::VkResult,
     util::Align,
     vk::{self, Handle, ObjectType},
     Device, Entry, Instance as AshInstance,
 };
 use openxr as xr;
-use std::{cmp::max, ffi::CString, fmt::Debug, ptr::copy, slice::from_ref as slice_from_ref};
+use std::{
+    cmp::max,
+    ffi::{CStr, CString},
+    fmt::Debug,
+    ptr::copy,
+};
 
 type XrVulkan = xr::Vulkan;
 
@@ -30,10 +38,19 @@ pub struct VulkanContext {
     pub command_pool: vk::CommandPool,
     pub queue_family_index: u32,
     pub graphics_queue: vk::Queue,
+    /// The queue family compute work is submitted to. The same as `queue_family_index` if there's no async compute.
+    pub compute_queue_family_index: u32,
+    /// The queue compute work, eg. culling, is submitted to. On GPUs with a separate compute queue, this work can
+    /// overlap with rendering; otherwise it's the graphics queue.
+    pub compute_queue: vk::Queue,
+    /// Command pool for `compute_queue`
+    pub compute_command_pool: vk::CommandPool,
     #[deprecated]
     pub descriptor_pool: vk::DescriptorPool,
     pub debug_utils: DebugUtils,
     pub physical_device_properties: vk::PhysicalDeviceProperties,
+    /// What the device can do, and so which features were turned on
+    pub capabilities: DeviceCapabilities,
 }
 
 impl VulkanContext {
@@ -42,6 +59,7 @@ impl VulkanContext {
         system: xr::SystemId,
         application_name: &str,
         application_version: u32,
+        physical_device_settings: &PhysicalDeviceSettings,
     ) -> Result<Self> {
         println!("[HOTHAM_VULKAN] Creating VulkanContext..");
         let vk_target_version_xr = xr::Version::new(1, 2, 128);
@@ -86,58 +104,31 @@ impl VulkanContext {
         };
 
         let physical_device = unsafe {
-            vk::PhysicalDevice::from_raw(
+            let runtime_device = vk::PhysicalDevice::from_raw(
                 xr_instance.vulkan_graphics_device(system, instance_handle)? as _,
-            )
+            );
+            pick_physical_device(&instance, runtime_device, physical_device_settings)?
         };
 
-        let queue_family_index = unsafe {
-            instance
-                .get_physical_device_queue_family_properties(physical_device)
-                .into_iter()
-                .enumerate()
-                .find_map(|(queue_family_index, info)| {
-                    if info.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                        Some(queue_family_index as u32)
-                    } else {
-                        None
-                    }
-                })
-                .ok_or(HothamError::EmptyListError)?
-        };
-
-        let graphics_queue_create_info = vk::DeviceQueueCreateInfo::builder()
-            .queue_family_index(queue_family_index)
-            .queue_priorities(&[1.0])
-            .build();
+        let capabilities = DeviceCapabilities::query(&instance, physical_device)?;
+        let queue_families = capabilities.queue_families;
+        let queue_create_infos = queue_families.queue_create_infos(&[1.0]);
 
         // We use a *whole bunch* of different features, and somewhat annoyingly they're all enabled in different ways.
-        let enabled_features = vk::PhysicalDeviceFeatures::builder()
-            .multi_draw_indirect(true)
-            .sampler_anisotropy(true)
-            .build();
+        let mut features = capabilities.features();
 
-        let mut physical_device_features = vk::PhysicalDeviceVulkan11Features::builder()
-            .multiview(true)
-            .shader_draw_parameters(true);
-
-        let mut descriptor_indexing_features =
-            vk::PhysicalDeviceDescriptorIndexingFeatures::builder()
-                .shader_sampled_image_array_non_uniform_indexing(true)
-                .descriptor_binding_partially_bound(true)
-                .descriptor_binding_variable_descriptor_count(true)
-                .descriptor_binding_sampled_image_update_after_bind(true)
-                .runtime_descriptor_array(true);
-
-        let mut robust_features =
-            vk::PhysicalDeviceRobustness2FeaturesEXT::builder().null_descriptor(true);
+        // The runtime adds the extensions it needs, so we only have to ask for our own.
+        let extension_names = capabilities
+            .extension_names()
+            .iter()
+            .map(|e| e.as_ptr())
+            .collect::<Vec<_>>();
 
-        let device_create_info = vk::DeviceCreateInfo::builder()
-            .queue_create_infos(slice_from_ref(&graphics_queue_create_info))
-            .enabled_features(&enabled_features)
-            .push_next(&mut descriptor_indexing_features)
-            .push_next(&mut robust_features)
-            .push_next(&mut physical_device_features);
+        let device_create_info = features.enable(
+            vk::DeviceCreateInfo::builder()
+                .queue_create_infos(&queue_create_infos)
+                .enabled_extension_names(&extension_names),
+        );
 
         let device_handle = unsafe {
             xr_instance.create_vulkan_device(
@@ -152,9 +143,12 @@ impl VulkanContext {
         let device =
             unsafe { Device::load(instance.fp_v1_0(), vk::Device::from_raw(device_handle as _)) };
 
-        let graphics_queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+        let (graphics_queue, compute_queue) = queue_families.get_queues(&device);
+        let queue_family_index = queue_families.graphics;
 
         let command_pool = create_command_pool(&device, queue_family_index)?;
+        let compute_command_pool =
+            create_compute_command_pool(&device, &queue_families, command_pool)?;
 
         let descriptor_pool = create_descriptor_pool(&device)?;
         let debug_utils = DebugUtils::new(&entry, &instance);
@@ -171,9 +165,13 @@ impl VulkanContext {
             command_pool,
             queue_family_index,
             graphics_queue,
+            compute_queue_family_index: queue_families.compute,
+            compute_queue,
+            compute_command_pool,
             descriptor_pool,
             debug_utils,
             physical_device_properties,
+            capabilities,
         })
     }
 
@@ -183,6 +181,7 @@ impl VulkanContext {
         system: xr::SystemId,
         application_name: &str,
         application_version: u32,
+        physical_device_settings: &PhysicalDeviceSettings,
     ) -> Result<Self> {
         let vk_target_version_xr = xr::Version::new(1, 1, 128);
 
@@ -196,16 +195,28 @@ impl VulkanContext {
         let (vulkan_instance, vulkan_entry) =
             vulkan_init_legacy(xr_instance, system, application_name, application_version)?;
         let physical_device = unsafe {
-            vk::PhysicalDevice::from_raw(
+            let runtime_device = vk::PhysicalDevice::from_raw(
                 xr_instance
                     .vulkan_graphics_device(system, vulkan_instance.handle().as_raw() as _)
                     .unwrap() as _,
-            )
+            );
+            pick_physical_device(&vulkan_instance, runtime_device, physical_device_settings)?
         };
-        let (device, graphics_queue, queue_family_index) =
-            create_vulkan_device_legacy(xr_instance, system, &vulkan_instance, physical_device)?;
+        let capabilities = DeviceCapabilities::query(&vulkan_instance, physical_device)?;
+        let queue_families = capabilities.queue_families;
+        let device = create_vulkan_device_legacy(
+            xr_instance,
+            system,
+            &vulkan_instance,
+            physical_device,
+            &capabilities,
+        )?;
+        let (graphics_queue, compute_queue) = queue_families.get_queues(&device);
+        let queue_family_index = queue_families.graphics;
 
         let command_pool = create_command_pool(&device, queue_family_index)?;
+        let compute_command_pool =
+            create_compute_command_pool(&device, &queue_families, command_pool)?;
 
         let descriptor_pool = create_descriptor_pool(&device)?;
         let debug_utils = DebugUtils::new(&vulkan_entry, &vulkan_instance);
@@ -219,23 +230,33 @@ impl VulkanContext {
             device,
             graphics_queue,
             queue_family_index,
+            compute_queue_family_index: queue_families.compute,
+            compute_queue,
             command_pool,
+            compute_command_pool,
             descriptor_pool,
             debug_utils,
             physical_device_properties,
+            capabilities,
         })
     }
 
     pub fn testing() -> Result<Self> {
         let (instance, entry) = vulkan_init_test()?;
         let physical_device = get_test_physical_device(&instance);
+        let capabilities = DeviceCapabilities::query(&instance, physical_device)?;
+        let queue_families = capabilities.queue_families;
         let mut extension_names = Vec::new();
-        add_device_extension_names(&mut extension_names);
+        add_device_extension_names(&mut extension_names, &capabilities);
 
-        let (device, graphics_queue, queue_family_index) =
-            create_vulkan_device(&extension_names, &instance, physical_device)?;
+        let device =
+            create_vulkan_device(&extension_names, &instance, physical_device, &capabilities)?;
+        let (graphics_queue, compute_queue) = queue_families.get_queues(&device);
+        let queue_family_index = queue_families.graphics;
 
         let command_pool = create_command_pool(&device, queue_family_index)?;
+        let compute_command_pool =
+            create_compute_command_pool(&device, &queue_families, command_pool)?;
         let descriptor_pool = create_descriptor_pool(&device)?;
         let debug_utils = DebugUtils::new(&entry, &instance);
         let physical_device_properties =
@@ -248,10 +269,14 @@ impl VulkanContext {
             device,
             graphics_queue,
             queue_family_index,
+            compute_queue_family_index: queue_families.compute,
+            compute_queue,
             command_pool,
+            compute_command_pool,
             descriptor_pool,
             debug_utils,
             physical_device_properties,
+            capabilities,
         })
     }
 
@@ -507,6 +532,7 @@ impl VulkanContext {
             .allocation_size(memory_requirements.size);
 
         let device_memory = unsafe { self.device.allocate_memory(&allocate_info, None) }?;
+        memory::track_allocation(memory_requirements.size);
 
         Ok((memory_requirements.size, device_memory))
     }
@@ -778,7 +804,7 @@ impl VulkanContext {
         println!("[HOTHAM_VULKAN] Creating staging buffer..");
         let usage = vk::BufferUsageFlags::TRANSFER_SRC;
         let size = image_buf.len();
-        let (staging_buffer, staging_memory, _) = self
+        let (staging_buffer, staging_memory, staging_size) = self
             .create_buffer_with_data(image_buf, usage, size as _)
             .unwrap();
         println!("[HOTHAM_VULKAN] ..done!");
@@ -814,6 +840,7 @@ impl VulkanContext {
             self.device.destroy_buffer(staging_buffer, None);
             self.device.free_memory(staging_memory, None);
         }
+        memory::track_free(staging_size);
 
         println!("[HOTHAM_VULKAN] ..done!");
     }
@@ -821,12 +848,17 @@ impl VulkanContext {
 
 #[allow(unused_variables)]
 #[allow(clippy::ptr_arg)] // https://github.com/rust-lang/rust-clippy/issues/8388
-fn add_device_extension_names(extension_names: &mut Vec<CString>) {
-    extension_names.push(vk::KhrShaderDrawParametersFn::name().to_owned());
-
-    // Add Multiview extension
-    extension_names.push(CString::new("VK_KHR_multiview").unwrap());
-    extension_names.push(CString::new("VK_EXT_descriptor_indexing").unwrap());
+fn add_device_extension_names(
+    extension_names: &mut Vec<CString>,
+    capabilities: &DeviceCapabilities,
+) {
+    // Multiview and shader draw parameters are part of Vulkan 1.1, so they're turned on with features, not extensions.
+    extension_names.extend(
+        capabilities
+            .extension_names()
+            .into_iter()
+            .map(|e| e.to_owned()),
+    );
 
     // If we're on macOS we've got to add portability
     #[cfg(target_os = "macos")]
@@ -851,6 +883,449 @@ fn create_command_pool(
     Ok(command_pool)
 }
 
+/// Command pool for compute work. If compute shares the graphics queue family, it shares its pool too.
+fn create_compute_command_pool(
+    device: &Device,
+    queue_families: &QueueFamilies,
+    command_pool: vk::CommandPool,
+) -> Result<vk::CommandPool, anyhow::Error> {
+    if queue_families.has_async_compute() {
+        create_command_pool(device, queue_families.compute)
+    } else {
+        Ok(command_pool)
+    }
+}
+
+/// The queue families work is submitted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFamilies {
+    /// The queue family used for rendering, and anything else that isn't compute
+    pub graphics: u32,
+    /// The queue family used for compute work, eg. culling
+    pub compute: u32,
+}
+
+impl QueueFamilies {
+    /// Pick a queue family for graphics, and a separate one for compute if the device has one, so compute work can
+    /// overlap with rendering. Falls back to doing compute on the graphics queue.
+    pub fn choose(families: &[vk::QueueFamilyProperties]) -> Option<Self> {
+        let find = |wanted: vk::QueueFlags, unwanted: vk::QueueFlags| {
+            families
+                .iter()
+                .position(|f| {
+                    f.queue_count > 0
+                        && f.queue_flags.contains(wanted)
+                        && !f.queue_flags.intersects(unwanted)
+                })
+                .map(|i| i as u32)
+        };
+
+        let graphics = find(vk::QueueFlags::GRAPHICS, vk::QueueFlags::empty())?;
+        let compute = find(vk::QueueFlags::COMPUTE, vk::QueueFlags::GRAPHICS).unwrap_or(graphics);
+        Some(Self { graphics, compute })
+    }
+
+    /// Is there a separate queue for compute work?
+    pub fn has_async_compute(&self) -> bool {
+        self.compute != self.graphics
+    }
+
+    /// The queues to create with the device. The graphics queue always comes first.
+    fn queue_create_infos(&self, priorities: &[f32]) -> Vec<vk::DeviceQueueCreateInfo> {
+        let mut families = vec![self.graphics];
+        if self.has_async_compute() {
+            families.push(self.compute);
+        }
+        families
+            .into_iter()
+            .map(|family| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(family)
+                    .queue_priorities(priorities)
+                    .build()
+            })
+            .collect()
+    }
+
+    /// The graphics and compute queues. These are the same queue if there's no async compute.
+    fn get_queues(&self, device: &Device) -> (vk::Queue, vk::Queue) {
+        unsafe {
+            (
+                device.get_device_queue(self.graphics, 0),
+                device.get_device_queue(self.compute, 0),
+            )
+        }
+    }
+}
+
+/// What the device can do, found when the [`VulkanContext`] is created.
+///
+/// Optional features are only turned on if the device has them, and some have fallbacks that are picked
+/// automatically:
+///
+/// - Without a separate compute queue family, culling runs on the graphics queue. See [`QueueFamilies`].
+/// - Without full descriptor indexing support, textures go in a smaller, fixed size array. See
+///   [`DescriptorIndexingSupport`].
+/// - Without ray queries (or the `ray-query` feature), there are no ray traced contact shadows or ambient occlusion.
+///   See [`crate::rendering::ray_query`]. They also need Vulkan 1.2, for buffer device addresses.
+/// - Without indirect draws that start past the first instance, crowds are drawn with direct draws instead. See
+///   [`crate::rendering::crowd::CrowdRenderer`].
+/// - Shader draw parameters, anisotropic filtering and multi-draw indirect are turned on when they're there, but
+///   nothing relies on them.
+///
+/// The rest can't be done without, and creating the context fails with [`HothamError::UnsupportedDevice`], naming
+/// what's missing:
+///
+/// - Vulkan 1.1. Everything else Hotham uses from Vulkan 1.2 comes from extensions, so 1.2 isn't needed.
+/// - Multiview, which draws both eyes in a single pass. It's a required part of Vulkan 1.1, which Hotham needs anyway,
+///   so there's no fallback to drawing each eye separately.
+/// - Timeline semaphores, which order culling and rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    /// The version of Vulkan the device supports
+    pub api_version: u32,
+    /// Can both eyes be drawn in a single pass?
+    pub multiview: bool,
+    /// The most views that can be drawn in a single pass
+    pub max_multiview_view_count: u32,
+    /// Can shaders use draw parameters, eg. `gl_BaseInstance`?
+    pub shader_draw_parameters: bool,
+    /// Are timeline semaphores supported?
+    pub timeline_semaphore: bool,
+    /// Is anisotropic filtering supported?
+    pub sampler_anisotropy: bool,
+    /// Can a single indirect draw command make more than one draw?
+    pub multi_draw_indirect: bool,
+    /// Can indirect draw commands start past the first instance?
+    pub draw_indirect_first_instance: bool,
+    /// Which parts of descriptor indexing are supported
+    pub descriptor_indexing: DescriptorIndexingSupport,
+    /// Can shaders trace rays against acceleration structures? Always `false` without the `ray-query` feature.
+    pub ray_query: bool,
+    /// The queue families to submit work to
+    pub queue_families: QueueFamilies,
+    /// Does the device have `VK_EXT_descriptor_indexing`? If not, none of descriptor indexing can be turned on.
+    descriptor_indexing_extension: bool,
+}
+
+impl DeviceCapabilities {
+    /// Ask the device what it can do, failing if it's missing anything Hotham can't run without.
+    pub fn query(instance: &AshInstance, physical_device: vk::PhysicalDevice) -> Result<Self> {
+        let mut multiview = vk::PhysicalDeviceMultiviewFeatures::default();
+        let mut draw_parameters = vk::PhysicalDeviceShaderDrawParametersFeatures::default();
+        let mut timeline_semaphore = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+        let mut multiview_properties = vk::PhysicalDeviceMultiviewProperties::default();
+
+        let features = unsafe {
+            let mut features2 = vk::PhysicalDeviceFeatures2::builder()
+                .push_next(&mut multiview)
+                .push_next(&mut draw_parameters)
+                .push_next(&mut timeline_semaphore);
+            instance.get_physical_device_features2(physical_device, &mut features2);
+            features2.features
+        };
+        let api_version = unsafe {
+            let mut properties2 =
+                vk::PhysicalDeviceProperties2::builder().push_next(&mut multiview_properties);
+            instance.get_physical_device_properties2(physical_device, &mut properties2);
+            properties2.properties.api_version
+        };
+
+        let extensions =
+            unsafe { instance.enumerate_device_extension_properties(physical_device) }?;
+        let descriptor_indexing_extension = extensions.iter().any(|extension| {
+            let name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+            name == vk::ExtDescriptorIndexingFn::name()
+        });
+
+        let ray_query = api_version >= vk::API_VERSION_1_2
+            && supports_ray_query(instance, physical_device, &extensions);
+
+        let queue_families = QueueFamilies::choose(&unsafe {
+            instance.get_physical_device_queue_family_properties(physical_device)
+        })
+        .ok_or(HothamError::EmptyListError)?;
+
+        let capabilities = Self {
+            api_version,
+            multiview: multiview.multiview == vk::TRUE,
+            max_multiview_view_count: multiview_properties.max_multiview_view_count,
+            shader_draw_parameters: draw_parameters.shader_draw_parameters == vk::TRUE,
+            timeline_semaphore: timeline_semaphore.timeline_semaphore == vk::TRUE,
+            sampler_anisotropy: features.sampler_anisotropy == vk::TRUE,
+            multi_draw_indirect: features.multi_draw_indirect == vk::TRUE,
+            draw_indirect_first_instance: features.draw_indirect_first_instance == vk::TRUE,
+            descriptor_indexing: if descriptor_indexing_extension {
+                DescriptorIndexingSupport::query(instance, physical_device)
+            } else {
+                Default::default()
+            },
+            ray_query,
+            queue_families,
+            descriptor_indexing_extension,
+        };
+        println!("[HOTHAM_VULKAN] Device capabilities: {:?}", capabilities);
+
+        let missing = capabilities.missing_features();
+        if !missing.is_empty() {
+            return Err(HothamError::UnsupportedDevice {
+                missing: missing.iter().map(|m| m.to_string()).collect(),
+            }
+            .into());
+        }
+
+        Ok(capabilities)
+    }
+
+    /// The features Hotham can't run without that this device doesn't have.
+    pub fn missing_features(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.api_version < vk::API_VERSION_1_1 {
+            missing.push("Vulkan 1.1");
+        }
+        // Both eyes are drawn in a single multiview pass, so the device has to be able to draw every view at once.
+        if !self.multiview || self.max_multiview_view_count < VIEW_COUNT {
+            missing.push("multiview");
+        }
+        if !self.timeline_semaphore {
+            missing.push("timeline semaphores");
+        }
+        missing
+    }
+
+    /// Does the device have a separate queue for compute work?
+    pub fn async_compute(&self) -> bool {
+        self.queue_families.has_async_compute()
+    }
+
+    /// The device extensions needed for the features that aren't part of Vulkan 1.1.
+    fn extension_names(&self) -> Vec<&'static CStr> {
+        let mut extension_names = vec![TimelineSemaphore::name()];
+        if self.descriptor_indexing_extension {
+            extension_names.push(vk::ExtDescriptorIndexingFn::name());
+        }
+        if self.ray_query {
+            extension_names.push(AccelerationStructure::name());
+            extension_names.push(DeferredHostOperations::name());
+            extension_names.push(vk::KhrRayQueryFn::name());
+        }
+        extension_names
+    }
+
+    /// The features to turn on when creating the device: everything Hotham uses that the device has.
+    fn features(&self) -> DeviceFeatures {
+        DeviceFeatures {
+            features: vk::PhysicalDeviceFeatures::builder()
+                .multi_draw_indirect(self.multi_draw_indirect)
+                .draw_indirect_first_instance(self.draw_indirect_first_instance)
+                .sampler_anisotropy(self.sampler_anisotropy)
+                .build(),
+            multiview: vk::PhysicalDeviceMultiviewFeatures::builder()
+                .multiview(self.multiview)
+                .build(),
+            draw_parameters: vk::PhysicalDeviceShaderDrawParametersFeatures::builder()
+                .shader_draw_parameters(self.shader_draw_parameters)
+                .build(),
+            descriptor_indexing: self
+                .descriptor_indexing_extension
+                .then(|| self.descriptor_indexing.features()),
+            timeline_semaphore: vk::PhysicalDeviceTimelineSemaphoreFeatures::builder()
+                .timeline_semaphore(self.timeline_semaphore)
+                .build(),
+            ray_query: self.ray_query.then(|| {
+                (
+                    vk::PhysicalDeviceAccelerationStructureFeaturesKHR::builder()
+                        .acceleration_structure(true)
+                        .build(),
+                    vk::PhysicalDeviceRayQueryFeaturesKHR::builder()
+                        .ray_query(true)
+                        .build(),
+                    vk::PhysicalDeviceBufferDeviceAddressFeatures::builder()
+                        .buffer_device_address(true)
+                        .build(),
+                )
+            }),
+        }
+    }
+}
+
+/// The feature structs to chain onto a [`vk::DeviceCreateInfo`]. They're kept together so they live as long as it does.
+struct DeviceFeatures {
+    features: vk::PhysicalDeviceFeatures,
+    multiview: vk::PhysicalDeviceMultiviewFeatures,
+    draw_parameters: vk::PhysicalDeviceShaderDrawParametersFeatures,
+    descriptor_indexing: Option<vk::PhysicalDeviceDescriptorIndexingFeatures>,
+    timeline_semaphore: vk::PhysicalDeviceTimelineSemaphoreFeatures,
+    ray_query: Option<(
+        vk::PhysicalDeviceAccelerationStructureFeaturesKHR,
+        vk::PhysicalDeviceRayQueryFeaturesKHR,
+        vk::PhysicalDeviceBufferDeviceAddressFeatures,
+    )>,
+}
+
+impl DeviceFeatures {
+    fn enable<'a>(
+        &'a mut self,
+        create_info: vk::DeviceCreateInfoBuilder<'a>,
+    ) -> vk::DeviceCreateInfoBuilder<'a> {
+        let mut create_info = create_info
+            .enabled_features(&self.features)
+            .push_next(&mut self.multiview)
+            .push_next(&mut self.draw_parameters)
+            .push_next(&mut self.timeline_semaphore);
+        if let Some(descriptor_indexing) = &mut self.descriptor_indexing {
+            create_info = create_info.push_next(descriptor_indexing);
+        }
+        if let Some((acceleration_structure, ray_query, buffer_device_address)) =
+            &mut self.ray_query
+        {
+            create_info = create_info
+                .push_next(acceleration_structure)
+                .push_next(ray_query)
+                .push_next(buffer_device_address);
+        }
+        create_info
+    }
+}
+
+/// Does the device have everything needed to build acceleration structures and trace rays against them from shaders?
+#[cfg(feature = "ray-query")]
+fn supports_ray_query(
+    instance: &AshInstance,
+    physical_device: vk::PhysicalDevice,
+    extensions: &[vk::ExtensionProperties],
+) -> bool {
+    let has_extension = |wanted: &CStr| {
+        extensions
+            .iter()
+            .any(|e| unsafe { CStr::from_ptr(e.extension_name.as_ptr()) } == wanted)
+    };
+    if !(has_extension(AccelerationStructure::name())
+        && has_extension(DeferredHostOperations::name())
+        && has_extension(vk::KhrRayQueryFn::name()))
+    {
+        return false;
+    }
+
+    let mut acceleration_structure = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+    let mut ray_query = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
+    let mut buffer_device_address = vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+    unsafe {
+        instance.get_physical_device_features2(
+            physical_device,
+            &mut vk::PhysicalDeviceFeatures2::builder()
+                .push_next(&mut acceleration_structure)
+                .push_next(&mut ray_query)
+                .push_next(&mut buffer_device_address),
+        );
+    }
+
+    acceleration_structure.acceleration_structure == vk::TRUE
+        && ray_query.ray_query == vk::TRUE
+        && buffer_device_address.buffer_device_address == vk::TRUE
+}
+
+/// Ray queries are only used with the `ray-query` feature.
+#[cfg(not(feature = "ray-query"))]
+fn supports_ray_query(
+    _instance: &AshInstance,
+    _physical_device: vk::PhysicalDevice,
+    _extensions: &[vk::ExtensionProperties],
+) -> bool {
+    false
+}
+
+/// The parts of descriptor indexing the device supports, which decide how the texture array is laid out.
+///
+/// Most devices support everything needed for "bindless" textures: one large array of textures that only needs to be
+/// partly filled, and can be written to while frames that use it are in flight. Those that don't get a smaller array
+/// that's kept completely filled instead. See [`crate::rendering::descriptors::Descriptors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DescriptorIndexingSupport {
+    /// Can textures in the array be left unwritten?
+    pub partially_bound: bool,
+    /// Can the size of the texture array be chosen when its descriptor set is allocated?
+    pub variable_descriptor_count: bool,
+    /// Can textures be written to the array while frames that use it are in flight?
+    pub update_after_bind: bool,
+    /// Can shaders declare texture arrays without a size?
+    pub runtime_descriptor_array: bool,
+    /// Can shaders index texture arrays with values that differ across a draw, using `nonuniformEXT`?
+    pub non_uniform_indexing: bool,
+    /// The most textures a single shader stage can use
+    pub max_textures: u32,
+}
+
+impl DescriptorIndexingSupport {
+    /// Ask the device what it supports.
+    pub fn query(instance: &AshInstance, physical_device: vk::PhysicalDevice) -> Self {
+        let mut features = vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+        let mut properties = vk::PhysicalDeviceDescriptorIndexingProperties::default();
+        let limits = unsafe {
+            instance.get_physical_device_features2(
+                physical_device,
+                &mut vk::PhysicalDeviceFeatures2::builder().push_next(&mut features),
+            );
+            let mut properties2 =
+                vk::PhysicalDeviceProperties2::builder().push_next(&mut properties);
+            instance.get_physical_device_properties2(physical_device, &mut properties2);
+            properties2.properties.limits
+        };
+
+        Self::new(&features, &properties, &limits)
+    }
+
+    fn new(
+        features: &vk::PhysicalDeviceDescriptorIndexingFeatures,
+        properties: &vk::PhysicalDeviceDescriptorIndexingProperties,
+        limits: &vk::PhysicalDeviceLimits,
+    ) -> Self {
+        let update_after_bind =
+            features.descriptor_binding_sampled_image_update_after_bind == vk::TRUE;
+
+        // Arrays that can be updated after they're bound have their own, usually much higher, limits.
+        let max_textures = if update_after_bind {
+            properties
+                .max_per_stage_descriptor_update_after_bind_samplers
+                .min(properties.max_descriptor_set_update_after_bind_sampled_images)
+        } else {
+            limits
+                .max_per_stage_descriptor_samplers
+                .min(limits.max_descriptor_set_sampled_images)
+        };
+
+        Self {
+            partially_bound: features.descriptor_binding_partially_bound == vk::TRUE,
+            variable_descriptor_count: features.descriptor_binding_variable_descriptor_count
+                == vk::TRUE,
+            update_after_bind,
+            runtime_descriptor_array: features.runtime_descriptor_array == vk::TRUE,
+            non_uniform_indexing: features.shader_sampled_image_array_non_uniform_indexing
+                == vk::TRUE,
+            max_textures,
+        }
+    }
+
+    /// Does the device support everything needed for a bindless texture array?
+    ///
+    /// `non_uniform_indexing` isn't needed: a draw only ever uses one material, so its texture IDs are the same across
+    /// the whole draw.
+    pub fn is_bindless(&self) -> bool {
+        self.partially_bound && self.variable_descriptor_count && self.update_after_bind
+    }
+
+    /// The features to enable on the device - only the ones it has.
+    fn features(&self) -> vk::PhysicalDeviceDescriptorIndexingFeatures {
+        vk::PhysicalDeviceDescriptorIndexingFeatures::builder()
+            .shader_sampled_image_array_non_uniform_indexing(self.non_uniform_indexing)
+            .descriptor_binding_partially_bound(self.partially_bound)
+            .descriptor_binding_variable_descriptor_count(self.variable_descriptor_count)
+            .descriptor_binding_sampled_image_update_after_bind(self.update_after_bind)
+            .runtime_descriptor_array(self.runtime_descriptor_array)
+            .build()
+    }
+}
+
 // TODO HACK: Make these values real
 #[deprecated]
 fn create_descriptor_pool(device: &Device) -> Result<vk::DescriptorPool, anyhow::Error> {
@@ -917,7 +1392,7 @@ fn vulkan_init_legacy(
         let mut vk_instance_exts = xr_instance
             .vulkan_legacy_instance_extensions(system)
             .unwrap()
-            .split(' ')
+            .split_whitespace()
             .map(|x| CString::new(x).unwrap())
             .collect::<Vec<_>>();
 
@@ -969,7 +1444,18 @@ fn vulkan_init_test() -> Result<(AshInstance, Entry)> {
     println!("[HOTHAM_VULKAN] Initializing Vulkan..");
     let app_name = CString::new("Hotham Testing")?;
     let entry = unsafe { Entry::new()? };
-    let layers = vec!["VK_LAYER_KHRONOS_validation\0"];
+
+    // Validate if we can, but don't make installing the validation layers a requirement for running the tests.
+    let validation_layer = CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0")?;
+    let has_validation = entry
+        .enumerate_instance_layer_properties()?
+        .iter()
+        .any(|l| unsafe { CStr::from_ptr(l.layer_name.as_ptr()) } == validation_layer);
+    let layers = if has_validation {
+        vec!["VK_LAYER_KHRONOS_validation\0"]
+    } else {
+        vec![]
+    };
     let layer_names = unsafe { get_raw_strings(layers) };
     println!("[HOTHAM_VULKAN] Trying to use layers: {:?}", unsafe {
         parse_raw_strings(&layer_names)
@@ -998,24 +1484,32 @@ pub fn create_vulkan_device_legacy(
     system: xr::SystemId,
     vulkan_instance: &AshInstance,
     physical_device: vk::PhysicalDevice,
-) -> Result<(Device, vk::Queue, u32)> {
+    capabilities: &DeviceCapabilities,
+) -> Result<Device> {
     println!("[HOTHAM_VULKAN] Creating logical device.. ");
 
     let extension_names = xr_instance.vulkan_legacy_device_extensions(system)?;
+    // Some runtimes (eg. Monado) return an empty list, which isn't an empty extension name.
     let mut extension_names = extension_names
-        .split(' ')
+        .split_whitespace()
         .map(|x| CString::new(x).unwrap())
         .collect::<Vec<_>>();
 
-    add_device_extension_names(&mut extension_names);
-    create_vulkan_device(&extension_names, vulkan_instance, physical_device)
+    add_device_extension_names(&mut extension_names, capabilities);
+    create_vulkan_device(
+        &extension_names,
+        vulkan_instance,
+        physical_device,
+        capabilities,
+    )
 }
 
 fn create_vulkan_device(
     extension_names: &[std::ffi::CString],
     vulkan_instance: &AshInstance,
     physical_device: vk::PhysicalDevice,
-) -> Result<(Device, vk::Queue, u32)> {
+    capabilities: &DeviceCapabilities,
+) -> Result<Device> {
     println!(
         "[HOTHAM_VULKAN] Using device extensions: {:?}",
         extension_names
@@ -1027,62 +1521,24 @@ fn create_vulkan_device(
         .collect::<Vec<_>>();
 
     let queue_priorities = [1.0];
-    let graphics_family_index = unsafe {
-        vulkan_instance
-            .get_physical_device_queue_family_properties(physical_device)
-            .into_iter()
-            .enumerate()
-            .find_map(|(queue_family_index, info)| {
-                if info.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                    Some(queue_family_index as u32)
-                } else {
-                    None
-                }
-            })
-            .ok_or(HothamError::EmptyListError)?
-    };
-
-    let queue_create_info = vk::DeviceQueueCreateInfo::builder()
-        .queue_priorities(&queue_priorities)
-        .queue_family_index(graphics_family_index)
-        .build();
+    let queue_create_infos = capabilities
+        .queue_families
+        .queue_create_infos(&queue_priorities);
 
     // We use a *whole bunch* of different features, and somewhat annoyingly they're all enabled in different ways.
-    let enabled_features = vk::PhysicalDeviceFeatures::builder()
-        .multi_draw_indirect(true)
-        .sampler_anisotropy(true)
-        .build();
-
-    let mut physical_device_features = vk::PhysicalDeviceVulkan11Features::builder()
-        .multiview(true)
-        .shader_draw_parameters(true);
-
-    let mut descriptor_indexing_features = vk::PhysicalDeviceDescriptorIndexingFeatures::builder()
-        .shader_sampled_image_array_non_uniform_indexing(true)
-        .descriptor_binding_partially_bound(true)
-        .descriptor_binding_variable_descriptor_count(true)
-        .descriptor_binding_sampled_image_update_after_bind(true)
-        .runtime_descriptor_array(true);
-
-    let mut robust_features =
-        vk::PhysicalDeviceRobustness2FeaturesEXT::builder().null_descriptor(true);
-
-    let device_create_info = vk::DeviceCreateInfo::builder()
-        .queue_create_infos(slice_from_ref(&queue_create_info))
-        .enabled_extension_names(&extension_names)
-        .enabled_features(&enabled_features)
-        .push_next(&mut descriptor_indexing_features)
-        .push_next(&mut robust_features)
-        .push_next(&mut physical_device_features);
+    let mut features = capabilities.features();
+    let device_create_info = features.enable(
+        vk::DeviceCreateInfo::builder()
+            .queue_create_infos(&queue_create_infos)
+            .enabled_extension_names(&extension_names),
+    );
 
     let device =
         unsafe { vulkan_instance.create_device(physical_device, &device_create_info, None) }?;
 
-    let graphics_queue = unsafe { device.get_device_queue(graphics_family_index, 0) };
-
     println!("[HOTHAM_VULKAN] ..done");
 
-    Ok((device, graphics_queue, graphics_family_index))
+    Ok(device)
 }
 
 fn get_stage(
@@ -1112,6 +1568,15 @@ fn get_stage(
             vk::PipelineStageFlags::TRANSFER,
             vk::PipelineStageFlags::FRAGMENT_SHADER,
         );
+    } else if old_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
+        && new_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+    {
+        return (
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::COLOR_ATTACHMENT_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        );
     } else if old_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
         && new_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL
     {
@@ -1126,6 +1591,122 @@ fn get_stage(
     panic!("Invalid layout transition!");
 }
 
+/// Which GPU to render with, on machines with more than one.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PhysicalDeviceSelection {
+    /// Whichever GPU the OpenXR runtime asks for
+    #[default]
+    Runtime,
+    /// The first GPU whose name contains this, ignoring case - eg. `"nvidia"`
+    Name(String),
+    /// The GPU at this index in the list Vulkan gives, as logged when the engine starts
+    Index(usize),
+    /// The first discrete (ie. not integrated) GPU
+    Discrete,
+}
+
+/// Which GPU to render with, and what to do if it's not the one the OpenXR runtime asks for.
+///
+/// Runtimes can only show what's been rendered on the GPU the headset is connected to, so by default choosing a
+/// different GPU to the runtime is an error that names the GPU it needs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PhysicalDeviceSettings {
+    /// The GPU the app wants to render with
+    pub selection: PhysicalDeviceSelection,
+    /// Use the selected GPU even if the runtime asks for a different one. Only some runtimes (eg. the simulator) can
+    /// show frames rendered on another GPU.
+    pub override_runtime: bool,
+}
+
+/// Pick the GPU to render with, checking it against `runtime_device`, the one the runtime asked for.
+unsafe fn pick_physical_device(
+    instance: &AshInstance,
+    runtime_device: vk::PhysicalDevice,
+    settings: &PhysicalDeviceSettings,
+) -> Result<vk::PhysicalDevice> {
+    let devices = instance.enumerate_physical_devices()?;
+    let device_info = devices
+        .iter()
+        .map(|device| {
+            let properties = instance.get_physical_device_properties(*device);
+            let name = CStr::from_ptr(properties.device_name.as_ptr());
+            (name.to_string_lossy().into_owned(), properties.device_type)
+        })
+        .collect::<Vec<_>>();
+    for (index, (name, device_type)) in device_info.iter().enumerate() {
+        let used_by_runtime = if devices[index] == runtime_device {
+            " - used by the OpenXR runtime"
+        } else {
+            ""
+        };
+        println!(
+            "[HOTHAM_VULKAN] GPU {}: {} ({:?}){}",
+            index, name, device_type, used_by_runtime
+        );
+    }
+
+    // Some runtimes hand out a device that isn't in the list, and there's nothing to check it against.
+    let runtime_index = match devices.iter().position(|d| *d == runtime_device) {
+        Some(index) => index,
+        None => return Ok(runtime_device),
+    };
+    let index = select_physical_device(settings, &device_info, runtime_index)?;
+    Ok(devices[index])
+}
+
+/// Find the GPU `settings` asks for in `devices`, by name and type, and check the runtime can use it.
+pub(crate) fn select_physical_device(
+    settings: &PhysicalDeviceSettings,
+    devices: &[(String, vk::PhysicalDeviceType)],
+    runtime_index: usize,
+) -> std::result::Result<usize, HothamError> {
+    let selected = match &settings.selection {
+        PhysicalDeviceSelection::Runtime => return Ok(runtime_index),
+        PhysicalDeviceSelection::Name(name) => {
+            let name = name.to_lowercase();
+            devices
+                .iter()
+                .position(|(n, _)| n.to_lowercase().contains(&name))
+        }
+        PhysicalDeviceSelection::Index(index) => Some(*index).filter(|i| *i < devices.len()),
+        PhysicalDeviceSelection::Discrete => devices
+            .iter()
+            .position(|(_, t)| *t == vk::PhysicalDeviceType::DISCRETE_GPU),
+    };
+
+    let selected = selected.ok_or_else(|| HothamError::PhysicalDeviceUnavailable {
+        requested: format!("{:?}", settings.selection),
+        reason: format!(
+            "there's no such GPU. The GPUs are: {}",
+            devices
+                .iter()
+                .map(|(n, _)| n.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    })?;
+
+    if selected == runtime_index {
+        return Ok(selected);
+    }
+    let runtime_name = &devices[runtime_index].0;
+    if settings.override_runtime {
+        println!(
+            "[HOTHAM_VULKAN] WARNING: Rendering on {} even though the OpenXR runtime uses {}",
+            devices[selected].0, runtime_name
+        );
+        return Ok(selected);
+    }
+    Err(HothamError::PhysicalDeviceUnavailable {
+        requested: devices[selected].0.clone(),
+        reason: format!(
+            "the OpenXR runtime renders on {}. Connect the headset to the GPU you want, select {} instead, or set \
+            `override_runtime` if the runtime can show frames from another GPU",
+            runtime_name, runtime_name
+        ),
+    })
+}
+
 pub fn get_test_physical_device(instance: &AshInstance) -> vk::PhysicalDevice {
     unsafe {
         println!("[HOTHAM_VULKAN] Getting physical device..");
@@ -1133,3 +1714,147 @@ pub fn get_test_physical_device(instance: &AshInstance) -> vk::PhysicalDevice {
         devices[0]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_choose_queue_families() {
+        let family = |queue_flags| vk::QueueFamilyProperties {
+            queue_flags,
+            queue_count: 1,
+            ..Default::default()
+        };
+        let graphics = vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE;
+
+        // A dedicated compute family should be used for compute..
+        let families = [
+            family(vk::QueueFlags::TRANSFER),
+            family(graphics),
+            family(vk::QueueFlags::COMPUTE),
+        ];
+        let chosen = QueueFamilies::choose(&families).unwrap();
+        assert_eq!(
+            chosen,
+            QueueFamilies {
+                graphics: 1,
+                compute: 2
+            }
+        );
+        assert!(chosen.has_async_compute());
+        assert_eq!(chosen.queue_create_infos(&[1.0]).len(), 2);
+
+        // ..otherwise compute shares the graphics queue.
+        let chosen = QueueFamilies::choose(&families[..2]).unwrap();
+        assert_eq!(
+            chosen,
+            QueueFamilies {
+                graphics: 1,
+                compute: 1
+            }
+        );
+        assert!(!chosen.has_async_compute());
+        assert_eq!(chosen.queue_create_infos(&[1.0]).len(), 1);
+
+        assert!(QueueFamilies::choose(&[family(vk::QueueFlags::COMPUTE)]).is_none());
+    }
+
+    #[test]
+    pub fn test_missing_features() {
+        let capabilities = DeviceCapabilities {
+            api_version: vk::API_VERSION_1_1,
+            multiview: true,
+            max_multiview_view_count: 6,
+            shader_draw_parameters: true,
+            timeline_semaphore: true,
+            sampler_anisotropy: true,
+            multi_draw_indirect: true,
+            draw_indirect_first_instance: false,
+            descriptor_indexing: Default::default(),
+            ray_query: false,
+            queue_families: QueueFamilies {
+                graphics: 0,
+                compute: 0,
+            },
+            descriptor_indexing_extension: false,
+        };
+        // Descriptor indexing, async compute and indirect first instance have fallbacks, so they're not missing..
+        assert!(capabilities.missing_features().is_empty());
+        assert!(!capabilities.async_compute());
+        assert!(capabilities.features().descriptor_indexing.is_none());
+        assert!(capabilities.features().ray_query.is_none());
+
+        // Indirect draws starting past the first instance, which crowds and instanced meshes make, are only turned on
+        // where the device has them. Elsewhere those draws are made directly.
+        let features = |capabilities: &DeviceCapabilities| capabilities.features().features;
+        assert_eq!(
+            features(&capabilities).draw_indirect_first_instance,
+            vk::FALSE
+        );
+        let with_first_instance = DeviceCapabilities {
+            draw_indirect_first_instance: true,
+            ..capabilities
+        };
+        assert_eq!(
+            features(&with_first_instance).draw_indirect_first_instance,
+            vk::TRUE
+        );
+
+        // ..but Vulkan 1.1, multiview and timeline semaphores don't.
+        let capabilities = DeviceCapabilities {
+            api_version: vk::API_VERSION_1_0,
+            max_multiview_view_count: 1,
+            timeline_semaphore: false,
+            ..capabilities
+        };
+        assert_eq!(
+            capabilities.missing_features(),
+            vec!["Vulkan 1.1", "multiview", "timeline semaphores"]
+        );
+    }
+
+    #[test]
+    pub fn test_select_physical_device() {
+        let devices = [
+            (
+                "Intel(R) UHD Graphics".to_string(),
+                vk::PhysicalDeviceType::INTEGRATED_GPU,
+            ),
+            (
+                "NVIDIA GeForce RTX 3070".to_string(),
+                vk::PhysicalDeviceType::DISCRETE_GPU,
+            ),
+        ];
+        let select = |selection, override_runtime, runtime_index| {
+            let settings = PhysicalDeviceSettings {
+                selection,
+                override_runtime,
+            };
+            select_physical_device(&settings, &devices, runtime_index)
+        };
+
+        // The runtime's GPU is used by default..
+        assert_eq!(
+            select(PhysicalDeviceSelection::Runtime, false, 0).unwrap(),
+            0
+        );
+
+        // ..and asking for it is fine..
+        let nvidia = PhysicalDeviceSelection::Name("nvidia".to_string());
+        assert_eq!(select(nvidia.clone(), false, 1).unwrap(), 1);
+        assert_eq!(
+            select(PhysicalDeviceSelection::Discrete, false, 1).unwrap(),
+            1
+        );
+
+        // ..but asking for another is an error that names the runtime's GPU, unless it's overridden.
+        let error = select(nvidia.clone(), false, 0).unwrap_err().to_string();
+        assert!(error.contains("Intel(R) UHD Graphics"), "{}", error);
+        assert_eq!(select(nvidia, true, 0).unwrap(), 1);
+
+        // GPUs that don't exist are always an error.
+        assert!(select(PhysicalDeviceSelection::Index(2), true, 0).is_err());
+        assert!(select(PhysicalDeviceSelection::Name("amd".to_string()), true, 0).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use glam::Vec2;
+use openxr::{self as xr, Action, ActionSet};
+
+/// The type of value an app-defined action has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// On or off, eg. a button
+    Bool,
+    /// From 0 to 1, or -1 to 1, eg. a trigger
+    Float,
+    /// Two axes, eg. a thumbstick
+    Vector2,
+}
+
+/// One action in an [`ActionSetSettings`], and the controls it's bound to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionSettings {
+    /// The action's name, unique within its action set. Lower case letters, numbers, `-`, `_` and `.` only.
+    pub name: String,
+    /// The type of value the action has
+    pub kind: ActionKind,
+    /// `(interaction profile, input path)` pairs, eg.
+    /// `("/interaction_profiles/oculus/touch_controller", "/user/hand/right/input/a/click")`. Only the controllers Hotham
+    /// has bindings for can be used.
+    pub bindings: Vec<(String, String)>,
+}
+
+/// An input context defined by the app: an OpenXR action set with a priority, eg. one for gameplay and a higher
+/// priority one for menus.
+///
+/// Action sets have to be created before the session starts, so they're passed to
+/// [`crate::EngineBuilder::action_set`]. While the app runs, input contexts are pushed and popped with
+/// [`super::XrContext::push_input_context`] and [`super::XrContext::pop_input_context`]. Only the highest priority
+/// contexts on the stack are active - the rest are suppressed, and their actions read as idle.
+///
+/// Hotham's own actions, which drive [`crate::contexts::InputContext`], are in a separate action set that's always
+/// active, so the hands keep tracking whichever input context is on top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionSetSettings {
+    /// The action set's name, unique within the app. Lower case letters, numbers, `-`, `_` and `.` only.
+    pub name: String,
+    /// When contexts with different priorities are pushed, only the highest priority ones are active.
+    pub priority: u32,
+    /// The actions in the set
+    pub actions: Vec<ActionSettings>,
+}
+
+impl ActionSetSettings {
+    /// An action set called `name`, with no actions yet
+    pub fn new(name: &str, priority: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            priority,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Add an action called `name`, bound to `bindings` - see [`ActionSettings::bindings`].
+    pub fn action(mut self, name: &str, kind: ActionKind, bindings: &[(&str, &str)]) -> Self {
+        self.actions.push(ActionSettings {
+            name: name.to_string(),
+            kind,
+            bindings: bindings
+                .iter()
+                .map(|(profile, path)| (profile.to_string(), path.to_string()))
+                .collect(),
+        });
+        self
+    }
+}
+
+/// A handle to one of the app's input contexts. Find it by name with [`ActionSets::find`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputContextId(usize);
+
+enum AnyAction {
+    Bool(Action<bool>),
+    Float(Action<f32>),
+    Vector2(Action<xr::Vector2f>),
+}
+
+struct AppActionSet {
+    name: String,
+    action_set: ActionSet,
+    actions: HashMap<String, (AnyAction, Vec<(String, String)>)>,
+}
+
+/// The app's own action sets, and the stack of input contexts that decides which of them are active.
+pub struct ActionSets {
+    sets: Vec<AppActionSet>,
+    stack: InputContextStack,
+}
+
+impl ActionSets {
+    pub(crate) fn new(instance: &xr::Instance, settings: &[ActionSetSettings]) -> Result<Self> {
+        let mut sets = Vec::with_capacity(settings.len());
+        for set_settings in settings {
+            // OpenXR uses the priority too: when active sets bind the same input, only the highest priority one gets it.
+            let action_set = instance.create_action_set(
+                &set_settings.name,
+                &set_settings.name,
+                set_settings.priority,
+            )?;
+            let mut actions = HashMap::new();
+            for action in &set_settings.actions {
+                let name = &action.name;
+                let created = match action.kind {
+                    ActionKind::Bool => {
+                        AnyAction::Bool(action_set.create_action(name, name, &[])?)
+                    }
+                    ActionKind::Float => {
+                        AnyAction::Float(action_set.create_action(name, name, &[])?)
+                    }
+                    ActionKind::Vector2 => {
+                        AnyAction::Vector2(action_set.create_action(name, name, &[])?)
+                    }
+                };
+                actions.insert(name.clone(), (created, action.bindings.clone()));
+            }
+            sets.push(AppActionSet {
+                name: set_settings.name.clone(),
+                action_set,
+                actions,
+            });
+        }
+
+        Ok(Self {
+            sets,
+            stack: InputContextStack::new(settings.iter().map(|s| s.priority).collect()),
+        })
+    }
+
+    /// The input context called `name`, if there is one
+    pub fn find(&self, name: &str) -> Option<InputContextId> {
+        self.sets
+            .iter()
+            .position(|s| s.name == name)
+            .map(InputContextId)
+    }
+
+    /// Is `context` on the stack, and not suppressed by a higher priority one?
+    pub fn is_active(&self, context: InputContextId) -> bool {
+        self.stack.active().contains(&context)
+    }
+
+    /// Is the action called `name` in `context` pressed? `false` if the context isn't active, or there's no such action.
+    pub fn bool_state(
+        &self,
+        session: &xr::Session<xr::Vulkan>,
+        context: InputContextId,
+        name: &str,
+    ) -> bool {
+        match self.action(context, name) {
+            Some(AnyAction::Bool(action)) => action
+                .state(session, xr::Path::NULL)
+                .map_or(false, |s| s.is_active && s.current_state),
+            _ => false,
+        }
+    }
+
+    /// The value of the action called `name` in `context`. `0` if the context isn't active, or there's no such action.
+    pub fn float_state(
+        &self,
+        session: &xr::Session<xr::Vulkan>,
+        context: InputContextId,
+        name: &str,
+    ) -> f32 {
+        match self.action(context, name) {
+            Some(AnyAction::Float(action)) => action
+                .state(session, xr::Path::NULL)
+                .map_or(0., |s| if s.is_active { s.current_state } else { 0. }),
+            _ => 0.,
+        }
+    }
+
+    /// The value of the action called `name` in `context`. Zero if the context isn't active, or there's no such action.
+    pub fn vector2_state(
+        &self,
+        session: &xr::Session<xr::Vulkan>,
+        context: InputContextId,
+        name: &str,
+    ) -> Vec2 {
+        match self.action(context, name) {
+            Some(AnyAction::Vector2(action)) => action
+                .state(session, xr::Path::NULL)
+                .ok()
+                .filter(|s| s.is_active)
+                .map_or(Vec2::ZERO, |s| {
+                    Vec2::new(s.current_state.x, s.current_state.y)
+                }),
+            _ => Vec2::ZERO,
+        }
+    }
+
+    /// Push `context` onto the stack, suppressing any lower priority contexts.
+    pub fn push(&mut self, context: InputContextId) {
+        self.stack.push(context);
+    }
+
+    /// Pop the most recently pushed context off the stack.
+    pub fn pop(&mut self) -> Option<InputContextId> {
+        self.stack.pop()
+    }
+
+    fn action(&self, context: InputContextId, name: &str) -> Option<&AnyAction> {
+        self.sets
+            .get(context.0)?
+            .actions
+            .get(name)
+            .map(|(action, _)| action)
+    }
+
+    /// Every action set, for attaching to the session.
+    pub(crate) fn action_sets(&self) -> impl Iterator<Item = &ActionSet> {
+        self.sets.iter().map(|s| &s.action_set)
+    }
+
+    /// The action sets to sync this frame.
+    pub(crate) fn active_action_sets(&self) -> impl Iterator<Item = xr::ActiveActionSet<'_>> {
+        self.stack
+            .active()
+            .into_iter()
+            .map(|context| xr::ActiveActionSet::new(&self.sets[context.0].action_set))
+    }
+
+    /// The app's bindings for the interaction profile at `profile`.
+    pub(crate) fn bindings(
+        &self,
+        instance: &xr::Instance,
+        profile: &str,
+    ) -> Result<Vec<xr::Binding<'_>>> {
+        let mut bindings = Vec::new();
+        for set in &self.sets {
+            for (action, action_bindings) in set.actions.values() {
+                for (_, path) in action_bindings.iter().filter(|(p, _)| p == profile) {
+                    let path = instance.string_to_path(path)?;
+                    bindings.push(match action {
+                        AnyAction::Bool(action) => xr::Binding::new(action, path),
+                        AnyAction::Float(action) => xr::Binding::new(action, path),
+                        AnyAction::Vector2(action) => xr::Binding::new(action, path),
+                    });
+                }
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+/// The input contexts that have been pushed, and their priorities.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct InputContextStack {
+    priorities: Vec<u32>,
+    stack: Vec<InputContextId>,
+}
+
+impl InputContextStack {
+    fn new(priorities: Vec<u32>) -> Self {
+        Self {
+            priorities,
+            stack: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, context: InputContextId) {
+        if context.0 < self.priorities.len() {
+            self.stack.push(context);
+        }
+    }
+
+    fn pop(&mut self) -> Option<InputContextId> {
+        self.stack.pop()
+    }
+
+    /// The contexts on the stack with the highest priority, each once.
+    fn active(&self) -> Vec<InputContextId> {
+        let highest = match self.stack.iter().map(|c| self.priorities[c.0]).max() {
+            Some(highest) => highest,
+            None => return Vec::new(),
+        };
+        let mut active = Vec::new();
+        for context in &self.stack {
+            if self.priorities[context.0] == highest && !active.contains(context) {
+                active.push(*context);
+            }
+        }
+        active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_input_context_stack() {
+        let gameplay = InputContextId(0);
+        let menu = InputContextId(1);
+        let chat = InputContextId(2);
+        let mut stack = InputContextStack::new(vec![0, 10, 10]);
+        assert!(stack.active().is_empty());
+
+        stack.push(gameplay);
+        assert_eq!(stack.active(), vec![gameplay]);
+
+        // A higher priority context suppresses gameplay..
+        stack.push(menu);
+        assert_eq!(stack.active(), vec![menu]);
+
+        // ..but not one with the same priority.
+        stack.push(chat);
+        stack.push(menu);
+        assert_eq!(stack.active(), vec![menu, chat]);
+
+        assert_eq!(stack.pop(), Some(menu));
+        assert_eq!(stack.pop(), Some(chat));
+        assert_eq!(stack.pop(), Some(menu));
+        assert_eq!(stack.active(), vec![gameplay]);
+
+        // Contexts that don't exist are ignored.
+        stack.push(InputContextId(3));
+        assert_eq!(stack.active(), vec![gameplay]);
+    }
+
+    #[test]
+    pub fn test_action_set_settings() {
+        let settings = ActionSetSettings::new("menu", 10).action(
+            "select",
+            ActionKind::Bool,
+            &[(
+                "/interaction_profiles/oculus/touch_controller",
+                "/user/hand/right/input/a/click",
+            )],
+        );
+        assert_eq!(settings.actions.len(), 1);
+        assert_eq!(settings.actions[0].kind, ActionKind::Bool);
+        assert_eq!(
+            settings.actions[0].bindings[0].1,
+            "/user/hand/right/input/a/click"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use openxr::{self as xr, sys, Swapchain, Vulkan};
+
+/// How much resolution is given up towards the edges of the view with fixed foveated rendering. Higher levels are
+/// faster, but the edges get blurrier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoveationLevel {
+    /// Render the whole view at full resolution
+    None,
+    /// Render the edges of the view at slightly lower resolution
+    Low,
+    /// Render the edges of the view at lower resolution
+    Medium,
+    /// Render everything but the middle of the view at lower resolution
+    High,
+}
+
+impl Default for FoveationLevel {
+    fn default() -> Self {
+        FoveationLevel::None
+    }
+}
+
+impl From<FoveationLevel> for xr::FoveationLevelFB {
+    fn from(level: FoveationLevel) -> Self {
+        match level {
+            FoveationLevel::None => xr::FoveationLevelFB::NONE,
+            FoveationLevel::Low => xr::FoveationLevelFB::LOW,
+            FoveationLevel::Medium => xr::FoveationLevelFB::MEDIUM,
+            FoveationLevel::High => xr::FoveationLevelFB::HIGH,
+        }
+    }
+}
+
+/// Apply `level` to `swapchain`, using `XR_FB_foveation`. `openxr` doesn't wrap the extension, so the profile is
+/// created and destroyed by hand - once the swapchain has been updated, it's no longer needed.
+pub(crate) fn set_foveation_level(
+    instance: &xr::Instance,
+    session: &xr::Session<Vulkan>,
+    swapchain: &Swapchain<Vulkan>,
+    level: FoveationLevel,
+) -> xr::Result<()> {
+    let extensions = instance.exts();
+    let (foveation, update_state) = extensions
+        .fb_foveation
+        .as_ref()
+        .zip(extensions.fb_swapchain_update_state.as_ref())
+        .ok_or(sys::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+
+    let level_info = sys::FoveationLevelProfileCreateInfoFB {
+        ty: sys::FoveationLevelProfileCreateInfoFB::TYPE,
+        next: std::ptr::null_mut(),
+        level: level.into(),
+        vertical_offset: 0.,
+        dynamic: xr::FoveationDynamicFB::DISABLED,
+    };
+    let profile_info = sys::FoveationProfileCreateInfoFB {
+        ty: sys::FoveationProfileCreateInfoFB::TYPE,
+        next: &level_info as *const _ as *mut _,
+    };
+    let mut profile = sys::FoveationProfileFB::NULL;
+    check(unsafe {
+        (foveation.create_foveation_profile)(session.as_raw(), &profile_info, &mut profile)
+    })?;
+
+    let state = sys::SwapchainStateFoveationFB {
+        ty: sys::SwapchainStateFoveationFB::TYPE,
+        next: std::ptr::null_mut(),
+        flags: xr::SwapchainStateFoveationFlagsFB::EMPTY,
+        profile,
+    };
+    let result = check(unsafe {
+        (update_state.update_swapchain)(
+            swapchain.as_raw(),
+            &state as *const sys::SwapchainStateFoveationFB
+                as *const sys::SwapchainStateBaseHeaderFB,
+        )
+    });
+    unsafe { (foveation.destroy_foveation_profile)(profile) };
+    result
+}
+
+fn check(result: sys::Result) -> xr::Result<()> {
+    if result.into_raw() < 0 {
+        Err(result)
+    } else {
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use openxr::{self as xr, Action, ActionSet, Haptic, Path, Posef, Space};
 
+use super::action_sets::ActionSets;
+
 pub struct Input {
     pub action_set: ActionSet,
     pub grip_pose_action: Action<Posef>,
@@ -32,114 +34,19 @@ pub struct Input {
 }
 
 impl Input {
-    pub fn oculus_touch_controller(
+    /// Create our actions, and bind them to every controller we know of that `enabled_extensions` makes available, along
+    /// with the actions in the app's `action_sets`.
+    pub fn new(
         instance: &xr::Instance,
         session: &xr::Session<xr::Vulkan>,
+        enabled_extensions: &xr::ExtensionSet,
+        action_sets: &ActionSets,
     ) -> Result<Self> {
         // Create an action set to encapsulate our actions
         let action_set = instance.create_action_set("input", "input pose information", 0)?;
 
         let left_hand_subaction_path = instance.string_to_path("/user/hand/left").unwrap();
         let right_hand_subaction_path = instance.string_to_path("/user/hand/right").unwrap();
-        let left_hand_grip_pose_path = instance
-            .string_to_path("/user/hand/left/input/grip/pose")
-            .unwrap();
-        let left_hand_aim_pose_path = instance
-            .string_to_path("/user/hand/left/input/aim/pose")
-            .unwrap();
-        let right_hand_grip_pose_path = instance
-            .string_to_path("/user/hand/right/input/grip/pose")
-            .unwrap();
-        let right_hand_aim_pose_path = instance
-            .string_to_path("/user/hand/right/input/aim/pose")
-            .unwrap();
-
-        let left_hand_squeeze_path = instance
-            .string_to_path("/user/hand/left/input/squeeze/value")
-            .unwrap();
-        let left_hand_trigger_path = instance
-            .string_to_path("/user/hand/left/input/trigger/value")
-            .unwrap();
-        let left_hand_trigger_touch_path = instance
-            .string_to_path("/user/hand/left/input/trigger/touch")
-            .unwrap();
-        let left_hand_haptic_feedback_path = instance
-            .string_to_path("/user/hand/left/output/haptic")
-            .unwrap();
-
-        let right_hand_squeeze_path = instance
-            .string_to_path("/user/hand/right/input/squeeze/value")
-            .unwrap();
-        let right_hand_trigger_path = instance
-            .string_to_path("/user/hand/right/input/trigger/value")
-            .unwrap();
-        let right_hand_trigger_touch_path = instance
-            .string_to_path("/user/hand/right/input/trigger/touch")
-            .unwrap();
-        let right_hand_haptic_feedback_path = instance
-            .string_to_path("/user/hand/right/output/haptic")
-            .unwrap();
-
-        let x_button_path = instance
-            .string_to_path("/user/hand/left/input/x/click")
-            .unwrap();
-        let x_button_touch_path = instance
-            .string_to_path("/user/hand/left/input/x/touch")
-            .unwrap();
-        let y_button_path = instance
-            .string_to_path("/user/hand/left/input/y/click")
-            .unwrap();
-        let y_button_touch_path = instance
-            .string_to_path("/user/hand/left/input/y/touch")
-            .unwrap();
-        let menu_button_path = instance
-            .string_to_path("/user/hand/left/input/menu/click")
-            .unwrap();
-
-        let a_button_path = instance
-            .string_to_path("/user/hand/right/input/a/click")
-            .unwrap();
-        let a_button_touch_path = instance
-            .string_to_path("/user/hand/right/input/a/touch")
-            .unwrap();
-        let b_button_path = instance
-            .string_to_path("/user/hand/right/input/b/click")
-            .unwrap();
-        let b_button_touch_path = instance
-            .string_to_path("/user/hand/right/input/b/touch")
-            .unwrap();
-
-        let left_hand_thumbstick_x_path = instance
-            .string_to_path("/user/hand/left/input/thumbstick/x")
-            .unwrap();
-        let left_hand_thumbstick_y_path = instance
-            .string_to_path("/user/hand/left/input/thumbstick/y")
-            .unwrap();
-        let left_hand_thumbstick_click_path = instance
-            .string_to_path("/user/hand/left/input/thumbstick/click")
-            .unwrap();
-        let left_hand_thumbstick_touch_path = instance
-            .string_to_path("/user/hand/left/input/thumbstick/touch")
-            .unwrap();
-        let left_hand_thumbrest_touch_path = instance
-            .string_to_path("/user/hand/left/input/thumbrest/touch")
-            .unwrap();
-
-        let right_hand_thumbstick_x_path = instance
-            .string_to_path("/user/hand/right/input/thumbstick/x")
-            .unwrap();
-        let right_hand_thumbstick_y_path = instance
-            .string_to_path("/user/hand/right/input/thumbstick/y")
-            .unwrap();
-        let right_hand_thumbstick_click_path = instance
-            .string_to_path("/user/hand/right/input/thumbstick/click")
-            .unwrap();
-        let right_hand_thumbstick_touch_path = instance
-            .string_to_path("/user/hand/right/input/thumbstick/touch")
-            .unwrap();
-        let right_hand_thumbrest_touch_path = instance
-            .string_to_path("/user/hand/right/input/thumbrest/touch")
-            .unwrap();
 
         let grip_pose_action = action_set.create_action::<xr::Posef>(
             "hand_pose",
@@ -219,46 +126,6 @@ impl Input {
             &[left_hand_subaction_path, right_hand_subaction_path],
         )?;
 
-        // Bind our actions to input devices using the given profile
-        instance.suggest_interaction_profile_bindings(
-            instance
-                .string_to_path("/interaction_profiles/oculus/touch_controller")
-                .unwrap(),
-            &[
-                xr::Binding::new(&grip_pose_action, left_hand_grip_pose_path),
-                xr::Binding::new(&grip_pose_action, right_hand_grip_pose_path),
-                xr::Binding::new(&aim_pose_action, left_hand_aim_pose_path),
-                xr::Binding::new(&aim_pose_action, right_hand_aim_pose_path),
-                xr::Binding::new(&squeeze_action, left_hand_squeeze_path),
-                xr::Binding::new(&squeeze_action, right_hand_squeeze_path),
-                xr::Binding::new(&trigger_action, left_hand_trigger_path),
-                xr::Binding::new(&trigger_action, right_hand_trigger_path),
-                xr::Binding::new(&trigger_touch_action, left_hand_trigger_touch_path),
-                xr::Binding::new(&trigger_touch_action, right_hand_trigger_touch_path),
-                xr::Binding::new(&haptic_feedback_action, left_hand_haptic_feedback_path),
-                xr::Binding::new(&haptic_feedback_action, right_hand_haptic_feedback_path),
-                xr::Binding::new(&x_button_action, x_button_path),
-                xr::Binding::new(&x_touch_action, x_button_touch_path),
-                xr::Binding::new(&y_button_action, y_button_path),
-                xr::Binding::new(&y_touch_action, y_button_touch_path),
-                xr::Binding::new(&menu_button_action, menu_button_path),
-                xr::Binding::new(&a_button_action, a_button_path),
-                xr::Binding::new(&a_touch_action, a_button_touch_path),
-                xr::Binding::new(&b_button_action, b_button_path),
-                xr::Binding::new(&b_touch_action, b_button_touch_path),
-                xr::Binding::new(&thumbstick_x_action, left_hand_thumbstick_x_path),
-                xr::Binding::new(&thumbstick_x_action, right_hand_thumbstick_x_path),
-                xr::Binding::new(&thumbstick_y_action, left_hand_thumbstick_y_path),
-                xr::Binding::new(&thumbstick_y_action, right_hand_thumbstick_y_path),
-                xr::Binding::new(&thumbstick_click_action, left_hand_thumbstick_click_path),
-                xr::Binding::new(&thumbstick_click_action, right_hand_thumbstick_click_path),
-                xr::Binding::new(&thumbstick_touch_action, left_hand_thumbstick_touch_path),
-                xr::Binding::new(&thumbstick_touch_action, right_hand_thumbstick_touch_path),
-                xr::Binding::new(&thumbrest_touch_action, left_hand_thumbrest_touch_path),
-                xr::Binding::new(&thumbrest_touch_action, right_hand_thumbrest_touch_path),
-            ],
-        )?;
-
         let left_hand_grip_space = grip_pose_action.create_space(
             session.clone(),
             left_hand_subaction_path,
@@ -281,7 +148,7 @@ impl Input {
             Posef::IDENTITY,
         )?;
 
-        Ok(Input {
+        let input = Input {
             action_set,
             grip_pose_action,
             aim_pose_action,
@@ -309,6 +176,369 @@ impl Input {
             right_hand_grip_space,
             right_hand_aim_space,
             right_hand_subaction_path,
-        })
+        };
+
+        input.suggest_bindings(instance, enabled_extensions, action_sets)?;
+        Ok(input)
+    }
+
+    /// Bind our actions to the inputs of every controller the runtime knows about. A runtime can reject a profile it
+    /// doesn't support without stopping the others from working, as long as one of them is accepted.
+    ///
+    /// Suggesting bindings for a profile replaces any earlier suggestion, so the app's bindings are suggested along with
+    /// ours.
+    fn suggest_bindings(
+        &self,
+        instance: &xr::Instance,
+        enabled_extensions: &xr::ExtensionSet,
+        action_sets: &ActionSets,
+    ) -> Result<()> {
+        let mut accepted = 0;
+        for profile in &INTERACTION_PROFILES {
+            if !profile.is_available(enabled_extensions) {
+                continue;
+            }
+
+            let mut bindings = profile
+                .bindings
+                .iter()
+                .map(|(control, path)| Ok(self.binding(*control, instance.string_to_path(path)?)))
+                .collect::<Result<Vec<_>>>()?;
+            bindings.extend(action_sets.bindings(instance, profile.path)?);
+
+            match instance.suggest_interaction_profile_bindings(
+                instance.string_to_path(profile.path)?,
+                &bindings,
+            ) {
+                Ok(()) => accepted += 1,
+                Err(e) => println!(
+                    "[HOTHAM_INPUT] Unable to suggest bindings for {} - {:?}",
+                    profile.path, e
+                ),
+            }
+        }
+
+        if accepted == 0 {
+            return Err(anyhow!(
+                "The runtime didn't accept bindings for any controllers"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn binding(&self, control: Control, path: Path) -> xr::Binding<'_> {
+        match control {
+            Control::GripPose => xr::Binding::new(&self.grip_pose_action, path),
+            Control::AimPose => xr::Binding::new(&self.aim_pose_action, path),
+            Control::Squeeze => xr::Binding::new(&self.squeeze_action, path),
+            Control::Trigger => xr::Binding::new(&self.trigger_action, path),
+            Control::TriggerTouch => xr::Binding::new(&self.trigger_touch_action, path),
+            Control::Haptic => xr::Binding::new(&self.haptic_feedback_action, path),
+            Control::XButton => xr::Binding::new(&self.x_button_action, path),
+            Control::XTouch => xr::Binding::new(&self.x_touch_action, path),
+            Control::YButton => xr::Binding::new(&self.y_button_action, path),
+            Control::YTouch => xr::Binding::new(&self.y_touch_action, path),
+            Control::MenuButton => xr::Binding::new(&self.menu_button_action, path),
+            Control::AButton => xr::Binding::new(&self.a_button_action, path),
+            Control::ATouch => xr::Binding::new(&self.a_touch_action, path),
+            Control::BButton => xr::Binding::new(&self.b_button_action, path),
+            Control::BTouch => xr::Binding::new(&self.b_touch_action, path),
+            Control::ThumbstickX => xr::Binding::new(&self.thumbstick_x_action, path),
+            Control::ThumbstickY => xr::Binding::new(&self.thumbstick_y_action, path),
+            Control::ThumbstickClick => xr::Binding::new(&self.thumbstick_click_action, path),
+            Control::ThumbstickTouch => xr::Binding::new(&self.thumbstick_touch_action, path),
+            Control::ThumbrestTouch => xr::Binding::new(&self.thumbrest_touch_action, path),
+        }
+    }
+}
+
+/// The name of the extension that adds the Pico controller profiles. `openxr` doesn't know about it yet.
+pub(crate) const BD_CONTROLLER_INTERACTION_EXTENSION_NAME: &str = "XR_BD_controller_interaction";
+
+/// One of Hotham's actions, for binding to a controller's inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    GripPose,
+    AimPose,
+    Squeeze,
+    Trigger,
+    TriggerTouch,
+    Haptic,
+    XButton,
+    XTouch,
+    YButton,
+    YTouch,
+    MenuButton,
+    AButton,
+    ATouch,
+    BButton,
+    BTouch,
+    ThumbstickX,
+    ThumbstickY,
+    ThumbstickClick,
+    ThumbstickTouch,
+    ThumbrestTouch,
+}
+
+/// The extension a runtime needs to have enabled before it knows about an interaction profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProfileExtension {
+    /// Part of core OpenXR
+    None,
+    /// `XR_HTC_vive_focus3_controller_interaction`
+    ViveFocus3,
+    /// `XR_BD_controller_interaction`
+    BdController,
+}
+
+/// A controller we have bindings for.
+struct InteractionProfile {
+    path: &'static str,
+    extension: ProfileExtension,
+    bindings: &'static [(Control, &'static str)],
+}
+
+impl InteractionProfile {
+    fn is_available(&self, enabled_extensions: &xr::ExtensionSet) -> bool {
+        match self.extension {
+            ProfileExtension::None => true,
+            ProfileExtension::ViveFocus3 => {
+                enabled_extensions.htc_vive_focus3_controller_interaction
+            }
+            ProfileExtension::BdController => enabled_extensions
+                .other
+                .iter()
+                .any(|e| e == BD_CONTROLLER_INTERACTION_EXTENSION_NAME),
+        }
+    }
+}
+
+/// Enable the extensions for every controller profile the runtime supports, so the same app works on any headset.
+pub(crate) fn enable_controller_extensions(
+    available: &xr::ExtensionSet,
+    enabled: &mut xr::ExtensionSet,
+) {
+    if available.htc_vive_focus3_controller_interaction {
+        enabled.htc_vive_focus3_controller_interaction = true;
+    }
+    let bd_controller = BD_CONTROLLER_INTERACTION_EXTENSION_NAME.to_string();
+    if available.other.contains(&bd_controller) && !enabled.other.contains(&bd_controller) {
+        enabled.other.push(bd_controller);
+    }
+}
+
+const INTERACTION_PROFILES: [InteractionProfile; 3] = [
+    InteractionProfile {
+        path: "/interaction_profiles/oculus/touch_controller",
+        extension: ProfileExtension::None,
+        bindings: &[
+            (Control::GripPose, "/user/hand/left/input/grip/pose"),
+            (Control::GripPose, "/user/hand/right/input/grip/pose"),
+            (Control::AimPose, "/user/hand/left/input/aim/pose"),
+            (Control::AimPose, "/user/hand/right/input/aim/pose"),
+            (Control::Squeeze, "/user/hand/left/input/squeeze/value"),
+            (Control::Squeeze, "/user/hand/right/input/squeeze/value"),
+            (Control::Trigger, "/user/hand/left/input/trigger/value"),
+            (Control::Trigger, "/user/hand/right/input/trigger/value"),
+            (Control::TriggerTouch, "/user/hand/left/input/trigger/touch"),
+            (
+                Control::TriggerTouch,
+                "/user/hand/right/input/trigger/touch",
+            ),
+            (Control::Haptic, "/user/hand/left/output/haptic"),
+            (Control::Haptic, "/user/hand/right/output/haptic"),
+            (Control::XButton, "/user/hand/left/input/x/click"),
+            (Control::XTouch, "/user/hand/left/input/x/touch"),
+            (Control::YButton, "/user/hand/left/input/y/click"),
+            (Control::YTouch, "/user/hand/left/input/y/touch"),
+            (Control::MenuButton, "/user/hand/left/input/menu/click"),
+            (Control::AButton, "/user/hand/right/input/a/click"),
+            (Control::ATouch, "/user/hand/right/input/a/touch"),
+            (Control::BButton, "/user/hand/right/input/b/click"),
+            (Control::BTouch, "/user/hand/right/input/b/touch"),
+            (Control::ThumbstickX, "/user/hand/left/input/thumbstick/x"),
+            (Control::ThumbstickX, "/user/hand/right/input/thumbstick/x"),
+            (Control::ThumbstickY, "/user/hand/left/input/thumbstick/y"),
+            (Control::ThumbstickY, "/user/hand/right/input/thumbstick/y"),
+            (
+                Control::ThumbstickClick,
+                "/user/hand/left/input/thumbstick/click",
+            ),
+            (
+                Control::ThumbstickClick,
+                "/user/hand/right/input/thumbstick/click",
+            ),
+            (
+                Control::ThumbstickTouch,
+                "/user/hand/left/input/thumbstick/touch",
+            ),
+            (
+                Control::ThumbstickTouch,
+                "/user/hand/right/input/thumbstick/touch",
+            ),
+            (
+                Control::ThumbrestTouch,
+                "/user/hand/left/input/thumbrest/touch",
+            ),
+            (
+                Control::ThumbrestTouch,
+                "/user/hand/right/input/thumbrest/touch",
+            ),
+        ],
+    },
+    // Pico 4 controllers are laid out like Touch controllers, so the bindings are the same.
+    InteractionProfile {
+        path: "/interaction_profiles/bytedance/pico4_controller",
+        extension: ProfileExtension::BdController,
+        bindings: &[
+            (Control::GripPose, "/user/hand/left/input/grip/pose"),
+            (Control::GripPose, "/user/hand/right/input/grip/pose"),
+            (Control::AimPose, "/user/hand/left/input/aim/pose"),
+            (Control::AimPose, "/user/hand/right/input/aim/pose"),
+            (Control::Squeeze, "/user/hand/left/input/squeeze/value"),
+            (Control::Squeeze, "/user/hand/right/input/squeeze/value"),
+            (Control::Trigger, "/user/hand/left/input/trigger/value"),
+            (Control::Trigger, "/user/hand/right/input/trigger/value"),
+            (Control::TriggerTouch, "/user/hand/left/input/trigger/touch"),
+            (
+                Control::TriggerTouch,
+                "/user/hand/right/input/trigger/touch",
+            ),
+            (Control::Haptic, "/user/hand/left/output/haptic"),
+            (Control::Haptic, "/user/hand/right/output/haptic"),
+            (Control::XButton, "/user/hand/left/input/x/click"),
+            (Control::XTouch, "/user/hand/left/input/x/touch"),
+            (Control::YButton, "/user/hand/left/input/y/click"),
+            (Control::YTouch, "/user/hand/left/input/y/touch"),
+            (Control::MenuButton, "/user/hand/left/input/menu/click"),
+            (Control::AButton, "/user/hand/right/input/a/click"),
+            (Control::ATouch, "/user/hand/right/input/a/touch"),
+            (Control::BButton, "/user/hand/right/input/b/click"),
+            (Control::BTouch, "/user/hand/right/input/b/touch"),
+            (Control::ThumbstickX, "/user/hand/left/input/thumbstick/x"),
+            (Control::ThumbstickX, "/user/hand/right/input/thumbstick/x"),
+            (Control::ThumbstickY, "/user/hand/left/input/thumbstick/y"),
+            (Control::ThumbstickY, "/user/hand/right/input/thumbstick/y"),
+            (
+                Control::ThumbstickClick,
+                "/user/hand/left/input/thumbstick/click",
+            ),
+            (
+                Control::ThumbstickClick,
+                "/user/hand/right/input/thumbstick/click",
+            ),
+            (
+                Control::ThumbstickTouch,
+                "/user/hand/left/input/thumbstick/touch",
+            ),
+            (
+                Control::ThumbstickTouch,
+                "/user/hand/right/input/thumbstick/touch",
+            ),
+            (
+                Control::ThumbrestTouch,
+                "/user/hand/left/input/thumbrest/touch",
+            ),
+            (
+                Control::ThumbrestTouch,
+                "/user/hand/right/input/thumbrest/touch",
+            ),
+        ],
+    },
+    // Vive Focus 3 controllers have no touch sensors on the buttons, and only a click for the grip. A clicked grip is
+    // read as a fully squeezed one.
+    InteractionProfile {
+        path: "/interaction_profiles/htc/vive_focus3_controller",
+        extension: ProfileExtension::ViveFocus3,
+        bindings: &[
+            (Control::GripPose, "/user/hand/left/input/grip/pose"),
+            (Control::GripPose, "/user/hand/right/input/grip/pose"),
+            (Control::AimPose, "/user/hand/left/input/aim/pose"),
+            (Control::AimPose, "/user/hand/right/input/aim/pose"),
+            (Control::Squeeze, "/user/hand/left/input/squeeze/click"),
+            (Control::Squeeze, "/user/hand/right/input/squeeze/click"),
+            (Control::Trigger, "/user/hand/left/input/trigger/value"),
+            (Control::Trigger, "/user/hand/right/input/trigger/value"),
+            (Control::TriggerTouch, "/user/hand/left/input/trigger/touch"),
+            (
+                Control::TriggerTouch,
+                "/user/hand/right/input/trigger/touch",
+            ),
+            (Control::Haptic, "/user/hand/left/output/haptic"),
+            (Control::Haptic, "/user/hand/right/output/haptic"),
+            (Control::XButton, "/user/hand/left/input/x/click"),
+            (Control::YButton, "/user/hand/left/input/y/click"),
+            (Control::MenuButton, "/user/hand/left/input/menu/click"),
+            (Control::AButton, "/user/hand/right/input/a/click"),
+            (Control::BButton, "/user/hand/right/input/b/click"),
+            (Control::ThumbstickX, "/user/hand/left/input/thumbstick/x"),
+            (Control::ThumbstickX, "/user/hand/right/input/thumbstick/x"),
+            (Control::ThumbstickY, "/user/hand/left/input/thumbstick/y"),
+            (Control::ThumbstickY, "/user/hand/right/input/thumbstick/y"),
+            (
+                Control::ThumbstickClick,
+                "/user/hand/left/input/thumbstick/click",
+            ),
+            (
+                Control::ThumbstickClick,
+                "/user/hand/right/input/thumbstick/click",
+            ),
+            (
+                Control::ThumbstickTouch,
+                "/user/hand/left/input/thumbstick/touch",
+            ),
+            (
+                Control::ThumbstickTouch,
+                "/user/hand/right/input/thumbstick/touch",
+            ),
+            (
+                Control::ThumbrestTouch,
+                "/user/hand/left/input/thumbrest/touch",
+            ),
+            (
+                Control::ThumbrestTouch,
+                "/user/hand/right/input/thumbrest/touch",
+            ),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_interaction_profiles() {
+        let mut available = xr::ExtensionSet::default();
+        available.htc_vive_focus3_controller_interaction = true;
+        available
+            .other
+            .push(BD_CONTROLLER_INTERACTION_EXTENSION_NAME.to_string());
+
+        // Only core profiles can be used until their extensions are enabled..
+        let mut enabled = xr::ExtensionSet::default();
+        let usable = |enabled: &xr::ExtensionSet| {
+            INTERACTION_PROFILES
+                .iter()
+                .filter(|p| p.is_available(enabled))
+                .count()
+        };
+        assert_eq!(usable(&enabled), 1);
+
+        // ..and enabling them doesn't add anything twice.
+        enable_controller_extensions(&available, &mut enabled);
+        enable_controller_extensions(&available, &mut enabled);
+        assert_eq!(usable(&enabled), 3);
+        assert_eq!(enabled.other.len(), 1);
+
+        // Each profile binds every path once, and only paths under a hand.
+        for profile in &INTERACTION_PROFILES {
+            let mut paths = profile.bindings.iter().map(|(_, p)| *p).collect::<Vec<_>>();
+            paths.sort_unstable();
+            paths.dedup();
+            assert_eq!(paths.len(), profile.bindings.len(), "{}", profile.path);
+            assert!(paths.iter().all(|p| p.starts_with("/user/hand/")));
+        }
     }
 }
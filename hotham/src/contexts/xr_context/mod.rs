@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ash::vk::{self, Handle};
 use openxr::{
     self as xr, EventDataBuffer, FrameStream, FrameWaiter, Session, SessionState, Space, Swapchain,
@@ -10,21 +10,60 @@ use xr::{
 };
 
 use crate::{
-    contexts::VulkanContext, util::is_view_valid, HothamError, HothamResult, BLEND_MODE,
-    COLOR_FORMAT, VIEW_COUNT, VIEW_TYPE,
+    components::hand::Handedness,
+    contexts::{device_context::PerformanceNotification, PhysicalDeviceSettings, VulkanContext},
+    placement::PlayArea,
+    splash_screen::SplashLayer,
+    util::is_view_valid,
+    HothamError, HothamResult, COLOR_FORMAT, VIEW_COUNT, VIEW_TYPE,
 };
 
+mod action_sets;
+pub use action_sets::{ActionKind, ActionSetSettings, ActionSets, ActionSettings, InputContextId};
+
+mod foveation;
+pub use foveation::FoveationLevel;
+
 mod input;
 use input::Input;
 
+mod overlay;
+pub use overlay::OverlaySettings;
+
+mod passthrough;
+use passthrough::PassthroughLayer;
+
+mod plugin;
+pub use plugin::XrPlugin;
+
+mod render_model;
+
+mod runtime;
+pub use runtime::XrRuntime;
+
+#[cfg(not(target_os = "android"))]
+mod simulator;
+#[cfg(not(target_os = "android"))]
+pub use simulator::SIMULATOR_PATH_VAR;
+
 #[derive(Default)]
 pub struct XrContextBuilder<'a> {
     path: Option<&'a std::path::Path>,
     application_name: Option<&'a str>,
     application_version: Option<u32>,
     required_extensions: Option<xr::ExtensionSet>,
+    overlay: Option<OverlaySettings>,
+    optional_extensions: Option<OptionalExtensions>,
+    action_sets: Vec<ActionSetSettings>,
+    plugins: Vec<Box<dyn XrPlugin>>,
+    swapchain_formats: Vec<vk::Format>,
+    physical_device: PhysicalDeviceSettings,
 }
 
+/// Called with the extensions the runtime has available, to enable any of them the app can make use of. This lets the
+/// same app use vendor extensions where they're supported, without failing to start on runtimes that lack them.
+pub type OptionalExtensions = fn(available: &xr::ExtensionSet, enabled: &mut xr::ExtensionSet);
+
 impl<'a> XrContextBuilder<'a> {
     pub fn new() -> Self {
         XrContextBuilder::default()
@@ -50,33 +89,126 @@ impl<'a> XrContextBuilder<'a> {
         self
     }
 
+    pub fn overlay(&mut self, overlay: Option<OverlaySettings>) -> &mut Self {
+        self.overlay = overlay;
+        self
+    }
+
+    pub fn optional_extensions(
+        &mut self,
+        optional_extensions: Option<OptionalExtensions>,
+    ) -> &mut Self {
+        self.optional_extensions = optional_extensions;
+        self
+    }
+
+    /// The app's own input contexts - see [`ActionSetSettings`].
+    pub fn action_sets(&mut self, action_sets: Vec<ActionSetSettings>) -> &mut Self {
+        self.action_sets = action_sets;
+        self
+    }
+
+    /// Plugins for extensions Hotham doesn't support itself - see [`XrPlugin`].
+    pub fn plugins(&mut self, plugins: Vec<Box<dyn XrPlugin>>) -> &mut Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// The swapchain formats the app would like to render to, best first - eg. `A2B10G10R10_UNORM_PACK32` for
+    /// 10-bit color. The first one the runtime supports is used, and if it supports none of them, Hotham picks one.
+    /// See [`RENDERABLE_SWAPCHAIN_FORMATS`] for the formats that can be used.
+    pub fn swapchain_formats(&mut self, formats: Vec<vk::Format>) -> &mut Self {
+        self.swapchain_formats = formats;
+        self
+    }
+
+    /// Which GPU to render with, on machines with more than one. Defaults to the GPU the runtime asks for.
+    pub fn physical_device(&mut self, settings: PhysicalDeviceSettings) -> &mut Self {
+        self.physical_device = settings;
+        self
+    }
+
     pub fn build(&mut self) -> Result<(XrContext, VulkanContext)> {
         let application_name = self.application_name.unwrap_or("Hotham Application");
         let application_version = self.application_version.unwrap_or(1);
-        let (instance, system) = create_xr_instance(
+        let mut required_extensions = self.required_extensions.clone().unwrap_or_default();
+        if self.overlay.is_some() {
+            required_extensions.extx_overlay = true;
+        }
+        let (instance, system, enabled_extensions) = create_xr_instance(
             self.path,
             application_name,
             application_version,
-            self.required_extensions.as_ref(),
+            Some(&required_extensions),
+            self.optional_extensions,
+            &mut self.plugins,
         )?;
-        XrContext::_new(instance, system, application_name, application_version)
+        let (mut xr_context, vulkan_context) = XrContext::_new(
+            instance,
+            system,
+            enabled_extensions,
+            application_name,
+            application_version,
+            self,
+        )?;
+
+        let mut plugins = std::mem::take(&mut self.plugins);
+        for plugin in &mut plugins {
+            plugin
+                .on_session_created(&xr_context)
+                .map_err(|e| anyhow!("Unable to set up the {} plugin: {}", plugin.name(), e))?;
+            println!("[HOTHAM_XR] Plugin {} is ready", plugin.name());
+        }
+        xr_context.plugins = plugins;
+
+        Ok((xr_context, vulkan_context))
     }
 }
 
 pub struct XrContext {
     pub instance: openxr::Instance,
+    /// The headset the session is running on
+    pub system: xr::SystemId,
     pub session: Session<Vulkan>,
     pub session_state: SessionState,
     pub swapchain: Swapchain<Vulkan>,
+    /// The space the world is drawn in: `STAGE` if the runtime has one, `LOCAL` if not.
     pub stage_space: Space,
     pub view_space: Space,
     pub input: Input,
+    /// The app's own action sets, and which input contexts are active
+    pub action_sets: ActionSets,
     pub swapchain_resolution: vk::Extent2D,
+    /// How much of each swapchain image was rendered to this frame. Smaller than `swapchain_resolution` when the
+    /// render scale is below 1 - see [`crate::contexts::RenderContext::set_render_scale`].
+    pub render_extent: vk::Extent2D,
+    /// The format of the swapchain images, picked from the ones the runtime supports. Custom pipelines that draw into
+    /// the swapchain should use this format, and encode their colors to sRGB if [`needs_srgb_encoding`] says so.
+    pub swapchain_format: vk::Format,
+    /// Every swapchain format the runtime supports, best first
+    pub available_swapchain_formats: Vec<vk::Format>,
+    /// Every extension the instance was created with, for checking whether a feature can be used on this runtime.
+    pub enabled_extensions: xr::ExtensionSet,
+    /// The runtime we're running on
+    pub runtime: XrRuntime,
+    /// How frames are blended with the real world, picked from the modes the runtime supports
+    pub blend_mode: xr::EnvironmentBlendMode,
     pub frame_waiter: FrameWaiter,
     pub frame_stream: FrameStream<Vulkan>,
     pub frame_state: FrameState,
     pub views: Vec<View>,
     pub view_state_flags: ViewStateFlags,
+    pub(crate) splash_layer: Option<SplashLayer>,
+    /// Set if this app is running as an overlay on top of another app
+    pub overlay: Option<OverlaySettings>,
+    /// Can the app this overlay is drawn over be seen? Always true if this app isn't an overlay.
+    pub main_session_visible: bool,
+    /// Performance level changes from the runtime that haven't been sent as events yet
+    pub(crate) performance_notifications: Vec<PerformanceNotification>,
+    /// The camera feed, shown behind what's drawn - see [`XrContext::enable_passthrough`]
+    pub(crate) passthrough: Option<PassthroughLayer>,
+    /// Plugins for extensions Hotham doesn't support itself
+    plugins: Vec<Box<dyn XrPlugin>>,
 }
 
 impl XrContext {
@@ -89,29 +221,90 @@ impl XrContext {
     }
 
     #[cfg(test)]
+    #[cfg(target_os = "windows")]
     pub fn testing() -> (XrContext, VulkanContext) {
         XrContext::new_from_path("../openxr_loader.dll").unwrap()
     }
 
+    /// Elsewhere the system's OpenXR loader is used. On Linux, pick the simulator by setting `XR_RUNTIME_JSON` to
+    /// `hotham-simulator/hotham_simulator_linux.json`.
+    #[cfg(test)]
+    #[cfg(not(target_os = "windows"))]
+    pub fn testing() -> (XrContext, VulkanContext) {
+        XrContext::new().unwrap()
+    }
+
     fn _new(
         instance: xr::Instance,
         system: xr::SystemId,
+        enabled_extensions: xr::ExtensionSet,
         application_name: &str,
         application_version: u32,
+        settings: &XrContextBuilder,
     ) -> Result<(XrContext, VulkanContext)> {
-        let vulkan_context =
-            create_vulkan_context(&instance, system, application_name, application_version)?;
+        let overlay = settings.overlay;
+        let runtime = XrRuntime::from_name(&instance.properties()?.runtime_name);
+        println!("[HOTHAM_XR] Running on {:?}", runtime);
+
+        // Check the runtime can show what we render before going any further.
+        if !instance
+            .enumerate_view_configurations(system)?
+            .contains(&VIEW_TYPE)
+        {
+            return Err(anyhow!("The runtime doesn't support {:?} views", VIEW_TYPE));
+        }
+        let blend_mode = runtime::pick_blend_mode(
+            &instance.enumerate_environment_blend_modes(system, VIEW_TYPE)?,
+        )
+        .ok_or_else(|| anyhow!("The runtime doesn't support any blend modes"))?;
+        println!("[HOTHAM_XR] Using blend mode {:?}", blend_mode);
+
+        let vulkan_context = create_vulkan_context(
+            &instance,
+            system,
+            application_name,
+            application_version,
+            &settings.physical_device,
+        )?;
 
-        let (session, frame_waiter, frame_stream) =
-            create_xr_session(&instance, system, &vulkan_context)?;
-        let stage_space =
-            session.create_reference_space(ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?;
+        let (session, frame_waiter, frame_stream) = match &overlay {
+            Some(overlay) => {
+                overlay::create_overlay_session(&instance, system, &vulkan_context, overlay)?
+            }
+            None => create_xr_session(&instance, system, &vulkan_context)?,
+        };
+        let stage_space_type =
+            runtime::pick_reference_space(&session.enumerate_reference_spaces()?);
+        println!(
+            "[HOTHAM_XR] Drawing the world in {:?} space",
+            stage_space_type
+        );
+        let stage_space = session.create_reference_space(stage_space_type, xr::Posef::IDENTITY)?;
         let view_space =
             session.create_reference_space(ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?;
         let swapchain_resolution = get_swapchain_resolution(&instance, system)?;
-        let swapchain = create_xr_swapchain(&session, &swapchain_resolution, VIEW_COUNT)?;
+        let available_swapchain_formats = session
+            .enumerate_swapchain_formats()?
+            .into_iter()
+            .map(|f| vk::Format::from_raw(f as i32))
+            .collect::<Vec<_>>();
+        let swapchain_format =
+            pick_swapchain_format(&settings.swapchain_formats, &available_swapchain_formats)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The runtime doesn't support any swapchain formats Hotham can render to"
+                    )
+                })?;
+        println!("[HOTHAM_XR] Using swapchain format {:?}", swapchain_format);
+        let swapchain = create_xr_swapchain(
+            &session,
+            &swapchain_resolution,
+            swapchain_format,
+            VIEW_COUNT,
+        )?;
 
-        let input = Input::oculus_touch_controller(&instance, &session)?;
+        let action_sets = ActionSets::new(&instance, &settings.action_sets)?;
+        let input = Input::new(&instance, &session, &enabled_extensions, &action_sets)?;
 
         let frame_state = FrameState {
             predicted_display_time: Time::from_nanos(0),
@@ -119,23 +312,47 @@ impl XrContext {
             should_render: false,
         };
 
-        // Attach the action set to the session
-        session.attach_action_sets(&[&input.action_set])?;
+        // Attach our action set, and the app's, to the session
+        let mut all_action_sets = vec![&input.action_set];
+        all_action_sets.extend(action_sets.action_sets());
+        session.attach_action_sets(&all_action_sets)?;
 
         let xr_context = XrContext {
             instance,
+            system,
             session,
             session_state: SessionState::IDLE,
             swapchain,
             stage_space,
             view_space,
             input,
+            action_sets,
             swapchain_resolution,
+            render_extent: swapchain_resolution,
+            swapchain_format,
+            available_swapchain_formats,
+            enabled_extensions,
+            runtime,
+            blend_mode,
             frame_waiter,
             frame_stream,
             frame_state,
-            views: vec![Default::default(); VIEW_COUNT as usize],
+            // Some runtimes (eg. SteamVR) reject frames with an invalid orientation, so start from identity poses
+            // rather than zeroes until the views have been located.
+            views: vec![
+                View {
+                    pose: xr::Posef::IDENTITY,
+                    fov: Default::default(),
+                };
+                VIEW_COUNT as usize
+            ],
             view_state_flags: ViewStateFlags::EMPTY,
+            splash_layer: None,
+            overlay,
+            main_session_visible: true,
+            performance_notifications: Vec::new(),
+            passthrough: None,
+            plugins: Vec::new(),
         };
 
         Ok((xr_context, vulkan_context))
@@ -145,7 +362,12 @@ impl XrContext {
         &mut self,
         event_buffer: &mut EventDataBuffer,
     ) -> Result<SessionState> {
-        match self.instance.poll_event(event_buffer)? {
+        let event = self.instance.poll_event(event_buffer)?;
+        if let Some(event) = &event {
+            self.with_plugins(|plugin, xr_context| plugin.on_event(xr_context, event));
+        }
+
+        match event {
             Some(xr::Event::SessionStateChanged(session_changed)) => {
                 let new_state = session_changed.state();
                 println!("[HOTHAM_POLL_EVENT] State is now {:?}", new_state);
@@ -154,6 +376,19 @@ impl XrContext {
             Some(xr::Event::InstanceLossPending(_)) => {
                 println!("[HOTHAM_POLL_EVENT] Instance loss pending!");
             }
+            Some(xr::Event::MainSessionVisibilityChangedEXTX(visibility_changed)) => {
+                let visible = visibility_changed.visible();
+                println!("[HOTHAM_POLL_EVENT] Main session visible: {}", visible);
+                self.main_session_visible = visible;
+            }
+            Some(xr::Event::PerfSettingsEXT(perf_settings)) => {
+                let notification = PerformanceNotification::from_xr(&perf_settings);
+                println!(
+                    "[HOTHAM_POLL_EVENT] Performance changed: {:?}",
+                    notification
+                );
+                self.performance_notifications.push(notification);
+            }
             Some(_) => println!("[HOTHAM_POLL_EVENT] Received some other event"),
             None => {}
         }
@@ -164,6 +399,7 @@ impl XrContext {
     pub(crate) fn begin_frame(&mut self) -> HothamResult<usize> {
         self.frame_state = self.frame_waiter.wait()?;
         self.frame_stream.begin()?;
+        self.with_plugins(|plugin, xr_context| plugin.on_frame(xr_context));
 
         if !self.frame_state.should_render {
             return Err(HothamError::NotRendering);
@@ -172,12 +408,74 @@ impl XrContext {
         let image_index = self.swapchain.acquire_image()? as _;
         self.swapchain.wait_image(openxr::Duration::INFINITE)?;
 
-        let active_action_set = xr::ActiveActionSet::new(&self.input.action_set);
-        self.session.sync_actions(&[active_action_set])?;
+        // Our action set is always synced, as the hands need it. The app's lower priority input contexts are left out,
+        // so their actions go idle until the contexts above them are popped.
+        let mut active_action_sets = vec![xr::ActiveActionSet::new(&self.input.action_set)];
+        active_action_sets.extend(self.action_sets.active_action_sets());
+        self.session.sync_actions(&active_action_sets)?;
 
         Ok(image_index)
     }
 
+    /// Look up an OpenXR function by name, eg. `xrCreateBodyTrackerFB`, for calling an extension `openxr` doesn't
+    /// wrap. Fails if the function's extension isn't enabled.
+    ///
+    /// # Safety
+    /// The function must be transmuted to its real signature before it's called.
+    pub unsafe fn get_instance_proc_addr(
+        &self,
+        name: &std::ffi::CStr,
+    ) -> HothamResult<xr::sys::pfn::VoidFunction> {
+        let mut function = None;
+        let result = (self.instance.fp().get_instance_proc_addr)(
+            self.instance.as_raw(),
+            name.as_ptr(),
+            &mut function,
+        );
+        if result.into_raw() < 0 {
+            return Err(HothamError::OpenXRError(result));
+        }
+        function.ok_or(HothamError::OpenXRError(
+            xr::sys::Result::ERROR_FUNCTION_UNSUPPORTED,
+        ))
+    }
+
+    /// Call `f` with each plugin. The plugins are taken out while they're called, so they can be given the context.
+    fn with_plugins(&mut self, mut f: impl FnMut(&mut dyn XrPlugin, &XrContext)) {
+        let mut plugins = std::mem::take(&mut self.plugins);
+        for plugin in &mut plugins {
+            f(plugin.as_mut(), self);
+        }
+        self.plugins = plugins;
+    }
+
+    /// Push the app's input context `context`, suppressing any with a lower priority. Takes effect from the next frame.
+    /// The format for the splash screen. Its image is 8-bit sRGB, so it uses the best 8-bit format when the app renders
+    /// to something else.
+    pub(crate) fn splash_screen_format(&self) -> vk::Format {
+        pick_swapchain_format(&[], &self.available_swapchain_formats)
+            .unwrap_or(self.swapchain_format)
+    }
+
+    pub fn push_input_context(&mut self, context: InputContextId) {
+        self.action_sets.push(context);
+    }
+
+    /// Pop the most recently pushed input context, making the ones it suppressed active again.
+    pub fn pop_input_context(&mut self) -> Option<InputContextId> {
+        self.action_sets.pop()
+    }
+
+    /// The play area the user set up, for placing content inside it. `None` if the runtime doesn't have one, eg. when
+    /// the world is drawn in `LOCAL` space.
+    pub fn play_area(&self) -> Option<PlayArea> {
+        self.session
+            .reference_space_bounds_rect(ReferenceSpaceType::STAGE)
+            .ok()
+            .flatten()
+            .map(PlayArea::from_xr)
+    }
+
     pub fn update_views(&'_ mut self) -> &[xr::View] {
         let (view_state_flags, views) = self
             .session
@@ -200,7 +498,11 @@ impl XrContext {
         // If we aren't in the rendering state, just submit empty views.
         if !self.frame_state.should_render {
             self.frame_stream
-                .end(self.frame_state.predicted_display_time, BLEND_MODE, &[])
+                .end(
+                    self.frame_state.predicted_display_time,
+                    self.blend_mode,
+                    &[],
+                )
                 .unwrap();
             return Ok(());
         }
@@ -211,13 +513,30 @@ impl XrContext {
         let rect = xr::Rect2Di {
             offset: xr::Offset2Di { x: 0, y: 0 },
             extent: xr::Extent2Di {
-                width: self.swapchain_resolution.width as _,
-                height: self.swapchain_resolution.height as _,
+                width: self.render_extent.width as _,
+                height: self.render_extent.height as _,
             },
         };
 
         let display_time = self.frame_state.predicted_display_time;
 
+        // An overlay that's hidden along with the app underneath it submits nothing.
+        if self.is_hidden_overlay() {
+            return self.frame_stream.end(display_time, self.blend_mode, &[]);
+        }
+
+        // While the splash screen is showing, it's the only layer submitted.
+        if let Some(splash_layer) = &mut self.splash_layer {
+            if splash_layer.is_showing() {
+                let layer_quad = splash_layer.layer(&self.view_space, &self.stage_space);
+                return self
+                    .frame_stream
+                    .end(display_time, self.blend_mode, &[&*layer_quad]);
+            }
+            println!("[HOTHAM_XR] - Splash screen finished");
+            self.splash_layer = None;
+        }
+
         let views = [
             xr::CompositionLayerProjectionView::new()
                 .pose(self.views[0].pose)
@@ -239,12 +558,121 @@ impl XrContext {
                 ),
         ];
 
+        // NOTE: No depth is submitted with these views, as our depth buffer is multisampled and never leaves the tile.
+        // If it ever is, its near_z and far_z must come from `ClipPlanes::depth_range` so they match the projection,
+        // whether or not depth is reversed.
+        // Overlays and apps showing passthrough are cleared to transparent, so whatever's underneath shows through
+        // wherever nothing was drawn.
+        let passthrough_layer = self
+            .passthrough
+            .as_ref()
+            .filter(|passthrough| passthrough.is_running())
+            .map(PassthroughLayer::composition_layer);
+        let layer_flags = if self.overlay.is_some() || passthrough_layer.is_some() {
+            xr::CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA
+        } else {
+            xr::CompositionLayerFlags::EMPTY
+        };
         let layer_projection = xr::CompositionLayerProjection::new()
+            .layer_flags(layer_flags)
             .space(&self.stage_space)
             .views(&views);
 
-        let layers = [&*layer_projection];
-        self.frame_stream.end(display_time, BLEND_MODE, &layers)
+        // The camera feed goes underneath everything else.
+        let mut layers = Vec::with_capacity(2);
+        if let Some(passthrough_layer) = &passthrough_layer {
+            // SAFETY: `passthrough_layer` lives until the end of this function.
+            layers.push(unsafe { passthrough::as_layer_base(passthrough_layer) });
+        }
+        layers.push(&*layer_projection);
+        self.frame_stream
+            .end(display_time, self.blend_mode, &layers)
+    }
+
+    /// Show the headset's camera feed behind everything that's drawn, using `XR_FB_passthrough`. Whatever's left of
+    /// the background is see-through, so the view must be cleared to transparent - use
+    /// [`crate::Engine::enable_passthrough`] to do both.
+    pub fn enable_passthrough(&mut self) -> HothamResult<()> {
+        if !self.enabled_extensions.fb_passthrough {
+            return Err(HothamError::OpenXRError(
+                xr::sys::Result::ERROR_EXTENSION_NOT_PRESENT,
+            ));
+        }
+        match &mut self.passthrough {
+            Some(passthrough) => passthrough.resume()?,
+            None => {
+                self.passthrough = Some(PassthroughLayer::new(&self.instance, &self.session)?);
+                println!("[HOTHAM_XR] Passthrough enabled");
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop showing the camera feed. The passthrough layer is kept around, so it can be shown again quickly.
+    pub fn disable_passthrough(&mut self) -> HothamResult<()> {
+        if let Some(passthrough) = &mut self.passthrough {
+            passthrough.pause()?;
+        }
+        Ok(())
+    }
+
+    /// Is the camera feed being shown behind what's drawn?
+    pub fn is_passthrough_enabled(&self) -> bool {
+        self.passthrough
+            .as_ref()
+            .map_or(false, PassthroughLayer::is_running)
+    }
+
+    /// Render the edges of the view at lower resolution with `XR_FB_foveation`, which makes each frame cheaper to draw.
+    /// Most noticeable in scenes limited by how many pixels are drawn, rather than how many triangles.
+    pub fn set_foveation_level(&self, level: FoveationLevel) -> HothamResult<()> {
+        let extensions = &self.enabled_extensions;
+        if !(extensions.fb_foveation
+            && extensions.fb_foveation_configuration
+            && extensions.fb_swapchain_update_state)
+        {
+            return Err(HothamError::OpenXRError(
+                xr::sys::Result::ERROR_EXTENSION_NOT_PRESENT,
+            ));
+        }
+        foveation::set_foveation_level(&self.instance, &self.session, &self.swapchain, level)?;
+        println!("[HOTHAM_XR] Foveation level set to {:?}", level);
+        Ok(())
+    }
+
+    /// Get the runtime's model of the controller on `handedness`'s side as a glb, using `XR_FB_render_model`, so it
+    /// matches the player's hardware. Returns `None` if the runtime has no model for it, eg. because the extension isn't
+    /// supported or the controller isn't connected - see [`crate::systems::controller_models::add_controller_model`]
+    /// for falling back to a built-in model.
+    pub fn controller_model(&self, handedness: Handedness) -> HothamResult<Option<Vec<u8>>> {
+        if !self.enabled_extensions.fb_render_model {
+            return Ok(None);
+        }
+        Ok(render_model::load_controller_model(
+            &self.instance,
+            &self.session,
+            handedness,
+        )?)
+    }
+
+    /// Is this an overlay that shouldn't be shown, because the app it's drawn over can't be seen?
+    pub fn is_hidden_overlay(&self) -> bool {
+        match self.overlay {
+            Some(overlay) => overlay.hide_with_main_session && !self.main_session_visible,
+            None => false,
+        }
+    }
+
+    /// End the frame without submitting any layers, eg. because rendering was interrupted.
+    pub(crate) fn abandon_frame(&mut self) -> std::result::Result<(), openxr::sys::Result> {
+        if self.frame_state.should_render {
+            self.swapchain.release_image()?;
+        }
+        self.frame_stream.end(
+            self.frame_state.predicted_display_time,
+            self.blend_mode,
+            &[],
+        )
     }
 
     pub(crate) fn end_session(&mut self) -> anyhow::Result<()> {
@@ -261,12 +689,14 @@ pub(crate) fn create_vulkan_context(
     system: xr::SystemId,
     application_name: &str,
     application_version: u32,
+    physical_device: &PhysicalDeviceSettings,
 ) -> Result<VulkanContext, crate::hotham_error::HothamError> {
     let vulkan_context = VulkanContext::create_from_xr_instance(
         xr_instance,
         system,
         application_name,
         application_version,
+        physical_device,
     )?;
     println!("[HOTHAM_VULKAN] - Vulkan Context created successfully");
     Ok(vulkan_context)
@@ -278,6 +708,7 @@ fn create_vulkan_context(
     system: xr::SystemId,
     application_name: &str,
     application_version: u32,
+    physical_device: &PhysicalDeviceSettings,
 ) -> Result<VulkanContext, crate::hotham_error::HothamError> {
     #[allow(deprecated)]
     let vulkan_context = VulkanContext::create_from_xr_instance_legacy(
@@ -285,6 +716,7 @@ fn create_vulkan_context(
         system,
         application_name,
         application_version,
+        physical_device,
     )?;
     println!("[HOTHAM_VULKAN] - Vulkan Context created successfully");
     Ok(vulkan_context)
@@ -296,24 +728,71 @@ pub(crate) fn get_swapchain_resolution(
 ) -> Result<vk::Extent2D> {
     let views = xr_instance.enumerate_view_configuration_views(system, VIEW_TYPE)?;
     println!("[HOTHAM_VULKAN] Views: {:?}", views);
-    let resolution = vk::Extent2D {
-        width: views[0].recommended_image_rect_width,
-        height: views[0].recommended_image_rect_height,
-    };
+    if views.len() != VIEW_COUNT as usize {
+        return Err(anyhow!(
+            "Expected {} views, but the runtime has {}",
+            VIEW_COUNT,
+            views.len()
+        ));
+    }
+
+    runtime::swapchain_resolution(&views).ok_or_else(|| anyhow!("The runtime has no views"))
+}
+
+/// Swapchain formats we render to when the app has no preference, best first. Quest runtimes support `COLOR_FORMAT`,
+/// but some others (eg. Vive Focus, SteamVR and Windows Mixed Reality) list BGRA formats first or only offer BGRA.
+const SWAPCHAIN_FORMATS: [vk::Format; 2] = [COLOR_FORMAT, vk::Format::B8G8R8A8_SRGB];
+
+/// Every swapchain format Hotham can render to.
+pub const RENDERABLE_SWAPCHAIN_FORMATS: [vk::Format; 7] = [
+    vk::Format::R8G8B8A8_SRGB,
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::R8G8B8A8_UNORM,
+    vk::Format::B8G8R8A8_UNORM,
+    vk::Format::A2B10G10R10_UNORM_PACK32,
+    vk::Format::A2R10G10B10_UNORM_PACK32,
+    vk::Format::R16G16B16A16_SFLOAT,
+];
+
+/// Pick the app's `preferred_formats` if the runtime supports them and we can render to them, falling back to the
+/// best of our own.
+pub(crate) fn pick_swapchain_format(
+    preferred_formats: &[vk::Format],
+    supported_formats: &[vk::Format],
+) -> Option<vk::Format> {
+    preferred_formats
+        .iter()
+        .filter(|f| RENDERABLE_SWAPCHAIN_FORMATS.contains(f))
+        .chain(&SWAPCHAIN_FORMATS)
+        .find(|f| supported_formats.contains(f))
+        .copied()
+}
 
-    Ok(resolution)
+/// Whether colors written to a swapchain image in `format` have to be encoded to sRGB by the shader.
+///
+/// sRGB formats are encoded by the GPU and float formats are linear, but runtimes show UNORM formats as they are, so
+/// drawing linear colors into them makes everything look dark and washed out.
+pub fn needs_srgb_encoding(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8G8B8A8_UNORM
+            | vk::Format::B8G8R8A8_UNORM
+            | vk::Format::A2B10G10R10_UNORM_PACK32
+            | vk::Format::A2R10G10B10_UNORM_PACK32
+    )
 }
 
 pub(crate) fn create_xr_swapchain(
     xr_session: &Session<Vulkan>,
     resolution: &vk::Extent2D,
+    format: vk::Format,
     array_size: u32,
 ) -> Result<Swapchain<Vulkan>> {
     xr_session
         .create_swapchain(&SwapchainCreateInfo {
             create_flags: SwapchainCreateFlags::EMPTY,
             usage_flags: SwapchainUsageFlags::COLOR_ATTACHMENT,
-            format: COLOR_FORMAT.as_raw() as u32,
+            format: format.as_raw() as u32,
             sample_count: 1,
             width: resolution.width,
             height: resolution.height,
@@ -345,17 +824,57 @@ pub(crate) fn create_xr_session(
     .unwrap())
 }
 
+/// Load OpenXR from `path` if one was given, or the system's loader if not.
+#[cfg(target_os = "android")]
+fn load_entry(path: Option<&std::path::Path>) -> anyhow::Result<xr::Entry> {
+    Ok(match path {
+        Some(path) => unsafe { xr::Entry::load_from(path)? },
+        None => unsafe { xr::Entry::load()? },
+    })
+}
+
+/// Load OpenXR from `path` if one was given, or the system's loader if not. Without an OpenXR runtime, fall back to
+/// the Hotham simulator, so apps can be run on desktop with a mouse and keyboard.
+#[cfg(not(target_os = "android"))]
+fn load_entry(path: Option<&std::path::Path>) -> anyhow::Result<xr::Entry> {
+    if let Some(path) = path {
+        return Ok(unsafe { xr::Entry::load_from(path)? });
+    }
+    if let Some(path) = simulator::requested() {
+        println!("[HOTHAM_XR] Using the simulator at {}", path.display());
+        return Ok(unsafe { xr::Entry::load_from(&path)? });
+    }
+
+    // The loader may be installed without a runtime, which only shows up once we ask it for something.
+    let error = match unsafe { xr::Entry::load() } {
+        Ok(entry) => match entry.enumerate_extensions() {
+            Ok(_) => return Ok(entry),
+            Err(e) => anyhow!(e),
+        },
+        Err(e) => anyhow!(e),
+    };
+    match simulator::find() {
+        Some(path) => {
+            println!(
+                "[HOTHAM_XR] No OpenXR runtime available ({}), using the simulator at {}",
+                error,
+                path.display()
+            );
+            Ok(unsafe { xr::Entry::load_from(&path)? })
+        }
+        None => Err(error),
+    }
+}
+
 pub(crate) fn create_xr_instance(
     path: Option<&std::path::Path>,
     application_name: &str,
     application_version: u32,
     required_extensions: Option<&xr::ExtensionSet>,
-) -> anyhow::Result<(xr::Instance, xr::SystemId)> {
-    let xr_entry = if let Some(path) = path {
-        unsafe { xr::Entry::load_from(path)? }
-    } else {
-        unsafe { xr::Entry::load()? }
-    };
+    optional_extensions: Option<OptionalExtensions>,
+    plugins: &mut [Box<dyn XrPlugin>],
+) -> anyhow::Result<(xr::Instance, xr::SystemId, xr::ExtensionSet)> {
+    let xr_entry = load_entry(path)?;
     let xr_app_info = openxr::ApplicationInfo {
         application_name,
         application_version,
@@ -363,17 +882,50 @@ pub(crate) fn create_xr_instance(
         engine_version: 1,
     };
 
-    let mut required_extensions = required_extensions.cloned().unwrap_or_default();
-    enable_xr_extensions(&mut required_extensions);
-
     #[cfg(target_os = "android")]
     {
         xr_entry.initialize_android_loader()?;
     }
 
-    let instance = xr_entry.create_instance(&xr_app_info, &required_extensions, &[])?;
+    let mut enabled_extensions = required_extensions.cloned().unwrap_or_default();
+    enable_xr_extensions(&mut enabled_extensions);
+
+    // Only ask for extensions that aren't part of every runtime if this one has them.
+    let available_extensions = xr_entry.enumerate_extensions()?;
+    input::enable_controller_extensions(&available_extensions, &mut enabled_extensions);
+    if available_extensions.ext_performance_settings {
+        enabled_extensions.ext_performance_settings = true;
+    }
+    if available_extensions.ext_hand_tracking {
+        enabled_extensions.ext_hand_tracking = true;
+    }
+    if available_extensions.fb_passthrough {
+        enabled_extensions.fb_passthrough = true;
+    }
+    if available_extensions.fb_render_model {
+        enabled_extensions.fb_render_model = true;
+    }
+    if available_extensions.fb_foveation
+        && available_extensions.fb_foveation_configuration
+        && available_extensions.fb_swapchain_update_state
+    {
+        enabled_extensions.fb_foveation = true;
+        enabled_extensions.fb_foveation_configuration = true;
+        enabled_extensions.fb_swapchain_update_state = true;
+    }
+    if let Some(optional_extensions) = optional_extensions {
+        optional_extensions(&available_extensions, &mut enabled_extensions);
+    }
+    plugin::request_extensions(plugins, &available_extensions, &mut enabled_extensions);
+
+    let instance = xr_entry.create_instance(&xr_app_info, &enabled_extensions, &[])?;
+    let properties = instance.properties()?;
+    println!(
+        "[HOTHAM_XR] Created instance for {} {}",
+        properties.runtime_name, properties.runtime_version
+    );
     let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
-    Ok((instance, system))
+    Ok((instance, system, enabled_extensions))
 }
 
 #[cfg(target_os = "android")]
@@ -387,13 +939,49 @@ fn enable_xr_extensions(required_extensions: &mut xr::ExtensionSet) {
     required_extensions.khr_vulkan_enable = true;
 }
 
-#[cfg(target_os = "windows")]
 #[cfg(test)]
 mod tests {
-    use super::XrContext;
+    use super::*;
 
+    #[cfg(target_os = "windows")]
     #[test]
     pub fn test_xr_context_smoke_test() {
         XrContext::testing();
     }
+
+    #[test]
+    pub fn test_pick_swapchain_format() {
+        // Quest
+        let formats = [vk::Format::B8G8R8A8_SRGB, COLOR_FORMAT];
+        assert_eq!(pick_swapchain_format(&[], &formats), Some(COLOR_FORMAT));
+
+        // Runtimes with only BGRA formats
+        let formats = [vk::Format::B8G8R8A8_UNORM, vk::Format::B8G8R8A8_SRGB];
+        assert_eq!(
+            pick_swapchain_format(&[], &formats),
+            Some(vk::Format::B8G8R8A8_SRGB)
+        );
+
+        // Nothing we can render to
+        let formats = [vk::Format::R8G8B8A8_UNORM];
+        assert_eq!(pick_swapchain_format(&[], &formats), None);
+
+        // Apps can ask for 10-bit color, and fall back if the runtime doesn't have it..
+        let ten_bit = vk::Format::A2B10G10R10_UNORM_PACK32;
+        let formats = [COLOR_FORMAT, ten_bit];
+        assert_eq!(pick_swapchain_format(&[ten_bit], &formats), Some(ten_bit));
+        assert_eq!(
+            pick_swapchain_format(&[ten_bit], &[COLOR_FORMAT]),
+            Some(COLOR_FORMAT)
+        );
+
+        // ..but not for formats we can't render to.
+        let formats = [vk::Format::R8_UNORM, COLOR_FORMAT];
+        assert_eq!(
+            pick_swapchain_format(&[vk::Format::R8_UNORM], &formats),
+            Some(COLOR_FORMAT)
+        );
+        assert!(needs_srgb_encoding(ten_bit));
+        assert!(!needs_srgb_encoding(COLOR_FORMAT));
+    }
 }
@@ -0,0 +1,71 @@
+use ash::vk::Handle;
+use openxr::{self as xr, sys, FrameStream, FrameWaiter, Session, Vulkan};
+
+use crate::contexts::VulkanContext;
+
+/// Settings for running as an overlay, drawn on top of whatever app is running, using `XR_EXTX_overlay`.
+///
+/// Overlays have fewer privileges than a normal app: they usually only get input while the user has focused them, and
+/// are told when the app they're drawn over is hidden. The background is cleared to transparent, so only what's drawn
+/// covers the app underneath.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlaySettings {
+    /// Where this overlay's layers go relative to other overlays. Overlays with a higher placement are drawn on top.
+    pub placement: u32,
+    /// Should the overlay stop showing while the app it's drawn over is hidden, eg. behind the system menu?
+    pub hide_with_main_session: bool,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            placement: 0,
+            hide_with_main_session: true,
+        }
+    }
+}
+
+/// Create a session that runs as an overlay. `openxr` has no way to extend its session create info, so the structs
+/// are chained together by hand.
+pub(crate) fn create_overlay_session(
+    xr_instance: &xr::Instance,
+    system: xr::SystemId,
+    vulkan_context: &VulkanContext,
+    overlay: &OverlaySettings,
+) -> xr::Result<(Session<Vulkan>, FrameWaiter, FrameStream<Vulkan>)> {
+    println!(
+        "[HOTHAM] Creating overlay session with placement {}..",
+        overlay.placement
+    );
+    let overlay_info = sys::SessionCreateInfoOverlayEXTX {
+        ty: sys::SessionCreateInfoOverlayEXTX::TYPE,
+        next: std::ptr::null(),
+        create_flags: xr::OverlaySessionCreateFlagsEXTX::EMPTY,
+        session_layers_placement: overlay.placement,
+    };
+    let graphics_binding = sys::GraphicsBindingVulkanKHR {
+        ty: sys::GraphicsBindingVulkanKHR::TYPE,
+        next: &overlay_info as *const _ as *const _,
+        instance: vulkan_context.instance.handle().as_raw() as _,
+        physical_device: vulkan_context.physical_device.as_raw() as _,
+        device: vulkan_context.device.handle().as_raw() as _,
+        queue_family_index: vulkan_context.queue_family_index,
+        queue_index: 0,
+    };
+    let create_info = sys::SessionCreateInfo {
+        ty: sys::SessionCreateInfo::TYPE,
+        next: &graphics_binding as *const _ as *const _,
+        create_flags: Default::default(),
+        system_id: system,
+    };
+
+    unsafe {
+        let mut handle = sys::Session::NULL;
+        let result =
+            (xr_instance.fp().create_session)(xr_instance.as_raw(), &create_info, &mut handle);
+        if result.into_raw() < 0 {
+            return Err(result);
+        }
+        Ok(Session::from_raw(xr_instance.clone(), handle, Box::new(())))
+    }
+}
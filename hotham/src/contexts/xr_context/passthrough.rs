@@ -0,0 +1,123 @@
+use openxr::{self as xr, raw, sys, CompositionLayerBase, Session, Vulkan};
+
+/// The headset's camera feed, shown behind what the app draws with `XR_FB_passthrough`.
+///
+/// `openxr` doesn't wrap the passthrough extension, so its handles are created and destroyed by hand. The layer holds
+/// on to the session, so the handles are always destroyed before it is. On Quest, the app's manifest needs
+/// `<uses-feature android:name="com.oculus.feature.PASSTHROUGH" android:required="true"/>`.
+pub(crate) struct PassthroughLayer {
+    fp: raw::PassthroughFB,
+    passthrough: sys::PassthroughFB,
+    layer: sys::PassthroughLayerFB,
+    running: bool,
+    /// Keeps the session alive until the passthrough handles have been destroyed
+    _session: Session<Vulkan>,
+}
+
+impl PassthroughLayer {
+    /// Start the camera feed, and create a layer to show it in.
+    pub(crate) fn new(instance: &xr::Instance, session: &Session<Vulkan>) -> xr::Result<Self> {
+        let fp = *instance
+            .exts()
+            .fb_passthrough
+            .as_ref()
+            .ok_or(sys::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+
+        let passthrough_info = sys::PassthroughCreateInfoFB {
+            ty: sys::PassthroughCreateInfoFB::TYPE,
+            next: std::ptr::null(),
+            flags: xr::PassthroughFlagsFB::IS_RUNNING_AT_CREATION,
+        };
+        let mut passthrough = sys::PassthroughFB::NULL;
+        check(unsafe {
+            (fp.create_passthrough)(session.as_raw(), &passthrough_info, &mut passthrough)
+        })?;
+
+        let layer_info = sys::PassthroughLayerCreateInfoFB {
+            ty: sys::PassthroughLayerCreateInfoFB::TYPE,
+            next: std::ptr::null(),
+            passthrough,
+            flags: xr::PassthroughFlagsFB::IS_RUNNING_AT_CREATION,
+            purpose: xr::PassthroughLayerPurposeFB::RECONSTRUCTION,
+        };
+        let mut layer = sys::PassthroughLayerFB::NULL;
+        if let Err(e) = check(unsafe {
+            (fp.create_passthrough_layer)(session.as_raw(), &layer_info, &mut layer)
+        }) {
+            unsafe { (fp.destroy_passthrough)(passthrough) };
+            return Err(e);
+        }
+
+        Ok(Self {
+            fp,
+            passthrough,
+            layer,
+            running: true,
+            _session: session.clone(),
+        })
+    }
+
+    /// Is the camera feed being shown?
+    pub(crate) fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Start showing the camera feed again.
+    pub(crate) fn resume(&mut self) -> xr::Result<()> {
+        if !self.running {
+            check(unsafe { (self.fp.passthrough_start)(self.passthrough) })?;
+            check(unsafe { (self.fp.passthrough_layer_resume)(self.layer) })?;
+            self.running = true;
+        }
+        Ok(())
+    }
+
+    /// Stop showing the camera feed, without destroying the layer. The cameras are turned off, which saves power.
+    pub(crate) fn pause(&mut self) -> xr::Result<()> {
+        if self.running {
+            check(unsafe { (self.fp.passthrough_layer_pause)(self.layer) })?;
+            check(unsafe { (self.fp.passthrough_pause)(self.passthrough) })?;
+            self.running = false;
+        }
+        Ok(())
+    }
+
+    /// The layer to submit underneath the app's projection layer.
+    pub(crate) fn composition_layer(&self) -> sys::CompositionLayerPassthroughFB {
+        sys::CompositionLayerPassthroughFB {
+            ty: sys::CompositionLayerPassthroughFB::TYPE,
+            next: std::ptr::null(),
+            flags: xr::CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA,
+            space: sys::Space::NULL,
+            layer_handle: self.layer,
+        }
+    }
+}
+
+impl Drop for PassthroughLayer {
+    fn drop(&mut self) {
+        unsafe {
+            (self.fp.destroy_passthrough_layer)(self.layer);
+            (self.fp.destroy_passthrough)(self.passthrough);
+        }
+    }
+}
+
+/// Treat `layer` as any other layer, so it can be submitted with the frame.
+///
+/// # Safety
+/// Every composition layer starts with the same header, which is all `CompositionLayerBase` looks at. `layer` must
+/// outlive the frame it's submitted with.
+pub(crate) unsafe fn as_layer_base(
+    layer: &sys::CompositionLayerPassthroughFB,
+) -> &CompositionLayerBase<'_, Vulkan> {
+    &*(layer as *const sys::CompositionLayerPassthroughFB as *const CompositionLayerBase<Vulkan>)
+}
+
+fn check(result: sys::Result) -> xr::Result<()> {
+    if result.into_raw() < 0 {
+        Err(result)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use openxr as xr;
+
+use super::XrContext;
+use crate::HothamResult;
+
+/// Adds support for an OpenXR extension from outside Hotham, so niche vendor extensions can live in their own crates
+/// rather than in [`XrContext`].
+///
+/// Add a plugin with [`crate::EngineBuilder::xr_plugin`]. It's then called:
+/// 1. before the instance is created, to ask for the extensions it needs
+/// 2. once the instance and session have been created, to set itself up
+/// 3. for every event the runtime sends
+/// 4. at the start of every frame
+///
+/// `openxr` only wraps the extensions it knows about, so plugins for newer ones ask for them by name through
+/// [`xr::ExtensionSet::other`], then call into them with the raw handles from [`XrContext::instance`] and
+/// [`XrContext::session`] (`as_raw()`), looking up their functions with [`XrContext::get_instance_proc_addr`].
+///
+/// Every method has a default that does nothing, so plugins only need to implement the ones they use.
+pub trait XrPlugin: Send {
+    /// The plugin's name, for logging
+    fn name(&self) -> &str;
+
+    /// Enable the extensions the plugin needs in `enabled`, if they're in `available`. Called before the instance is
+    /// created.
+    fn request_extensions(
+        &mut self,
+        _available: &xr::ExtensionSet,
+        _enabled: &mut xr::ExtensionSet,
+    ) {
+    }
+
+    /// Called once the instance and session have been created. Check [`XrContext::enabled_extensions`] to find out
+    /// if the extensions the plugin asked for were enabled.
+    fn on_session_created(&mut self, _xr_context: &XrContext) -> HothamResult<()> {
+        Ok(())
+    }
+
+    /// Called with every event polled from the runtime, before Hotham handles it.
+    fn on_event(&mut self, _xr_context: &XrContext, _event: &xr::Event) {}
+
+    /// Called at the start of every frame, once the frame has begun. The frame may not be rendered - check
+    /// [`XrContext::frame_state`].
+    fn on_frame(&mut self, _xr_context: &XrContext) {}
+}
+
+/// Let each plugin enable the extensions it needs.
+pub(crate) fn request_extensions(
+    plugins: &mut [Box<dyn XrPlugin>],
+    available: &xr::ExtensionSet,
+    enabled: &mut xr::ExtensionSet,
+) {
+    for plugin in plugins {
+        plugin.request_extensions(available, enabled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct VendorExtension;
+
+    impl XrPlugin for VendorExtension {
+        fn name(&self) -> &str {
+            "Vendor extension"
+        }
+
+        fn request_extensions(
+            &mut self,
+            available: &xr::ExtensionSet,
+            enabled: &mut xr::ExtensionSet,
+        ) {
+            const EXTENSION: &str = "XR_VENDOR_example";
+            if available.other.iter().any(|e| e == EXTENSION) {
+                enabled.other.push(EXTENSION.to_string());
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_request_extensions() {
+        let mut plugins: Vec<Box<dyn XrPlugin>> = vec![Box::new(VendorExtension)];
+        let mut enabled = xr::ExtensionSet::default();
+
+        // Plugins only ask for extensions the runtime has..
+        request_extensions(&mut plugins, &xr::ExtensionSet::default(), &mut enabled);
+        assert!(enabled.other.is_empty());
+
+        // ..and can ask for them by name.
+        let mut available = xr::ExtensionSet::default();
+        available.other.push("XR_VENDOR_example".to_string());
+        request_extensions(&mut plugins, &available, &mut enabled);
+        assert_eq!(enabled.other, vec!["XR_VENDOR_example".to_string()]);
+    }
+}
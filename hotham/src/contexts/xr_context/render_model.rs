@@ -0,0 +1,88 @@
+use std::ptr;
+
+use openxr::{self as xr, sys, Session, Vulkan};
+
+use crate::components::hand::Handedness;
+
+/// The path of the render model of the controller on `handedness`'s side
+pub(crate) fn controller_model_path(handedness: Handedness) -> &'static str {
+    match handedness {
+        Handedness::Left => "/model_fb/controller/left",
+        Handedness::Right => "/model_fb/controller/right",
+    }
+}
+
+/// Load the runtime's model of the controller on `handedness`'s side with `XR_FB_render_model`, as a glb. `openxr`
+/// doesn't wrap the extension, so it's called by hand.
+///
+/// Returns `None` if the runtime has no model for that controller, eg. because it isn't connected.
+pub(crate) fn load_controller_model(
+    instance: &xr::Instance,
+    session: &Session<Vulkan>,
+    handedness: Handedness,
+) -> xr::Result<Option<Vec<u8>>> {
+    let fp = instance
+        .exts()
+        .fb_render_model
+        .as_ref()
+        .ok_or(sys::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+    let path = instance.string_to_path(controller_model_path(handedness))?;
+
+    // The runtime only hands out models whose paths have been enumerated.
+    let mut count = 0;
+    check(unsafe {
+        (fp.enumerate_render_model_paths)(session.as_raw(), 0, &mut count, ptr::null_mut())
+    })?;
+    let mut paths = vec![
+        sys::RenderModelPathInfoFB {
+            ty: sys::RenderModelPathInfoFB::TYPE,
+            next: ptr::null_mut(),
+            path: xr::Path::NULL,
+        };
+        count as usize
+    ];
+    check(unsafe {
+        (fp.enumerate_render_model_paths)(session.as_raw(), count, &mut count, paths.as_mut_ptr())
+    })?;
+    if !paths.iter().any(|info| info.path == path) {
+        return Ok(None);
+    }
+
+    let mut properties: sys::RenderModelPropertiesFB = unsafe { std::mem::zeroed() };
+    properties.ty = sys::RenderModelPropertiesFB::TYPE;
+    let result =
+        unsafe { (fp.get_render_model_properties)(session.as_raw(), path, &mut properties) };
+    check(result)?;
+    if result == sys::Result::RENDER_MODEL_UNAVAILABLE_FB {
+        return Ok(None);
+    }
+
+    let load_info = sys::RenderModelLoadInfoFB {
+        ty: sys::RenderModelLoadInfoFB::TYPE,
+        next: ptr::null_mut(),
+        model_key: properties.model_key,
+    };
+    let mut buffer = sys::RenderModelBufferFB {
+        ty: sys::RenderModelBufferFB::TYPE,
+        next: ptr::null_mut(),
+        buffer_capacity_input: 0,
+        buffer_count_output: 0,
+        buffer: ptr::null_mut(),
+    };
+    check(unsafe { (fp.load_render_model)(session.as_raw(), &load_info, &mut buffer) })?;
+    let mut glb = vec![0; buffer.buffer_count_output as usize];
+    buffer.buffer_capacity_input = glb.len() as _;
+    buffer.buffer = glb.as_mut_ptr();
+    check(unsafe { (fp.load_render_model)(session.as_raw(), &load_info, &mut buffer) })?;
+    glb.truncate(buffer.buffer_count_output as usize);
+
+    Ok(Some(glb))
+}
+
+fn check(result: sys::Result) -> xr::Result<()> {
+    if result.into_raw() < 0 {
+        Err(result)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use ash::vk;
+use openxr::{self as xr, EnvironmentBlendMode, ReferenceSpaceType};
+
+use crate::BLEND_MODE;
+
+/// The OpenXR runtime an app is running on, for working around the ways runtimes differ.
+///
+/// Anything that can be asked of the runtime directly, like the formats or blend modes it supports, should be - this
+/// is only for behaviour that can't be queried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrRuntime {
+    Oculus,
+    SteamVR,
+    WindowsMixedReality,
+    Monado,
+    Pico,
+    ViveWave,
+    HothamSimulator,
+    Unknown,
+}
+
+impl XrRuntime {
+    /// Work out which runtime this is from the name it reports in its instance properties.
+    pub fn from_name(runtime_name: &str) -> Self {
+        let name = runtime_name.to_lowercase();
+        if name.contains("oculus") || name.contains("meta") {
+            XrRuntime::Oculus
+        } else if name.contains("steamvr") {
+            XrRuntime::SteamVR
+        } else if name.contains("windows mixed reality") {
+            XrRuntime::WindowsMixedReality
+        } else if name.contains("monado") {
+            XrRuntime::Monado
+        } else if name.contains("pico") {
+            XrRuntime::Pico
+        } else if name.contains("wave") {
+            XrRuntime::ViveWave
+        } else if name.contains("hotham simulator") {
+            XrRuntime::HothamSimulator
+        } else {
+            XrRuntime::Unknown
+        }
+    }
+
+    /// Can swapchains be created with `SwapchainCreateFlags::STATIC_IMAGE`? Monado rejects them, so there's no splash
+    /// screen there.
+    pub fn supports_static_swapchains(self) -> bool {
+        self != XrRuntime::Monado
+    }
+}
+
+/// Pick how our frames are blended with the real world. We'd rather be `BLEND_MODE`, but an AR headset may only offer
+/// alpha blended or additive modes.
+pub(crate) fn pick_blend_mode(
+    supported_modes: &[EnvironmentBlendMode],
+) -> Option<EnvironmentBlendMode> {
+    [
+        BLEND_MODE,
+        EnvironmentBlendMode::ALPHA_BLEND,
+        EnvironmentBlendMode::ADDITIVE,
+    ]
+    .iter()
+    .find(|m| supported_modes.contains(m))
+    .copied()
+}
+
+/// Pick the space the world is drawn in. Runtimes without a play area set up (eg. Monado, or Windows Mixed Reality
+/// before the boundary is drawn) have no `STAGE`, so fall back to `LOCAL`, which every runtime supports.
+pub(crate) fn pick_reference_space(supported_spaces: &[ReferenceSpaceType]) -> ReferenceSpaceType {
+    if supported_spaces.contains(&ReferenceSpaceType::STAGE) {
+        ReferenceSpaceType::STAGE
+    } else {
+        ReferenceSpaceType::LOCAL
+    }
+}
+
+/// The size of the swapchain images. Desktop runtimes can recommend a slightly different size for each eye, but we
+/// render both eyes into one multiview image, so use the largest that fits.
+pub(crate) fn swapchain_resolution(views: &[xr::ViewConfigurationView]) -> Option<vk::Extent2D> {
+    let width = views.iter().map(|v| v.recommended_image_rect_width).max()?;
+    let height = views
+        .iter()
+        .map(|v| v.recommended_image_rect_height)
+        .max()?;
+    let max_width = views.iter().map(|v| v.max_image_rect_width).min()?;
+    let max_height = views.iter().map(|v| v.max_image_rect_height).min()?;
+
+    Some(vk::Extent2D {
+        width: width.min(max_width),
+        height: height.min(max_height),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_runtime_from_name() {
+        let runtimes = [
+            ("Oculus", XrRuntime::Oculus),
+            ("SteamVR/OpenXR", XrRuntime::SteamVR),
+            (
+                "Windows Mixed Reality Runtime",
+                XrRuntime::WindowsMixedReality,
+            ),
+            ("Monado(XRT) by Collabora et al", XrRuntime::Monado),
+            ("Pico OpenXR", XrRuntime::Pico),
+            ("VIVE WAVE OpenXR", XrRuntime::ViveWave),
+            ("Hotham Simulator", XrRuntime::HothamSimulator),
+            ("Some New Runtime", XrRuntime::Unknown),
+        ];
+        for (name, runtime) in runtimes {
+            assert_eq!(XrRuntime::from_name(name), runtime, "{}", name);
+        }
+        assert!(!XrRuntime::Monado.supports_static_swapchains());
+        assert!(XrRuntime::SteamVR.supports_static_swapchains());
+    }
+
+    #[test]
+    pub fn test_pick_blend_mode_and_space() {
+        use EnvironmentBlendMode as Mode;
+        assert_eq!(
+            pick_blend_mode(&[Mode::ADDITIVE, Mode::OPAQUE]),
+            Some(Mode::OPAQUE)
+        );
+        assert_eq!(pick_blend_mode(&[Mode::ADDITIVE]), Some(Mode::ADDITIVE));
+        assert_eq!(pick_blend_mode(&[]), None);
+
+        use ReferenceSpaceType as Space;
+        assert_eq!(
+            pick_reference_space(&[Space::VIEW, Space::LOCAL, Space::STAGE]),
+            Space::STAGE
+        );
+        assert_eq!(
+            pick_reference_space(&[Space::VIEW, Space::LOCAL]),
+            Space::LOCAL
+        );
+    }
+
+    #[test]
+    pub fn test_swapchain_resolution() {
+        let view = |width, height| xr::ViewConfigurationView {
+            recommended_image_rect_width: width,
+            recommended_image_rect_height: height,
+            max_image_rect_width: 4096,
+            max_image_rect_height: 2048,
+            recommended_swapchain_sample_count: 1,
+            max_swapchain_sample_count: 4,
+        };
+
+        let resolution = swapchain_resolution(&[view(2016, 2224), view(2020, 2220)]).unwrap();
+        assert_eq!(resolution.width, 2020);
+        assert_eq!(resolution.height, 2048);
+
+        assert!(swapchain_resolution(&[]).is_none());
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+
+/// Set this to the path of the simulator's library to use it even when an OpenXR runtime is installed.
+pub const SIMULATOR_PATH_VAR: &str = "HOTHAM_SIMULATOR";
+
+#[cfg(target_os = "windows")]
+const SIMULATOR_LIBRARY: &str = "hotham_simulator.dll";
+#[cfg(not(target_os = "windows"))]
+const SIMULATOR_LIBRARY: &str = "libhotham_simulator.so";
+
+/// The simulator the app has been asked to use with `HOTHAM_SIMULATOR`, if any.
+pub(crate) fn requested() -> Option<PathBuf> {
+    std::env::var_os(SIMULATOR_PATH_VAR).map(PathBuf::from)
+}
+
+/// Look for a simulator built in the same workspace as the app, to fall back to when there's no OpenXR runtime.
+pub(crate) fn find() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    candidates(&exe).into_iter().find(|p| p.is_file())
+}
+
+/// Cargo puts the simulator next to apps, and a directory up from examples and tests.
+fn candidates(exe: &Path) -> Vec<PathBuf> {
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(SIMULATOR_LIBRARY))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_candidates() {
+        let debug = Path::new("target").join("debug");
+        let examples = debug.join("examples");
+        assert_eq!(
+            candidates(&examples.join("beat_saber")),
+            vec![
+                examples.join(SIMULATOR_LIBRARY),
+                debug.join(SIMULATOR_LIBRARY)
+            ]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    panic,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, ThreadId},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ash::vk;
+use glam::{Vec2, Vec3};
+use openxr as xr;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{ui_panel::add_ui_panel_to_world, Parent, Visible},
+    contexts::StorageContext,
+    logging::LogHistory,
+    systems::{
+        draw_gui_system, rendering_system, update_global_transform_system,
+        update_global_transform_with_parent_system,
+    },
+    Engine, HothamResult,
+};
+
+/// The [`StorageContext`] key the most recent crash report is stored under.
+pub const CRASH_REPORT_KEY: &str = "crash_report";
+
+/// How many log entries are included in a crash report.
+const CRASH_REPORT_LOG_LINES: usize = 32;
+
+/// How long the fatal error panel is shown for, unless the player dismisses it first.
+const FATAL_ERROR_PANEL_DURATION: Duration = Duration::from_secs(15);
+
+/// Everything we know about a panic, written to storage by the panic hook the `Engine` installs.
+///
+/// Check for one when your app starts with [`CrashReport::take`] to find out if the last run ended badly, eg. to
+/// upload it or to tell the player what happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// The panic message
+    pub message: String,
+    /// Where the panic happened, as `file:line:column`
+    pub location: Option<String>,
+    /// The name of the thread that panicked
+    pub thread: Option<String>,
+    /// The backtrace, as captured when the panic happened
+    pub backtrace: String,
+    /// When the panic happened, in seconds since the UNIX epoch
+    pub timestamp: u64,
+    /// The last few entries logged before the panic
+    pub recent_logs: Vec<String>,
+}
+
+impl CrashReport {
+    fn new(message: String, location: Option<String>, log_history: &LogHistory) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            message,
+            location,
+            thread: thread::current().name().map(String::from),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            timestamp,
+            recent_logs: log_history
+                .recent(log::Level::Trace, CRASH_REPORT_LOG_LINES)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+
+    /// Read the crash report left behind by the last panic, if any, and remove it from storage so it's only seen once.
+    pub fn take(storage_context: &StorageContext) -> HothamResult<Option<CrashReport>> {
+        let report = storage_context.get(CRASH_REPORT_KEY)?;
+        storage_context.remove(CRASH_REPORT_KEY)?;
+        Ok(report)
+    }
+
+    /// A short description of the crash, suitable for showing to a player.
+    pub fn summary(&self) -> String {
+        let location = self.location.as_deref().unwrap_or("an unknown location");
+        format!(
+            "Sorry, something went wrong and this app has to close.\n\n{}\n\nat {}\n\nPress A or X to quit.",
+            self.message, location
+        )
+    }
+}
+
+/// State shared between the panic hook and [`Engine::run_with_crash_handler`].
+#[derive(Debug, Default)]
+pub(crate) struct CrashState {
+    /// The report for the most recent panic
+    pub report: Mutex<Option<CrashReport>>,
+    /// The thread `run_with_crash_handler` is catching panics on, if it's running
+    pub handler_thread: Mutex<Option<ThreadId>>,
+}
+
+impl CrashState {
+    pub fn take_report(&self) -> Option<CrashReport> {
+        self.report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    pub fn set_handler_thread(&self, thread: Option<ThreadId>) {
+        *self
+            .handler_thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = thread;
+    }
+
+    fn is_handled(&self, thread: ThreadId) -> bool {
+        *self
+            .handler_thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            == Some(thread)
+    }
+}
+
+/// Install a panic hook that:
+///
+/// - writes a [`CrashReport`] to the storage in `storage_root`
+/// - asks the runtime to end the session, unless `run_with_crash_handler` will do it for us
+/// - then carries on with the previous hook, eg. to print the panic
+pub(crate) fn install_panic_hook(
+    storage_root: PathBuf,
+    session: xr::Session<xr::Vulkan>,
+    log_history: LogHistory,
+    crash_state: Arc<CrashState>,
+) {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "Box<dyn Any>".to_string(),
+            },
+        };
+        let location = info.location().map(ToString::to_string);
+        let report = CrashReport::new(message, location, &log_history);
+
+        log::error!(
+            "[HOTHAM_CRASH] Panicked at {}: {}",
+            report.location.as_deref().unwrap_or("unknown location"),
+            report.message
+        );
+        if let Err(e) = StorageContext::with_root(&storage_root).set(CRASH_REPORT_KEY, &report) {
+            log::error!("[HOTHAM_CRASH] Unable to write crash report: {:?}", e);
+        }
+
+        // If nobody is going to catch this panic, at least let the runtime know we're going away.
+        if !crash_state.is_handled(thread::current().id()) {
+            if let Err(e) = session.request_exit() {
+                log::error!("[HOTHAM_CRASH] Unable to request session exit: {:?}", e);
+            }
+        }
+
+        *crash_state
+            .report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(report);
+
+        previous_hook(info);
+    }));
+}
+
+/// Show `report` in a panel in front of the player until they dismiss it, it times out or the session ends.
+pub(crate) fn show_fatal_error_panel(engine: &mut Engine, report: &CrashReport) {
+    // Hide everything else, in case it's what's broken.
+    let visible = engine
+        .world
+        .query::<()>()
+        .with::<&Visible>()
+        .iter()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    for entity in visible {
+        let _ = engine.world.remove_one::<Visible>(entity);
+    }
+
+    let panel = add_ui_panel_to_world(
+        &report.summary(),
+        vk::Extent2D {
+            width: 800,
+            height: 600,
+        },
+        Vec2::new(1.2, 0.9),
+        Vec3::new(0., 0., -1.5),
+        vec![],
+        &engine.vulkan_context,
+        &mut engine.render_context,
+        &engine.gui_context,
+        &mut engine.world,
+    );
+    engine
+        .world
+        .insert_one(panel, Parent(engine.hmd_entity))
+        .unwrap();
+
+    let start = Instant::now();
+    while start.elapsed() < FATAL_ERROR_PANEL_DURATION {
+        let tick_data = match engine.update() {
+            Ok(tick_data) => tick_data,
+            Err(_) => return,
+        };
+
+        update_global_transform_system(engine);
+        update_global_transform_with_parent_system(engine);
+        draw_gui_system(engine);
+        rendering_system(engine, tick_data.swapchain_image_index);
+        if engine.finish().is_err() {
+            return;
+        }
+
+        let input_context = &engine.input_context;
+        if input_context.left.x_button_just_pressed() || input_context.right.a_button_just_pressed()
+        {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LogEntry;
+
+    #[test]
+    pub fn test_crash_report() {
+        let log_history = LogHistory::new(10);
+        log_history.push(LogEntry {
+            level: log::Level::Info,
+            target: "hotham".to_string(),
+            message: "Loaded models".to_string(),
+        });
+
+        let report = CrashReport::new(
+            "Index out of bounds".to_string(),
+            Some("src/lib.rs:10:5".to_string()),
+            &log_history,
+        );
+        assert_eq!(report.recent_logs, vec!["INFO  hotham - Loaded models"]);
+        assert!(report
+            .summary()
+            .contains("Index out of bounds\n\nat src/lib.rs:10:5"));
+
+        // Reports should only be taken once.
+        let root = std::env::temp_dir().join(format!("hotham_crash_test_{}", std::process::id()));
+        let storage_context = StorageContext::with_root(&root);
+        storage_context.set(CRASH_REPORT_KEY, &report).unwrap();
+        assert_eq!(CrashReport::take(&storage_context).unwrap(), Some(report));
+        assert_eq!(CrashReport::take(&storage_context).unwrap(), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,24 +1,51 @@
 use crate::{
+    analytics::Analytics,
+    captions::Captions,
     components::{GlobalTransform, LocalTransform, Parent, Stage, HMD},
     contexts::{
-        AudioContext, GuiContext, HapticContext, InputContext, PhysicsContext, RenderContext,
-        VulkanContext, XrContext, XrContextBuilder,
+        device_context::PerformanceNotification, physics_context::DELTA_TIME,
+        xr_context::ActionSetSettings, AudioContext, DeviceContext, EffectsContext, GuiContext,
+        HandTrackingContext, HapticContext, InputContext, OptionalExtensions, OverlaySettings,
+        PermissionsContext, PhysicalDeviceSettings, PhysicsContext, PlatformContext, RenderContext,
+        StorageContext, TimeContext, VulkanContext, XrContext, XrContextBuilder, XrPlugin,
     },
+    crash::{self, CrashState},
+    frame_pacing::FramePacing,
+    input_recording::{CameraPath, InputPlayback, InputRecorder, InputSource},
+    intent::{self, Intent},
+    localization::Localization,
+    logging::{LogHistory, LogSink},
+    memory_stats::MemoryStats,
+    paths::Paths,
+    rendering::{
+        fog::FogQuality,
+        spectator::{SpectatorCamera, SpectatorView},
+    },
+    snapshot::{Snapshot, Snapshots, SNAPSHOT_KEY},
+    splash_screen::{SplashLayer, SplashScreen},
+    util::posef_from_affine,
     HothamError, HothamResult, VIEW_TYPE,
 };
+use ash::vk;
+use log::LevelFilter;
 use openxr as xr;
 
 use std::{
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use xr::{EventDataBuffer, SessionState};
 
+/// Temporary files older than this are removed when the engine starts.
+const TEMP_FILE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
 #[cfg(target_os = "android")]
 pub static ANDROID_LOOPER_ID_MAIN: u32 = 0;
 #[cfg(target_os = "android")]
@@ -32,6 +59,26 @@ pub struct EngineBuilder<'a> {
     application_name: Option<&'a str>,
     application_version: Option<u32>,
     openxr_extensions: Option<xr::ExtensionSet>,
+    optional_openxr_extensions: Option<OptionalExtensions>,
+    remote_log_port: Option<u16>,
+    show_fatal_error_panel: bool,
+    record_input: Option<PathBuf>,
+    play_input: Option<PathBuf>,
+    loop_input: bool,
+    camera_path: Option<CameraPath>,
+    reversed_z: Option<bool>,
+    splash_screen: Option<SplashScreen>,
+    spectator_camera: Option<SpectatorCamera>,
+    volumetric_fog: Option<FogQuality>,
+    overlay: Option<OverlaySettings>,
+    action_sets: Vec<ActionSetSettings>,
+    xr_plugins: Vec<Box<dyn XrPlugin>>,
+    swapchain_formats: Vec<vk::Format>,
+    physical_device: PhysicalDeviceSettings,
+    #[cfg(feature = "inspector")]
+    inspector_port: Option<u16>,
+    #[cfg(feature = "inspector")]
+    inspector_on_lan: bool,
 }
 
 impl<'a> EngineBuilder<'a> {
@@ -58,15 +105,150 @@ impl<'a> EngineBuilder<'a> {
         self
     }
 
+    /// Enable any OpenXR extensions the runtime has that the app can use, but doesn't need. Check
+    /// [`XrContext::enabled_extensions`] to find out which were enabled.
+    pub fn optional_openxr_extensions(
+        &mut self,
+        optional_extensions: Option<OptionalExtensions>,
+    ) -> &mut Self {
+        self.optional_openxr_extensions = optional_extensions;
+        self
+    }
+
+    /// Add a plugin for an OpenXR extension Hotham doesn't support itself. See [`XrPlugin`].
+    pub fn xr_plugin(&mut self, plugin: impl XrPlugin + 'static) -> &mut Self {
+        self.xr_plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// The swapchain formats to render to, best first - eg. a UNORM or 10-bit format instead of the default sRGB one.
+    /// The first one the runtime supports is used; check [`XrContext::swapchain_format`] to see which.
+    pub fn swapchain_formats(&mut self, formats: Vec<vk::Format>) -> &mut Self {
+        self.swapchain_formats = formats;
+        self
+    }
+
+    /// Which GPU to render with, on machines with more than one. By default, the one the OpenXR runtime asks for is
+    /// used - see [`PhysicalDeviceSettings`].
+    pub fn physical_device(&mut self, settings: PhysicalDeviceSettings) -> &mut Self {
+        self.physical_device = settings;
+        self
+    }
+
+    /// Stream logs over TCP on this port, so they can be read on a desktop. See [`LogSink`].
+    pub fn remote_log_port(&mut self, port: Option<u16>) -> &mut Self {
+        self.remote_log_port = port;
+        self
+    }
+
+    /// Show a panel describing the crash when [`Engine::run_with_crash_handler`] catches a panic
+    pub fn show_fatal_error_panel(&mut self, show: bool) -> &mut Self {
+        self.show_fatal_error_panel = show;
+        self
+    }
+
+    /// Record the HMD and controller input for each frame to the file at `path`, so the session can be replayed with
+    /// [`EngineBuilder::play_input`]. See [`InputRecorder`].
+    pub fn record_input(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.record_input = Some(path.into());
+        self
+    }
+
+    /// Ignore the real HMD and controllers, and replay the input recorded by [`EngineBuilder::record_input`] from the
+    /// file at `path` instead. The engine shuts down once the recording has finished.
+    ///
+    /// The same systems will see the same input on the same frames, so bugs can be reproduced without putting the
+    /// headset on and performance can be compared between runs.
+    pub fn play_input(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.play_input = Some(path.into());
+        self
+    }
+
+    /// Start the recording passed to [`EngineBuilder::play_input`] again whenever it finishes, instead of shutting
+    /// down. Useful for soak tests - see [`crate::soak_test::SoakTest`].
+    pub fn loop_input(&mut self, loop_input: bool) -> &mut Self {
+        self.loop_input = loop_input;
+        self
+    }
+
+    /// Ignore the real HMD and controllers, and move the HMD along `camera_path` instead, over and over. Ignored if
+    /// [`EngineBuilder::play_input`] is used.
+    pub fn play_camera_path(&mut self, camera_path: CameraPath) -> &mut Self {
+        self.camera_path = Some(camera_path);
+        self
+    }
+
+    /// Use a reversed-Z depth buffer, with the near plane at depth 1 and the far plane at depth 0. This gives much
+    /// better depth precision in large scenes, so it's on by default.
+    pub fn reversed_z(&mut self, reversed_z: bool) -> &mut Self {
+        self.reversed_z = Some(reversed_z);
+        self
+    }
+
+    /// Show `splash_screen` as soon as the session starts, instead of the app's first frames
+    pub fn splash_screen(&mut self, splash_screen: SplashScreen) -> &mut Self {
+        self.splash_screen = Some(splash_screen);
+        self
+    }
+
+    /// Render a spectator view from `spectator_camera`, alongside the headset's view
+    pub fn spectator_camera(&mut self, spectator_camera: SpectatorCamera) -> &mut Self {
+        self.spectator_camera = Some(spectator_camera);
+        self
+    }
+
+    /// Draw volumetric fog inside [`crate::components::FogVolume`]s, computed at `quality`. Use
+    /// [`FogQuality::Low`] on standalone headsets.
+    pub fn volumetric_fog(&mut self, quality: FogQuality) -> &mut Self {
+        self.volumetric_fog = Some(quality);
+        self
+    }
+
+    /// Run as an overlay on top of other apps, using `XR_EXTX_overlay`
+    pub fn overlay(&mut self, overlay: OverlaySettings) -> &mut Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Add an input context of the app's own, eg. one for gameplay and one for menus. Push and pop it with
+    /// [`XrContext::push_input_context`] once the engine is running, finding it with
+    /// [`crate::contexts::xr_context::ActionSets::find`].
+    pub fn action_set(&mut self, action_set: ActionSetSettings) -> &mut Self {
+        self.action_sets.push(action_set);
+        self
+    }
+
+    /// Listen for the inspector UI on this port, instead of [`crate::contexts::inspector_context::DEFAULT_INSPECTOR_PORT`]
+    #[cfg(feature = "inspector")]
+    pub fn inspector_port(&mut self, port: u16) -> &mut Self {
+        self.inspector_port = Some(port);
+        self
+    }
+
+    /// Let anything on the network connect to the inspector, rather than only `adb forward`. It has no
+    /// authentication, so only do this on a network you trust.
+    #[cfg(feature = "inspector")]
+    pub fn inspector_on_lan(&mut self, on_lan: bool) -> &mut Self {
+        self.inspector_on_lan = on_lan;
+        self
+    }
+
     /// Build the `Engine`
     pub fn build(self) -> Engine {
+        // Capture logs first, so nothing is missed.
+        let log_history =
+            LogSink::install(LevelFilter::Info, self.remote_log_port).unwrap_or_else(|e| {
+                println!("[HOTHAM_ENGINE] Unable to install log sink: {:?}", e);
+                LogHistory::new(0)
+            });
+
         #[allow(unused_mut)] // Only Android mutates this.
         let mut resumed = false;
         let should_quit = Arc::new(AtomicBool::from(false));
 
         // Before we do ANYTHING - we should process android events
         #[cfg(target_os = "android")]
-        process_android_events(&mut resumed, &should_quit);
+        process_android_events(&mut resumed, &should_quit, &mut Vec::new());
 
         // On desktop, register a Ctrl-C handler.
         #[cfg(not(target_os = "android"))]
@@ -76,15 +258,118 @@ impl<'a> EngineBuilder<'a> {
         }
 
         // Now initialize the engine.
-        let (xr_context, vulkan_context) = XrContextBuilder::new()
+        let (mut xr_context, vulkan_context) = XrContextBuilder::new()
             .application_name(self.application_name)
             .application_version(self.application_version)
             .required_extensions(self.openxr_extensions)
+            .optional_extensions(self.optional_openxr_extensions)
+            .overlay(self.overlay)
+            .action_sets(self.action_sets)
+            .plugins(self.xr_plugins)
+            .swapchain_formats(self.swapchain_formats)
+            .physical_device(self.physical_device)
             .build()
             .expect("!!FATAL ERROR - Unable to initialize OpenXR!!");
-        let render_context = RenderContext::new(&vulkan_context, &xr_context)
-            .expect("!!FATAL ERROR - Unable to initialize renderer!");
+        match &self.splash_screen {
+            Some(_) if !xr_context.runtime.supports_static_swapchains() => {
+                log::error!(
+                    "[HOTHAM_ENGINE] Splash screens can't be shown on {:?}",
+                    xr_context.runtime
+                );
+            }
+            Some(splash_screen) => {
+                xr_context.splash_layer = SplashLayer::new(
+                    splash_screen,
+                    &xr_context.session,
+                    &vulkan_context,
+                    xr_context.splash_screen_format(),
+                )
+                .map_err(|e| log::error!("[HOTHAM_ENGINE] Unable to show splash screen: {:?}", e))
+                .ok();
+            }
+            None => {}
+        }
+        let mut render_context = RenderContext::with_reversed_z(
+            &vulkan_context,
+            &xr_context,
+            self.reversed_z.unwrap_or(true),
+        )
+        .expect("!!FATAL ERROR - Unable to initialize renderer!");
+        if self.overlay.is_some() {
+            render_context.clear_color = [0.0, 0.0, 0.0, 0.0];
+        }
+        if let Some(quality) = self.volumetric_fog {
+            render_context
+                .enable_volumetric_fog(&vulkan_context, quality)
+                .unwrap_or_else(|e| {
+                    log::error!("[HOTHAM_ENGINE] Unable to enable volumetric fog: {:?}", e)
+                });
+        }
+        let spectator_view = self.spectator_camera.and_then(|camera| {
+            SpectatorView::new(&vulkan_context, &render_context, camera)
+                .map_err(|e| {
+                    log::error!("[HOTHAM_ENGINE] Unable to create spectator view: {:?}", e)
+                })
+                .ok()
+        });
         let gui_context = GuiContext::new(&vulkan_context);
+        let paths = Paths::new(self.application_name.unwrap_or("Hotham"));
+        let storage_context = StorageContext::with_root(paths.internal());
+        match paths.clean_temp_files(TEMP_FILE_MAX_AGE) {
+            Ok(0) => {}
+            Ok(removed) => log::info!("[HOTHAM_ENGINE] Removed {} old temporary files", removed),
+            Err(e) => log::error!(
+                "[HOTHAM_ENGINE] Unable to remove old temporary files: {:?}",
+                e
+            ),
+        }
+
+        // Now that we have somewhere to write crash reports, and a session to end, we can handle panics.
+        let crash_state = Arc::new(CrashState::default());
+        crash::install_panic_hook(
+            storage_context.root().to_path_buf(),
+            xr_context.session.clone(),
+            log_history.clone(),
+            crash_state.clone(),
+        );
+
+        #[cfg(feature = "inspector")]
+        let inspector_context = crate::contexts::InspectorContext::new(
+            self.inspector_port
+                .unwrap_or(crate::contexts::inspector_context::DEFAULT_INSPECTOR_PORT),
+            self.inspector_on_lan,
+        )
+        .map_err(|e| log::error!("[HOTHAM_ENGINE] Unable to start inspector: {:?}", e))
+        .ok();
+
+        let loop_input = self.loop_input;
+        let input_source = match (self.play_input, self.camera_path, self.record_input) {
+            (Some(path), _, _) => {
+                log::info!("[HOTHAM_ENGINE] Playing input from {}", path.display());
+                InputPlayback::open(path).map(|mut playback| {
+                    playback.set_looping(loop_input);
+                    InputSource::Playback(playback)
+                })
+            }
+            (None, Some(camera_path), _) => {
+                log::info!("[HOTHAM_ENGINE] Following a camera path");
+                Ok(InputSource::CameraPath {
+                    camera_path,
+                    frames: 0,
+                })
+            }
+            (None, None, Some(path)) => {
+                log::info!("[HOTHAM_ENGINE] Recording input to {}", path.display());
+                InputRecorder::create(path).map(InputSource::Recording)
+            }
+            (None, None, None) => Ok(InputSource::Live),
+        }
+        .expect("!!FATAL ERROR - Unable to use input recording!!");
+
+        let launch_intent = intent::current_intent();
+        if let Some(launch_intent) = &launch_intent {
+            log::info!("[HOTHAM_ENGINE] Launched with {:?}", launch_intent);
+        }
 
         // Initialize the world with our "tracking" entities, the stage and the HMD.
         let mut world = hecs::World::default();
@@ -95,14 +380,49 @@ impl<'a> EngineBuilder<'a> {
             should_quit,
             resumed,
             event_data_buffer: Default::default(),
+            frame_in_progress: false,
+            pending_events: launch_intent
+                .iter()
+                .cloned()
+                .map(EngineEvent::LaunchIntent)
+                .collect(),
+            current_intent: launch_intent.clone(),
+            launch_intent,
+            crash_state,
+            show_fatal_error_panel: self.show_fatal_error_panel,
+            input_source,
             xr_context,
             vulkan_context,
             render_context,
             audio_context: Default::default(),
             gui_context,
             haptic_context: Default::default(),
+            effects_context: Default::default(),
             input_context: Default::default(),
+            hand_tracking_context: Default::default(),
             physics_context: Default::default(),
+            storage_context,
+            paths,
+            platform_context: Default::default(),
+            permissions_context: Default::default(),
+            device_context: Default::default(),
+            localization: Default::default(),
+            captions: Default::default(),
+            analytics: Default::default(),
+            snapshots: Default::default(),
+            time_context: Default::default(),
+            #[cfg(feature = "wasm-scripting")]
+            script_context: Default::default(),
+            #[cfg(feature = "lua-scripting")]
+            lua_context: Default::default(),
+            #[cfg(feature = "http")]
+            http_context: Default::default(),
+            #[cfg(feature = "inspector")]
+            inspector_context,
+            log_history,
+            memory_stats: Default::default(),
+            frame_pacing: Default::default(),
+            spectator_view,
             stage_entity,
             hmd_entity,
         }
@@ -132,6 +452,13 @@ pub struct Engine {
     #[allow(dead_code)]
     resumed: bool,
     event_data_buffer: EventDataBuffer,
+    frame_in_progress: bool,
+    pending_events: Vec<EngineEvent>,
+    launch_intent: Option<Intent>,
+    current_intent: Option<Intent>,
+    crash_state: Arc<CrashState>,
+    show_fatal_error_panel: bool,
+    input_source: InputSource,
 
     /// World
     pub world: hecs::World,
@@ -149,8 +476,52 @@ pub struct Engine {
     pub gui_context: GuiContext,
     /// Haptics context
     pub haptic_context: HapticContext,
+    /// Effects context
+    pub effects_context: EffectsContext,
     /// Input context
     pub input_context: InputContext,
+    /// Hand tracking context, for hands tracked without controllers
+    pub hand_tracking_context: HandTrackingContext,
+    /// Storage context
+    pub storage_context: StorageContext,
+    /// Where the app can keep its files
+    pub paths: Paths,
+    /// Platform context, for the system keyboard and dialogs
+    pub platform_context: PlatformContext,
+    /// Permissions context, for Android runtime permissions
+    pub permissions_context: PermissionsContext,
+    /// Device context, for the battery, thermals and performance levels
+    pub device_context: DeviceContext,
+    /// The app's text in each language, and the language it's being shown in
+    pub localization: Localization,
+    /// Captions waiting to be shown on a [`crate::components::CaptionPanel`]
+    pub captions: Captions,
+    /// Where the player's interactions are sent, if anywhere - see [`Analytics`]
+    pub analytics: Analytics,
+    /// What's saved when the app is paused, and restored if it was killed - see [`Snapshots`]
+    pub snapshots: Snapshots,
+    /// Time context
+    pub time_context: TimeContext,
+    /// Scripting context
+    #[cfg(feature = "wasm-scripting")]
+    pub script_context: crate::contexts::ScriptContext,
+    /// Lua scripting context
+    #[cfg(feature = "lua-scripting")]
+    pub lua_context: crate::contexts::LuaContext,
+    /// HTTP context
+    #[cfg(feature = "http")]
+    pub http_context: crate::contexts::HttpContext,
+    /// Inspector context, if the inspector could be started
+    #[cfg(feature = "inspector")]
+    pub inspector_context: Option<crate::contexts::InspectorContext>,
+    /// Recent log entries
+    pub log_history: LogHistory,
+    /// How much memory is in use
+    pub memory_stats: MemoryStats,
+    /// How frames line up with the runtime's display times, and how long to delay the simulation by
+    pub frame_pacing: FramePacing,
+    /// The spectator view, if a spectator camera was set with [`EngineBuilder::spectator_camera`]
+    pub spectator_view: Option<SpectatorView>,
     /// Stage entity
     pub stage_entity: hecs::Entity,
     /// HMD entity
@@ -165,6 +536,20 @@ pub struct TickData {
     pub current_state: xr::SessionState,
     /// The index of the currently acquired image on the OpenXR swapchain
     pub swapchain_image_index: usize,
+    /// Anything that happened to the app since the last frame
+    pub events: Vec<EngineEvent>,
+}
+
+/// Something that happened to the app outside of OpenXR, delivered in [`TickData::events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// The app was launched with this intent. Delivered with the first frame.
+    LaunchIntent(Intent),
+    /// The app was brought back to the front with a different intent. Android's `NativeActivity` doesn't pass new
+    /// intents on by itself, so this needs an activity that calls `setIntent` from `onNewIntent`.
+    NewIntent(Intent),
+    /// The runtime changed how well it's keeping up on the CPU or GPU, eg. because the device is throttling.
+    PerformanceNotification(PerformanceNotification),
 }
 
 impl Engine {
@@ -178,13 +563,35 @@ impl Engine {
     pub fn update(&mut self) -> HothamResult<TickData> {
         loop {
             #[cfg(target_os = "android")]
-            process_android_events(&mut self.resumed, &self.should_quit);
+            {
+                let was_resumed = self.resumed;
+                let mut key_presses = Vec::new();
+                process_android_events(&mut self.resumed, &self.should_quit, &mut key_presses);
+                for key in key_presses {
+                    self.platform_context.key_pressed(key);
+                }
+                if self.resumed && !was_resumed {
+                    // We weren't killed while we were paused, so there's nothing to restore.
+                    if let Err(e) = self.storage_context.remove(SNAPSHOT_KEY) {
+                        log::error!("[HOTHAM_ENGINE] Unable to remove snapshot: {:?}", e);
+                    }
+                    self.check_for_new_intent();
+                }
+                if was_resumed && !self.resumed {
+                    // We may be killed while we're paused, so save what we can before waiting to be resumed.
+                    if let Err(e) = self.save_snapshot() {
+                        log::error!("[HOTHAM_ENGINE] Unable to save snapshot: {:?}", e);
+                    }
+                    continue;
+                }
+            }
 
             // TODO: We *STILL* don't handle being shut down correctly. Something very odd is going on.
             // https://github.com/leetvr/hotham/issues/220
             if self.should_quit.load(Ordering::Acquire) {
                 // Show's over
-                println!("[HOTHAM_ENGINE] Hotham is now exiting!");
+                log::info!("[HOTHAM_ENGINE] Hotham is now exiting!");
+                self.analytics.session_ended();
                 return Err(HothamError::ShuttingDown);
             }
 
@@ -193,11 +600,30 @@ impl Engine {
                 let current_state = self.xr_context.poll_xr_event(&mut self.event_data_buffer)?;
                 (previous_state, current_state)
             };
-
-            // If we're in the FOCUSSED state, process input.
-            if current_state == SessionState::FOCUSED {
+            self.platform_context
+                .update(current_state == SessionState::FOCUSED);
+            self.permissions_context.update(&mut self.platform_context);
+            self.device_context.update(Instant::now());
+            self.render_context.readback.update(&self.vulkan_context);
+            self.pending_events.extend(
+                self.xr_context
+                    .performance_notifications
+                    .drain(..)
+                    .map(EngineEvent::PerformanceNotification),
+            );
+
+            // If we're in the FOCUSSED state, process input. Overlays are rarely focused, so they follow the HMD
+            // whenever they can be seen, but only read the controllers when they have focus.
+            let is_visible_overlay =
+                self.xr_context.overlay.is_some() && current_state == SessionState::VISIBLE;
+            if current_state == SessionState::FOCUSED || is_visible_overlay {
                 self.xr_context.update_views();
-                self.input_context.update(&self.xr_context);
+                if is_visible_overlay {
+                    self.input_context.hmd.update(&self.xr_context);
+                } else {
+                    self.update_input();
+                    self.hand_tracking_context.update(&self.xr_context);
+                }
 
                 // Since the HMD is parented to the Stage, its LocalTransform (ie. its transform with respect to the parent)
                 // is equal to its pose in stage space.
@@ -221,31 +647,45 @@ impl Engine {
                 }
                 (SessionState::IDLE, SessionState::READY) => {
                     self.xr_context.session.begin(VIEW_TYPE)?;
+                    self.analytics.session_started();
                 }
                 (_, SessionState::EXITING | SessionState::LOSS_PENDING) => {
                     // Show's over
-                    println!("[HOTHAM_ENGINE] Hotham is now exiting!");
+                    log::info!("[HOTHAM_ENGINE] Hotham is now exiting!");
+                    self.analytics.session_ended();
                     return Err(HothamError::ShuttingDown);
                 }
                 (_, SessionState::STOPPING) => {
+                    self.analytics.session_ended();
                     self.xr_context.end_session()?;
                     continue;
                 }
                 _ => {}
             }
 
-            let vulkan_context = &self.vulkan_context;
-            let render_context = &mut self.render_context;
-
             // In any other state, begin the frame loop.
             match self.xr_context.begin_frame() {
                 Err(HothamError::NotRendering) => continue,
                 Ok(swapchain_image_index) => {
+                    let frame_state = self.xr_context.frame_state;
+                    self.frame_pacing.frame_waited(
+                        Instant::now(),
+                        frame_state.predicted_display_time,
+                        frame_state.predicted_display_period,
+                    );
+                    self.delay_simulation();
+
+                    let vulkan_context = &self.vulkan_context;
+                    let render_context = &mut self.render_context;
                     render_context.begin_frame(vulkan_context);
+                    self.time_context
+                        .update(self.xr_context.frame_state.predicted_display_time);
+                    self.frame_in_progress = true;
                     return Ok(TickData {
                         previous_state,
                         current_state,
                         swapchain_image_index,
+                        events: std::mem::take(&mut self.pending_events),
                     });
                 }
                 err => panic!("Error beginning frame: {:?}", err),
@@ -253,16 +693,207 @@ impl Engine {
         }
     }
 
+    /// Wait out the frame pacing's simulation delay, then sample the head pose again so the frame starts with the
+    /// freshest one.
+    fn delay_simulation(&mut self) {
+        let display_period = self.frame_pacing.stats().display_period;
+        let delay = self.frame_pacing.effective_delay(display_period);
+        if delay.is_zero() {
+            return;
+        }
+        sleep(delay);
+
+        // Played back input has its own head pose.
+        if !matches!(self.input_source, InputSource::Live)
+            || self.xr_context.session_state != SessionState::FOCUSED
+        {
+            return;
+        }
+        self.xr_context.update_views();
+        self.input_context.hmd.update(&self.xr_context);
+        let hmd_in_stage = self.input_context.hmd.hmd_in_stage();
+        self.world
+            .get::<&mut LocalTransform>(self.hmd_entity)
+            .unwrap()
+            .update_from_affine(&hmd_in_stage);
+    }
+
+    /// The intent the app was launched with, if any - see [`Intent`].
+    pub fn launch_intent(&self) -> Option<&Intent> {
+        self.launch_intent.as_ref()
+    }
+
+    /// The most recent intent the app was launched or brought back to the front with, if any.
+    pub fn intent(&self) -> Option<&Intent> {
+        self.current_intent.as_ref()
+    }
+
+    /// When the app is resumed, see if it was with a different intent.
+    #[cfg(target_os = "android")]
+    fn check_for_new_intent(&mut self) {
+        let intent = match intent::current_intent() {
+            Some(intent) if Some(&intent) != self.current_intent.as_ref() => intent,
+            _ => return,
+        };
+        log::info!("[HOTHAM_ENGINE] Resumed with {:?}", intent);
+        self.pending_events
+            .push(EngineEvent::NewIntent(intent.clone()));
+        self.current_intent = Some(intent);
+    }
+
+    /// Save a [`Snapshot`] of the app now. The engine does this itself when the app is paused.
+    pub fn save_snapshot(&self) -> HothamResult<()> {
+        self.snapshots.save(&self.world, &self.storage_context)
+    }
+
+    /// Restore the snapshot saved when the app was paused, if it was killed before it could be resumed. Call this
+    /// once the app has loaded its scene, so its [`crate::components::Persistent`] entities can be found.
+    pub fn restore_snapshot(&mut self) -> HothamResult<Option<Snapshot>> {
+        self.snapshots.load(&mut self.world, &self.storage_context)
+    }
+
+    /// Show the real world behind everything that's drawn, using the headset's cameras. Anything the app doesn't draw
+    /// over, including the background, shows the camera feed. Fails if the runtime doesn't support
+    /// `XR_FB_passthrough`.
+    pub fn enable_passthrough(&mut self) -> HothamResult<()> {
+        self.xr_context.enable_passthrough()?;
+        self.render_context.transparent_background = true;
+        Ok(())
+    }
+
+    /// Stop showing the real world, and clear the background to `RenderContext::clear_color` again.
+    pub fn disable_passthrough(&mut self) -> HothamResult<()> {
+        self.xr_context.disable_passthrough()?;
+        self.render_context.transparent_background = false;
+        Ok(())
+    }
+
+    /// Update the `InputContext`, either from OpenXR or from whatever is being played back.
+    fn update_input(&mut self) {
+        match &mut self.input_source {
+            InputSource::Live => {
+                self.input_context.update(&self.xr_context);
+                return;
+            }
+            InputSource::Recording(recorder) => {
+                self.input_context.update(&self.xr_context);
+                if let Err(e) = recorder.record(&self.input_context) {
+                    log::error!("[HOTHAM_ENGINE] Unable to record input, stopping: {}", e);
+                    self.input_source = InputSource::Live;
+                }
+                return;
+            }
+            InputSource::Playback(playback) => match playback.next_frame() {
+                Ok(Some(input_context)) => self.input_context = input_context,
+                Ok(None) => {
+                    log::info!(
+                        "[HOTHAM_ENGINE] Finished playing {} frames of input",
+                        playback.frames()
+                    );
+                    self.should_quit.store(true, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    log::error!("[HOTHAM_ENGINE] Unable to play input: {}", e);
+                    self.should_quit.store(true, Ordering::Relaxed);
+                }
+            },
+            InputSource::CameraPath {
+                camera_path,
+                frames,
+            } => {
+                let hmd_in_stage = camera_path.sample(*frames as f32 * DELTA_TIME);
+                self.input_context.hmd.set_hmd_in_stage(hmd_in_stage);
+                *frames += 1;
+            }
+        }
+
+        // Render from wherever the played back HMD is, too.
+        let eyes_in_stage = self.input_context.hmd.eyes_in_stage();
+        for (view, eye_in_stage) in self.xr_context.views.iter_mut().zip(eyes_in_stage) {
+            view.pose = posef_from_affine(eye_in_stage);
+        }
+    }
+
     /// Call this after update
     pub fn finish(&mut self) -> xr::Result<()> {
         let vulkan_context = &self.vulkan_context;
         let render_context = &mut self.render_context;
 
         if self.xr_context.frame_state.should_render {
+            // Played back input has its own head pose, which doesn't change during the frame.
+            let late_latch = self.frame_pacing.late_latch_views
+                && matches!(self.input_source, InputSource::Live)
+                && self.xr_context.session_state == SessionState::FOCUSED;
+            if late_latch {
+                render_context.late_latch_views(self.xr_context.update_views());
+            }
             render_context.end_frame(vulkan_context);
+            self.xr_context.render_extent = render_context.scaled_render_area().extent;
         }
+        self.frame_pacing.cpu_finished(Instant::now());
+        self.frame_in_progress = false;
         self.xr_context.end_frame()
     }
+
+    /// Run `main_loop` - usually the loop that calls `update` and `finish` - catching any panic it causes.
+    ///
+    /// By the time the panic is caught, the panic hook has already written a [`crash::CrashReport`] to storage. The
+    /// frame that was interrupted is thrown away, the report is shown in a panel if
+    /// [`EngineBuilder::show_fatal_error_panel`] was set, and the session is ended cleanly so the headset isn't left
+    /// in a bad state. The panic then carries on as normal.
+    pub fn run_with_crash_handler<F>(&mut self, main_loop: F) -> HothamResult<()>
+    where
+        F: FnOnce(&mut Engine) -> HothamResult<()>,
+    {
+        self.crash_state
+            .set_handler_thread(Some(std::thread::current().id()));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| main_loop(self)));
+        self.crash_state.set_handler_thread(None);
+
+        let payload = match result {
+            Ok(result) => return result,
+            Err(payload) => payload,
+        };
+
+        log::error!("[HOTHAM_CRASH] Caught a panic, ending the session..");
+        if self.frame_in_progress {
+            self.abandon_frame();
+        }
+        if self.show_fatal_error_panel {
+            if let Some(report) = self.crash_state.take_report() {
+                crash::show_fatal_error_panel(self, &report);
+            }
+        }
+        self.exit_session();
+
+        panic::resume_unwind(payload)
+    }
+
+    /// Throw away the frame that's being recorded, eg. because a panic interrupted it.
+    fn abandon_frame(&mut self) {
+        if self.xr_context.frame_state.should_render {
+            self.render_context.abandon_frame(&self.vulkan_context);
+        }
+        if let Err(e) = self.xr_context.abandon_frame() {
+            log::warn!("[HOTHAM_ENGINE] Unable to abandon frame: {:?}", e);
+        }
+        self.frame_in_progress = false;
+    }
+
+    /// Ask the runtime to end the session, then keep the frame loop going until it has.
+    fn exit_session(&mut self) {
+        if let Err(e) = self.xr_context.session.request_exit() {
+            log::warn!("[HOTHAM_ENGINE] Unable to request session exit: {:?}", e);
+            return;
+        }
+
+        while self.update().is_ok() {
+            if self.finish().is_err() {
+                return;
+            }
+        }
+        log::info!("[HOTHAM_ENGINE] ..session ended.");
+    }
 }
 
 impl Default for Engine {
@@ -271,18 +902,27 @@ impl Default for Engine {
     }
 }
 
+/// Handle Android's lifecycle events, and collect any keys typed on the system keyboard into `key_presses`.
 #[cfg(target_os = "android")]
-pub fn process_android_events(resumed: &mut bool, should_quit: &Arc<AtomicBool>) {
+pub fn process_android_events(
+    resumed: &mut bool,
+    should_quit: &Arc<AtomicBool>,
+    key_presses: &mut Vec<crate::contexts::platform_context::KeyPress>,
+) {
     while let Some(event) = poll_android_events(*resumed) {
-        println!("[HOTHAM_ANDROID] Received event {:?}", event);
+        log::info!("[HOTHAM_ANDROID] Received event {:?}", event);
         match event {
             ndk_glue::Event::Resume => *resumed = true,
             ndk_glue::Event::Destroy => {
-                println!("[HOTHAM_ANDROID] !! DESTROY CALLED! DESTROY EVERYTHING! DESTROY!!!!");
+                log::warn!("[HOTHAM_ANDROID] !! DESTROY CALLED! DESTROY EVERYTHING! DESTROY!!!!");
                 should_quit.store(true, Ordering::Release);
                 return;
             }
-            ndk_glue::Event::Pause => *resumed = false,
+            ndk_glue::Event::Pause => {
+                // Return straight away, so the engine can save its state before it waits to be resumed.
+                *resumed = false;
+                return;
+            }
             _ => {}
         }
     }
@@ -290,7 +930,20 @@ pub fn process_android_events(resumed: &mut bool, should_quit: &Arc<AtomicBool>)
     if let Some(ref input_queue) = *ndk_glue::input_queue() {
         while let Some(event) = input_queue.get_event() {
             if let Some(event) = input_queue.pre_dispatch(event) {
-                input_queue.finish_event(event, false);
+                let mut handled = false;
+                if let ndk::event::InputEvent::KeyEvent(key_event) = &event {
+                    if key_event.action() == ndk::event::KeyAction::Down {
+                        let shift = key_event.meta_state().shift_on();
+                        if let Some(key) = crate::contexts::platform_context::key_press_from_keycode(
+                            key_event.key_code() as u32,
+                            shift,
+                        ) {
+                            key_presses.push(key);
+                            handled = true;
+                        }
+                    }
+                }
+                input_queue.finish_event(event, handled);
             }
         }
     }
@@ -0,0 +1,197 @@
+use hecs::{Entity, World};
+
+use crate::components::{physics::Disabled, Visible};
+
+/// A set of entities that are made once and reused, rather than spawned and despawned each time they're needed - eg.
+/// bullets, enemies or crab-saber's cubes. Spawning an entity with a mesh and a collider means moving it between
+/// archetypes and adding it to the physics simulation, which adds up when it happens many times a second.
+///
+/// Entities that aren't in use are put away: they're hidden by removing their [`Visible`] component, and taken out
+/// of the physics simulation with [`Disabled`]. Only the entity itself is put away, so if a model's meshes are on its
+/// children, hide those too.
+#[derive(Debug, Clone, Default)]
+pub struct EntityPool {
+    available: Vec<Entity>,
+    active: Vec<Entity>,
+    stats: PoolStats,
+}
+
+/// How hard an [`EntityPool`] is being worked, to help decide how big it should be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// How many entities the pool has
+    pub size: usize,
+    /// How many of them are in use
+    pub active: usize,
+    /// The most that have been in use at once
+    pub peak_active: usize,
+    /// How many times an entity has been taken from the pool
+    pub acquired: u64,
+    /// How many times an entity was wanted but they were all in use
+    pub exhausted: u64,
+}
+
+impl EntityPool {
+    /// Create a pool of `size` entities, each spawned with `spawn`. They all start put away.
+    pub fn new(
+        world: &mut World,
+        size: usize,
+        mut spawn: impl FnMut(&mut World) -> Entity,
+    ) -> Self {
+        let mut pool = Self::default();
+        for _ in 0..size {
+            let entity = spawn(world);
+            pool.add(world, entity);
+        }
+        pool
+    }
+
+    /// Put `entity` away and add it to the pool, eg. to grow the pool after [`EntityPool::acquire`] returns `None`.
+    pub fn add(&mut self, world: &mut World, entity: Entity) {
+        deactivate(world, entity);
+        self.available.push(entity);
+        self.stats.size += 1;
+    }
+
+    /// Take an entity from the pool, showing it and adding it back to the physics simulation. Returns `None` if
+    /// they're all in use.
+    ///
+    /// The entity is just as it was when it was put away, so reset anything that matters, like its position.
+    pub fn acquire(&mut self, world: &mut World) -> Option<Entity> {
+        let entity = match self.available.pop() {
+            Some(entity) => entity,
+            None => {
+                self.stats.exhausted += 1;
+                return None;
+            }
+        };
+
+        let _ = world.insert_one(entity, Visible {});
+        let _ = world.remove_one::<Disabled>(entity);
+        self.active.push(entity);
+
+        self.stats.acquired += 1;
+        self.stats.active = self.active.len();
+        self.stats.peak_active = self.stats.peak_active.max(self.stats.active);
+        Some(entity)
+    }
+
+    /// Put `entity` away so it can be used again. Returns `false`, and does nothing, if it isn't an entity in use from
+    /// this pool.
+    pub fn release(&mut self, world: &mut World, entity: Entity) -> bool {
+        let index = match self.active.iter().position(|e| *e == entity) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.active.swap_remove(index);
+        deactivate(world, entity);
+        self.available.push(entity);
+        self.stats.active = self.active.len();
+        true
+    }
+
+    /// Put away every entity in use
+    pub fn release_all(&mut self, world: &mut World) {
+        for entity in std::mem::take(&mut self.active) {
+            deactivate(world, entity);
+            self.available.push(entity);
+        }
+        self.stats.active = 0;
+    }
+
+    /// The entities in use
+    pub fn active(&self) -> &[Entity] {
+        &self.active
+    }
+
+    /// Is `entity` in use from this pool?
+    pub fn is_active(&self, entity: Entity) -> bool {
+        self.active.contains(&entity)
+    }
+
+    /// How hard the pool is being worked
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+}
+
+impl PoolStats {
+    /// What fraction of the pool is in use, from 0 to 1. A pool that's often close to 1, or that has been
+    /// `exhausted`, could do with being bigger.
+    pub fn pressure(&self) -> f32 {
+        match self.size {
+            0 => 1.,
+            size => self.active as f32 / size as f32,
+        }
+    }
+}
+
+fn deactivate(world: &mut World, entity: Entity) {
+    let _ = world.remove_one::<Visible>(entity);
+    let _ = world.insert_one(entity, Disabled {});
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Affine3A, Vec3};
+
+    use crate::{
+        components::{physics::SharedShape, Collider, GlobalTransform},
+        contexts::PhysicsContext,
+        systems::physics::physics_system_inner,
+    };
+
+    #[test]
+    pub fn test_entity_pool() {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let mut pool = EntityPool::new(&mut world, 2, |world| {
+            world.spawn((
+                Collider::new(SharedShape::ball(0.5)),
+                GlobalTransform(Affine3A::from_translation(Vec3::new(0., 0., -5.))),
+                Visible {},
+            ))
+        });
+        physics_system_inner(&mut physics_context, &mut world);
+
+        // Pooled entities are hidden, and out of the way.
+        let hit = |physics_context: &PhysicsContext, world: &World| {
+            physics_context
+                .cast_surface_ray(world, Vec3::ZERO, -Vec3::Z, 10.)
+                .map(|hit| hit.entity)
+        };
+        assert_eq!(world.query::<&Visible>().iter().count(), 0);
+        assert_eq!(hit(&physics_context, &world), None);
+
+        let a = pool.acquire(&mut world).unwrap();
+        let b = pool.acquire(&mut world).unwrap();
+        assert!(pool.acquire(&mut world).is_none());
+        physics_system_inner(&mut physics_context, &mut world);
+        assert!(world.get::<&Visible>(a).is_ok());
+        assert!(hit(&physics_context, &world).is_some());
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                size: 2,
+                active: 2,
+                peak_active: 2,
+                acquired: 2,
+                exhausted: 1,
+            }
+        );
+        assert_eq!(pool.stats().pressure(), 1.);
+
+        // Released entities go back in the pool, to be used again.
+        assert!(pool.release(&mut world, b));
+        assert!(!pool.release(&mut world, b));
+        assert_eq!(pool.acquire(&mut world), Some(b));
+
+        pool.release_all(&mut world);
+        physics_system_inner(&mut physics_context, &mut world);
+        assert!(world.get::<&Visible>(a).is_err());
+        assert!(!pool.is_active(a));
+        assert_eq!(hit(&physics_context, &world), None);
+        assert_eq!(pool.stats().pressure(), 0.);
+    }
+}
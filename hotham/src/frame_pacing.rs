@@ -0,0 +1,200 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use openxr as xr;
+
+/// How much each new frame moves [`FramePacingStats::average_cpu_time`] towards it.
+const AVERAGE_WEIGHT: f64 = 0.05;
+
+/// How the engine's frames line up with the runtime's, and settings to trade latency for headroom.
+///
+/// Each frame starts when `xrWaitFrame` returns, which the runtime times so that there's about one display period to
+/// build the frame before it's needed. A frame whose CPU work takes longer than that misses its display time, and the
+/// runtime shows the last one again.
+///
+/// Apps that finish well inside the period can start their simulation later with [`FramePacing::simulation_delay`], so
+/// the head pose and input they use are fresher when the frame is shown. [`FramePacing::late_latch_views`] goes further,
+/// moving the cameras to a head pose located just before the frame is submitted.
+///
+/// Updated by the engine each frame, and shown in any [`crate::components::DebugPanel`].
+#[derive(Debug, Clone, Default)]
+pub struct FramePacing {
+    /// How long to wait after `xrWaitFrame` returns before sampling the head pose and starting the simulation. Never
+    /// more than half a display period, so there's always time left to build the frame. Zero by default.
+    pub simulation_delay: Duration,
+    /// Locate the views again just before the frame is submitted, and draw from them - see
+    /// [`crate::contexts::RenderContext::late_latch_views`]. Off by default.
+    pub late_latch_views: bool,
+    stats: FramePacingStats,
+    frame_started: Option<Instant>,
+    last_display_time: Option<xr::Time>,
+}
+
+/// Frame pacing counters - see [`FramePacing`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FramePacingStats {
+    /// How many frames have been started
+    pub frames: u64,
+    /// How many display times passed without a new frame, so the runtime had to show an old one again
+    pub missed_frames: u64,
+    /// How many frames took longer than a display period, from `xrWaitFrame` returning to the frame being submitted
+    pub late_frames: u64,
+    /// The time between display times, as predicted by the runtime
+    pub display_period: Duration,
+    /// How long the last frame's CPU work took, from `xrWaitFrame` returning to the frame being submitted
+    pub cpu_time: Duration,
+    /// A moving average of `cpu_time`
+    pub average_cpu_time: Duration,
+    /// How long before the end of its display period the last frame was submitted, in milliseconds. Negative if it was
+    /// late.
+    pub headroom_ms: f32,
+}
+
+impl FramePacing {
+    /// The counters so far
+    pub fn stats(&self) -> &FramePacingStats {
+        &self.stats
+    }
+
+    /// How long the simulation will actually be delayed by, for a display period of `display_period`.
+    pub fn effective_delay(&self, display_period: Duration) -> Duration {
+        self.simulation_delay.min(display_period / 2)
+    }
+
+    /// Record that `xrWaitFrame` returned at `now`, for a frame to be displayed at `display_time`. Called by the engine.
+    pub(crate) fn frame_waited(
+        &mut self,
+        now: Instant,
+        display_time: xr::Time,
+        display_period: xr::Duration,
+    ) {
+        let period_nanos = display_period.as_nanos().max(1);
+        if let Some(last_display_time) = self.last_display_time {
+            // Each display period that went by without a frame of ours is one the runtime had to fill in.
+            let elapsed_nanos = display_time.as_nanos() - last_display_time.as_nanos();
+            let periods = (elapsed_nanos as f64 / period_nanos as f64).round() as i64;
+            if periods > 1 {
+                self.stats.missed_frames += (periods - 1) as u64;
+            }
+        }
+
+        self.last_display_time = Some(display_time);
+        self.frame_started = Some(now);
+        self.stats.frames += 1;
+        self.stats.display_period = Duration::from_nanos(period_nanos as u64);
+    }
+
+    /// Record that the frame's CPU work finished at `now`, just before it was submitted. Called by the engine.
+    pub(crate) fn cpu_finished(&mut self, now: Instant) {
+        let frame_started = match self.frame_started.take() {
+            Some(frame_started) => frame_started,
+            None => return,
+        };
+
+        let cpu_time = now.saturating_duration_since(frame_started);
+        let period = self.stats.display_period;
+        if cpu_time > period {
+            self.stats.late_frames += 1;
+        }
+
+        self.stats.average_cpu_time = if self.stats.average_cpu_time.is_zero() {
+            cpu_time
+        } else {
+            self.stats.average_cpu_time.mul_f64(1. - AVERAGE_WEIGHT)
+                + cpu_time.mul_f64(AVERAGE_WEIGHT)
+        };
+        self.stats.cpu_time = cpu_time;
+        self.stats.headroom_ms = (period.as_secs_f64() - cpu_time.as_secs_f64()) as f32 * 1000.;
+    }
+}
+
+impl fmt::Display for FramePacingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "CPU: {:.2} ms (average {:.2} ms) of {:.2} ms, {:.2} ms headroom",
+            self.cpu_time.as_secs_f32() * 1000.,
+            self.average_cpu_time.as_secs_f32() * 1000.,
+            self.display_period.as_secs_f32() * 1000.,
+            self.headroom_ms
+        )?;
+        writeln!(
+            f,
+            "Frames: {} ({} late, {} missed)",
+            self.frames, self.late_frames, self.missed_frames
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD_NANOS: i64 = 13_888_889;
+
+    #[test]
+    pub fn test_missed_frames() {
+        let mut frame_pacing = FramePacing::default();
+        let period = xr::Duration::from_nanos(PERIOD_NANOS);
+        let now = Instant::now();
+
+        frame_pacing.frame_waited(now, xr::Time::from_nanos(PERIOD_NANOS), period);
+        frame_pacing.frame_waited(now, xr::Time::from_nanos(PERIOD_NANOS * 2), period);
+        assert_eq!(frame_pacing.stats().missed_frames, 0);
+
+        // Two display times went by without a frame.
+        frame_pacing.frame_waited(now, xr::Time::from_nanos(PERIOD_NANOS * 5), period);
+        let stats = frame_pacing.stats();
+        assert_eq!(stats.missed_frames, 2);
+        assert_eq!(stats.frames, 3);
+        assert_eq!(
+            stats.display_period,
+            Duration::from_nanos(PERIOD_NANOS as _)
+        );
+    }
+
+    #[test]
+    pub fn test_cpu_time() {
+        let mut frame_pacing = FramePacing::default();
+        let period = xr::Duration::from_nanos(PERIOD_NANOS);
+        let start = Instant::now();
+
+        // Finishing without having started a frame does nothing.
+        frame_pacing.cpu_finished(start);
+        assert_eq!(frame_pacing.stats().cpu_time, Duration::ZERO);
+
+        frame_pacing.frame_waited(start, xr::Time::from_nanos(PERIOD_NANOS), period);
+        frame_pacing.cpu_finished(start + Duration::from_millis(4));
+        let stats = *frame_pacing.stats();
+        assert_eq!(stats.cpu_time, Duration::from_millis(4));
+        assert_eq!(stats.average_cpu_time, Duration::from_millis(4));
+        assert_eq!(stats.late_frames, 0);
+        assert!((stats.headroom_ms - 9.888889).abs() < 0.001);
+
+        // A frame that takes longer than the period is late, and has no headroom.
+        let start = start + Duration::from_millis(20);
+        frame_pacing.frame_waited(start, xr::Time::from_nanos(PERIOD_NANOS * 2), period);
+        frame_pacing.cpu_finished(start + Duration::from_millis(20));
+        let stats = *frame_pacing.stats();
+        assert_eq!(stats.late_frames, 1);
+        assert!(stats.headroom_ms < 0.);
+        assert!(stats.average_cpu_time > Duration::from_millis(4));
+        assert!(stats.average_cpu_time < Duration::from_millis(20));
+    }
+
+    #[test]
+    pub fn test_effective_delay() {
+        let frame_pacing = FramePacing {
+            simulation_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let period = Duration::from_nanos(PERIOD_NANOS as _);
+        assert_eq!(frame_pacing.effective_delay(period), period / 2);
+        assert_eq!(
+            frame_pacing.effective_delay(Duration::from_millis(40)),
+            Duration::from_millis(10)
+        );
+    }
+}
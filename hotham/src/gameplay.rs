@@ -0,0 +1,421 @@
+use std::{
+    fmt::{self, Debug, Display},
+    time::{Duration, Instant},
+};
+
+/// How many hits in a row it takes for a [`Score`]'s multiplier to go up by one
+pub const HITS_PER_MULTIPLIER: u32 = 8;
+
+/// The highest a [`Score`]'s multiplier can go
+pub const MAX_MULTIPLIER: u32 = 4;
+
+/// Keeps track of the state a game is in - eg. a main menu, playing a level or game over - and what it was in before.
+///
+/// Like the rest of an application's state, it's usually kept in a struct passed to the application's systems, which
+/// decide when to change state and what to do when they do. See the `crab-saber` example for how that looks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateMachine<S> {
+    current: S,
+    previous: Option<S>,
+    time_in_state: Duration,
+}
+
+impl<S: Clone + PartialEq + Debug> StateMachine<S> {
+    /// Create a state machine that starts in `initial`
+    pub fn new(initial: S) -> Self {
+        Self {
+            current: initial,
+            previous: None,
+            time_in_state: Duration::ZERO,
+        }
+    }
+
+    /// The state the game is in
+    pub fn current(&self) -> &S {
+        &self.current
+    }
+
+    /// The state the game was in before the last change, if it's changed
+    pub fn previous(&self) -> Option<&S> {
+        self.previous.as_ref()
+    }
+
+    /// How long the game has been in its current state, going by [`StateMachine::update`]
+    pub fn time_in_state(&self) -> Duration {
+        self.time_in_state
+    }
+
+    /// Change to `next`, returning the state the game was in. Changing to the state the game is already in starts it
+    /// again.
+    pub fn set(&mut self, next: S) -> S {
+        let previous = std::mem::replace(&mut self.current, next);
+        self.previous = Some(previous.clone());
+        self.time_in_state = Duration::ZERO;
+        previous
+    }
+
+    /// Count `delta_time` towards how long the game has been in its current state. Call this once a frame.
+    pub fn update(&mut self, delta_time: Duration) {
+        self.time_in_state += delta_time;
+    }
+}
+
+/// Counts the beats of a song, so things can happen in time with the music.
+///
+/// The clock only moves when it's updated, so pause it when the music is paused to keep them in step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatClock {
+    /// How long each beat lasts
+    pub beat_length: Duration,
+    elapsed: Duration,
+    paused: bool,
+    last_update: Option<Instant>,
+}
+
+impl BeatClock {
+    /// Create a clock with beats `beat_length` apart, starting at the first beat
+    pub fn new(beat_length: Duration) -> Self {
+        Self {
+            beat_length,
+            elapsed: Duration::ZERO,
+            paused: false,
+            last_update: None,
+        }
+    }
+
+    /// Create a clock for a song with `beats_per_minute`
+    pub fn from_bpm(beats_per_minute: f32) -> Self {
+        Self::new(Duration::from_secs_f32(60. / beats_per_minute))
+    }
+
+    /// Move the clock on by however much real time has passed since it was last updated. Call this once a frame.
+    pub fn update(&mut self) {
+        if self.paused {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last_update) = self.last_update {
+            self.advance(now - last_update);
+        }
+        self.last_update = Some(now);
+    }
+
+    /// Move the clock on by `delta_time`, eg. to skip ahead or to drive it from a fixed time step
+    pub fn advance(&mut self, delta_time: Duration) {
+        self.elapsed += delta_time;
+    }
+
+    /// Stop the clock, eg. when the music is paused
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.last_update = None;
+    }
+
+    /// Start the clock again after [`BeatClock::pause`]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Is the clock paused?
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// How long the clock has been running for
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// How many beats the clock has been running for, including how far it is through the current beat
+    pub fn beat(&self) -> f32 {
+        if self.beat_length.is_zero() {
+            return 0.;
+        }
+        self.elapsed.as_secs_f32() / self.beat_length.as_secs_f32()
+    }
+}
+
+/// Decides when to spawn things - eg. notes or enemies - every `interval` beats of a [`BeatClock`], starting on the
+/// first beat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatSpawner {
+    /// How many beats apart spawns are. Can be fractional, eg. `0.5` to spawn twice a beat.
+    pub interval: f32,
+    next_beat: f32,
+}
+
+impl BeatSpawner {
+    /// Create a spawner that spawns every `interval` beats
+    pub fn new(interval: f32) -> Self {
+        Self {
+            interval,
+            next_beat: 0.,
+        }
+    }
+
+    /// Is it time to spawn something? Call this once a frame. If the clock has moved on by several intervals since
+    /// the last spawn, eg. after a long frame, only one spawn is due and the rest are skipped.
+    pub fn due(&mut self, clock: &BeatClock) -> bool {
+        let beat = clock.beat();
+        if self.interval <= 0. || beat < self.next_beat {
+            return false;
+        }
+        let intervals_passed = ((beat - self.next_beat) / self.interval).floor() + 1.;
+        self.next_beat += intervals_passed * self.interval;
+        true
+    }
+
+    /// The beat the next spawn is due on
+    pub fn next_beat(&self) -> f32 {
+        self.next_beat
+    }
+
+    /// Start again from the first beat, eg. when a level is restarted
+    pub fn reset(&mut self) {
+        self.next_beat = 0.;
+    }
+}
+
+/// Counts down a number of seconds, eg. to spawn something every few seconds or to bring something back after a
+/// while. Tick it with [`crate::contexts::TimeContext::delta_time`] so it runs at the same speed on any headset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timer {
+    /// How long the timer runs for, in seconds
+    pub duration: f32,
+    elapsed: f32,
+    repeating: bool,
+}
+
+impl Timer {
+    /// Create a timer that finishes once, after `duration` seconds
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            elapsed: 0.,
+            repeating: false,
+        }
+    }
+
+    /// Create a timer that finishes every `duration` seconds
+    pub fn repeating(duration: f32) -> Self {
+        Self {
+            repeating: true,
+            ..Self::new(duration)
+        }
+    }
+
+    /// Move the timer on by `delta_time` seconds. Returns `true` on the tick the timer finishes. Like
+    /// [`BeatSpawner::due`], a repeating timer that's moved on by several durations at once only finishes once.
+    pub fn tick(&mut self, delta_time: f32) -> bool {
+        if self.finished() && !self.repeating {
+            return false;
+        }
+        self.elapsed += delta_time;
+        if self.elapsed < self.duration {
+            return false;
+        }
+        if self.repeating {
+            self.elapsed = if self.duration > 0. {
+                self.elapsed % self.duration
+            } else {
+                0.
+            };
+        } else {
+            self.elapsed = self.duration;
+        }
+        true
+    }
+
+    /// Has a timer that doesn't repeat finished?
+    pub fn finished(&self) -> bool {
+        !self.repeating && self.elapsed >= self.duration
+    }
+
+    /// How long until the timer next finishes, in seconds
+    pub fn remaining(&self) -> f32 {
+        (self.duration - self.elapsed).max(0.)
+    }
+
+    /// Start the timer again
+    pub fn reset(&mut self) {
+        self.elapsed = 0.;
+    }
+}
+
+/// A player's score for a level, with a combo multiplier that goes up as they hit things in a row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    /// How many points the player has
+    pub points: i32,
+    /// How many hits in a row the player has made
+    pub combo: u32,
+    /// The longest combo the player has made
+    pub max_combo: u32,
+    /// How many hits the player has made
+    pub hits: u32,
+    /// How many misses the player has made
+    pub misses: u32,
+}
+
+impl Score {
+    /// Record a hit worth `points`, multiplied by the current multiplier. Returns how many points were awarded.
+    pub fn hit(&mut self, points: i32) -> i32 {
+        let awarded = points * self.multiplier() as i32;
+        self.points += awarded;
+        self.hits += 1;
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+        awarded
+    }
+
+    /// Record a miss that costs `penalty` points, and break the combo
+    pub fn miss(&mut self, penalty: i32) {
+        self.points -= penalty;
+        self.misses += 1;
+        self.combo = 0;
+    }
+
+    /// What hits are currently multiplied by: one more for every [`HITS_PER_MULTIPLIER`] hits in a row, up to
+    /// [`MAX_MULTIPLIER`]
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / HITS_PER_MULTIPLIER).min(MAX_MULTIPLIER)
+    }
+
+    /// Start again from nothing
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Summarise the score, eg. for a results screen
+    pub fn result(&self) -> LevelResult {
+        LevelResult {
+            points: self.points,
+            hits: self.hits,
+            misses: self.misses,
+            max_combo: self.max_combo,
+        }
+    }
+}
+
+/// A summary of how a player did in a level, from [`Score::result`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelResult {
+    /// The player's final score
+    pub points: i32,
+    /// How many hits the player made
+    pub hits: u32,
+    /// How many misses the player made
+    pub misses: u32,
+    /// The longest combo the player made
+    pub max_combo: u32,
+}
+
+impl LevelResult {
+    /// What fraction of hits and misses were hits, from 0 to 1
+    pub fn accuracy(&self) -> f32 {
+        match self.hits + self.misses {
+            0 => 0.,
+            total => self.hits as f32 / total as f32,
+        }
+    }
+}
+
+impl Display for LevelResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Score: {}", self.points)?;
+        writeln!(f, "Best combo: {}", self.max_combo)?;
+        write!(f, "Accuracy: {:.0}%", self.accuracy() * 100.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_state_machine() {
+        let mut state = StateMachine::new("menu");
+        assert_eq!(state.previous(), None);
+        state.update(Duration::from_secs(1));
+        assert_eq!(state.time_in_state(), Duration::from_secs(1));
+
+        assert_eq!(state.set("playing"), "menu");
+        assert_eq!(state.current(), &"playing");
+        assert_eq!(state.previous(), Some(&"menu"));
+        assert_eq!(state.time_in_state(), Duration::ZERO);
+    }
+
+    #[test]
+    pub fn test_beat_spawner() {
+        let mut clock = BeatClock::from_bpm(120.);
+        let mut spawner = BeatSpawner::new(2.);
+
+        // The first spawn is on the first beat..
+        assert!(spawner.due(&clock));
+        assert!(!spawner.due(&clock));
+
+        // ..and the next is two beats later.
+        clock.advance(Duration::from_millis(900));
+        assert_eq!(clock.beat(), 1.8);
+        assert!(!spawner.due(&clock));
+        clock.advance(Duration::from_millis(100));
+        assert!(spawner.due(&clock));
+
+        // Paused clocks don't move.
+        clock.pause();
+        clock.update();
+        clock.update();
+        assert_eq!(clock.beat(), 2.);
+
+        // Missed spawns are skipped, rather than all happening at once.
+        clock.advance(Duration::from_secs(5));
+        assert!(spawner.due(&clock));
+        assert!(!spawner.due(&clock));
+        assert_eq!(spawner.next_beat(), 14.);
+    }
+
+    #[test]
+    pub fn test_timer() {
+        let mut timer = Timer::new(1.);
+        assert!(!timer.tick(0.75));
+        assert_eq!(timer.remaining(), 0.25);
+        assert!(timer.tick(0.5));
+        assert!(timer.finished());
+        assert!(!timer.tick(0.5));
+
+        // Repeating timers keep going, but a long frame only counts once.
+        let mut timer = Timer::repeating(0.5);
+        assert!(timer.tick(0.5));
+        assert!(!timer.finished());
+        assert!(timer.tick(1.75));
+        assert_eq!(timer.remaining(), 0.25);
+    }
+
+    #[test]
+    pub fn test_score() {
+        let mut score = Score::default();
+        for _ in 0..HITS_PER_MULTIPLIER {
+            assert_eq!(score.hit(10), 10);
+        }
+        assert_eq!(score.multiplier(), 2);
+        assert_eq!(score.hit(10), 20);
+
+        score.miss(5);
+        assert_eq!(score.multiplier(), 1);
+        assert_eq!(score.points, 95);
+
+        let result = score.result();
+        assert_eq!(result.max_combo, 9);
+        assert_eq!(result.accuracy(), 0.9);
+        assert_eq!(
+            result.to_string(),
+            "Score: 95\nBest combo: 9\nAccuracy: 90%"
+        );
+
+        // The multiplier tops out.
+        for _ in 0..HITS_PER_MULTIPLIER * 10 {
+            score.hit(1);
+        }
+        assert_eq!(score.multiplier(), MAX_MULTIPLIER);
+    }
+}
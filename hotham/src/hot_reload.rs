@@ -0,0 +1,307 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use libloading::Library;
+
+use crate::{Engine, HothamError, HothamResult, TickData};
+
+/// How long a library has to go unchanged before it's loaded, so we don't load one that's still being written.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// How often the library is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type InitFn = fn(&mut Engine);
+type TickFn = fn(&mut Engine, TickData);
+type LayoutFn = fn() -> u64;
+type SetLoggerFn = fn(&'static dyn log::Log, log::LevelFilter);
+
+/// The functions exported by a library built with [`crate::hot_reloadable`].
+struct Game {
+    init: InitFn,
+    tick: TickFn,
+    layout: u64,
+}
+
+/// Runs game logic from a dynamic library, reloading it whenever it's rebuilt.
+///
+/// Build your game's systems as a `cdylib` that uses [`crate::hot_reloadable`], then drive it from a small host
+/// binary:
+///
+/// ```ignore
+/// let mut engine = Engine::new();
+/// let mut reloader = HotReloader::new(library_path("my_game", "target/debug"));
+///
+/// while let Ok(tick_data) = engine.update() {
+///     reloader.tick(&mut engine, tick_data)?;
+///     engine.finish()?;
+/// }
+/// ```
+///
+/// Now `cargo build -p my_game` in another terminal will swap the new systems in without restarting. The `World` is
+/// kept between reloads unless the layout of the components the library declared has changed, in which case
+/// everything but the stage and HMD is despawned and the library's `init` is called again.
+///
+/// This is meant for iterating on desktop, with the simulator. Both sides must be built by the same compiler with the
+/// same version of Hotham - if Hotham itself changes, restart the host. Old libraries are never unloaded, so
+/// `&'static str`s and other data that point into them stay valid.
+pub struct HotReloader {
+    path: PathBuf,
+    game: Option<Game>,
+    libraries: Vec<Library>,
+    last_modified: Option<SystemTime>,
+    last_checked: Instant,
+    generation: usize,
+}
+
+impl HotReloader {
+    /// Create a `HotReloader` for the library at `path`. It's loaded on the first `tick`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            game: None,
+            libraries: Vec::new(),
+            last_modified: None,
+            last_checked: Instant::now(),
+            generation: 0,
+        }
+    }
+
+    /// How many times the library has been loaded
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Load a new build of the library if there is one, then tick the game. Only fails if the first build can't be
+    /// loaded - later failures are logged, and the previous build keeps running.
+    pub fn tick(&mut self, engine: &mut Engine, tick_data: TickData) -> HothamResult<()> {
+        if self.game.is_none() {
+            self.reload(engine)?;
+        } else if self.has_changed() {
+            if let Err(e) = self.reload(engine) {
+                log::error!("[HOTHAM_HOT_RELOAD] {} - keeping the last build", e);
+            }
+        }
+
+        let game = self.game.as_ref().unwrap();
+        (game.tick)(engine, tick_data);
+        Ok(())
+    }
+
+    fn has_changed(&mut self) -> bool {
+        if self.last_checked.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_checked = Instant::now();
+
+        // The library may briefly disappear while it's being rebuilt.
+        let modified = match modified(&self.path) {
+            Some(modified) => modified,
+            None => return false,
+        };
+        let has_settled = match modified.elapsed() {
+            Ok(age) => age >= SETTLE_TIME,
+            Err(_) => true,
+        };
+        Some(modified) != self.last_modified && has_settled
+    }
+
+    fn reload(&mut self, engine: &mut Engine) -> HothamResult<()> {
+        let modified = modified(&self.path)
+            .ok_or_else(|| hot_reload_error(&self.path, "it doesn't exist - has it been built?"))?;
+        // Don't try the same build again if it fails.
+        self.last_modified = Some(modified);
+
+        // Load a copy, so the next build can overwrite the original. Windows won't let you replace a loaded library.
+        let copy = copy_path(&self.path, self.generation + 1);
+        fs::create_dir_all(copy.parent().unwrap())?;
+        fs::copy(&self.path, &copy)?;
+
+        let (library, game) =
+            unsafe { load(&copy) }.map_err(|e| hot_reload_error(&self.path, e))?;
+        self.generation += 1;
+        log::info!(
+            "[HOTHAM_HOT_RELOAD] Loaded {} (generation {})",
+            self.path.display(),
+            self.generation
+        );
+
+        let preserve_world = match &self.game {
+            Some(old_game) => old_game.layout == game.layout,
+            None => false,
+        };
+        if !preserve_world {
+            if self.game.is_some() {
+                log::warn!(
+                    "[HOTHAM_HOT_RELOAD] Component layouts have changed, resetting the World.."
+                );
+                reset_world(engine);
+            }
+            (game.init)(engine);
+        }
+
+        self.libraries.push(library);
+        self.game = Some(game);
+        Ok(())
+    }
+}
+
+/// Load the library at `path` and find the functions exported by [`crate::hot_reloadable`].
+unsafe fn load(path: &Path) -> Result<(Library, Game), libloading::Error> {
+    let library = Library::new(path)?;
+    let game = Game {
+        init: *library.get::<InitFn>(b"hotham_hot_reload_init")?,
+        tick: *library.get::<TickFn>(b"hotham_hot_reload_tick")?,
+        layout: library.get::<LayoutFn>(b"hotham_hot_reload_layout")?(),
+    };
+
+    // The library has its own copy of `log`, so point it at our logger.
+    let set_logger = library.get::<SetLoggerFn>(b"hotham_hot_reload_set_logger")?;
+    set_logger(log::logger(), log::max_level());
+
+    Ok((library, game))
+}
+
+/// Despawn everything the game created, leaving the engine as it was when it was built.
+fn reset_world(engine: &mut Engine) {
+    let entities = engine
+        .world
+        .iter()
+        .map(|entity_ref| entity_ref.entity())
+        .filter(|entity| *entity != engine.stage_entity && *entity != engine.hmd_entity)
+        .collect::<Vec<_>>();
+    for entity in entities {
+        engine.world.despawn(entity).unwrap();
+    }
+    engine.physics_context = Default::default();
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn copy_path(path: &Path, generation: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    std::env::temp_dir().join("hotham_hot_reload").join(format!(
+        "{}-{}-{}{}",
+        stem,
+        std::process::id(),
+        generation,
+        DLL_SUFFIX
+    ))
+}
+
+fn hot_reload_error(path: &Path, reason: impl ToString) -> HothamError {
+    HothamError::HotReloadFailed {
+        path: path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
+
+/// The path cargo builds the `cdylib` for `crate_name` to in `target_dir`, eg. `target/debug/libmy_game.so` on Linux.
+pub fn library_path(crate_name: &str, target_dir: impl AsRef<Path>) -> PathBuf {
+    target_dir.as_ref().join(format!(
+        "{}{}{}",
+        DLL_PREFIX,
+        crate_name.replace('-', "_"),
+        DLL_SUFFIX
+    ))
+}
+
+/// Combine the name, size and alignment of each component into a value that changes when any of them do. Used by
+/// [`crate::hot_reloadable`] to decide if the `World` can be kept after a reload.
+pub fn layout_hash(components: &[(&str, usize, usize)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    components.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Export the functions a [`HotReloader`] needs from a game library.
+///
+/// `init` is called with the `Engine` when the library is first loaded, and again whenever the `World` has to be
+/// reset. `tick` is called every frame, between `update` and `finish`. List every component the library defines in
+/// `components`, so the `World` is only kept when they haven't changed.
+///
+/// ```ignore
+/// hotham::hot_reloadable! {
+///     init: init,
+///     tick: tick,
+///     components: [Health, Enemy],
+/// }
+/// ```
+#[macro_export]
+macro_rules! hot_reloadable {
+    (init: $init:path, tick: $tick:path, components: [$($component:ty),* $(,)?] $(,)?) => {
+        #[no_mangle]
+        pub fn hotham_hot_reload_init(engine: &mut $crate::Engine) {
+            $init(engine)
+        }
+
+        #[no_mangle]
+        pub fn hotham_hot_reload_tick(engine: &mut $crate::Engine, tick_data: $crate::TickData) {
+            $tick(engine, tick_data)
+        }
+
+        #[no_mangle]
+        pub fn hotham_hot_reload_layout() -> u64 {
+            $crate::hot_reload::layout_hash(&[$((
+                ::std::any::type_name::<$component>(),
+                ::std::mem::size_of::<$component>(),
+                ::std::mem::align_of::<$component>(),
+            )),*])
+        }
+
+        #[no_mangle]
+        pub fn hotham_hot_reload_set_logger(
+            logger: &'static dyn $crate::log::Log,
+            level: $crate::log::LevelFilter,
+        ) {
+            let _ = $crate::log::set_logger(logger);
+            $crate::log::set_max_level(level);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_layout_hash() {
+        let layout = layout_hash(&[("Health", 4, 4), ("Enemy", 16, 8)]);
+        assert_eq!(layout, layout_hash(&[("Health", 4, 4), ("Enemy", 16, 8)]));
+
+        // Adding a field, adding a component or renaming one should all change the layout.
+        assert_ne!(layout, layout_hash(&[("Health", 8, 4), ("Enemy", 16, 8)]));
+        assert_ne!(
+            layout,
+            layout_hash(&[("Health", 4, 4), ("Enemy", 16, 8), ("Boss", 4, 4)])
+        );
+        assert_ne!(layout, layout_hash(&[("Hp", 4, 4), ("Enemy", 16, 8)]));
+    }
+
+    #[test]
+    pub fn test_paths() {
+        let path = library_path("my-game", "target/debug");
+        assert_eq!(
+            path,
+            Path::new("target/debug").join(format!("{}my_game{}", DLL_PREFIX, DLL_SUFFIX))
+        );
+
+        // Each generation should be loaded from its own copy.
+        assert_ne!(copy_path(&path, 1), copy_path(&path, 2));
+        assert!(copy_path(&path, 1).starts_with(std::env::temp_dir()));
+    }
+
+    #[test]
+    pub fn test_missing_library() {
+        assert!(unsafe { load(Path::new("does/not/exist.so")) }.is_err());
+        assert!(modified(Path::new("does/not/exist.so")).is_none());
+    }
+}
@@ -32,6 +32,90 @@ pub enum HothamError {
     /// Not rendering yet
     #[error("this session is not rendering yet")]
     NotRendering,
+    /// A storage key contained characters that can't be used in a file name
+    #[error("{key:?} is not a valid storage key")]
+    InvalidStorageKey {
+        /// The key that was invalid
+        key: String,
+    },
+    /// Stored data couldn't be read or written
+    #[error("The data stored for {key:?} could not be used: {reason}")]
+    InvalidStorageData {
+        /// The key the data was stored under
+        key: String,
+        /// What went wrong
+        reason: String,
+    },
+    /// A library couldn't be hot reloaded
+    #[error("Unable to hot reload {path:?}: {reason}")]
+    HotReloadFailed {
+        /// The library that was being loaded
+        path: std::path::PathBuf,
+        /// What went wrong
+        reason: String,
+    },
+    /// A script couldn't be loaded or run
+    #[error("The script {name:?} could not be used: {reason}")]
+    ScriptError {
+        /// The name the script was loaded with
+        name: String,
+        /// What went wrong
+        reason: String,
+    },
+    /// An input recording couldn't be read or written
+    #[error("Unable to use the input recording {path:?}: {reason}")]
+    InvalidInputRecording {
+        /// The recording that was being used
+        path: std::path::PathBuf,
+        /// What went wrong
+        reason: String,
+    },
+    /// A snapshot couldn't be taken or restored
+    #[error("Unable to use the snapshot of {component}: {reason}")]
+    InvalidSnapshot {
+        /// The component, or app state, that couldn't be used
+        component: String,
+        /// What went wrong
+        reason: String,
+    },
+    /// The device is missing features Hotham can't run without
+    #[error("This device is missing features Hotham needs: {}", .missing.join(", "))]
+    UnsupportedDevice {
+        /// The features that are missing
+        missing: Vec<String>,
+    },
+    /// A string table couldn't be read
+    #[error("Unable to read the {locale} string table, line {line}: {reason}")]
+    InvalidStringTable {
+        /// The locale the table was for
+        locale: String,
+        /// The line the problem is on, starting from 1
+        line: usize,
+        /// What went wrong
+        reason: String,
+    },
+    /// A sound or piece of music couldn't be decoded
+    #[error("Unable to decode audio: {reason}")]
+    InvalidAudio {
+        /// What went wrong
+        reason: String,
+    },
+    /// A message couldn't be sent on a WebSocket
+    #[error("Unable to send a message to {url}: {reason}")]
+    WebSocketSendFailed {
+        /// Where the WebSocket is connected to
+        url: String,
+        /// What went wrong
+        reason: String,
+    },
+    /// The GPU the app asked for can't be rendered with
+    #[error("Unable to render with the GPU {requested}: {reason}")]
+    PhysicalDeviceUnavailable {
+        /// The GPU that was asked for
+        requested: String,
+        /// What went wrong
+        reason: String,
+    },
     /// Some other error
     #[error(transparent)]
     Other(#[from] anyhow::Error),
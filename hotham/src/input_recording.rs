@@ -0,0 +1,328 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+};
+
+use glam::{Affine3A, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{contexts::InputContext, HothamError, HothamResult};
+
+/// Bumped whenever the format of a recording changes, so old recordings are rejected instead of misread.
+const RECORDING_VERSION: u32 = 1;
+
+/// The first line of every recording.
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordingHeader {
+    version: u32,
+}
+
+/// Writes the state of the [`InputContext`] to a file each frame, so it can be replayed with an [`InputPlayback`].
+///
+/// Each frame is written as a single line of JSON, and flushed straight away, so a recording of a session that
+/// crashed is still complete up to the crash.
+///
+/// You won't usually need to use this directly - use [`crate::EngineBuilder::record_input`] instead.
+pub struct InputRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    frames: usize,
+}
+
+impl InputRecorder {
+    /// Start a new recording at `path`, replacing any that's already there.
+    pub fn create(path: impl Into<PathBuf>) -> HothamResult<Self> {
+        let path = path.into();
+        let mut recorder = Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            frames: 0,
+        };
+        recorder.write_line(&RecordingHeader {
+            version: RECORDING_VERSION,
+        })?;
+        Ok(recorder)
+    }
+
+    /// Add a frame to the recording.
+    pub fn record(&mut self, input_context: &InputContext) -> HothamResult<()> {
+        self.write_line(input_context)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// How many frames have been recorded
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> HothamResult<()> {
+        serde_json::to_writer(&mut self.writer, value)
+            .map_err(|e| invalid_recording(&self.path, e))?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads back a recording made by an [`InputRecorder`], one frame at a time.
+///
+/// You won't usually need to use this directly - use [`crate::EngineBuilder::play_input`] instead.
+pub struct InputPlayback {
+    path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    frames: usize,
+    looping: bool,
+    loops: usize,
+}
+
+impl InputPlayback {
+    /// Open the recording at `path`.
+    pub fn open(path: impl Into<PathBuf>) -> HothamResult<Self> {
+        let path = path.into();
+        let lines = read_header(&path)?;
+
+        Ok(Self {
+            path,
+            lines,
+            frames: 0,
+            looping: false,
+            loops: 0,
+        })
+    }
+
+    /// Start again from the beginning whenever the recording finishes, instead of ending.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// How many times the recording has been started again from the beginning
+    pub fn loops(&self) -> usize {
+        self.loops
+    }
+
+    /// Get the next frame of the recording, or `None` if it's finished.
+    pub fn next_frame(&mut self) -> HothamResult<Option<InputContext>> {
+        let line = match self.lines.next() {
+            Some(line) => line?,
+            // Don't loop forever over a recording with no frames in it.
+            None if self.looping && self.frames > 0 => {
+                self.lines = read_header(&self.path)?;
+                self.loops += 1;
+                return self.next_frame();
+            }
+            None => return Ok(None),
+        };
+        let input_context =
+            serde_json::from_str(&line).map_err(|e| invalid_recording(&self.path, e))?;
+        self.frames += 1;
+        Ok(Some(input_context))
+    }
+
+    /// How many frames have been played, including any from previous loops
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+}
+
+/// Open the recording at `path`, check its header and return the lines that follow it.
+fn read_header(path: &Path) -> HothamResult<Lines<BufReader<File>>> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    let header = lines
+        .next()
+        .ok_or_else(|| invalid_recording(path, "it's empty"))??;
+    let header: RecordingHeader =
+        serde_json::from_str(&header).map_err(|e| invalid_recording(path, e))?;
+    if header.version != RECORDING_VERSION {
+        return Err(invalid_recording(
+            path,
+            format!(
+                "it's version {}, but only version {} can be played",
+                header.version, RECORDING_VERSION
+            ),
+        ));
+    }
+
+    Ok(lines)
+}
+
+/// A point on a [`CameraPath`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    /// How far into the path this keyframe is, in seconds
+    pub time: f32,
+    /// Where the HMD is, in stage space
+    pub translation: Vec3,
+    /// Which way the HMD is facing, in stage space
+    pub rotation: Quat,
+}
+
+/// A scripted path for the HMD to follow, used instead of recorded input when all you need is for the camera to move
+/// around the scene, eg. for a demo or a soak test. The controllers are left where they are.
+///
+/// The HMD moves smoothly between each keyframe, then jumps back to the first one and starts again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    /// The keyframes on the path, sorted by time
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    /// Load a path from a JSON file, eg. `{"keyframes": [{"time": 0, "translation": [0, 1.6, 0], "rotation": [0, 0, 0, 1]}]}`
+    pub fn load(path: impl AsRef<Path>) -> HothamResult<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| invalid_recording(path, e))
+    }
+
+    /// How long it takes to follow the path from start to finish, in seconds
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map(|k| k.time).unwrap_or_default()
+    }
+
+    /// Where the HMD should be `time` seconds after it started following the path.
+    pub fn sample(&self, time: f32) -> Affine3A {
+        let duration = self.duration();
+        let time = if duration > 0. { time % duration } else { 0. };
+
+        let next = match self.keyframes.iter().position(|k| k.time > time) {
+            Some(0) | None => {
+                return match self.keyframes.first() {
+                    Some(k) => Affine3A::from_rotation_translation(k.rotation, k.translation),
+                    None => Affine3A::IDENTITY,
+                };
+            }
+            Some(next) => next,
+        };
+
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let s = ((time - from.time) / (to.time - from.time)).clamp(0., 1.);
+        Affine3A::from_rotation_translation(
+            from.rotation.slerp(to.rotation, s),
+            from.translation.lerp(to.translation, s),
+        )
+    }
+}
+
+/// Where the `Engine` gets its input from each frame.
+pub(crate) enum InputSource {
+    /// Straight from OpenXR
+    Live,
+    /// From OpenXR, writing each frame to a recording
+    Recording(InputRecorder),
+    /// From a recording, ignoring OpenXR
+    Playback(InputPlayback),
+    /// From a scripted path for the HMD, ignoring OpenXR
+    CameraPath {
+        /// The path to follow
+        camera_path: CameraPath,
+        /// How many frames have been played
+        frames: usize,
+    },
+}
+
+fn invalid_recording(path: &Path, reason: impl ToString) -> HothamError {
+    HothamError::InvalidInputRecording {
+        path: path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_record_and_play() {
+        let path = std::env::temp_dir().join(format!(
+            "hotham_input_recording_test_{}.jsonl",
+            std::process::id()
+        ));
+
+        let first = InputContext::default();
+        let second = InputContext::testing();
+
+        let mut recorder = InputRecorder::create(&path).unwrap();
+        recorder.record(&first).unwrap();
+        recorder.record(&second).unwrap();
+        assert_eq!(recorder.frames(), 2);
+        drop(recorder);
+
+        // Every frame should come back exactly as it was recorded, then the recording should end.
+        let mut playback = InputPlayback::open(&path).unwrap();
+        assert_eq!(playback.next_frame().unwrap(), Some(first));
+        assert_eq!(playback.next_frame().unwrap(), Some(second));
+        assert_eq!(playback.next_frame().unwrap(), None);
+        assert_eq!(playback.frames(), 2);
+
+        // Recordings from other versions shouldn't be played.
+        std::fs::write(&path, "{\"version\":0}\n").unwrap();
+        assert!(matches!(
+            InputPlayback::open(&path),
+            Err(HothamError::InvalidInputRecording { .. })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_looping() {
+        let path = std::env::temp_dir().join(format!(
+            "hotham_input_looping_test_{}.jsonl",
+            std::process::id()
+        ));
+
+        let mut recorder = InputRecorder::create(&path).unwrap();
+        recorder.record(&InputContext::testing()).unwrap();
+        drop(recorder);
+
+        let mut playback = InputPlayback::open(&path).unwrap();
+        playback.set_looping(true);
+        for _ in 0..3 {
+            assert_eq!(
+                playback.next_frame().unwrap(),
+                Some(InputContext::testing())
+            );
+        }
+        assert_eq!(playback.loops(), 2);
+        assert_eq!(playback.frames(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_camera_path() {
+        let camera_path = CameraPath {
+            keyframes: vec![
+                CameraKeyframe {
+                    time: 0.,
+                    translation: Vec3::ZERO,
+                    rotation: Quat::IDENTITY,
+                },
+                CameraKeyframe {
+                    time: 2.,
+                    translation: Vec3::new(2., 0., 0.),
+                    rotation: Quat::IDENTITY,
+                },
+            ],
+        };
+        assert_eq!(camera_path.duration(), 2.);
+
+        // The HMD should move smoothly along the path..
+        assert_eq!(
+            camera_path.sample(0.5).translation,
+            Vec3::new(0.5, 0., 0.).into()
+        );
+
+        // ..and start again when it gets to the end.
+        assert_eq!(
+            camera_path.sample(2.5).translation,
+            Vec3::new(0.5, 0., 0.).into()
+        );
+
+        // An empty path should just leave the HMD at the origin.
+        assert_eq!(CameraPath::default().sample(1.), Affine3A::IDENTITY);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+/// An Android intent the app was launched, or re-opened, with - eg. from a companion app or a link in the browser.
+///
+/// Intents arrive as [`crate::EngineEvent`]s in [`crate::TickData::events`], so apps can open straight into the content
+/// they point at. Try it out with `adb`:
+/// ```text
+/// adb shell am start -a android.intent.action.VIEW -d "mygame://level?id=3" -e friend kane <package>/android.app.NativeActivity
+/// ```
+///
+/// On desktop, set `HOTHAM_LAUNCH_URI` to launch with a `VIEW` intent for that URI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Intent {
+    /// The intent's action, eg. `android.intent.action.VIEW`
+    pub action: Option<String>,
+    /// The URI the intent points at, eg. a deep link like `mygame://level?id=3`
+    pub data: Option<String>,
+    /// The intent's extras. Values that aren't strings are converted to strings.
+    pub extras: BTreeMap<String, String>,
+}
+
+impl Intent {
+    /// The extra called `key`, if there is one
+    pub fn extra(&self, key: &str) -> Option<&str> {
+        self.extras.get(key).map(String::as_str)
+    }
+
+    /// The value of `name` in the query string of [`Intent::data`], eg. `3` for `id` in `mygame://level?id=3`.
+    /// Values aren't percent-decoded.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        let data = self.data.as_deref()?;
+        let query = data.split_once('?')?.1;
+        let query = query.split('#').next().unwrap_or_default();
+        query
+            .split('&')
+            .find_map(|pair| match pair.split_once('=') {
+                Some((key, value)) if key == name => Some(value),
+                None if pair == name => Some(""),
+                _ => None,
+            })
+    }
+}
+
+/// The activity's current intent, if it can be read.
+#[cfg(target_os = "android")]
+pub(crate) fn current_intent() -> Option<Intent> {
+    use jni::{
+        objects::{JObject, JString},
+        JNIEnv,
+    };
+
+    fn optional_string(env: &JNIEnv, object: JObject) -> jni::errors::Result<Option<String>> {
+        if object.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(env.get_string(JString::from(object))?.into()))
+    }
+
+    fn read(env: &JNIEnv, activity: JObject) -> jni::errors::Result<Option<Intent>> {
+        let intent = env
+            .call_method(activity, "getIntent", "()Landroid/content/Intent;", &[])?
+            .l()?;
+        if intent.is_null() {
+            return Ok(None);
+        }
+
+        let action = env
+            .call_method(intent, "getAction", "()Ljava/lang/String;", &[])?
+            .l()?;
+        let data = env
+            .call_method(intent, "getDataString", "()Ljava/lang/String;", &[])?
+            .l()?;
+        let mut extras = BTreeMap::new();
+        let bundle = env
+            .call_method(intent, "getExtras", "()Landroid/os/Bundle;", &[])?
+            .l()?;
+        if !bundle.is_null() {
+            let keys = env
+                .call_method(bundle, "keySet", "()Ljava/util/Set;", &[])?
+                .l()?;
+            let keys = env
+                .call_method(keys, "toArray", "()[Ljava/lang/Object;", &[])?
+                .l()?;
+            for i in 0..env.get_array_length(keys.into_inner())? {
+                let key = env.get_object_array_element(keys.into_inner(), i)?;
+                let value = env
+                    .call_method(
+                        bundle,
+                        "get",
+                        "(Ljava/lang/String;)Ljava/lang/Object;",
+                        &[key.into()],
+                    )?
+                    .l()?;
+                let value = if value.is_null() {
+                    None
+                } else {
+                    let value = env
+                        .call_method(value, "toString", "()Ljava/lang/String;", &[])?
+                        .l()?;
+                    optional_string(env, value)?
+                };
+                if let Some(key) = optional_string(env, key)? {
+                    extras.insert(key, value.unwrap_or_default());
+                }
+            }
+        }
+
+        Ok(Some(Intent {
+            action: optional_string(env, action)?,
+            data: optional_string(env, data)?,
+            extras,
+        }))
+    }
+
+    crate::contexts::platform_context::with_activity(read)
+        .map_err(|e| log::error!("[HOTHAM_INTENT] Unable to read intent: {:?}", e))
+        .ok()
+        .flatten()
+}
+
+/// A `VIEW` intent for `HOTHAM_LAUNCH_URI`, if it's set.
+#[cfg(not(target_os = "android"))]
+pub(crate) fn current_intent() -> Option<Intent> {
+    let uri = std::env::var("HOTHAM_LAUNCH_URI").ok()?;
+    Some(Intent {
+        action: Some("android.intent.action.VIEW".to_string()),
+        data: Some(uri),
+        extras: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_query_param() {
+        let intent = Intent {
+            data: Some("mygame://level?id=3&mode=hard&debug#start".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(intent.query_param("id"), Some("3"));
+        assert_eq!(intent.query_param("mode"), Some("hard"));
+        assert_eq!(intent.query_param("debug"), Some(""));
+        assert_eq!(intent.query_param("start"), None);
+        assert_eq!(intent.query_param("missing"), None);
+        assert_eq!(Intent::default().query_param("id"), None);
+    }
+}
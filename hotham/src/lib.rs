@@ -18,11 +18,12 @@ pub use ash::vk;
 pub use openxr as xr;
 pub use vk_shader_macros;
 
-pub use engine::{Engine, EngineBuilder, TickData};
+pub use engine::{Engine, EngineBuilder, EngineEvent, TickData};
 pub use glam;
 pub use hecs;
 pub use hotham_error::HothamError;
 pub use id_arena;
+pub use log;
 
 /// Components are data that are used to update the simulation and interact with the external world
 pub mod components;
@@ -36,6 +37,62 @@ mod hotham_error;
 /// Systems are functions called each frame to update either the external state or the current simulation
 pub mod systems;
 
+/// Crash reports, and ending the session cleanly when the app panics
+pub mod crash;
+
+/// Reloading game logic from a dynamic library while the engine is running
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
+
+/// Constants and helpers shared by the scripting backends
+#[cfg(any(feature = "wasm-scripting", feature = "lua-scripting"))]
+pub mod scripting;
+
+/// Reusable pieces of game logic: game states, scores and spawning things in time with music
+pub mod gameplay;
+
+/// Reusing entities that are spawned often, rather than spawning and despawning them
+pub mod entity_pool;
+
+/// Recording input to a file, and playing it back
+pub mod input_recording;
+
+/// Tracking how much memory the engine is using
+pub mod memory_stats;
+
+/// Measuring how frames line up with the runtime's display times
+pub mod frame_pacing;
+
+/// Where the app can keep its files on each platform
+pub mod paths;
+
+/// The Android intents the app is launched with
+pub mod intent;
+
+/// Placing content so it fits inside the user's play area
+pub mod placement;
+
+/// Subtitles for dialogue and other important sounds
+pub mod captions;
+
+/// Saving the state of the app when it's paused, and restoring it if the app was killed
+pub mod snapshot;
+
+/// Showing a static image while the app starts up
+pub mod splash_screen;
+
+/// Measuring frame times and memory usage over long sessions
+pub mod soak_test;
+
+/// Showing the app's text in the player's language
+pub mod localization;
+
+/// Capturing logs, streaming them to a desktop and showing them in the headset
+pub mod logging;
+
+/// Sending the player's interactions to analytics services
+pub mod analytics;
+
 /// Kitchen sink utility functions
 pub mod util;
 
@@ -59,5 +116,5 @@ pub const SWAPCHAIN_LENGTH: usize = 3;
 /// OpenXR view type
 pub const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
 
-/// OpenXR blend mode
+/// Preferred OpenXR blend mode, used if the runtime supports it. See `XrContext::blend_mode`.
 pub const BLEND_MODE: xr::EnvironmentBlendMode = xr::EnvironmentBlendMode::OPAQUE;
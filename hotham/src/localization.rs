@@ -0,0 +1,324 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use egui::{FontDefinitions, FontFamily};
+
+use crate::{HothamError, HothamResult};
+
+/// The locale used when the app has no strings for the player's own.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// The app's text in each language it's been translated into, and the language it's being shown in.
+///
+/// String tables are plain text, with one `key = value` pair per line. Blank lines and lines starting with `#` are
+/// ignored, and `\n` in a value is a line break. Values can have `{name}` placeholders, filled in by
+/// [`Localization::format`]:
+/// ```text
+/// # Main menu
+/// menu.play = Play
+/// menu.greeting = Welcome back, {name}!
+/// ```
+///
+/// The engine starts out in the player's language, from the operating system's locale. Switch languages at runtime
+/// with [`Localization::set_locale`] - [`crate::components::LocalizedText`] panels update themselves, and anything
+/// else can be redrawn when [`Localization::version`] changes. Strings missing from the current locale's table come
+/// from [`Localization::fallback_locale`]'s, and if they're missing there too the key is shown.
+///
+/// egui's built in fonts only cover Latin, Greek and Cyrillic text. To show Chinese, Japanese or Korean text, add a
+/// font that covers it with [`Localization::add_fallback_font`], eg. Noto Sans CJK. Hotham only draws text on
+/// [`crate::components::UIPanel`]s, so that's where the fallback fonts are used.
+#[derive(Debug, Clone)]
+pub struct Localization {
+    tables: HashMap<String, StringTable>,
+    locale: String,
+    fallback_locale: String,
+    fallback_fonts: Vec<(String, Vec<u8>)>,
+    version: u64,
+    fonts_version: u64,
+}
+
+/// The strings for one locale.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StringTable {
+    strings: HashMap<String, String>,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self::new(&system_locale().unwrap_or_else(|| DEFAULT_LOCALE.to_string()))
+    }
+}
+
+impl Localization {
+    /// Create a `Localization` showing text in `locale`, with no strings yet
+    pub fn new(locale: &str) -> Self {
+        Self {
+            tables: HashMap::new(),
+            locale: normalize_locale(locale).unwrap_or_else(|| DEFAULT_LOCALE.to_string()),
+            fallback_locale: DEFAULT_LOCALE.to_string(),
+            fallback_fonts: Vec::new(),
+            version: 0,
+            fonts_version: 0,
+        }
+    }
+
+    /// Add the strings for `locale`, from a string table in the format described above. Strings already added for the
+    /// locale are replaced.
+    pub fn add_table(&mut self, locale: &str, source: &str) -> HothamResult<()> {
+        let locale = normalize_locale(locale).unwrap_or_else(|| locale.to_string());
+        let table = StringTable::parse(&locale, source)?;
+        self.tables
+            .entry(locale)
+            .or_default()
+            .strings
+            .extend(table.strings);
+        self.version += 1;
+        Ok(())
+    }
+
+    /// The locale text is being shown in, eg. `en-US`
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// The locale strings come from when they're missing from the current one. Defaults to [`DEFAULT_LOCALE`].
+    pub fn fallback_locale(&self) -> &str {
+        &self.fallback_locale
+    }
+
+    /// Show text in `locale` from now on.
+    pub fn set_locale(&mut self, locale: &str) {
+        if let Some(locale) = normalize_locale(locale) {
+            self.locale = locale;
+            self.version += 1;
+        }
+    }
+
+    /// Take strings missing from the current locale from `locale`'s table instead.
+    pub fn set_fallback_locale(&mut self, locale: &str) {
+        if let Some(locale) = normalize_locale(locale) {
+            self.fallback_locale = locale;
+            self.version += 1;
+        }
+    }
+
+    /// The locales there are strings for
+    pub fn available_locales(&self) -> Vec<&str> {
+        let mut locales: Vec<_> = self.tables.keys().map(String::as_str).collect();
+        locales.sort_unstable();
+        locales
+    }
+
+    /// Goes up whenever the locale or the strings change, so anything showing text knows to look it up again.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The string for `key` in the current locale
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.lookup(&self.locale)
+            .chain(self.lookup(&self.fallback_locale))
+            .find_map(|table| table.strings.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// The string for `key` in the current locale, with each `{name}` placeholder replaced by its value in `args`.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        args.iter()
+            .fold(self.get(key).to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+    }
+
+    /// Draw characters egui's own fonts don't have with the font in `ttf_bytes`, eg. a CJK font. Fonts are tried in
+    /// the order they were added.
+    pub fn add_fallback_font(&mut self, name: &str, ttf_bytes: Vec<u8>) {
+        self.fallback_fonts.retain(|(n, _)| n != name);
+        self.fallback_fonts.push((name.to_string(), ttf_bytes));
+        self.fonts_version += 1;
+    }
+
+    /// Goes up whenever a fallback font is added, so panels know to load the fonts again.
+    pub(crate) fn fonts_version(&self) -> u64 {
+        self.fonts_version
+    }
+
+    /// egui's fonts, followed by the fallback fonts.
+    pub(crate) fn font_definitions(&self) -> FontDefinitions {
+        let mut fonts = FontDefinitions::default();
+        for (name, ttf_bytes) in &self.fallback_fonts {
+            fonts
+                .font_data
+                .insert(name.clone(), Cow::Owned(ttf_bytes.clone()));
+            for family in [FontFamily::Proportional, FontFamily::Monospace] {
+                fonts
+                    .fonts_for_family
+                    .entry(family)
+                    .or_default()
+                    .push(name.clone());
+            }
+        }
+        fonts
+    }
+
+    /// The tables to look in for `locale`: its own, then the one for its language, eg. `pt-BR` then `pt`.
+    fn lookup<'a>(&'a self, locale: &'a str) -> impl Iterator<Item = &'a StringTable> {
+        let language = locale.split('-').next().filter(|l| *l != locale);
+        std::iter::once(locale)
+            .chain(language)
+            .filter_map(move |locale| self.tables.get(locale))
+    }
+}
+
+impl StringTable {
+    /// Read a string table for `locale`
+    pub fn parse(locale: &str, source: &str) -> HothamResult<Self> {
+        let mut strings = HashMap::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(locale, index, "expected `key = value`"))?;
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(invalid(locale, index, "the key is empty"));
+            }
+            strings.insert(key.to_string(), value.trim().replace("\\n", "\n"));
+        }
+        Ok(Self { strings })
+    }
+
+    /// How many strings are in the table
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Is the table empty?
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+fn invalid(locale: &str, index: usize, reason: &str) -> HothamError {
+    HothamError::InvalidStringTable {
+        locale: locale.to_string(),
+        line: index + 1,
+        reason: reason.to_string(),
+    }
+}
+
+/// Tidy a locale up into a BCP 47 language tag, eg. `en_US.UTF-8` into `en-US`. Returns `None` for locales that don't
+/// name a language, like `C` and `POSIX`.
+pub fn normalize_locale(locale: &str) -> Option<String> {
+    let locale = locale.split(['.', '@']).next()?.trim().replace('_', "-");
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return None;
+    }
+    let mut parts = locale.split('-');
+    let language = parts.next()?.to_lowercase();
+    Some(
+        std::iter::once(language)
+            .chain(parts.map(|part| match part.len() {
+                // Regions are upper case, scripts are title case.
+                2 => part.to_uppercase(),
+                4 => part[..1].to_uppercase() + &part[1..].to_lowercase(),
+                _ => part.to_string(),
+            }))
+            .collect::<Vec<_>>()
+            .join("-"),
+    )
+}
+
+/// The player's locale, from Android's default `Locale`.
+#[cfg(target_os = "android")]
+pub fn system_locale() -> Option<String> {
+    use jni::{
+        objects::{JObject, JString},
+        JNIEnv,
+    };
+
+    fn read(env: &JNIEnv, _activity: JObject) -> jni::errors::Result<String> {
+        let locale = env
+            .call_static_method(
+                "java/util/Locale",
+                "getDefault",
+                "()Ljava/util/Locale;",
+                &[],
+            )?
+            .l()?;
+        let tag = env
+            .call_method(locale, "toLanguageTag", "()Ljava/lang/String;", &[])?
+            .l()?;
+        Ok(env.get_string(JString::from(tag))?.into())
+    }
+
+    crate::contexts::platform_context::with_activity(read)
+        .map_err(|e| log::error!("[HOTHAM_LOCALIZATION] Unable to read locale: {:?}", e))
+        .ok()
+        .and_then(|locale| normalize_locale(&locale))
+}
+
+/// The player's locale, from the usual environment variables.
+#[cfg(not(target_os = "android"))]
+pub fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find_map(|locale| normalize_locale(&locale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_localization() {
+        let mut localization = Localization::new("pt_BR.UTF-8");
+        assert_eq!(localization.locale(), "pt-BR");
+
+        localization
+            .add_table(
+                "en",
+                "# Menu\nmenu.play = Play\nmenu.quit = Quit\ngreeting = Hello, {name}!\\nReady?",
+            )
+            .unwrap();
+        localization
+            .add_table("pt", "menu.play = Jogar\ngreeting = Olá, {name}!")
+            .unwrap();
+        assert_eq!(localization.available_locales(), vec!["en", "pt"]);
+
+        // pt-BR falls back to pt, then to en, then to the key itself.
+        assert_eq!(localization.get("menu.play"), "Jogar");
+        assert_eq!(localization.get("menu.quit"), "Quit");
+        assert_eq!(localization.get("menu.missing"), "menu.missing");
+        assert_eq!(
+            localization.format("greeting", &[("name", "Ana")]),
+            "Olá, Ana!"
+        );
+
+        // Switching languages bumps the version, so panels know to update.
+        let version = localization.version();
+        localization.set_locale("en-GB");
+        assert!(localization.version() > version);
+        assert_eq!(
+            localization.format("greeting", &[("name", "Ana")]),
+            "Hello, Ana!\nReady?"
+        );
+
+        assert!(matches!(
+            localization.add_table("fr", "menu.play Jouer"),
+            Err(HothamError::InvalidStringTable { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_normalize_locale() {
+        assert_eq!(normalize_locale("en_US.UTF-8").unwrap(), "en-US");
+        assert_eq!(normalize_locale("zh-hant-tw").unwrap(), "zh-Hant-TW");
+        assert_eq!(normalize_locale("de_DE@euro").unwrap(), "de-DE");
+        assert_eq!(normalize_locale("C"), None);
+        assert_eq!(normalize_locale(""), None);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::HothamResult;
+
+/// The port logs are streamed on by default. Forward it from a Quest with `adb forward tcp:7878 tcp:7878`.
+pub const DEFAULT_REMOTE_LOG_PORT: u16 = 7878;
+
+/// How many log entries are kept by default.
+pub const DEFAULT_HISTORY_LENGTH: usize = 256;
+
+/// How long to wait for a remote client to accept a log entry before giving up on it.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// A single line logged through the [`log`] crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// How important is this entry?
+    pub level: Level,
+    /// The module that logged it
+    pub target: String,
+    /// What was logged
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<5} {} - {}", self.level, self.target, self.message)
+    }
+}
+
+/// The most recent entries logged through the [`LogSink`], shared between threads.
+#[derive(Debug, Clone)]
+pub struct LogHistory {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+    max_length: usize,
+}
+
+impl LogHistory {
+    /// Create an empty history that keeps at most `max_length` entries.
+    pub fn new(max_length: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            max_length,
+        }
+    }
+
+    /// Add an entry, forgetting the oldest one if the history is full.
+    pub fn push(&self, entry: LogEntry) {
+        if self.max_length == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_length {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Get the last `count` entries that are at least as important as `level`, oldest first.
+    pub fn recent(&self, level: Level, count: usize) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let mut recent = entries
+            .iter()
+            .rev()
+            .filter(|e| e.level <= level)
+            .take(count)
+            .cloned()
+            .collect::<Vec<_>>();
+        recent.reverse();
+        recent
+    }
+}
+
+/// A logger for the [`log`] crate that:
+///
+/// - prints each entry to stdout, which ends up in logcat on Android
+/// - keeps a [`LogHistory`] that can be shown in the headset with a [`crate::components::LogPanel`]
+/// - optionally streams entries over TCP, so they can be read on a desktop with `hotham remote-log`, or any other
+///   TCP client, without pulling the headset off to read logcat
+///
+/// `Engine` installs one automatically, unless another logger has already been installed.
+pub struct LogSink {
+    level: LevelFilter,
+    history: LogHistory,
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl LogSink {
+    /// Install a `LogSink` as the global logger, returning its history. Logs are streamed on `remote_port`, if given.
+    pub fn install(level: LevelFilter, remote_port: Option<u16>) -> HothamResult<LogHistory> {
+        let history = LogHistory::new(DEFAULT_HISTORY_LENGTH);
+        let sink = LogSink {
+            level,
+            history: history.clone(),
+            clients: Default::default(),
+        };
+
+        if let Some(port) = remote_port {
+            sink.listen(port)?;
+        }
+
+        log::set_boxed_logger(Box::new(sink))
+            .map_err(|_| anyhow!("Another logger has already been installed"))?;
+        log::set_max_level(level);
+
+        Ok(history)
+    }
+
+    /// Accept connections on `port` in the background. Each new client is sent the history so far, then every new
+    /// entry as it's logged.
+    ///
+    /// Only connections from the device itself are accepted, so logs aren't shared with the whole network - reach it
+    /// from a desktop with `adb forward`.
+    fn listen(&self, port: u16) -> HothamResult<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let history = self.history.clone();
+        let clients = self.clients.clone();
+
+        thread::Builder::new()
+            .name("hotham_remote_log".to_string())
+            .spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    // Never let a slow client hold up the game.
+                    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                    let backlog = history.recent(Level::Trace, usize::MAX);
+                    if backlog
+                        .iter()
+                        .all(|entry| writeln!(stream, "{}", entry).is_ok())
+                    {
+                        clients.lock().unwrap().push(stream);
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
+impl Log for LogSink {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let entry = LogEntry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        println!("{}", entry);
+
+        // Send the entry to everyone that's listening, forgetting anyone that's disconnected.
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain_mut(|client| writeln!(client, "{}", entry).is_ok());
+        drop(clients);
+
+        self.history.push(entry);
+    }
+
+    fn flush(&self) {
+        for client in self.clients.lock().unwrap().iter_mut() {
+            let _ = client.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: Level, message: &str) -> LogEntry {
+        LogEntry {
+            level,
+            target: "hotham".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    pub fn test_history() {
+        let history = LogHistory::new(3);
+        history.push(entry(Level::Warn, "one"));
+        history.push(entry(Level::Info, "two"));
+        history.push(entry(Level::Error, "three"));
+        history.push(entry(Level::Debug, "four"));
+
+        // The oldest entry should have been forgotten..
+        let all = history.recent(Level::Trace, 10);
+        assert_eq!(
+            all.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(),
+            vec!["two", "three", "four"]
+        );
+
+        // ..and it should be possible to only get the important ones.
+        assert_eq!(
+            history.recent(Level::Warn, 10),
+            vec![entry(Level::Error, "three")]
+        );
+        assert_eq!(
+            history.recent(Level::Trace, 1),
+            vec![entry(Level::Debug, "four")]
+        );
+    }
+
+    #[test]
+    pub fn test_display() {
+        assert_eq!(
+            entry(Level::Warn, "Out of memory").to_string(),
+            "WARN  hotham - Out of memory"
+        );
+    }
+}
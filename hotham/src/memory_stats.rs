@@ -0,0 +1,266 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use ash::vk;
+use hecs::World;
+
+use crate::{
+    contexts::VulkanContext,
+    rendering::{buffer::Buffer, memory::allocated_device_memory, resources::Resources},
+};
+
+/// How often [`MemoryStats`] are refreshed by the `memory_stats_system`.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How full one of the fixed size buffers in [`Resources`] is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Occupancy {
+    /// How many items are in use
+    pub used: usize,
+    /// How many items there is room for
+    pub capacity: usize,
+}
+
+impl Occupancy {
+    fn of<T>(buffer: &Buffer<T>) -> Self {
+        Self {
+            used: buffer.len,
+            capacity: buffer.max_len,
+        }
+    }
+
+    /// How full the buffer is, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        match self.capacity {
+            0 => 0.,
+            capacity => self.used as f32 / capacity as f32,
+        }
+    }
+}
+
+impl fmt::Display for Occupancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}% ({}/{})",
+            self.fraction() * 100.,
+            self.used,
+            self.capacity
+        )
+    }
+}
+
+/// A snapshot of how much memory the engine is using, refreshed every second by the `memory_stats_system` and
+/// shown in any [`crate::components::DebugPanel`].
+///
+/// Keep an eye on these when loading and unloading assets - [`Resources`] are never freed, so anything that keeps
+/// growing is a leak.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStats {
+    /// Bytes of device memory allocated by Hotham. Doesn't include the OpenXR runtime's allocations.
+    pub device_memory_bytes: u64,
+    /// How many separate allocations of device memory Hotham has made
+    pub device_memory_allocations: u64,
+    /// The total size of the device local memory heaps
+    pub device_local_heap_bytes: u64,
+    /// How much of the vertex buffer is in use
+    pub vertex_buffer: Occupancy,
+    /// How much of the index buffer is in use
+    pub index_buffer: Occupancy,
+    /// How much of the materials buffer is in use
+    pub materials_buffer: Occupancy,
+    /// How much of the skins buffer is in use
+    pub skins_buffer: Occupancy,
+    /// How many of the texture descriptors are in use
+    pub textures: Occupancy,
+    /// How many meshes are in the mesh arena
+    pub meshes: usize,
+    /// How many entities are in the `World`
+    pub entities: u32,
+    /// How many archetypes the `World` has - each new combination of components adds one, and they're never removed
+    pub archetypes: usize,
+    /// The resident set size of the process, where the platform can tell us
+    pub resident_bytes: Option<u64>,
+    /// Bytes allocated on the native heap. Only available on Android.
+    pub native_heap_bytes: Option<u64>,
+    last_refreshed: Option<Instant>,
+}
+
+impl MemoryStats {
+    /// Take a new snapshot.
+    pub fn refresh(
+        &mut self,
+        world: &World,
+        resources: &Resources,
+        vulkan_context: &VulkanContext,
+    ) {
+        let (device_memory_bytes, device_memory_allocations) = allocated_device_memory();
+        let memory_properties = unsafe {
+            vulkan_context
+                .instance
+                .get_physical_device_memory_properties(vulkan_context.physical_device)
+        };
+
+        *self = Self {
+            device_memory_bytes,
+            device_memory_allocations,
+            device_local_heap_bytes: memory_properties.memory_heaps
+                [..memory_properties.memory_heap_count as usize]
+                .iter()
+                .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+                .map(|heap| heap.size)
+                .sum(),
+            vertex_buffer: Occupancy::of(&resources.vertex_buffer),
+            index_buffer: Occupancy::of(&resources.index_buffer),
+            materials_buffer: Occupancy::of(&resources.materials_buffer),
+            skins_buffer: Occupancy::of(&resources.skins_buffer),
+            textures: Occupancy {
+                used: resources.texture_count() as _,
+                capacity: resources.texture_capacity() as _,
+            },
+            meshes: resources.mesh_data.len(),
+            entities: world.len(),
+            archetypes: world.archetypes().len(),
+            resident_bytes: resident_bytes(),
+            native_heap_bytes: native_heap_bytes(),
+            last_refreshed: Some(Instant::now()),
+        };
+    }
+
+    /// Is this snapshot old enough that it should be taken again?
+    pub(crate) fn needs_refresh(&self) -> bool {
+        match self.last_refreshed {
+            Some(last_refreshed) => last_refreshed.elapsed() >= REFRESH_INTERVAL,
+            None => true,
+        }
+    }
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "GPU memory: {} in {} allocations ({} device local)",
+            format_bytes(self.device_memory_bytes),
+            self.device_memory_allocations,
+            format_bytes(self.device_local_heap_bytes)
+        )?;
+        writeln!(f, "Vertices: {}", self.vertex_buffer)?;
+        writeln!(f, "Indices: {}", self.index_buffer)?;
+        writeln!(f, "Materials: {}", self.materials_buffer)?;
+        writeln!(f, "Skins: {}", self.skins_buffer)?;
+        writeln!(f, "Textures: {}", self.textures)?;
+        writeln!(f, "Meshes: {}", self.meshes)?;
+        writeln!(
+            f,
+            "Entities: {} in {} archetypes",
+            self.entities, self.archetypes
+        )?;
+        if let Some(resident_bytes) = self.resident_bytes {
+            writeln!(f, "Resident: {}", format_bytes(resident_bytes))?;
+        }
+        if let Some(native_heap_bytes) = self.native_heap_bytes {
+            writeln!(f, "Native heap: {}", format_bytes(native_heap_bytes))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024. * 1024.))
+}
+
+/// The resident set size of this process. Only available on Linux and Android.
+pub(crate) fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_resident_bytes(&status)
+}
+
+/// Find the resident set size in the contents of `/proc/self/status`.
+fn parse_resident_bytes(status: &str) -> Option<u64> {
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Bytes allocated on the native heap, as reported by Bionic. This is what `Debug.getNativeHeapAllocatedSize` reports.
+#[cfg(target_os = "android")]
+fn native_heap_bytes() -> Option<u64> {
+    /// Bionic's `struct mallinfo`, from `malloc.h`
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct MallInfo {
+        arena: usize,
+        ordblks: usize,
+        smblks: usize,
+        hblks: usize,
+        hblkhd: usize,
+        usmblks: usize,
+        fsmblks: usize,
+        uordblks: usize,
+        fordblks: usize,
+        keepcost: usize,
+    }
+
+    extern "C" {
+        fn mallinfo() -> MallInfo;
+    }
+
+    let info = unsafe { mallinfo() };
+    Some(info.uordblks as _)
+}
+
+#[cfg(not(target_os = "android"))]
+fn native_heap_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_display() {
+        let stats = MemoryStats {
+            device_memory_bytes: 64 * 1024 * 1024,
+            device_memory_allocations: 12,
+            device_local_heap_bytes: 4096 * 1024 * 1024,
+            vertex_buffer: Occupancy {
+                used: 500,
+                capacity: 2000,
+            },
+            entities: 30,
+            archetypes: 4,
+            resident_bytes: Some(200 * 1024 * 1024),
+            ..Default::default()
+        };
+
+        let text = stats.to_string();
+        assert!(
+            text.starts_with("GPU memory: 64.0 MB in 12 allocations (4096.0 MB device local)\n")
+        );
+        assert!(text.contains("Vertices: 25.0% (500/2000)\n"));
+        assert!(text.contains("Textures: 0.0% (0/0)\n"));
+        assert!(text.contains("Entities: 30 in 4 archetypes\n"));
+        assert!(text.contains("Resident: 200.0 MB\n"));
+        assert!(!text.contains("Native heap"));
+
+        // Stats that have never been taken should be taken straight away.
+        assert!(stats.needs_refresh());
+    }
+
+    #[test]
+    pub fn test_parse_resident_bytes() {
+        let status = "Name:\thotham\nVmPeak:\t  200000 kB\nVmRSS:\t  123456 kB\nThreads:\t12\n";
+        assert_eq!(parse_resident_bytes(status), Some(123456 * 1024));
+        assert_eq!(parse_resident_bytes("Name:\thotham\n"), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Where the app can keep its files on this platform.
+///
+/// On Android these come from the activity, so they're right for whichever user and storage device the app is
+/// installed for. On desktop they're the usual per-user directories, with the application's name on the end:
+///
+/// | | Android | Desktop |
+/// |-|-|-|
+/// | [`Paths::internal`] | The app's private `files` directory | The config directory, eg. `~/.config/<name>` |
+/// | [`Paths::external`] | The app's directory on shared storage | The documents directory, eg. `~/Documents/<name>` |
+/// | [`Paths::cache`] | The app's private `cache` directory | The cache directory, eg. `~/.cache/<name>` |
+///
+/// Directories are created when they're first written to. Android may delete anything in the cache when storage runs
+/// low, and nothing else will, so keep it in check with [`Paths::trim_cache`]. The engine removes temporary files
+/// left over from earlier runs when it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths {
+    internal: PathBuf,
+    external: PathBuf,
+    cache: PathBuf,
+}
+
+impl Paths {
+    /// The paths for this platform. `application_name` is only used on desktop - Android apps already have
+    /// directories of their own.
+    #[cfg(target_os = "android")]
+    pub fn new(_application_name: &str) -> Self {
+        let native_activity = ndk_glue::native_activity();
+        let internal = native_activity.internal_data_path().to_path_buf();
+        let external = native_activity.external_data_path().to_path_buf();
+        // The activity doesn't say where the cache is, but it's always next to `files`.
+        let cache = internal
+            .parent()
+            .map(|data| data.join("cache"))
+            .unwrap_or_else(|| internal.join("cache"));
+        Self {
+            external: if external.as_os_str().is_empty() {
+                internal.clone()
+            } else {
+                external
+            },
+            internal,
+            cache,
+        }
+    }
+
+    /// The paths for this platform. `application_name` is only used on desktop - Android apps already have
+    /// directories of their own.
+    #[cfg(not(target_os = "android"))]
+    pub fn new(application_name: &str) -> Self {
+        // If we can't find anywhere sensible, fall back to the working directory.
+        let internal = dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(application_name);
+        let external = dirs::document_dir()
+            .map(|dir| dir.join(application_name))
+            .unwrap_or_else(|| internal.clone());
+        let cache = dirs::cache_dir()
+            .map(|dir| dir.join(application_name))
+            .unwrap_or_else(|| internal.join("cache"));
+        Self {
+            internal,
+            external,
+            cache,
+        }
+    }
+
+    /// Use these directories instead of the platform's, eg. for tests.
+    pub fn with_roots(
+        internal: impl Into<PathBuf>,
+        external: impl Into<PathBuf>,
+        cache: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            internal: internal.into(),
+            external: external.into(),
+            cache: cache.into(),
+        }
+    }
+
+    /// Private storage that lasts until the app is uninstalled: settings, save games. [`crate::contexts::StorageContext`]
+    /// keeps its data here.
+    pub fn internal(&self) -> &Path {
+        &self.internal
+    }
+
+    /// Storage the user can get at, eg. over USB: screenshots, recordings, exported files. The same as
+    /// [`Paths::internal`] if there's no shared storage.
+    pub fn external(&self) -> &Path {
+        &self.external
+    }
+
+    /// Files that can be made again if they're lost, like downloads and baked data
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+
+    /// Where temporary files are made, inside [`Paths::cache`]
+    pub fn temp(&self) -> PathBuf {
+        self.cache.join("tmp")
+    }
+
+    /// Create a new, empty temporary file whose name starts with `prefix`. It's removed by
+    /// [`Paths::clean_temp_files`] once it's old enough, if it isn't removed before then.
+    pub fn create_temp_file(&self, prefix: &str) -> io::Result<(PathBuf, File)> {
+        let temp = self.temp();
+        fs::create_dir_all(&temp)?;
+        let path = temp.join(format!("{}{}", prefix, uuid::Uuid::new_v4()));
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        Ok((path, file))
+    }
+
+    /// Remove temporary files last modified more than `max_age` ago. Returns how many were removed.
+    pub fn clean_temp_files(&self, max_age: Duration) -> io::Result<usize> {
+        let cutoff = SystemTime::now()
+            .checked_sub(max_age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        self.clean_temp_files_before(cutoff)
+    }
+
+    fn clean_temp_files_before(&self, cutoff: SystemTime) -> io::Result<usize> {
+        let mut removed = 0;
+        for (path, _, modified) in files_in(&self.temp())? {
+            if modified < cutoff {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// How many bytes the files in [`Paths::cache`] take up, including temporary files
+    pub fn cache_size(&self) -> io::Result<u64> {
+        Ok(files_in(&self.cache)?.iter().map(|(_, len, _)| len).sum())
+    }
+
+    /// Remove the least recently modified files in [`Paths::cache`] until it takes up no more than `max_bytes`.
+    /// Returns how many bytes were freed.
+    pub fn trim_cache(&self, max_bytes: u64) -> io::Result<u64> {
+        let mut files = files_in(&self.cache)?;
+        let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut freed = 0;
+        for (path, len, _) in files {
+            if size <= max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= len;
+            freed += len;
+        }
+        Ok(freed)
+    }
+}
+
+/// Every file under `dir`, with its size and when it was last modified. Empty if `dir` doesn't exist.
+fn files_in(dir: &Path) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                files.push((entry.path(), metadata.len(), metadata.modified()?));
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    pub fn test_temp_files() {
+        let paths = temp_paths();
+        assert_eq!(paths.clean_temp_files(Duration::ZERO).unwrap(), 0);
+
+        let (path, mut file) = paths.create_temp_file("download_").unwrap();
+        file.write_all(b"hello").unwrap();
+        assert!(path.starts_with(paths.temp()));
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("download_"));
+
+        // Too new to clean up..
+        assert_eq!(
+            paths
+                .clean_temp_files_before(SystemTime::UNIX_EPOCH)
+                .unwrap(),
+            0
+        );
+        assert!(path.exists());
+
+        // ..until it isn't.
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert_eq!(paths.clean_temp_files_before(later).unwrap(), 1);
+        assert!(!path.exists());
+
+        fs::remove_dir_all(paths.cache().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    pub fn test_trim_cache() {
+        let paths = temp_paths();
+        assert_eq!(paths.cache_size().unwrap(), 0);
+
+        fs::create_dir_all(paths.cache().join("levels")).unwrap();
+        fs::write(paths.cache().join("a.bin"), [0; 100]).unwrap();
+        fs::write(paths.cache().join("levels").join("b.bin"), [0; 50]).unwrap();
+        let (_, mut file) = paths.create_temp_file("c").unwrap();
+        file.write_all(&[0; 25]).unwrap();
+        drop(file);
+        assert_eq!(paths.cache_size().unwrap(), 175);
+
+        // Nothing to do if it's already small enough.
+        assert_eq!(paths.trim_cache(1000).unwrap(), 0);
+
+        let freed = paths.trim_cache(100).unwrap();
+        assert!(freed >= 75);
+        assert!(paths.cache_size().unwrap() <= 100);
+
+        assert_eq!(paths.trim_cache(0).unwrap(), 175 - freed);
+        assert_eq!(paths.cache_size().unwrap(), 0);
+
+        fs::remove_dir_all(paths.cache().parent().unwrap()).unwrap();
+    }
+
+    fn temp_paths() -> Paths {
+        let root = std::env::temp_dir().join(format!("hotham_paths_{}", uuid::Uuid::new_v4()));
+        Paths::with_roots(
+            root.join("files"),
+            root.join("external"),
+            root.join("cache"),
+        )
+    }
+}
@@ -0,0 +1,215 @@
+use glam::{Vec2, Vec3};
+use openxr as xr;
+
+use crate::{components::LocalTransform, util::look_at_rotation};
+
+/// Play areas narrower or shallower than this, in metres, count as small. See [`PlayArea::is_small`].
+pub const SMALL_PLAY_AREA: f32 = 2.0;
+
+/// A good default for how far to keep content from the edge of the play area, in metres.
+pub const DEFAULT_MARGIN: f32 = 0.3;
+
+/// The user's play area, for placing content where they can reach it without walking into the boundary.
+///
+/// Get it from [`crate::contexts::XrContext::play_area`]. The runtime describes the play area as the largest
+/// rectangle that fits inside the boundary the user drew, centred on the `STAGE` origin, so irregular rooms are
+/// handled for you. Positions here are in stage space, which is what [`LocalTransform`]s of entities parented to
+/// [`crate::Engine::stage_entity`] are relative to - the HMD's [`LocalTransform`] is a good `user`.
+///
+/// Placed content is turned to face the user: its forward (-Z) axis points at them, like [`LocalTransform::look_at`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayArea {
+    half_extents: Vec2,
+}
+
+impl PlayArea {
+    /// A play area `width` metres across (X) and `depth` metres deep (Z)
+    pub fn new(width: f32, depth: f32) -> Self {
+        Self {
+            half_extents: Vec2::new(width.max(0.), depth.max(0.)) / 2.,
+        }
+    }
+
+    /// The play area for the bounds OpenXR gives for the `STAGE` space
+    pub fn from_xr(bounds: xr::Extent2Df) -> Self {
+        Self::new(bounds.width, bounds.height)
+    }
+
+    /// How wide the play area is, in metres
+    pub fn width(&self) -> f32 {
+        self.half_extents.x * 2.
+    }
+
+    /// How deep the play area is, in metres
+    pub fn depth(&self) -> f32 {
+        self.half_extents.y * 2.
+    }
+
+    /// Is this play area smaller than [`SMALL_PLAY_AREA`] on either side? Apps may want to bring content in closer,
+    /// or spawn fewer things at once.
+    pub fn is_small(&self) -> bool {
+        self.width() < SMALL_PLAY_AREA || self.depth() < SMALL_PLAY_AREA
+    }
+
+    /// Is `position` inside the play area, at least `margin` from its edges? Height is ignored.
+    pub fn contains(&self, position: Vec3, margin: f32) -> bool {
+        position.x.abs() <= self.half_extents.x - margin
+            && position.z.abs() <= self.half_extents.y - margin
+    }
+
+    /// The closest point to `position` that's at least `margin` from the edges of the play area, at the same height.
+    /// If the play area is too small for the margin, that's the middle.
+    pub fn clamp(&self, position: Vec3, margin: f32) -> Vec3 {
+        let limit = self.inner_half_extents(margin);
+        Vec3::new(
+            position.x.clamp(-limit.x, limit.x),
+            position.y,
+            position.z.clamp(-limit.y, limit.y),
+        )
+    }
+
+    /// Where to put something the user reads or reaches for, like a menu: `distance` metres in front of `user`, at
+    /// `height`.
+    ///
+    /// If that's outside the play area it's brought inside, and if that would put it almost on top of the user -
+    /// because they're facing the boundary - it's put between them and the middle of the play area instead.
+    pub fn in_front_of(
+        &self,
+        user: &LocalTransform,
+        distance: f32,
+        height: f32,
+        margin: f32,
+    ) -> LocalTransform {
+        let user_position = on_floor(user.translation);
+        let forward = on_floor(user.forward())
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Z);
+
+        let mut position = self.clamp(user_position + forward * distance, margin);
+        if position.distance(user_position) < distance / 2. {
+            if let Some(towards_middle) = (-user_position).try_normalize() {
+                position = self.clamp(user_position + towards_middle * distance, margin);
+            }
+        }
+
+        facing(position + Vec3::Y * height, user.translation)
+    }
+
+    /// The middle of the play area at `height`, facing `user` - for things the user walks around, like a table.
+    pub fn middle(&self, user: &LocalTransform, height: f32) -> LocalTransform {
+        facing(Vec3::Y * height, user.translation)
+    }
+
+    /// `count` places around the edge of the play area, `margin` in from it and at `height`, for things that come at
+    /// the user, like enemies.
+    ///
+    /// They're spread evenly by direction from the middle of the play area, starting with the direction the user is
+    /// facing, so the first ones are in view.
+    pub fn around_edge(
+        &self,
+        user: &LocalTransform,
+        count: usize,
+        height: f32,
+        margin: f32,
+    ) -> Vec<LocalTransform> {
+        let limit = self.inner_half_extents(margin);
+        let forward = on_floor(user.forward())
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Z);
+        let start = forward.z.atan2(forward.x);
+
+        (0..count)
+            .map(|i| {
+                let angle = start + std::f32::consts::TAU * i as f32 / count as f32;
+                let direction = Vec2::new(angle.cos(), angle.sin());
+                // How far we can go in this direction before leaving the rectangle.
+                let reach = (limit.x / direction.x.abs()).min(limit.y / direction.y.abs());
+                let point = direction * reach;
+                facing(Vec3::new(point.x, height, point.y), user.translation)
+            })
+            .collect()
+    }
+
+    fn inner_half_extents(&self, margin: f32) -> Vec2 {
+        (self.half_extents - Vec2::splat(margin)).max(Vec2::ZERO)
+    }
+}
+
+fn on_floor(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, 0., v.z)
+}
+
+/// A transform at `position`, turned about the vertical axis to face `target`.
+fn facing(position: Vec3, target: Vec3) -> LocalTransform {
+    let target = Vec3::new(target.x, position.y, target.z);
+    let rotation = look_at_rotation(position, target, Vec3::Y).unwrap_or_default();
+    LocalTransform::from_rotation_translation(rotation, position)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+
+    #[test]
+    pub fn test_clamp() {
+        let play_area = PlayArea::new(3., 2.);
+        assert!(!play_area.is_small());
+        assert!(PlayArea::new(3., 1.5).is_small());
+
+        assert!(play_area.contains(Vec3::new(1., 1.7, 0.5), 0.3));
+        assert!(!play_area.contains(Vec3::new(1.4, 0., 0.), 0.3));
+        assert_eq!(
+            play_area.clamp(Vec3::new(5., 1., -5.), 0.5),
+            Vec3::new(1., 1., -0.5)
+        );
+
+        // Too small for the margin, so everything goes in the middle.
+        let tiny = PlayArea::new(0.5, 0.5);
+        assert_eq!(tiny.clamp(Vec3::new(1., 0., 1.), 0.3), Vec3::ZERO);
+    }
+
+    #[test]
+    pub fn test_in_front_of() {
+        let play_area = PlayArea::new(4., 4.);
+
+        // Plenty of room in front of the user.
+        let user =
+            LocalTransform::from_rotation_translation(Quat::IDENTITY, Vec3::new(0., 1.6, 0.));
+        let menu = play_area.in_front_of(&user, 1., 1.2, DEFAULT_MARGIN);
+        assert!(menu.translation.abs_diff_eq(Vec3::new(0., 1.2, -1.), 0.001));
+        assert!(menu.forward().abs_diff_eq(Vec3::Z, 0.001));
+
+        // Facing the boundary, so the menu goes towards the middle instead.
+        let user =
+            LocalTransform::from_rotation_translation(Quat::IDENTITY, Vec3::new(0., 1.6, -1.6));
+        let menu = play_area.in_front_of(&user, 1., 1.2, DEFAULT_MARGIN);
+        assert!(menu
+            .translation
+            .abs_diff_eq(Vec3::new(0., 1.2, -0.6), 0.001));
+        assert!(menu.forward().abs_diff_eq(Vec3::NEG_Z, 0.001));
+    }
+
+    #[test]
+    pub fn test_around_edge() {
+        let play_area = PlayArea::new(3., 2.);
+        let user =
+            LocalTransform::from_rotation_translation(Quat::IDENTITY, Vec3::new(0., 1.6, 0.));
+        let spawns = play_area.around_edge(&user, 4, 0., DEFAULT_MARGIN);
+        assert_eq!(spawns.len(), 4);
+
+        // The first is straight ahead, and the rest go around the edge.
+        assert!(spawns[0]
+            .translation
+            .abs_diff_eq(Vec3::new(0., 0., -0.7), 0.001));
+        assert!(spawns[1]
+            .translation
+            .abs_diff_eq(Vec3::new(1.2, 0., 0.), 0.001));
+        for spawn in &spawns {
+            assert!(play_area.contains(spawn.translation, DEFAULT_MARGIN - 0.001));
+            let to_user = (on_floor(user.translation) - spawn.translation).normalize();
+            assert!(spawn.forward().abs_diff_eq(to_user, 0.001));
+        }
+    }
+}
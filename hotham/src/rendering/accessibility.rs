@@ -0,0 +1,398 @@
+use std::{mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Mat3, Mat4, Vec4};
+use vk_shader_macros::include_glsl;
+
+use crate::{
+    contexts::{
+        render_context::{
+            create_push_constant, create_shader, RENDER_PASS_DYNAMIC_STATES, SAMPLES,
+        },
+        VulkanContext,
+    },
+    rendering::{resources::Resources, vertex::Vertex},
+};
+
+static OUTLINE_VERT: &[u32] = include_glsl!("src/shaders/outline.vert", target: vulkan1_1);
+static OUTLINE_FRAG: &[u32] = include_glsl!("src/shaders/outline.frag", target: vulkan1_1);
+
+/// A kind of color blindness to simulate or correct for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorVision {
+    /// Colors are drawn as they are
+    #[default]
+    Normal,
+    /// No red cones, so reds and greens are hard to tell apart
+    Protanopia,
+    /// No green cones, the most common kind of color blindness, so reds and greens are hard to tell apart
+    Deuteranopia,
+    /// No blue cones, so blues and greens, and yellows and pinks, are hard to tell apart
+    Tritanopia,
+}
+
+/// What to do about [`AccessibilitySettings::color_vision`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorVisionMode {
+    /// Shift the colors that are hard to tell apart towards ones that aren't, for players who are color blind
+    #[default]
+    Daltonize,
+    /// Show how the view looks to someone who is color blind, for checking an app can be played without the colors
+    /// that are lost
+    Simulate,
+}
+
+/// Settings to make the view easier to see. These can be changed at any time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccessibilitySettings {
+    /// The kind of color blindness to simulate or correct for
+    pub color_vision: ColorVision,
+    /// Whether to correct for [`AccessibilitySettings::color_vision`], or simulate it
+    pub color_vision_mode: ColorVisionMode,
+    /// Draw a solid outline around every visible [`crate::components::Grabbable`], so things that can be picked up
+    /// stand out
+    pub outline_interactables: bool,
+    /// The color of the outlines, in linear RGB
+    pub outline_color: Vec4,
+    /// How thick the outlines are, in meters
+    pub outline_width: f32,
+    /// How much bigger to draw the text on [`crate::components::UIPanel`]s. Everything else on the panel is scaled
+    /// with it, so buttons still fit their labels.
+    pub text_scale: f32,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            color_vision: Default::default(),
+            color_vision_mode: Default::default(),
+            outline_interactables: false,
+            outline_color: Vec4::new(1., 0.85, 0., 1.),
+            outline_width: 0.004,
+            text_scale: 1.,
+        }
+    }
+}
+
+impl AccessibilitySettings {
+    /// The matrix every color is multiplied by after tonemapping, as it's sent to the shaders in
+    /// [`super::scene_data::SceneData::color_transform`].
+    pub fn color_transform(&self) -> Mat4 {
+        let simulation = match self.color_vision {
+            ColorVision::Normal => return Mat4::IDENTITY,
+            ColorVision::Protanopia => PROTANOPIA,
+            ColorVision::Deuteranopia => DEUTERANOPIA,
+            ColorVision::Tritanopia => TRITANOPIA,
+        };
+        let simulation = Mat3::from_cols_array(&simulation).transpose();
+
+        let transform = match self.color_vision_mode {
+            ColorVisionMode::Simulate => simulation,
+            // Add back the color that's lost, moved into channels that can still be seen.
+            ColorVisionMode::Daltonize => {
+                let error_shift = Mat3::from_cols_array(&ERROR_SHIFT).transpose();
+                Mat3::IDENTITY + error_shift * (Mat3::IDENTITY - simulation)
+            }
+        };
+        Mat4::from_mat3(transform)
+    }
+
+    /// How much the text on panels is scaled by, kept to a size that still fits on them
+    pub(crate) fn panel_text_scale(&self) -> f32 {
+        self.text_scale.clamp(0.5, 3.)
+    }
+}
+
+// How colors look in linear RGB with each kind of color blindness, at full severity, row by row. From Machado,
+// Oliveira and Fernandes, "A Physiologically-based Model for Simulation of Color Vision Deficiency" (2009).
+#[rustfmt::skip]
+const PROTANOPIA: [f32; 9] = [
+    0.152286, 1.052583, -0.204868,
+    0.114503, 0.786281, 0.099216,
+    -0.003882, -0.048116, 1.051998,
+];
+#[rustfmt::skip]
+const DEUTERANOPIA: [f32; 9] = [
+    0.367322, 0.860646, -0.227968,
+    0.280085, 0.672501, 0.047413,
+    -0.011820, 0.042940, 0.968881,
+];
+#[rustfmt::skip]
+const TRITANOPIA: [f32; 9] = [
+    1.255528, -0.076749, -0.178779,
+    -0.078411, 0.930809, 0.147602,
+    0.004733, 0.691367, 0.303900,
+];
+
+// Moves the color that's lost into the green and blue channels, row by row, as in Fidaner, Lin and Ozguven,
+// "Analysis of Color Blindness" (2005).
+#[rustfmt::skip]
+const ERROR_SHIFT: [f32; 9] = [
+    0.0, 0.0, 0.0,
+    0.7, 1.0, 0.0,
+    0.7, 0.0, 1.0,
+];
+
+/// A mesh to outline, in globally oriented stage space
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OutlineInstance {
+    pub gos_from_local: Mat4,
+    pub skin_id: u32,
+    pub index_buffer_offset: u32,
+    pub indices_count: u32,
+    pub vertex_buffer_offset: u32,
+}
+
+/// An outline, as it's sent to the outline shaders
+#[repr(C)]
+struct OutlineParams {
+    gos_from_local: Mat4,
+    color: Vec4,
+    width: f32,
+    skin_id: u32,
+}
+
+/// Draws high-contrast outlines around meshes, by drawing the back of each mesh pushed out along its normals. Anything
+/// in front of the mesh still hides its outline.
+pub struct OutlinePipeline {
+    /// Draws the outlines, depth tested against the scene
+    pub pipeline: vk::Pipeline,
+    /// Uses the shared descriptor set for the scene data and skins
+    pub pipeline_layout: vk::PipelineLayout,
+}
+
+impl OutlinePipeline {
+    pub(crate) fn new(
+        vulkan_context: &VulkanContext,
+        set_layout: vk::DescriptorSetLayout,
+        render_area: &vk::Rect2D,
+        render_pass: vk::RenderPass,
+        reversed_z: bool,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<OutlineParams>() as _)
+            .build();
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(slice_from_ref(&set_layout))
+                    .push_constant_ranges(slice_from_ref(&push_constant_range)),
+                None,
+            )
+        }?;
+
+        let (vertex_shader, vertex_stage) =
+            create_shader(OUTLINE_VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
+        let (fragment_shader, fragment_stage) =
+            create_shader(OUTLINE_FRAG, vk::ShaderStageFlags::FRAGMENT, vulkan_context)?;
+        let stages = [vertex_stage, fragment_stage];
+
+        let vertex_binding_description = vk::VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(size_of::<Vertex>() as _)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build();
+        let vertex_attribute_descriptions = Vertex::attribute_descriptions();
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_attribute_descriptions(&vertex_attribute_descriptions)
+            .vertex_binding_descriptions(slice_from_ref(&vertex_binding_description));
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: render_area.extent.width as _,
+            height: render_area.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice_from_ref(&viewport))
+            .scissors(slice_from_ref(render_area));
+
+        // Only the back of the pushed out mesh is drawn, so the mesh itself covers everything but its rim.
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::FRONT)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .line_width(1.0);
+        let multisample_state =
+            vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(SAMPLES);
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(if reversed_z {
+                vk::CompareOp::GREATER_OR_EQUAL
+            } else {
+                vk::CompareOp::LESS_OR_EQUAL
+            });
+
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(false)
+            .build();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(slice_from_ref(&color_blend_attachment));
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
+        let create_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .dynamic_state(&dynamic_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .layout(pipeline_layout)
+            .render_pass(render_pass)
+            .subpass(0)
+            .build();
+
+        let pipelines = unsafe {
+            device.create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                slice_from_ref(&create_info),
+                None,
+            )
+        }
+        .map_err(|(_, r)| r)?;
+
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+
+        Ok(Self {
+            pipeline: pipelines[0],
+            pipeline_layout,
+        })
+    }
+
+    /// Draw an outline around each of `outlines`. Must be inside the PBR render pass.
+    pub(crate) unsafe fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        resources: &Resources,
+        outlines: &[OutlineInstance],
+        settings: &AccessibilitySettings,
+    ) {
+        if outlines.is_empty() {
+            return;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            slice_from_ref(&descriptor_set),
+            &[],
+        );
+        device.cmd_bind_index_buffer(
+            command_buffer,
+            resources.index_buffer.buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            slice_from_ref(&resources.vertex_buffer.buffer),
+            &[0],
+        );
+
+        for outline in outlines {
+            let params = OutlineParams {
+                gos_from_local: outline.gos_from_local,
+                color: settings.outline_color,
+                width: settings.outline_width,
+                skin_id: outline.skin_id,
+            };
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                create_push_constant(&params),
+            );
+            device.cmd_draw_indexed(
+                command_buffer,
+                outline.indices_count,
+                1,
+                outline.index_buffer_offset,
+                outline.vertex_buffer_offset as _,
+                0,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::Vec3;
+
+    fn apply(transform: &Mat4, color: Vec3) -> Vec3 {
+        transform.transform_vector3(color)
+    }
+
+    #[test]
+    pub fn test_color_transform() {
+        let mut settings = AccessibilitySettings::default();
+        assert_eq!(settings.color_transform(), Mat4::IDENTITY);
+
+        for color_vision in [
+            ColorVision::Protanopia,
+            ColorVision::Deuteranopia,
+            ColorVision::Tritanopia,
+        ] {
+            settings.color_vision = color_vision;
+
+            // Greys look the same to everyone, and are left alone.
+            for color_vision_mode in [ColorVisionMode::Simulate, ColorVisionMode::Daltonize] {
+                settings.color_vision_mode = color_vision_mode;
+                let grey = Vec3::splat(0.5);
+                assert_relative_eq!(
+                    apply(&settings.color_transform(), grey),
+                    grey,
+                    epsilon = 0.001
+                );
+            }
+        }
+
+        // Red and green are hard to tell apart without green cones..
+        settings.color_vision = ColorVision::Deuteranopia;
+        settings.color_vision_mode = ColorVisionMode::Simulate;
+        let simulation = settings.color_transform();
+        let (red, green) = (Vec3::new(0.8, 0.2, 0.1), Vec3::new(0.3, 0.6, 0.1));
+        let before = apply(&simulation, red).distance(apply(&simulation, green));
+
+        // ..but easier once they've been daltonized.
+        settings.color_vision_mode = ColorVisionMode::Daltonize;
+        let daltonize = settings.color_transform();
+        let after = apply(&simulation, apply(&daltonize, red))
+            .distance(apply(&simulation, apply(&daltonize, green)));
+        assert!(after > before * 1.5, "{} vs {}", after, before);
+    }
+}
@@ -5,7 +5,7 @@ use vulkan_context::VulkanContext;
 
 use crate::contexts::vulkan_context;
 
-use super::memory::allocate_memory;
+use super::memory::{allocate_memory, track_free};
 
 /// A wrapper around a chunk of allocated memory on the GPU
 #[derive(Debug, Clone)]
@@ -115,8 +115,10 @@ impl<T: Sized> Buffer<T> {
 
     /// safety: After calling this function the buffer will be in an UNUSABLE state
     pub unsafe fn destroy(&mut self, device: &ash::Device) {
+        let memory_requirements = device.get_buffer_memory_requirements(self.buffer);
         device.unmap_memory(self.device_memory);
         device.free_memory(self.device_memory, None);
+        track_free(memory_requirements.size);
         device.destroy_buffer(self.buffer, None);
         self.len = 0;
     }
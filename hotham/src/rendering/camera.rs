@@ -44,6 +44,55 @@ impl Camera {
     }
 }
 
+/// The distance to the near clip plane used if none is set
+pub const DEFAULT_NEAR_CLIP: f32 = 0.05;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// How close and how far away things can be from the camera before they are clipped.
+///
+/// By default depth is reversed, so the near plane is at depth 1 and the far plane is at depth 0. Along with a float
+/// depth buffer, this keeps depth precision good enough for both cockpit-scale and planetary-scale content.
+pub struct ClipPlanes {
+    /// Distance to the near clip plane, in meters
+    pub near: f32,
+    /// Distance to the far clip plane, in meters, or `None` for an infinitely far away far plane
+    pub far: Option<f32>,
+}
+
+impl Default for ClipPlanes {
+    fn default() -> Self {
+        Self::infinite(DEFAULT_NEAR_CLIP)
+    }
+}
+
+impl ClipPlanes {
+    /// Clip planes with a finite far plane
+    pub fn new(near: f32, far: f32) -> Self {
+        debug_assert!(near > 0. && far > near, "Invalid clip planes");
+        Self {
+            near,
+            far: Some(far),
+        }
+    }
+
+    /// Clip planes with an infinitely far away far plane
+    pub fn infinite(near: f32) -> Self {
+        debug_assert!(near > 0., "Invalid clip planes");
+        Self { near, far: None }
+    }
+
+    /// The distances at depth 0 and depth 1, in that order. These are the `near_z` and `far_z` that
+    /// `XR_KHR_composition_layer_depth` expects for the depth buffer.
+    pub fn depth_range(&self, reversed_z: bool) -> (f32, f32) {
+        let far = self.far.unwrap_or(f32::INFINITY);
+        if reversed_z {
+            (far, self.near)
+        } else {
+            (self.near, far)
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 /// A frustrum for the virtual camera.
 pub struct Frustum {
@@ -58,9 +107,14 @@ pub struct Frustum {
 }
 
 impl Frustum {
-    #[rustfmt::skip]
-    /// Compute right-handed y-up inverse Z perspective projection matrix
+    /// Compute right-handed y-up inverse Z perspective projection matrix with an infinite far plane
     pub fn projection(&self, znear: f32) -> Mat4 {
+        self.projection_with_clip_planes(&ClipPlanes::infinite(znear), true)
+    }
+
+    #[rustfmt::skip]
+    /// Compute right-handed y-up perspective projection matrix, with inverse Z if `reversed_z` is set
+    pub fn projection_with_clip_planes(&self, clip_planes: &ClipPlanes, reversed_z: bool) -> Mat4 {
         // Based on http://dev.theomader.com/depth-precision/ + OpenVR docs
         let left = self.left.tan();
         let right = self.right.tan();
@@ -71,12 +125,21 @@ impl Frustum {
         let sx = right + left;
         let sy = down + up;
 
+        // Map the near plane to depth 1 and the far plane to depth 0, or the other way around without reversed Z.
+        let znear = clip_planes.near;
+        let (a, b) = match (clip_planes.far, reversed_z) {
+            (Some(zfar), true) => (znear / (zfar - znear), znear * zfar / (zfar - znear)),
+            (None, true) => (0.0, znear),
+            (Some(zfar), false) => (-zfar / (zfar - znear), -znear * zfar / (zfar - znear)),
+            (None, false) => (-1.0, -znear),
+        };
+
         // TODO: This was originally written using nalgebra's row-order format, so we just
         // transpose the resulting matrix. We should probably just.. you know, rewrite this.
         Mat4::from_cols_array(&[
             2.0 * idx, 0.0, sx * idx, 0.0,
             0.0, 2.0 * idy, sy * idy, 0.0,
-            0.0,       0.0,      0.0, znear,
+            0.0,       0.0,        a, b,
             0.0,       0.0,     -1.0, 0.0]).transpose()
     }
 }
@@ -93,7 +156,8 @@ impl From<xr::Fovf> for Frustum {
 }
 
 /// Normals of the clipping planes are pointing towards the inside of the frustum.
-/// We are only using four planes per camera. The near and far planes are not used.
+/// We are only using four planes per camera. The near and far planes are not used, so these are the same whether
+/// or not depth is reversed.
 /// This link points to a paper describing the math behind these expressions:
 /// https://www.gamedevs.org/uploads/fast-extraction-viewing-frustum-planes-from-world-view-projection-matrix.pdf
 pub(crate) fn extract_planes_from_frustum(frustum: &Mat4) -> Mat4 {
@@ -110,3 +174,57 @@ pub(crate) fn extract_planes_from_frustum(frustum: &Mat4) -> Mat4 {
 pub(crate) fn normalize_plane(p: Vec4) -> Vec4 {
     p / p.truncate().length()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::Vec3;
+
+    #[test]
+    pub fn test_projection_with_clip_planes() {
+        let frustum = Frustum {
+            left: -0.7,
+            right: 0.7,
+            up: 0.7,
+            down: -0.7,
+        };
+        let depth = |projection: Mat4, distance: f32| {
+            projection.project_point3(Vec3::new(0., 0., -distance)).z
+        };
+
+        // Finite clip planes should map the near plane to 1 and the far plane to 0..
+        let projection = frustum.projection_with_clip_planes(&ClipPlanes::new(0.1, 1000.), true);
+        assert_relative_eq!(depth(projection, 0.1), 1.);
+        assert_relative_eq!(depth(projection, 1000.), 0.);
+        assert!(depth(projection, 2000.) < 0.);
+
+        // ..and infinite ones should never reach 0.
+        let projection = frustum.projection_with_clip_planes(&ClipPlanes::infinite(0.1), true);
+        assert_relative_eq!(depth(projection, 0.1), 1.);
+        assert!(depth(projection, 1.0e6) > 0.);
+        assert_eq!(projection, frustum.projection(0.1));
+
+        // Without reversed Z, it's the other way around.
+        let projection = frustum.projection_with_clip_planes(&ClipPlanes::new(0.1, 1000.), false);
+        assert_relative_eq!(depth(projection, 0.1), 0.);
+        assert_relative_eq!(depth(projection, 1000.), 1., epsilon = 1.0e-6);
+        let projection = frustum.projection_with_clip_planes(&ClipPlanes::infinite(0.1), false);
+        assert_relative_eq!(depth(projection, 0.1), 0.);
+        assert!(depth(projection, 1.0e6) < 1.);
+
+        // Culling shouldn't care which way around depth is.
+        assert_eq!(
+            extract_planes_from_frustum(&projection),
+            extract_planes_from_frustum(&frustum.projection(0.1))
+        );
+
+        let clip_planes = ClipPlanes::new(0.1, 1000.);
+        assert_eq!(clip_planes.depth_range(true), (1000., 0.1));
+        assert_eq!(clip_planes.depth_range(false), (0.1, 1000.));
+        assert_eq!(
+            ClipPlanes::default().depth_range(true),
+            (f32::INFINITY, DEFAULT_NEAR_CLIP)
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use std::{ffi::CStr, slice::from_ref as slice_from_ref};
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+
+use crate::contexts::{render_context::PIPELINE_DEPTH, VulkanContext};
+
+use super::descriptors::Descriptors;
+
+/// The first word of every SPIR-V module
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// A buffer bound to an app's compute pass - see [`ComputePasses::add`]. Each is bound as a storage buffer, at the
+/// binding matching its position in the list the pass was created with, in set 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeBuffer {
+    /// Every mesh's vertices, as [`super::vertex::Vertex`]es
+    Vertices,
+    /// This frame's [`super::resources::DrawData`], one for each instance drawn. It's rewritten every frame, so only
+    /// read from it.
+    DrawData,
+    /// Every [`super::material::Material`]
+    Materials,
+    /// A buffer of the app's own, created with `STORAGE_BUFFER` usage
+    App(vk::Buffer),
+}
+
+/// Identifies a compute pass created with [`ComputePasses::add`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ComputePassId(usize);
+
+/// The engine's buffers, as they are this frame
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EngineBuffers {
+    pub vertices: vk::Buffer,
+    pub draw_data: vk::Buffer,
+    pub materials: vk::Buffer,
+}
+
+impl EngineBuffers {
+    fn get(&self, buffer: ComputeBuffer) -> vk::Buffer {
+        match buffer {
+            ComputeBuffer::Vertices => self.vertices,
+            ComputeBuffer::DrawData => self.draw_data,
+            ComputeBuffer::Materials => self.materials,
+            ComputeBuffer::App(buffer) => buffer,
+        }
+    }
+}
+
+struct ComputePass {
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_sets: [vk::DescriptorSet; PIPELINE_DEPTH],
+    bindings: Vec<ComputeBuffer>,
+    push_constant_size: u32,
+}
+
+struct ComputeDispatch {
+    pass: usize,
+    group_count: [u32; 3],
+    push_constants: Vec<u8>,
+}
+
+/// Compute shaders written by apps, eg. for simulations like boids or fluids that feed what's drawn.
+///
+/// Create a pass from SPIR-V with [`ComputePasses::add`], then [`ComputePasses::dispatch`] it each frame it should
+/// run. Dispatches are recorded in the order they were made, after the engine's own compute work and before the world
+/// is drawn. Barriers are put in automatically: each dispatch sees what the ones before it wrote, everything drawn
+/// this frame sees what they all wrote - as vertices, indices, indirect commands or from shaders - and nothing is
+/// written while the last frame is still reading it.
+#[derive(Default)]
+pub struct ComputePasses {
+    passes: Vec<ComputePass>,
+    scheduled: Vec<ComputeDispatch>,
+}
+
+impl ComputePasses {
+    /// Create a compute pass running `spirv`, which reads and writes `bindings`, with `push_constant_size` bytes of push
+    /// constants given to each dispatch.
+    pub fn add(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        descriptors: &Descriptors,
+        spirv: &[u32],
+        bindings: &[ComputeBuffer],
+        push_constant_size: u32,
+    ) -> Result<ComputePassId> {
+        let max_push_constant_size = vulkan_context
+            .physical_device_properties
+            .limits
+            .max_push_constants_size;
+        check_pass(spirv, push_constant_size, max_push_constant_size)?;
+
+        let device = &vulkan_context.device;
+        let pass = unsafe {
+            let layout_bindings = (0..bindings.len() as u32)
+                .map(|binding| vk::DescriptorSetLayoutBinding {
+                    binding,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    descriptor_count: 1,
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+            let set_layout = device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings),
+                None,
+            )?;
+
+            let push_constant_range = vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(push_constant_size)
+                .build();
+            let push_constant_ranges = if push_constant_size > 0 {
+                slice_from_ref(&push_constant_range)
+            } else {
+                &[]
+            };
+            let pipeline_layout = device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(slice_from_ref(&set_layout))
+                    .push_constant_ranges(push_constant_ranges),
+                None,
+            )?;
+            let pipeline = create_compute_pipeline(device, pipeline_layout, spirv)?;
+
+            let set_layouts = [set_layout; PIPELINE_DEPTH];
+            let descriptor_sets = device.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(descriptors.pool)
+                    .set_layouts(&set_layouts),
+            )?;
+
+            ComputePass {
+                pipeline,
+                pipeline_layout,
+                descriptor_sets: [descriptor_sets[0], descriptor_sets[1]],
+                bindings: bindings.to_vec(),
+                push_constant_size,
+            }
+        };
+
+        self.passes.push(pass);
+        Ok(ComputePassId(self.passes.len() - 1))
+    }
+
+    /// Run `pass` this frame with `group_count` workgroups, giving it `push_constants`, which must be the size the pass
+    /// was created with.
+    pub fn dispatch(
+        &mut self,
+        pass: ComputePassId,
+        group_count: [u32; 3],
+        push_constants: &[u8],
+    ) -> Result<()> {
+        let compute_pass = self
+            .passes
+            .get(pass.0)
+            .ok_or_else(|| anyhow!("There's no compute pass {:?}", pass))?;
+        if push_constants.len() != compute_pass.push_constant_size as usize {
+            return Err(anyhow!(
+                "Compute pass {:?} takes {} bytes of push constants, but was given {}",
+                pass,
+                compute_pass.push_constant_size,
+                push_constants.len()
+            ));
+        }
+        if group_count.contains(&0) {
+            return Ok(());
+        }
+
+        self.scheduled.push(ComputeDispatch {
+            pass: pass.0,
+            group_count,
+            push_constants: push_constants.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Record this frame's dispatches into `command_buffer`, which must be outside a render pass, and forget them.
+    pub(crate) unsafe fn record(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        buffers: EngineBuffers,
+    ) {
+        if self.scheduled.is_empty() {
+            return;
+        }
+
+        // Don't write anything the last frame might still be reading.
+        barrier(
+            device,
+            command_buffer,
+            DRAW_STAGES,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::empty(),
+        );
+
+        for (index, dispatch) in self.scheduled.iter().enumerate() {
+            let pass = &self.passes[dispatch.pass];
+            let descriptor_set = pass.descriptor_sets[frame_index];
+
+            // Draw data has a buffer for each frame, so the set is brought up to date each time.
+            let buffer_infos = pass
+                .bindings
+                .iter()
+                .map(|binding| {
+                    vk::DescriptorBufferInfo::builder()
+                        .buffer(buffers.get(*binding))
+                        .offset(0)
+                        .range(vk::WHOLE_SIZE)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            let writes = buffer_infos
+                .iter()
+                .zip(0..)
+                .map(|(buffer_info, binding)| {
+                    vk::WriteDescriptorSet::builder()
+                        .buffer_info(slice_from_ref(buffer_info))
+                        .dst_set(descriptor_set)
+                        .dst_binding(binding)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            device.update_descriptor_sets(&writes, &[]);
+
+            // Each dispatch sees what the ones before it wrote.
+            if index > 0 {
+                barrier(
+                    device,
+                    command_buffer,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::AccessFlags::SHADER_WRITE,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                );
+            }
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pass.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pass.pipeline_layout,
+                0,
+                slice_from_ref(&descriptor_set),
+                &[],
+            );
+            if !dispatch.push_constants.is_empty() {
+                device.cmd_push_constants(
+                    command_buffer,
+                    pass.pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &dispatch.push_constants,
+                );
+            }
+            let [x, y, z] = dispatch.group_count;
+            device.cmd_dispatch(command_buffer, x, y, z);
+        }
+
+        // Everything drawn this frame sees what was written, however it's read.
+        barrier(
+            device,
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            DRAW_STAGES,
+            vk::AccessFlags::INDIRECT_COMMAND_READ
+                | vk::AccessFlags::INDEX_READ
+                | vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                | vk::AccessFlags::SHADER_READ,
+        );
+
+        self.scheduled.clear();
+    }
+}
+
+/// Every stage that drawing reads buffers in
+const DRAW_STAGES: vk::PipelineStageFlags = vk::PipelineStageFlags::from_raw(
+    vk::PipelineStageFlags::DRAW_INDIRECT.as_raw()
+        | vk::PipelineStageFlags::VERTEX_INPUT.as_raw()
+        | vk::PipelineStageFlags::VERTEX_SHADER.as_raw()
+        | vk::PipelineStageFlags::FRAGMENT_SHADER.as_raw(),
+);
+
+unsafe fn barrier(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    src_stage: vk::PipelineStageFlags,
+    src_access: vk::AccessFlags,
+    dst_stage: vk::PipelineStageFlags,
+    dst_access: vk::AccessFlags,
+) {
+    let memory_barrier = vk::MemoryBarrier::builder()
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access)
+        .build();
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        src_stage,
+        dst_stage,
+        vk::DependencyFlags::empty(),
+        slice_from_ref(&memory_barrier),
+        &[],
+        &[],
+    );
+}
+
+/// Check that `spirv` looks like a SPIR-V module, and that `push_constant_size` bytes of push constants can be used.
+fn check_pass(spirv: &[u32], push_constant_size: u32, max_push_constant_size: u32) -> Result<()> {
+    if spirv.first() != Some(&SPIRV_MAGIC) {
+        return Err(anyhow!("The compute shader isn't SPIR-V"));
+    }
+    if push_constant_size % 4 != 0 || push_constant_size > max_push_constant_size {
+        return Err(anyhow!(
+            "Compute passes can have up to {} bytes of push constants, in multiples of four, but {} were asked for",
+            max_push_constant_size,
+            push_constant_size
+        ));
+    }
+    Ok(())
+}
+
+unsafe fn create_compute_pipeline(
+    device: &ash::Device,
+    layout: vk::PipelineLayout,
+    spirv: &[u32],
+) -> Result<vk::Pipeline> {
+    let shader_entry_name = CStr::from_bytes_with_nul_unchecked(b"main\0");
+    let module =
+        device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(spirv), None)?;
+
+    let create_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(vk::PipelineShaderStageCreateInfo {
+            stage: vk::ShaderStageFlags::COMPUTE,
+            module,
+            p_name: shader_entry_name.as_ptr(),
+            ..Default::default()
+        })
+        .layout(layout);
+
+    let pipelines = device
+        .create_compute_pipelines(
+            vk::PipelineCache::null(),
+            slice_from_ref(&create_info),
+            None,
+        )
+        .map_err(|(_, r)| r);
+    device.destroy_shader_module(module, None);
+
+    Ok(pipelines?[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_check_pass() {
+        let spirv = [SPIRV_MAGIC, 0x0001_0000];
+        assert!(check_pass(&spirv, 0, 128).is_ok());
+        assert!(check_pass(&spirv, 128, 128).is_ok());
+
+        // Push constants must fit, in whole words..
+        assert!(check_pass(&spirv, 132, 128).is_err());
+        assert!(check_pass(&spirv, 6, 128).is_err());
+
+        // ..and the shader has to be SPIR-V.
+        assert!(check_pass(&[], 0, 128).is_err());
+        assert!(check_pass(&[0x1234_5678], 0, 128).is_err());
+    }
+}
@@ -0,0 +1,720 @@
+use std::{ffi::CStr, mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+use glam::{Affine3A, Mat4};
+use hecs::{Entity, World};
+use vk_shader_macros::include_glsl;
+
+use crate::{
+    components::{CrowdMember, GlobalTransform, Skin},
+    contexts::{
+        render_context::{create_pipeline, create_push_constant, FRAG},
+        VulkanContext,
+    },
+    systems::{
+        skinning::update_joint_matrices,
+        update_global_transform::update_global_transform_system_inner,
+        update_global_transform_with_parent::update_global_transform_with_parent_system_inner,
+    },
+};
+
+use super::{
+    buffer::Buffer,
+    descriptors::{Descriptors, DESCRIPTOR_SET_COUNT},
+    primitive::Primitive,
+    resources::MAX_JOINTS,
+};
+
+static CROWD_VERT: &[u32] = include_glsl!("src/shaders/crowd.vert", target: vulkan1_1);
+static CROWD_ANIMATE: &[u32] = include_glsl!("src/shaders/crowd_animate.comp", target: vulkan1_1);
+
+/// The most crowd members that can be drawn in a frame, across all crowds
+pub const MAX_CROWD_INSTANCES: usize = 4096;
+
+/// The most joint matrices the crowd members drawn in a frame can have between them
+pub const MAX_CROWD_JOINT_MATRICES: usize = 65_536;
+
+/// The most baked animation frames' joint matrices that can be stored, across all crowds
+pub const MAX_BAKED_MATRICES: usize = 262_144;
+
+/// The most baked animations that can be stored, across all crowds
+pub const MAX_CROWD_ANIMATIONS: usize = 256;
+
+/// The most indirect draws that can be recorded for crowds in a frame - one for each primitive of each crowd's mesh
+pub const MAX_CROWD_DRAWS: usize = 256;
+
+const INSTANCE_BINDING: u32 = 0;
+const ANIMATION_BINDING: u32 = 1;
+const BAKED_FRAME_BINDING: u32 = 2;
+const JOINT_BINDING: u32 = 3;
+
+/// Identifies a crowd created with [`crate::contexts::RenderContext::add_crowd`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CrowdId(u32);
+
+/// A skinned animation, sampled ahead of time into a joint matrix for every joint at every frame, so that crowds can
+/// play it back on the GPU without walking a skeleton.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedAnimation {
+    /// The number of joints in the skin the animation was baked from
+    pub joint_count: u32,
+    /// How many frames were baked per second of animation
+    pub frame_rate: f32,
+    /// The joint matrices, `joint_count` for each frame, one frame after another
+    pub matrices: Vec<Mat4>,
+}
+
+impl BakedAnimation {
+    /// Bake `frame_count` frames of the skin on `skinned_entity` at `frame_rate` frames per second. For each frame,
+    /// `pose` is called with the time in seconds and should pose the skeleton - eg. by setting
+    /// [`crate::components::AnimationController::blend_amount`] and running `animation_system` - and the joint matrices
+    /// are worked out as `skinning_system` would.
+    pub fn bake(
+        world: &mut World,
+        skinned_entity: Entity,
+        frame_count: usize,
+        frame_rate: f32,
+        mut pose: impl FnMut(&mut World, f32),
+    ) -> Result<Self> {
+        let joint_count = world.get::<&Skin>(skinned_entity)?.joints.len();
+        if joint_count > MAX_JOINTS {
+            return Err(anyhow!(
+                "Crowds can only be animated with up to {} joints, but this skin has {}",
+                MAX_JOINTS,
+                joint_count
+            ));
+        }
+
+        let mut matrices = Vec::with_capacity(frame_count * joint_count);
+        let mut joint_matrices = [Mat4::IDENTITY; MAX_JOINTS];
+        for frame in 0..frame_count {
+            pose(world, frame as f32 / frame_rate);
+            update_global_transform_system_inner(world);
+            update_global_transform_with_parent_system_inner(world);
+
+            let skin = world.get::<&Skin>(skinned_entity)?;
+            let global_transform = world.get::<&GlobalTransform>(skinned_entity)?;
+            update_joint_matrices(world, &skin, &global_transform, &mut joint_matrices);
+            matrices.extend_from_slice(&joint_matrices[..joint_count]);
+        }
+
+        Ok(Self {
+            joint_count: joint_count as _,
+            frame_rate,
+            matrices,
+        })
+    }
+
+    /// The number of frames in the animation
+    pub fn frame_count(&self) -> usize {
+        if self.joint_count == 0 {
+            0
+        } else {
+            self.matrices.len() / self.joint_count as usize
+        }
+    }
+}
+
+/// The frames either side of `time` seconds into a looping animation with `frame_count` frames, baked at `frame_rate`,
+/// and how far to blend from the first to the second. Matches `crowd_animate.comp`.
+pub fn frame_blend(time: f32, frame_count: usize, frame_rate: f32) -> (usize, usize, f32) {
+    let frame = time.max(0.) * frame_rate;
+    let from = frame.floor() as usize % frame_count;
+    let to = (from + 1) % frame_count;
+    (from, to, frame.fract())
+}
+
+/// A crowd member, as it's sent to the crowd shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrowdInstanceData {
+    /// The transform of the member's mesh
+    pub gos_from_local: Mat4,
+    /// The inverse of `gos_from_local`, for transforming normals
+    pub local_from_gos: Mat4,
+    /// How far into its animation the member is, in seconds
+    pub time_offset: f32,
+    /// How fast the member's animation plays
+    pub speed: f32,
+    /// The index of the member's animation in the animation buffer
+    pub animation: u32,
+    /// The index of the member's first joint matrix in the joint buffer, filled in by [`build_crowd_draws`]
+    pub joint_offset: u32,
+}
+
+impl CrowdInstanceData {
+    /// Create the data for `member`, playing the animation at `animation` in the animation buffer, at `gos_from_local`
+    pub fn new(member: &CrowdMember, animation: u32, gos_from_local: &Affine3A) -> Self {
+        Self {
+            gos_from_local: (*gos_from_local).into(),
+            local_from_gos: gos_from_local.inverse().into(),
+            time_offset: member.time_offset,
+            speed: member.speed,
+            animation,
+            joint_offset: 0,
+        }
+    }
+}
+
+/// Where a [`BakedAnimation`] is in the baked frame buffer, as it's sent to the crowd animation shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CrowdAnimationData {
+    /// The index of the animation's first joint matrix in the baked frame buffer
+    pub first_matrix: u32,
+    /// The number of frames in the animation
+    pub frame_count: u32,
+    /// The number of joints in each frame
+    pub joint_count: u32,
+    /// How many frames were baked per second of animation
+    pub frame_rate: f32,
+}
+
+/// Parameters for the crowd animation shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CrowdParams {
+    time: f32,
+    instance_count: u32,
+}
+
+/// One crowd: a skinned mesh, the animations it can play and the members to draw this frame.
+#[derive(Debug, Clone)]
+pub struct Crowd {
+    /// The primitives of the crowd's mesh
+    pub primitives: Vec<Primitive>,
+    /// The index of the crowd's first animation in the animation buffer
+    pub first_animation: u32,
+    /// How many animations the crowd has
+    pub animation_count: u32,
+    /// The number of joints in the crowd's skin
+    pub joint_count: u32,
+    /// The members to draw this frame
+    pub members: Vec<CrowdInstanceData>,
+}
+
+/// Lay out the members of each of `crowds` in `instances`, giving each of them room for its joint matrices, and record
+/// one indirect draw command for each primitive of each crowd in `commands`, with its material in `materials`. Members
+/// past [`MAX_CROWD_INSTANCES`] or [`MAX_CROWD_JOINT_MATRICES`] are left out.
+pub fn build_crowd_draws(
+    crowds: &[Crowd],
+    instances: &mut Vec<CrowdInstanceData>,
+    commands: &mut Vec<vk::DrawIndexedIndirectCommand>,
+    materials: &mut Vec<u32>,
+) {
+    instances.clear();
+    commands.clear();
+    materials.clear();
+
+    let mut joint_offset = 0;
+    for crowd in crowds {
+        let first_instance = instances.len();
+        let joint_count = crowd.joint_count as usize;
+        for member in &crowd.members {
+            if instances.len() >= MAX_CROWD_INSTANCES
+                || joint_offset + joint_count > MAX_CROWD_JOINT_MATRICES
+            {
+                break;
+            }
+            instances.push(CrowdInstanceData {
+                joint_offset: joint_offset as _,
+                ..*member
+            });
+            joint_offset += joint_count;
+        }
+
+        let instance_count = (instances.len() - first_instance) as u32;
+        if instance_count == 0 {
+            continue;
+        }
+        for primitive in crowd
+            .primitives
+            .iter()
+            .take(MAX_CROWD_DRAWS - commands.len())
+        {
+            commands.push(vk::DrawIndexedIndirectCommand {
+                index_count: primitive.indices_count,
+                instance_count,
+                first_index: primitive.index_buffer_offset,
+                vertex_offset: primitive.vertex_buffer_offset as _,
+                first_instance: first_instance as _,
+            });
+            materials.push(primitive.material_id);
+        }
+    }
+}
+
+/// The per-frame buffers used to draw crowds
+pub(crate) struct CrowdFrame {
+    instance_buffer: Buffer<CrowdInstanceData>,
+    joint_buffer: Buffer<Mat4>,
+    indirect_buffer: Buffer<vk::DrawIndexedIndirectCommand>,
+    descriptor_set: vk::DescriptorSet,
+}
+
+/// Draws crowds of skinned meshes - see [`CrowdMember`].
+///
+/// Every member of a crowd shares the crowd's mesh, and plays one of its [`BakedAnimation`]s from its own point in
+/// time. Before the PBR render pass, a compute pass samples each member's animation into its joint matrices; the
+/// members are then drawn with one indirect, instanced draw for each primitive of the crowd's mesh, however many
+/// members there are. Crowd members aren't culled, so keep crowds close to where they're seen.
+pub struct CrowdRenderer {
+    /// The crowds that have been created
+    pub crowds: Vec<Crowd>,
+    /// Draws crowd members, reading the joint matrices written by the animation pass
+    pub pipeline: vk::Pipeline,
+    /// The shared descriptor set, the fog set and the crowd set, with the material ID as a push constant
+    pub pipeline_layout: vk::PipelineLayout,
+    /// Samples each member's animation
+    pub animate_pipeline: vk::Pipeline,
+    /// The crowd set, with the time and member count as push constants
+    pub animate_pipeline_layout: vk::PipelineLayout,
+    /// Layout of the crowd set, used by both pipelines
+    pub set_layout: vk::DescriptorSetLayout,
+    animation_buffer: Buffer<CrowdAnimationData>,
+    baked_frame_buffer: Buffer<Mat4>,
+    // One per frame, plus one for the spectator view, like the descriptor sets.
+    pub(crate) frames: [CrowdFrame; DESCRIPTOR_SET_COUNT],
+    instance_scratch: Vec<CrowdInstanceData>,
+    command_scratch: Vec<vk::DrawIndexedIndirectCommand>,
+    material_scratch: Vec<u32>,
+    /// Can indirect draws start past the first instance? If not, the draws are recorded directly instead.
+    draw_indirect_first_instance: bool,
+}
+
+impl CrowdRenderer {
+    pub(crate) unsafe fn new(
+        vulkan_context: &VulkanContext,
+        descriptors: &Descriptors,
+        render_area: &vk::Rect2D,
+        render_pass: vk::RenderPass,
+        reversed_z: bool,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let set_layout = create_crowd_set_layout(device)?;
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(size_of::<u32>() as _)
+            .build();
+        let set_layouts = [
+            descriptors.graphics_layout,
+            descriptors.fog_layout,
+            set_layout,
+        ];
+        let pipeline_layout = device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(slice_from_ref(&push_constant_range)),
+            None,
+        )?;
+        let pipeline = create_pipeline(
+            vulkan_context,
+            pipeline_layout,
+            render_area,
+            render_pass,
+            reversed_z,
+            descriptors.texture_capacity,
+            CROWD_VERT,
+            FRAG,
+        )?;
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(size_of::<CrowdParams>() as _)
+            .build();
+        let animate_pipeline_layout = device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(slice_from_ref(&set_layout))
+                .push_constant_ranges(slice_from_ref(&push_constant_range)),
+            None,
+        )?;
+        let animate_pipeline = create_animate_pipeline(device, animate_pipeline_layout)?;
+
+        let storage = vk::BufferUsageFlags::STORAGE_BUFFER;
+        let animation_buffer = Buffer::new(vulkan_context, storage, MAX_CROWD_ANIMATIONS);
+        let baked_frame_buffer = Buffer::new(vulkan_context, storage, MAX_BAKED_MATRICES);
+
+        let frames = [(); DESCRIPTOR_SET_COUNT].map(|_| {
+            let descriptor_set = device
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(descriptors.pool)
+                        .set_layouts(slice_from_ref(&set_layout)),
+                )
+                .unwrap()[0];
+            let frame = CrowdFrame {
+                instance_buffer: Buffer::new(vulkan_context, storage, MAX_CROWD_INSTANCES),
+                joint_buffer: Buffer::new(vulkan_context, storage, MAX_CROWD_JOINT_MATRICES),
+                indirect_buffer: Buffer::new(
+                    vulkan_context,
+                    vk::BufferUsageFlags::INDIRECT_BUFFER,
+                    MAX_CROWD_DRAWS,
+                ),
+                descriptor_set,
+            };
+
+            frame
+                .instance_buffer
+                .update_descriptor_set(device, descriptor_set, INSTANCE_BINDING);
+            animation_buffer.update_descriptor_set(device, descriptor_set, ANIMATION_BINDING);
+            baked_frame_buffer.update_descriptor_set(device, descriptor_set, BAKED_FRAME_BINDING);
+            frame
+                .joint_buffer
+                .update_descriptor_set(device, descriptor_set, JOINT_BINDING);
+            frame
+        });
+
+        Ok(Self {
+            crowds: Vec::new(),
+            pipeline,
+            pipeline_layout,
+            animate_pipeline,
+            animate_pipeline_layout,
+            set_layout,
+            animation_buffer,
+            baked_frame_buffer,
+            frames,
+            instance_scratch: Vec::new(),
+            command_scratch: Vec::new(),
+            material_scratch: Vec::new(),
+            draw_indirect_first_instance: vulkan_context.capabilities.draw_indirect_first_instance,
+        })
+    }
+
+    /// Create a crowd of `primitives`, which can play any of `animations`. The animations must all have been baked from
+    /// the same skin.
+    pub fn add_crowd(
+        &mut self,
+        primitives: Vec<Primitive>,
+        animations: &[BakedAnimation],
+    ) -> Result<CrowdId> {
+        let joint_count = animations.first().map_or(0, |a| a.joint_count);
+        if animations.iter().any(|a| a.joint_count != joint_count) {
+            return Err(anyhow!(
+                "A crowd's animations must all have the same number of joints"
+            ));
+        }
+        if animations.iter().any(|a| a.frame_count() == 0) {
+            return Err(anyhow!("A crowd's animations must have at least one frame"));
+        }
+        let matrix_count: usize = animations.iter().map(|a| a.matrices.len()).sum();
+        if self.animation_buffer.len + animations.len() > MAX_CROWD_ANIMATIONS
+            || self.baked_frame_buffer.len + matrix_count > MAX_BAKED_MATRICES
+        {
+            return Err(anyhow!("There's no room left for this crowd's animations"));
+        }
+
+        // Animations are only ever added to the end of the buffers, so frames in flight never see them change.
+        let first_animation = self.animation_buffer.len as u32;
+        for animation in animations {
+            let data = CrowdAnimationData {
+                first_matrix: self.baked_frame_buffer.len as _,
+                frame_count: animation.frame_count() as _,
+                joint_count,
+                frame_rate: animation.frame_rate,
+            };
+            unsafe {
+                self.animation_buffer.push(&data);
+                self.baked_frame_buffer.append(&animation.matrices);
+            }
+        }
+
+        self.crowds.push(Crowd {
+            primitives,
+            first_animation,
+            animation_count: animations.len() as _,
+            joint_count,
+            members: Vec::new(),
+        });
+        Ok(CrowdId(self.crowds.len() as u32 - 1))
+    }
+
+    /// Forget the members gathered for the last frame
+    pub fn clear_members(&mut self) {
+        for crowd in &mut self.crowds {
+            crowd.members.clear();
+        }
+    }
+
+    /// Add `member` to its crowd for this frame, at `gos_from_local`. Members of crowds that don't exist, or playing
+    /// animations their crowd doesn't have, are ignored.
+    pub fn push_member(&mut self, member: &CrowdMember, gos_from_local: &Affine3A) {
+        if let Some(crowd) = self.crowds.get_mut(member.crowd.0 as usize) {
+            if member.animation < crowd.animation_count {
+                let animation = crowd.first_animation + member.animation;
+                crowd
+                    .members
+                    .push(CrowdInstanceData::new(member, animation, gos_from_local));
+            }
+        }
+    }
+
+    /// Upload this frame's members and record the animation pass into `command_buffer`, which must be outside a render
+    /// pass. `time` is the scene's time, in seconds.
+    pub(crate) unsafe fn animate(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        time: f32,
+    ) {
+        build_crowd_draws(
+            &self.crowds,
+            &mut self.instance_scratch,
+            &mut self.command_scratch,
+            &mut self.material_scratch,
+        );
+        let frame = &mut self.frames[frame_index];
+        frame.instance_buffer.overwrite(&self.instance_scratch);
+        frame.indirect_buffer.overwrite(&self.command_scratch);
+        if self.instance_scratch.is_empty() {
+            return;
+        }
+
+        let params = CrowdParams {
+            time,
+            instance_count: self.instance_scratch.len() as _,
+        };
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.animate_pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.animate_pipeline_layout,
+            0,
+            slice_from_ref(&frame.descriptor_set),
+            &[],
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            self.animate_pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            create_push_constant(&params),
+        );
+
+        // One workgroup per member, one invocation per joint.
+        device.cmd_dispatch(command_buffer, params.instance_count, 1, 1);
+
+        let memory_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build();
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::DependencyFlags::empty(),
+            slice_from_ref(&memory_barrier),
+            &[],
+            &[],
+        );
+    }
+
+    /// Record the draws for this frame's members. Must be inside the PBR render pass, with its index and vertex buffers
+    /// bound. Returns whether anything was drawn, as the PBR pipeline will need binding again if it was.
+    pub(crate) unsafe fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        shared_sets: [vk::DescriptorSet; 2],
+    ) -> bool {
+        if self.material_scratch.is_empty() {
+            return false;
+        }
+
+        let frame = &self.frames[frame_index];
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[shared_sets[0], shared_sets[1], frame.descriptor_set],
+            &[],
+        );
+
+        let stride = size_of::<vk::DrawIndexedIndirectCommand>();
+        for (index, material_id) in self.material_scratch.iter().enumerate() {
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                create_push_constant(material_id),
+            );
+            if self.draw_indirect_first_instance {
+                device.cmd_draw_indexed_indirect(
+                    command_buffer,
+                    frame.indirect_buffer.buffer,
+                    (index * stride) as _,
+                    1,
+                    stride as _,
+                );
+            } else {
+                // Every crowd after the first starts past instance zero, which only direct draws can always do.
+                let command = &self.command_scratch[index];
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    command.index_count,
+                    command.instance_count,
+                    command.first_index,
+                    command.vertex_offset,
+                    command.first_instance,
+                );
+            }
+        }
+
+        true
+    }
+}
+
+unsafe fn create_crowd_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
+    let storage_buffer = |binding, stage_flags| vk::DescriptorSetLayoutBinding {
+        binding,
+        descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+        stage_flags,
+        descriptor_count: 1,
+        ..Default::default()
+    };
+    let bindings = [
+        storage_buffer(
+            INSTANCE_BINDING,
+            vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX,
+        ),
+        storage_buffer(ANIMATION_BINDING, vk::ShaderStageFlags::COMPUTE),
+        storage_buffer(BAKED_FRAME_BINDING, vk::ShaderStageFlags::COMPUTE),
+        storage_buffer(
+            JOINT_BINDING,
+            vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX,
+        ),
+    ];
+
+    Ok(device.create_descriptor_set_layout(
+        &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+        None,
+    )?)
+}
+
+unsafe fn create_animate_pipeline(
+    device: &ash::Device,
+    layout: vk::PipelineLayout,
+) -> Result<vk::Pipeline> {
+    let shader_entry_name = CStr::from_bytes_with_nul_unchecked(b"main\0");
+    let module = device.create_shader_module(
+        &vk::ShaderModuleCreateInfo::builder().code(CROWD_ANIMATE),
+        None,
+    )?;
+
+    let create_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(vk::PipelineShaderStageCreateInfo {
+            stage: vk::ShaderStageFlags::COMPUTE,
+            module,
+            p_name: shader_entry_name.as_ptr(),
+            ..Default::default()
+        })
+        .layout(layout);
+
+    let pipelines = device
+        .create_compute_pipelines(
+            vk::PipelineCache::null(),
+            slice_from_ref(&create_info),
+            None,
+        )
+        .map_err(|(_, r)| r)?;
+    device.destroy_shader_module(module, None);
+
+    Ok(pipelines[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{Vec3, Vec4};
+
+    #[test]
+    pub fn test_frame_blend() {
+        // Halfway between two frames..
+        let (from, to, blend) = frame_blend(0.25, 10, 10.);
+        assert_eq!((from, to), (2, 3));
+        assert_relative_eq!(blend, 0.5);
+
+        // ..the last frame blends back into the first..
+        let (from, to, _) = frame_blend(0.95, 10, 10.);
+        assert_eq!((from, to), (9, 0));
+
+        // ..and the animation loops.
+        let (from, to, blend) = frame_blend(1.25, 10, 10.);
+        assert_eq!((from, to), (2, 3));
+        assert_relative_eq!(blend, 0.5);
+    }
+
+    #[test]
+    pub fn test_build_crowd_draws() {
+        let primitive = |index_buffer_offset, material_id| Primitive {
+            index_buffer_offset,
+            vertex_buffer_offset: index_buffer_offset * 2,
+            indices_count: 30,
+            material_id,
+            bounding_sphere: Vec4::ZERO,
+        };
+        let member = CrowdInstanceData::new(
+            &CrowdMember::new(CrowdId(0), 0, 0.5),
+            0,
+            &Affine3A::from_translation(Vec3::X),
+        );
+        let crowd = |primitives, joint_count, member_count| Crowd {
+            primitives,
+            first_animation: 0,
+            animation_count: 1,
+            joint_count,
+            members: vec![member; member_count],
+        };
+        let crowds = [
+            crowd(vec![primitive(0, 1), primitive(100, 2)], 20, 3),
+            crowd(vec![primitive(200, 3)], 10, 0),
+            crowd(vec![primitive(300, 4)], 10, 2),
+        ];
+
+        let (mut instances, mut commands, mut materials) = (Vec::new(), Vec::new(), Vec::new());
+        build_crowd_draws(&crowds, &mut instances, &mut commands, &mut materials);
+
+        // Each member gets room for its own joints..
+        let joint_offsets = instances.iter().map(|i| i.joint_offset).collect::<Vec<_>>();
+        assert_eq!(joint_offsets, [0, 20, 40, 60, 70]);
+
+        // ..and there's one draw for each primitive of each crowd with members.
+        let draws = commands
+            .iter()
+            .map(|c| {
+                (
+                    c.first_index,
+                    c.vertex_offset,
+                    c.instance_count,
+                    c.first_instance,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(draws, [(0, 0, 3, 0), (100, 200, 3, 0), (300, 600, 2, 3)]);
+        assert_eq!(materials, [1, 2, 4]);
+
+        // Members that don't fit are left out.
+        let crowds = [crowd(vec![primitive(0, 1)], 64, MAX_CROWD_INSTANCES + 1)];
+        build_crowd_draws(&crowds, &mut instances, &mut commands, &mut materials);
+        assert_eq!(instances.len(), MAX_CROWD_JOINT_MATRICES / 64);
+        assert_eq!(commands[0].instance_count as usize, instances.len());
+    }
+}
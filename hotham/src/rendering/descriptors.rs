@@ -1,54 +1,164 @@
 use std::convert::TryInto;
 
-use crate::contexts::{render_context::PIPELINE_DEPTH, VulkanContext};
+use crate::contexts::{
+    render_context::PIPELINE_DEPTH, vulkan_context::DescriptorIndexingSupport, VulkanContext,
+};
 use ash::vk;
 
 pub const DRAW_DATA_BINDING: u32 = 0;
 pub const MATERIALS_BINDING: u32 = 1;
 pub const SKINS_BINDING: u32 = 2;
 pub const SCENE_DATA_BINDING: u32 = 3;
-pub const TEXTURE_BINDING: u32 = 4;
-pub const CUBE_TEXTURE_BINDING: u32 = 5;
+// The texture array must be the last binding, so it can have a variable descriptor count.
+pub const CUBE_TEXTURE_BINDING: u32 = 4;
+pub const TEXTURE_BINDING: u32 = 5;
 
 pub const PRIMITIVE_CULL_DATA_BINDING: u32 = 0;
 pub const CULL_PARAMS_BINDING: u32 = 1;
 
-const TEXTURE_BINDING_DESCRIPTOR_COUNT: u32 = 10_000;
+// Volumetric fog compute passes
+pub const FOG_PARAMS_BINDING: u32 = 0;
+pub const FOG_SCATTERING_BINDING: u32 = 1;
+pub const FOG_INTEGRATED_BINDING: u32 = 2;
+
+// The fog and shadow maps are sampled from their own descriptor set, set 1, as the texture array has to stay last in
+// set 0.
+pub const FOG_VOLUME_BINDING: u32 = 0;
+pub const SHADOW_MAP_BINDING: u32 = 1;
+
+pub(crate) const TEXTURE_BINDING_DESCRIPTOR_COUNT: u32 = 10_000;
+
+/// How many textures fit in the texture array on devices without bindless support, where every one of them has to be
+/// written before it's used.
+pub(crate) const FALLBACK_TEXTURE_DESCRIPTOR_COUNT: u32 = 256;
+
+pub(crate) const CUBE_TEXTURE_DESCRIPTOR_COUNT: u32 = 2;
+
+/// One descriptor set per frame, plus one for the spectator view's frame.
+pub(crate) const DESCRIPTOR_SET_COUNT: usize = PIPELINE_DEPTH + 1;
+
+/// The descriptor set used by [`crate::rendering::spectator::SpectatorView`].
+pub(crate) const SPECTATOR_DESCRIPTOR_SET: usize = PIPELINE_DEPTH;
 
 /// A wrapper around all the various bits of descriptor functionality
 #[derive(Clone, Debug)]
 pub struct Descriptors {
     pub graphics_layout: vk::DescriptorSetLayout,
     pub compute_layout: vk::DescriptorSetLayout,
-    // One descriptor set per frame
-    pub sets: [vk::DescriptorSet; PIPELINE_DEPTH],
-    // One descriptor set per frame
-    pub compute_sets: [vk::DescriptorSet; PIPELINE_DEPTH],
+    // One descriptor set per frame, plus one for the spectator view
+    pub sets: [vk::DescriptorSet; DESCRIPTOR_SET_COUNT],
+    // One descriptor set per frame, plus one for the spectator view
+    pub compute_sets: [vk::DescriptorSet; DESCRIPTOR_SET_COUNT],
+    pub fog_compute_layout: vk::DescriptorSetLayout,
+    // One descriptor set per frame, plus one for the spectator view
+    pub fog_compute_sets: [vk::DescriptorSet; DESCRIPTOR_SET_COUNT],
+    /// Layout of the second set used by the PBR pipeline, holding the fog volume and the shadow atlas
+    pub fog_layout: vk::DescriptorSetLayout,
+    /// The fog volume and shadow atlas are only ever written by the GPU, so every frame shares this set.
+    pub fog_set: vk::DescriptorSet,
     #[allow(unused)]
     pub pool: vk::DescriptorPool,
+    /// Is the texture array bindless? If not, it's smaller, and every texture in it must always be written.
+    pub bindless: bool,
+    /// How many textures the texture array can hold
+    pub texture_capacity: u32,
 }
 
 impl Descriptors {
     pub unsafe fn new(vulkan_context: &VulkanContext) -> Self {
+        let support = &vulkan_context.capabilities.descriptor_indexing;
+        let bindless = support.is_bindless();
+        let texture_capacity = texture_capacity(support);
+        if !bindless {
+            println!(
+                "[HOTHAM_DESCRIPTORS] Bindless textures aren't supported, falling back to {} textures",
+                texture_capacity
+            );
+        }
+
         // First, create a pool.
-        let pool = create_descriptor_pool(&vulkan_context.device);
+        let pool = create_descriptor_pool(&vulkan_context.device, bindless);
 
         // Then create a layout.
-        let (graphics_layout, compute_layout) = create_descriptor_layouts(&vulkan_context.device);
+        let (graphics_layout, compute_layout) =
+            create_descriptor_layouts(&vulkan_context.device, bindless, texture_capacity);
 
         // Finally, allocate the shared descriptor set.
-        let sets = allocate_descriptor_sets(vulkan_context, pool, graphics_layout);
+        let sets = allocate_descriptor_sets(
+            vulkan_context,
+            pool,
+            graphics_layout,
+            if bindless {
+                Some(texture_capacity)
+            } else {
+                None
+            },
+        );
         let compute_sets = allocate_compute_descriptor_sets(vulkan_context, pool, compute_layout);
 
+        // Volumetric fog gets its own layouts, so the fog set can be bound alongside the shared set.
+        let (fog_compute_layout, fog_layout) =
+            create_fog_descriptor_layouts(&vulkan_context.device);
+        let fog_compute_sets =
+            allocate_compute_descriptor_sets(vulkan_context, pool, fog_compute_layout);
+        let fog_set = vulkan_context
+            .device
+            .allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(pool)
+                    .set_layouts(std::slice::from_ref(&fog_layout)),
+            )
+            .unwrap()[0];
+
         Self {
             graphics_layout,
             sets,
             pool,
             compute_layout,
             compute_sets,
+            fog_compute_layout,
+            fog_compute_sets,
+            fog_layout,
+            fog_set,
+            bindless,
+            texture_capacity,
         }
     }
 
+    /// Write the same texture to every slot in the texture array.
+    ///
+    /// Without bindless support every texture in the array must be valid, even those that are never used, so the
+    /// array is filled with `image_view` before anything else is written to it.
+    pub unsafe fn fill_texture_array(
+        &self,
+        vulkan_context: &VulkanContext,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) {
+        let image_infos = vec![
+            vk::DescriptorImageInfo {
+                sampler,
+                image_view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            };
+            self.texture_capacity as usize
+        ];
+
+        let texture_writes = self.sets.map(|set| {
+            vk::WriteDescriptorSet::builder()
+                .image_info(&image_infos)
+                .dst_binding(TEXTURE_BINDING)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .dst_array_element(0)
+                .dst_set(set)
+                .build()
+        });
+
+        vulkan_context
+            .device
+            .update_descriptor_sets(&texture_writes, &[]);
+    }
+
     pub unsafe fn write_texture_descriptor(
         &self,
         vulkan_context: &VulkanContext,
@@ -56,6 +166,12 @@ impl Descriptors {
         sampler: vk::Sampler,
         array_index: u32,
     ) {
+        // Without update-after-bind, descriptor sets can't be written while a frame that uses them is in flight.
+        // Textures aren't loaded often, so just wait for the GPU.
+        if !self.bindless {
+            vulkan_context.device.device_wait_idle().unwrap();
+        }
+
         let image_info = vk::DescriptorImageInfo {
             sampler,
             image_view,
@@ -77,6 +193,80 @@ impl Descriptors {
             .update_descriptor_sets(&texture_writes, &[]);
     }
 
+    /// Point the fog passes at the images they render to, and the PBR pipeline at the result.
+    pub unsafe fn write_fog_descriptors(
+        &self,
+        vulkan_context: &VulkanContext,
+        scattering: vk::ImageView,
+        integrated: vk::ImageView,
+        sampler: vk::Sampler,
+    ) {
+        // The fog images are always in the `GENERAL` layout, as they're both written and read by shaders every frame.
+        let storage_image_info = |image_view| vk::DescriptorImageInfo {
+            sampler: vk::Sampler::null(),
+            image_view,
+            image_layout: vk::ImageLayout::GENERAL,
+        };
+        let scattering_info = storage_image_info(scattering);
+        let integrated_info = storage_image_info(integrated);
+        let sampled_info = vk::DescriptorImageInfo {
+            sampler,
+            image_view: integrated,
+            image_layout: vk::ImageLayout::GENERAL,
+        };
+
+        let mut writes = Vec::new();
+        for set in self.fog_compute_sets {
+            for (binding, image_info) in [
+                (FOG_SCATTERING_BINDING, &scattering_info),
+                (FOG_INTEGRATED_BINDING, &integrated_info),
+            ] {
+                writes.push(
+                    vk::WriteDescriptorSet::builder()
+                        .image_info(std::slice::from_ref(image_info))
+                        .dst_binding(binding)
+                        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                        .dst_set(set)
+                        .build(),
+                );
+            }
+        }
+        writes.push(
+            vk::WriteDescriptorSet::builder()
+                .image_info(std::slice::from_ref(&sampled_info))
+                .dst_binding(FOG_VOLUME_BINDING)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .dst_set(self.fog_set)
+                .build(),
+        );
+
+        vulkan_context.device.update_descriptor_sets(&writes, &[]);
+    }
+
+    /// Point the PBR pipeline at the shadow atlas, which is in the `DEPTH_STENCIL_READ_ONLY_OPTIMAL` layout whenever
+    /// it's sampled.
+    pub unsafe fn write_shadow_map_descriptor(
+        &self,
+        vulkan_context: &VulkanContext,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler,
+            image_view,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        };
+        let write = vk::WriteDescriptorSet::builder()
+            .image_info(std::slice::from_ref(&image_info))
+            .dst_binding(SHADOW_MAP_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .dst_set(self.fog_set)
+            .build();
+        vulkan_context
+            .device
+            .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+    }
+
     pub unsafe fn write_cube_texture_descriptor(
         &self,
         vulkan_context: &VulkanContext,
@@ -106,20 +296,44 @@ impl Descriptors {
     }
 }
 
+/// How many textures the texture array can hold on this device.
+pub(crate) fn texture_capacity(support: &DescriptorIndexingSupport) -> u32 {
+    let wanted = if support.is_bindless() {
+        TEXTURE_BINDING_DESCRIPTOR_COUNT
+    } else {
+        FALLBACK_TEXTURE_DESCRIPTOR_COUNT
+    };
+
+    // The cube textures count towards the same limit.
+    wanted.min(
+        support
+            .max_textures
+            .saturating_sub(CUBE_TEXTURE_DESCRIPTOR_COUNT),
+    )
+}
+
 unsafe fn allocate_descriptor_sets(
     vulkan_context: &VulkanContext,
     pool: vk::DescriptorPool,
     layout: vk::DescriptorSetLayout,
-) -> [vk::DescriptorSet; PIPELINE_DEPTH] {
-    let layouts = [layout; PIPELINE_DEPTH];
+    variable_texture_count: Option<u32>,
+) -> [vk::DescriptorSet; DESCRIPTOR_SET_COUNT] {
+    let layouts = [layout; DESCRIPTOR_SET_COUNT];
+
+    // With a variable descriptor count, the layout only gives the most textures there could be, so say how many.
+    let texture_counts = [variable_texture_count.unwrap_or_default(); DESCRIPTOR_SET_COUNT];
+    let mut variable_count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+        .descriptor_counts(&texture_counts);
+    let mut allocate_info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(pool)
+        .set_layouts(&layouts);
+    if variable_texture_count.is_some() {
+        allocate_info = allocate_info.push_next(&mut variable_count_info);
+    }
 
     vulkan_context
         .device
-        .allocate_descriptor_sets(
-            &vk::DescriptorSetAllocateInfo::builder()
-                .descriptor_pool(pool)
-                .set_layouts(&layouts),
-        )
+        .allocate_descriptor_sets(&allocate_info)
         .unwrap()
         .as_slice()
         .try_into()
@@ -130,8 +344,8 @@ unsafe fn allocate_compute_descriptor_sets(
     vulkan_context: &VulkanContext,
     pool: vk::DescriptorPool,
     layout: vk::DescriptorSetLayout,
-) -> [vk::DescriptorSet; PIPELINE_DEPTH] {
-    let layouts = [layout; PIPELINE_DEPTH];
+) -> [vk::DescriptorSet; DESCRIPTOR_SET_COUNT] {
+    let layouts = [layout; DESCRIPTOR_SET_COUNT];
 
     vulkan_context
         .device
@@ -148,6 +362,8 @@ unsafe fn allocate_compute_descriptor_sets(
 
 unsafe fn create_descriptor_layouts(
     device: &ash::Device,
+    bindless: bool,
+    texture_capacity: u32,
 ) -> (vk::DescriptorSetLayout, vk::DescriptorSetLayout) {
     let graphics_bindings = [
         // Draw Data
@@ -162,7 +378,7 @@ unsafe fn create_descriptor_layouts(
         vk::DescriptorSetLayoutBinding {
             binding: MATERIALS_BINDING,
             descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
             descriptor_count: 1,
             ..Default::default()
         },
@@ -182,20 +398,20 @@ unsafe fn create_descriptor_layouts(
             descriptor_count: 1,
             ..Default::default()
         },
-        // Textures
+        // Cube Textures
         vk::DescriptorSetLayoutBinding {
-            binding: TEXTURE_BINDING,
+            binding: CUBE_TEXTURE_BINDING,
             descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             stage_flags: vk::ShaderStageFlags::FRAGMENT,
-            descriptor_count: TEXTURE_BINDING_DESCRIPTOR_COUNT,
+            descriptor_count: CUBE_TEXTURE_DESCRIPTOR_COUNT,
             ..Default::default()
         },
-        // Cube Textures
+        // Textures
         vk::DescriptorSetLayoutBinding {
-            binding: CUBE_TEXTURE_BINDING,
+            binding: TEXTURE_BINDING,
             descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             stage_flags: vk::ShaderStageFlags::FRAGMENT,
-            descriptor_count: 2,
+            descriptor_count: texture_capacity,
             ..Default::default()
         },
     ];
@@ -219,16 +435,28 @@ unsafe fn create_descriptor_layouts(
         },
     ];
 
-    let flags =
-        vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND;
+    // Without bindless support, the texture array is a plain old array of descriptors.
+    let (texture_flags, layout_flags) = if bindless {
+        (
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+                | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
+            vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL,
+        )
+    } else {
+        (
+            vk::DescriptorBindingFlags::empty(),
+            vk::DescriptorSetLayoutCreateFlags::empty(),
+        )
+    };
 
     let descriptor_flags = [
         vk::DescriptorBindingFlags::empty(),
         vk::DescriptorBindingFlags::empty(),
         vk::DescriptorBindingFlags::empty(),
         vk::DescriptorBindingFlags::empty(),
-        flags,
         vk::DescriptorBindingFlags::empty(),
+        texture_flags,
     ];
     let mut binding_flags = vk::DescriptorSetLayoutBindingFlagsCreateInfoEXT::builder()
         .binding_flags(&descriptor_flags);
@@ -238,7 +466,7 @@ unsafe fn create_descriptor_layouts(
             &vk::DescriptorSetLayoutCreateInfo::builder()
                 .bindings(&graphics_bindings)
                 .push_next(&mut binding_flags)
-                .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL),
+                .flags(layout_flags),
             None,
         )
         .unwrap();
@@ -253,7 +481,66 @@ unsafe fn create_descriptor_layouts(
     (graphics_layout, compute_layout)
 }
 
-unsafe fn create_descriptor_pool(device: &ash::Device) -> vk::DescriptorPool {
+unsafe fn create_fog_descriptor_layouts(
+    device: &ash::Device,
+) -> (vk::DescriptorSetLayout, vk::DescriptorSetLayout) {
+    let storage_image = |binding| vk::DescriptorSetLayoutBinding {
+        binding,
+        descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+        stage_flags: vk::ShaderStageFlags::COMPUTE,
+        descriptor_count: 1,
+        ..Default::default()
+    };
+    let compute_bindings = [
+        // Fog Params
+        vk::DescriptorSetLayoutBinding {
+            binding: FOG_PARAMS_BINDING,
+            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            descriptor_count: 1,
+            ..Default::default()
+        },
+        // Scattering
+        storage_image(FOG_SCATTERING_BINDING),
+        // Integrated
+        storage_image(FOG_INTEGRATED_BINDING),
+    ];
+
+    let graphics_bindings = [
+        // Fog Volume
+        vk::DescriptorSetLayoutBinding {
+            binding: FOG_VOLUME_BINDING,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            descriptor_count: 1,
+            ..Default::default()
+        },
+        // Shadow Map
+        vk::DescriptorSetLayoutBinding {
+            binding: SHADOW_MAP_BINDING,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            descriptor_count: 1,
+            ..Default::default()
+        },
+    ];
+
+    let create_layout = |bindings: &[vk::DescriptorSetLayoutBinding]| {
+        device
+            .create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings),
+                None,
+            )
+            .unwrap()
+    };
+
+    (
+        create_layout(&compute_bindings),
+        create_layout(&graphics_bindings),
+    )
+}
+
+unsafe fn create_descriptor_pool(device: &ash::Device, bindless: bool) -> vk::DescriptorPool {
     let pool_sizes = [
         vk::DescriptorPoolSize {
             ty: vk::DescriptorType::STORAGE_BUFFER,
@@ -267,14 +554,72 @@ unsafe fn create_descriptor_pool(device: &ash::Device) -> vk::DescriptorPool {
             ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             descriptor_count: 60_000,
         },
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 20,
+        },
+        // For apps' own pipelines, reading from `RenderContext::app_data`.
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            descriptor_count: 16,
+        },
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            descriptor_count: 16,
+        },
     ];
     device
         .create_descriptor_pool(
             &vk::DescriptorPoolCreateInfo::builder()
                 .pool_sizes(&pool_sizes)
                 .max_sets(1000)
-                .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND),
+                .flags(if bindless {
+                    vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND
+                } else {
+                    vk::DescriptorPoolCreateFlags::empty()
+                }),
             None,
         )
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_texture_capacity() {
+        let bindless = DescriptorIndexingSupport {
+            partially_bound: true,
+            variable_descriptor_count: true,
+            update_after_bind: true,
+            runtime_descriptor_array: true,
+            non_uniform_indexing: false,
+            max_textures: 500_000,
+        };
+        assert!(bindless.is_bindless());
+        assert_eq!(
+            texture_capacity(&bindless),
+            TEXTURE_BINDING_DESCRIPTOR_COUNT
+        );
+
+        // The device's limits win, leaving room for the cube textures..
+        let limited = DescriptorIndexingSupport {
+            max_textures: 4096,
+            ..bindless
+        };
+        assert_eq!(texture_capacity(&limited), 4094);
+
+        // ..and without bindless support, we fall back to a small fixed size array.
+        let fallback = DescriptorIndexingSupport {
+            variable_descriptor_count: false,
+            max_textures: 1024,
+            ..bindless
+        };
+        assert!(!fallback.is_bindless());
+        assert_eq!(
+            texture_capacity(&fallback),
+            FALLBACK_TEXTURE_DESCRIPTOR_COUNT
+        );
+    }
+}
@@ -0,0 +1,547 @@
+use std::{ffi::CStr, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, Mat4, UVec4, Vec3, Vec4};
+use vk_shader_macros::include_glsl;
+
+use crate::{components::FogVolume, contexts::VulkanContext};
+
+use super::{
+    descriptors::Descriptors,
+    image::Image,
+    light::{Light, MAX_LIGHTS},
+    memory::{allocate_memory, track_free},
+    scene_data::SceneData,
+    texture::DEFAULT_COMPONENT_MAPPING,
+};
+
+static INJECT: &[u32] = include_glsl!("src/shaders/fog_inject.comp", target: vulkan1_1);
+static INTEGRATE: &[u32] = include_glsl!("src/shaders/fog_integrate.comp", target: vulkan1_1);
+
+/// The distance from the viewer at which the first froxel slice starts. Must match `FOG_NEAR_DISTANCE` in `fog.glsl`.
+pub const FOG_NEAR_DISTANCE: f32 = 0.1;
+
+/// The most fog volumes that can be drawn at once. Must match `MAX_FOG_VOLUMES` in `fog_params.glsl`.
+pub const MAX_FOG_VOLUMES: usize = 16;
+
+const FOG_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// How finely volumetric fog is computed. Fog is computed in a froxel grid - cells that line up with the view
+/// frustum - so this sets how blurry the edges of fog volumes and light shafts are, and how much GPU time they take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FogQuality {
+    /// A coarse grid, cheap enough for standalone headsets like Quest
+    Low,
+    /// A good default for PC VR
+    Medium,
+    /// Sharper light shafts, for fast desktop GPUs
+    High,
+}
+
+impl FogQuality {
+    /// The size of the froxel grid for each eye, as `[width, height, depth slices]`
+    pub fn froxel_grid(self) -> [u32; 3] {
+        match self {
+            FogQuality::Low => [16, 16, 32],
+            FogQuality::Medium => [32, 32, 64],
+            FogQuality::High => [64, 64, 128],
+        }
+    }
+}
+
+/// Settings for volumetric fog. These can be changed at any time through [`crate::contexts::RenderContext::volumetric_fog`].
+///
+/// Fog is only drawn inside [`FogVolume`]s, and is lit by the scene's dynamic lights. There are no shadow maps, so
+/// light shafts ("god rays") come from the shape of spotlight cones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumetricFog {
+    /// How far from the viewer fog is drawn, in metres. The froxel slices are spread over this distance, so smaller
+    /// values give more detail.
+    pub max_distance: f32,
+    /// How much light is scattered forward rather than back, from -1 to 1. Positive values make light shafts stand
+    /// out when looking towards a light.
+    pub anisotropy: f32,
+    /// Light scattered by fog everywhere, so unlit fog isn't completely black
+    pub ambient: Vec3,
+}
+
+impl Default for VolumetricFog {
+    fn default() -> Self {
+        Self {
+            max_distance: 30.,
+            anisotropy: 0.6,
+            ambient: Vec3::splat(0.02),
+        }
+    }
+}
+
+/// A fog volume, as it's sent to the fog injection shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FogVolumeData {
+    /// Transforms a point in globally oriented stage space to the volume's space, without scale
+    pub fog_from_gos: Mat4,
+    /// The volume's albedo in `xyz` and density in `w`
+    pub albedo_density: Vec4,
+    /// The volume's half extents, with scale applied, in `xyz` and edge fade in `w`
+    pub half_extents_fade: Vec4,
+}
+
+impl FogVolumeData {
+    /// Create the data for `volume`, which is at `gos_from_local`.
+    pub fn new(volume: &FogVolume, gos_from_local: &Affine3A) -> Self {
+        // Scale is applied to the box rather than the transform, so the signed distance stays in metres.
+        let (scale, rotation, translation) = gos_from_local.to_scale_rotation_translation();
+        let fog_from_gos = Mat4::from_rotation_translation(rotation, translation).inverse();
+        let half_extents = volume.half_extents * scale.abs();
+
+        Self {
+            fog_from_gos,
+            albedo_density: volume.albedo.extend(volume.density),
+            half_extents_fade: half_extents.extend(volume.edge_fade),
+        }
+    }
+}
+
+/// Parameters for the fog compute shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FogParams {
+    /// Transforms from clip space to globally oriented stage space (one per eye)
+    pub gos_from_clip: [Mat4; 2],
+    /// Position of the cameras (one per eye)
+    pub camera_position: [Vec4; 2],
+    /// The froxel grid for one eye in `xyz`, and the number of fog volumes in `w`
+    pub grid: UVec4,
+    /// x = max distance, y = anisotropy, zw = unused
+    pub params: Vec4,
+    /// Ambient light scattered by the fog
+    pub ambient: Vec4,
+    /// The scene's dynamic lights, in globally oriented stage space
+    pub lights: [Light; MAX_LIGHTS],
+    /// The fog volumes to draw. Only the first `grid.w` are used.
+    pub volumes: [FogVolumeData; MAX_FOG_VOLUMES],
+}
+
+impl FogParams {
+    /// Create the parameters for drawing `volumes` into a froxel grid of size `grid`. `scene_data` must already be in
+    /// globally oriented stage space.
+    pub fn new(
+        settings: &VolumetricFog,
+        grid: [u32; 3],
+        scene_data: &SceneData,
+        volumes: &[FogVolumeData],
+    ) -> Self {
+        let volume_count = volumes.len().min(MAX_FOG_VOLUMES);
+        let mut volume_data = [FogVolumeData::default(); MAX_FOG_VOLUMES];
+        volume_data[..volume_count].copy_from_slice(&volumes[..volume_count]);
+
+        Self {
+            gos_from_clip: scene_data.view_projection.map(|m| m.inverse()),
+            camera_position: scene_data.camera_position,
+            grid: UVec4::new(grid[0], grid[1], grid[2], volume_count as _),
+            params: Vec4::new(settings.max_distance, settings.anisotropy, 0., 0.),
+            ambient: settings.ambient.extend(0.),
+            lights: scene_data.lights,
+            volumes: volume_data,
+        }
+    }
+}
+
+/// The distance from the viewer to the start of `slice` of `slices`, spread exponentially between
+/// [`FOG_NEAR_DISTANCE`] and `max_distance`. Matches `fogSliceToDepth` in `fog.glsl`.
+pub fn slice_to_depth(slice: f32, slices: u32, max_distance: f32) -> f32 {
+    FOG_NEAR_DISTANCE * (max_distance / FOG_NEAR_DISTANCE).powf(slice / slices as f32)
+}
+
+/// The (fractional) slice at `depth` from the viewer. Matches `fogDepthToSlice` in `fog.glsl`.
+pub fn depth_to_slice(depth: f32, slices: u32, max_distance: f32) -> f32 {
+    slices as f32 * (depth.max(FOG_NEAR_DISTANCE) / FOG_NEAR_DISTANCE).ln()
+        / (max_distance / FOG_NEAR_DISTANCE).ln()
+}
+
+/// The GPU resources used to draw volumetric fog.
+///
+/// Fog is drawn in two compute passes, recorded before the PBR render pass: the first works out how much light each
+/// froxel scatters and absorbs, and the second adds that up from the viewer outwards. The PBR fragment shader then
+/// looks up the froxel each fragment is in. Both eyes share one 3D image, side by side.
+pub struct Fog {
+    /// How finely fog is computed, or `None` if it's turned off
+    pub quality: Option<FogQuality>,
+    /// The size of the froxel grid for each eye
+    pub grid: [u32; 3],
+    /// Light scattered in each froxel in `rgb`, and its density in `a`
+    pub scattering: Image,
+    /// Light scattered towards the viewer in `rgb`, and how much of the scene is still visible in `a`, from the
+    /// viewer to the far side of each froxel
+    pub integrated: Image,
+    /// Used to sample the integrated image from the PBR fragment shader
+    pub sampler: vk::Sampler,
+    /// Layout shared by both fog passes
+    pub pipeline_layout: vk::PipelineLayout,
+    /// Lights each froxel
+    pub inject_pipeline: vk::Pipeline,
+    /// Adds up the froxels from the viewer outwards
+    pub integrate_pipeline: vk::Pipeline,
+}
+
+impl Fog {
+    /// Create the fog resources. The PBR pipeline always samples the fog, so even when it's turned off there is a tiny
+    /// grid for it to sample.
+    pub(crate) unsafe fn new(
+        vulkan_context: &VulkanContext,
+        descriptors: &Descriptors,
+        quality: Option<FogQuality>,
+    ) -> Result<Self> {
+        let grid = froxel_grid(quality);
+        let device = &vulkan_context.device;
+        let pipeline_layout = device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(slice_from_ref(&descriptors.fog_compute_layout)),
+            None,
+        )?;
+        let inject_pipeline = create_fog_pipeline(device, pipeline_layout, INJECT)?;
+        let integrate_pipeline = create_fog_pipeline(device, pipeline_layout, INTEGRATE)?;
+
+        let sampler = device.create_sampler(
+            &vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::LINEAR)
+                .min_filter(vk::Filter::LINEAR)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .max_lod(0.),
+            None,
+        )?;
+
+        let scattering = create_fog_image(vulkan_context, grid)?;
+        let integrated = create_fog_image(vulkan_context, grid)?;
+        descriptors.write_fog_descriptors(
+            vulkan_context,
+            scattering.view,
+            integrated.view,
+            sampler,
+        );
+
+        Ok(Self {
+            quality,
+            grid,
+            scattering,
+            integrated,
+            sampler,
+            pipeline_layout,
+            inject_pipeline,
+            integrate_pipeline,
+        })
+    }
+
+    /// Recreate the fog images for a new quality. Waits for the GPU to be idle.
+    pub(crate) unsafe fn set_quality(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        descriptors: &Descriptors,
+        quality: Option<FogQuality>,
+    ) -> Result<()> {
+        let grid = froxel_grid(quality);
+        self.quality = quality;
+        if grid == self.grid {
+            return Ok(());
+        }
+
+        let device = &vulkan_context.device;
+        device.device_wait_idle()?;
+        let scattering = create_fog_image(vulkan_context, grid)?;
+        let integrated = create_fog_image(vulkan_context, grid)?;
+        descriptors.write_fog_descriptors(
+            vulkan_context,
+            scattering.view,
+            integrated.view,
+            self.sampler,
+        );
+
+        destroy_fog_image(device, &std::mem::replace(&mut self.scattering, scattering));
+        destroy_fog_image(device, &std::mem::replace(&mut self.integrated, integrated));
+        self.grid = grid;
+
+        Ok(())
+    }
+
+    /// Record the fog passes into `command_buffer`, which must be outside a render pass.
+    pub(crate) unsafe fn record(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        let [width, height, depth] = self.grid;
+        let group_count = |size: u32, local_size: u32| size.div_ceil(local_size);
+        let barrier = |src_stage, src_access, dst_stage, dst_access| {
+            let memory_barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .build();
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                slice_from_ref(&memory_barrier),
+                &[],
+                &[],
+            );
+        };
+
+        // Don't overwrite the fog while the last frame is still reading it.
+        barrier(
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+        );
+
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.pipeline_layout,
+            0,
+            slice_from_ref(&descriptor_set),
+            &[],
+        );
+
+        // Light each froxel..
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.inject_pipeline,
+        );
+        device.cmd_dispatch(
+            command_buffer,
+            group_count(width * 2, 4),
+            group_count(height, 4),
+            group_count(depth, 4),
+        );
+        barrier(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        // ..then add them up, front to back.
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.integrate_pipeline,
+        );
+        device.cmd_dispatch(
+            command_buffer,
+            group_count(width * 2, 8),
+            group_count(height, 8),
+            1,
+        );
+        barrier(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+    }
+}
+
+fn froxel_grid(quality: Option<FogQuality>) -> [u32; 3] {
+    quality.map_or([1, 1, 1], FogQuality::froxel_grid)
+}
+
+unsafe fn create_fog_pipeline(
+    device: &ash::Device,
+    layout: vk::PipelineLayout,
+    code: &[u32],
+) -> Result<vk::Pipeline> {
+    let shader_entry_name = CStr::from_bytes_with_nul_unchecked(b"main\0");
+    let module =
+        device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(code), None)?;
+
+    let create_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(vk::PipelineShaderStageCreateInfo {
+            stage: vk::ShaderStageFlags::COMPUTE,
+            module,
+            p_name: shader_entry_name.as_ptr(),
+            ..Default::default()
+        })
+        .layout(layout);
+
+    let pipelines = device
+        .create_compute_pipelines(
+            vk::PipelineCache::null(),
+            slice_from_ref(&create_info),
+            None,
+        )
+        .map_err(|(_, r)| r)?;
+    device.destroy_shader_module(module, None);
+
+    Ok(pipelines[0])
+}
+
+/// Create a 3D image for a froxel grid, with both eyes side by side, and clear it to "no fog".
+unsafe fn create_fog_image(vulkan_context: &VulkanContext, grid: [u32; 3]) -> Result<Image> {
+    let device = &vulkan_context.device;
+    let usage = vk::ImageUsageFlags::STORAGE
+        | vk::ImageUsageFlags::SAMPLED
+        | vk::ImageUsageFlags::TRANSFER_DST;
+    let extent = vk::Extent2D {
+        width: grid[0] * 2,
+        height: grid[1],
+    };
+
+    let handle = device.create_image(
+        &vk::ImageCreateInfo::builder()
+            .format(FOG_FORMAT)
+            .image_type(vk::ImageType::TYPE_3D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: grid[2],
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE),
+        None,
+    )?;
+    let device_memory = allocate_memory(
+        vulkan_context,
+        device.get_image_memory_requirements(handle),
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    );
+    device.bind_image_memory(handle, device_memory, 0)?;
+    let view = vulkan_context.create_image_view(
+        &handle,
+        FOG_FORMAT,
+        vk::ImageViewType::TYPE_3D,
+        1,
+        1,
+        DEFAULT_COMPONENT_MAPPING,
+    )?;
+
+    // The fog images stay in the `GENERAL` layout, as they're written and read by shaders every frame.
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let command_buffer = vulkan_context.begin_single_time_commands();
+    let barrier = vk::ImageMemoryBarrier::builder()
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::GENERAL)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .subresource_range(subresource_range)
+        .image(handle)
+        .build();
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        slice_from_ref(&barrier),
+    );
+    device.cmd_clear_color_image(
+        command_buffer,
+        handle,
+        vk::ImageLayout::GENERAL,
+        &vk::ClearColorValue {
+            float32: [0., 0., 0., 1.],
+        },
+        slice_from_ref(&subresource_range),
+    );
+    vulkan_context.end_single_time_commands(command_buffer);
+
+    Ok(Image::new(
+        handle,
+        view,
+        device_memory,
+        extent,
+        usage,
+        FOG_FORMAT,
+        vk::ImageViewType::TYPE_3D,
+        1,
+    ))
+}
+
+unsafe fn destroy_fog_image(device: &ash::Device, image: &Image) {
+    let memory_requirements = device.get_image_memory_requirements(image.handle);
+    device.destroy_image_view(image.view, None);
+    device.destroy_image(image.handle, None);
+    device.free_memory(image.device_memory, None);
+    track_free(memory_requirements.size);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::Quat;
+
+    #[test]
+    pub fn test_fog_slices() {
+        // Slices are spread exponentially from the near distance to the max distance..
+        assert_relative_eq!(slice_to_depth(0., 32, 30.), FOG_NEAR_DISTANCE);
+        assert_relative_eq!(slice_to_depth(32., 32, 30.), 30., epsilon = 0.001);
+        assert!(slice_to_depth(1., 32, 30.) - slice_to_depth(0., 32, 30.) < 0.02);
+        assert!(slice_to_depth(32., 32, 30.) - slice_to_depth(31., 32, 30.) > 4.);
+
+        // ..and finding the slice at a depth is the reverse.
+        for slice in [0., 0.5, 7., 31.25, 32.] {
+            let depth = slice_to_depth(slice, 32, 30.);
+            assert_relative_eq!(depth_to_slice(depth, 32, 30.), slice, epsilon = 0.001);
+        }
+
+        // Anything closer than the near distance is in the first slice.
+        assert_relative_eq!(depth_to_slice(0., 32, 30.), 0.);
+    }
+
+    #[test]
+    pub fn test_fog_params() {
+        let volume = FogVolume {
+            density: 0.5,
+            albedo: Vec3::new(1., 0.5, 0.25),
+            half_extents: Vec3::ONE,
+            edge_fade: 0.2,
+        };
+        let gos_from_local = Affine3A::from_scale_rotation_translation(
+            Vec3::new(2., 3., 4.),
+            Quat::from_rotation_y(1.),
+            Vec3::new(1., 2., 3.),
+        );
+        let data = FogVolumeData::new(&volume, &gos_from_local);
+
+        // The volume's centre is at its origin, and scale is applied to the box instead.
+        assert_relative_eq!(
+            data.fog_from_gos.transform_point3(Vec3::new(1., 2., 3.)),
+            Vec3::ZERO,
+            epsilon = 0.0001
+        );
+        assert_relative_eq!(data.half_extents_fade, Vec4::new(2., 3., 4., 0.2));
+        assert_relative_eq!(data.albedo_density, Vec4::new(1., 0.5, 0.25, 0.5));
+
+        // Volumes past the maximum are dropped.
+        let volumes = vec![data; MAX_FOG_VOLUMES + 2];
+        let params = FogParams::new(
+            &Default::default(),
+            FogQuality::Low.froxel_grid(),
+            &Default::default(),
+            &volumes,
+        );
+        assert_eq!(params.grid, UVec4::new(16, 16, 32, MAX_FOG_VOLUMES as _));
+    }
+}
@@ -6,11 +6,14 @@ use anyhow::Result;
 use super::{
     buffer::Buffer,
     descriptors::{
-        Descriptors, CULL_PARAMS_BINDING, DRAW_DATA_BINDING, PRIMITIVE_CULL_DATA_BINDING,
-        SCENE_DATA_BINDING,
+        Descriptors, CULL_PARAMS_BINDING, DRAW_DATA_BINDING, FOG_PARAMS_BINDING,
+        PRIMITIVE_CULL_DATA_BINDING, SCENE_DATA_BINDING,
     },
+    fog::FogParams,
     resources::{DrawData, PrimitiveCullData},
     scene_data::SceneData,
+    sprite::{SpriteData, MAX_SPRITES},
+    timeline::Pass,
 };
 
 // We *can* draw this many objects, but.. seriously?
@@ -19,13 +22,13 @@ static DRAW_DATA_BUFFER_SIZE: usize = 100_000;
 /// A container for all the resources necessary to render a single frame.
 #[derive(Debug, Clone)]
 pub struct Frame {
-    /// The fence used to signal when the frame has completed rendering
-    pub fence: vk::Fence,
+    /// The value on the renderer's [`super::timeline::Timeline`] that's signalled when the frame has completed rendering
+    pub render_value: u64,
+    /// The pass the cull buffers have been released to, if they're waiting to be acquired by its queue family
+    pub cull_buffers_released_to: Option<Pass>,
     /// A command buffer used to record commands
     pub command_buffer: vk::CommandBuffer,
-    /// The fence used to signal when the frame has completed rendering
-    pub compute_fence: vk::Fence,
-    /// A command buffer used to record commands
+    /// A command buffer used to record commands for the compute queue
     pub compute_command_buffer: vk::CommandBuffer,
     /// Data for the primitives that will be drawn this frame, indexed by gl_InstanceId
     pub draw_data_buffer: Buffer<DrawData>,
@@ -35,6 +38,10 @@ pub struct Frame {
     pub scene_data_buffer: Buffer<SceneData>,
     /// Shared data used in a scene
     pub cull_params_buffer: Buffer<CullParams>,
+    /// Parameters for the volumetric fog passes
+    pub fog_params_buffer: Buffer<FogParams>,
+    /// The sprites to draw this frame, read once per instance by the sprite pipeline
+    pub sprite_data_buffer: Buffer<SpriteData>,
 }
 
 impl Frame {
@@ -44,27 +51,20 @@ impl Frame {
         descriptors: &Descriptors,
     ) -> Result<Self> {
         let device = &vulkan_context.device;
-        let command_pool = vulkan_context.command_pool;
-
-        let fence = unsafe {
-            device.create_fence(
-                &vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED),
-                None,
-            )
-        }?;
-        let compute_fence = unsafe { device.create_fence(&vk::FenceCreateInfo::builder(), None) }?;
-
-        let command_buffers = unsafe {
-            device.allocate_command_buffers(
-                &vk::CommandBufferAllocateInfo::builder()
-                    .command_buffer_count(2)
-                    .level(vk::CommandBufferLevel::PRIMARY)
-                    .command_pool(command_pool),
-            )
-        }?;
+        let allocate_command_buffer = |command_pool| unsafe {
+            device
+                .allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::builder()
+                        .command_buffer_count(1)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_pool(command_pool),
+                )
+                .map(|command_buffers| command_buffers[0])
+        };
 
-        let command_buffer = command_buffers[0];
-        let compute_command_buffer = command_buffers[1];
+        let command_buffer = allocate_command_buffer(vulkan_context.command_pool)?;
+        // Culling is submitted to the compute queue, which may be a different family to graphics.
+        let compute_command_buffer = allocate_command_buffer(vulkan_context.compute_command_pool)?;
 
         let draw_data_buffer = unsafe {
             Buffer::new(
@@ -84,6 +84,15 @@ impl Frame {
             unsafe { Buffer::new(vulkan_context, vk::BufferUsageFlags::UNIFORM_BUFFER, 1) };
         let cull_params_buffer =
             unsafe { Buffer::new(vulkan_context, vk::BufferUsageFlags::UNIFORM_BUFFER, 1) };
+        let fog_params_buffer =
+            unsafe { Buffer::new(vulkan_context, vk::BufferUsageFlags::UNIFORM_BUFFER, 1) };
+        let sprite_data_buffer = unsafe {
+            Buffer::new(
+                vulkan_context,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                MAX_SPRITES,
+            )
+        };
 
         // Update the descriptor sets for this frame.
         unsafe {
@@ -110,20 +119,35 @@ impl Frame {
                 descriptors.compute_sets[index],
                 CULL_PARAMS_BINDING,
             );
+            fog_params_buffer.update_descriptor_set(
+                &vulkan_context.device,
+                descriptors.fog_compute_sets[index],
+                FOG_PARAMS_BINDING,
+            );
 
             // Add some default data to the scene buffer.
             scene_data_buffer.push(&Default::default());
         }
 
         Ok(Self {
-            fence,
-            compute_fence,
+            render_value: 0,
+            cull_buffers_released_to: None,
             command_buffer,
             compute_command_buffer,
             draw_data_buffer,
             primitive_cull_data_buffer,
             scene_data_buffer,
             cull_params_buffer,
+            fog_params_buffer,
+            sprite_data_buffer,
         })
     }
+
+    /// The buffers the culling shader reads and writes, which are handed between the compute and graphics queues
+    pub fn cull_buffers(&self) -> [vk::Buffer; 2] {
+        [
+            self.primitive_cull_data_buffer.buffer,
+            self.cull_params_buffer.buffer,
+        ]
+    }
 }
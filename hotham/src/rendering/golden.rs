@@ -0,0 +1,399 @@
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+use openxr::{Fovf, Posef, Quaternionf, Vector3f};
+
+use crate::util::should_update_images;
+
+#[cfg(target_os = "windows")]
+use {
+    crate::{
+        asset_importer,
+        components::{GlobalTransform, LocalTransform, Stage},
+        contexts::{physics_context::DELTA_TIME, RenderContext, VulkanContext},
+        systems::{
+            rendering::rendering_system_inner,
+            update_global_transform::update_global_transform_system_inner,
+            update_global_transform_with_parent::update_global_transform_with_parent_system_inner,
+        },
+        util::read_image_from_gpu,
+    },
+    hecs::World,
+};
+
+/// Where reference images are stored, relative to the `hotham` crate.
+const GOLDEN_DIRECTORY: &str = "../test_assets/golden";
+
+/// The largest possible difference between two colours in YIQ space, ie. between black and white.
+const MAX_YIQ_DELTA: f32 = 35215.;
+
+/// A screenshot test for the renderer.
+///
+/// Renders a scene for a fixed number of frames, with a fixed timestep and fixed views, then compares the result
+/// against a reference image in `test_assets/golden`. Rather than requiring an exact match, which would break on every
+/// driver update, images are compared perceptually: a pixel only counts as different if a person would notice, and the
+/// test only fails if too many pixels are different.
+///
+/// Run the tests with `UPDATE_IMAGES=1` to record new reference images.
+pub(crate) struct GoldenTest {
+    name: String,
+    // Scenes can only be rendered on Windows, so these are unused elsewhere.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    views: Vec<openxr::View>,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    frames: usize,
+    pixel_threshold: f32,
+    max_different_pixels: f32,
+}
+
+impl GoldenTest {
+    /// Create a golden test called `name`, which will be compared against `test_assets/golden/{name}.png`.
+    /// By default the camera is 1.5m back from the origin, looking down -Z.
+    pub fn new(name: &str) -> Self {
+        let view = openxr::View {
+            pose: Posef {
+                orientation: Quaternionf::IDENTITY,
+                position: Vector3f {
+                    x: 0.,
+                    y: 0.,
+                    z: 1.5,
+                },
+            },
+            fov: Fovf {
+                angle_up: 45.0_f32.to_radians(),
+                angle_down: -45.0_f32.to_radians(),
+                angle_left: -45.0_f32.to_radians(),
+                angle_right: 45.0_f32.to_radians(),
+            },
+        };
+
+        Self {
+            name: name.to_string(),
+            views: vec![view.clone(), view],
+            frames: 1,
+            pixel_threshold: 0.1,
+            max_different_pixels: 0.001,
+        }
+    }
+
+    /// Render this many frames before taking the screenshot.
+    #[cfg(target_os = "windows")]
+    pub fn frames(mut self, frames: usize) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    /// How different, from 0 to 1, two pixels have to be to count as different.
+    pub fn pixel_threshold(mut self, pixel_threshold: f32) -> Self {
+        self.pixel_threshold = pixel_threshold;
+        self
+    }
+
+    /// What fraction of the image, from 0 to 1, is allowed to be different before the test fails.
+    pub fn max_different_pixels(mut self, max_different_pixels: f32) -> Self {
+        self.max_different_pixels = max_different_pixels;
+        self
+    }
+
+    /// Load the glTF scene in `glb_buffer`, render it and compare it against the reference image.
+    #[cfg(target_os = "windows")]
+    pub fn run_glb(
+        &self,
+        glb_buffer: &[u8],
+        update: impl FnMut(&mut World, f32),
+    ) -> Result<(), String> {
+        self.run(
+            |vulkan_context, render_context| load_scene(glb_buffer, vulkan_context, render_context),
+            update,
+        )
+    }
+
+    /// Build a scene with `setup`, render it and compare it against the reference image.
+    ///
+    /// `update` is called before each frame with the time since the first frame, so animations and the like can be
+    /// driven deterministically.
+    #[cfg(target_os = "windows")]
+    pub fn run<S, U>(&self, setup: S, update: U) -> Result<(), String>
+    where
+        S: FnOnce(&VulkanContext, &mut RenderContext) -> World,
+        U: FnMut(&mut World, f32),
+    {
+        let actual = self.render(setup, update);
+        self.compare(&actual)
+    }
+
+    /// Build a scene with `setup` and render it, without comparing it against anything.
+    #[cfg(target_os = "windows")]
+    pub fn render<S, U>(&self, setup: S, mut update: U) -> RgbaImage
+    where
+        S: FnOnce(&VulkanContext, &mut RenderContext) -> World,
+        U: FnMut(&mut World, f32),
+    {
+        let (mut render_context, vulkan_context, image) = RenderContext::testing_with_image();
+        let mut world = setup(&vulkan_context, &mut render_context);
+
+        for frame in 0..self.frames {
+            // Use a fixed timestep, so the scene ends up in the same state no matter how fast the machine is.
+            update(&mut world, frame as f32 * DELTA_TIME);
+
+            render_context.begin_frame(&vulkan_context);
+            update_global_transform_system_inner(&mut world);
+            update_global_transform_with_parent_system_inner(&mut world);
+            rendering_system_inner(
+                &mut world,
+                &vulkan_context,
+                &mut render_context,
+                &self.views,
+                0,
+            );
+            render_context.end_frame(&vulkan_context);
+        }
+
+        unsafe { read_image_from_gpu(&vulkan_context, &image) }
+    }
+
+    /// Compare `actual` against the reference image. If they're too different, the actual image and a diff are
+    /// saved next to the reference so the failure can be inspected.
+    pub fn compare(&self, actual: &RgbaImage) -> Result<(), String> {
+        let reference_path = self.path("");
+        if should_update_images() {
+            std::fs::create_dir_all(GOLDEN_DIRECTORY).map_err(|e| e.to_string())?;
+            actual.save(&reference_path).map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+
+        let reference = image::open(&reference_path)
+            .map_err(|e| {
+                format!(
+                    "Unable to open reference image {:?} - {}. Run with UPDATE_IMAGES=1 to record it.",
+                    reference_path, e
+                )
+            })?
+            .to_rgba8();
+
+        let diff = perceptual_diff(&reference, actual, self.pixel_threshold)?;
+        if diff.fraction_different() <= self.max_different_pixels {
+            return Ok(());
+        }
+
+        let actual_path = self.path("_actual");
+        let diff_path = self.path("_diff");
+        let _ = actual.save(&actual_path);
+        let _ = diff.image.save(&diff_path);
+        Err(format!(
+            "Bad render: {} - {:.3}% of pixels are different, but only {:.3}% are allowed. See {:?} and {:?}",
+            self.name,
+            diff.fraction_different() * 100.,
+            self.max_different_pixels * 100.,
+            actual_path,
+            diff_path
+        ))
+    }
+
+    fn path(&self, suffix: &str) -> PathBuf {
+        PathBuf::from(GOLDEN_DIRECTORY).join(format!("{}{}.png", self.name, suffix))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn load_scene(
+    glb_buffer: &[u8],
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+) -> World {
+    let models =
+        asset_importer::load_models_from_glb(&[glb_buffer], vulkan_context, render_context)
+            .unwrap();
+
+    let mut world = World::new();
+    world.spawn((
+        Stage {},
+        LocalTransform::default(),
+        GlobalTransform::default(),
+    ));
+
+    // Add the models in a fixed order, so draw order is the same on every run.
+    let mut names = models.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        asset_importer::add_model_to_world(name, &models, &mut world, None);
+    }
+
+    world
+}
+
+/// The result of comparing two images with [`perceptual_diff`]
+pub(crate) struct Diff {
+    /// How many pixels were noticeably different
+    pub different_pixels: usize,
+    /// How many pixels were compared
+    pub total_pixels: usize,
+    /// A faded copy of the expected image, with different pixels marked in red
+    pub image: RgbaImage,
+}
+
+impl Diff {
+    /// What fraction of the image was different, from 0 to 1
+    pub fn fraction_different(&self) -> f32 {
+        if self.total_pixels == 0 {
+            return 0.;
+        }
+        self.different_pixels as f32 / self.total_pixels as f32
+    }
+}
+
+/// Compare two images, counting the pixels that are noticeably different.
+///
+/// Colours are compared in YIQ space, which weights differences in brightness more heavily than differences in hue,
+/// roughly matching how people perceive them. `threshold` is how different two pixels have to be, from 0 to 1.
+pub(crate) fn perceptual_diff(
+    expected: &RgbaImage,
+    actual: &RgbaImage,
+    threshold: f32,
+) -> Result<Diff, String> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(format!(
+            "Image sizes don't match - expected {:?}, got {:?}",
+            expected.dimensions(),
+            actual.dimensions()
+        ));
+    }
+
+    let max_delta = MAX_YIQ_DELTA * threshold * threshold;
+    let (width, height) = expected.dimensions();
+    let mut image = RgbaImage::new(width, height);
+    let mut different_pixels = 0;
+
+    for ((e, a), d) in expected
+        .pixels()
+        .zip(actual.pixels())
+        .zip(image.pixels_mut())
+    {
+        if colour_delta(e, a) > max_delta {
+            different_pixels += 1;
+            *d = Rgba([255, 0, 0, 255]);
+        } else {
+            let faded = (255. - (255. - luma(e)) * 0.1) as u8;
+            *d = Rgba([faded, faded, faded, 255]);
+        }
+    }
+
+    Ok(Diff {
+        different_pixels,
+        total_pixels: (width * height) as _,
+        image,
+    })
+}
+
+fn colour_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let [r1, g1, b1] = rgb(a);
+    let [r2, g2, b2] = rgb(b);
+    let (r, g, b) = (r1 - r2, g1 - g2, b1 - b2);
+
+    let y = r * 0.29889531 + g * 0.58662247 + b * 0.11448223;
+    let i = r * 0.59597799 - g * 0.27417610 - b * 0.32180189;
+    let q = r * 0.21147017 - g * 0.52261711 + b * 0.31114694;
+
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    let [r, g, b] = rgb(pixel);
+    r * 0.29889531 + g * 0.58662247 + b * 0.11448223
+}
+
+fn rgb(pixel: &Rgba<u8>) -> [f32; 3] {
+    [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_perceptual_diff() {
+        let expected = RgbaImage::from_pixel(10, 10, Rgba([100, 150, 200, 255]));
+
+        // Identical images shouldn't be different at all..
+        let diff = perceptual_diff(&expected, &expected, 0.1).unwrap();
+        assert_eq!(diff.different_pixels, 0);
+
+        // ..and neither should images with some noise you can't see.
+        let noisy = RgbaImage::from_pixel(10, 10, Rgba([102, 149, 201, 255]));
+        let diff = perceptual_diff(&expected, &noisy, 0.1).unwrap();
+        assert_eq!(diff.different_pixels, 0);
+
+        // But a block of a different colour should be picked up.
+        let mut actual = expected.clone();
+        for x in 0..5 {
+            for y in 0..2 {
+                actual.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        let diff = perceptual_diff(&expected, &actual, 0.1).unwrap();
+        assert_eq!(diff.different_pixels, 10);
+        assert_eq!(diff.fraction_different(), 0.1);
+        assert_eq!(*diff.image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_ne!(*diff.image.get_pixel(9, 9), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    pub fn test_compare() {
+        let test = GoldenTest::new("gradient")
+            .pixel_threshold(0.1)
+            .max_different_pixels(0.01);
+        let white = Rgba([255, 255, 255, 255]);
+
+        // The image the reference was recorded from matches it..
+        let mut actual = gradient();
+        test.compare(&actual).unwrap();
+        if should_update_images() {
+            return;
+        }
+
+        // ..and so does one with a few pixels changed, up to the tolerance..
+        for x in 0..40 {
+            actual.put_pixel(x, 0, white);
+        }
+        test.compare(&actual).unwrap();
+
+        // ..but not past it, when the actual image and a diff are saved next to the reference.
+        for x in 0..64 {
+            actual.put_pixel(x, 1, white);
+        }
+        let error = test.compare(&actual).unwrap_err();
+        assert!(error.starts_with("Bad render: gradient"));
+        for suffix in ["_actual", "_diff"] {
+            let path = test.path(suffix);
+            assert!(path.exists());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    /// The image `test_assets/golden/gradient.png` was recorded from
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |x, y| {
+            Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])
+        })
+    }
+
+    #[test]
+    pub fn test_perceptual_diff_size_mismatch() {
+        let expected = RgbaImage::new(10, 10);
+        let actual = RgbaImage::new(10, 5);
+        assert!(perceptual_diff(&expected, &actual, 0.1).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    pub fn test_rendering_is_deterministic() {
+        let test = GoldenTest::new("damaged_helmet").frames(3);
+        let glb_buffer: &[u8] = include_bytes!("../../../test_assets/damaged_helmet.glb");
+
+        // Rendering the same scene twice must give exactly the same image, or golden tests would be flaky.
+        let first = test.render(|v, r| load_scene(glb_buffer, v, r), |_, _| {});
+        let second = test.render(|v, r| load_scene(glb_buffer, v, r), |_, _| {});
+        let diff = perceptual_diff(&first, &second, 0.).unwrap();
+        assert_eq!(diff.different_pixels, 0);
+    }
+}
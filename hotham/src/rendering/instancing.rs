@@ -0,0 +1,343 @@
+use std::{mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, Mat4};
+use vk_shader_macros::include_glsl;
+
+use crate::contexts::{
+    render_context::{create_pipeline, create_push_constant, FRAG},
+    VulkanContext,
+};
+
+use super::{
+    buffer::Buffer,
+    descriptors::{Descriptors, DESCRIPTOR_SET_COUNT},
+    primitive::Primitive,
+};
+
+static INSTANCED_VERT: &[u32] = include_glsl!("src/shaders/instanced.vert", target: vulkan1_1);
+
+/// The most instances that can be drawn in a frame, across all [`crate::components::Instanced`] entities
+pub const MAX_INSTANCES: usize = 32_768;
+
+/// The most indirect draws that can be recorded for instanced meshes in a frame - one for each primitive of each mesh
+pub const MAX_INSTANCED_DRAWS: usize = 256;
+
+const INSTANCE_BINDING: u32 = 0;
+
+/// One copy of an instanced mesh, as it's sent to `instanced.vert`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstanceData {
+    /// The transform of this copy of the mesh
+    pub gos_from_local: Mat4,
+    /// The inverse of `gos_from_local`, for transforming normals
+    pub local_from_gos: Mat4,
+}
+
+impl InstanceData {
+    /// Create the data for a copy of a mesh at `gos_from_local`
+    pub fn new(gos_from_local: &Affine3A) -> Self {
+        Self {
+            gos_from_local: (*gos_from_local).into(),
+            local_from_gos: gos_from_local.inverse().into(),
+        }
+    }
+}
+
+/// The instances, draw commands and materials gathered for a frame
+#[derive(Debug, Clone, Default)]
+pub struct InstancedDraws {
+    /// Every instance of every mesh, each mesh's instances one after another
+    pub instances: Vec<InstanceData>,
+    /// One draw for each primitive of each mesh, covering all of that mesh's instances
+    pub commands: Vec<vk::DrawIndexedIndirectCommand>,
+    /// The material of each draw in `commands`
+    pub materials: Vec<u32>,
+}
+
+impl InstancedDraws {
+    /// Forget everything gathered for the last frame
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.commands.clear();
+        self.materials.clear();
+    }
+
+    /// Add a copy of the mesh made of `primitives` at each of `transforms`, relative to `gos_from_local`, and one draw
+    /// for each primitive. Instances past [`MAX_INSTANCES`] and draws past [`MAX_INSTANCED_DRAWS`] are left out.
+    pub fn push(
+        &mut self,
+        primitives: &[Primitive],
+        gos_from_local: &Affine3A,
+        transforms: &[Affine3A],
+    ) {
+        let first_instance = self.instances.len();
+        let room = MAX_INSTANCES - first_instance;
+        self.instances.extend(
+            transforms
+                .iter()
+                .take(room)
+                .map(|transform| InstanceData::new(&(*gos_from_local * *transform))),
+        );
+
+        let instance_count = (self.instances.len() - first_instance) as u32;
+        if instance_count == 0 {
+            return;
+        }
+        for primitive in primitives
+            .iter()
+            .take(MAX_INSTANCED_DRAWS - self.commands.len())
+        {
+            self.commands.push(vk::DrawIndexedIndirectCommand {
+                index_count: primitive.indices_count,
+                instance_count,
+                first_index: primitive.index_buffer_offset,
+                vertex_offset: primitive.vertex_buffer_offset as _,
+                first_instance: first_instance as _,
+            });
+            self.materials.push(primitive.material_id);
+        }
+    }
+}
+
+/// The per-frame buffers used to draw instanced meshes
+pub(crate) struct InstancedFrame {
+    instance_buffer: Buffer<InstanceData>,
+    indirect_buffer: Buffer<vk::DrawIndexedIndirectCommand>,
+    descriptor_set: vk::DescriptorSet,
+}
+
+/// Draws [`crate::components::Instanced`] meshes.
+///
+/// Each instance's transform is written to a storage buffer, and each primitive of each mesh is drawn with a single
+/// indirect, instanced draw, which `instanced.vert` looks its instances up in. Unlike meshes drawn one entity at a
+/// time, there's no [`super::resources::DrawData`] for each instance, and instances aren't culled.
+pub struct InstancedRenderer {
+    /// Draws instanced meshes, with the same fragment shader as the PBR pipeline
+    pub pipeline: vk::Pipeline,
+    /// The shared descriptor set, the fog set and the instance set, with the material ID as a push constant
+    pub pipeline_layout: vk::PipelineLayout,
+    /// Layout of the instance set
+    pub set_layout: vk::DescriptorSetLayout,
+    /// The instances and draws gathered for this frame
+    pub draws: InstancedDraws,
+    // One per frame, plus one for the spectator view, like the descriptor sets.
+    pub(crate) frames: [InstancedFrame; DESCRIPTOR_SET_COUNT],
+    /// Can indirect draws start past the first instance? If not, the draws are recorded directly instead.
+    draw_indirect_first_instance: bool,
+}
+
+impl InstancedRenderer {
+    pub(crate) unsafe fn new(
+        vulkan_context: &VulkanContext,
+        descriptors: &Descriptors,
+        render_area: &vk::Rect2D,
+        render_pass: vk::RenderPass,
+        reversed_z: bool,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let binding = vk::DescriptorSetLayoutBinding {
+            binding: INSTANCE_BINDING,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            descriptor_count: 1,
+            ..Default::default()
+        };
+        let set_layout = device.create_descriptor_set_layout(
+            &vk::DescriptorSetLayoutCreateInfo::builder().bindings(slice_from_ref(&binding)),
+            None,
+        )?;
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(size_of::<u32>() as _)
+            .build();
+        let set_layouts = [
+            descriptors.graphics_layout,
+            descriptors.fog_layout,
+            set_layout,
+        ];
+        let pipeline_layout = device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(slice_from_ref(&push_constant_range)),
+            None,
+        )?;
+        let pipeline = create_pipeline(
+            vulkan_context,
+            pipeline_layout,
+            render_area,
+            render_pass,
+            reversed_z,
+            descriptors.texture_capacity,
+            INSTANCED_VERT,
+            FRAG,
+        )?;
+
+        let frames = [(); DESCRIPTOR_SET_COUNT].map(|_| {
+            let descriptor_set = device
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(descriptors.pool)
+                        .set_layouts(slice_from_ref(&set_layout)),
+                )
+                .unwrap()[0];
+            let frame = InstancedFrame {
+                instance_buffer: Buffer::new(
+                    vulkan_context,
+                    vk::BufferUsageFlags::STORAGE_BUFFER,
+                    MAX_INSTANCES,
+                ),
+                indirect_buffer: Buffer::new(
+                    vulkan_context,
+                    vk::BufferUsageFlags::INDIRECT_BUFFER,
+                    MAX_INSTANCED_DRAWS,
+                ),
+                descriptor_set,
+            };
+            frame
+                .instance_buffer
+                .update_descriptor_set(device, descriptor_set, INSTANCE_BINDING);
+            frame
+        });
+
+        Ok(Self {
+            pipeline,
+            pipeline_layout,
+            set_layout,
+            draws: Default::default(),
+            frames,
+            draw_indirect_first_instance: vulkan_context.capabilities.draw_indirect_first_instance,
+        })
+    }
+
+    /// Upload this frame's instances and record their draws. Must be inside the PBR render pass, with its index and
+    /// vertex buffers bound. Returns whether anything was drawn, as the PBR pipeline will need binding again if it was.
+    pub(crate) unsafe fn draw(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        shared_sets: [vk::DescriptorSet; 2],
+    ) -> bool {
+        let frame = &mut self.frames[frame_index];
+        frame.instance_buffer.overwrite(&self.draws.instances);
+        frame.indirect_buffer.overwrite(&self.draws.commands);
+        if self.draws.commands.is_empty() {
+            return false;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[shared_sets[0], shared_sets[1], frame.descriptor_set],
+            &[],
+        );
+
+        let stride = size_of::<vk::DrawIndexedIndirectCommand>();
+        for (index, (command, material_id)) in self
+            .draws
+            .commands
+            .iter()
+            .zip(&self.draws.materials)
+            .enumerate()
+        {
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                create_push_constant(material_id),
+            );
+            if self.draw_indirect_first_instance {
+                device.cmd_draw_indexed_indirect(
+                    command_buffer,
+                    frame.indirect_buffer.buffer,
+                    (index * stride) as _,
+                    1,
+                    stride as _,
+                );
+            } else {
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    command.index_count,
+                    command.instance_count,
+                    command.first_index,
+                    command.vertex_offset,
+                    command.first_instance,
+                );
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Vec3, Vec4};
+
+    #[test]
+    pub fn test_push_instanced_draws() {
+        let primitive = |index_buffer_offset, material_id| Primitive {
+            index_buffer_offset,
+            vertex_buffer_offset: index_buffer_offset * 2,
+            indices_count: 30,
+            material_id,
+            bounding_sphere: Vec4::ZERO,
+        };
+        let tree = [primitive(0, 1), primitive(100, 2)];
+        let rock = [primitive(300, 3)];
+        let row = |count| {
+            (0..count)
+                .map(|i| Affine3A::from_translation(Vec3::X * i as f32))
+                .collect::<Vec<_>>()
+        };
+
+        let mut draws = InstancedDraws::default();
+        let gos_from_local = Affine3A::from_translation(Vec3::Y);
+        draws.push(&tree, &gos_from_local, &row(3));
+        draws.push(&rock, &gos_from_local, &[]);
+        draws.push(&rock, &gos_from_local, &row(2));
+
+        // Each instance is placed relative to its entity..
+        assert_eq!(draws.instances.len(), 5);
+        assert_eq!(
+            draws.instances[2],
+            InstanceData::new(&Affine3A::from_translation(Vec3::new(2., 1., 0.)))
+        );
+
+        // ..and there's one draw for each primitive of each mesh with instances.
+        let commands = draws
+            .commands
+            .iter()
+            .map(|c| {
+                (
+                    c.first_index,
+                    c.vertex_offset,
+                    c.instance_count,
+                    c.first_instance,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(commands, [(0, 0, 3, 0), (100, 200, 3, 0), (300, 600, 2, 3)]);
+        assert_eq!(draws.materials, [1, 2, 3]);
+
+        // Instances that don't fit are left out.
+        draws.clear();
+        draws.push(&rock, &gos_from_local, &row(MAX_INSTANCES + 1));
+        assert_eq!(draws.instances.len(), MAX_INSTANCES);
+        assert_eq!(draws.commands[0].instance_count, MAX_INSTANCES as u32);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, Vec4};
+use vk_shader_macros::include_glsl;
+
+use crate::{
+    components::LensFlare,
+    contexts::{
+        render_context::{
+            create_push_constant, create_shader, RENDER_PASS_DYNAMIC_STATES, SAMPLES,
+        },
+        VulkanContext,
+    },
+};
+
+use super::light::{Light, LIGHT_TYPE_DIRECTIONAL, LIGHT_TYPE_NONE};
+
+static LENS_FLARE_VERT: &[u32] = include_glsl!("src/shaders/lens_flare.vert", target: vulkan1_1);
+static LENS_FLARE_FRAG: &[u32] = include_glsl!("src/shaders/lens_flare.frag", target: vulkan1_1);
+
+/// A lens flare, as it's sent to the lens flare shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensFlareData {
+    /// The light's position in globally oriented stage space, or the direction towards it if `w` is 0
+    pub position: Vec4,
+    /// The flare's color, scaled by its intensity and visibility
+    pub color: Vec4,
+    /// `x` is the tangent of the flare's radius
+    pub size: Vec4,
+}
+
+impl LensFlareData {
+    /// Create the data for `lens_flare`, drawn over `light`, which is in global space. Returns `None` if the flare
+    /// can't be seen.
+    pub fn new(lens_flare: &LensFlare, light: &Light, gos_from_global: &Affine3A) -> Option<Self> {
+        if lens_flare.visibility <= 0. || light.light_type == LIGHT_TYPE_NONE {
+            return None;
+        }
+
+        let position = if light.light_type == LIGHT_TYPE_DIRECTIONAL {
+            (-gos_from_global.transform_vector3(light.direction)).extend(0.)
+        } else {
+            gos_from_global.transform_point3(light.position).extend(1.)
+        };
+        let color = lens_flare.color * light.color * lens_flare.intensity * lens_flare.visibility;
+
+        Some(Self {
+            position,
+            color: color.extend(1.),
+            size: Vec4::new(lens_flare.size.tan(), 0., 0., 0.),
+        })
+    }
+}
+
+/// Draws lens flares as sprites, added on top of everything else in the PBR render pass.
+pub struct LensFlarePipeline {
+    /// The pipeline itself
+    pub pipeline: vk::Pipeline,
+    /// Uses the shared descriptor set for the scene data, plus a push constant for each flare
+    pub pipeline_layout: vk::PipelineLayout,
+}
+
+impl LensFlarePipeline {
+    pub(crate) fn new(
+        vulkan_context: &VulkanContext,
+        set_layout: vk::DescriptorSetLayout,
+        render_area: &vk::Rect2D,
+        render_pass: vk::RenderPass,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<LensFlareData>() as _)
+            .build();
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(slice_from_ref(&set_layout))
+                    .push_constant_ranges(slice_from_ref(&push_constant_range)),
+                None,
+            )
+        }?;
+
+        let (vertex_shader, vertex_stage) = create_shader(
+            LENS_FLARE_VERT,
+            vk::ShaderStageFlags::VERTEX,
+            vulkan_context,
+        )?;
+        let (fragment_shader, fragment_stage) = create_shader(
+            LENS_FLARE_FRAG,
+            vk::ShaderStageFlags::FRAGMENT,
+            vulkan_context,
+        )?;
+        let stages = [vertex_stage, fragment_stage];
+
+        // The sprites are generated in the vertex shader, so there are no vertex inputs.
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: render_area.extent.width as _,
+            height: render_area.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice_from_ref(&viewport))
+            .scissors(slice_from_ref(render_area));
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .line_width(1.0);
+        let multisample_state =
+            vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(SAMPLES);
+
+        // Occlusion is checked with raycasts instead, as the sprite is much closer than the light.
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false);
+
+        // Add the flares to the color, and leave alpha alone.
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ONE)
+            .dst_color_blend_factor(vk::BlendFactor::ONE)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(slice_from_ref(&color_blend_attachment));
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
+        let create_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .dynamic_state(&dynamic_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .layout(pipeline_layout)
+            .render_pass(render_pass)
+            .subpass(0)
+            .build();
+
+        let pipelines = unsafe {
+            device.create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                slice_from_ref(&create_info),
+                None,
+            )
+        }
+        .map_err(|(_, r)| r)?;
+
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+
+        Ok(Self {
+            pipeline: pipelines[0],
+            pipeline_layout,
+        })
+    }
+
+    /// Draw `lens_flares`. Must be inside the PBR render pass.
+    pub(crate) unsafe fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        lens_flares: &[LensFlareData],
+    ) {
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            slice_from_ref(&descriptor_set),
+            &[],
+        );
+        for lens_flare in lens_flares {
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                create_push_constant(lens_flare),
+            );
+            device.cmd_draw(command_buffer, 6, 1, 0, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::Vec3;
+
+    #[test]
+    pub fn test_lens_flare_data() {
+        let gos_from_global = Affine3A::from_translation(Vec3::new(0., -1., 0.));
+        let lens_flare = LensFlare {
+            intensity: 2.,
+            visibility: 0.5,
+            ..LensFlare::new(0)
+        };
+
+        // Point lights are moved into globally oriented stage space..
+        let light = Light::new_point(Vec3::new(0., 2., -3.), 10., 5., Vec3::new(1., 0.5, 0.));
+        let data = LensFlareData::new(&lens_flare, &light, &gos_from_global).unwrap();
+        assert_relative_eq!(data.position, Vec4::new(0., 1., -3., 1.));
+        assert_relative_eq!(data.color, Vec4::new(1., 0.5, 0., 1.));
+        assert_relative_eq!(data.size.x, 0.1f32.tan());
+
+        // ..while directional lights are a direction towards the light.
+        let light = Light::new_directional(Vec3::NEG_Y, 5., Vec3::ONE);
+        let data = LensFlareData::new(&lens_flare, &light, &gos_from_global).unwrap();
+        assert_relative_eq!(data.position, Vec4::new(0., 1., 0., 0.));
+
+        // Hidden flares, and flares without a light, aren't drawn.
+        let hidden = LensFlare::new(0);
+        assert!(LensFlareData::new(&hidden, &light, &gos_from_global).is_none());
+        assert!(LensFlareData::new(&lens_flare, &Light::none(), &gos_from_global).is_none());
+    }
+}
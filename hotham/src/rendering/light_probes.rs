@@ -0,0 +1,387 @@
+use glam::{UVec3, Vec3, Vec4};
+use hecs::World;
+use serde::{Deserialize, Serialize};
+
+use super::light::{Light, LIGHT_TYPE_DIRECTIONAL, LIGHT_TYPE_NONE, LIGHT_TYPE_POINT};
+use crate::contexts::PhysicsContext;
+
+/// The constant spherical harmonic basis function
+const SH_Y0: f32 = 0.282_095;
+
+/// The scale of the linear spherical harmonic basis functions, which are this times the direction's x, y or z
+const SH_Y1: f32 = 0.488_603;
+
+/// Ambient light arriving at a point from every direction, stored as first order (L1) spherical harmonics - four
+/// coefficients per color channel.
+///
+/// That's only enough to capture the broad shape of the light, like "brighter from above, and a little blue from the
+/// left", which is all a diffuse surface needs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShProbe {
+    /// The coefficients for the constant basis function, then the x, y and z basis functions
+    pub coefficients: [Vec3; 4],
+}
+
+impl ShProbe {
+    /// A probe with the same `radiance` arriving from every direction
+    pub fn uniform(radiance: Vec3) -> Self {
+        let mut probe = Self::default();
+        probe.coefficients[0] = radiance * SH_Y0 * 4. * std::f32::consts::PI;
+        probe
+    }
+
+    /// Add `radiance` arriving from `direction`. When adding `n` samples spread evenly over the sphere, `weight`
+    /// should be `4π / n`.
+    pub fn add_sample(&mut self, direction: Vec3, radiance: Vec3, weight: f32) {
+        let d = direction.normalize() * SH_Y1;
+        let basis = [SH_Y0, d.x, d.y, d.z];
+        for (coefficient, basis) in self.coefficients.iter_mut().zip(basis) {
+            *coefficient += radiance * basis * weight;
+        }
+    }
+
+    /// The light a diffuse surface facing `normal` receives, divided by π - the same as an irradiance map holds. This
+    /// must match the PBR shader's `getDiffuseLight`.
+    pub fn evaluate(&self, normal: Vec3) -> Vec3 {
+        let [r, g, b] = self.packed();
+        let basis = Vec4::new(1., normal.x, normal.y, normal.z);
+        Vec3::new(r.dot(basis), g.dot(basis), b.dot(basis)).max(Vec3::ZERO)
+    }
+
+    /// Blend between this probe and `other`
+    pub fn lerp(&self, other: &ShProbe, t: f32) -> ShProbe {
+        let mut probe = *self;
+        for (coefficient, other) in probe.coefficients.iter_mut().zip(other.coefficients) {
+            *coefficient = coefficient.lerp(other, t);
+        }
+        probe
+    }
+
+    /// The probe as it's sent to the PBR shader: one `Vec4` per color channel, already convolved with a cosine lobe
+    /// so that the light facing `n` is `dot(channel, vec4(1, n))`
+    pub fn packed(&self) -> [Vec4; 3] {
+        // The cosine lobe scales the constant band by π and the linear band by 2π/3, then the whole lot is divided by
+        // π to match the irradiance map.
+        let [c0, cx, cy, cz] = self.coefficients;
+        let c0 = c0 * SH_Y0;
+        let linear = SH_Y1 * 2. / 3.;
+        let (cx, cy, cz) = (cx * linear, cy * linear, cz * linear);
+        [
+            Vec4::new(c0.x, cx.x, cy.x, cz.x),
+            Vec4::new(c0.y, cx.y, cy.y, cz.y),
+            Vec4::new(c0.z, cx.z, cy.z, cz.z),
+        ]
+    }
+}
+
+/// A grid of ambient light probes baked for a level with [`bake_light_probes`], to light dynamic objects as they move
+/// around it.
+///
+/// Grids are baked once - eg. in a desktop build while the level is being made - and shipped with the level, so they
+/// can be saved with [`crate::contexts::StorageContext`] or `serde_json`. Set
+/// [`crate::contexts::RenderContext::light_probes`] to the level's grid when it's loaded, and add
+/// [`crate::components::AmbientProbe`] to anything that should be lit by it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightProbeGrid {
+    /// Where the first probe is, in global space
+    pub origin: Vec3,
+    /// How far apart the probes are, in meters
+    pub spacing: f32,
+    /// How many probes there are along each axis
+    pub size: UVec3,
+    /// The probes, in x, then y, then z order
+    pub probes: Vec<ShProbe>,
+}
+
+impl LightProbeGrid {
+    /// Where the probe at `(x, y, z)` in the grid is, in global space
+    pub fn probe_position(&self, x: u32, y: u32, z: u32) -> Vec3 {
+        self.origin + UVec3::new(x, y, z).as_vec3() * self.spacing
+    }
+
+    /// The light at `position`, in global space, blended from the eight probes around it. Positions outside the grid
+    /// use the nearest probes at its edge.
+    pub fn sample(&self, position: Vec3) -> ShProbe {
+        if self.probes.is_empty() {
+            return ShProbe::default();
+        }
+        let max = (self.size.max(UVec3::ONE) - UVec3::ONE).as_vec3();
+        let cell = ((position - self.origin) / self.spacing).clamp(Vec3::ZERO, max);
+        let low = cell.floor().as_uvec3();
+        let high = (low + UVec3::ONE).min(max.as_uvec3());
+        let t = cell - low.as_vec3();
+
+        let probe = |x: u32, y: u32, z: u32| self.probes[self.index(x, y, z)];
+        let along_x = |y: u32, z: u32| probe(low.x, y, z).lerp(&probe(high.x, y, z), t.x);
+        let along_y = |z: u32| along_x(low.y, z).lerp(&along_x(high.y, z), t.y);
+        along_y(low.z).lerp(&along_y(high.z), t.z)
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        (x + self.size.x * (y + self.size.y * z)) as usize
+    }
+}
+
+/// Settings for [`bake_light_probes`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightBakeSettings {
+    /// The corner of the area to fill with probes with the lowest x, y and z, in global space
+    pub min: Vec3,
+    /// The opposite corner of the area to fill with probes, in global space
+    pub max: Vec3,
+    /// How far apart the probes are, in meters
+    pub spacing: f32,
+    /// How many directions to look in from each probe. More directions take longer to bake, but are less noisy.
+    pub samples_per_probe: u32,
+    /// The light from the sky, seen when looking straight up without anything in the way
+    pub sky_color: Vec3,
+    /// The light from below the horizon, seen when looking straight down without anything in the way
+    pub ground_color: Vec3,
+    /// How much of the light that hits the scene bounces back off it
+    pub albedo: f32,
+    /// How far to look for things in the way, in meters
+    pub max_distance: f32,
+}
+
+impl Default for LightBakeSettings {
+    fn default() -> Self {
+        Self {
+            min: Vec3::new(-5., 0., -5.),
+            max: Vec3::new(5., 3., 5.),
+            spacing: 1.,
+            samples_per_probe: 256,
+            sky_color: Vec3::new(0.6, 0.7, 0.9),
+            ground_color: Vec3::new(0.2, 0.18, 0.15),
+            albedo: 0.5,
+            max_distance: 100.,
+        }
+    }
+}
+
+/// Bake a grid of ambient light probes from the scene: the sky, plus the sky and `lights` bouncing once off the
+/// scene's colliders. Direct light from `lights` isn't included, as the PBR shader already adds it.
+///
+/// The scene's geometry comes from the colliders in `physics_context`, so add colliders to anything that should block
+/// or bounce light, and run `physics_system` first so they're in the simulation. Baking is slow, so do it ahead of
+/// time rather than while the level is being played.
+pub fn bake_light_probes(
+    world: &World,
+    physics_context: &PhysicsContext,
+    lights: &[Light],
+    settings: &LightBakeSettings,
+) -> LightProbeGrid {
+    let size = ((settings.max - settings.min) / settings.spacing)
+        .floor()
+        .as_uvec3()
+        + UVec3::ONE;
+    let directions = sphere_directions(settings.samples_per_probe.max(1));
+    let weight = 4. * std::f32::consts::PI / directions.len() as f32;
+
+    let mut grid = LightProbeGrid {
+        origin: settings.min,
+        spacing: settings.spacing,
+        size,
+        probes: Vec::with_capacity((size.x * size.y * size.z) as usize),
+    };
+
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let position = grid.probe_position(x, y, z);
+                let mut probe = ShProbe::default();
+                for direction in &directions {
+                    let radiance = match physics_context.cast_surface_ray(
+                        world,
+                        position,
+                        *direction,
+                        settings.max_distance,
+                    ) {
+                        Some(hit) => {
+                            let irradiance = sky_irradiance(hit.normal, settings)
+                                + lights
+                                    .iter()
+                                    .map(|light| {
+                                        direct_irradiance(
+                                            light,
+                                            hit.point,
+                                            hit.normal,
+                                            world,
+                                            physics_context,
+                                        )
+                                    })
+                                    .fold(Vec3::ZERO, |sum, irradiance| sum + irradiance);
+                            irradiance * settings.albedo / std::f32::consts::PI
+                        }
+                        None => sky_radiance(*direction, settings),
+                    };
+                    probe.add_sample(*direction, radiance, weight);
+                }
+                grid.probes.push(probe);
+            }
+        }
+    }
+
+    grid
+}
+
+/// The light from the sky when looking in `direction`
+fn sky_radiance(direction: Vec3, settings: &LightBakeSettings) -> Vec3 {
+    settings
+        .ground_color
+        .lerp(settings.sky_color, direction.y * 0.5 + 0.5)
+}
+
+/// Roughly the light from the sky arriving at a surface facing `normal`, ignoring anything in the way
+fn sky_irradiance(normal: Vec3, settings: &LightBakeSettings) -> Vec3 {
+    sky_radiance(normal, settings) * std::f32::consts::PI
+}
+
+/// The light from `light` arriving at a surface at `point` facing `normal`, if nothing is in the way
+fn direct_irradiance(
+    light: &Light,
+    point: Vec3,
+    normal: Vec3,
+    world: &World,
+    physics_context: &PhysicsContext,
+) -> Vec3 {
+    if light.light_type == LIGHT_TYPE_NONE {
+        return Vec3::ZERO;
+    }
+
+    let (to_light, distance) = if light.light_type == LIGHT_TYPE_DIRECTIONAL {
+        (-light.direction.normalize(), f32::INFINITY)
+    } else {
+        let offset = light.position - point;
+        (offset.normalize(), offset.length())
+    };
+    let n_dot_l = normal.dot(to_light);
+    if n_dot_l <= 0. {
+        return Vec3::ZERO;
+    }
+
+    // Match the falloff in the PBR shader's `getLightIntensity`.
+    let mut attenuation = 1.;
+    if light.light_type != LIGHT_TYPE_DIRECTIONAL {
+        if light.range > 0. {
+            attenuation = (1. - (distance / light.range).powi(4)).clamp(0., 1.);
+        }
+        attenuation /= (distance * distance).max(0.01);
+    }
+    if light.light_type != LIGHT_TYPE_DIRECTIONAL && light.light_type != LIGHT_TYPE_POINT {
+        let cos = light.direction.normalize().dot(-to_light);
+        let scale = 1. / (light.inner_cone_cos - light.outer_cone_cos).max(0.001);
+        let t = ((cos - light.outer_cone_cos) * scale).clamp(0., 1.);
+        attenuation *= t * t * (3. - 2. * t);
+    }
+    if attenuation <= 0. {
+        return Vec3::ZERO;
+    }
+
+    // Start a little off the surface, so it doesn't shadow itself.
+    let start = point + normal * 0.01;
+    let max_distance = distance.min(1000.) - 0.01;
+    if physics_context
+        .cast_surface_ray(world, start, to_light, max_distance)
+        .is_some()
+    {
+        return Vec3::ZERO;
+    }
+
+    light.color * light.intensity * attenuation * n_dot_l
+}
+
+/// `count` directions spread evenly over the sphere, on a Fibonacci spiral
+fn sphere_directions(count: u32) -> Vec<Vec3> {
+    let golden_angle = std::f32::consts::PI * (3. - 5_f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let y = 1. - 2. * (i as f32 + 0.5) / count as f32;
+            let radius = (1. - y * y).sqrt();
+            let angle = golden_angle * i as f32;
+            Vec3::new(angle.cos() * radius, y, angle.sin() * radius)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Affine3A;
+
+    use crate::{
+        components::{physics::SharedShape, Collider, GlobalTransform},
+        systems::physics::physics_system_inner,
+    };
+
+    #[test]
+    pub fn test_sh_probe() {
+        // A probe lit evenly from every direction looks the same from every direction..
+        let probe = ShProbe::uniform(Vec3::ONE);
+        for normal in [Vec3::X, -Vec3::Y, Vec3::Z] {
+            assert!(probe.evaluate(normal).abs_diff_eq(Vec3::ONE, 1e-4));
+        }
+
+        // ..and a probe lit from above is brightest facing up, and dark facing down.
+        let mut probe = ShProbe::default();
+        let directions = sphere_directions(512);
+        let weight = 4. * std::f32::consts::PI / directions.len() as f32;
+        for direction in &directions {
+            let radiance = if direction.y > 0. {
+                Vec3::ONE
+            } else {
+                Vec3::ZERO
+            };
+            probe.add_sample(*direction, radiance, weight);
+        }
+        let up = probe.evaluate(Vec3::Y).x;
+        let side = probe.evaluate(Vec3::X).x;
+        let down = probe.evaluate(-Vec3::Y).x;
+        assert!(up > side && side > down);
+        assert!((side - 0.5).abs() < 0.05);
+        assert!(down < 0.1);
+    }
+
+    #[test]
+    pub fn test_bake_light_probes() {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+
+        // A red-ish lit floor under the probes..
+        world.spawn((
+            Collider::new(SharedShape::cuboid(10., 0.1, 10.)),
+            GlobalTransform(Affine3A::from_translation(Vec3::new(0., -0.1, 0.))),
+        ));
+        physics_system_inner(&mut physics_context, &mut world);
+        let lights = [Light::new_directional(
+            -Vec3::Y,
+            5.,
+            Vec3::new(1., 0.2, 0.2),
+        )];
+        let settings = LightBakeSettings {
+            min: Vec3::new(0., 1., 0.),
+            max: Vec3::new(1., 2., 0.),
+            samples_per_probe: 128,
+            sky_color: Vec3::new(0., 0., 1.),
+            ground_color: Vec3::ZERO,
+            ..Default::default()
+        };
+        let grid = bake_light_probes(&world, &physics_context, &lights, &settings);
+        assert_eq!(grid.size, UVec3::new(2, 2, 1));
+        assert_eq!(grid.probes.len(), 4);
+
+        // ..lights things from below, and the blue sky lights them from above.
+        let probe = grid.sample(Vec3::new(0.5, 1.5, 0.));
+        let from_below = probe.evaluate(-Vec3::Y);
+        let from_above = probe.evaluate(Vec3::Y);
+        assert!(from_below.x > from_below.z);
+        assert!(from_above.z > from_above.x);
+
+        // Grids can be stored with the level.
+        let json = serde_json::to_string(&grid).unwrap();
+        let loaded: LightProbeGrid = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, grid);
+
+        // Outside the grid, the nearest probes are used.
+        assert_eq!(grid.sample(Vec3::new(-10., 0., 0.)), grid.probes[0]);
+    }
+}
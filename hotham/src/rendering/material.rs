@@ -1,8 +1,8 @@
-use glam::Vec4;
+use glam::{Vec2, Vec3, Vec4};
 use gltf::Material as MaterialData;
 
 use crate::{
-    asset_importer::ImportContext,
+    asset_importer::{vrm::approximate_mtoon, ImportContext},
     rendering::texture::{Texture, TextureUsage, NO_TEXTURE},
 };
 
@@ -41,6 +41,19 @@ pub struct Material {
     pub alpha_mask: f32,
     /// Alpha mask cutoff - see fragment shader
     pub alpha_mask_cutoff: f32,
+    /// How far the texture coordinates scroll each second, for flowing water, conveyor belts and the like
+    pub uv_scroll: Vec2,
+    /// How many columns of frames the flipbook texture has
+    pub flipbook_columns: u32,
+    /// How many rows of frames the flipbook texture has
+    pub flipbook_rows: u32,
+    /// How many of the flipbook's frames to play, left to right and then top to bottom. The flipbook is off unless
+    /// this is more than 1.
+    pub flipbook_frame_count: u32,
+    /// How many flipbook frames to show each second
+    pub flipbook_fps: f32,
+    /// The color of the light emitted by the material, multiplied with the emissive texture if there is one
+    pub emissive_factor: Vec3,
 }
 
 impl Default for Material {
@@ -116,6 +129,7 @@ impl Material {
             .unwrap_or(NO_TEXTURE);
 
         // Factors
+        let emissive_factor = Vec3::from(material.emissive_factor());
         let metallic_factor = pbr_metallic_roughness.metallic_factor();
         let roughness_factor = pbr_metallic_roughness.roughness_factor();
 
@@ -135,7 +149,10 @@ impl Material {
         };
 
         // Collect the material properties.
-        let material = Material {
+        let is_mtoon = material.index().map_or(false, |index| {
+            import_context.mtoon_materials.contains(&index)
+        });
+        let mut material = Material {
             base_color_factor,
             workflow,
             base_color_texture_set,
@@ -147,7 +164,12 @@ impl Material {
             roughness_factor,
             alpha_mask,
             alpha_mask_cutoff,
+            emissive_factor,
+            ..Default::default()
         };
+        if is_mtoon {
+            approximate_mtoon(&mut material);
+        }
 
         // Then push it into the materials buffer
         unsafe {
@@ -182,6 +204,75 @@ impl Material {
             roughness_factor: 1.0,
             alpha_mask: Default::default(),
             alpha_mask_cutoff: Default::default(),
+            uv_scroll: Vec2::ZERO,
+            flipbook_columns: 1,
+            flipbook_rows: 1,
+            flipbook_frame_count: 0,
+            flipbook_fps: 0.,
+            emissive_factor: Vec3::ZERO,
         }
     }
+
+    /// Play the textures as a flipbook, laid out in a grid of `columns` by `rows`, showing `frame_count` frames at
+    /// `fps` frames per second.
+    pub fn with_flipbook(self, columns: u32, rows: u32, frame_count: u32, fps: f32) -> Self {
+        Self {
+            flipbook_columns: columns,
+            flipbook_rows: rows,
+            flipbook_frame_count: frame_count,
+            flipbook_fps: fps,
+            ..self
+        }
+    }
+
+    /// Scroll the textures by `uv_scroll` texture coordinates per second
+    pub fn with_uv_scroll(self, uv_scroll: Vec2) -> Self {
+        Self { uv_scroll, ..self }
+    }
+
+    /// Where the vertex shader will sample the textures for `uv`, `time` seconds after the renderer was created.
+    /// The flipbook picks the frame, then the coordinates are scrolled.
+    pub fn animate_uv(&self, uv: Vec2, time: f32) -> Vec2 {
+        let mut uv = uv;
+        if self.flipbook_frame_count > 1 && self.flipbook_columns > 0 && self.flipbook_rows > 0 {
+            let frame = (time * self.flipbook_fps) as u32 % self.flipbook_frame_count;
+            let cell = Vec2::new(
+                (frame % self.flipbook_columns) as f32,
+                (frame / self.flipbook_columns) as f32,
+            );
+            uv = (uv + cell) / Vec2::new(self.flipbook_columns as f32, self.flipbook_rows as f32);
+        }
+        uv + self.uv_scroll * time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_animate_uv() {
+        // Materials don't move unless they're asked to..
+        let material = Material::default();
+        assert_eq!(
+            material.animate_uv(Vec2::new(0.5, 0.5), 10.),
+            Vec2::new(0.5, 0.5)
+        );
+
+        // ..scrolling moves the texture coordinates along over time..
+        let material = Material::default().with_uv_scroll(Vec2::new(0.25, 0.));
+        assert_relative_eq!(
+            material.animate_uv(Vec2::new(0.5, 0.5), 2.),
+            Vec2::new(1.0, 0.5)
+        );
+
+        // ..and flipbooks step through the cells of the grid, looping back to the start.
+        let material = Material::default().with_flipbook(4, 2, 6, 2.);
+        let frame_centre = |time| material.animate_uv(Vec2::new(0.5, 0.5), time);
+        assert_relative_eq!(frame_centre(0.), Vec2::new(0.125, 0.25));
+        assert_relative_eq!(frame_centre(0.6), Vec2::new(0.375, 0.25));
+        assert_relative_eq!(frame_centre(2.1), Vec2::new(0.125, 0.75));
+        assert_relative_eq!(frame_centre(3.1), Vec2::new(0.125, 0.25));
+    }
 }
@@ -1,10 +1,55 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::contexts::VulkanContext;
 use ash::vk;
 
+/// How many bytes of device memory Hotham has allocated and not yet freed.
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// How many allocations of device memory Hotham has made and not yet freed.
+static ALLOCATION_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// How much device memory is currently allocated by Hotham, as `(bytes, allocations)`. Memory allocated by the
+/// OpenXR runtime, eg. for the swapchain, isn't included.
+pub fn allocated_device_memory() -> (u64, u64) {
+    (
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+        ALLOCATION_COUNT.load(Ordering::Relaxed),
+    )
+}
+
+/// Record that `size` bytes of device memory have been allocated.
+pub(crate) fn track_allocation(size: vk::DeviceSize) {
+    ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed);
+    ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record that an allocation of `size` bytes of device memory has been freed.
+pub(crate) fn track_free(size: vk::DeviceSize) {
+    ALLOCATED_BYTES.fetch_sub(size, Ordering::Relaxed);
+    ALLOCATION_COUNT.fetch_sub(1, Ordering::Relaxed);
+}
+
 pub(crate) unsafe fn allocate_memory(
     vulkan_context: &VulkanContext,
     memory_requirements: vk::MemoryRequirements,
     memory_property_flags: vk::MemoryPropertyFlags,
+) -> vk::DeviceMemory {
+    allocate_memory_with_flags(
+        vulkan_context,
+        memory_requirements,
+        memory_property_flags,
+        vk::MemoryAllocateFlags::empty(),
+    )
+}
+
+/// Like [`allocate_memory`], with extra allocation flags - eg. `DEVICE_ADDRESS` for buffers that shaders or
+/// acceleration structure builds find by address.
+pub(crate) unsafe fn allocate_memory_with_flags(
+    vulkan_context: &VulkanContext,
+    memory_requirements: vk::MemoryRequirements,
+    memory_property_flags: vk::MemoryPropertyFlags,
+    memory_allocate_flags: vk::MemoryAllocateFlags,
 ) -> vk::DeviceMemory {
     let instance = &vulkan_context.instance;
     let device = &vulkan_context.device;
@@ -14,14 +59,16 @@ pub(crate) unsafe fn allocate_memory(
     let memory_properties = instance.get_physical_device_memory_properties(physical_device);
     let memory_type_index =
         find_memory_type_index(memory_properties, memory_type_bits, memory_property_flags);
-    device
-        .allocate_memory(
-            &vk::MemoryAllocateInfo::builder()
-                .allocation_size(memory_requirements.size)
-                .memory_type_index(memory_type_index as _),
-            None,
-        )
-        .unwrap()
+    let mut flags_info = vk::MemoryAllocateFlagsInfo::builder().flags(memory_allocate_flags);
+    let mut allocate_info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(memory_requirements.size)
+        .memory_type_index(memory_type_index as _);
+    if !memory_allocate_flags.is_empty() {
+        allocate_info = allocate_info.push_next(&mut flags_info);
+    }
+    let device_memory = device.allocate_memory(&allocate_info, None).unwrap();
+    track_allocation(memory_requirements.size);
+    device_memory
 }
 
 fn find_memory_type_index(
@@ -40,7 +40,71 @@ pub mod resources;
 /// Data to instruct the renderer how a primitive should look
 pub mod material;
 
+/// Screenshot tests for the renderer
+#[cfg(test)]
+pub(crate) mod golden;
+
+/// A smoothed camera for spectators, rendered separately from the headset
+pub mod spectator;
+
+/// Ordering GPU work with timeline semaphores
+pub mod timeline;
+
 /// Lights and related functionality
 pub mod light;
+
+/// Volumetric fog, lit by the scene's lights
+pub mod fog;
+
+/// Glare sprites drawn over bright lights
+pub mod lens_flare;
+
+/// Narrowing the view during artificial motion, for comfort
+pub mod vignette;
+
+/// Drawing and sampling shadow maps for directional lights and spotlights
+pub mod shadows;
+
+/// Sharing a shadow map texture between lights, and budgeting how many shadow maps are drawn each frame
+pub mod shadow_atlas;
+
+/// Cascaded shadow maps for the sun
+pub mod shadow_cascades;
+
+/// Baking ambient light into a grid of probes, to light dynamic objects
+pub mod light_probes;
+
+/// Drawing crowds of skinned meshes, animated on the GPU
+pub mod crowd;
+
+/// Drawing many copies of a mesh with one instanced draw per primitive
+pub mod instancing;
+
+/// A compact description of the scene's static geometry, and acceleration structures built from it
+pub mod scene_description;
+
+/// Ray traced contact shadows and ambient occlusion, using ray queries
+pub mod ray_query;
+
 /// Wrapper around geometry data.
 pub mod mesh_data;
+/// Flat, textured quads for markers, icons and HUD elements
+pub mod sprite;
+
+/// Reading rendered data back from the GPU without stalling it
+pub mod readback;
+
+/// A per-frame buffer for data that changes every draw, like app material parameters
+pub mod ring_buffer;
+
+/// Compute shaders written by apps, run each frame before the world is drawn
+pub mod compute;
+
+/// Rounded, 9-sliced meshes for the backs of UI panels
+pub mod panel_background;
+
+/// Finding the entity under a ray by drawing entity IDs on the GPU
+pub mod picking;
+
+/// Color blindness filters, outlines around interactables and larger text
+pub mod accessibility;
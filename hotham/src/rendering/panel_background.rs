@@ -0,0 +1,311 @@
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Vec2, Vec3};
+
+use crate::{
+    components::Mesh,
+    contexts::RenderContext,
+    rendering::{mesh_data::MeshData, primitive::Primitive, vertex::Vertex},
+};
+
+/// Which way each corner is from the middle of the panel, counter-clockwise from the top right
+const CORNER_SIGNS: [[f32; 2]; 4] = [[1., 1.], [-1., 1.], [-1., -1.], [1., -1.]];
+
+/// A mesh for the back of a UI panel: a rounded rectangle with a bevelled front edge and some depth, so panels look
+/// finished without a model made for each one. Put the [`crate::components::Panel`] just in front of it.
+///
+/// The front faces +Z. Its texture coordinates are 9-sliced: the outer `border` metres of each side show the outer
+/// `border_uv` of the texture, so a frame drawn into the texture's edges keeps its size whatever size the panel is, and
+/// only the middle is stretched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanelBackground {
+    /// Width and height of the background, in metres
+    pub size: Vec2,
+    /// Radius of the rounded corners, in metres. Can't be any bigger than `border`.
+    pub corner_radius: f32,
+    /// How many triangles make up each rounded corner
+    pub corner_segments: u32,
+    /// How wide the part of each side that isn't stretched is, in metres
+    pub border: f32,
+    /// How much of the texture, on each side, is the border, from 0 to 0.5
+    pub border_uv: f32,
+    /// How thick the background is, in metres. Zero makes it flat, with nothing behind the front.
+    pub depth: f32,
+    /// How much of the front edge is bevelled, in metres. Can't be any bigger than `corner_radius` or `depth`.
+    pub bevel: f32,
+}
+
+impl Default for PanelBackground {
+    fn default() -> Self {
+        Self {
+            size: [0.6, 0.4].into(),
+            corner_radius: 0.03,
+            corner_segments: 6,
+            border: 0.04,
+            border_uv: 0.25,
+            depth: 0.01,
+            bevel: 0.005,
+        }
+    }
+}
+
+impl PanelBackground {
+    /// Create a mesh of the background, drawn with `material_id`
+    pub fn create_mesh(&self, render_context: &mut RenderContext, material_id: u32) -> Mesh {
+        let (vertices, indices) = self.geometry();
+        let primitive = Primitive::new(&vertices, &indices, material_id, render_context);
+        Mesh::new(MeshData::new(vec![primitive]), render_context)
+    }
+
+    /// Build the background's vertices and indices, eg. to make a mesh with more than one primitive
+    pub fn geometry(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let half_size = (self.size * 0.5).max(Vec2::ZERO);
+        let border = self.border.clamp(0., half_size.min_element());
+        let radius = self.corner_radius.clamp(0., border);
+        let depth = self.depth.max(0.);
+        let bevel = self.bevel.clamp(0., radius.min(depth));
+        // Square corners need two points so their edges stay sharp, but no more.
+        let segments = if radius > 0. {
+            self.corner_segments.max(1)
+        } else {
+            1
+        };
+
+        let mut builder = Builder {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            half_size,
+            border,
+            border_uv: self.border_uv.clamp(0., 0.5),
+        };
+        let outline_at = |inset: f32| outline(half_size, radius, inset, segments);
+
+        // The front, in nine parts so the texture stretches like a 9-slice..
+        let front_size = half_size - Vec2::splat(bevel);
+        let inner = half_size - Vec2::splat(border);
+        let front = outline_at(bevel);
+        let inner_corner = |corner: usize| Vec2::from(CORNER_SIGNS[corner]) * inner;
+        let arc_length = segments as usize + 1;
+        builder.polygon(
+            &[0, 1, 2, 3].map(|corner| inner_corner((corner + 2) % 4)),
+            0.,
+        );
+        for (corner, sign) in CORNER_SIGNS.iter().enumerate() {
+            let sign = Vec2::from(*sign);
+            let (start, end) = if corner % 2 == 0 {
+                ((front_size.x, inner.y), (inner.x, front_size.y))
+            } else {
+                ((inner.x, front_size.y), (front_size.x, inner.y))
+            };
+            let (start, end) = (sign * Vec2::from(start), sign * Vec2::from(end));
+
+            let mut polygon = vec![inner_corner(corner), start];
+            polygon.extend(
+                front[corner * arc_length..(corner + 1) * arc_length]
+                    .iter()
+                    .map(|(position, _)| *position),
+            );
+            polygon.push(end);
+            builder.polygon(&polygon, 0.);
+
+            // ..with a strip along the side that comes after each corner..
+            let next = (corner + 1) % 4;
+            let next_start = if next % 2 == 0 {
+                (front_size.x, inner.y)
+            } else {
+                (inner.x, front_size.y)
+            };
+            let next_start = Vec2::from(CORNER_SIGNS[next]) * Vec2::from(next_start);
+            builder.polygon(
+                &[inner_corner(corner), end, next_start, inner_corner(next)],
+                0.,
+            );
+        }
+
+        // ..the bevel, sloping back to the full size..
+        let back = outline_at(0.);
+        if bevel > 0. {
+            builder.band(&front, 0., &back, -bevel, 1.);
+        }
+
+        // ..and the sides and the back.
+        if depth > bevel {
+            builder.band(&back, -bevel, &back, -depth, 0.);
+        }
+        if depth > 0. {
+            let mut back = back
+                .iter()
+                .map(|(position, _)| *position)
+                .collect::<Vec<_>>();
+            back.reverse();
+            builder.polygon(&back, -depth);
+        }
+
+        (builder.vertices, builder.indices)
+    }
+}
+
+/// The points around a rounded rectangle with `half_size`, inset by `inset`, counter-clockwise from the start of the
+/// top right corner, with the direction each one faces. Each corner has `segments + 1` points.
+fn outline(half_size: Vec2, radius: f32, inset: f32, segments: u32) -> Vec<(Vec2, Vec2)> {
+    let mut points = Vec::new();
+    for (corner, sign) in CORNER_SIGNS.iter().enumerate() {
+        let centre = Vec2::from(*sign) * (half_size - Vec2::splat(radius));
+        for segment in 0..=segments {
+            let angle = (corner as f32 + segment as f32 / segments as f32) * FRAC_PI_2;
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            points.push((centre + direction * (radius - inset), direction));
+        }
+    }
+    points
+}
+
+/// Map a point `distance` along a side `length` long to a texture coordinate, keeping the `border` at each end the size
+/// it is in the texture, where it's `border_uv` wide
+fn nine_slice(distance: f32, length: f32, border: f32, border_uv: f32) -> f32 {
+    let middle = length - 2. * border;
+    if border <= 0. {
+        distance / length
+    } else if distance <= border {
+        distance / border * border_uv
+    } else if distance >= length - border {
+        1. - (length - distance) / border * border_uv
+    } else {
+        border_uv + (distance - border) / middle * (1. - 2. * border_uv)
+    }
+}
+
+struct Builder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    half_size: Vec2,
+    border: f32,
+    border_uv: f32,
+}
+
+impl Builder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3) -> u32 {
+        let size = self.half_size * 2.;
+        let texture_coords = Vec2::new(
+            nine_slice(
+                position.x + self.half_size.x,
+                size.x,
+                self.border,
+                self.border_uv,
+            ),
+            nine_slice(
+                self.half_size.y - position.y,
+                size.y,
+                self.border,
+                self.border_uv,
+            ),
+        );
+        self.vertices.push(Vertex {
+            position,
+            normal,
+            texture_coords,
+            ..Default::default()
+        });
+        (self.vertices.len() - 1) as _
+    }
+
+    /// Add a convex polygon at `z`, fanned out from its first point. Counter-clockwise polygons face +Z, and clockwise
+    /// ones face -Z.
+    fn polygon(&mut self, points: &[Vec2], z: f32) {
+        let normal = if z < 0. { Vec3::NEG_Z } else { Vec3::Z };
+        let first = self.vertices.len() as u32;
+        for point in points {
+            self.vertex(point.extend(z), normal);
+        }
+        for index in 1..points.len().saturating_sub(1) as u32 {
+            self.indices
+                .extend_from_slice(&[first, first + index, first + index + 1]);
+        }
+    }
+
+    /// Join the outline `near`, at `near_z`, to the outline `far`, further back at `far_z`, facing outwards and tilted
+    /// forwards by `normal_z`.
+    fn band(
+        &mut self,
+        near: &[(Vec2, Vec2)],
+        near_z: f32,
+        far: &[(Vec2, Vec2)],
+        far_z: f32,
+        normal_z: f32,
+    ) {
+        let first = self.vertices.len() as u32;
+        for ((near, direction), (far, _)) in near.iter().zip(far) {
+            let normal = direction.extend(normal_z).normalize();
+            self.vertex(near.extend(near_z), normal);
+            self.vertex(far.extend(far_z), normal);
+        }
+
+        let count = near.len() as u32;
+        for index in 0..count {
+            let next = (index + 1) % count;
+            let (a, d) = (first + index * 2, first + index * 2 + 1);
+            let (b, c) = (first + next * 2, first + next * 2 + 1);
+            self.indices.extend_from_slice(&[a, d, c, a, c, b]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f32::consts::PI;
+
+    #[test]
+    pub fn test_nine_slice() {
+        // The border keeps its size in the texture..
+        assert_relative_eq!(nine_slice(0., 2., 0.1, 0.25), 0.);
+        assert_relative_eq!(nine_slice(0.05, 2., 0.1, 0.25), 0.125);
+        assert_relative_eq!(nine_slice(0.1, 2., 0.1, 0.25), 0.25);
+        assert_relative_eq!(nine_slice(1.95, 2., 0.1, 0.25), 0.875);
+        assert_relative_eq!(nine_slice(2., 2., 0.1, 0.25), 1.);
+
+        // ..and the middle is stretched.
+        assert_relative_eq!(nine_slice(1., 2., 0.1, 0.25), 0.5);
+        assert_relative_eq!(nine_slice(0.55, 2., 0.1, 0.25), 0.375);
+    }
+
+    #[test]
+    pub fn test_panel_background_geometry() {
+        let background = PanelBackground {
+            corner_segments: 64,
+            ..Default::default()
+        };
+        let (vertices, indices) = background.geometry();
+        let triangle = |i: &[u32]| i.iter().map(|i| vertices[*i as usize]).collect::<Vec<_>>();
+
+        // Every triangle faces the way its normals do..
+        let mut front_area = 0.;
+        for triangle in indices.chunks(3).map(triangle) {
+            let cross = (triangle[1].position - triangle[0].position)
+                .cross(triangle[2].position - triangle[0].position);
+            assert!(cross.dot(triangle[0].normal) >= 0.);
+            if triangle[0].normal == Vec3::Z {
+                front_area += cross.length() * 0.5;
+            }
+        }
+
+        // ..and the front is the size of the panel, less its bevel and rounded corners.
+        let front = background.size - Vec2::splat(background.bevel * 2.);
+        let radius = background.corner_radius - background.bevel;
+        let expected_area = front.x * front.y - (4. - PI) * radius * radius;
+        assert_relative_eq!(front_area, expected_area, epsilon = 0.0001);
+
+        // Everything is within the panel, and the texture covers the front.
+        for vertex in &vertices {
+            assert!(vertex.position.x.abs() <= 0.3 + f32::EPSILON);
+            assert!(vertex.position.y.abs() <= 0.2 + f32::EPSILON);
+            assert!((-0.01..=0.).contains(&vertex.position.z));
+        }
+        let top_left = vertices
+            .iter()
+            .map(|v| v.texture_coords)
+            .fold(Vec2::ONE, Vec2::min);
+        assert!(top_left.x < 0.01 && top_left.y < 0.01);
+    }
+}
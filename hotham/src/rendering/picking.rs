@@ -0,0 +1,606 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::size_of,
+    slice::from_ref as slice_from_ref,
+};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, Mat4, Vec2, Vec3};
+use openxr as xr;
+use vk_shader_macros::include_glsl;
+
+use crate::{
+    contexts::{
+        render_context::{create_push_constant, create_shader, DrawBatch, InstancedPrimitive},
+        VulkanContext,
+    },
+    rendering::{
+        image::Image,
+        readback::{Readback, ReadbackId},
+        vertex::Vertex,
+    },
+    util::affine_from_posef,
+    DEPTH_FORMAT,
+};
+
+static PICKING_VERT: &[u32] = include_glsl!("src/shaders/picking.vert", target: vulkan1_1);
+static PICKING_FRAG: &[u32] = include_glsl!("src/shaders/picking.frag", target: vulkan1_1);
+
+/// The entity ID given to instances that don't belong to an entity, so can't be picked.
+pub const NO_ENTITY: u32 = u32::MAX;
+
+/// The width and height of the image the picking ray is drawn into, in pixels. Odd, so there's a pixel in the middle.
+pub const PICKING_RESOLUTION: u32 = 9;
+
+const PICKING_FORMAT: vk::Format = vk::Format::R32_UINT;
+
+/// How close to the ray's origin things can be picked, in meters.
+const NEAR: f32 = 0.01;
+
+/// A ray to find the entity under, in global space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickRay {
+    /// Where the ray starts
+    pub origin: Vec3,
+    /// Which way it points
+    pub direction: Vec3,
+}
+
+impl PickRay {
+    /// A ray from `origin` towards `direction`.
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    /// A ray pointing forward (-Z) from `global_from_local`, eg. a controller's [`crate::components::GlobalTransform`].
+    pub fn from_transform(global_from_local: &Affine3A) -> Self {
+        Self {
+            origin: global_from_local.translation.into(),
+            direction: global_from_local.transform_vector3(Vec3::NEG_Z),
+        }
+    }
+
+    /// A ray through `point` on the image drawn for `view`, which is in stage space. `point` runs from (0, 0) at the
+    /// top left of the image to (1, 1) at the bottom right.
+    pub fn through_view(view: &xr::View, global_from_stage: &Affine3A, point: Vec2) -> Self {
+        let fov = view.fov;
+        let x = lerp(fov.angle_left.tan(), fov.angle_right.tan(), point.x);
+        let y = lerp(fov.angle_up.tan(), fov.angle_down.tan(), point.y);
+        let global_from_view = *global_from_stage * affine_from_posef(view.pose);
+        Self {
+            origin: global_from_view.translation.into(),
+            direction: global_from_view.transform_vector3(Vec3::new(x, y, -1.)),
+        }
+    }
+
+    /// Looking down the ray with a field of view of `cone_angle`, in globally oriented stage space.
+    fn view_projection(&self, gos_from_global: &Affine3A, cone_angle: f32) -> Mat4 {
+        let origin = gos_from_global.transform_point3(self.origin);
+        let direction = gos_from_global
+            .transform_vector3(self.direction)
+            .normalize_or_zero();
+        let up = if direction.y.abs() > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let view = Mat4::look_at_rh(origin, origin + direction, up);
+        Mat4::perspective_infinite_rh(cone_angle, 1., NEAR) * view
+    }
+}
+
+/// Identifies a pick requested with [`crate::contexts::RenderContext::pick`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PickId(u64);
+
+/// Finds the entity under a ray by drawing the entities' IDs down it, for selecting things that are too small or
+/// too close together to pick reliably with physics raycasts.
+///
+/// Picks are drawn one per frame, after the world, into a tiny image looking down the ray. Each pixel holds the ID of
+/// the closest entity drawn there, and is read back with [`Readback`] a frame or two later. If nothing is under the
+/// middle of the image, the closest entity to it within [`Picking::cone_angle`] is picked, so small things don't need
+/// to be hit exactly.
+///
+/// Only entities that were drawn for the headset can be picked - anything outside its view has already been culled.
+/// Turn it on with [`crate::contexts::RenderContext::enable_picking`].
+pub struct Picking {
+    /// How wide a cone around the ray to look for entities in, in radians
+    pub cone_angle: f32,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    image: Image,
+    depth_image: Image,
+    framebuffer: vk::Framebuffer,
+    next_id: u64,
+    pending: VecDeque<(PickId, PickRay)>,
+    prepared: Option<(PickId, Mat4)>,
+    drawn: Option<PickId>,
+    in_flight: Vec<(PickId, ReadbackId)>,
+    results: HashMap<PickId, Option<u32>>,
+}
+
+impl Picking {
+    pub(crate) fn new(
+        vulkan_context: &VulkanContext,
+        set_layout: vk::DescriptorSetLayout,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let extent = vk::Extent2D {
+            width: PICKING_RESOLUTION,
+            height: PICKING_RESOLUTION,
+        };
+        let image = vulkan_context.create_image(
+            PICKING_FORMAT,
+            &extent,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            1,
+            1,
+        )?;
+        let depth_image = vulkan_context.create_image(
+            DEPTH_FORMAT,
+            &extent,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            1,
+            1,
+        )?;
+        let render_pass = create_render_pass(vulkan_context)?;
+        let attachments = [image.view, depth_image.view];
+        let framebuffer = unsafe {
+            device.create_framebuffer(
+                &vk::FramebufferCreateInfo::builder()
+                    .render_pass(render_pass)
+                    .attachments(&attachments)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .layers(1),
+                None,
+            )
+        }?;
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(size_of::<Mat4>() as _)
+            .build();
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(slice_from_ref(&set_layout))
+                    .push_constant_ranges(slice_from_ref(&push_constant_range)),
+                None,
+            )
+        }?;
+        let pipeline = create_pipeline(vulkan_context, pipeline_layout, render_pass, extent)?;
+
+        Ok(Self {
+            cone_angle: 2_f32.to_radians(),
+            pipeline,
+            pipeline_layout,
+            render_pass,
+            image,
+            depth_image,
+            framebuffer,
+            next_id: 0,
+            pending: Default::default(),
+            prepared: None,
+            drawn: None,
+            in_flight: Vec::new(),
+            results: Default::default(),
+        })
+    }
+
+    /// Queue up a pick down `ray`.
+    pub(crate) fn request(&mut self, ray: PickRay) -> PickId {
+        let id = PickId(self.next_id);
+        self.next_id += 1;
+        self.pending.push_back((id, ray));
+        id
+    }
+
+    /// Take the [`hecs::Entity::id`] of the entity `id` found, if it's arrived. `Some(None)` means nothing was there.
+    pub(crate) fn poll(&mut self, id: PickId, readback: &mut Readback) -> Option<Option<u32>> {
+        self.in_flight.retain(|&(pick_id, readback_id)| {
+            match readback.poll(readback_id) {
+                Some(data) => {
+                    let ids: Vec<u32> = data
+                        .bytes
+                        .chunks_exact(4)
+                        .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect();
+                    self.results
+                        .insert(pick_id, nearest_to_middle(&ids, PICKING_RESOLUTION));
+                    false
+                }
+                // If the readback has gone missing, eg. because the device was lost, nothing was found.
+                None if !readback.is_pending(readback_id) => {
+                    self.results.insert(pick_id, None);
+                    false
+                }
+                None => true,
+            }
+        });
+        self.results.remove(&id)
+    }
+
+    /// Get the next pick ready to be drawn this frame.
+    pub(crate) fn prepare(&mut self, gos_from_global: &Affine3A) {
+        self.prepared = self
+            .pending
+            .pop_front()
+            .map(|(id, ray)| (id, ray.view_projection(gos_from_global, self.cone_angle)));
+    }
+
+    /// Draw the pick prepared this frame, if there is one. Must be outside the PBR render pass, with the frame's draw
+    /// data in `descriptor_set`.
+    pub(crate) unsafe fn draw(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        vertex_buffer: vk::Buffer,
+        index_buffer: vk::Buffer,
+        draw_batches: &[DrawBatch],
+        primitive_map: &HashMap<u32, InstancedPrimitive>,
+    ) {
+        let (id, view_projection) = match self.prepared.take() {
+            Some(prepared) => prepared,
+            None => return,
+        };
+
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue { uint32: [0; 4] },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        device.cmd_begin_render_pass(
+            command_buffer,
+            &vk::RenderPassBeginInfo::builder()
+                .render_pass(self.render_pass)
+                .framebuffer(self.framebuffer)
+                .render_area(vk::Rect2D {
+                    offset: Default::default(),
+                    extent: self.image.extent,
+                })
+                .clear_values(&clear_values),
+            vk::SubpassContents::INLINE,
+        );
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            slice_from_ref(&descriptor_set),
+            &[],
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            self.pipeline_layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            create_push_constant(&view_projection),
+        );
+        device.cmd_bind_index_buffer(command_buffer, index_buffer, 0, vk::IndexType::UINT32);
+        device.cmd_bind_vertex_buffers(command_buffer, 0, slice_from_ref(&vertex_buffer), &[0]);
+
+        for batch in draw_batches {
+            let primitive = &primitive_map.get(&batch.primitive_id).unwrap().primitive;
+            device.cmd_draw_indexed(
+                command_buffer,
+                primitive.indices_count,
+                batch.instance_count,
+                primitive.index_buffer_offset,
+                primitive.vertex_buffer_offset as _,
+                batch.instance_offset,
+            );
+        }
+        device.cmd_end_render_pass(command_buffer);
+        self.drawn = Some(id);
+    }
+
+    /// Read back the pick drawn this frame, if there is one. Must be called after the frame has been submitted.
+    pub(crate) fn read_back(&mut self, vulkan_context: &VulkanContext, readback: &mut Readback) {
+        let id = match self.drawn.take() {
+            Some(id) => id,
+            None => return,
+        };
+        match readback.read_image(
+            vulkan_context,
+            &self.image,
+            0,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        ) {
+            Ok(readback_id) => self.in_flight.push((id, readback_id)),
+            Err(e) => {
+                log::error!(
+                    "[HOTHAM_PICKING] Unable to read back pick {:?}: {:?}",
+                    id,
+                    e
+                );
+                self.results.insert(id, None);
+            }
+        }
+    }
+
+    /// Free everything. The GPU must be idle.
+    pub(crate) unsafe fn destroy(&self, device: &ash::Device) {
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.pipeline_layout, None);
+        device.destroy_framebuffer(self.framebuffer, None);
+        device.destroy_render_pass(self.render_pass, None);
+        for image in [&self.image, &self.depth_image] {
+            device.destroy_image_view(image.view, None);
+            device.destroy_image(image.handle, None);
+            device.free_memory(image.device_memory, None);
+        }
+    }
+}
+
+/// The entity ID in `ids`, a `size` by `size` picking image, that's closest to the middle. Pixels hold the ID plus one,
+/// so zero is nothing.
+fn nearest_to_middle(ids: &[u32], size: u32) -> Option<u32> {
+    let middle = (size / 2) as i32;
+    ids.iter()
+        .enumerate()
+        .filter(|(_, &id)| id != 0)
+        .min_by_key(|(i, _)| {
+            let x = (*i as u32 % size) as i32 - middle;
+            let y = (*i as u32 / size) as i32 - middle;
+            x * x + y * y
+        })
+        .map(|(_, &id)| id - 1)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn create_render_pass(vulkan_context: &VulkanContext) -> Result<vk::RenderPass> {
+    let color_attachment = vk::AttachmentDescription::builder()
+        .format(PICKING_FORMAT)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .build();
+    let depth_attachment = vk::AttachmentDescription::builder()
+        .format(DEPTH_FORMAT)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        .build();
+
+    let color_reference = vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        .build();
+    let depth_reference = vk::AttachmentReference::builder()
+        .attachment(1)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        .build();
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(slice_from_ref(&color_reference))
+        .depth_stencil_attachment(&depth_reference)
+        .build();
+
+    // Wait for the last pick to be copied out before drawing over it, and for this one to be drawn before copying it.
+    let dependencies = [
+        vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            )
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_stage_mask(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            )
+            .dst_access_mask(
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .build(),
+    ];
+
+    let attachments = [color_attachment, depth_attachment];
+    let render_pass = unsafe {
+        vulkan_context.device.create_render_pass(
+            &vk::RenderPassCreateInfo::builder()
+                .attachments(&attachments)
+                .subpasses(slice_from_ref(&subpass))
+                .dependencies(&dependencies),
+            None,
+        )
+    }?;
+    Ok(render_pass)
+}
+
+fn create_pipeline(
+    vulkan_context: &VulkanContext,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+) -> Result<vk::Pipeline> {
+    let device = &vulkan_context.device;
+    let (vertex_shader, vertex_stage) =
+        create_shader(PICKING_VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
+    let (fragment_shader, fragment_stage) =
+        create_shader(PICKING_FRAG, vk::ShaderStageFlags::FRAGMENT, vulkan_context)?;
+    let stages = [vertex_stage, fragment_stage];
+
+    let vertex_binding_description = vk::VertexInputBindingDescription::builder()
+        .binding(0)
+        .stride(size_of::<Vertex>() as _)
+        .input_rate(vk::VertexInputRate::VERTEX)
+        .build();
+    let vertex_attribute_descriptions = Vertex::attribute_descriptions();
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_attribute_descriptions(&vertex_attribute_descriptions)
+        .vertex_binding_descriptions(slice_from_ref(&vertex_binding_description));
+    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let render_area = vk::Rect2D {
+        offset: Default::default(),
+        extent,
+    };
+    let viewport = vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: extent.width as _,
+        height: extent.height as _,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    };
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewports(slice_from_ref(&viewport))
+        .scissors(slice_from_ref(&render_area));
+
+    // The picking projection isn't flipped like the headset's, so the winding is reversed. Draw both sides instead.
+    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .line_width(1.0);
+    let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+    let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(vk::CompareOp::LESS);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::R)
+        .blend_enable(false)
+        .build();
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .attachments(slice_from_ref(&color_blend_attachment));
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_state)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterization_state)
+        .multisample_state(&multisample_state)
+        .depth_stencil_state(&depth_stencil_state)
+        .color_blend_state(&color_blend_state)
+        .layout(pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0)
+        .build();
+
+    let pipelines = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            slice_from_ref(&create_info),
+            None,
+        )
+    }
+    .map_err(|(_, r)| r)?;
+
+    unsafe {
+        device.destroy_shader_module(vertex_shader, None);
+        device.destroy_shader_module(fragment_shader, None);
+    }
+
+    Ok(pipelines[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::posef_from_affine;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_view_projection() {
+        let gos_from_global = Affine3A::from_translation(Vec3::new(0., -1., 0.));
+        let ray = PickRay::new(Vec3::new(1., 2., 0.), Vec3::new(0., 0., -2.));
+        let view_projection = ray.view_projection(&gos_from_global, 2_f32.to_radians());
+
+        // Points along the ray are in the middle of the picking image, and further away is deeper..
+        let near = view_projection.project_point3(Vec3::new(1., 1., -1.));
+        let far = view_projection.project_point3(Vec3::new(1., 1., -10.));
+        assert_relative_eq!(near.x, 0., epsilon = 0.0001);
+        assert_relative_eq!(near.y, 0., epsilon = 0.0001);
+        assert_relative_eq!(far.x, 0., epsilon = 0.0001);
+        assert!(near.z < far.z && far.z < 1.);
+
+        // ..while things just off it are out of view.
+        let beside = view_projection.project_point3(Vec3::new(1.1, 1., -1.));
+        assert!(beside.x.abs() > 1.);
+
+        // Looking straight down still works.
+        let ray = PickRay::new(Vec3::Y, Vec3::NEG_Y);
+        let view_projection = ray.view_projection(&Affine3A::IDENTITY, 2_f32.to_radians());
+        let below = view_projection.project_point3(Vec3::ZERO);
+        assert_relative_eq!(below.x, 0., epsilon = 0.0001);
+        assert_relative_eq!(below.y, 0., epsilon = 0.0001);
+    }
+
+    #[test]
+    pub fn test_nearest_to_middle() {
+        let mut ids = vec![0; 9];
+        assert_eq!(nearest_to_middle(&ids, 3), None);
+
+        // Anything hit is better than nothing..
+        ids[0] = 5;
+        assert_eq!(nearest_to_middle(&ids, 3), Some(4));
+
+        // ..but the closest to the middle wins.
+        ids[5] = 8;
+        assert_eq!(nearest_to_middle(&ids, 3), Some(7));
+        ids[4] = 1;
+        assert_eq!(nearest_to_middle(&ids, 3), Some(0));
+    }
+
+    #[test]
+    pub fn test_through_view() {
+        let view = xr::View {
+            pose: posef_from_affine(Affine3A::IDENTITY),
+            fov: xr::Fovf {
+                angle_left: -0.5,
+                angle_right: 0.5,
+                angle_up: 0.5,
+                angle_down: -0.5,
+            },
+        };
+        let global_from_stage = Affine3A::from_translation(Vec3::X);
+
+        // The middle of the image is straight ahead, and the top left is up and to the left.
+        let ray = PickRay::through_view(&view, &global_from_stage, Vec2::splat(0.5));
+        assert_relative_eq!(ray.origin, Vec3::X);
+        assert_relative_eq!(ray.direction, Vec3::NEG_Z);
+        let ray = PickRay::through_view(&view, &global_from_stage, Vec2::ZERO);
+        assert_relative_eq!(ray.direction, Vec3::new(-0.5_f32.tan(), 0.5_f32.tan(), -1.));
+    }
+}
@@ -0,0 +1,250 @@
+use glam::{Affine3A, Vec3, Vec4};
+
+/// Contact shadows: a ray towards each light, so small objects cast shadows on what they're resting on.
+pub const RAY_QUERY_CONTACT_SHADOWS: u32 = 1;
+/// Ambient occlusion: a few rays over the hemisphere around the surface, darkening the ambient light in corners.
+pub const RAY_QUERY_AMBIENT_OCCLUSION: u32 = 2;
+
+/// Settings for ray traced contact shadows and ambient occlusion, traced against the scene's
+/// [`crate::components::StaticGeometry`].
+///
+/// These only need hardware ray queries, not full ray tracing pipelines, so they run in the normal PBR fragment shader.
+/// Devices without ray queries, and builds without the `ray-query` feature, draw without them. See
+/// [`crate::contexts::RenderContext::enable_ray_query`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayQuerySettings {
+    /// Trace contact shadows?
+    pub contact_shadows: bool,
+    /// Trace ambient occlusion?
+    pub ambient_occlusion: bool,
+    /// How far contact shadow rays go, in meters. Short rays are cheap, and leave distant shadows to shadow maps.
+    pub shadow_distance: f32,
+    /// How far ambient occlusion rays go, in meters
+    pub occlusion_distance: f32,
+    /// How many ambient occlusion rays to trace for each pixel. More is smoother, and slower.
+    pub occlusion_ray_count: u32,
+}
+
+impl Default for RayQuerySettings {
+    fn default() -> Self {
+        Self {
+            contact_shadows: true,
+            ambient_occlusion: true,
+            shadow_distance: 0.5,
+            occlusion_distance: 0.3,
+            occlusion_ray_count: 4,
+        }
+    }
+}
+
+/// The ray query settings, as they're pushed to `pbr.frag`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RayQueryParams {
+    /// Added to a position in globally oriented stage space to move it into global space, where the acceleration
+    /// structure was built
+    pub global_from_gos: Vec4,
+    /// Which effects are on - [`RAY_QUERY_CONTACT_SHADOWS`] and [`RAY_QUERY_AMBIENT_OCCLUSION`]
+    pub mode: u32,
+    /// How far contact shadow rays go
+    pub shadow_distance: f32,
+    /// How far ambient occlusion rays go
+    pub occlusion_distance: f32,
+    /// How many ambient occlusion rays to trace for each pixel
+    pub occlusion_ray_count: u32,
+}
+
+impl RayQueryParams {
+    /// Create the params for `settings`. `gos_from_global` only ever translates, so moving back into global space is
+    /// just the opposite translation.
+    pub fn new(settings: &RayQuerySettings, gos_from_global: &Affine3A) -> Self {
+        let mut mode = 0;
+        if settings.contact_shadows {
+            mode |= RAY_QUERY_CONTACT_SHADOWS;
+        }
+        if settings.ambient_occlusion {
+            mode |= RAY_QUERY_AMBIENT_OCCLUSION;
+        }
+
+        Self {
+            global_from_gos: Vec3::from(-gos_from_global.translation).extend(0.),
+            mode,
+            shadow_distance: settings.shadow_distance.max(0.),
+            occlusion_distance: settings.occlusion_distance.max(0.),
+            occlusion_ray_count: settings.occlusion_ray_count,
+        }
+    }
+}
+
+#[cfg(feature = "ray-query")]
+pub use shading::RayQueryShading;
+
+#[cfg(feature = "ray-query")]
+mod shading {
+    use std::{mem::size_of, slice::from_ref as slice_from_ref};
+
+    use anyhow::Result;
+    use ash::vk;
+    use vk_shader_macros::include_glsl;
+
+    use super::RayQueryParams;
+    use crate::{
+        contexts::{
+            render_context::{create_pipeline, VERT},
+            VulkanContext,
+        },
+        rendering::{
+            descriptors::Descriptors,
+            scene_description::{SceneAccelerationStructure, SceneDescription},
+        },
+    };
+
+    static RAY_QUERY_FRAG: &[u32] =
+        include_glsl!("src/shaders/pbr.frag", target: vulkan1_2, define: RAY_QUERY);
+
+    /// The PBR pipeline with ray traced contact shadows and ambient occlusion, and the acceleration structure it traces
+    /// rays against. Used in place of the normal PBR pipeline while ray queries are turned on.
+    pub struct RayQueryShading {
+        /// The pipeline itself
+        pub pipeline: vk::Pipeline,
+        /// The PBR pipeline's descriptor sets, then the acceleration structure, plus the ray query settings as a push
+        /// constant
+        pub pipeline_layout: vk::PipelineLayout,
+        /// Holds the top level acceleration structure
+        pub descriptor_set: vk::DescriptorSet,
+        /// The static geometry in the scene
+        pub acceleration_structure: SceneAccelerationStructure,
+        set_layout: vk::DescriptorSetLayout,
+        // The shared pool has no room for acceleration structures, so this has a small one of its own.
+        pool: vk::DescriptorPool,
+    }
+
+    impl RayQueryShading {
+        /// Build the acceleration structure for `description` and create the pipeline. Waits for the GPU.
+        pub(crate) unsafe fn new(
+            vulkan_context: &VulkanContext,
+            descriptors: &Descriptors,
+            render_area: &vk::Rect2D,
+            render_pass: vk::RenderPass,
+            reversed_z: bool,
+            description: &SceneDescription,
+        ) -> Result<Self> {
+            let device = &vulkan_context.device;
+            let acceleration_structure =
+                SceneAccelerationStructure::build(vulkan_context, description)?;
+
+            let pool = device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::builder()
+                    .pool_sizes(&[vk::DescriptorPoolSize {
+                        ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                        descriptor_count: 1,
+                    }])
+                    .max_sets(1),
+                None,
+            )?;
+            let binding = vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build();
+            let set_layout = device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder().bindings(slice_from_ref(&binding)),
+                None,
+            )?;
+            let descriptor_set = device.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(pool)
+                    .set_layouts(slice_from_ref(&set_layout)),
+            )?[0];
+
+            let mut acceleration_structure_write =
+                vk::WriteDescriptorSetAccelerationStructureKHR::builder()
+                    .acceleration_structures(slice_from_ref(&acceleration_structure.top_level));
+            let mut write = vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .push_next(&mut acceleration_structure_write)
+                .build();
+            // The count usually comes from the image or buffer infos, which acceleration structures don't have.
+            write.descriptor_count = 1;
+            device.update_descriptor_sets(slice_from_ref(&write), &[]);
+
+            let push_constant_range = vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .offset(0)
+                .size(size_of::<RayQueryParams>() as _)
+                .build();
+            let set_layouts = [
+                descriptors.graphics_layout,
+                descriptors.fog_layout,
+                set_layout,
+            ];
+            let pipeline_layout = device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(&set_layouts)
+                    .push_constant_ranges(slice_from_ref(&push_constant_range)),
+                None,
+            )?;
+            let pipeline = create_pipeline(
+                vulkan_context,
+                pipeline_layout,
+                render_area,
+                render_pass,
+                reversed_z,
+                descriptors.texture_capacity,
+                VERT,
+                RAY_QUERY_FRAG,
+            )?;
+
+            Ok(Self {
+                pipeline,
+                pipeline_layout,
+                descriptor_set,
+                acceleration_structure,
+                set_layout,
+                pool,
+            })
+        }
+
+        /// Destroy everything. The GPU must have finished using it.
+        pub(crate) unsafe fn destroy(&self, device: &ash::Device) {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_pool(self.pool, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            self.acceleration_structure.destroy(device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_ray_query_params() {
+        let gos_from_global = Affine3A::from_translation(Vec3::new(-1., -2., -3.));
+        let params = RayQueryParams::new(&Default::default(), &gos_from_global);
+        assert_eq!(
+            params.mode,
+            RAY_QUERY_CONTACT_SHADOWS | RAY_QUERY_AMBIENT_OCCLUSION
+        );
+        assert_eq!(params.global_from_gos, Vec4::new(1., 2., 3., 0.));
+
+        // A point in globally oriented stage space moves back to where it was in global space.
+        let global = Vec3::new(4., 5., 6.);
+        let gos = gos_from_global.transform_point3(global);
+        assert_eq!(gos + params.global_from_gos.truncate(), global);
+
+        let settings = RayQuerySettings {
+            contact_shadows: false,
+            shadow_distance: -1.,
+            ..Default::default()
+        };
+        let params = RayQueryParams::new(&settings, &gos_from_global);
+        assert_eq!(params.mode, RAY_QUERY_AMBIENT_OCCLUSION);
+        assert_eq!(params.shadow_distance, 0.);
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use ash::vk;
+
+use crate::{
+    contexts::VulkanContext,
+    rendering::{buffer::Buffer, image::Image},
+    util::{is_bgra, swap_red_and_blue},
+};
+
+/// Called with the data from a readback once the GPU has finished copying it.
+pub type ReadbackCallback = Box<dyn FnOnce(ReadbackData) + Send>;
+
+/// Identifies a readback requested from [`Readback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadbackId(u64);
+
+/// Data copied back from the GPU.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadbackData {
+    /// The size of the image the data was copied from. Zero for buffers.
+    pub extent: vk::Extent2D,
+    /// The format of the image the data was copied from. `UNDEFINED` for buffers.
+    pub format: vk::Format,
+    /// The data itself: tightly packed rows of pixels for images
+    pub bytes: Vec<u8>,
+}
+
+impl ReadbackData {
+    /// The data as an RGBA image, if it was copied from an 8-bit RGBA or BGRA image.
+    pub fn to_rgba_image(&self) -> Option<image::RgbaImage> {
+        let mut bytes = self.bytes.clone();
+        match self.format {
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => {}
+            format if is_bgra(format) => swap_red_and_blue(&mut bytes),
+            _ => return None,
+        }
+        image::RgbaImage::from_raw(self.extent.width, self.extent.height, bytes)
+    }
+}
+
+/// Reads rendered data back from the GPU without stalling it, for screenshots, picking or feeding images to a model.
+///
+/// Each request records its own copy into a staging buffer, and is submitted after whatever the GPU has already been
+/// given - so an image is read as it was last rendered. [`Readback::update`] is called by the engine every frame and
+/// checks which copies have finished, usually a frame or two later. Finished readbacks are passed to their callback,
+/// or kept until they're collected with [`Readback::poll`]:
+/// ```ignore
+/// let id = engine.render_context.readback.read_image(vulkan_context, &image, 0, layout)?;
+/// // A few frames later..
+/// if let Some(data) = engine.render_context.readback.poll(id) {
+///     data.to_rgba_image().unwrap().save("screenshot.png")?;
+/// }
+/// ```
+#[derive(Default)]
+pub struct Readback {
+    next_id: u64,
+    in_flight: Vec<InFlight>,
+    completed: HashMap<ReadbackId, ReadbackData>,
+}
+
+/// A copy the GPU hasn't finished yet.
+struct InFlight {
+    id: ReadbackId,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    staging: Buffer<u8>,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    callback: Option<ReadbackCallback>,
+}
+
+impl Readback {
+    /// Copy layer `layer` of `image`, which is in `layout`, back from the GPU. It's left in the same layout.
+    pub fn read_image(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        image: &Image,
+        layer: u32,
+        layout: vk::ImageLayout,
+    ) -> Result<ReadbackId> {
+        self.submit_image(vulkan_context, image, layer, layout, None)
+    }
+
+    /// Copy layer `layer` of `image` back from the GPU, and call `callback` with it once it's arrived.
+    pub fn read_image_then<F>(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        image: &Image,
+        layer: u32,
+        layout: vk::ImageLayout,
+        callback: F,
+    ) -> Result<ReadbackId>
+    where
+        F: FnOnce(ReadbackData) + Send + 'static,
+    {
+        self.submit_image(
+            vulkan_context,
+            image,
+            layer,
+            layout,
+            Some(Box::new(callback)),
+        )
+    }
+
+    /// Copy `size` bytes from `buffer`, starting at `offset`, back from the GPU.
+    pub fn read_buffer(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: usize,
+    ) -> Result<ReadbackId> {
+        self.submit_buffer(vulkan_context, buffer, offset, size, None)
+    }
+
+    /// Copy `size` bytes from `buffer` back from the GPU, and call `callback` with them once they've arrived.
+    pub fn read_buffer_then<F>(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: usize,
+        callback: F,
+    ) -> Result<ReadbackId>
+    where
+        F: FnOnce(ReadbackData) + Send + 'static,
+    {
+        self.submit_buffer(
+            vulkan_context,
+            buffer,
+            offset,
+            size,
+            Some(Box::new(callback)),
+        )
+    }
+
+    /// Take the data for `id`, if it's arrived. Readbacks with a callback are never kept here.
+    pub fn poll(&mut self, id: ReadbackId) -> Option<ReadbackData> {
+        self.completed.remove(&id)
+    }
+
+    /// Is the GPU still copying `id`?
+    pub fn is_pending(&self, id: ReadbackId) -> bool {
+        self.in_flight.iter().any(|i| i.id == id)
+    }
+
+    /// Collect any copies the GPU has finished. Called by the engine every frame.
+    pub fn update(&mut self, vulkan_context: &VulkanContext) {
+        let device = &vulkan_context.device;
+        let mut still_in_flight = Vec::new();
+        for mut in_flight in self.in_flight.drain(..) {
+            // If the device has been lost there's nothing coming, so give up on it.
+            match unsafe { device.get_fence_status(in_flight.fence) } {
+                Ok(false) => {
+                    still_in_flight.push(in_flight);
+                    continue;
+                }
+                Ok(true) => {}
+                Err(e) => {
+                    log::error!(
+                        "[HOTHAM_READBACK] Readback {:?} failed: {:?}",
+                        in_flight.id,
+                        e
+                    );
+                    unsafe { in_flight.free(vulkan_context) };
+                    continue;
+                }
+            }
+
+            let data = ReadbackData {
+                extent: in_flight.extent,
+                format: in_flight.format,
+                bytes: unsafe { in_flight.staging.as_slice() }.to_vec(),
+            };
+            unsafe { in_flight.free(vulkan_context) };
+            match in_flight.callback.take() {
+                Some(callback) => callback(data),
+                None => {
+                    self.completed.insert(in_flight.id, data);
+                }
+            }
+        }
+        self.in_flight = still_in_flight;
+    }
+
+    /// Wait for any copies still in flight, and free everything. Their data is thrown away.
+    pub fn destroy(&mut self, vulkan_context: &VulkanContext) {
+        for mut in_flight in self.in_flight.drain(..) {
+            unsafe {
+                let _ = vulkan_context
+                    .device
+                    .wait_for_fences(&[in_flight.fence], true, u64::MAX);
+                in_flight.free(vulkan_context);
+            }
+        }
+        self.completed.clear();
+    }
+
+    fn submit_image(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        image: &Image,
+        layer: u32,
+        layout: vk::ImageLayout,
+        callback: Option<ReadbackCallback>,
+    ) -> Result<ReadbackId> {
+        let (bytes_per_pixel, aspect_mask) = texel_info(image.format)
+            .ok_or_else(|| anyhow!("Unable to read back images in {:?}", image.format))?;
+        if layer >= image.layer_count {
+            return Err(anyhow!(
+                "Unable to read back layer {} of an image with {} layers",
+                layer,
+                image.layer_count
+            ));
+        }
+        let size = (image.extent.width * image.extent.height * bytes_per_pixel) as usize;
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: layer,
+            layer_count: 1,
+        };
+
+        self.submit(
+            vulkan_context,
+            size,
+            image.extent,
+            image.format,
+            callback,
+            |device, command_buffer, staging| unsafe {
+                // Wait for anything already submitted that writes to the image, then copy it out and put it back how
+                // it was.
+                let to_transfer = vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .old_layout(layout)
+                    .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image.handle)
+                    .subresource_range(subresource_range);
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[*to_transfer],
+                );
+
+                let region = vk::BufferImageCopy::builder()
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask,
+                        mip_level: 0,
+                        base_array_layer: layer,
+                        layer_count: 1,
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: image.extent.width,
+                        height: image.extent.height,
+                        depth: 1,
+                    });
+                device.cmd_copy_image_to_buffer(
+                    command_buffer,
+                    image.handle,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    staging,
+                    &[*region],
+                );
+
+                let back = vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                    .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                    .new_layout(layout)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image.handle)
+                    .subresource_range(subresource_range);
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[*back],
+                );
+            },
+        )
+    }
+
+    fn submit_buffer(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: usize,
+        callback: Option<ReadbackCallback>,
+    ) -> Result<ReadbackId> {
+        self.submit(
+            vulkan_context,
+            size,
+            vk::Extent2D::default(),
+            vk::Format::UNDEFINED,
+            callback,
+            |device, command_buffer, staging| unsafe {
+                let memory_barrier = vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[*memory_barrier],
+                    &[],
+                    &[],
+                );
+                let region = vk::BufferCopy {
+                    src_offset: offset,
+                    dst_offset: 0,
+                    size: size as _,
+                };
+                device.cmd_copy_buffer(command_buffer, buffer, staging, &[region]);
+            },
+        )
+    }
+
+    /// Record a copy into a new staging buffer with `record`, and submit it with a fence to check on later.
+    fn submit(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        size: usize,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        callback: Option<ReadbackCallback>,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer, vk::Buffer),
+    ) -> Result<ReadbackId> {
+        let device = &vulkan_context.device;
+        let id = ReadbackId(self.next_id);
+        self.next_id += 1;
+
+        unsafe {
+            let mut staging = Buffer::new(vulkan_context, vk::BufferUsageFlags::TRANSFER_DST, size);
+            staging.len = size;
+
+            let command_buffer = device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::builder()
+                    .command_buffer_count(1)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_pool(vulkan_context.command_pool),
+            )?[0];
+            device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )?;
+            record(device, command_buffer, staging.buffer);
+
+            // Make the copy visible to the CPU once the fence has been signalled.
+            let to_host = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ);
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[*to_host],
+                &[],
+                &[],
+            );
+            device.end_command_buffer(command_buffer)?;
+
+            let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+            device.queue_submit(
+                vulkan_context.graphics_queue,
+                &[*vk::SubmitInfo::builder().command_buffers(&[command_buffer])],
+                fence,
+            )?;
+
+            self.in_flight.push(InFlight {
+                id,
+                command_buffer,
+                fence,
+                staging,
+                extent,
+                format,
+                callback,
+            });
+        }
+
+        Ok(id)
+    }
+}
+
+impl InFlight {
+    unsafe fn free(&mut self, vulkan_context: &VulkanContext) {
+        let device = &vulkan_context.device;
+        device.destroy_fence(self.fence, None);
+        device.free_command_buffers(vulkan_context.command_pool, &[self.command_buffer]);
+        self.staging.destroy(device);
+    }
+}
+
+/// How many bytes each texel of `format` takes up when it's copied to a buffer, and which aspect to copy.
+fn texel_info(format: vk::Format) -> Option<(u32, vk::ImageAspectFlags)> {
+    let color = vk::ImageAspectFlags::COLOR;
+    Some(match format {
+        vk::Format::R8_UNORM | vk::Format::R8_UINT => (1, color),
+        vk::Format::R8G8B8A8_SRGB
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SFLOAT => (4, color),
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_UINT => (8, color),
+        vk::Format::R32G32B32A32_SFLOAT => (16, color),
+        vk::Format::D32_SFLOAT => (4, vk::ImageAspectFlags::DEPTH),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_texel_info() {
+        assert_eq!(
+            texel_info(vk::Format::R8G8B8A8_SRGB),
+            Some((4, vk::ImageAspectFlags::COLOR))
+        );
+        assert_eq!(
+            texel_info(vk::Format::D32_SFLOAT),
+            Some((4, vk::ImageAspectFlags::DEPTH))
+        );
+        assert_eq!(texel_info(vk::Format::BC7_SRGB_BLOCK), None);
+    }
+
+    #[test]
+    pub fn test_to_rgba_image() {
+        let data = ReadbackData {
+            extent: vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+            format: vk::Format::B8G8R8A8_SRGB,
+            bytes: vec![1, 2, 3, 4],
+        };
+        assert_eq!(data.to_rgba_image().unwrap().into_raw(), vec![3, 2, 1, 4]);
+
+        let depth = ReadbackData {
+            format: vk::Format::D32_SFLOAT,
+            ..data
+        };
+        assert!(depth.to_rgba_image().is_none());
+    }
+}
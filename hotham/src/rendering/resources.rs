@@ -1,9 +1,10 @@
+use anyhow::{anyhow, Result};
 use ash::vk;
 use glam::{Mat4, Vec4};
 use id_arena::Arena;
 use vulkan_context::VulkanContext;
 
-use crate::contexts::vulkan_context;
+use crate::{contexts::vulkan_context, DEPTH_FORMAT};
 
 use super::{
     buffer::Buffer,
@@ -44,8 +45,15 @@ pub struct Resources {
     /// Shared sampler
     pub cube_sampler: vk::Sampler,
 
+    /// The shadow atlas, holding every light's shadow map. A single texel until shadows are turned on.
+    pub shadow_map: Image,
+
+    /// Compares depths as the shadow map is sampled, so the PBR shader gets back how much of a texel is in the light
+    pub shadow_sampler: vk::Sampler,
+
     /// Texture descriptor information
     texture_count: u32,
+    texture_capacity: u32,
 }
 
 impl Resources {
@@ -53,7 +61,7 @@ impl Resources {
     pub(crate) unsafe fn new(vulkan_context: &VulkanContext, descriptors: &Descriptors) -> Self {
         let vertex_buffer = Buffer::new(
             vulkan_context,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
             VERTEX_BUFFER_SIZE,
         );
 
@@ -95,6 +103,10 @@ impl Resources {
 
         load_ibl_textures(vulkan_context, descriptors, cube_sampler);
 
+        let shadow_map = create_shadow_map(vulkan_context, 1).unwrap();
+        let shadow_sampler = create_shadow_sampler(vulkan_context).unwrap();
+        descriptors.write_shadow_map_descriptor(vulkan_context, shadow_map.view, shadow_sampler);
+
         Self {
             vertex_buffer,
             index_buffer,
@@ -104,6 +116,9 @@ impl Resources {
             texture_count: 1, // IMPORTANT! Because we stashed the BRDF Lut texture in here, make sure we increment the count accordingly
             texture_sampler,
             cube_sampler,
+            shadow_map,
+            shadow_sampler,
+            texture_capacity: descriptors.texture_capacity,
         }
     }
 
@@ -112,18 +127,154 @@ impl Resources {
         vulkan_context: &VulkanContext,
         descriptors: &Descriptors,
         image: &Image,
-    ) -> u32 {
+    ) -> Result<u32> {
         // There doesn't seem any reason to add support for dynamic cube maps yet as there isn't any user facing way of loading them.
         let sampler = self.texture_sampler;
 
         let index = self.texture_count;
+        if index >= self.texture_capacity {
+            return Err(anyhow!(
+                "The texture array is full - it can only hold {} textures",
+                self.texture_capacity
+            ));
+        }
         descriptors.write_texture_descriptor(vulkan_context, image.view, sampler, index);
         self.texture_count += 1;
 
-        index
+        Ok(index)
+    }
+
+    /// Replace the shadow atlas with an empty one `size` texels across. The GPU must be idle.
+    pub(crate) unsafe fn resize_shadow_map(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        descriptors: &Descriptors,
+        size: u32,
+    ) -> Result<()> {
+        let shadow_map = create_shadow_map(vulkan_context, size)?;
+        let device = &vulkan_context.device;
+        device.destroy_image_view(self.shadow_map.view, None);
+        device.destroy_image(self.shadow_map.handle, None);
+        device.free_memory(self.shadow_map.device_memory, None);
+        self.shadow_map = shadow_map;
+        descriptors.write_shadow_map_descriptor(
+            vulkan_context,
+            self.shadow_map.view,
+            self.shadow_sampler,
+        );
+        Ok(())
+    }
+
+    /// How many textures have been written to the texture array, including the BRDF LUT.
+    pub fn texture_count(&self) -> u32 {
+        self.texture_count
+    }
+
+    /// How many textures the texture array can hold on this device.
+    pub fn texture_capacity(&self) -> u32 {
+        self.texture_capacity
     }
 }
 
+/// Create a shadow atlas `size` texels across, cleared to the far plane so nothing is in shadow, and ready to be
+/// sampled.
+fn create_shadow_map(vulkan_context: &VulkanContext, size: u32) -> Result<Image> {
+    let image = vulkan_context.create_image(
+        DEPTH_FORMAT,
+        &vk::Extent2D {
+            width: size,
+            height: size,
+        },
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+            | vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::TRANSFER_DST,
+        1,
+        1,
+    )?;
+
+    let range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::DEPTH,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+        vk::ImageMemoryBarrier::builder()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image.handle)
+            .subresource_range(range)
+            .build()
+    };
+
+    let device = &vulkan_context.device;
+    let command_buffer = vulkan_context.begin_single_time_commands();
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier(
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::TRANSFER_WRITE,
+            )],
+        );
+        device.cmd_clear_depth_stencil_image(
+            command_buffer,
+            image.handle,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+            std::slice::from_ref(&range),
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier(
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            )],
+        );
+    }
+    vulkan_context.end_single_time_commands(command_buffer);
+
+    Ok(image)
+}
+
+fn create_shadow_sampler(vulkan_context: &VulkanContext) -> Result<vk::Sampler> {
+    // Anything outside the atlas is in the light.
+    let create_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .compare_enable(true)
+        .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .max_lod(0.0);
+    unsafe { vulkan_context.device.create_sampler(&create_info, None) }.map_err(Into::into)
+}
+
 // Upload the textures required for Image Based Lighting. A bit of silliness is required here.
 // Our normal methods of creating textures are somewhat limited here as we don't have access to RenderContext.
 // A better way to handle this would be to make Texture a little more flexible, but we can get to that.
@@ -153,6 +304,10 @@ fn load_ibl_textures(
         .unwrap();
 
     unsafe {
+        // Without bindless support, the rest of the array can't be left empty, so fill it with the LUT.
+        if !descriptors.bindless {
+            descriptors.fill_texture_array(vulkan_context, image.view, texture_sampler);
+        }
         descriptors.write_texture_descriptor(vulkan_context, image.view, texture_sampler, 0);
     }
 
@@ -209,6 +364,12 @@ pub struct DrawData {
     pub material_id: u32,
     /// An optional skin to use.
     pub skin_id: u32,
+    /// Whether `ambient_probe` should be used instead of the irradiance map - 1 if so, 0 if not.
+    pub has_ambient_probe: u32,
+    /// The [`hecs::Entity::id`] of the entity being drawn, for picking. `u32::MAX` if it can't be picked.
+    pub entity_id: u32,
+    /// Ambient light from a baked light probe, packed with [`crate::rendering::light_probes::ShProbe::packed`].
+    pub ambient_probe: [Vec4; 3],
 }
 
 /// Information for the culling shader on how to cull this primitive.
@@ -0,0 +1,185 @@
+use std::{mem::size_of_val, ptr::copy_nonoverlapping};
+
+use ash::vk;
+
+use crate::contexts::{render_context::PIPELINE_DEPTH, VulkanContext};
+
+use super::buffer::Buffer;
+
+/// How much data apps can write to [`crate::contexts::RenderContext::app_data`] each frame, in bytes
+pub const APP_DATA_FRAME_SIZE: usize = 256 * 1024;
+
+/// Space in a [`RingBuffer`] that's been written to this frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingAllocation {
+    /// The buffer the data was written to
+    pub buffer: vk::Buffer,
+    /// Where the data starts in `buffer`, in bytes. Use it as a dynamic descriptor offset, or pass it to a shader in a
+    /// push constant.
+    pub offset: u32,
+    /// How much data was written, in bytes
+    pub size: u32,
+}
+
+/// A host visible buffer for data that changes every draw, like the parameters of an app's own materials.
+///
+/// The buffer is split into a region for each frame in flight. Data written with [`RingBuffer::write`] goes in the
+/// current frame's region, and is left alone until the GPU has finished with that frame, so apps never have to manage
+/// Vulkan buffers or wait on the GPU themselves. Each allocation starts at an offset that can be used with
+/// `UNIFORM_BUFFER_DYNAMIC` and `STORAGE_BUFFER_DYNAMIC` descriptors - see [`RingBuffer::update_descriptor_set`].
+pub struct RingBuffer {
+    /// The memory shared by every frame's region
+    pub buffer: Buffer<u8>,
+    allocator: RingAllocator,
+}
+
+impl RingBuffer {
+    /// Create a ring buffer with `frame_size` bytes for each frame in flight, usable as both a uniform and a storage
+    /// buffer.
+    pub unsafe fn new(vulkan_context: &VulkanContext, frame_size: usize) -> Self {
+        let limits = &vulkan_context.physical_device_properties.limits;
+        let alignment = limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment)
+            .max(1) as usize;
+        let allocator = RingAllocator::new(frame_size, alignment, PIPELINE_DEPTH);
+        let buffer = Buffer::new(
+            vulkan_context,
+            vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
+            allocator.frame_size * PIPELINE_DEPTH,
+        );
+        Self { buffer, allocator }
+    }
+
+    /// Write `data` to this frame's region, returning where it went, or `None` if there's no room left this frame.
+    pub fn write<T: Copy>(&mut self, data: &T) -> Option<RingAllocation> {
+        self.write_slice(std::slice::from_ref(data))
+    }
+
+    /// Write all of `data` to this frame's region, returning where it went, or `None` if there's no room left this
+    /// frame.
+    pub fn write_slice<T: Copy>(&mut self, data: &[T]) -> Option<RingAllocation> {
+        let size = size_of_val(data);
+        let offset = self.allocator.allocate(size)?;
+        unsafe {
+            copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                self.buffer.memory_address.as_ptr().add(offset),
+                size,
+            );
+        }
+        Some(RingAllocation {
+            buffer: self.buffer.buffer,
+            offset: offset as _,
+            size: size as _,
+        })
+    }
+
+    /// How many bytes can still be written this frame, before alignment
+    pub fn remaining(&self) -> usize {
+        self.allocator.remaining()
+    }
+
+    /// Point `binding` of `descriptor_set` at the buffer, as a dynamic descriptor of `descriptor_type` covering `range`
+    /// bytes. Pass the [`RingAllocation::offset`] of each draw's data as its dynamic offset when binding the set.
+    pub unsafe fn update_descriptor_set(
+        &self,
+        device: &ash::Device,
+        descriptor_set: vk::DescriptorSet,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        range: vk::DeviceSize,
+    ) {
+        let buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.buffer.buffer)
+            .offset(0)
+            .range(range);
+        let write = vk::WriteDescriptorSet::builder()
+            .buffer_info(std::slice::from_ref(&buffer_info))
+            .dst_set(descriptor_set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type);
+        device.update_descriptor_sets(std::slice::from_ref(&write), &[]);
+    }
+
+    /// Start writing to `frame_index`'s region, forgetting what was written there last time. Only call this once the
+    /// GPU has finished with that frame.
+    pub(crate) fn next_frame(&mut self, frame_index: usize) {
+        self.allocator.next_frame(frame_index);
+    }
+}
+
+/// Hands out aligned space in each frame's region of a [`RingBuffer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RingAllocator {
+    frame_size: usize,
+    alignment: usize,
+    frame_count: usize,
+    frame_index: usize,
+    head: usize,
+}
+
+impl RingAllocator {
+    fn new(frame_size: usize, alignment: usize, frame_count: usize) -> Self {
+        Self {
+            // Every region has to start on an aligned offset too.
+            frame_size: align_up(frame_size, alignment),
+            alignment,
+            frame_count,
+            frame_index: 0,
+            head: 0,
+        }
+    }
+
+    /// Find room for `size` bytes in the current frame's region, returning its offset from the start of the buffer.
+    fn allocate(&mut self, size: usize) -> Option<usize> {
+        let start = align_up(self.head, self.alignment);
+        if start + size > self.frame_size {
+            return None;
+        }
+        self.head = start + size;
+        Some(self.frame_index * self.frame_size + start)
+    }
+
+    fn remaining(&self) -> usize {
+        self.frame_size.saturating_sub(self.head)
+    }
+
+    fn next_frame(&mut self, frame_index: usize) {
+        self.frame_index = frame_index % self.frame_count;
+        self.head = 0;
+    }
+}
+
+fn align_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_ring_allocator() {
+        let mut allocator = RingAllocator::new(1000, 256, 2);
+        assert_eq!(allocator.frame_size, 1024);
+
+        // Each allocation starts on an aligned offset..
+        assert_eq!(allocator.allocate(100), Some(0));
+        assert_eq!(allocator.allocate(4), Some(256));
+        assert_eq!(allocator.allocate(512), Some(512));
+        assert_eq!(allocator.remaining(), 0);
+
+        // ..until the frame's region is full.
+        assert_eq!(allocator.allocate(1), None);
+
+        // The next frame gets its own region..
+        allocator.next_frame(1);
+        assert_eq!(allocator.allocate(1024), Some(1024));
+        assert_eq!(allocator.allocate(1), None);
+
+        // ..and then the first one is reused.
+        allocator.next_frame(2);
+        assert_eq!(allocator.allocate(16), Some(0));
+    }
+}
@@ -1,7 +1,11 @@
 use glam::{Mat4, Vec4};
 use serde::{Deserialize, Serialize};
 
-use super::light::{Light, MAX_LIGHTS};
+use super::{
+    light::{Light, MAX_LIGHTS},
+    shadow_cascades::MAX_CASCADES,
+    shadows::LightShadow,
+};
 
 /// The amount of Image Based Lighting (IBL) to show in the scene
 pub const DEFAULT_IBL_INTENSITY: f32 = 1.0;
@@ -14,10 +18,20 @@ pub struct SceneData {
     pub view_projection: [Mat4; 2],
     /// Position of the cameras (one per eye)
     pub camera_position: [Vec4; 2],
-    /// Scene Parameters - x = IBL intensity, y = unused, z = debug render inputs, w = debug render algorithm
+    /// Scene Parameters - x = IBL intensity, y = volumetric fog distance (0 when fog is off), z = debug render inputs, w = debug render algorithm
     pub params: Vec4,
+    /// Time parameters - x = seconds since the renderer was created, used to animate materials. y = 1 when colors have
+    /// to be encoded to sRGB by the shaders, as the swapchain won't. zw = unused
+    pub time: Vec4,
     /// Dynamic punctual lights
     pub lights: [Light; MAX_LIGHTS],
+    /// How to sample each light's shadow map. Filled in by [`super::shadows::Shadows`] every frame.
+    pub shadows: [LightShadow; MAX_LIGHTS],
+    /// How to sample each of the sun's shadow cascades, nearest first - see [`super::shadows::Shadows::cascades`]
+    pub shadow_cascades: [LightShadow; MAX_CASCADES],
+    /// Multiplies every color after tonemapping, to simulate or correct for color blindness. Set from
+    /// [`super::accessibility::AccessibilitySettings::color_transform`] every frame.
+    pub color_transform: Mat4,
 }
 
 impl Default for SceneData {
@@ -26,7 +40,11 @@ impl Default for SceneData {
             view_projection: [Mat4::IDENTITY, Mat4::IDENTITY],
             camera_position: [Vec4::ZERO, Vec4::ZERO],
             params: [DEFAULT_IBL_INTENSITY, 0., 0., 0.].into(),
+            time: Vec4::ZERO,
             lights: [Light::none(); MAX_LIGHTS],
+            shadows: [LightShadow::default(); MAX_LIGHTS],
+            shadow_cascades: [LightShadow::default(); MAX_CASCADES],
+            color_transform: Mat4::IDENTITY,
         }
     }
 }
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+
+use glam::Affine3A;
+use hecs::{With, World};
+use id_arena::Arena;
+
+use crate::{
+    components::{GlobalTransform, Mesh, StaticGeometry},
+    rendering::{mesh_data::MeshData, primitive::Primitive, vertex::Vertex},
+};
+
+/// One primitive's triangles, packed into [`SceneDescription::positions`] and [`SceneDescription::indices`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SceneGeometry {
+    /// Where the primitive's positions start
+    pub first_vertex: u32,
+    /// How many positions the primitive has
+    pub vertex_count: u32,
+    /// Where the primitive's indices start. They're relative to `first_vertex`.
+    pub first_index: u32,
+    /// How many indices the primitive has - three per triangle
+    pub index_count: u32,
+}
+
+/// A copy of one [`SceneGeometry`] placed in the world.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneInstance {
+    /// The instance's `global_from_local` transform - see [`transform_rows`]
+    pub transform: [f32; 12],
+    /// Index into [`SceneDescription::geometries`]
+    pub geometry: u32,
+}
+
+/// A compact description of the static geometry in the scene: just positions and indices, with each primitive stored
+/// once no matter how many entities use it. This is everything needed to build acceleration structures for ray queries,
+/// without any of the vertex data that's only used for shading.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SceneDescription {
+    /// Positions of every geometry's vertices, in the geometry's local space
+    pub positions: Vec<[f32; 3]>,
+    /// Indices of every geometry's triangles
+    pub indices: Vec<u32>,
+    /// The unique primitives in the scene
+    pub geometries: Vec<SceneGeometry>,
+    /// Every primitive of every entity with [`StaticGeometry`]
+    pub instances: Vec<SceneInstance>,
+}
+
+impl SceneDescription {
+    /// Describe every entity with [`StaticGeometry`], a [`Mesh`] and a [`GlobalTransform`]. `vertices` and `indices` are
+    /// the contents of the vertex and index buffers the meshes' primitives point into.
+    pub fn gather(
+        world: &World,
+        mesh_data: &Arena<MeshData>,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let mut description = Self::default();
+
+        // Primitives are shared between every entity that uses the mesh, and their index buffer offset is unique.
+        let mut geometry_ids = HashMap::new();
+        for (_, (mesh, global_transform)) in world
+            .query::<With<(&Mesh, &GlobalTransform), &StaticGeometry>>()
+            .iter()
+        {
+            let mesh_data = match mesh_data.get(mesh.handle) {
+                Some(mesh_data) => mesh_data,
+                None => continue,
+            };
+            let transform = transform_rows(&global_transform.0);
+            for primitive in &mesh_data.primitives {
+                let geometry = *geometry_ids
+                    .entry(primitive.index_buffer_offset)
+                    .or_insert_with(|| description.push_geometry(primitive, vertices, indices));
+                description.instances.push(SceneInstance {
+                    transform,
+                    geometry,
+                });
+            }
+        }
+
+        description
+    }
+
+    /// How many triangles are in the scene, counting each instance separately
+    pub fn triangle_count(&self) -> usize {
+        self.instances
+            .iter()
+            .map(|i| self.geometries[i.geometry as usize].index_count as usize / 3)
+            .sum()
+    }
+
+    fn push_geometry(
+        &mut self,
+        primitive: &Primitive,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> u32 {
+        let first_index = primitive.index_buffer_offset as usize;
+        let primitive_indices =
+            &indices[first_index..first_index + primitive.indices_count as usize];
+
+        // Indices are relative to the primitive's first vertex, so it uses everything up to the largest one.
+        let vertex_count = primitive_indices.iter().max().map_or(0, |i| i + 1);
+        let first_vertex = primitive.vertex_buffer_offset as usize;
+
+        let geometry = SceneGeometry {
+            first_vertex: self.positions.len() as _,
+            vertex_count,
+            first_index: self.indices.len() as _,
+            index_count: primitive.indices_count,
+        };
+        self.positions.extend(
+            vertices[first_vertex..first_vertex + vertex_count as usize]
+                .iter()
+                .map(|v| v.position.to_array()),
+        );
+        self.indices.extend_from_slice(primitive_indices);
+        self.geometries.push(geometry);
+
+        (self.geometries.len() - 1) as _
+    }
+}
+
+/// The top three rows of `transform` as a row-major 4x4 matrix - the layout acceleration structure instances use.
+pub fn transform_rows(transform: &Affine3A) -> [f32; 12] {
+    let m = transform.matrix3;
+    let t = transform.translation;
+    [
+        m.x_axis.x, m.y_axis.x, m.z_axis.x, t.x, //
+        m.x_axis.y, m.y_axis.y, m.z_axis.y, t.y, //
+        m.x_axis.z, m.y_axis.z, m.z_axis.z, t.z,
+    ]
+}
+
+#[cfg(feature = "ray-query")]
+pub use acceleration_structure::SceneAccelerationStructure;
+
+#[cfg(feature = "ray-query")]
+mod acceleration_structure {
+    use std::slice::from_ref as slice_from_ref;
+
+    use anyhow::Result;
+    use ash::{extensions::khr::AccelerationStructure, vk};
+
+    use super::SceneDescription;
+    use crate::{
+        contexts::VulkanContext,
+        rendering::memory::{allocate_memory_with_flags, track_free},
+    };
+
+    /// A buffer that acceleration structure builds find by its device address.
+    struct AddressedBuffer {
+        buffer: vk::Buffer,
+        device_memory: vk::DeviceMemory,
+        size: vk::DeviceSize,
+        address: vk::DeviceAddress,
+    }
+
+    impl AddressedBuffer {
+        unsafe fn new(
+            vulkan_context: &VulkanContext,
+            size: vk::DeviceSize,
+            usage: vk::BufferUsageFlags,
+            memory_property_flags: vk::MemoryPropertyFlags,
+        ) -> Result<Self> {
+            let device = &vulkan_context.device;
+            // Empty buffers aren't allowed, and an empty scene is still a valid one.
+            let size = size.max(16);
+            let buffer = device.create_buffer(
+                &vk::BufferCreateInfo::builder()
+                    .size(size)
+                    .usage(usage | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS),
+                None,
+            )?;
+            let memory_requirements = device.get_buffer_memory_requirements(buffer);
+            let device_memory = allocate_memory_with_flags(
+                vulkan_context,
+                memory_requirements,
+                memory_property_flags,
+                vk::MemoryAllocateFlags::DEVICE_ADDRESS,
+            );
+            device.bind_buffer_memory(buffer, device_memory, 0)?;
+            let address = device
+                .get_buffer_device_address(&vk::BufferDeviceAddressInfo::builder().buffer(buffer));
+
+            Ok(Self {
+                buffer,
+                device_memory,
+                size: memory_requirements.size,
+                address,
+            })
+        }
+
+        /// A host visible buffer holding `data`, for the build to read from.
+        unsafe fn with_data<T: Copy>(vulkan_context: &VulkanContext, data: &[T]) -> Result<Self> {
+            let buffer = Self::new(
+                vulkan_context,
+                std::mem::size_of_val(data) as _,
+                vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+            let device = &vulkan_context.device;
+            let memory = device.map_memory(
+                buffer.device_memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+            )?;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), memory as *mut T, data.len());
+            device.unmap_memory(buffer.device_memory);
+            Ok(buffer)
+        }
+
+        /// A device local buffer, for acceleration structures and their scratch space.
+        unsafe fn device_local(
+            vulkan_context: &VulkanContext,
+            size: vk::DeviceSize,
+            usage: vk::BufferUsageFlags,
+        ) -> Result<Self> {
+            Self::new(
+                vulkan_context,
+                size,
+                usage,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        }
+
+        unsafe fn destroy(&self, device: &ash::Device) {
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.device_memory, None);
+            track_free(self.size);
+        }
+    }
+
+    /// Acceleration structures for a [`SceneDescription`]: one bottom level structure for each geometry, and a top
+    /// level structure placing them in the world. Built once on the GPU, then only read by ray queries.
+    pub struct SceneAccelerationStructure {
+        /// The top level acceleration structure, which shaders trace rays against
+        pub top_level: vk::AccelerationStructureKHR,
+        loader: AccelerationStructure,
+        bottom_levels: Vec<vk::AccelerationStructureKHR>,
+        // Backing storage for the acceleration structures. The build inputs are kept too, as they're small.
+        buffers: Vec<AddressedBuffer>,
+    }
+
+    impl SceneAccelerationStructure {
+        /// Build acceleration structures for `description`, waiting for the GPU to finish.
+        pub unsafe fn build(
+            vulkan_context: &VulkanContext,
+            description: &SceneDescription,
+        ) -> Result<Self> {
+            let device = &vulkan_context.device;
+            let loader = AccelerationStructure::new(&vulkan_context.instance, device);
+            let mut buffers = Vec::new();
+
+            let positions = AddressedBuffer::with_data(vulkan_context, &description.positions)?;
+            let indices = AddressedBuffer::with_data(vulkan_context, &description.indices)?;
+            let (positions_address, indices_address) = (positions.address, indices.address);
+            buffers.push(positions);
+            buffers.push(indices);
+
+            let command_buffer = vulkan_context.begin_single_time_commands();
+
+            // Bottom level: one per geometry, in its own local space.
+            let mut bottom_levels = Vec::with_capacity(description.geometries.len());
+            for geometry in &description.geometries {
+                let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::builder()
+                    .vertex_format(vk::Format::R32G32B32_SFLOAT)
+                    .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                        device_address: positions_address
+                            + (geometry.first_vertex as usize * std::mem::size_of::<[f32; 3]>())
+                                as vk::DeviceAddress,
+                    })
+                    .vertex_stride(std::mem::size_of::<[f32; 3]>() as _)
+                    .max_vertex(geometry.vertex_count.saturating_sub(1))
+                    .index_type(vk::IndexType::UINT32)
+                    .index_data(vk::DeviceOrHostAddressConstKHR {
+                        device_address: indices_address
+                            + (geometry.first_index as usize * std::mem::size_of::<u32>())
+                                as vk::DeviceAddress,
+                    })
+                    .build();
+                let geometry_info = vk::AccelerationStructureGeometryKHR::builder()
+                    .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+                    .geometry(vk::AccelerationStructureGeometryDataKHR { triangles })
+                    .flags(vk::GeometryFlagsKHR::OPAQUE)
+                    .build();
+                let (acceleration_structure, mut new_buffers) = build_level(
+                    vulkan_context,
+                    &loader,
+                    command_buffer,
+                    vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                    &geometry_info,
+                    geometry.index_count / 3,
+                )?;
+                bottom_levels.push(acceleration_structure);
+                buffers.append(&mut new_buffers);
+            }
+
+            // The top level builds read the bottom levels, so they have to be finished first.
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::DependencyFlags::empty(),
+                slice_from_ref(
+                    &vk::MemoryBarrier::builder()
+                        .src_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR)
+                        .dst_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR)
+                        .build(),
+                ),
+                &[],
+                &[],
+            );
+
+            // Top level: every instance, in global space.
+            let instances = description
+                .instances
+                .iter()
+                .map(|instance| {
+                    let bottom_level = bottom_levels[instance.geometry as usize];
+                    let device_handle = loader.get_acceleration_structure_device_address(
+                        &vk::AccelerationStructureDeviceAddressInfoKHR::builder()
+                            .acceleration_structure(bottom_level),
+                    );
+                    vk::AccelerationStructureInstanceKHR {
+                        transform: vk::TransformMatrixKHR {
+                            matrix: instance.transform,
+                        },
+                        instance_custom_index_and_mask: vk::Packed24_8::new(0, 0xff),
+                        instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+                            0,
+                            vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw()
+                                as u8,
+                        ),
+                        acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                            device_handle,
+                        },
+                    }
+                })
+                .collect::<Vec<_>>();
+            let instance_buffer = AddressedBuffer::with_data(vulkan_context, &instances)?;
+            let geometry_info = vk::AccelerationStructureGeometryKHR::builder()
+                .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+                .geometry(vk::AccelerationStructureGeometryDataKHR {
+                    instances: vk::AccelerationStructureGeometryInstancesDataKHR::builder()
+                        .array_of_pointers(false)
+                        .data(vk::DeviceOrHostAddressConstKHR {
+                            device_address: instance_buffer.address,
+                        })
+                        .build(),
+                })
+                .build();
+            buffers.push(instance_buffer);
+            let (top_level, mut new_buffers) = build_level(
+                vulkan_context,
+                &loader,
+                command_buffer,
+                vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+                &geometry_info,
+                instances.len() as _,
+            )?;
+            buffers.append(&mut new_buffers);
+
+            vulkan_context.end_single_time_commands(command_buffer);
+
+            Ok(Self {
+                top_level,
+                loader,
+                bottom_levels,
+                buffers,
+            })
+        }
+
+        /// Destroy the acceleration structures. The GPU must have finished using them.
+        pub unsafe fn destroy(&self, device: &ash::Device) {
+            self.loader
+                .destroy_acceleration_structure(self.top_level, None);
+            for bottom_level in &self.bottom_levels {
+                self.loader
+                    .destroy_acceleration_structure(*bottom_level, None);
+            }
+            for buffer in &self.buffers {
+                buffer.destroy(device);
+            }
+        }
+    }
+
+    /// Create an acceleration structure for `geometry` and record its build into `command_buffer`, returning it along
+    /// with the buffers that have to outlive the build.
+    unsafe fn build_level(
+        vulkan_context: &VulkanContext,
+        loader: &AccelerationStructure,
+        command_buffer: vk::CommandBuffer,
+        ty: vk::AccelerationStructureTypeKHR,
+        geometry: &vk::AccelerationStructureGeometryKHR,
+        primitive_count: u32,
+    ) -> Result<(vk::AccelerationStructureKHR, Vec<AddressedBuffer>)> {
+        let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+            .ty(ty)
+            .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(slice_from_ref(geometry))
+            .build();
+        let sizes = loader.get_acceleration_structure_build_sizes(
+            vk::AccelerationStructureBuildTypeKHR::DEVICE,
+            &build_info,
+            &[primitive_count],
+        );
+
+        let storage = AddressedBuffer::device_local(
+            vulkan_context,
+            sizes.acceleration_structure_size,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR,
+        )?;
+        let scratch = AddressedBuffer::device_local(
+            vulkan_context,
+            sizes.build_scratch_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+        let acceleration_structure = loader.create_acceleration_structure(
+            &vk::AccelerationStructureCreateInfoKHR::builder()
+                .buffer(storage.buffer)
+                .size(sizes.acceleration_structure_size)
+                .ty(ty),
+            None,
+        )?;
+
+        build_info.dst_acceleration_structure = acceleration_structure;
+        build_info.scratch_data = vk::DeviceOrHostAddressKHR {
+            device_address: scratch.address,
+        };
+        let range = vk::AccelerationStructureBuildRangeInfoKHR {
+            primitive_count,
+            primitive_offset: 0,
+            first_vertex: 0,
+            transform_offset: 0,
+        };
+        loader.cmd_build_acceleration_structures(
+            command_buffer,
+            slice_from_ref(&build_info),
+            &[slice_from_ref(&range)],
+        );
+
+        Ok((acceleration_structure, vec![storage, scratch]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Quat, Vec2, Vec3, Vec4};
+
+    #[test]
+    pub fn test_transform_rows() {
+        let transform = Affine3A::from_rotation_translation(
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            Vec3::new(1., 2., 3.),
+        );
+        let rows = transform_rows(&transform);
+        let point = Vec3::new(1., 0., 0.);
+        let expected = transform.transform_point3(point);
+        for (row, expected) in rows.chunks(4).zip(expected.to_array()) {
+            let actual = row[0] * point.x + row[1] * point.y + row[2] * point.z + row[3];
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_gather_shares_geometry() {
+        let vertex = |x: f32| Vertex::new(Vec3::new(x, 0., 0.), Vec3::Y, Vec2::ZERO, 0, 0);
+        // Something else is in the buffers first, so the primitive's offsets aren't zero.
+        let vertices = [vertex(9.), vertex(0.), vertex(1.), vertex(2.)];
+        let indices = [0, 0, 1, 2];
+        let primitive = Primitive {
+            index_buffer_offset: 1,
+            vertex_buffer_offset: 1,
+            indices_count: 3,
+            material_id: 0,
+            bounding_sphere: Vec4::ZERO,
+        };
+        let mut mesh_data = Arena::new();
+        let handle = mesh_data.alloc(MeshData::new(vec![primitive]));
+
+        let mut world = World::new();
+        let placed = |x| GlobalTransform(Affine3A::from_translation(Vec3::new(x, 0., 0.)));
+        world.spawn((Mesh { handle }, placed(0.), StaticGeometry));
+        world.spawn((Mesh { handle }, placed(5.), StaticGeometry));
+        // Not static, so it's left out.
+        world.spawn((Mesh { handle }, placed(10.)));
+
+        let description = SceneDescription::gather(&world, &mesh_data, &vertices, &indices);
+        assert_eq!(
+            description.geometries,
+            vec![SceneGeometry {
+                first_vertex: 0,
+                vertex_count: 3,
+                first_index: 0,
+                index_count: 3,
+            }]
+        );
+        assert_eq!(
+            description.positions,
+            vec![[0., 0., 0.], [1., 0., 0.], [2., 0., 0.]]
+        );
+        assert_eq!(description.indices, vec![0, 1, 2]);
+        assert_eq!(description.instances.len(), 2);
+        assert!(description.instances.iter().all(|i| i.geometry == 0));
+        assert_eq!(description.triangle_count(), 2);
+    }
+}
@@ -0,0 +1,448 @@
+use glam::{Vec3, Vec4};
+
+use super::light::{Light, LIGHT_TYPE_DIRECTIONAL, LIGHT_TYPE_NONE, MAX_LIGHTS};
+
+/// The default width and height of the shadow atlas, in texels
+pub const DEFAULT_SHADOW_ATLAS_SIZE: u32 = 2048;
+
+/// How detailed a light's shadow map is. Each tier is a square tile in the shadow atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShadowResolution {
+    /// 256x256 texels
+    Low,
+    /// 512x512 texels
+    Medium,
+    /// 1024x1024 texels
+    High,
+}
+
+impl ShadowResolution {
+    /// The width and height of a shadow map at this resolution, in texels
+    pub fn size(&self) -> u32 {
+        match self {
+            ShadowResolution::Low => 256,
+            ShadowResolution::Medium => 512,
+            ShadowResolution::High => 1024,
+        }
+    }
+
+    /// The next resolution down, if there is one
+    pub fn lower(&self) -> Option<Self> {
+        match self {
+            ShadowResolution::Low => None,
+            ShadowResolution::Medium => Some(ShadowResolution::Low),
+            ShadowResolution::High => Some(ShadowResolution::Medium),
+        }
+    }
+}
+
+/// How to decide which lights matter most, and so get the biggest shadow maps and the most updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowPriority {
+    /// Lights closest to the viewer come first. Directional lights are always closest.
+    Nearest,
+    /// Lights that are brightest where the viewer is come first.
+    Brightest,
+}
+
+/// Settings for a [`ShadowAtlas`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    /// The most shadow maps that can be drawn in a frame. Shadow maps that aren't drawn keep what they had last time,
+    /// so lights that don't move can be updated less often without anyone noticing.
+    pub updates_per_frame: usize,
+    /// How to decide which lights matter most
+    pub priority: ShadowPriority,
+    /// The resolution each light gets, by how much it matters - the first is for the light that matters most. Lights
+    /// get a lower resolution if there isn't room in the atlas.
+    pub tiers: [ShadowResolution; MAX_LIGHTS],
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            updates_per_frame: 2,
+            priority: ShadowPriority::Nearest,
+            tiers: [
+                ShadowResolution::High,
+                ShadowResolution::Medium,
+                ShadowResolution::Medium,
+                ShadowResolution::Low,
+            ],
+        }
+    }
+}
+
+/// A square area of the shadow atlas, holding one light's shadow map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowTile {
+    /// The left edge of the tile, in texels
+    pub x: u32,
+    /// The top edge of the tile, in texels
+    pub y: u32,
+    /// The width and height of the tile, in texels
+    pub size: u32,
+}
+
+impl ShadowTile {
+    /// Where the tile is in an atlas that's `atlas_size` texels across, as texture coordinates: `xy` is the top left
+    /// corner and `zw` is the size, so the shadow map's own coordinates can be mapped with `uv * zw + xy`.
+    pub fn uv_rect(&self, atlas_size: u32) -> Vec4 {
+        Vec4::new(
+            self.x as f32,
+            self.y as f32,
+            self.size as f32,
+            self.size as f32,
+        ) / atlas_size as f32
+    }
+
+    /// The four quarters of the tile: top left, top right, bottom left then bottom right
+    pub(crate) fn split(&self) -> [ShadowTile; 4] {
+        let size = self.size / 2;
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| ShadowTile {
+            x: self.x + x * size,
+            y: self.y + y * size,
+            size,
+        })
+    }
+
+    fn parent(&self) -> ShadowTile {
+        let size = self.size * 2;
+        ShadowTile {
+            x: self.x / size * size,
+            y: self.y / size * size,
+            size,
+        }
+    }
+}
+
+/// A light whose shadow map should be drawn this frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowUpdate {
+    /// The light's index in [`super::scene_data::SceneData::lights`]
+    pub light_index: usize,
+    /// Where to draw the light's shadow map
+    pub tile: ShadowTile,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ShadowSlot {
+    max_resolution: Option<ShadowResolution>,
+    wanted: Option<ShadowResolution>,
+    resolution: Option<ShadowResolution>,
+    tile: Option<ShadowTile>,
+    needs_update: bool,
+    last_update: u64,
+}
+
+/// Shares one shadow map texture between the scene's shadowed lights, and decides which of them get their shadow
+/// maps drawn each frame.
+///
+/// Each frame, the lights are ranked with [`ShadowSettings::priority`] and given a tile in the atlas at the
+/// resolution for their rank, then up to [`ShadowSettings::updates_per_frame`] of them are picked to be drawn. Lights
+/// that have just been given a tile go first, as they have nothing to show yet; after that, lights that have gone the
+/// longest without an update go first, with lights that matter more counting as having waited longer.
+#[derive(Debug, Clone)]
+pub struct ShadowAtlas {
+    /// Settings for the atlas. These can be changed at any time.
+    pub settings: ShadowSettings,
+    size: u32,
+    free: Vec<ShadowTile>,
+    slots: [ShadowSlot; MAX_LIGHTS],
+    frame: u64,
+    updates: Vec<ShadowUpdate>,
+}
+
+impl Default for ShadowAtlas {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHADOW_ATLAS_SIZE, Default::default())
+    }
+}
+
+impl ShadowAtlas {
+    /// Create an atlas `size` texels across, which should be a power of two
+    pub fn new(size: u32, settings: ShadowSettings) -> Self {
+        Self {
+            settings,
+            size,
+            free: vec![ShadowTile { x: 0, y: 0, size }],
+            slots: Default::default(),
+            frame: 0,
+            updates: Vec::new(),
+        }
+    }
+
+    /// How many texels across the atlas is
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Set whether the light at `light_index` casts shadows, and the most detailed shadow map it should have. `None`
+    /// turns its shadows off.
+    pub fn set_shadow_caster(
+        &mut self,
+        light_index: usize,
+        max_resolution: Option<ShadowResolution>,
+    ) {
+        self.slots[light_index].max_resolution = max_resolution;
+    }
+
+    /// Where the shadow map for the light at `light_index` is, if it has one
+    pub fn tile(&self, light_index: usize) -> Option<ShadowTile> {
+        self.slots[light_index].tile
+    }
+
+    /// The resolution of the shadow map for the light at `light_index`, if it has one
+    pub fn resolution(&self, light_index: usize) -> Option<ShadowResolution> {
+        self.slots[light_index].resolution
+    }
+
+    /// Decide where each of `lights` goes in the atlas this frame, as seen from `viewer`, and which of them should be
+    /// drawn. Returns the shadow maps to draw this frame.
+    pub fn update(&mut self, lights: &[Light; MAX_LIGHTS], viewer: Vec3) -> &[ShadowUpdate] {
+        self.frame += 1;
+
+        // Rank the lights that cast shadows..
+        let mut ranked = (0..MAX_LIGHTS)
+            .filter(|i| {
+                self.slots[*i].max_resolution.is_some() && lights[*i].light_type != LIGHT_TYPE_NONE
+            })
+            .map(|i| (i, self.priority_of(&lights[i], viewer)))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // ..work out the resolution each of them should have..
+        let mut wanted = [None; MAX_LIGHTS];
+        for (rank, (light_index, _)) in ranked.iter().enumerate() {
+            let max_resolution = self.slots[*light_index].max_resolution.unwrap();
+            wanted[*light_index] = Some(self.settings.tiers[rank].min(max_resolution));
+        }
+
+        // ..make room by taking away tiles from lights that should now have a different resolution..
+        for (light_index, wanted) in wanted.iter().enumerate() {
+            let slot = self.slots[light_index];
+            if slot.wanted != *wanted {
+                if let Some(tile) = slot.tile {
+                    self.release(tile);
+                }
+                self.slots[light_index] = ShadowSlot {
+                    max_resolution: slot.max_resolution,
+                    wanted: *wanted,
+                    ..Default::default()
+                };
+            }
+        }
+
+        // ..then hand out tiles, most important first, dropping to lower resolutions if they don't fit.
+        for (light_index, _) in &ranked {
+            if self.slots[*light_index].tile.is_some() {
+                continue;
+            }
+            let mut resolution = self.slots[*light_index].wanted;
+            while let Some(r) = resolution {
+                if let Some(tile) = self.allocate(r.size()) {
+                    let slot = &mut self.slots[*light_index];
+                    slot.resolution = Some(r);
+                    slot.tile = Some(tile);
+                    slot.needs_update = true;
+                    break;
+                }
+                resolution = r.lower();
+            }
+        }
+
+        // Finally, pick which shadow maps to draw.
+        let frame = self.frame;
+        let mut candidates = ranked
+            .iter()
+            .enumerate()
+            .filter_map(|(rank, (light_index, _))| {
+                let slot = &self.slots[*light_index];
+                let tile = slot.tile?;
+                let waited = (frame - slot.last_update) * (MAX_LIGHTS - rank) as u64;
+                Some((
+                    slot.needs_update,
+                    waited,
+                    rank,
+                    ShadowUpdate {
+                        light_index: *light_index,
+                        tile,
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+
+        self.updates.clear();
+        for (_, _, _, update) in candidates.into_iter().take(self.settings.updates_per_frame) {
+            let slot = &mut self.slots[update.light_index];
+            slot.needs_update = false;
+            slot.last_update = frame;
+            self.updates.push(update);
+        }
+
+        &self.updates
+    }
+
+    fn priority_of(&self, light: &Light, viewer: Vec3) -> f32 {
+        let distance_squared = if light.light_type == LIGHT_TYPE_DIRECTIONAL {
+            0.
+        } else {
+            light.position.distance_squared(viewer)
+        };
+        match self.settings.priority {
+            ShadowPriority::Nearest => -distance_squared,
+            ShadowPriority::Brightest => light.intensity / distance_squared.max(1.),
+        }
+    }
+
+    /// Find a free tile `size` texels across, splitting a bigger one if need be
+    fn allocate(&mut self, size: u32) -> Option<ShadowTile> {
+        let index = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.size >= size)
+            .min_by_key(|(_, tile)| (tile.size, tile.y, tile.x))
+            .map(|(index, _)| index)?;
+        let mut tile = self.free.swap_remove(index);
+
+        while tile.size > size {
+            let [first, rest @ ..] = tile.split();
+            self.free.extend(rest);
+            tile = first;
+        }
+
+        Some(tile)
+    }
+
+    /// Give `tile` back, merging it with its neighbours if they're free too
+    fn release(&mut self, mut tile: ShadowTile) {
+        while tile.size < self.size {
+            let siblings = tile.parent().split();
+            let all_free = siblings
+                .iter()
+                .all(|sibling| *sibling == tile || self.free.contains(sibling));
+            if !all_free {
+                break;
+            }
+            self.free.retain(|free| !siblings.contains(free));
+            tile = tile.parent();
+        }
+        self.free.push(tile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spotlight(position: Vec3, intensity: f32) -> Light {
+        Light::new_spotlight(
+            -Vec3::Y,
+            10.,
+            intensity,
+            Vec3::ONE,
+            position,
+            0.,
+            std::f32::consts::FRAC_PI_4,
+        )
+    }
+
+    #[test]
+    pub fn test_shadow_tiles() {
+        let mut atlas = ShadowAtlas::new(1024, Default::default());
+        let a = atlas.allocate(512).unwrap();
+        let b = atlas.allocate(256).unwrap();
+        assert_eq!(
+            a,
+            ShadowTile {
+                x: 0,
+                y: 0,
+                size: 512
+            }
+        );
+        assert_eq!(
+            b,
+            ShadowTile {
+                x: 512,
+                y: 0,
+                size: 256
+            }
+        );
+        assert_eq!(b.uv_rect(1024), Vec4::new(0.5, 0., 0.25, 0.25));
+
+        // Freed tiles are merged back together, so there's room for a full size tile again.
+        atlas.release(a);
+        atlas.release(b);
+        assert_eq!(
+            atlas.free,
+            vec![ShadowTile {
+                x: 0,
+                y: 0,
+                size: 1024
+            }]
+        );
+        assert!(atlas.allocate(1024).is_some());
+        assert!(atlas.allocate(256).is_none());
+
+        // Shadow maps that don't fit get a lower resolution.
+        let mut atlas = ShadowAtlas::new(512, Default::default());
+        let mut lights = [Light::none(); MAX_LIGHTS];
+        lights[0] = spotlight(Vec3::ZERO, 1.);
+        atlas.set_shadow_caster(0, Some(ShadowResolution::High));
+        assert_eq!(atlas.update(&lights, Vec3::ZERO).len(), 1);
+        assert_eq!(atlas.resolution(0), Some(ShadowResolution::Medium));
+
+        // They aren't drawn again until they need to be.
+        let tile = atlas.tile(0);
+        assert_eq!(atlas.update(&lights, Vec3::ZERO).len(), 1);
+        assert_eq!(atlas.tile(0), tile);
+    }
+
+    #[test]
+    pub fn test_shadow_budget() {
+        let mut atlas = ShadowAtlas::new(
+            DEFAULT_SHADOW_ATLAS_SIZE,
+            ShadowSettings {
+                updates_per_frame: 1,
+                ..Default::default()
+            },
+        );
+        let mut lights = [Light::none(); MAX_LIGHTS];
+        lights[0] = spotlight(Vec3::new(5., 0., 0.), 10.);
+        lights[1] = spotlight(Vec3::new(1., 0., 0.), 1.);
+        lights[2] = spotlight(Vec3::new(3., 0., 0.), 5.);
+        for i in 0..3 {
+            atlas.set_shadow_caster(i, Some(ShadowResolution::High));
+        }
+
+        // The nearest light gets the biggest shadow map, and is drawn first.
+        let updates = atlas.update(&lights, Vec3::ZERO).to_vec();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].light_index, 1);
+        assert_eq!(atlas.resolution(1), Some(ShadowResolution::High));
+        assert_eq!(atlas.resolution(2), Some(ShadowResolution::Medium));
+        assert_eq!(atlas.resolution(0), Some(ShadowResolution::Medium));
+
+        // Every light gets drawn eventually, without going over budget.
+        let mut drawn = vec![1];
+        for _ in 0..4 {
+            let updates = atlas.update(&lights, Vec3::ZERO);
+            assert_eq!(updates.len(), 1);
+            drawn.push(updates[0].light_index);
+        }
+        assert!(drawn.contains(&0) && drawn.contains(&2));
+
+        // Brightness can be used instead, and lights can stop casting shadows.
+        atlas.settings.priority = ShadowPriority::Brightest;
+        atlas.set_shadow_caster(1, None);
+        atlas.settings.updates_per_frame = MAX_LIGHTS;
+        let updates = atlas.update(&lights, Vec3::ZERO);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].light_index, 2);
+        assert_eq!(atlas.tile(1), None);
+        assert_eq!(atlas.resolution(2), Some(ShadowResolution::High));
+        assert_eq!(atlas.resolution(0), Some(ShadowResolution::Medium));
+    }
+}
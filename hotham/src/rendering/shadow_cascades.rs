@@ -0,0 +1,357 @@
+use glam::{Affine3A, Mat4, Vec3, Vec4};
+
+use super::camera::Frustum;
+
+/// The fewest cascades the sun's shadows can be split into
+pub const MIN_CASCADES: usize = 2;
+
+/// The most cascades the sun's shadows can be split into
+pub const MAX_CASCADES: usize = 4;
+
+/// The color each cascade is drawn in by [`ShadowCascades::debug_lines`], nearest first: red, green, blue and yellow.
+pub const CASCADE_DEBUG_COLORS: [Vec4; MAX_CASCADES] = [
+    Vec4::new(1., 0., 0., 1.),
+    Vec4::new(0., 1., 0., 1.),
+    Vec4::new(0., 0., 1., 1.),
+    Vec4::new(1., 1., 0., 1.),
+];
+
+/// Settings for [`ShadowCascades`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CascadeSettings {
+    /// How many cascades to use, from [`MIN_CASCADES`] to [`MAX_CASCADES`]
+    pub count: usize,
+    /// How far from the viewer shadows reach, in meters
+    pub max_distance: f32,
+    /// How the cascades are split up, from 0 to 1. At 0 each cascade covers the same distance; at 1 each cascade
+    /// covers the same ratio of distances, so nearby cascades are much smaller and sharper.
+    pub split_lambda: f32,
+    /// How far towards the sun to look for things that cast shadows into a cascade, in meters
+    pub caster_distance: f32,
+    /// The width and height of each cascade's shadow map, in texels. [`super::shadows::Shadows`] sets this to a quarter
+    /// of the sun's tile in the atlas.
+    pub resolution: u32,
+}
+
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        Self {
+            count: 3,
+            max_distance: 50.,
+            split_lambda: 0.75,
+            caster_distance: 50.,
+            resolution: 1024,
+        }
+    }
+}
+
+/// One slice of the view, with its own shadow map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cascade {
+    /// How far from the viewer the cascade starts, in meters
+    pub near: f32,
+    /// How far from the viewer the cascade ends, in meters
+    pub far: f32,
+    /// The corners of the slice of the view the cascade covers, in globally oriented stage space: the near corners,
+    /// then the far corners
+    pub corners: [Vec3; 8],
+    /// The radius of the sphere around the slice, which is the size of the shadow map
+    pub radius: f32,
+    /// Looks down the sun's direction, to the centre of the cascade
+    pub light_from_gos: Mat4,
+    /// The projection to draw the cascade's shadow map with, and look it up again
+    pub clip_from_gos: Mat4,
+    /// The centre of the cascade in light space, snapped to whole shadow map texels
+    center: Vec3,
+    /// How far the shadow map reaches, along the sun's direction
+    depth_range: (f32, f32),
+}
+
+impl Cascade {
+    /// Could a sphere at `center`, in globally oriented stage space, cast a shadow into this cascade? Anything that
+    /// can't doesn't need to be drawn into its shadow map.
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        let center_in_light = self.light_from_gos.transform_point3(center);
+        let offset = center_in_light - self.center;
+        let depth = -center_in_light.z;
+        offset.x.abs() <= self.radius + radius
+            && offset.y.abs() <= self.radius + radius
+            && depth >= self.depth_range.0 - radius
+            && depth <= self.depth_range.1 + radius
+    }
+}
+
+/// Cascaded shadow maps for the sun - a directional light - so large outdoor scenes can have sharp shadows nearby and
+/// shadows in the distance, without a huge shadow map.
+///
+/// The view is cut into slices along its depth, each with its own shadow map. To keep shadows from shimmering as the
+/// viewer moves, each cascade is fitted to a sphere around its slice, which keeps its size the same as the viewer
+/// turns, and is moved in steps of whole shadow map texels.
+#[derive(Debug, Clone, Default)]
+pub struct ShadowCascades {
+    /// Settings for the cascades. These can be changed at any time, and take effect on the next update.
+    pub settings: CascadeSettings,
+    cascades: Vec<Cascade>,
+}
+
+impl ShadowCascades {
+    /// Create cascades with `settings`
+    pub fn new(settings: CascadeSettings) -> Self {
+        Self {
+            settings,
+            cascades: Vec::new(),
+        }
+    }
+
+    /// The cascades, nearest first
+    pub fn cascades(&self) -> &[Cascade] {
+        &self.cascades
+    }
+
+    /// The distances from the viewer where each cascade ends, starting at `near`
+    pub fn split_distances(&self, near: f32) -> Vec<f32> {
+        let count = self.settings.count.clamp(MIN_CASCADES, MAX_CASCADES);
+        let far = self.settings.max_distance.max(near);
+        let lambda = self.settings.split_lambda.clamp(0., 1.);
+        (1..=count)
+            .map(|i| {
+                let fraction = i as f32 / count as f32;
+                let logarithmic = near * (far / near).powf(fraction);
+                let uniform = near + (far - near) * fraction;
+                lambda * logarithmic + (1. - lambda) * uniform
+            })
+            .collect()
+    }
+
+    /// Fit the cascades to a view at `gos_from_view` with `frustum`, starting `near` meters in front of it, for a sun
+    /// shining in `light_direction`.
+    pub fn update(
+        &mut self,
+        light_direction: Vec3,
+        gos_from_view: &Affine3A,
+        frustum: &Frustum,
+        near: f32,
+    ) {
+        let light_direction = light_direction.normalize();
+        let up = if light_direction.y.abs() > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let light_from_gos = Mat4::look_at_rh(Vec3::ZERO, light_direction, up);
+        let texel_size_per_radius = 2. / self.settings.resolution as f32;
+
+        let mut slice_near = near;
+        self.cascades.clear();
+        for slice_far in self.split_distances(near) {
+            let corners = slice_corners(gos_from_view, frustum, slice_near, slice_far);
+
+            // Fit a sphere around the slice. Rounding its radius up keeps it from changing size as the view turns.
+            let centroid = corners.iter().fold(Vec3::ZERO, |sum, corner| sum + *corner) / 8.;
+            let radius = corners
+                .iter()
+                .map(|corner| corner.distance(centroid))
+                .fold(0., f32::max);
+            let radius = (radius * 16.).ceil() / 16.;
+
+            // Move the cascade in whole texels, so shadow edges don't crawl as the viewer moves.
+            let texel_size = radius * texel_size_per_radius;
+            let mut center = light_from_gos.transform_point3(centroid);
+            center.x = (center.x / texel_size).floor() * texel_size;
+            center.y = (center.y / texel_size).floor() * texel_size;
+
+            let depth_range = (
+                -center.z - radius - self.settings.caster_distance,
+                -center.z + radius,
+            );
+            let projection = Mat4::orthographic_rh(
+                center.x - radius,
+                center.x + radius,
+                center.y - radius,
+                center.y + radius,
+                depth_range.0,
+                depth_range.1,
+            );
+
+            self.cascades.push(Cascade {
+                near: slice_near,
+                far: slice_far,
+                corners,
+                radius,
+                light_from_gos,
+                clip_from_gos: projection * light_from_gos,
+                center,
+                depth_range,
+            });
+            slice_near = slice_far;
+        }
+    }
+
+    /// Which cascade covers something `distance` meters in front of the viewer, if any
+    pub fn cascade_for_distance(&self, distance: f32) -> Option<usize> {
+        self.cascades
+            .iter()
+            .position(|cascade| distance >= cascade.near && distance <= cascade.far)
+    }
+
+    /// The indices of the cascades a sphere at `center`, in globally oriented stage space, could cast a shadow into.
+    /// Use this to leave things out of shadow maps they can't affect.
+    pub fn cascades_for_sphere(
+        &self,
+        center: Vec3,
+        radius: f32,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.cascades
+            .iter()
+            .enumerate()
+            .filter(move |(_, cascade)| cascade.intersects_sphere(center, radius))
+            .map(|(index, _)| index)
+    }
+
+    /// Lines around the slice of the view each cascade covers, in globally oriented stage space, colored with
+    /// [`CASCADE_DEBUG_COLORS`] - eg. to draw with [`crate::components::projectile::add_tracer_to_world`] while
+    /// tuning [`CascadeSettings`].
+    pub fn debug_lines(&self) -> Vec<(Vec3, Vec3, Vec4)> {
+        // Around the near face, around the far face, then joining them.
+        const EDGES: [(usize, usize); 12] = [
+            (0, 1),
+            (1, 2),
+            (2, 3),
+            (3, 0),
+            (4, 5),
+            (5, 6),
+            (6, 7),
+            (7, 4),
+            (0, 4),
+            (1, 5),
+            (2, 6),
+            (3, 7),
+        ];
+        self.cascades
+            .iter()
+            .zip(CASCADE_DEBUG_COLORS)
+            .flat_map(|(cascade, color)| {
+                EDGES
+                    .iter()
+                    .map(move |(a, b)| (cascade.corners[*a], cascade.corners[*b], color))
+            })
+            .collect()
+    }
+}
+
+/// The corners of the part of the view from `near` to `far` meters in front of it
+fn slice_corners(gos_from_view: &Affine3A, frustum: &Frustum, near: f32, far: f32) -> [Vec3; 8] {
+    let (left, right) = (frustum.left.tan(), frustum.right.tan());
+    let (up, down) = (frustum.up.tan(), frustum.down.tan());
+    let mut corners = [Vec3::ZERO; 8];
+    let face = [(left, down), (right, down), (right, up), (left, up)];
+    for (i, depth) in [near, far].iter().enumerate() {
+        for (j, (x, y)) in face.iter().enumerate() {
+            corners[i * 4 + j] = gos_from_view.transform_point3(Vec3::new(*x, *y, -1.) * *depth);
+        }
+    }
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frustum() -> Frustum {
+        let angle = std::f32::consts::FRAC_PI_4;
+        Frustum {
+            left: -angle,
+            right: angle,
+            up: angle,
+            down: -angle,
+        }
+    }
+
+    #[test]
+    pub fn test_cascade_splits() {
+        let cascades = ShadowCascades::new(CascadeSettings {
+            count: 4,
+            max_distance: 100.,
+            split_lambda: 1.,
+            ..Default::default()
+        });
+        let splits = cascades.split_distances(0.1);
+        assert_eq!(splits.len(), 4);
+        assert!((splits[0] - 0.5623).abs() < 1e-3);
+        assert!((splits[3] - 100.).abs() < 1e-3);
+
+        // Too many cascades are capped.
+        let cascades = ShadowCascades::new(CascadeSettings {
+            count: 8,
+            ..Default::default()
+        });
+        assert_eq!(cascades.split_distances(0.1).len(), MAX_CASCADES);
+    }
+
+    #[test]
+    pub fn test_cascades_are_stable() {
+        let mut cascades = ShadowCascades::default();
+        let sun = Vec3::new(0.3, -1., 0.2);
+        cascades.update(sun, &Affine3A::IDENTITY, &frustum(), 0.1);
+        assert_eq!(cascades.cascades().len(), 3);
+        let before = cascades.cascades()[1];
+
+        // Turning the view doesn't change the size of the cascades..
+        let turned = Affine3A::from_rotation_y(0.3);
+        cascades.update(sun, &turned, &frustum(), 0.1);
+        assert_eq!(cascades.cascades()[1].radius, before.radius);
+
+        // ..moving it less than a texel doesn't move them at all..
+        let nudged = Affine3A::from_translation(Vec3::new(0.0123, 0., 0.));
+        cascades.update(sun, &nudged, &frustum(), 0.1);
+        assert_eq!(
+            cascades.cascades()[1].center.truncate(),
+            before.center.truncate()
+        );
+
+        // ..and moving it further moves them in whole texels, so shadow map texels land in the same places.
+        let moved = Affine3A::from_translation(Vec3::new(1.2345, 0., 0.));
+        cascades.update(sun, &moved, &frustum(), 0.1);
+        let after = cascades.cascades()[1];
+        let texel_size = after.radius * 2. / cascades.settings.resolution as f32;
+        let offset = (after.center - before.center).truncate() / texel_size;
+        assert_ne!(offset, glam::Vec2::ZERO);
+        assert!((offset - offset.round()).abs().max_element() < 1e-2);
+    }
+
+    #[test]
+    pub fn test_cascade_culling() {
+        let mut cascades = ShadowCascades::default();
+        cascades.update(-Vec3::Y, &Affine3A::IDENTITY, &frustum(), 0.1);
+
+        // Something just in front of the viewer shadows the nearest cascade..
+        let near = cascades
+            .cascades_for_sphere(Vec3::new(0., 0., -0.5), 0.1)
+            .collect::<Vec<_>>();
+        assert_eq!(near[0], 0);
+        assert_eq!(cascades.cascade_for_distance(0.5), Some(0));
+
+        // ..but things off to the side are left out of it..
+        let side = cascades
+            .cascades_for_sphere(Vec3::new(9., 0., -2.), 0.1)
+            .collect::<Vec<_>>();
+        assert_eq!(side, vec![1, 2]);
+
+        // ..a tall tower far away only shadows the furthest cascade..
+        let far = cascades
+            .cascades_for_sphere(Vec3::new(0., 30., -40.), 1.)
+            .collect::<Vec<_>>();
+        assert_eq!(far, vec![2]);
+
+        // ..and something behind the viewer doesn't shadow any of them.
+        assert_eq!(
+            cascades
+                .cascades_for_sphere(Vec3::new(0., 0., 60.), 1.)
+                .count(),
+            0
+        );
+
+        assert_eq!(cascades.debug_lines().len(), 3 * 12);
+        assert_eq!(cascades.debug_lines()[12].2, CASCADE_DEBUG_COLORS[1]);
+    }
+}
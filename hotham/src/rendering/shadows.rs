@@ -0,0 +1,807 @@
+use std::{collections::HashMap, mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, Mat4, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use vk_shader_macros::include_glsl;
+
+use crate::{
+    contexts::{
+        render_context::{create_push_constant, create_shader, InstancedPrimitive},
+        VulkanContext,
+    },
+    rendering::{
+        camera::{extract_planes_from_frustum, Frustum},
+        image::Image,
+        light::{Light, LIGHT_TYPE_DIRECTIONAL, LIGHT_TYPE_SPOT, MAX_LIGHTS},
+        resources::Resources,
+        shadow_atlas::{ShadowAtlas, ShadowTile},
+        shadow_cascades::{ShadowCascades, MAX_CASCADES},
+        vertex::Vertex,
+    },
+    DEPTH_FORMAT,
+};
+
+static SHADOW_VERT: &[u32] = include_glsl!("src/shaders/shadow.vert", target: vulkan1_1);
+
+/// How close to a spotlight things can cast shadows, in meters
+const SPOTLIGHT_NEAR: f32 = 0.05;
+
+/// How far spotlights with an infinite range cast shadows, in meters
+const SPOTLIGHT_DEFAULT_RANGE: f32 = 50.;
+
+/// The widest spotlight that can cast shadows, in radians
+const SPOTLIGHT_MAX_FOV: f32 = 170. * std::f32::consts::PI / 180.;
+
+/// How a light's shadow map is sampled, as it's sent to the PBR fragment shader
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct LightShadow {
+    /// Transforms a point in globally oriented stage space to its place in the shadow atlas: `xy` is the texture
+    /// coordinate, and `z` the depth to compare against
+    pub atlas_from_gos: Mat4,
+    /// The light's tile in the atlas, as texture coordinates - see [`ShadowTile::uv_rect`]
+    pub tile: Vec4,
+    /// x = 1 if the light has a shadow map, 0 if not. y = depth bias. z = 1 if the light's shadows are split into
+    /// [`super::scene_data::SceneData::shadow_cascades`], or for a cascade, how far in front of the viewer it reaches.
+    /// w = unused
+    pub params: Vec4,
+}
+
+impl Default for LightShadow {
+    fn default() -> Self {
+        Self {
+            atlas_from_gos: Mat4::IDENTITY,
+            tile: Vec4::ZERO,
+            params: Vec4::ZERO,
+        }
+    }
+}
+
+/// Shadow maps for the scene's directional lights and spotlights, drawn into a [`ShadowAtlas`].
+///
+/// Pick the lights that cast shadows with [`ShadowAtlas::set_shadow_caster`], then turn shadows on with
+/// [`crate::contexts::RenderContext::enable_shadows`]. Each frame the atlas decides which shadow maps to redraw, and
+/// they're drawn before the world from every mesh, whether or not the headset can see it. Directional lights cover
+/// [`Shadows::directional_distance`] around the viewer, unless they're split into [`Shadows::cascades`]; point lights
+/// don't cast shadows yet.
+pub struct Shadows {
+    /// Which lights cast shadows, and where their shadow maps go
+    pub atlas: ShadowAtlas,
+    /// How far from the viewer directional lights cast shadows, in meters
+    pub directional_distance: f32,
+    /// How much closer to the light a surface must be than the shadow map says before it's in shadow. Raise it if
+    /// surfaces are covered in stripes of shadow, and lower it if shadows start too far from what casts them.
+    pub depth_bias: f32,
+    /// Cascaded shadow maps for the sun. When set, the first directional light that casts shadows has its tile in the
+    /// atlas split into a quarter for each cascade, and each fragment is shadowed by the nearest cascade that reaches
+    /// it. `None` by default.
+    pub cascades: Option<ShadowCascades>,
+    pass: Option<ShadowPass>,
+    // Where each light's shadow map was last drawn, and with what, in global space.
+    drawn: [Option<(ShadowTile, Mat4)>; MAX_LIGHTS],
+    // Where the sun's cascades were last drawn.
+    drawn_cascades: Option<DrawnCascades>,
+}
+
+/// Where the sun's cascades were last drawn - see [`Shadows::cascades`].
+#[derive(Debug, Clone)]
+struct DrawnCascades {
+    /// The light the cascades are for
+    light_index: usize,
+    /// The light's tile in the atlas, which the cascades were split from
+    tile: ShadowTile,
+    /// Each cascade's tile, the view projection it was drawn with in global space and how far it reaches, nearest
+    /// first
+    cascades: Vec<(ShadowTile, Mat4, f32)>,
+}
+
+impl Default for Shadows {
+    fn default() -> Self {
+        Self {
+            atlas: Default::default(),
+            directional_distance: 10.,
+            depth_bias: 0.001,
+            cascades: None,
+            pass: None,
+            drawn: [None; MAX_LIGHTS],
+            drawn_cascades: None,
+        }
+    }
+}
+
+impl Shadows {
+    /// Are shadow maps being drawn?
+    pub fn is_enabled(&self) -> bool {
+        self.pass.is_some()
+    }
+
+    pub(crate) fn enable(
+        &mut self,
+        vulkan_context: &VulkanContext,
+        set_layout: vk::DescriptorSetLayout,
+        shadow_map: &Image,
+    ) -> Result<()> {
+        self.disable(&vulkan_context.device);
+        self.pass = Some(ShadowPass::new(vulkan_context, set_layout, shadow_map)?);
+        Ok(())
+    }
+
+    pub(crate) fn disable(&mut self, device: &ash::Device) {
+        if let Some(pass) = self.pass.take() {
+            unsafe { pass.destroy(device) };
+        }
+        self.drawn = [None; MAX_LIGHTS];
+        self.drawn_cascades = None;
+    }
+
+    /// Draw the shadow maps the atlas picks for this frame, from `primitive_map`, and work out how to sample every
+    /// light's shadow map, and the sun's cascades. `lights` are in global space, and the cascades are fitted to the
+    /// view at `gos_from_view` with `frustum`, starting `near` meters in front of it. Must be outside any render pass.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) unsafe fn draw(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        resources: &Resources,
+        primitive_map: &HashMap<u32, InstancedPrimitive>,
+        lights: &[Light; MAX_LIGHTS],
+        viewer: Vec3,
+        gos_from_global: &Affine3A,
+        gos_from_view: &Affine3A,
+        frustum: &Frustum,
+        near: f32,
+    ) -> ([LightShadow; MAX_LIGHTS], [LightShadow; MAX_CASCADES]) {
+        let mut shadows = [LightShadow::default(); MAX_LIGHTS];
+        let mut cascade_shadows = [LightShadow::default(); MAX_CASCADES];
+        let pass = match &self.pass {
+            Some(pass) => pass,
+            None => return (shadows, cascade_shadows),
+        };
+        let global_from_gos = Mat4::from(gos_from_global.inverse());
+
+        let updates = self.atlas.update(lights, viewer).to_vec();
+        let cascaded_light = self.cascaded_light(lights);
+        if cascaded_light.is_none() {
+            self.drawn_cascades = None;
+        }
+
+        let mut casters = Vec::new();
+        for update in updates {
+            let light = &lights[update.light_index];
+            if Some(update.light_index) == cascaded_light {
+                let (cascades, direction) =
+                    match (&mut self.cascades, light.direction.try_normalize()) {
+                        (Some(cascades), Some(direction)) => (cascades, direction),
+                        _ => continue,
+                    };
+                cascades.settings.resolution = update.tile.size / 2;
+                cascades.update(
+                    gos_from_global.transform_vector3(direction),
+                    gos_from_view,
+                    frustum,
+                    near,
+                );
+                let drawn = cascade_tiles(cascades, update.tile, gos_from_global);
+                casters.extend(drawn.iter().map(|(tile, clip_from_global, _)| {
+                    (*tile, *clip_from_global * global_from_gos)
+                }));
+                self.drawn[update.light_index] = None;
+                self.drawn_cascades = Some(DrawnCascades {
+                    light_index: update.light_index,
+                    tile: update.tile,
+                    cascades: drawn,
+                });
+                continue;
+            }
+
+            // The sun may have stopped being split into cascades.
+            if matches!(&self.drawn_cascades, Some(drawn) if drawn.light_index == update.light_index)
+            {
+                self.drawn_cascades = None;
+            }
+            if let Some(clip_from_global) =
+                light_clip_from_global(light, viewer, self.directional_distance, update.tile.size)
+            {
+                self.drawn[update.light_index] = Some((update.tile, clip_from_global));
+                casters.push((update.tile, clip_from_global * global_from_gos));
+            }
+        }
+        if !casters.is_empty() {
+            pass.draw(
+                device,
+                command_buffer,
+                descriptor_set,
+                resources,
+                primitive_map,
+                &casters,
+            );
+        }
+
+        // Lights whose tile has moved since their shadow map was drawn have nothing to show yet.
+        let atlas_size = self.atlas.size();
+        for (light_index, shadow) in shadows.iter_mut().enumerate() {
+            if let Some((tile, clip_from_global)) = self.drawn[light_index] {
+                if self.atlas.tile(light_index) == Some(tile) {
+                    let tile = tile.uv_rect(atlas_size);
+                    *shadow = LightShadow {
+                        atlas_from_gos: atlas_from_clip(tile) * clip_from_global * global_from_gos,
+                        tile,
+                        params: Vec4::new(1., self.depth_bias, 0., 0.),
+                    };
+                }
+            }
+        }
+        if let Some(drawn) = &self.drawn_cascades {
+            if self.atlas.tile(drawn.light_index) == Some(drawn.tile) {
+                cascade_shadows = cascade_shadows_from(
+                    &drawn.cascades,
+                    atlas_size,
+                    self.depth_bias,
+                    global_from_gos,
+                );
+                shadows[drawn.light_index].params = Vec4::new(1., self.depth_bias, 1., 0.);
+            }
+        }
+        (shadows, cascade_shadows)
+    }
+
+    /// The light whose shadows are split into [`Shadows::cascades`], if any: the first directional light with a tile in
+    /// the atlas.
+    fn cascaded_light(&self, lights: &[Light; MAX_LIGHTS]) -> Option<usize> {
+        self.cascades.as_ref()?;
+        (0..MAX_LIGHTS).find(|light_index| {
+            lights[*light_index].light_type == LIGHT_TYPE_DIRECTIONAL
+                && self.atlas.tile(*light_index).is_some()
+        })
+    }
+}
+
+/// Split `tile` into a quarter for each of `cascades`, nearest first, with the view projection to draw it with in
+/// global space and how far in front of the viewer it reaches.
+fn cascade_tiles(
+    cascades: &ShadowCascades,
+    tile: ShadowTile,
+    gos_from_global: &Affine3A,
+) -> Vec<(ShadowTile, Mat4, f32)> {
+    let gos_from_global = Mat4::from(*gos_from_global);
+    cascades
+        .cascades()
+        .iter()
+        .zip(tile.split())
+        .map(|(cascade, tile)| (tile, cascade.clip_from_gos * gos_from_global, cascade.far))
+        .collect()
+}
+
+/// How the PBR fragment shader samples each of the cascades drawn into `drawn` - see [`cascade_tiles`].
+fn cascade_shadows_from(
+    drawn: &[(ShadowTile, Mat4, f32)],
+    atlas_size: u32,
+    depth_bias: f32,
+    global_from_gos: Mat4,
+) -> [LightShadow; MAX_CASCADES] {
+    let mut shadows = [LightShadow::default(); MAX_CASCADES];
+    for ((tile, clip_from_global, far), shadow) in drawn.iter().zip(&mut shadows) {
+        let tile = tile.uv_rect(atlas_size);
+        *shadow = LightShadow {
+            atlas_from_gos: atlas_from_clip(tile) * *clip_from_global * global_from_gos,
+            tile,
+            params: Vec4::new(1., depth_bias, *far, 0.),
+        };
+    }
+    shadows
+}
+
+/// The view projection to draw `light`'s shadow map with, in global space, for a tile `tile_size` texels across.
+/// Directional lights are centred on `viewer`, covering `directional_distance` around them. `None` if the light can't
+/// cast shadows.
+pub fn light_clip_from_global(
+    light: &Light,
+    viewer: Vec3,
+    directional_distance: f32,
+    tile_size: u32,
+) -> Option<Mat4> {
+    let direction = light.direction.try_normalize()?;
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+
+    match light.light_type {
+        LIGHT_TYPE_DIRECTIONAL => {
+            let view = Mat4::look_at_rh(Vec3::ZERO, direction, up);
+            let radius = directional_distance;
+
+            // Move the shadow map in whole texels, so shadows don't shimmer as the viewer moves.
+            let texel = 2. * radius / tile_size as f32;
+            let centre = view.transform_point3(viewer);
+            let x = (centre.x / texel).round() * texel;
+            let y = (centre.y / texel).round() * texel;
+
+            // Catch casters between the viewer and the light, as well as behind the viewer.
+            let projection = Mat4::orthographic_rh(
+                x - radius,
+                x + radius,
+                y - radius,
+                y + radius,
+                -centre.z - 2. * radius,
+                -centre.z + 2. * radius,
+            );
+            Some(projection * view)
+        }
+        LIGHT_TYPE_SPOT => {
+            let fov =
+                (2. * light.outer_cone_cos.clamp(-1., 1.).acos()).clamp(0.01, SPOTLIGHT_MAX_FOV);
+            let range = if light.range > 0. {
+                light.range
+            } else {
+                SPOTLIGHT_DEFAULT_RANGE
+            };
+            let view = Mat4::look_at_rh(light.position, light.position + direction, up);
+            Some(Mat4::perspective_rh(fov, 1., SPOTLIGHT_NEAR, range) * view)
+        }
+        _ => None,
+    }
+}
+
+/// Maps clip space onto `tile`, a rectangle in the shadow atlas as texture coordinates.
+fn atlas_from_clip(tile: Vec4) -> Mat4 {
+    Mat4::from_translation(Vec3::new(tile.x, tile.y, 0.))
+        * Mat4::from_scale(Vec3::new(tile.z, tile.w, 1.))
+        * Mat4::from_translation(Vec3::new(0.5, 0.5, 0.))
+        * Mat4::from_scale(Vec3::new(0.5, 0.5, 1.))
+}
+
+/// A mesh drawn into a shadow map, as it's sent to the shadow vertex shader
+#[repr(C)]
+struct ShadowCaster {
+    clip_from_local: Mat4,
+    skin_id: u32,
+}
+
+/// Draws meshes' depth into the shadow atlas.
+struct ShadowPass {
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+}
+
+impl ShadowPass {
+    fn new(
+        vulkan_context: &VulkanContext,
+        set_layout: vk::DescriptorSetLayout,
+        shadow_map: &Image,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let render_pass = create_render_pass(vulkan_context)?;
+        let framebuffer = unsafe {
+            device.create_framebuffer(
+                &vk::FramebufferCreateInfo::builder()
+                    .render_pass(render_pass)
+                    .attachments(slice_from_ref(&shadow_map.view))
+                    .width(shadow_map.extent.width)
+                    .height(shadow_map.extent.height)
+                    .layers(1),
+                None,
+            )
+        }?;
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(size_of::<ShadowCaster>() as _)
+            .build();
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(slice_from_ref(&set_layout))
+                    .push_constant_ranges(slice_from_ref(&push_constant_range)),
+                None,
+            )
+        }?;
+        let pipeline = create_pipeline(vulkan_context, pipeline_layout, render_pass)?;
+
+        Ok(Self {
+            pipeline,
+            pipeline_layout,
+            render_pass,
+            framebuffer,
+            extent: shadow_map.extent,
+        })
+    }
+
+    /// Draw every instance in `primitive_map` into each of `casters`' tiles, from its view projection in globally
+    /// oriented stage space.
+    unsafe fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        resources: &Resources,
+        primitive_map: &HashMap<u32, InstancedPrimitive>,
+        casters: &[(ShadowTile, Mat4)],
+    ) {
+        device.cmd_begin_render_pass(
+            command_buffer,
+            &vk::RenderPassBeginInfo::builder()
+                .render_pass(self.render_pass)
+                .framebuffer(self.framebuffer)
+                .render_area(vk::Rect2D {
+                    offset: Default::default(),
+                    extent: self.extent,
+                }),
+            vk::SubpassContents::INLINE,
+        );
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            slice_from_ref(&descriptor_set),
+            &[],
+        );
+        device.cmd_bind_index_buffer(
+            command_buffer,
+            resources.index_buffer.buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            slice_from_ref(&resources.vertex_buffer.buffer),
+            &[0],
+        );
+
+        for (tile, clip_from_gos) in casters {
+            let rect = vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: tile.x as _,
+                    y: tile.y as _,
+                },
+                extent: vk::Extent2D {
+                    width: tile.size,
+                    height: tile.size,
+                },
+            };
+            let viewport = vk::Viewport {
+                x: tile.x as _,
+                y: tile.y as _,
+                width: tile.size as _,
+                height: tile.size as _,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            device.cmd_set_viewport(command_buffer, 0, slice_from_ref(&viewport));
+            device.cmd_set_scissor(command_buffer, 0, slice_from_ref(&rect));
+
+            // The rest of the atlas holds other lights' shadow maps, so only clear this tile.
+            device.cmd_clear_attachments(
+                command_buffer,
+                &[vk::ClearAttachment {
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    color_attachment: 0,
+                    clear_value: vk::ClearValue {
+                        depth_stencil: vk::ClearDepthStencilValue {
+                            depth: 1.0,
+                            stencil: 0,
+                        },
+                    },
+                }],
+                &[vk::ClearRect {
+                    rect,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                }],
+            );
+
+            // Skip anything outside the light's view.
+            let planes = extract_planes_from_frustum(clip_from_gos);
+            for instanced_primitive in primitive_map.values() {
+                let primitive = &instanced_primitive.primitive;
+                for (index, bounding_sphere) in
+                    instanced_primitive.bounding_spheres.iter().enumerate()
+                {
+                    let center = bounding_sphere.truncate().extend(1.);
+                    let distances = planes * center;
+                    if distances.min_element() < -bounding_sphere.w {
+                        continue;
+                    }
+
+                    let caster = ShadowCaster {
+                        clip_from_local: *clip_from_gos
+                            * Mat4::from(instanced_primitive.gos_from_local[index]),
+                        skin_id: instanced_primitive.skin_ids[index],
+                    };
+                    device.cmd_push_constants(
+                        command_buffer,
+                        self.pipeline_layout,
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        create_push_constant(&caster),
+                    );
+                    device.cmd_draw_indexed(
+                        command_buffer,
+                        primitive.indices_count,
+                        1,
+                        primitive.index_buffer_offset,
+                        primitive.vertex_buffer_offset as _,
+                        0,
+                    );
+                }
+            }
+        }
+
+        device.cmd_end_render_pass(command_buffer);
+    }
+
+    unsafe fn destroy(&self, device: &ash::Device) {
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.pipeline_layout, None);
+        device.destroy_framebuffer(self.framebuffer, None);
+        device.destroy_render_pass(self.render_pass, None);
+    }
+}
+
+fn create_render_pass(vulkan_context: &VulkanContext) -> Result<vk::RenderPass> {
+    // Shadow maps that aren't redrawn keep what they had, so the atlas is loaded rather than cleared.
+    let depth_attachment = vk::AttachmentDescription::builder()
+        .format(DEPTH_FORMAT)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::LOAD)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+        .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+        .build();
+    let depth_reference = vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        .build();
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_reference)
+        .build();
+
+    // Wait for last frame's shading to finish reading the atlas before drawing over it, and for the shadow maps to be
+    // drawn before this frame's shading reads them.
+    let depth_stages =
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+    let dependencies = [
+        vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_stage_mask(depth_stages)
+            .dst_access_mask(
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(depth_stages)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build(),
+    ];
+
+    let render_pass = unsafe {
+        vulkan_context.device.create_render_pass(
+            &vk::RenderPassCreateInfo::builder()
+                .attachments(slice_from_ref(&depth_attachment))
+                .subpasses(slice_from_ref(&subpass))
+                .dependencies(&dependencies),
+            None,
+        )
+    }?;
+    Ok(render_pass)
+}
+
+fn create_pipeline(
+    vulkan_context: &VulkanContext,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+) -> Result<vk::Pipeline> {
+    let device = &vulkan_context.device;
+
+    // Only depth is written, so there's no fragment shader.
+    let (vertex_shader, vertex_stage) =
+        create_shader(SHADOW_VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
+
+    let vertex_binding_description = vk::VertexInputBindingDescription::builder()
+        .binding(0)
+        .stride(size_of::<Vertex>() as _)
+        .input_rate(vk::VertexInputRate::VERTEX)
+        .build();
+    let vertex_attribute_descriptions = Vertex::attribute_descriptions();
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_attribute_descriptions(&vertex_attribute_descriptions)
+        .vertex_binding_descriptions(slice_from_ref(&vertex_binding_description));
+    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    // Each shadow map is drawn into its own tile, so the viewport is set per light.
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    // Both sides are drawn, so thin and open meshes still cast shadows. Sloped surfaces are pushed away from the light
+    // a little more, so they don't shadow themselves.
+    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .depth_bias_enable(true)
+        .depth_bias_constant_factor(1.25)
+        .depth_bias_slope_factor(1.75)
+        .line_width(1.0);
+    let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+    let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(vk::CompareOp::LESS);
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder();
+
+    let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(slice_from_ref(&vertex_stage))
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_state)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterization_state)
+        .multisample_state(&multisample_state)
+        .depth_stencil_state(&depth_stencil_state)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0)
+        .build();
+
+    let pipelines = unsafe {
+        device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            slice_from_ref(&create_info),
+            None,
+        )
+    }
+    .map_err(|(_, r)| r)?;
+
+    unsafe {
+        device.destroy_shader_module(vertex_shader, None);
+    }
+
+    Ok(pipelines[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_spotlight_shadow() {
+        let light = Light::new_spotlight(
+            Vec3::NEG_Y,
+            10.,
+            10.,
+            Vec3::ONE,
+            Vec3::new(0., 3., 0.),
+            0.,
+            0.5,
+        );
+        let clip_from_global = light_clip_from_global(&light, Vec3::ZERO, 10., 512).unwrap();
+
+        // Straight below the light is in the middle of the shadow map, and further away is deeper.
+        let floor = clip_from_global.project_point3(Vec3::ZERO);
+        let table = clip_from_global.project_point3(Vec3::new(0., 1., 0.));
+        assert_relative_eq!(floor.x, 0., epsilon = 0.0001);
+        assert_relative_eq!(floor.y, 0., epsilon = 0.0001);
+        assert!(table.z < floor.z && floor.z < 1.);
+
+        // Things outside the cone are outside the shadow map.
+        let outside = clip_from_global.project_point3(Vec3::new(3., 0., 0.));
+        assert!(outside.x.abs() > 1.);
+
+        // Point lights don't cast shadows yet.
+        let point = Light::new_point(Vec3::ZERO, 10., 10., Vec3::ONE);
+        assert!(light_clip_from_global(&point, Vec3::ZERO, 10., 512).is_none());
+    }
+
+    #[test]
+    pub fn test_directional_shadow() {
+        let light = Light::new_directional(Vec3::new(0.3, -1., 0.2), 5., Vec3::ONE);
+        let viewer = Vec3::new(1.2, 1.6, -3.4);
+        let clip_from_global = light_clip_from_global(&light, viewer, 10., 1024).unwrap();
+
+        // The viewer is in the shadow map, and so is a tall caster between them and the light.
+        for point in [viewer, viewer - light.direction * 10.] {
+            let clip = clip_from_global.project_point3(point);
+            assert!(clip.x.abs() < 1. && clip.y.abs() < 1.);
+            assert!(clip.z > 0. && clip.z < 1.);
+        }
+
+        // Moving the viewer moves the shadow map in whole texels, so shadows don't shimmer.
+        let moved =
+            light_clip_from_global(&light, viewer + Vec3::new(0.37, 0., 0.21), 10., 1024).unwrap();
+        let offset = moved.project_point3(Vec3::ZERO) - clip_from_global.project_point3(Vec3::ZERO);
+        let texels = offset.truncate() * 512.;
+        assert_relative_eq!(texels, texels.round(), epsilon = 0.01);
+    }
+
+    #[test]
+    pub fn test_cascaded_shadows() {
+        let angle = std::f32::consts::FRAC_PI_4;
+        let frustum = Frustum {
+            left: -angle,
+            right: angle,
+            up: angle,
+            down: -angle,
+        };
+        let mut cascades = ShadowCascades::default();
+        cascades.update(Vec3::new(0.3, -1., 0.2), &Affine3A::IDENTITY, &frustum, 0.1);
+
+        // Each cascade gets a quarter of the sun's tile, nearest first..
+        let gos_from_global = Affine3A::from_translation(Vec3::new(0., 0., 1.));
+        let tile = ShadowTile {
+            x: 0,
+            y: 0,
+            size: 2048,
+        };
+        let drawn = cascade_tiles(&cascades, tile, &gos_from_global);
+        assert_eq!(drawn.len(), 3);
+        assert_eq!(drawn[0].0, tile.split()[0]);
+        assert_eq!(drawn[1].0.size, 1024);
+        assert_eq!(drawn[2].2, cascades.cascades()[2].far);
+
+        // ..and something just in front of the viewer lands in the nearest cascade's quarter of the atlas.
+        let global_from_gos = Mat4::from(gos_from_global.inverse());
+        let shadows = cascade_shadows_from(&drawn, 4096, 0.001, global_from_gos);
+        let atlas_position = shadows[0]
+            .atlas_from_gos
+            .project_point3(Vec3::new(0., -0.5, -1.));
+        let quarter = shadows[0].tile;
+        assert!(atlas_position.x > quarter.x && atlas_position.x < quarter.x + quarter.z);
+        assert!(atlas_position.y > quarter.y && atlas_position.y < quarter.y + quarter.w);
+        assert_eq!(shadows[0].params.z, cascades.cascades()[0].far);
+
+        // Cascades that weren't drawn aren't sampled.
+        assert_eq!(shadows[3].params.x, 0.);
+    }
+
+    #[test]
+    pub fn test_atlas_from_clip() {
+        let tile = ShadowTile {
+            x: 512,
+            y: 0,
+            size: 512,
+        }
+        .uv_rect(1024);
+        let atlas_from_clip = atlas_from_clip(tile);
+        assert_relative_eq!(
+            atlas_from_clip.transform_point3(Vec3::new(-1., -1., 0.25)),
+            Vec3::new(0.5, 0., 0.25)
+        );
+        assert_relative_eq!(
+            atlas_from_clip.transform_point3(Vec3::new(1., 1., 0.25)),
+            Vec3::new(1., 0.5, 0.25)
+        );
+    }
+}
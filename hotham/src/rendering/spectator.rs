@@ -0,0 +1,358 @@
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, EulerRot, Quat, Vec3};
+use hecs::World;
+use openxr as xr;
+
+use crate::{
+    contexts::{RenderContext, VulkanContext},
+    rendering::{
+        descriptors::SPECTATOR_DESCRIPTOR_SET,
+        frame::Frame,
+        image::Image,
+        readback::{Readback, ReadbackId},
+        swapchain::{Swapchain, SwapchainInfo},
+    },
+    systems::rendering::{begin, draw_world, end},
+    util::{posef_from_affine, read_image_from_gpu},
+};
+
+/// Where a [`SpectatorCamera`] watches from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectatorMode {
+    /// Looking over the player's shoulder. `offset` is where the camera sits, relative to the player's head as they
+    /// turn, ignoring where they're looking up or down.
+    ThirdPerson {
+        /// Where the camera sits relative to the player's head, in meters
+        offset: Vec3,
+    },
+    /// Looking out of the player's eyes, with the horizon kept level.
+    FirstPerson,
+}
+
+impl Default for SpectatorMode {
+    fn default() -> Self {
+        SpectatorMode::ThirdPerson {
+            offset: Vec3::new(0.3, 0.3, 1.2),
+        }
+    }
+}
+
+/// A camera for people watching someone play, eg. on a stream.
+///
+/// Raw head movement is fine in a headset but horrible to watch, so the camera follows the player's head with its
+/// movement smoothed out, and uses a wider field of view than the headset.
+#[derive(Debug, Clone)]
+pub struct SpectatorCamera {
+    /// Where the camera watches from
+    pub mode: SpectatorMode,
+    /// The horizontal field of view, in radians
+    pub horizontal_fov: f32,
+    /// How long the camera takes to catch up with the player's head, in seconds. Larger is smoother.
+    pub position_smoothing: f32,
+    /// How long the camera takes to turn to where the player's looking, in seconds. Larger is smoother.
+    pub rotation_smoothing: f32,
+    pose: Option<Affine3A>,
+}
+
+impl Default for SpectatorCamera {
+    fn default() -> Self {
+        Self {
+            mode: Default::default(),
+            horizontal_fov: 100_f32.to_radians(),
+            position_smoothing: 0.2,
+            rotation_smoothing: 0.3,
+            pose: None,
+        }
+    }
+}
+
+impl SpectatorCamera {
+    /// A spectator camera that watches from `mode`, with the default field of view and smoothing.
+    pub fn new(mode: SpectatorMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Where the camera is, in stage space. `None` until the first update.
+    pub fn pose(&self) -> Option<Affine3A> {
+        self.pose
+    }
+
+    /// Move the camera on by `delta_time` seconds, following the HMD at `hmd_in_stage`. Returns the new pose of the
+    /// camera, in stage space.
+    pub fn update(&mut self, hmd_in_stage: Affine3A, delta_time: f32) -> Affine3A {
+        let (_, hmd_rotation, hmd_position) = hmd_in_stage.to_scale_rotation_translation();
+        let (yaw, pitch, _) = hmd_rotation.to_euler(EulerRot::YXZ);
+
+        let (target_position, target_rotation) = match self.mode {
+            SpectatorMode::ThirdPerson { offset } => {
+                let position = hmd_position + Quat::from_rotation_y(yaw) * offset;
+                let looking_at_head =
+                    Affine3A::look_at_rh(position, hmd_position, Vec3::Y).inverse();
+                let (_, rotation, _) = looking_at_head.to_scale_rotation_translation();
+                (position, rotation)
+            }
+            // Rolling the view is the most uncomfortable thing to watch, so it's removed entirely.
+            SpectatorMode::FirstPerson => (
+                hmd_position,
+                Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.),
+            ),
+        };
+
+        let (position, rotation) = match self.pose {
+            Some(pose) => {
+                let (_, rotation, position) = pose.to_scale_rotation_translation();
+                (
+                    position.lerp(
+                        target_position,
+                        smoothing_factor(delta_time, self.position_smoothing),
+                    ),
+                    rotation.slerp(
+                        target_rotation,
+                        smoothing_factor(delta_time, self.rotation_smoothing),
+                    ),
+                )
+            }
+            // Snap to the player on the first update, rather than flying in from the origin.
+            None => (target_position, target_rotation),
+        };
+
+        let pose = Affine3A::from_rotation_translation(rotation, position);
+        self.pose = Some(pose);
+        pose
+    }
+
+    /// The views to render the camera with, for an image of the given `aspect_ratio` (width / height).
+    ///
+    /// The renderer always draws two views, so both are the same.
+    pub fn views(&self, aspect_ratio: f32) -> [xr::View; 2] {
+        let half_horizontal = self.horizontal_fov / 2.;
+        let half_vertical = (half_horizontal.tan() / aspect_ratio).atan();
+        let view = xr::View {
+            pose: posef_from_affine(self.pose.unwrap_or(Affine3A::IDENTITY)),
+            fov: xr::Fovf {
+                angle_left: -half_horizontal,
+                angle_right: half_horizontal,
+                angle_up: half_vertical,
+                angle_down: -half_vertical,
+            },
+        };
+        [view, view]
+    }
+}
+
+/// How far to move towards a target this frame, for exponential smoothing with a time constant of `smoothing`.
+/// This is independent of the frame rate.
+fn smoothing_factor(delta_time: f32, smoothing: f32) -> f32 {
+    if smoothing <= 0. {
+        return 1.;
+    }
+    1. - (-delta_time / smoothing).exp()
+}
+
+/// A [`SpectatorCamera`] rendered into its own image, separate from what's shown in the headset.
+///
+/// The spectator view shares the renderer's pipelines and resources, so everything that's loaded for the headset can
+/// be seen by spectators, but has its own image and per-frame buffers. The pipelines' viewports are fixed when the
+/// renderer is created, so the image is the same size as the headset's eye images.
+///
+/// Use the `spectator_system` to render it each frame, then read it back with [`SpectatorView::read_image`] to send
+/// it to a desktop window or video encoder.
+pub struct SpectatorView {
+    /// The camera being rendered
+    pub camera: SpectatorCamera,
+    /// What the camera sees. The first layer holds the view; the renderer always draws two.
+    pub image: Image,
+    frame: Frame,
+    swapchain: Swapchain,
+}
+
+impl SpectatorView {
+    /// Create somewhere to render `camera` to.
+    pub fn new(
+        vulkan_context: &VulkanContext,
+        render_context: &RenderContext,
+        camera: SpectatorCamera,
+    ) -> Result<Self> {
+        let resolution = render_context.swapchain.render_area.extent;
+        let format = render_context.swapchain.format;
+        let image = vulkan_context.create_image(
+            format,
+            &resolution,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            2,
+            1,
+        )?;
+        let swapchain = Swapchain::new(
+            &SwapchainInfo {
+                resolution,
+                images: vec![image.handle],
+                format,
+            },
+            vulkan_context,
+            render_context.render_pass,
+        );
+        let frame = Frame::new(
+            vulkan_context,
+            SPECTATOR_DESCRIPTOR_SET,
+            &render_context.descriptors,
+        )?;
+
+        Ok(Self {
+            camera,
+            image,
+            frame,
+            swapchain,
+        })
+    }
+
+    /// Render `world` from the spectator camera, following the HMD at `hmd_in_stage`.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called between [`begin`] and [`end`] of the main render pass.
+    pub unsafe fn render(
+        &mut self,
+        world: &mut World,
+        vulkan_context: &VulkanContext,
+        render_context: &mut RenderContext,
+        hmd_in_stage: Affine3A,
+        delta_time: f32,
+    ) {
+        self.camera.update(hmd_in_stage, delta_time);
+        let extent = self.image.extent;
+        let views = self
+            .camera
+            .views(extent.width as f32 / extent.height as f32);
+
+        // Borrow the renderer, pointing it at our image and buffers instead of the headset's.
+        let cameras = render_context.cameras.clone();
+        let headset_views = render_context.views.clone();
+        let scene_data = render_context.scene_data;
+        let render_scale = std::mem::replace(&mut render_context.render_scale, 1.);
+        self.swap_targets(render_context);
+
+        render_context.begin_frame(vulkan_context);
+        begin(world, vulkan_context, render_context, &views, 0);
+        draw_world(vulkan_context, render_context);
+        end(vulkan_context, render_context);
+        render_context.submit_frame(vulkan_context);
+
+        // ..then give it back, as if nothing had happened.
+        self.swap_targets(render_context);
+        render_context.cameras = cameras;
+        render_context.views = headset_views;
+        render_context.scene_data = scene_data;
+        render_context.render_scale = render_scale;
+    }
+
+    /// Copy what the camera saw last back from the GPU. This waits for the GPU to be idle, so it's best not done every
+    /// frame if the frame rate matters - see [`SpectatorView::request_image`].
+    pub fn read_image(&self, vulkan_context: &VulkanContext) -> image::RgbaImage {
+        unsafe { read_image_from_gpu(vulkan_context, &self.image) }
+    }
+
+    /// Copy what the camera saw last back from the GPU without waiting for it. The image arrives with `readback` a
+    /// frame or two later, so this is fine to do every frame, eg. for recording.
+    pub fn request_image(
+        &self,
+        vulkan_context: &VulkanContext,
+        readback: &mut Readback,
+    ) -> Result<ReadbackId> {
+        readback.read_image(
+            vulkan_context,
+            &self.image,
+            0,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        )
+    }
+
+    fn swap_targets(&mut self, render_context: &mut RenderContext) {
+        let frame_index = render_context.frame_index;
+        let descriptors = &mut render_context.descriptors;
+        std::mem::swap(&mut render_context.frames[frame_index], &mut self.frame);
+        std::mem::swap(&mut render_context.swapchain, &mut self.swapchain);
+        descriptors.sets.swap(frame_index, SPECTATOR_DESCRIPTOR_SET);
+        descriptors
+            .compute_sets
+            .swap(frame_index, SPECTATOR_DESCRIPTOR_SET);
+        descriptors
+            .fog_compute_sets
+            .swap(frame_index, SPECTATOR_DESCRIPTOR_SET);
+        render_context
+            .crowds
+            .frames
+            .swap(frame_index, SPECTATOR_DESCRIPTOR_SET);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_spectator_camera() {
+        let hmd_in_stage = Affine3A::from_rotation_translation(
+            Quat::from_euler(EulerRot::YXZ, 0.5, 0.2, 0.3),
+            Vec3::new(1., 1.6, 0.),
+        );
+
+        // A first person camera should snap to the player's head, without any roll..
+        let mut camera = SpectatorCamera::new(SpectatorMode::FirstPerson);
+        let pose = camera.update(hmd_in_stage, 1. / 72.);
+        let (_, rotation, position) = pose.to_scale_rotation_translation();
+        assert_relative_eq!(position, Vec3::new(1., 1.6, 0.));
+        let (yaw, pitch, roll) = rotation.to_euler(EulerRot::YXZ);
+        assert_relative_eq!(yaw, 0.5, epsilon = 0.0001);
+        assert_relative_eq!(pitch, 0.2, epsilon = 0.0001);
+        assert_relative_eq!(roll, 0., epsilon = 0.0001);
+
+        // ..then follow it smoothly.
+        let moved = Affine3A::from_translation(Vec3::new(0., 0.1, 0.)) * hmd_in_stage;
+        let position = camera.update(moved, 1. / 72.).translation;
+        assert!(position.y > 1.6 && position.y < 1.7);
+
+        // A third person camera should sit behind the player, looking at their head.
+        let mut camera = SpectatorCamera::new(SpectatorMode::ThirdPerson {
+            offset: Vec3::new(0., 0., 2.),
+        });
+        let pose = camera.update(Affine3A::from_translation(Vec3::Y), 1. / 72.);
+        assert_relative_eq!(Vec3::from(pose.translation), Vec3::new(0., 1., 2.));
+        assert_relative_eq!(
+            pose.transform_vector3(Vec3::NEG_Z),
+            Vec3::NEG_Z,
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    pub fn test_views() {
+        let camera = SpectatorCamera {
+            horizontal_fov: 90_f32.to_radians(),
+            ..Default::default()
+        };
+        let [left, right] = camera.views(2.);
+        assert_eq!(left.fov.angle_left, right.fov.angle_left);
+        assert_relative_eq!(left.fov.angle_right, 45_f32.to_radians());
+        assert_relative_eq!(left.fov.angle_up, 0.5_f32.atan());
+        assert_relative_eq!(left.fov.angle_down, -left.fov.angle_up);
+    }
+
+    #[test]
+    pub fn test_smoothing_factor() {
+        assert_eq!(smoothing_factor(1. / 72., 0.), 1.);
+        assert_relative_eq!(smoothing_factor(0.2, 0.2), 1. - (-1_f32).exp());
+
+        // Two short frames should move as far as one long one.
+        let short = smoothing_factor(0.1, 0.3);
+        assert_relative_eq!(
+            1. - (1. - short) * (1. - short),
+            smoothing_factor(0.2, 0.3),
+            epsilon = 0.0001
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use std::{mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::{Affine3A, Vec3, Vec4};
+use vk_shader_macros::include_glsl;
+
+use crate::{
+    components::{Sprite, SpriteLayer},
+    contexts::{
+        render_context::{
+            create_push_constant, create_shader, RENDER_PASS_DYNAMIC_STATES, SAMPLES,
+            TEXTURE_COUNT_CONSTANT_ID,
+        },
+        VulkanContext,
+    },
+};
+
+use super::buffer::Buffer;
+
+static SPRITE_VERT: &[u32] = include_glsl!("src/shaders/sprite.vert", target: vulkan1_1);
+static SPRITE_FRAG: &[u32] = include_glsl!("src/shaders/sprite.frag", target: vulkan1_1);
+
+/// The most sprites that can be drawn in a frame
+pub const MAX_SPRITES: usize = 10_000;
+
+/// A sprite, as it's sent to the sprite shaders. One of these is read for each instance of the quad.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteData {
+    /// The centre of the sprite in globally oriented stage space. `w` is 1 if the sprite is a billboard.
+    pub position: Vec4,
+    /// Half the sprite's width, along its X axis
+    pub right: Vec4,
+    /// Half the sprite's height, along its Y axis
+    pub up: Vec4,
+    /// `xy` is the top left corner of the texture region, and `zw` its size
+    pub uv_rect: Vec4,
+    /// The sprite's color
+    pub color: Vec4,
+    /// The index of the sprite's texture in the texture array
+    pub texture_id: u32,
+}
+
+impl SpriteData {
+    /// Create the data for `sprite`, transformed by `gos_from_local`
+    pub fn new(sprite: &Sprite, gos_from_local: &Affine3A) -> Self {
+        let half_size = sprite.size * 0.5;
+        Self {
+            position: Vec3::from(gos_from_local.translation).extend(if sprite.billboard {
+                1.
+            } else {
+                0.
+            }),
+            right: (Vec3::from(gos_from_local.matrix3.x_axis) * half_size.x).extend(0.),
+            up: (Vec3::from(gos_from_local.matrix3.y_axis) * half_size.y).extend(0.),
+            uv_rect: Vec4::new(
+                sprite.uv_offset.x,
+                sprite.uv_offset.y,
+                sprite.uv_extent.x,
+                sprite.uv_extent.y,
+            ),
+            color: sprite.color,
+            texture_id: sprite.texture_id,
+        }
+    }
+
+    /// How far the centre of the sprite is from `point`, in globally oriented stage space
+    pub fn distance(&self, point: Vec3) -> f32 {
+        self.position.truncate().distance(point)
+    }
+}
+
+/// A run of sprites in the same layer, with the same texture, that can be drawn with one instanced draw call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteBatch {
+    /// The layer the sprites are in
+    pub layer: SpriteLayer,
+    /// The index of the first sprite's [`SpriteData`] in the sprite buffer
+    pub instance_offset: u32,
+    /// The number of sprites in this batch
+    pub instance_count: u32,
+}
+
+/// Split `sprites`, all in `layer`, into batches that share a texture, without changing their order. `first_instance`
+/// is the index of the first of `sprites` in the sprite buffer.
+pub fn build_sprite_batches(
+    layer: SpriteLayer,
+    first_instance: usize,
+    sprites: &[SpriteData],
+    batches: &mut Vec<SpriteBatch>,
+) {
+    let mut previous_texture_id = None;
+    for (index, sprite) in sprites.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch) if previous_texture_id == Some(sprite.texture_id) => {
+                batch.instance_count += 1
+            }
+            _ => batches.push(SpriteBatch {
+                layer,
+                instance_offset: (first_instance + index) as _,
+                instance_count: 1,
+            }),
+        }
+        previous_texture_id = Some(sprite.texture_id);
+    }
+}
+
+/// Draws [`Sprite`]s as instanced quads, alpha blended over the PBR render pass.
+pub struct SpritePipeline {
+    /// Draws sprites in the [`SpriteLayer::World`] layer, depth tested against the scene
+    pub world_pipeline: vk::Pipeline,
+    /// Draws sprites in the [`SpriteLayer::HeadLocked`] layer, over the top of everything
+    pub head_locked_pipeline: vk::Pipeline,
+    /// Uses the shared descriptor set for the scene data and the texture array
+    pub pipeline_layout: vk::PipelineLayout,
+}
+
+impl SpritePipeline {
+    pub(crate) fn new(
+        vulkan_context: &VulkanContext,
+        set_layout: vk::DescriptorSetLayout,
+        render_area: &vk::Rect2D,
+        render_pass: vk::RenderPass,
+        reversed_z: bool,
+        texture_capacity: u32,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder().set_layouts(slice_from_ref(&set_layout)),
+                None,
+            )
+        }?;
+
+        let (vertex_shader, vertex_stage) =
+            create_shader(SPRITE_VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
+        let (fragment_shader, mut fragment_stage) =
+            create_shader(SPRITE_FRAG, vk::ShaderStageFlags::FRAGMENT, vulkan_context)?;
+        let texture_count_entry = vk::SpecializationMapEntry {
+            constant_id: TEXTURE_COUNT_CONSTANT_ID,
+            offset: 0,
+            size: size_of::<u32>(),
+        };
+        let fragment_specialization = vk::SpecializationInfo::builder()
+            .map_entries(slice_from_ref(&texture_count_entry))
+            .data(create_push_constant(&texture_capacity));
+        fragment_stage.p_specialization_info = &*fragment_specialization;
+        let stages = [vertex_stage, fragment_stage];
+
+        // The quad is generated in the vertex shader, so the only vertex input is the data for each sprite.
+        let binding_description = vk::VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(size_of::<SpriteData>() as _)
+            .input_rate(vk::VertexInputRate::INSTANCE)
+            .build();
+        let attribute_descriptions = SpriteData::attribute_descriptions();
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(slice_from_ref(&binding_description))
+            .vertex_attribute_descriptions(&attribute_descriptions);
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: render_area.extent.width as _,
+            height: render_area.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice_from_ref(&viewport))
+            .scissors(slice_from_ref(render_area));
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .line_width(1.0);
+        let multisample_state =
+            vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(SAMPLES);
+
+        // Sprites are sorted back to front, so they're tested against the depth buffer but don't write to it.
+        let world_depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(if reversed_z {
+                vk::CompareOp::GREATER_OR_EQUAL
+            } else {
+                vk::CompareOp::LESS_OR_EQUAL
+            });
+        let head_locked_depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false);
+
+        // Blend the sprites over the color, and leave alpha alone.
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(slice_from_ref(&color_blend_attachment));
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
+        let create_infos = [&world_depth_stencil_state, &head_locked_depth_stencil_state].map(
+            |depth_stencil_state| {
+                vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(&stages)
+                    .vertex_input_state(&vertex_input_state)
+                    .input_assembly_state(&input_assembly_state)
+                    .viewport_state(&viewport_state)
+                    .dynamic_state(&dynamic_state)
+                    .rasterization_state(&rasterization_state)
+                    .multisample_state(&multisample_state)
+                    .depth_stencil_state(depth_stencil_state)
+                    .color_blend_state(&color_blend_state)
+                    .layout(pipeline_layout)
+                    .render_pass(render_pass)
+                    .subpass(0)
+                    .build()
+            },
+        );
+
+        let pipelines = unsafe {
+            device.create_graphics_pipelines(vk::PipelineCache::null(), &create_infos, None)
+        }
+        .map_err(|(_, r)| r)?;
+
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+
+        Ok(Self {
+            world_pipeline: pipelines[0],
+            head_locked_pipeline: pipelines[1],
+            pipeline_layout,
+        })
+    }
+
+    /// Draw the batches in `batches` that are in `layer`, reading the sprites from `sprite_buffer`. Must be inside
+    /// the PBR render pass.
+    pub(crate) unsafe fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        sprite_buffer: &Buffer<SpriteData>,
+        batches: &[SpriteBatch],
+        layer: SpriteLayer,
+    ) {
+        let mut batches = batches.iter().filter(|b| b.layer == layer).peekable();
+        if batches.peek().is_none() {
+            return;
+        }
+
+        let pipeline = match layer {
+            SpriteLayer::World => self.world_pipeline,
+            SpriteLayer::HeadLocked => self.head_locked_pipeline,
+        };
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            slice_from_ref(&descriptor_set),
+            &[],
+        );
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            slice_from_ref(&sprite_buffer.buffer),
+            &[0],
+        );
+        for batch in batches {
+            device.cmd_draw(
+                command_buffer,
+                6,
+                batch.instance_count,
+                0,
+                batch.instance_offset,
+            );
+        }
+    }
+}
+
+impl SpriteData {
+    /// Get the per-instance attributes read by the sprite vertex shader
+    pub fn attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        let vec4_attribute = |location, offset: usize| {
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(location)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offset as _)
+                .build()
+        };
+
+        vec![
+            vec4_attribute(0, memoffset::offset_of!(SpriteData, position)),
+            vec4_attribute(1, memoffset::offset_of!(SpriteData, right)),
+            vec4_attribute(2, memoffset::offset_of!(SpriteData, up)),
+            vec4_attribute(3, memoffset::offset_of!(SpriteData, uv_rect)),
+            vec4_attribute(4, memoffset::offset_of!(SpriteData, color)),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(5)
+                .format(vk::Format::R32_UINT)
+                .offset(memoffset::offset_of!(SpriteData, texture_id) as _)
+                .build(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{Quat, Vec2};
+
+    #[test]
+    pub fn test_sprite_data() {
+        let sprite = Sprite {
+            uv_offset: Vec2::new(0.5, 0.25),
+            uv_extent: Vec2::new(0.5, 0.25),
+            billboard: false,
+            ..Sprite::new(3, Vec2::new(2., 1.))
+        };
+        let gos_from_local = Affine3A::from_rotation_translation(
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            Vec3::new(1., 2., 3.),
+        );
+        let data = SpriteData::new(&sprite, &gos_from_local);
+        assert_relative_eq!(data.position, Vec4::new(1., 2., 3., 0.));
+        assert_relative_eq!(data.right, Vec4::new(0., 0., -1., 0.));
+        assert_relative_eq!(data.up, Vec4::new(0., 0.5, 0., 0.));
+        assert_relative_eq!(data.uv_rect, Vec4::new(0.5, 0.25, 0.5, 0.25));
+        assert_eq!(data.texture_id, 3);
+        assert_relative_eq!(data.distance(Vec3::new(1., 2., 0.)), 3.);
+
+        // Billboards are flagged in position.w.
+        let data = SpriteData::new(&Sprite::default(), &Affine3A::IDENTITY);
+        assert_eq!(data.position.w, 1.);
+    }
+
+    #[test]
+    pub fn test_build_sprite_batches() {
+        let sprite = |texture_id| SpriteData {
+            texture_id,
+            ..SpriteData::new(&Sprite::default(), &Affine3A::IDENTITY)
+        };
+        let mut batches = Vec::new();
+
+        // Sprites with the same texture are batched together, as long as they're next to each other..
+        let world = [sprite(0), sprite(0), sprite(1), sprite(0)];
+        build_sprite_batches(SpriteLayer::World, 0, &world, &mut batches);
+
+        // ..and batches never span layers.
+        let head_locked = [sprite(0), sprite(0)];
+        build_sprite_batches(SpriteLayer::HeadLocked, 4, &head_locked, &mut batches);
+
+        let batch = |layer, instance_offset, instance_count| SpriteBatch {
+            layer,
+            instance_offset,
+            instance_count,
+        };
+        assert_eq!(
+            batches,
+            [
+                batch(SpriteLayer::World, 0, 2),
+                batch(SpriteLayer::World, 2, 1),
+                batch(SpriteLayer::World, 3, 1),
+                batch(SpriteLayer::HeadLocked, 4, 2),
+            ]
+        );
+    }
+}
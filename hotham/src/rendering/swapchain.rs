@@ -3,7 +3,7 @@ use ash::vk::{self, Handle};
 use openxr::{Swapchain as SwapchainHandle, Vulkan};
 use vulkan_context::VulkanContext;
 
-use crate::{contexts::vulkan_context, COLOR_FORMAT, DEPTH_FORMAT};
+use crate::{contexts::vulkan_context, DEPTH_FORMAT};
 
 use super::texture::DEFAULT_COMPONENT_MAPPING;
 
@@ -13,12 +13,15 @@ pub struct SwapchainInfo {
     pub resolution: vk::Extent2D,
     /// The images held in the swapchain
     pub images: Vec<vk::Image>,
+    /// The format of the images
+    pub format: vk::Format,
 }
 
 impl SwapchainInfo {
     pub(crate) fn from_openxr_swapchain(
         handle: &SwapchainHandle<Vulkan>,
         resolution: vk::Extent2D,
+        format: vk::Format,
     ) -> Result<Self> {
         let images = handle
             .enumerate_images()?
@@ -26,7 +29,11 @@ impl SwapchainInfo {
             .map(vk::Image::from_raw)
             .collect::<Vec<_>>();
 
-        Ok(Self { resolution, images })
+        Ok(Self {
+            resolution,
+            images,
+            format,
+        })
     }
 }
 
@@ -36,6 +43,8 @@ pub struct Swapchain {
     pub render_area: vk::Rect2D,
     /// The framebuffers of the swapchain, one per swapchain image.
     pub framebuffers: Vec<vk::Framebuffer>,
+    /// The format of the swapchain images.
+    pub format: vk::Format,
 }
 
 impl Swapchain {
@@ -65,7 +74,7 @@ impl Swapchain {
         // Color image, used for MSAA.
         let color_image = vulkan_context
             .create_image(
-                COLOR_FORMAT,
+                swapchain_info.format,
                 &swapchain_info.resolution,
                 vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
                 2,
@@ -80,7 +89,7 @@ impl Swapchain {
             .flat_map(|i| {
                 vulkan_context.create_image_view(
                     i,
-                    COLOR_FORMAT,
+                    swapchain_info.format,
                     vk::ImageViewType::TYPE_2D_ARRAY,
                     2,
                     1,
@@ -109,6 +118,7 @@ impl Swapchain {
         Self {
             render_area,
             framebuffers,
+            format: swapchain_info.format,
         }
     }
 }
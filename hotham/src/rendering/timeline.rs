@@ -0,0 +1,369 @@
+use anyhow::Result;
+use ash::{extensions::khr::TimelineSemaphore, prelude::VkResult, vk};
+
+use crate::contexts::VulkanContext;
+
+/// The kinds of GPU work that are ordered on a [`Timeline`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// The compute shader that culls primitives against the views
+    Cull = 0,
+    /// The render pass that draws a frame
+    Render = 1,
+}
+
+/// How many kinds of [`Pass`] there are
+const PASS_COUNT: usize = 2;
+
+impl Pass {
+    /// The queue family this pass is submitted to
+    pub fn queue_family_index(self, vulkan_context: &VulkanContext) -> u32 {
+        match self {
+            Pass::Cull => vulkan_context.compute_queue_family_index,
+            Pass::Render => vulkan_context.queue_family_index,
+        }
+    }
+}
+
+/// Orders the renderer's GPU work with one timeline semaphore for each [`Pass`].
+///
+/// Passes run on different queues, which don't finish their work in the order it was submitted, so each pass signals
+/// its own semaphore with its own increasing values. The value each pass last signalled is kept, so later submissions
+/// can wait for exactly the work they depend on - on the GPU, or on the CPU with [`Timeline::wait`]. Adding a new
+/// kind of work, eg. an async upload, is a matter of adding a [`Pass`] and saying which passes wait for it.
+pub struct Timeline {
+    /// The timeline semaphore for each pass, indexed by [`Pass`]
+    pub semaphores: [vk::Semaphore; PASS_COUNT],
+    loader: TimelineSemaphore,
+    device: vk::Device,
+    values: TimelineValues,
+}
+
+/// Which values on each pass's semaphore have been handed out.
+#[derive(Debug, Clone, Default)]
+struct TimelineValues {
+    pass_values: [u64; PASS_COUNT],
+}
+
+impl TimelineValues {
+    fn reserve(&mut self, pass: Pass) -> u64 {
+        let value = &mut self.pass_values[pass as usize];
+        *value += 1;
+        *value
+    }
+}
+
+/// One submission to the queue, with the values it waits for and signals on a [`Timeline`].
+pub struct TimelineSubmit {
+    pass: Pass,
+    semaphore: vk::Semaphore,
+    wait_semaphores: Vec<vk::Semaphore>,
+    wait_values: Vec<u64>,
+    wait_stages: Vec<vk::PipelineStageFlags>,
+    signal_value: u64,
+}
+
+impl Timeline {
+    /// Create a timeline, with every pass starting at zero.
+    pub fn new(vulkan_context: &VulkanContext) -> Result<Self> {
+        let create_semaphore = || {
+            let mut type_create_info = vk::SemaphoreTypeCreateInfo::builder()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+            unsafe {
+                vulkan_context.device.create_semaphore(
+                    &vk::SemaphoreCreateInfo::builder().push_next(&mut type_create_info),
+                    None,
+                )
+            }
+        };
+
+        Ok(Self {
+            semaphores: [create_semaphore()?, create_semaphore()?],
+            loader: TimelineSemaphore::new(&vulkan_context.entry, &vulkan_context.instance),
+            device: vulkan_context.device.handle(),
+            values: Default::default(),
+        })
+    }
+
+    /// The value `pass` will have signalled once its last submission has finished. Zero if it's never been submitted.
+    pub fn value(&self, pass: Pass) -> u64 {
+        self.values.pass_values[pass as usize]
+    }
+
+    /// The semaphore `pass` signals
+    pub fn semaphore(&self, pass: Pass) -> vk::Semaphore {
+        self.semaphores[pass as usize]
+    }
+
+    /// Start building a submission for `pass`, reserving the next value on its semaphore for it to signal.
+    pub fn submit(&mut self, pass: Pass) -> TimelineSubmit {
+        TimelineSubmit::new(pass, self.semaphore(pass), self.values.reserve(pass))
+    }
+
+    /// Block the CPU until `pass` reaches `value`, or `timeout` nanoseconds pass.
+    pub fn wait(&self, pass: Pass, value: u64, timeout: u64) -> VkResult<()> {
+        let semaphore = self.semaphore(pass);
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(std::slice::from_ref(&semaphore))
+            .values(std::slice::from_ref(&value));
+        unsafe {
+            self.loader
+                .wait_semaphores(self.device, &wait_info, timeout)
+        }
+    }
+
+    /// The value the GPU has got up to for `pass`
+    pub fn completed_value(&self, pass: Pass) -> VkResult<u64> {
+        unsafe {
+            self.loader
+                .get_semaphore_counter_value(self.device, self.semaphore(pass))
+        }
+    }
+}
+
+impl TimelineSubmit {
+    fn new(pass: Pass, semaphore: vk::Semaphore, signal_value: u64) -> Self {
+        Self {
+            pass,
+            semaphore,
+            wait_semaphores: Vec::new(),
+            wait_values: Vec::new(),
+            wait_stages: Vec::new(),
+            signal_value,
+        }
+    }
+
+    /// Don't start `stage` of this submission until `pass` has finished its last submission.
+    pub fn wait_for(self, timeline: &Timeline, pass: Pass, stage: vk::PipelineStageFlags) -> Self {
+        // Submissions to the same pass are already ordered by their queue.
+        if pass == self.pass {
+            return self;
+        }
+        self.wait_for_value(timeline.semaphore(pass), timeline.value(pass), stage)
+    }
+
+    fn wait_for_value(
+        mut self,
+        semaphore: vk::Semaphore,
+        value: u64,
+        stage: vk::PipelineStageFlags,
+    ) -> Self {
+        // Nothing to wait for if it's never been submitted.
+        if value == 0 {
+            return self;
+        }
+        self.wait_semaphores.push(semaphore);
+        self.wait_values.push(value);
+        self.wait_stages.push(stage);
+        self
+    }
+
+    fn queue(&self, vulkan_context: &VulkanContext) -> vk::Queue {
+        match self.pass {
+            Pass::Cull => vulkan_context.compute_queue,
+            Pass::Render => vulkan_context.graphics_queue,
+        }
+    }
+
+    /// The value this submission signals when it's finished
+    pub fn signal_value(&self) -> u64 {
+        self.signal_value
+    }
+
+    /// Submit `command_buffer` to the queue for this pass: culling goes to the compute queue, so it can overlap with
+    /// rendering on GPUs that have async compute, and everything else goes to the graphics queue.
+    pub fn submit(
+        &self,
+        vulkan_context: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+    ) -> VkResult<()> {
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&self.wait_values)
+            .signal_semaphore_values(std::slice::from_ref(&self.signal_value));
+        let submit_info = vk::SubmitInfo::builder()
+            .wait_semaphores(&self.wait_semaphores)
+            .wait_dst_stage_mask(&self.wait_stages)
+            .command_buffers(std::slice::from_ref(&command_buffer))
+            .signal_semaphores(std::slice::from_ref(&self.semaphore))
+            .push_next(&mut timeline_info);
+        unsafe {
+            vulkan_context.device.queue_submit(
+                self.queue(vulkan_context),
+                std::slice::from_ref(&submit_info),
+                vk::Fence::null(),
+            )
+        }
+    }
+}
+
+/// Hands buffers over from the queue family one [`Pass`] is submitted to, to the queue family another is submitted to.
+///
+/// Buffers are created for exclusive use by one queue family at a time, so when the two passes are on different
+/// families the first has to release the buffers at the end of its work, and the second acquire them before it uses
+/// them - after waiting for the first on the [`Timeline`]. When they're on the same family, this does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueTransfer {
+    src_queue_family_index: u32,
+    dst_queue_family_index: u32,
+}
+
+impl QueueTransfer {
+    /// A transfer from the queue family of `from` to the queue family of `to`.
+    pub fn new(vulkan_context: &VulkanContext, from: Pass, to: Pass) -> Self {
+        Self {
+            src_queue_family_index: from.queue_family_index(vulkan_context),
+            dst_queue_family_index: to.queue_family_index(vulkan_context),
+        }
+    }
+
+    /// Do the buffers actually change queue family?
+    pub fn is_needed(&self) -> bool {
+        self.src_queue_family_index != self.dst_queue_family_index
+    }
+
+    /// Record the release into a command buffer for the source pass, once `stage` has finished accessing the buffers
+    /// with `access`.
+    pub unsafe fn release(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        buffers: &[vk::Buffer],
+        stage: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+    ) {
+        self.record(
+            device,
+            command_buffer,
+            &self.barriers(buffers, access, vk::AccessFlags::empty()),
+            stage,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        );
+    }
+
+    /// Record the acquire into a command buffer for the destination pass, before `stage` accesses the buffers with
+    /// `access`. `stage` should be the stage the submission waits for the source pass at.
+    pub unsafe fn acquire(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        buffers: &[vk::Buffer],
+        stage: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+    ) {
+        self.record(
+            device,
+            command_buffer,
+            &self.barriers(buffers, vk::AccessFlags::empty(), access),
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            stage,
+        );
+    }
+
+    unsafe fn record(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        barriers: &[vk::BufferMemoryBarrier],
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+    ) {
+        if barriers.is_empty() {
+            return;
+        }
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            barriers,
+            &[],
+        );
+    }
+
+    fn barriers(
+        &self,
+        buffers: &[vk::Buffer],
+        src_access: vk::AccessFlags,
+        dst_access: vk::AccessFlags,
+    ) -> Vec<vk::BufferMemoryBarrier> {
+        if !self.is_needed() {
+            return Vec::new();
+        }
+        buffers
+            .iter()
+            .map(|&buffer| {
+                vk::BufferMemoryBarrier::builder()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .src_queue_family_index(self.src_queue_family_index)
+                    .dst_queue_family_index(self.dst_queue_family_index)
+                    .buffer(buffer)
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE)
+                    .build()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_timeline_values() {
+        let mut values = TimelineValues::default();
+        assert_eq!(values.reserve(Pass::Cull), 1);
+        assert_eq!(values.reserve(Pass::Render), 1);
+        assert_eq!(values.reserve(Pass::Cull), 2);
+        assert_eq!(values.pass_values, [2, 1]);
+    }
+
+    #[test]
+    pub fn test_wait_for() {
+        let stage = vk::PipelineStageFlags::VERTEX_SHADER;
+        let semaphore = vk::Semaphore::null();
+        let submit = TimelineSubmit::new(Pass::Render, semaphore, 5)
+            .wait_for_value(semaphore, 3, stage)
+            // Passes that have never been submitted aren't waited for.
+            .wait_for_value(semaphore, 0, stage);
+
+        assert_eq!(submit.wait_values, vec![3]);
+        assert_eq!(submit.wait_stages, vec![stage]);
+        assert_eq!(submit.wait_semaphores.len(), 1);
+        assert_eq!(submit.signal_value(), 5);
+    }
+
+    #[test]
+    pub fn test_queue_transfer() {
+        let buffers = [vk::Buffer::null(); 2];
+        let read = vk::AccessFlags::SHADER_READ;
+
+        // Passes on the same queue family don't need to hand anything over..
+        let transfer = QueueTransfer {
+            src_queue_family_index: 0,
+            dst_queue_family_index: 0,
+        };
+        assert!(!transfer.is_needed());
+        assert!(transfer
+            .barriers(&buffers, vk::AccessFlags::empty(), read)
+            .is_empty());
+
+        // ..but passes on different ones hand over every buffer, whole.
+        let transfer = QueueTransfer {
+            src_queue_family_index: 0,
+            dst_queue_family_index: 1,
+        };
+        assert!(transfer.is_needed());
+        let barriers = transfer.barriers(&buffers, vk::AccessFlags::empty(), read);
+        assert_eq!(barriers.len(), 2);
+        for barrier in barriers {
+            assert_eq!(barrier.src_queue_family_index, 0);
+            assert_eq!(barrier.dst_queue_family_index, 1);
+            assert_eq!(barrier.dst_access_mask, read);
+            assert_eq!(barrier.size, vk::WHOLE_SIZE);
+        }
+    }
+}
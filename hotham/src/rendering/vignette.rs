@@ -0,0 +1,217 @@
+use std::{f32::consts::SQRT_2, mem::size_of, slice::from_ref as slice_from_ref};
+
+use anyhow::Result;
+use ash::vk;
+use glam::Vec4;
+use vk_shader_macros::include_glsl;
+
+use crate::contexts::{
+    effects_context::VignetteSettings,
+    render_context::{create_push_constant, create_shader, RENDER_PASS_DYNAMIC_STATES, SAMPLES},
+    VulkanContext,
+};
+
+static VIGNETTE_VERT: &[u32] = include_glsl!("src/shaders/vignette.vert", target: vulkan1_1);
+static VIGNETTE_FRAG: &[u32] = include_glsl!("src/shaders/vignette.frag", target: vulkan1_1);
+
+/// A comfort vignette, as it's sent to the vignette shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VignetteData {
+    /// The vignette's color, with its intensity in `w`
+    pub color: Vec4,
+    /// `x` is how far from the middle of the view the vignette starts, and `y` is how far it is before it's opaque, in
+    /// normalized device coordinates
+    pub radii: Vec4,
+}
+
+impl VignetteData {
+    /// Create the data for a vignette with `settings` at `intensity`, from 0 to 1. Returns `None` if the vignette can't
+    /// be seen.
+    pub fn new(settings: &VignetteSettings, intensity: f32) -> Option<Self> {
+        let intensity = intensity.clamp(0., 1.);
+        if !settings.enabled || intensity == 0. {
+            return None;
+        }
+
+        // With no intensity the vignette starts just outside the corners of the view, and at full intensity only
+        // `min_radius` is left clear.
+        let feather = settings.feather.max(0.);
+        let clear = SQRT_2 + (settings.min_radius - SQRT_2) * intensity;
+        Some(Self {
+            color: settings.color.extend(intensity),
+            radii: Vec4::new(clear, clear + feather, 0., 0.),
+        })
+    }
+}
+
+/// Narrows the view with a vignette during artificial motion, drawn over everything else in the PBR render pass.
+pub struct VignettePipeline {
+    /// The pipeline itself
+    pub pipeline: vk::Pipeline,
+    /// Just a push constant for the vignette
+    pub pipeline_layout: vk::PipelineLayout,
+}
+
+impl VignettePipeline {
+    pub(crate) fn new(
+        vulkan_context: &VulkanContext,
+        render_area: &vk::Rect2D,
+        render_pass: vk::RenderPass,
+    ) -> Result<Self> {
+        let device = &vulkan_context.device;
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<VignetteData>() as _)
+            .build();
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .push_constant_ranges(slice_from_ref(&push_constant_range)),
+                None,
+            )
+        }?;
+
+        let (vertex_shader, vertex_stage) =
+            create_shader(VIGNETTE_VERT, vk::ShaderStageFlags::VERTEX, vulkan_context)?;
+        let (fragment_shader, fragment_stage) = create_shader(
+            VIGNETTE_FRAG,
+            vk::ShaderStageFlags::FRAGMENT,
+            vulkan_context,
+        )?;
+        let stages = [vertex_stage, fragment_stage];
+
+        // The triangle is generated in the vertex shader, so there are no vertex inputs.
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: render_area.extent.width as _,
+            height: render_area.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice_from_ref(&viewport))
+            .scissors(slice_from_ref(render_area));
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .line_width(1.0);
+        let multisample_state =
+            vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(SAMPLES);
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false);
+
+        // Blend the vignette over the color, and leave alpha alone.
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                vk::ColorComponentFlags::R
+                    | vk::ColorComponentFlags::G
+                    | vk::ColorComponentFlags::B
+                    | vk::ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(slice_from_ref(&color_blend_attachment));
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&RENDER_PASS_DYNAMIC_STATES);
+
+        let create_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .dynamic_state(&dynamic_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .layout(pipeline_layout)
+            .render_pass(render_pass)
+            .subpass(0)
+            .build();
+
+        let pipelines = unsafe {
+            device.create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                slice_from_ref(&create_info),
+                None,
+            )
+        }
+        .map_err(|(_, r)| r)?;
+
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+
+        Ok(Self {
+            pipeline: pipelines[0],
+            pipeline_layout,
+        })
+    }
+
+    /// Draw `vignette` over the whole view. Must be inside the PBR render pass.
+    pub(crate) unsafe fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        vignette: &VignetteData,
+    ) {
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            self.pipeline_layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            create_push_constant(vignette),
+        );
+        device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_vignette_data() {
+        let settings = VignetteSettings::default();
+        assert_eq!(VignetteData::new(&settings, 0.), None);
+
+        // At full intensity only the middle of the view is left clear..
+        let data = VignetteData::new(&settings, 1.).unwrap();
+        assert_relative_eq!(data.radii.x, settings.min_radius);
+        assert_relative_eq!(data.radii.y, settings.min_radius + settings.feather);
+        assert_eq!(data.color.w, 1.);
+
+        // ..and it closes in gradually.
+        let half = VignetteData::new(&settings, 0.5).unwrap();
+        assert!(half.radii.x > data.radii.x && half.radii.x < SQRT_2);
+
+        let disabled = VignetteSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(VignetteData::new(&disabled, 1.), None);
+    }
+}
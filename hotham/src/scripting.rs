@@ -0,0 +1,52 @@
+use crate::contexts::InputContext;
+
+/// Buttons passed to a script's `on_input` function.
+pub mod buttons {
+    /// The A button on the right controller
+    pub const A: i32 = 0;
+    /// The B button on the right controller
+    pub const B: i32 = 1;
+    /// The X button on the left controller
+    pub const X: i32 = 2;
+    /// The Y button on the left controller
+    pub const Y: i32 = 3;
+    /// The trigger on the left controller
+    pub const LEFT_TRIGGER: i32 = 4;
+    /// The trigger on the right controller
+    pub const RIGHT_TRIGGER: i32 = 5;
+    /// The grip on the left controller
+    pub const LEFT_GRIP: i32 = 6;
+    /// The grip on the right controller
+    pub const RIGHT_GRIP: i32 = 7;
+    /// The menu button on the left controller
+    pub const MENU: i32 = 8;
+}
+
+/// Events a script can `subscribe` to.
+pub mod events {
+    /// Call `on_collision` for each entity the script's entity collides with
+    pub const COLLISION: i32 = 0;
+    /// Call `on_input` for each button pressed this frame
+    pub const INPUT: i32 = 1;
+}
+
+/// The [`buttons`] that were pressed this frame.
+pub(crate) fn buttons_pressed(input_context: &InputContext) -> Vec<i32> {
+    let left = &input_context.left;
+    let right = &input_context.right;
+    [
+        (right.a_button_just_pressed(), buttons::A),
+        (right.b_button_just_pressed(), buttons::B),
+        (left.x_button_just_pressed(), buttons::X),
+        (left.y_button_just_pressed(), buttons::Y),
+        (left.trigger_button_just_pressed(), buttons::LEFT_TRIGGER),
+        (right.trigger_button_just_pressed(), buttons::RIGHT_TRIGGER),
+        (left.grip_button_just_pressed(), buttons::LEFT_GRIP),
+        (right.grip_button_just_pressed(), buttons::RIGHT_GRIP),
+        (left.menu_button_just_pressed(), buttons::MENU),
+    ]
+    .iter()
+    .filter(|(pressed, _)| *pressed)
+    .map(|(_, button)| *button)
+    .collect()
+}
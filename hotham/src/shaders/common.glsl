@@ -10,31 +10,38 @@ struct DrawData {
     mat4 localFromGos;
     uint materialID;
     uint skinID;
+    uint hasAmbientProbe;
+    // The entity being drawn, for picking - see `DrawData::entity_id`.
+    uint entityID;
+    // Ambient light from a baked light probe, one channel per row - see `ShProbe::packed`.
+    vec4 ambientProbe[3];
 };
 
-// Representation of a light in a scene, based on the KHR_lights_punctual extension:
-// https://github.com/KhronosGroup/glTF/tree/master/extensions/2.0/Khronos/KHR_lights_punctual
-struct Light {
-    vec3 direction;
-    float range;
-
-    vec3 color;
-    float intensity;
-
-    vec3 position;
-    float innerConeCos;
-
-    float outerConeCos;
-    uint type;
-};
-
-const uint LightType_Directional = 0;
-const uint LightType_Point = 1;
-const uint LightType_Spot = 2;
+#include "light_data.glsl"
 
 layout (set = 0, binding = 3) readonly uniform SceneData {
     mat4 viewProjection[2];
     vec4 cameraPosition[2];
     vec4 params;
+    vec4 time;
     Light lights[4];
+    LightShadow shadows[4];
+    // The sun's shadow cascades, nearest first - see `Shadows::cascades`.
+    LightShadow shadowCascades[4];
+    // Simulates or corrects for color blindness - see `AccessibilitySettings::color_transform`.
+    mat4 colorTransform;
 } sceneData;
+
+// Encode a linear color to sRGB, for swapchains with UNORM formats.
+vec3 linearToSRGB(vec3 color) {
+    bvec3 cutoff = lessThan(color, vec3(0.0031308));
+    vec3 higher = vec3(1.055) * pow(color, vec3(1.0 / 2.4)) - vec3(0.055);
+    vec3 lower = color * vec3(12.92);
+    return mix(higher, lower, cutoff);
+}
+
+// Applied to every color drawn into the view, after tonemapping.
+vec3 applyColorTransform(vec3 color) {
+    color = clamp(mat3(sceneData.colorTransform) * color, 0.0, 1.0);
+    return sceneData.time.y > 0.0 ? linearToSRGB(color) : color;
+}
@@ -0,0 +1,22 @@
+// Shared between the crowd animation and vertex shaders. Must match `CrowdInstanceData` and `CrowdAnimationData` in
+// `crowd.rs`.
+
+struct CrowdInstance {
+    mat4 gosFromLocal;
+    mat4 localFromGos;
+    float timeOffset;
+    float speed;
+    uint animation;
+    uint jointOffset;
+};
+
+struct CrowdAnimation {
+    uint firstMatrix;
+    uint frameCount;
+    uint jointCount;
+    float frameRate;
+};
+
+layout (std430, set = CROWD_SET, binding = 0) readonly buffer CrowdInstanceBuffer {
+    CrowdInstance instances[];
+} crowdInstanceBuffer;
@@ -0,0 +1,52 @@
+// Draws crowd members, skinned with the joint matrices written by `crowd_animate.comp`. Shares `pbr.frag` with the
+// PBR pipeline.
+#version 460
+
+#define CROWD_SET 2
+#include "common.glsl"
+#include "crowd.glsl"
+
+layout (location = 0) in vec3 inPos;
+layout (location = 1) in vec3 inNormal;
+layout (location = 2) in vec2 inUV;
+layout (location = 3) in uint inJoint;
+layout (location = 4) in uint inWeight;
+
+layout (location = 0) out vec4 outGosPos;
+layout (location = 1) out vec2 outUV;
+layout (location = 2) flat out uint outMaterialID;
+layout (location = 3) out vec3 outNormal;
+layout (location = 4) flat out uint outHasAmbientProbe;
+layout (location = 5) flat out vec4 outAmbientProbe[3];
+
+layout (std430, set = CROWD_SET, binding = 3) readonly buffer CrowdJointBuffer {
+    mat4 matrices[];
+} crowdJointBuffer;
+
+layout (push_constant) uniform CrowdDraw {
+    uint materialID;
+} crowdDraw;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    CrowdInstance instance = crowdInstanceBuffer.instances[gl_InstanceIndex];
+    uint offset = instance.jointOffset;
+
+    // Shift and mask to unpack the individual indices and weights, as in `pbr.vert`.
+    mat4 skinMatrix =
+        ((inWeight) & 255)       * crowdJointBuffer.matrices[offset + ((inJoint) & 255)] +
+        ((inWeight >> 8) & 255)  * crowdJointBuffer.matrices[offset + ((inJoint >> 8) & 255)] +
+        ((inWeight >> 16) & 255) * crowdJointBuffer.matrices[offset + ((inJoint >> 16) & 255)] +
+        ((inWeight >> 24) & 255) * crowdJointBuffer.matrices[offset + ((inJoint >> 24) & 255)];
+
+    outGosPos = instance.gosFromLocal * skinMatrix * vec4(inPos, 1.0);
+    outNormal = normalize(mat3(skinMatrix) * inNormal * mat3(instance.localFromGos));
+    outUV = inUV;
+    outMaterialID = crowdDraw.materialID;
+    outHasAmbientProbe = 0;
+    outAmbientProbe = vec4[3](vec4(0.), vec4(0.), vec4(0.));
+    gl_Position = sceneData.viewProjection[gl_ViewIndex] * outGosPos;
+}
@@ -0,0 +1,12 @@
+#version 460
+
+layout (push_constant) uniform FadeColor {
+    vec4 color;
+} fadeColor;
+
+// Outputs
+layout (location = 0) out vec4 outColor;
+
+void main() {
+    outColor = fadeColor.color;
+}
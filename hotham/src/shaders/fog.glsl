@@ -0,0 +1,15 @@
+// Volumetric fog is computed in a froxel grid: a grid of cells that line up with the view frustum, with slices
+// spaced exponentially in depth so there's more detail close to the viewer. Both eyes share one 3D image, side by side.
+
+// Must match `FOG_NEAR_DISTANCE` in `fog.rs`
+const float FOG_NEAR_DISTANCE = 0.1;
+
+// The distance from the camera at the start of `slice`, which may be fractional.
+float fogSliceToDepth(float slice, float sliceCount, float maxDistance) {
+    return FOG_NEAR_DISTANCE * pow(maxDistance / FOG_NEAR_DISTANCE, slice / sliceCount);
+}
+
+// The (fractional) slice at `depth` from the camera.
+float fogDepthToSlice(float depth, float sliceCount, float maxDistance) {
+    return sliceCount * log(max(depth, FOG_NEAR_DISTANCE) / FOG_NEAR_DISTANCE) / log(maxDistance / FOG_NEAR_DISTANCE);
+}
@@ -0,0 +1,22 @@
+#include "light_data.glsl"
+
+#define MAX_FOG_VOLUMES 16
+
+struct FogVolume {
+    mat4 fogFromGos;
+    vec4 albedoDensity;
+    vec4 halfExtentsFade;
+};
+
+layout (set = 0, binding = 0) readonly uniform FogParams {
+    mat4 gosFromClip[2];
+    vec4 cameraPosition[2];
+    // xyz = froxel grid size for one eye, w = number of fog volumes
+    uvec4 grid;
+    // x = max distance, y = anisotropy, zw = unused
+    vec4 params;
+    // rgb = ambient light scattered by the fog
+    vec4 ambient;
+    Light lights[4];
+    FogVolume volumes[MAX_FOG_VOLUMES];
+} fogParams;
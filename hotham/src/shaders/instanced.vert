@@ -0,0 +1,48 @@
+// Draws many copies of a mesh with one instanced draw, each with its own transform. Shares `pbr.frag` with the PBR
+// pipeline.
+#version 460
+
+#include "common.glsl"
+
+layout (location = 0) in vec3 inPos;
+layout (location = 1) in vec3 inNormal;
+layout (location = 2) in vec2 inUV;
+layout (location = 3) in uint inJoint;
+layout (location = 4) in uint inWeight;
+
+layout (location = 0) out vec4 outGosPos;
+layout (location = 1) out vec2 outUV;
+layout (location = 2) flat out uint outMaterialID;
+layout (location = 3) out vec3 outNormal;
+layout (location = 4) flat out uint outHasAmbientProbe;
+layout (location = 5) flat out vec4 outAmbientProbe[3];
+
+// Must match `InstanceData` in `instancing.rs`.
+struct Instance {
+    mat4 gosFromLocal;
+    mat4 localFromGos;
+};
+
+layout (std430, set = 2, binding = 0) readonly buffer InstanceBuffer {
+    Instance instances[];
+} instanceBuffer;
+
+layout (push_constant) uniform InstancedDraw {
+    uint materialID;
+} instancedDraw;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    Instance instance = instanceBuffer.instances[gl_InstanceIndex];
+
+    outGosPos = instance.gosFromLocal * vec4(inPos, 1.0);
+    outNormal = normalize(inNormal * mat3(instance.localFromGos));
+    outUV = inUV;
+    outMaterialID = instancedDraw.materialID;
+    outHasAmbientProbe = 0;
+    outAmbientProbe = vec4[3](vec4(0.), vec4(0.), vec4(0.));
+    gl_Position = sceneData.viewProjection[gl_ViewIndex] * outGosPos;
+}
@@ -0,0 +1,18 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "lens_flare.glsl"
+
+layout (location = 0) in vec2 inUV;
+
+// Outputs
+layout (location = 0) out vec4 outColor;
+
+void main() {
+    // A soft glow, with a thin horizontal streak of glare through it.
+    float glow = pow(max(1.0 - length(inUV), 0.0), 3.0);
+    float streak = max(1.0 - abs(inUV.x), 0.0) * exp(-abs(inUV.y) * 40.0) * 0.5;
+
+    // The flare is added to what's already been drawn, which has been tonemapped.
+    outColor = vec4(min(lensFlare.color.rgb * (glow + streak), vec3(1.0)), 0.0);
+}
@@ -0,0 +1,8 @@
+layout (push_constant) uniform LensFlare {
+    // xyz = position of the light, or the direction towards it if w = 0
+    vec4 position;
+    // rgb = color, already scaled by intensity and visibility
+    vec4 color;
+    // x = tan of the flare's radius, yzw = unused
+    vec4 size;
+} lensFlare;
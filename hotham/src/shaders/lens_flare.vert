@@ -0,0 +1,29 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+#include "lens_flare.glsl"
+
+layout (location = 0) out vec2 outUV;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+// A sprite facing each eye, a metre away from it in the direction of the light, so it sits over the light in both
+// eyes and is the same apparent size however far away the light is.
+void main() {
+    vec3 cameraPosition = sceneData.cameraPosition[gl_ViewIndex].xyz;
+    vec3 toLight = lensFlare.position.w > 0.0 ? lensFlare.position.xyz - cameraPosition : lensFlare.position.xyz;
+    vec3 forward = normalize(toLight);
+    vec3 up = abs(forward.y) > 0.99 ? vec3(0.0, 0.0, 1.0) : vec3(0.0, 1.0, 0.0);
+    vec3 right = normalize(cross(forward, up));
+    up = cross(right, forward);
+
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 position = cameraPosition + forward + (right * corner.x + up * corner.y) * lensFlare.size.x;
+
+    outUV = corner;
+    gl_Position = sceneData.viewProjection[gl_ViewIndex] * vec4(position, 1.0);
+}
@@ -0,0 +1,28 @@
+// Representation of a light in a scene, based on the KHR_lights_punctual extension:
+// https://github.com/KhronosGroup/glTF/tree/master/extensions/2.0/Khronos/KHR_lights_punctual
+struct Light {
+    vec3 direction;
+    float range;
+
+    vec3 color;
+    float intensity;
+
+    vec3 position;
+    float innerConeCos;
+
+    float outerConeCos;
+    uint type;
+};
+
+// How to sample a light's shadow map - see `LightShadow`.
+struct LightShadow {
+    mat4 atlasFromGos;
+    vec4 tile;
+    // x = 1 if the light has a shadow map, y = depth bias, z = 1 if it's split into cascades, or for a cascade, how
+    // far in front of the viewer it reaches
+    vec4 params;
+};
+
+const uint LightType_Directional = 0;
+const uint LightType_Point = 1;
+const uint LightType_Spot = 2;
@@ -0,0 +1,24 @@
+// Must match `Material` in `material.rs`.
+struct Material {
+    vec4 baseColorFactor;
+    uint workflow;
+    uint baseColorTextureID;
+    uint metallicRoughnessTextureID;
+    uint normalTextureID;
+    uint occlusionTextureID;
+    uint emissiveTextureID;
+    float metallicFactor;
+    float roughnessFactor;
+    float alphaMask;
+    float alphaMaskCutoff;
+    vec2 uvScroll;
+    uint flipbookColumns;
+    uint flipbookRows;
+    uint flipbookFrameCount;
+    float flipbookFPS;
+    vec3 emissiveFactor;
+};
+
+layout (std430, set = 0, binding = 1) readonly buffer MaterialBuffer {
+    Material materials[];
+} materialBuffer;
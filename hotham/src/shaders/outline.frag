@@ -0,0 +1,18 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+
+layout (push_constant) uniform Outline {
+    mat4 gosFromLocal;
+    vec4 color;
+    float width;
+    uint skinID;
+} outline;
+
+// Outputs
+layout (location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(applyColorTransform(outline.color.rgb), outline.color.a);
+}
@@ -0,0 +1,46 @@
+// Pushes a mesh out along its normals, for a high-contrast outline. The skinning must match `pbr.vert`.
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+
+layout (location = 0) in vec3 inPos;
+layout (location = 1) in vec3 inNormal;
+layout (location = 3) in uint inJoint;
+layout (location = 4) in uint inWeight;
+
+layout (std430, set = 0, binding = 2) readonly buffer SkinsBuffer {
+    mat4 jointMatrices[100][64]; // dynamically sized array of 64 element long arrays of mat4.
+} skinsBuffer;
+
+layout (push_constant) uniform Outline {
+    mat4 gosFromLocal;
+    vec4 color;
+    float width;
+    uint skinID;
+} outline;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    vec4 localPos = vec4(inPos, 1.0);
+    vec3 localNormal = inNormal;
+    if (outline.skinID != NOT_PRESENT) {
+        mat4 skinMatrix =
+            ((inWeight) & 255)       * skinsBuffer.jointMatrices[outline.skinID][(inJoint) & 255] +
+            ((inWeight >> 8) & 255)  * skinsBuffer.jointMatrices[outline.skinID][(inJoint >> 8) & 255] +
+            ((inWeight >> 16) & 255) * skinsBuffer.jointMatrices[outline.skinID][(inJoint >> 16) & 255] +
+            ((inWeight >> 24) & 255) * skinsBuffer.jointMatrices[outline.skinID][(inJoint >> 24) & 255];
+        localPos = skinMatrix * localPos;
+        localNormal = mat3(skinMatrix) * localNormal;
+    }
+
+    // The outline is the same thickness however the mesh is scaled.
+    vec4 gosPos = outline.gosFromLocal * localPos;
+    vec3 gosNormal = normalize(transpose(inverse(mat3(outline.gosFromLocal))) * localNormal);
+    gosPos.xyz += gosNormal * outline.width;
+
+    gl_Position = sceneData.viewProjection[gl_ViewIndex] * gosPos;
+}
@@ -6,26 +6,54 @@
 #include "common.glsl"
 #include "lights.glsl"
 #include "brdf.glsl"
+#include "fog.glsl"
 
 // Inputs
 layout (location = 0) in vec3 inGosPos;
 layout (location = 1) in vec2 inUV;
 layout (location = 2) flat in uint inMaterialID;
 layout (location = 3) in vec3 inNormal;
+layout (location = 4) flat in uint inHasAmbientProbe;
+layout (location = 5) flat in vec4 inAmbientProbe[3];
 
 // Textures
-layout (set = 0, binding = 4) uniform sampler2D textures[];
-layout (set = 0, binding = 5) uniform samplerCube cubeTextures[];
+// The texture array's size depends on what the device supports, so it's set when the pipeline is created. Texture IDs
+// come from the material, which is the same for a whole draw, so they don't need to be marked nonuniformEXT.
+layout (constant_id = 0) const uint TEXTURE_COUNT = 10000;
+layout (set = 0, binding = 4) uniform samplerCube cubeTextures[2];
+layout (set = 0, binding = 5) uniform sampler2D textures[TEXTURE_COUNT];
 
-#include "pbr.glsl"
+// Volumetric fog, with the light scattered towards the viewer in rgb and the transmittance in a.
+layout (set = 1, binding = 0) uniform sampler3D fogVolume;
+
+#include "shadows.glsl"
 
-layout (std430, set = 0, binding = 1) readonly buffer MaterialBuffer {
-    Material materials[];
-} materialBuffer;
+#ifdef RAY_QUERY
+#include "ray_query.glsl"
+#endif
+
+#include "material.glsl"
+#include "pbr.glsl"
 
 // Outputs
 layout (location = 0) out vec4 outColor;
 
+// Find the froxel this fragment is in, and blend in the fog between it and the viewer.
+vec3 applyFog(vec3 color) {
+    vec4 clip = sceneData.viewProjection[gl_ViewIndex] * vec4(inGosPos, 1.0);
+    vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+    vec3 size = vec3(textureSize(fogVolume, 0));
+
+    // Both eyes are in the same image, so don't let filtering blend them together.
+    uv.x = clamp(uv.x, 1.0 / size.x, 1.0 - 1.0 / size.x);
+
+    // Each froxel holds the fog up to its far side.
+    float depth = distance(inGosPos, sceneData.cameraPosition[gl_ViewIndex].xyz);
+    float slice = fogDepthToSlice(depth, size.z, sceneData.params.y);
+    vec4 fog = texture(fogVolume, vec3((float(gl_ViewIndex) + uv.x) * 0.5, uv.y, (slice - 0.5) / size.z));
+    return color * fog.a + fog.rgb;
+}
+
 void main() {
     // Start by setting the output color to a familiar "error" magenta.
     outColor = ERROR_MAGENTA;
@@ -57,8 +85,14 @@ void main() {
         outColor = baseColor;
     }
 
+    // Add volumetric fog, if it's turned on.
+    if (sceneData.params.y > 0.0) {
+        outColor.rgb = applyFog(outColor.rgb);
+    }
+
     // Finally, tonemap the color.
     outColor.rgb = tonemap(outColor.rgb);
+    outColor.rgb = applyColorTransform(outColor.rgb);
 
     // Debugging
     // Shader inputs debug visualization
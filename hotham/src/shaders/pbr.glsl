@@ -6,20 +6,6 @@
 #define ENVIRONMENT_MAP_TEXTURE_ID 1
 #define ERROR_MAGENTA vec4(1., 0., 1., 1.)
 
-struct Material {
-    vec4 baseColorFactor;
-    uint workflow;
-    uint baseColorTextureID;
-    uint metallicRoughnessTextureID;
-    uint normalTextureID;
-    uint occlusionTextureID;
-    uint emissiveTextureID;
-    float metallicFactor;
-    float roughnessFactor;
-    float alphaMask;
-    float alphaMaskCutoff;
-};
-
 const float PBR_WORKFLOW_METALLIC_ROUGHNESS = 0.0;
 const float PBR_WORKFLOW_UNLIT = 1.0;
 
@@ -71,8 +57,18 @@ vec3 getNormal(uint normalTextureID) {
     return normalize(TBN * textureNormal);
 }
 
+// Diffuse ambient light, from the object's baked light probe if it has one, or the irradiance map if not.
+// Must match `ShProbe::evaluate`.
+vec3 getDiffuseLight(vec3 n, vec3 reflection, float lod) {
+    if (inHasAmbientProbe != 0) {
+        vec4 basis = vec4(1.0, n);
+        return max(vec3(dot(inAmbientProbe[0], basis), dot(inAmbientProbe[1], basis), dot(inAmbientProbe[2], basis)), vec3(0.0));
+    }
+    return textureLod(cubeTextures[SAMPLER_IRRADIANCE_TEXTURE_ID], reflection, lod).rgb;
+}
+
 // Calculation of the lighting contribution from an optional Image Based Light source.
-vec3 getIBLContribution(vec3 F0, float perceptualRoughness, vec3 diffuseColor, vec3 reflection, float NdotV) {
+vec3 getIBLContribution(vec3 F0, float perceptualRoughness, vec3 diffuseColor, vec3 n, vec3 reflection, float NdotV) {
     float lod = perceptualRoughness * float(DEFAULT_CUBE_MIPMAP_LEVELS - 1);
 
     vec2 brdfSamplePoint = clamp(vec2(NdotV, perceptualRoughness), vec2(0.0, 0.0), vec2(1.0, 1.0));
@@ -89,7 +85,7 @@ vec3 getIBLContribution(vec3 F0, float perceptualRoughness, vec3 diffuseColor, v
     vec3 specular = specularLight * FssEss;
 
     // Multiple scattering, from Fdez-Aguera
-    vec3 diffuseLight = textureLod(cubeTextures[SAMPLER_IRRADIANCE_TEXTURE_ID], reflection, lod).rgb;
+    vec3 diffuseLight = getDiffuseLight(n, reflection, lod);
     float Ems = (1.0 - (f_ab.x + f_ab.y));
     vec3 F_avg = F0 + (1.0 - F0) / 21.0;
     vec3 FmsEms = Ems * FssEss * F_avg / (1.0 - F_avg * Ems);
@@ -100,7 +96,9 @@ vec3 getIBLContribution(vec3 F0, float perceptualRoughness, vec3 diffuseColor, v
     return diffuse + specular;
 }
 
-vec3 getLightContribution(vec3 F0, float alphaRoughness, vec3 diffuseColor, vec3 n, vec3 v, float NdotV, Light light) {
+vec3 getLightContribution(vec3 F0, float alphaRoughness, vec3 diffuseColor, vec3 n, vec3 v, float NdotV, uint lightIndex) {
+    Light light = sceneData.lights[lightIndex];
+
     // Get a vector between this point and the light.
     vec3 pointToLight;
     if (light.type != LightType_Directional) {
@@ -120,6 +118,10 @@ vec3 getLightContribution(vec3 F0, float alphaRoughness, vec3 diffuseColor, vec3
 
     if (NdotL > 0. || NdotV > 0.) {
         vec3 intensity = getLightIntensity(light, pointToLight);
+        intensity *= getShadow(lightIndex);
+#ifdef RAY_QUERY
+        intensity *= getContactShadow(n, pointToLight, light);
+#endif
 
         // Obtain final intensity as reflectance (BRDF) scaled by the energy of the light (cosine law)
         vec3 diffuseContrib = intensity * NdotL * BRDF_lambertian(F0, diffuseColor, VdotH);
@@ -176,7 +178,7 @@ vec3 getPBRMetallicRoughnessColor(Material material, vec4 baseColor) {
     // Calculate lighting contribution from image based lighting source (IBL), scaled by a scene data parameter.
     vec3 color;
     if (sceneData.params.x > 0.) {
-        color = getIBLContribution(f0, perceptualRoughness, diffuseColor, reflection, NdotV) * sceneData.params.x;
+        color = getIBLContribution(f0, perceptualRoughness, diffuseColor, n, reflection, NdotV) * sceneData.params.x;
     } else {
         color = vec3(0.);
     }
@@ -187,28 +189,32 @@ vec3 getPBRMetallicRoughnessColor(Material material, vec4 baseColor) {
         float ao = texture(textures[material.occlusionTextureID], inUV).r;
         color = color * ao;
     }
+#ifdef RAY_QUERY
+    color *= getRayTracedOcclusion(n);
+#endif
 
     // Walk through each light and add its color contribution.
     // Qualcomm's documentation suggests that loops are undesirable, so we do branches instead.
     // Since these values are uniform, they shouldn't have too high of a penalty.
     if (sceneData.lights[0].type != NOT_PRESENT) {
-        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, sceneData.lights[0]);
+        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, 0);
     }
     if (sceneData.lights[1].type != NOT_PRESENT) {
-        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, sceneData.lights[1]);
+        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, 1);
     }
     if (sceneData.lights[2].type != NOT_PRESENT) {
-        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, sceneData.lights[2]);
+        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, 2);
     }
     if (sceneData.lights[3].type != NOT_PRESENT) {
-        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, sceneData.lights[3]);
+        color += getLightContribution(f0, alphaRoughness, diffuseColor, n, v, NdotV, 3);
     }
 
-    // Add emission, if present
+    // Add emission, scaled by the emissive texture if there is one.
+    vec3 emissive = material.emissiveFactor;
     if (material.emissiveTextureID != NOT_PRESENT) {
-        vec3 emissive = texture(textures[material.emissiveTextureID], inUV).rgb;
-        color += emissive;
+        emissive *= texture(textures[material.emissiveTextureID], inUV).rgb;
     }
+    color += emissive;
 
     return color;
 }
@@ -3,6 +3,7 @@
 #version 460
 
 #include "common.glsl"
+#include "material.glsl"
 
 layout (location = 0) in vec3 inPos;
 layout (location = 1) in vec3 inNormal;
@@ -14,6 +15,8 @@ layout (location = 0) out vec4 outGosPos;
 layout (location = 1) out vec2 outUV;
 layout (location = 2) flat out uint outMaterialID;
 layout (location = 3) out vec3 outNormal;
+layout (location = 4) flat out uint outHasAmbientProbe;
+layout (location = 5) flat out vec4 outAmbientProbe[3];
 
 layout (std430, set = 0, binding = 0) readonly buffer DrawDataBuffer {
     DrawData data[];
@@ -23,6 +26,18 @@ layout (std430, set = 0, binding = 2) readonly buffer SkinsBuffer {
     mat4 jointMatrices[100][64]; // dynamically sized array of 64 element long arrays of mat4.
 } skinsBuffer;
 
+// Scroll the texture coordinates and step through flipbook frames, driven by the scene's time. Must match
+// `Material::animate_uv`.
+vec2 animateUV(Material material, vec2 uv) {
+    float time = sceneData.time.x;
+    if (material.flipbookFrameCount > 1 && material.flipbookColumns > 0 && material.flipbookRows > 0) {
+        uint frame = uint(time * material.flipbookFPS) % material.flipbookFrameCount;
+        vec2 cell = vec2(frame % material.flipbookColumns, frame / material.flipbookColumns);
+        uv = (uv + cell) / vec2(material.flipbookColumns, material.flipbookRows);
+    }
+    return uv + material.uvScroll * time;
+}
+
 out gl_PerVertex {
     vec4 gl_Position;
 };
@@ -48,7 +63,9 @@ void main() {
         outNormal = normalize(mat3(skinMatrix) * inNormal * mat3(d.localFromGos));
     }
 
-    outUV = inUV;
+    outUV = animateUV(materialBuffer.materials[d.materialID], inUV);
     outMaterialID = d.materialID;
+    outHasAmbientProbe = d.hasAmbientProbe;
+    outAmbientProbe = d.ambientProbe;
     gl_Position = sceneData.viewProjection[gl_ViewIndex] * outGosPos;
 }
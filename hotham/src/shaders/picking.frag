@@ -0,0 +1,9 @@
+#version 460
+
+layout (location = 0) flat in uint inPickID;
+
+layout (location = 0) out uint outPickID;
+
+void main() {
+    outPickID = inPickID;
+}
@@ -0,0 +1,48 @@
+// Draws the ID of each entity, for picking. The skinning must match `pbr.vert`.
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+
+layout (location = 0) in vec3 inPos;
+layout (location = 3) in uint inJoint;
+layout (location = 4) in uint inWeight;
+
+layout (location = 0) flat out uint outPickID;
+
+layout (std430, set = 0, binding = 0) readonly buffer DrawDataBuffer {
+    DrawData data[];
+} drawDataBuffer;
+
+layout (std430, set = 0, binding = 2) readonly buffer SkinsBuffer {
+    mat4 jointMatrices[100][64]; // dynamically sized array of 64 element long arrays of mat4.
+} skinsBuffer;
+
+// Looks down the picking ray, in globally oriented stage space.
+layout (push_constant) uniform Picking {
+    mat4 viewProjection;
+} picking;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    DrawData d = drawDataBuffer.data[gl_InstanceIndex];
+
+    vec4 gosPos;
+    if (d.skinID == NOT_PRESENT) {
+        gosPos = d.gosFromLocal * vec4(inPos, 1.0);
+    } else {
+        mat4 skinMatrix =
+            ((inWeight) & 255)       * skinsBuffer.jointMatrices[d.skinID][(inJoint) & 255] +
+            ((inWeight >> 8) & 255)  * skinsBuffer.jointMatrices[d.skinID][(inJoint >> 8) & 255] +
+            ((inWeight >> 16) & 255) * skinsBuffer.jointMatrices[d.skinID][(inJoint >> 16) & 255] +
+            ((inWeight >> 24) & 255) * skinsBuffer.jointMatrices[d.skinID][(inJoint >> 24) & 255];
+        gosPos = d.gosFromLocal * skinMatrix * vec4(inPos, 1.0);
+    }
+
+    // Entities that can't be picked have an ID of NOT_PRESENT, which wraps around to 0 - nothing.
+    outPickID = d.entityID + 1;
+    gl_Position = picking.viewProjection * gosPos;
+}
@@ -0,0 +1,79 @@
+// Contact shadows and ambient occlusion, traced with ray queries against the static geometry in the scene. Only
+// included when pbr.frag is compiled with RAY_QUERY.
+#extension GL_EXT_ray_query : require
+
+#define RAY_QUERY_CONTACT_SHADOWS 1
+#define RAY_QUERY_AMBIENT_OCCLUSION 2
+
+// Rays start this far off the surface so they don't hit the triangle they started on.
+#define RAY_QUERY_BIAS 0.01
+
+layout (set = 2, binding = 0) uniform accelerationStructureEXT sceneAccelerationStructure;
+
+// Must match `RayQueryParams` in `ray_query.rs`.
+layout (push_constant) uniform RayQueryParams {
+    // The acceleration structure is built in global space: add this to a position in globally oriented stage space.
+    vec4 globalFromGos;
+    uint mode;
+    float shadowDistance;
+    float occlusionDistance;
+    uint occlusionRayCount;
+} rayQueryParams;
+
+// Does a ray from `origin`, in globally oriented stage space, hit anything before `maxDistance`?
+bool rayQueryHit(vec3 origin, vec3 direction, float maxDistance) {
+    rayQueryEXT rayQuery;
+    rayQueryInitializeEXT(
+        rayQuery,
+        sceneAccelerationStructure,
+        gl_RayFlagsOpaqueEXT | gl_RayFlagsTerminateOnFirstHitEXT,
+        0xFF,
+        origin + rayQueryParams.globalFromGos.xyz,
+        0.0,
+        direction,
+        maxDistance);
+    while (rayQueryProceedEXT(rayQuery)) {}
+    return rayQueryGetIntersectionTypeEXT(rayQuery, true) != gl_RayQueryCommittedIntersectionNoneEXT;
+}
+
+// How much of a light reaches this point: 0 if something close by is in the way, 1 if not.
+float getContactShadow(vec3 n, vec3 pointToLight, Light light) {
+    if ((rayQueryParams.mode & RAY_QUERY_CONTACT_SHADOWS) == 0) {
+        return 1.0;
+    }
+
+    float maxDistance = rayQueryParams.shadowDistance;
+    if (light.type != LightType_Directional) {
+        maxDistance = min(maxDistance, length(pointToLight));
+    }
+    vec3 origin = inGosPos + n * RAY_QUERY_BIAS;
+    return rayQueryHit(origin, normalize(pointToLight), maxDistance) ? 0.0 : 1.0;
+}
+
+// How much ambient light reaches this point, from a few rays spread over the hemisphere around `n`.
+float getRayTracedOcclusion(vec3 n) {
+    uint rayCount = rayQueryParams.occlusionRayCount;
+    if ((rayQueryParams.mode & RAY_QUERY_AMBIENT_OCCLUSION) == 0 || rayCount == 0) {
+        return 1.0;
+    }
+
+    // Build a basis around the normal, and rotate it per pixel so the pattern turns into noise rather than banding.
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+
+    vec3 origin = inGosPos + n * RAY_QUERY_BIAS;
+    float visible = 0.0;
+    for (uint i = 0; i < rayCount; i++) {
+        // Cosine weighted directions on a spiral, so each ray counts equally.
+        float u = (float(i) + 0.5) / float(rayCount);
+        float phi = 6.28318530718 * (float(i) * 0.61803398875 + noise);
+        float r = sqrt(u);
+        vec3 direction = tangent * (r * cos(phi)) + bitangent * (r * sin(phi)) + n * sqrt(1.0 - u);
+        if (!rayQueryHit(origin, direction, rayQueryParams.occlusionDistance)) {
+            visible += 1.0;
+        }
+    }
+    return visible / float(rayCount);
+}
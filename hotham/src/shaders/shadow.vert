@@ -0,0 +1,37 @@
+// Draws meshes' depth into a light's shadow map. The skinning must match `pbr.vert`.
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+
+layout (location = 0) in vec3 inPos;
+layout (location = 3) in uint inJoint;
+layout (location = 4) in uint inWeight;
+
+layout (std430, set = 0, binding = 2) readonly buffer SkinsBuffer {
+    mat4 jointMatrices[100][64]; // dynamically sized array of 64 element long arrays of mat4.
+} skinsBuffer;
+
+// The mesh being drawn, as seen from the light.
+layout (push_constant) uniform ShadowCaster {
+    mat4 clipFromLocal;
+    uint skinID;
+} caster;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    vec4 localPos = vec4(inPos, 1.0);
+    if (caster.skinID != NOT_PRESENT) {
+        mat4 skinMatrix =
+            ((inWeight) & 255)       * skinsBuffer.jointMatrices[caster.skinID][(inJoint) & 255] +
+            ((inWeight >> 8) & 255)  * skinsBuffer.jointMatrices[caster.skinID][(inJoint >> 8) & 255] +
+            ((inWeight >> 16) & 255) * skinsBuffer.jointMatrices[caster.skinID][(inJoint >> 16) & 255] +
+            ((inWeight >> 24) & 255) * skinsBuffer.jointMatrices[caster.skinID][(inJoint >> 24) & 255];
+        localPos = skinMatrix * localPos;
+    }
+
+    gl_Position = caster.clipFromLocal * localPos;
+}
@@ -0,0 +1,53 @@
+// Shadow maps for directional lights and spotlights, all drawn into one atlas - see `Shadows`.
+layout (set = 1, binding = 1) uniform sampler2DShadow shadowMap;
+
+// The nearest of the sun's cascades that reaches this fragment, if any. The fragment's depth in front of the eye is
+// 1 / gl_FragCoord.w.
+bool getCascade(out LightShadow cascade) {
+    float depth = 1.0 / gl_FragCoord.w;
+    for (int i = 0; i < 4; i++) {
+        cascade = sceneData.shadowCascades[i];
+        if (cascade.params.x != 0.0 && depth <= cascade.params.z) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// How much of a light reaches this fragment, from 0 in shadow to 1 in the light.
+float getShadow(uint lightIndex) {
+    LightShadow shadow = sceneData.shadows[lightIndex];
+    if (shadow.params.x == 0.0) {
+        return 1.0;
+    }
+
+    // The sun's shadows may be split into cascades, and nothing beyond the furthest one is shadowed.
+    if (shadow.params.z != 0.0 && !getCascade(shadow)) {
+        return 1.0;
+    }
+
+    vec4 atlasPos = shadow.atlasFromGos * vec4(inGosPos, 1.0);
+    if (atlasPos.w <= 0.0) {
+        return 1.0;
+    }
+    vec3 pos = atlasPos.xyz / atlasPos.w;
+
+    // Anything outside the light's shadow map, or beyond its far plane, isn't shadowed.
+    vec2 tileMin = shadow.tile.xy;
+    vec2 tileMax = shadow.tile.xy + shadow.tile.zw;
+    if (any(lessThan(pos.xy, tileMin)) || any(greaterThan(pos.xy, tileMax)) || pos.z >= 1.0) {
+        return 1.0;
+    }
+
+    // Soften the edges with four filtered taps, kept inside the tile so they don't pick up other lights' shadows.
+    vec2 texel = 1.0 / vec2(textureSize(shadowMap, 0));
+    vec2 lower = tileMin + texel;
+    vec2 upper = tileMax - texel;
+    float depth = pos.z - shadow.params.y;
+    float lit = 0.0;
+    lit += texture(shadowMap, vec3(clamp(pos.xy + vec2(-0.5, -0.5) * texel, lower, upper), depth));
+    lit += texture(shadowMap, vec3(clamp(pos.xy + vec2( 0.5, -0.5) * texel, lower, upper), depth));
+    lit += texture(shadowMap, vec3(clamp(pos.xy + vec2(-0.5,  0.5) * texel, lower, upper), depth));
+    lit += texture(shadowMap, vec3(clamp(pos.xy + vec2( 0.5,  0.5) * texel, lower, upper), depth));
+    return lit * 0.25;
+}
@@ -0,0 +1,24 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+
+layout (location = 0) in vec2 inUV;
+layout (location = 1) in vec4 inColor;
+layout (location = 2) flat in uint inTextureID;
+
+// Sprites are batched by texture, so the texture ID is the same for a whole draw and doesn't need to be marked
+// nonuniformEXT.
+layout (constant_id = 0) const uint TEXTURE_COUNT = 10000;
+layout (set = 0, binding = 5) uniform sampler2D textures[TEXTURE_COUNT];
+
+// Outputs
+layout (location = 0) out vec4 outColor;
+
+void main() {
+    outColor = inColor;
+    if (inTextureID != NOT_PRESENT) {
+        outColor *= texture(textures[inTextureID], inUV);
+    }
+    outColor.rgb = applyColorTransform(outColor.rgb);
+}
@@ -0,0 +1,44 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "common.glsl"
+
+// Per instance
+layout (location = 0) in vec4 inPosition;
+layout (location = 1) in vec4 inRight;
+layout (location = 2) in vec4 inUp;
+layout (location = 3) in vec4 inUVRect;
+layout (location = 4) in vec4 inColor;
+layout (location = 5) in uint inTextureID;
+
+layout (location = 0) out vec2 outUV;
+layout (location = 1) out vec4 outColor;
+layout (location = 2) flat out uint outTextureID;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+void main() {
+    vec3 right = inRight.xyz;
+    vec3 up = inUp.xyz;
+
+    // Billboards face each eye separately, keeping their size.
+    if (inPosition.w > 0.0) {
+        vec3 forward = normalize(inPosition.xyz - sceneData.cameraPosition[gl_ViewIndex].xyz);
+        vec3 worldUp = abs(forward.y) > 0.99 ? vec3(0.0, 0.0, 1.0) : vec3(0.0, 1.0, 0.0);
+        vec3 billboardRight = normalize(cross(forward, worldUp));
+        right = billboardRight * length(right);
+        up = cross(billboardRight, forward) * length(up);
+    }
+
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 position = inPosition.xyz + right * corner.x + up * corner.y;
+
+    // Texture coordinates start at the top left.
+    outUV = inUVRect.xy + vec2(corner.x * 0.5 + 0.5, 0.5 - corner.y * 0.5) * inUVRect.zw;
+    outColor = inColor;
+    outTextureID = inTextureID;
+    gl_Position = sceneData.viewProjection[gl_ViewIndex] * vec4(position, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 460
+
+layout (push_constant) uniform VignetteData {
+    // The vignette's color, with its intensity in w
+    vec4 color;
+    // Where the vignette starts and where it's opaque, as distances from the middle of the view
+    vec4 radii;
+} vignette;
+
+// Inputs
+layout (location = 0) in vec2 inPosition;
+
+// Outputs
+layout (location = 0) out vec4 outColor;
+
+void main() {
+    float alpha = smoothstep(vignette.radii.x, vignette.radii.y, length(inPosition));
+    outColor = vec4(vignette.color.rgb, alpha);
+}
@@ -0,0 +1,11 @@
+#version 460
+
+// Where this corner is in normalized device coordinates
+layout (location = 0) out vec2 outPosition;
+
+// A single triangle that covers the whole view. Multiview draws it once for each eye.
+void main() {
+    vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    outPosition = uv * 2.0 - 1.0;
+    gl_Position = vec4(outPosition, 0.0, 1.0);
+}
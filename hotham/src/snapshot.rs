@@ -0,0 +1,331 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, SystemTime},
+};
+
+use glam::Vec3;
+use hecs::{Component, Entity, EntityRef, World};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    components::{
+        physics::Teleport, GlobalTransform, LocalTransform, Parent, Persistent, RigidBody,
+    },
+    contexts::StorageContext,
+    HothamError, HothamResult,
+};
+
+/// The [`StorageContext`] key snapshots are saved under.
+pub const SNAPSHOT_KEY: &str = "hotham_snapshot";
+
+/// Saves the state of [`Persistent`] entities when the app is paused, so it can be put back if Android kills the app
+/// while it's in the background.
+///
+/// Quest suspends apps that aren't in front, and may kill them to free up memory. When the app is paused, the engine
+/// saves a [`Snapshot`] of every [`Persistent`] entity's registered components, where its rigid body is and how
+/// fast it's moving, and the app's own state from [`Snapshots::set_app_state`]. Restore it with
+/// [`crate::Engine::restore_snapshot`] once the app has loaded its scene:
+/// ```ignore
+/// engine.snapshots.register::<Health>("Health");
+/// load_level(&mut engine);
+/// if let Some(snapshot) = engine.restore_snapshot()? {
+///     game_state = snapshot.app_state()?.unwrap_or_default();
+/// }
+/// ```
+///
+/// Only data is saved - meshes, textures and the like are the app's to load again. [`LocalTransform`] is always
+/// registered. Entities in the snapshot that the app hasn't spawned again are spawned with just their saved
+/// components.
+pub struct Snapshots {
+    components: Vec<ComponentSnapshotter>,
+    app_state: Option<Value>,
+}
+
+/// Saves and restores one kind of component.
+struct ComponentSnapshotter {
+    name: String,
+    save: fn(&EntityRef) -> Option<serde_json::Result<Value>>,
+    restore: fn(&mut World, Entity, Value) -> serde_json::Result<()>,
+}
+
+/// The saved state of the app's [`Persistent`] entities, and the app's own state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    entities: Vec<EntitySnapshot>,
+    app_state: Option<Value>,
+    saved_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EntitySnapshot {
+    id: String,
+    components: BTreeMap<String, Value>,
+    linear_velocity: Option<Vec3>,
+}
+
+impl Default for Snapshots {
+    fn default() -> Self {
+        let mut snapshots = Self {
+            components: Vec::new(),
+            app_state: None,
+        };
+        snapshots.register::<LocalTransform>("LocalTransform");
+        snapshots
+    }
+}
+
+impl Snapshots {
+    /// Save `T` for [`Persistent`] entities under `name`. The name is how the component is found in the snapshot, so
+    /// it shouldn't change between versions of the app.
+    pub fn register<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.components.retain(|c| c.name != name);
+        self.components.push(ComponentSnapshotter {
+            name: name.to_string(),
+            save: save_component::<T>,
+            restore: restore_component::<T>,
+        });
+        self
+    }
+
+    /// Set the app's own state to save with the next snapshot, eg. the current level and score. Call this whenever it
+    /// changes - the app doesn't get a chance to when it's paused.
+    pub fn set_app_state<T: Serialize>(&mut self, state: &T) -> HothamResult<()> {
+        self.app_state = Some(serde_json::to_value(state).map_err(|e| invalid("app state", e))?);
+        Ok(())
+    }
+
+    /// Take a snapshot of the [`Persistent`] entities in `world`.
+    pub fn take(&self, world: &World) -> HothamResult<Snapshot> {
+        let mut entities = Vec::new();
+        for (entity, persistent) in world.query::<&Persistent>().iter() {
+            let entity_ref = world.entity(entity).unwrap();
+            let mut components = BTreeMap::new();
+            for snapshotter in &self.components {
+                if let Some(value) = (snapshotter.save)(&entity_ref) {
+                    let value = value.map_err(|e| invalid(&snapshotter.name, e))?;
+                    components.insert(snapshotter.name.clone(), value);
+                }
+            }
+            let linear_velocity = entity_ref
+                .get::<&RigidBody>()
+                .map(|rigid_body| rigid_body.linear_velocity);
+            entities.push(EntitySnapshot {
+                id: persistent.0.clone(),
+                components,
+                linear_velocity,
+            });
+        }
+        entities.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let saved_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Ok(Snapshot {
+            entities,
+            app_state: self.app_state.clone(),
+            saved_at,
+        })
+    }
+
+    /// Put the [`Persistent`] entities in `world` back the way they were in `snapshot`. Returns how many entities were
+    /// restored.
+    pub fn restore(&self, world: &mut World, snapshot: &Snapshot) -> HothamResult<usize> {
+        let existing: HashMap<String, Entity> = world
+            .query::<&Persistent>()
+            .iter()
+            .map(|(entity, persistent)| (persistent.0.clone(), entity))
+            .collect();
+
+        for entity_snapshot in &snapshot.entities {
+            let entity = existing
+                .get(&entity_snapshot.id)
+                .copied()
+                .unwrap_or_else(|| world.spawn((Persistent(entity_snapshot.id.clone()),)));
+
+            for (name, value) in &entity_snapshot.components {
+                match self.components.iter().find(|c| &c.name == name) {
+                    Some(snapshotter) => (snapshotter.restore)(world, entity, value.clone())
+                        .map_err(|e| invalid(name, e))?,
+                    None => log::warn!(
+                        "[HOTHAM_SNAPSHOT] Ignoring {} on {:?}, as it hasn't been registered",
+                        name,
+                        entity_snapshot.id
+                    ),
+                }
+            }
+
+            // Move the entity's rigid body to where it was, and get it moving the same way.
+            let top_level_transform = world
+                .query_one_mut::<(&LocalTransform, Option<&Parent>)>(entity)
+                .ok()
+                .and_then(|(local_transform, parent)| {
+                    parent.is_none().then(|| local_transform.to_affine())
+                });
+            if let Some(transform) = top_level_transform {
+                world
+                    .insert_one(entity, GlobalTransform(transform))
+                    .unwrap();
+            }
+            let has_rigid_body = match world.get::<&mut RigidBody>(entity) {
+                Ok(mut rigid_body) => {
+                    if let Some(linear_velocity) = entity_snapshot.linear_velocity {
+                        rigid_body.linear_velocity = linear_velocity;
+                    }
+                    true
+                }
+                Err(_) => false,
+            };
+            if has_rigid_body {
+                world.insert_one(entity, Teleport {}).unwrap();
+            }
+        }
+
+        Ok(snapshot.entities.len())
+    }
+
+    /// Take a snapshot of `world` and save it in `storage_context`, replacing the last one.
+    pub fn save(&self, world: &World, storage_context: &StorageContext) -> HothamResult<()> {
+        let snapshot = self.take(world)?;
+        storage_context.set(SNAPSHOT_KEY, &snapshot)?;
+        log::info!(
+            "[HOTHAM_SNAPSHOT] Saved {} entities",
+            snapshot.entities.len()
+        );
+        Ok(())
+    }
+
+    /// Restore the snapshot saved in `storage_context`, if there is one, and remove it so it's only restored once.
+    pub fn load(
+        &self,
+        world: &mut World,
+        storage_context: &StorageContext,
+    ) -> HothamResult<Option<Snapshot>> {
+        let snapshot: Snapshot = match storage_context.get(SNAPSHOT_KEY)? {
+            Some(snapshot) => snapshot,
+            None => return Ok(None),
+        };
+        storage_context.remove(SNAPSHOT_KEY)?;
+        let restored = self.restore(world, &snapshot)?;
+        log::info!("[HOTHAM_SNAPSHOT] Restored {} entities", restored);
+        Ok(Some(snapshot))
+    }
+}
+
+impl Snapshot {
+    /// The app's state when the snapshot was taken, if it set one with [`Snapshots::set_app_state`]
+    pub fn app_state<T: DeserializeOwned>(&self) -> HothamResult<Option<T>> {
+        self.app_state
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| invalid("app state", e))
+    }
+
+    /// When the snapshot was taken. Apps may want to start afresh if it was a long time ago.
+    pub fn saved_at(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.saved_at)
+    }
+}
+
+fn save_component<T: Component + Serialize>(
+    entity: &EntityRef,
+) -> Option<serde_json::Result<Value>> {
+    entity
+        .get::<&T>()
+        .map(|component| serde_json::to_value(&*component))
+}
+
+fn restore_component<T: Component + DeserializeOwned>(
+    world: &mut World,
+    entity: Entity,
+    value: Value,
+) -> serde_json::Result<()> {
+    let component: T = serde_json::from_value(value)?;
+    world.insert_one(entity, component).unwrap();
+    Ok(())
+}
+
+fn invalid(component: &str, reason: impl ToString) -> HothamError {
+    HothamError::InvalidSnapshot {
+        component: component.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Score(u32);
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct GameState {
+        level: u32,
+    }
+
+    #[test]
+    pub fn test_take_and_restore() {
+        let mut snapshots = Snapshots::default();
+        snapshots.register::<Score>("Score");
+        snapshots.set_app_state(&GameState { level: 3 }).unwrap();
+
+        let mut world = World::new();
+        let transform = LocalTransform::from_rotation_translation(Quat::IDENTITY, Vec3::X);
+        world.spawn((
+            Persistent("ball".to_string()),
+            transform,
+            RigidBody {
+                linear_velocity: Vec3::Y,
+                ..Default::default()
+            },
+        ));
+        world.spawn((Persistent("player".to_string()), Score(10)));
+        // Not persistent, so not saved.
+        world.spawn((Score(99),));
+
+        let snapshot = snapshots.take(&world).unwrap();
+        assert_eq!(snapshot.entities.len(), 2);
+
+        // It survives being written to disk..
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            snapshot.app_state::<GameState>().unwrap(),
+            Some(GameState { level: 3 })
+        );
+
+        // ..and puts a fresh world back the way it was. The ball has been loaded again, the player hasn't.
+        let mut world = World::new();
+        let new_ball = world.spawn((
+            Persistent("ball".to_string()),
+            LocalTransform::default(),
+            RigidBody::default(),
+        ));
+        assert_eq!(snapshots.restore(&mut world, &snapshot).unwrap(), 2);
+        assert_eq!(*world.get::<&LocalTransform>(new_ball).unwrap(), transform);
+        assert_eq!(
+            world.get::<&RigidBody>(new_ball).unwrap().linear_velocity,
+            Vec3::Y
+        );
+        assert_eq!(
+            world.get::<&GlobalTransform>(new_ball).unwrap().0,
+            transform.to_affine()
+        );
+        assert!(world.get::<&Teleport>(new_ball).is_ok());
+
+        let (_, (_, score)) = world
+            .query_mut::<(&Persistent, &Score)>()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(*score, Score(10));
+    }
+}
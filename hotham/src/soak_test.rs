@@ -0,0 +1,304 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{memory_stats::resident_bytes, Engine, HothamResult};
+
+/// How wide each bucket in a [`FrameTimeHistogram`] is, in milliseconds.
+const BUCKET_WIDTH_MS: f32 = 1.;
+
+/// How many buckets a [`FrameTimeHistogram`] has. Frames slower than this many milliseconds all go in the last one.
+const BUCKET_COUNT: usize = 100;
+
+/// How long frames took, grouped into 1ms buckets so a session of any length can be summarised in a fixed space.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameTimeHistogram {
+    /// How many frames took between `n` and `n + 1` milliseconds, for each `n`
+    pub buckets: Vec<u64>,
+    /// The slowest frame, in milliseconds
+    pub max_ms: f32,
+    total_ms: f64,
+}
+
+impl Default for FrameTimeHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; BUCKET_COUNT],
+            max_ms: 0.,
+            total_ms: 0.,
+        }
+    }
+}
+
+impl FrameTimeHistogram {
+    /// Add a frame that took `frame_time`.
+    pub fn record(&mut self, frame_time: Duration) {
+        let ms = frame_time.as_secs_f32() * 1000.;
+        let bucket = ((ms / BUCKET_WIDTH_MS) as usize).min(BUCKET_COUNT - 1);
+        self.buckets[bucket] += 1;
+        self.max_ms = self.max_ms.max(ms);
+        self.total_ms += ms as f64;
+    }
+
+    /// How many frames have been recorded
+    pub fn frames(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// The average frame time, in milliseconds
+    pub fn mean_ms(&self) -> f32 {
+        match self.frames() {
+            0 => 0.,
+            frames => (self.total_ms / frames as f64) as f32,
+        }
+    }
+
+    /// The time `percentile`% of frames were at least as fast as, in milliseconds. Accurate to the width of a bucket.
+    pub fn percentile_ms(&self, percentile: f32) -> f32 {
+        let target = (self.frames() as f32 * percentile / 100.).ceil() as u64;
+        let mut frames = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            frames += count;
+            if frames >= target.max(1) {
+                return (bucket + 1) as f32 * BUCKET_WIDTH_MS;
+            }
+        }
+        0.
+    }
+}
+
+/// How much memory the app was using at some point during a soak test.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemorySample {
+    /// When the sample was taken, in seconds since the soak test started
+    pub elapsed_seconds: f32,
+    /// The resident set size of the process, if the platform can tell us
+    pub resident_bytes: Option<u64>,
+    /// How many entities were in the `World`
+    pub entities: u32,
+    /// How many meshes were in the mesh arena
+    pub meshes: usize,
+    /// How many materials were in the materials buffer
+    pub materials: usize,
+    /// How many textures had been loaded
+    pub textures: u32,
+}
+
+impl MemorySample {
+    fn new(elapsed: Duration, engine: &Engine) -> Self {
+        let resources = &engine.render_context.resources;
+        Self {
+            elapsed_seconds: elapsed.as_secs_f32(),
+            resident_bytes: resident_bytes(),
+            entities: engine.world.len(),
+            meshes: resources.mesh_data.len(),
+            materials: resources.materials_buffer.len,
+            textures: resources.texture_count(),
+        }
+    }
+}
+
+/// The results of a [`SoakTest`], written to disk as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoakTestReport {
+    /// How long the soak test has been running, in seconds
+    pub elapsed_seconds: f32,
+    /// How many frames have been rendered
+    pub frames: u64,
+    /// The average frame time, in milliseconds
+    pub mean_frame_time_ms: f32,
+    /// The median frame time, in milliseconds
+    pub p50_frame_time_ms: f32,
+    /// The frame time 99% of frames were at least as fast as, in milliseconds
+    pub p99_frame_time_ms: f32,
+    /// Every frame time, as a histogram
+    pub frame_times: FrameTimeHistogram,
+    /// How much the resident set size has grown since the first sample. If this keeps going up, something's leaking.
+    pub resident_growth_bytes: Option<i64>,
+    /// How much memory was in use, sampled regularly
+    pub memory_samples: Vec<MemorySample>,
+}
+
+/// Measures frame times and memory usage over a long session, regularly writing a [`SoakTestReport`] to disk.
+///
+/// Pair it with looping input, so the app can be left running unattended on a headset for hours to catch leaks and
+/// performance regressions:
+///
+/// ```ignore
+/// let mut engine = EngineBuilder::new()
+///     .play_input("soak_test_input.jsonl")
+///     .loop_input(true)
+///     .build();
+/// let report_path = engine.storage_context.root().join("soak_test_report.json");
+/// let mut soak_test = SoakTest::new(report_path, Duration::from_secs(60), Some(Duration::from_secs(3600)));
+///
+/// while let Ok(tick_data) = engine.update() {
+///     tick(tick_data, &mut engine);
+///     engine.finish()?;
+///     soak_test.tick(&engine)?;
+///     if soak_test.is_finished() {
+///         break;
+///     }
+/// }
+/// ```
+///
+/// [`crate::EngineBuilder::play_camera_path`] can be used instead of recorded input, for a hands-free demo mode.
+pub struct SoakTest {
+    report_path: PathBuf,
+    sample_interval: Duration,
+    duration: Option<Duration>,
+    started: Instant,
+    last_frame: Option<Instant>,
+    last_sample: Option<Instant>,
+    frame_times: FrameTimeHistogram,
+    memory_samples: Vec<MemorySample>,
+}
+
+impl SoakTest {
+    /// Start a soak test that samples memory usage and writes a report to `report_path` every `sample_interval`,
+    /// and finishes after `duration`, if given.
+    pub fn new(
+        report_path: impl Into<PathBuf>,
+        sample_interval: Duration,
+        duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            report_path: report_path.into(),
+            sample_interval,
+            duration,
+            started: Instant::now(),
+            last_frame: None,
+            last_sample: None,
+            frame_times: Default::default(),
+            memory_samples: Vec::new(),
+        }
+    }
+
+    /// Call this once per frame, after `Engine::finish`.
+    pub fn tick(&mut self, engine: &Engine) -> HothamResult<()> {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_times.record(now - last_frame);
+        }
+        self.last_frame = Some(now);
+
+        let should_sample = match self.last_sample {
+            Some(last_sample) => now - last_sample >= self.sample_interval,
+            None => true,
+        };
+        if should_sample || self.is_finished() {
+            self.last_sample = Some(now);
+            self.memory_samples
+                .push(MemorySample::new(now - self.started, engine));
+            self.write_report()?;
+        }
+
+        Ok(())
+    }
+
+    /// Has the soak test run for as long as it was asked to?
+    pub fn is_finished(&self) -> bool {
+        match self.duration {
+            Some(duration) => self.started.elapsed() >= duration,
+            None => false,
+        }
+    }
+
+    /// Summarise everything that's been measured so far.
+    pub fn report(&self) -> SoakTestReport {
+        build_report(
+            self.started.elapsed(),
+            &self.frame_times,
+            &self.memory_samples,
+        )
+    }
+
+    fn write_report(&self) -> HothamResult<()> {
+        let json = serde_json::to_string_pretty(&self.report()).map_err(anyhow::Error::new)?;
+        std::fs::write(&self.report_path, json)?;
+        Ok(())
+    }
+}
+
+fn build_report(
+    elapsed: Duration,
+    frame_times: &FrameTimeHistogram,
+    memory_samples: &[MemorySample],
+) -> SoakTestReport {
+    let resident_growth_bytes = match (memory_samples.first(), memory_samples.last()) {
+        (Some(first), Some(last)) => match (first.resident_bytes, last.resident_bytes) {
+            (Some(first), Some(last)) => Some(last as i64 - first as i64),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    SoakTestReport {
+        elapsed_seconds: elapsed.as_secs_f32(),
+        frames: frame_times.frames(),
+        mean_frame_time_ms: frame_times.mean_ms(),
+        p50_frame_time_ms: frame_times.percentile_ms(50.),
+        p99_frame_time_ms: frame_times.percentile_ms(99.),
+        frame_times: frame_times.clone(),
+        resident_growth_bytes,
+        memory_samples: memory_samples.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_frame_time_histogram() {
+        let mut histogram = FrameTimeHistogram::default();
+        assert_eq!(histogram.mean_ms(), 0.);
+
+        for _ in 0..98 {
+            histogram.record(Duration::from_micros(13_500));
+        }
+        histogram.record(Duration::from_millis(30));
+        histogram.record(Duration::from_secs(1));
+
+        assert_eq!(histogram.frames(), 100);
+        assert_eq!(histogram.buckets[13], 98);
+        assert_eq!(histogram.buckets[30], 1);
+        assert_eq!(histogram.buckets[BUCKET_COUNT - 1], 1);
+        assert_eq!(histogram.max_ms, 1000.);
+        assert_eq!(histogram.percentile_ms(50.), 14.);
+        assert_eq!(histogram.percentile_ms(99.), 31.);
+        assert_eq!(histogram.percentile_ms(100.), BUCKET_COUNT as f32);
+    }
+
+    #[test]
+    pub fn test_report() {
+        let sample = |elapsed_seconds, resident_bytes| MemorySample {
+            elapsed_seconds,
+            resident_bytes,
+            entities: 10,
+            meshes: 2,
+            materials: 3,
+            textures: 1,
+        };
+
+        // A process that's grown should be reported..
+        let report = build_report(
+            Duration::from_secs(120),
+            &FrameTimeHistogram::default(),
+            &[sample(0., Some(1000)), sample(60., Some(1500))],
+        );
+        assert_eq!(report.resident_growth_bytes, Some(500));
+        assert_eq!(report.memory_samples.len(), 2);
+
+        // ..but only if we know how big it is.
+        let report = build_report(
+            Duration::from_secs(120),
+            &FrameTimeHistogram::default(),
+            &[sample(0., None), sample(60., None)],
+        );
+        assert_eq!(report.resident_growth_bytes, None);
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use ash::vk::{self, Handle};
+use glam::{Affine3A, Vec2, Vec3};
+use openxr as xr;
+
+use crate::{
+    contexts::VulkanContext,
+    rendering::{image::Image, memory},
+    util::{is_bgra, posef_from_affine, swap_red_and_blue},
+};
+
+/// How long a [`SplashScreen`] is shown for if no other duration is set.
+pub const DEFAULT_SPLASH_DURATION: Duration = Duration::from_secs(2);
+
+/// A static image shown by the compositor as soon as the session starts, in place of the app's first frames.
+///
+/// The image is handed to the compositor once as a quad layer, so it stays smooth while the app is busy getting
+/// its first real frame ready. Set one with [`crate::EngineBuilder::splash_screen`].
+#[derive(Debug, Clone)]
+pub struct SplashScreen {
+    /// The image to show, as the contents of a PNG or JPEG file
+    pub image: Vec<u8>,
+    /// How big the quad is, in meters. The image is stretched to fit.
+    pub size: Vec2,
+    /// Where the quad is. In view space if `head_locked` is set, otherwise in stage space.
+    pub pose: Affine3A,
+    /// Should the quad follow the user's head, instead of staying in one place?
+    pub head_locked: bool,
+    /// The shortest time the splash screen is shown for, starting from the first frame
+    pub min_duration: Duration,
+}
+
+impl SplashScreen {
+    /// A world-locked splash screen showing `image`, a 2m x 1m quad 3m in front of the center of the stage.
+    pub fn new(image: Vec<u8>) -> Self {
+        Self {
+            image,
+            size: Vec2::new(2., 1.),
+            pose: Affine3A::from_translation(Vec3::new(0., 1.5, -3.)),
+            head_locked: false,
+            min_duration: DEFAULT_SPLASH_DURATION,
+        }
+    }
+}
+
+/// A [`SplashScreen`] that's been uploaded to the compositor.
+pub(crate) struct SplashLayer {
+    swapchain: xr::Swapchain<xr::Vulkan>,
+    resolution: vk::Extent2D,
+    size: xr::Extent2Df,
+    pose: xr::Posef,
+    head_locked: bool,
+    min_duration: Duration,
+    first_shown: Option<Instant>,
+}
+
+impl SplashLayer {
+    /// Decode the splash screen's image and copy it into a static swapchain.
+    pub(crate) fn new(
+        splash_screen: &SplashScreen,
+        session: &xr::Session<xr::Vulkan>,
+        vulkan_context: &VulkanContext,
+        format: vk::Format,
+    ) -> Result<Self> {
+        let mut image = image::load_from_memory(&splash_screen.image)?.into_rgba8();
+        if is_bgra(format) {
+            swap_red_and_blue(&mut image);
+        }
+        let resolution = vk::Extent2D {
+            width: image.width(),
+            height: image.height(),
+        };
+
+        let mut swapchain = session.create_swapchain(&xr::SwapchainCreateInfo {
+            create_flags: xr::SwapchainCreateFlags::STATIC_IMAGE,
+            usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT
+                | xr::SwapchainUsageFlags::TRANSFER_DST,
+            format: format.as_raw() as u32,
+            sample_count: 1,
+            width: resolution.width,
+            height: resolution.height,
+            face_count: 1,
+            array_size: 1,
+            mip_count: 1,
+        })?;
+
+        // A static swapchain has exactly one image, which can only be acquired once.
+        let images = swapchain.enumerate_images()?;
+        let index = swapchain.acquire_image()? as usize;
+        swapchain.wait_image(xr::Duration::INFINITE)?;
+        let handle = vk::Image::from_raw(images[index]);
+        upload_splash_image(vulkan_context, handle, resolution, format, image.as_raw())?;
+        swapchain.release_image()?;
+
+        Ok(Self {
+            swapchain,
+            resolution,
+            size: xr::Extent2Df {
+                width: splash_screen.size.x,
+                height: splash_screen.size.y,
+            },
+            pose: posef_from_affine(splash_screen.pose),
+            head_locked: splash_screen.head_locked,
+            min_duration: splash_screen.min_duration,
+            first_shown: None,
+        })
+    }
+
+    /// Should the splash screen still be shown? Starts the clock the first time it's called.
+    pub(crate) fn is_showing(&mut self) -> bool {
+        let first_shown = *self.first_shown.get_or_insert_with(Instant::now);
+        first_shown.elapsed() < self.min_duration
+    }
+
+    /// The quad layer to submit, in `view_space` or `stage_space` depending on whether it's head locked.
+    pub(crate) fn layer<'a>(
+        &'a self,
+        view_space: &'a xr::Space,
+        stage_space: &'a xr::Space,
+    ) -> xr::CompositionLayerQuad<'a, xr::Vulkan> {
+        let rect = xr::Rect2Di {
+            offset: xr::Offset2Di { x: 0, y: 0 },
+            extent: xr::Extent2Di {
+                width: self.resolution.width as _,
+                height: self.resolution.height as _,
+            },
+        };
+
+        xr::CompositionLayerQuad::new()
+            .layer_flags(xr::CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA)
+            .space(if self.head_locked {
+                view_space
+            } else {
+                stage_space
+            })
+            .eye_visibility(xr::EyeVisibility::BOTH)
+            .sub_image(
+                xr::SwapchainSubImage::new()
+                    .swapchain(&self.swapchain)
+                    .image_array_index(0)
+                    .image_rect(rect),
+            )
+            .pose(self.pose)
+            .size(self.size)
+    }
+}
+
+/// Copy `pixels` into a swapchain image, leaving it in the layout the compositor expects.
+fn upload_splash_image(
+    vulkan_context: &VulkanContext,
+    handle: vk::Image,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    pixels: &[u8],
+) -> Result<()> {
+    let (staging_buffer, staging_memory, staging_size) = vulkan_context.create_buffer_with_data(
+        pixels,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        pixels.len() as _,
+    )?;
+
+    // Only the handle and extent are needed to copy into the image.
+    let image = Image::new(
+        handle,
+        vk::ImageView::null(),
+        vk::DeviceMemory::null(),
+        extent,
+        vk::ImageUsageFlags::TRANSFER_DST,
+        format,
+        vk::ImageViewType::TYPE_2D,
+        1,
+    );
+
+    vulkan_context.transition_image_layout(
+        handle,
+        vk::ImageLayout::UNDEFINED,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        1,
+        1,
+    );
+    vulkan_context.copy_buffer_to_image(staging_buffer, &image, 1, vec![pixels.len() as _]);
+    vulkan_context.transition_image_layout(
+        handle,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        1,
+        1,
+    );
+
+    unsafe {
+        vulkan_context.device.destroy_buffer(staging_buffer, None);
+        vulkan_context.device.free_memory(staging_memory, None);
+    }
+    memory::track_free(staging_size);
+
+    Ok(())
+}
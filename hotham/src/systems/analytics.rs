@@ -0,0 +1,139 @@
+use hecs::{Entity, World};
+
+use crate::{
+    analytics::{Analytics, AnalyticsEvent},
+    components::{physics::Teleport, Hand, Info, LocalTransform, UIPanel},
+    Engine,
+};
+
+/// Analytics system
+/// Records the player's interactions with [`Analytics`]:
+/// - hands grabbing and letting go of things
+/// - entities about to be teleported
+/// - buttons on `UIPanel`s that were clicked
+///
+/// Should be run once a frame, before `physics_system`, so teleports are seen before they happen. Does nothing until
+/// a sink has been added to `engine.analytics`.
+pub fn analytics_system(engine: &mut Engine) {
+    analytics_system_inner(&engine.world, &mut engine.analytics);
+}
+
+pub fn analytics_system_inner(world: &World, analytics: &mut Analytics) {
+    if !analytics.is_enabled() {
+        return;
+    }
+
+    let name = |entity: Entity| {
+        world
+            .get::<&Info>(entity)
+            .ok()
+            .map(|info| info.name.clone())
+    };
+    let mut events = Vec::new();
+
+    for (hand_entity, hand) in world.query::<&Hand>().iter() {
+        let previous = analytics.held_entities.get(&hand_entity).copied();
+        if previous == hand.grabbed_entity {
+            continue;
+        }
+        if let Some(released) = previous {
+            events.push(AnalyticsEvent::Released {
+                hand: hand.handedness,
+                entity: name(released),
+            });
+        }
+        match hand.grabbed_entity {
+            Some(grabbed) => {
+                events.push(AnalyticsEvent::Grabbed {
+                    hand: hand.handedness,
+                    entity: name(grabbed),
+                });
+                analytics.held_entities.insert(hand_entity, grabbed);
+            }
+            None => {
+                analytics.held_entities.remove(&hand_entity);
+            }
+        }
+    }
+
+    for (entity, (_, local_transform)) in world.query::<(&Teleport, &LocalTransform)>().iter() {
+        events.push(AnalyticsEvent::Teleported {
+            entity: name(entity),
+            position: local_transform.translation,
+        });
+    }
+
+    for (_, panel) in world.query::<&UIPanel>().iter() {
+        for button in panel.buttons.iter().filter(|b| b.clicked_this_frame) {
+            events.push(AnalyticsEvent::UiClicked {
+                button: button.text.clone(),
+            });
+        }
+    }
+
+    for event in events {
+        analytics.record(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        analytics::{AnalyticsRecord, AnalyticsSink},
+        components::hand::Handedness,
+    };
+
+    #[derive(Clone, Default)]
+    struct TestSink(Arc<Mutex<Vec<AnalyticsEvent>>>);
+
+    impl AnalyticsSink for TestSink {
+        fn record(&mut self, record: &AnalyticsRecord) {
+            self.0.lock().unwrap().push(record.event.clone());
+        }
+    }
+
+    #[test]
+    pub fn test_analytics_system() {
+        let mut world = World::new();
+        let mut analytics = Analytics::default();
+        let sink = TestSink::default();
+        analytics.add_sink(sink.clone());
+
+        let cube = world.spawn((Info {
+            name: "Cube".to_string(),
+            node_id: 0,
+        },));
+        let hand = world.spawn((Hand::left(),));
+
+        // Nothing happens while the hand is empty..
+        analytics_system_inner(&world, &mut analytics);
+        assert!(sink.0.lock().unwrap().is_empty());
+
+        // ..then it grabs the cube once, however long it holds it for..
+        world.get::<&mut Hand>(hand).unwrap().grabbed_entity = Some(cube);
+        analytics_system_inner(&world, &mut analytics);
+        analytics_system_inner(&world, &mut analytics);
+
+        // ..and lets go.
+        world.get::<&mut Hand>(hand).unwrap().grabbed_entity = None;
+        analytics_system_inner(&world, &mut analytics);
+
+        let cube_name = Some("Cube".to_string());
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            vec![
+                AnalyticsEvent::Grabbed {
+                    hand: Handedness::Left,
+                    entity: cube_name.clone(),
+                },
+                AnalyticsEvent::Released {
+                    hand: Handedness::Left,
+                    entity: cube_name,
+                },
+            ]
+        );
+    }
+}
@@ -1,28 +1,177 @@
+use std::collections::HashMap;
+
+use glam::{Quat, Vec3, Vec4};
+use hecs::Entity;
+
 use crate::{
-    components::{animation_controller::AnimationController, LocalTransform},
+    components::{
+        animation_controller::AnimationController, AnimatedProperty, ChannelValue, LocalTransform,
+    },
+    contexts::{physics_context::DELTA_TIME, RenderContext},
     Engine,
 };
 
 /// Animation system
-/// Walks through each AnimationController and applies the appropriate animation to its targets.
+/// Walks through each AnimationController and applies the appropriate animation to its targets, including any
+/// material and light properties.
+///
+/// Controllers that are playing clips have them moved forward a frame, blended by their weights and layered on top of
+/// each other.
 pub fn animation_system(engine: &mut Engine) {
-    animation_system_inner(&mut engine.world);
+    animation_system_inner(&mut engine.world, &mut engine.render_context, DELTA_TIME);
 }
 
-fn animation_system_inner(world: &mut hecs::World) {
-    for (_, controller) in world.query::<&AnimationController>().iter() {
+fn animation_system_inner(
+    world: &mut hecs::World,
+    render_context: &mut RenderContext,
+    delta_time: f32,
+) {
+    for (_, controller) in world.query::<&mut AnimationController>().iter() {
         let blend_from = controller.blend_from;
         let blend_to = controller.blend_to;
         let blend_amount = controller.blend_amount;
 
-        for target in &controller.targets {
-            let mut local_transform = world.get::<&mut LocalTransform>(target.target).unwrap();
-            local_transform.translation =
-                target.translations[blend_from].lerp(target.translations[blend_to], blend_amount);
-            local_transform.rotation =
-                target.rotations[blend_from].slerp(target.rotations[blend_to], blend_amount);
-            local_transform.scale =
-                target.scales[blend_from].lerp(target.scales[blend_to], blend_amount);
+        if controller.is_playing_clips() {
+            controller.advance(delta_time);
+            apply_clips(world, controller);
+        } else {
+            for target in &controller.targets {
+                let mut local_transform = world.get::<&mut LocalTransform>(target.target).unwrap();
+                local_transform.translation = target.translations[blend_from]
+                    .lerp(target.translations[blend_to], blend_amount);
+                local_transform.rotation =
+                    target.rotations[blend_from].slerp(target.rotations[blend_to], blend_amount);
+                local_transform.scale =
+                    target.scales[blend_from].lerp(target.scales[blend_to], blend_amount);
+            }
+        }
+
+        for target in &controller.property_targets {
+            // Properties can have a different number of keyframes to the transforms, so skip any that run out.
+            if let (Some(from), Some(to)) =
+                (target.values.get(blend_from), target.values.get(blend_to))
+            {
+                apply_property(
+                    render_context,
+                    target.property,
+                    from.lerp(*to, blend_amount),
+                );
+            }
+        }
+    }
+}
+
+/// Blend the clips playing on each of the controller's layers, and apply them to their targets.
+fn apply_clips(world: &hecs::World, controller: &AnimationController) {
+    for layer in &controller.layers {
+        if layer.weight <= 0. {
+            continue;
+        }
+
+        let mut poses: HashMap<Entity, BlendedPose> = HashMap::new();
+        for playback in &layer.playing {
+            let clip = match controller.clips.get(playback.clip) {
+                Some(clip) if playback.weight > 0. => clip,
+                _ => continue,
+            };
+            for channel in clip.channels.iter().filter(|c| layer.masks(c.target)) {
+                if let Some(value) = channel.sample(playback.time) {
+                    poses
+                        .entry(channel.target)
+                        .or_default()
+                        .add(value, playback.weight);
+                }
+            }
+        }
+
+        for (entity, pose) in poses {
+            if let Ok(mut local_transform) = world.get::<&mut LocalTransform>(entity) {
+                pose.apply(&mut local_transform, layer.weight);
+            }
+        }
+    }
+}
+
+/// The weighted sum of each clip's translation, rotation and scale for a single entity.
+#[derive(Default)]
+struct BlendedPose {
+    translation: (Vec3, f32),
+    rotation: (Vec4, f32),
+    scale: (Vec3, f32),
+}
+
+impl BlendedPose {
+    fn add(&mut self, value: ChannelValue, weight: f32) {
+        match value {
+            ChannelValue::Translation(t) => {
+                self.translation.0 += t * weight;
+                self.translation.1 += weight;
+            }
+            ChannelValue::Rotation(r) => {
+                // q and -q are the same rotation, so keep them all in the same hemisphere before adding them up.
+                let r = Vec4::from(r);
+                let r = if self.rotation.0.dot(r) < 0. { -r } else { r };
+                self.rotation.0 += r * weight;
+                self.rotation.1 += weight;
+            }
+            ChannelValue::Scale(s) => {
+                self.scale.0 += s * weight;
+                self.scale.1 += weight;
+            }
+        }
+    }
+
+    /// Blend the pose into `local_transform`. If the clips' weights add up to less than one, the rest is made up by
+    /// whatever `local_transform` was already.
+    fn apply(&self, local_transform: &mut LocalTransform, layer_weight: f32) {
+        let amount = |weight: f32| weight.min(1.) * layer_weight;
+
+        let (translation, weight) = self.translation;
+        if weight > 0. {
+            local_transform.translation = local_transform
+                .translation
+                .lerp(translation / weight, amount(weight));
+        }
+        let (rotation, weight) = self.rotation;
+        if weight > 0. {
+            let rotation = Quat::from_vec4(rotation).normalize();
+            local_transform.rotation = local_transform.rotation.slerp(rotation, amount(weight));
+        }
+        let (scale, weight) = self.scale;
+        if weight > 0. {
+            local_transform.scale = local_transform.scale.lerp(scale / weight, amount(weight));
+        }
+    }
+}
+
+/// Set a material or light property to `value`.
+pub(crate) fn apply_property(
+    render_context: &mut RenderContext,
+    property: AnimatedProperty,
+    value: Vec4,
+) {
+    let materials = unsafe { render_context.resources.materials_buffer.as_slice_mut() };
+    let lights = &mut render_context.scene_data.lights;
+    match property {
+        AnimatedProperty::BaseColorFactor(material_id) => {
+            if let Some(material) = materials.get_mut(material_id as usize) {
+                material.base_color_factor = value;
+            }
+        }
+        AnimatedProperty::EmissiveFactor(material_id) => {
+            if let Some(material) = materials.get_mut(material_id as usize) {
+                material.emissive_factor = value.truncate();
+            }
+        }
+        AnimatedProperty::LightColor(index) => {
+            if let Some(light) = lights.get_mut(index) {
+                light.color = value.truncate();
+            }
+        }
+        AnimatedProperty::LightIntensity(index) => {
+            if let Some(light) = lights.get_mut(index) {
+                light.intensity = value.x;
+            }
         }
     }
 }
@@ -62,7 +211,7 @@ mod tests {
             .collect::<Vec<LocalTransform>>();
 
         // Run the animation system
-        animation_system_inner(&mut world);
+        animation_system_inner(&mut world, &mut render_context, DELTA_TIME);
 
         // Collect all the transforms after the system has been run.
         let transforms_after = world
@@ -1,27 +1,55 @@
-use glam::Vec3;
-use hecs::World;
+use std::cmp::Ordering;
+
+use glam::{Quat, Vec3};
+use hecs::{Entity, World};
 use openxr::SpaceVelocityFlags;
 
 use crate::{
-    components::{sound_emitter::SoundState, GlobalTransform, RigidBody, SoundEmitter},
-    contexts::{AudioContext, XrContext},
+    captions::Captions,
+    components::{
+        sound_emitter::{SoundState, VirtualVoice},
+        GlobalTransform, RigidBody, SoundEmitter,
+    },
+    contexts::{physics_context::DELTA_TIME, AudioContext, PhysicsContext, XrContext},
     util::is_space_valid,
     Engine,
 };
 
+/// How much louder a sound that's already being mixed is treated as, so sounds on the edge of the voice budget don't
+/// keep swapping in and out.
+const VOICE_STICKINESS: f32 = 1.2;
+
+/// How long it takes a sound to become muffled, or clear again, in seconds, so sounds don't pop as things pass in
+/// front of them.
+const OCCLUSION_FADE_TIME: f32 = 0.2;
+
 /// Audio system
 /// Walks through each SoundEmitter that has a RigidBody and:
-/// - updates its position in space
-/// - updates its playing state
+/// - updates its position in space, and how much it's occluded if `AudioContext::occlusion` is turned on
+/// - updates its playing state, and queues its caption when it starts playing
+/// - virtualizes the least important sounds when more than `AudioContext::max_voices` are playing, and revives them
+///   when there's room
+///
+/// It also moves on any crossfades between pieces of streaming music.
 pub fn audio_system(engine: &mut Engine) {
     let world = &mut engine.world;
     let audio_context = &mut engine.audio_context;
     let xr_context = &engine.xr_context;
+    let physics_context = &engine.physics_context;
+    let captions = &mut engine.captions;
 
-    audio_system_inner(world, audio_context, xr_context);
+    audio_system_inner(world, audio_context, xr_context, physics_context, captions);
 }
 
-fn audio_system_inner(world: &mut World, audio_context: &mut AudioContext, xr_context: &XrContext) {
+fn audio_system_inner(
+    world: &mut World,
+    audio_context: &mut AudioContext,
+    xr_context: &XrContext,
+    physics_context: &PhysicsContext,
+    captions: &mut Captions,
+) {
+    audio_context.update_streaming_music(DELTA_TIME);
+
     // First, where is the listener?
     let (stage_from_listener, listener_velocity_in_stage) = xr_context
         .view_space
@@ -42,14 +70,24 @@ fn audio_system_inner(world: &mut World, audio_context: &mut AudioContext, xr_co
         return;
     }
 
-    audio_context.update_listener_rotation(stage_from_listener.pose.orientation.into());
+    let listener_rotation_in_stage: Quat =
+        mint::Quaternion::from(stage_from_listener.pose.orientation).into();
+    audio_context.update_listener_rotation(listener_rotation_in_stage);
 
     let listener_position_in_stage: Vec3 =
         mint::Vector3::from(stage_from_listener.pose.position).into();
     let listener_velocity_in_stage: Vec3 =
         mint::Vector3::from(listener_velocity_in_stage.linear_velocity).into();
 
-    for (_, (sound_emitter, rigid_body, global_transform)) in
+    let occluded = if audio_context.occlusion {
+        find_occluded(world, physics_context, listener_position_in_stage)
+    } else {
+        Vec::new()
+    };
+
+    let mut voices = Vec::new();
+
+    for (entity, (sound_emitter, rigid_body, global_transform)) in
         world.query_mut::<(&mut SoundEmitter, &RigidBody, &GlobalTransform)>()
     {
         // Get the position and velocity of the entity.
@@ -57,19 +95,23 @@ fn audio_system_inner(world: &mut World, audio_context: &mut AudioContext, xr_co
         let source_velocity_in_stage = rigid_body.linear_velocity;
 
         // Compute relative position and velocity
-        let relative_position_in_stage =
-            (source_position_in_stage - listener_position_in_stage).into();
-        let relative_velocity_in_stage =
-            (source_velocity_in_stage - listener_velocity_in_stage).into();
+        let relative_position_in_stage = source_position_in_stage - listener_position_in_stage;
+        let relative_velocity_in_stage = source_velocity_in_stage - listener_velocity_in_stage;
+
+        // Virtual voices keep time, even though nobody can hear them.
+        advance_virtual_voice(sound_emitter);
 
         // Determine what we should do with the audio source
         match (sound_emitter.current_state(), &sound_emitter.next_state) {
             (SoundState::Stopped, Some(SoundState::Playing)) => {
-                audio_context.play_audio(
-                    sound_emitter,
-                    relative_position_in_stage,
-                    relative_velocity_in_stage,
-                );
+                // New sounds start out virtual, and are given a voice below if there's room for them.
+                sound_emitter.virtual_voice = Some(VirtualVoice {
+                    position: 0.,
+                    paused: false,
+                });
+                if let Some(caption) = &sound_emitter.caption {
+                    captions.push(caption.clone());
+                }
             }
             (SoundState::Paused, Some(SoundState::Playing)) => {
                 audio_context.resume_audio(sound_emitter);
@@ -89,12 +131,253 @@ fn audio_system_inner(world: &mut World, audio_context: &mut AudioContext, xr_co
         // Reset the sound emitter's intent
         sound_emitter.next_state = None;
 
+        let target_occlusion = if occluded.contains(&entity) { 1. } else { 0. };
+        fade_occlusion(sound_emitter, target_occlusion);
+
         // Update its position and velocity
         audio_context.update_motion(
             sound_emitter,
             relative_position_in_stage,
             relative_velocity_in_stage,
         );
+
+        if sound_emitter.current_state() == SoundState::Playing {
+            voices.push(Voice {
+                entity,
+                priority: sound_emitter.priority,
+                audibility: audio_context
+                    .sound_emitter_volume(sound_emitter, relative_position_in_stage),
+                is_virtual: sound_emitter.is_virtual(),
+                should_be_virtual: true,
+                position: relative_position_in_stage,
+                velocity: relative_velocity_in_stage,
+            });
+        }
+    }
+
+    choose_voices(
+        &mut voices,
+        audio_context.max_voices,
+        audio_context.audibility_threshold,
+    );
+
+    for voice in voices {
+        let mut sound_emitter = world.get::<&mut SoundEmitter>(voice.entity).unwrap();
+        match (voice.is_virtual, voice.should_be_virtual) {
+            (true, false) => {
+                audio_context.revive_audio(&mut sound_emitter, voice.position, voice.velocity)
+            }
+            (false, true) => audio_context.virtualize_audio(&mut sound_emitter),
+            _ => {}
+        }
+    }
+}
+
+/// A sound that's playing, and may or may not be mixed
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Voice {
+    entity: Entity,
+    priority: u8,
+    audibility: f32,
+    is_virtual: bool,
+    should_be_virtual: bool,
+    position: Vec3,
+    velocity: Vec3,
+}
+
+/// Find the sounds that are playing with something between them and the listener. The entity making each sound is
+/// ignored, so it isn't blocked by its own collider.
+fn find_occluded(
+    world: &World,
+    physics_context: &PhysicsContext,
+    listener_position: Vec3,
+) -> Vec<Entity> {
+    world
+        .query::<(&SoundEmitter, &GlobalTransform)>()
+        .iter()
+        .filter(|(_, (sound_emitter, _))| {
+            sound_emitter.handle.is_some() || sound_emitter.virtual_voice.is_some()
+        })
+        .filter(|(entity, (_, global_transform))| {
+            let to_source = Vec3::from(global_transform.0.translation) - listener_position;
+            physics_context
+                .hitscan(
+                    world,
+                    listener_position,
+                    to_source,
+                    to_source.length(),
+                    Some(*entity),
+                )
+                .is_some()
+        })
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+fn fade_occlusion(sound_emitter: &mut SoundEmitter, target: f32) {
+    let step = DELTA_TIME / OCCLUSION_FADE_TIME;
+    sound_emitter.occlusion += (target - sound_emitter.occlusion).clamp(-step, step);
+}
+
+fn advance_virtual_voice(sound_emitter: &mut SoundEmitter) {
+    let runtime = sound_emitter.frames.runtime();
+    if let Some(virtual_voice) = sound_emitter.virtual_voice.as_mut() {
+        if !virtual_voice.paused {
+            virtual_voice.position += DELTA_TIME as f64;
+        }
+        if virtual_voice.position >= runtime {
+            sound_emitter.virtual_voice = None;
+        }
+    }
+}
+
+/// Give voices to the most important sounds that can be heard, and virtualize the rest.
+fn choose_voices(voices: &mut [Voice], max_voices: usize, audibility_threshold: f32) {
+    let score = |voice: &Voice| {
+        if voice.is_virtual {
+            voice.audibility
+        } else {
+            voice.audibility * VOICE_STICKINESS
+        }
+    };
+    voices.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then(score(b).partial_cmp(&score(a)).unwrap_or(Ordering::Equal))
+    });
+
+    let mut free_voices = max_voices;
+    for voice in voices {
+        voice.should_be_virtual = free_voices == 0 || voice.audibility < audibility_threshold;
+        if !voice.should_be_virtual {
+            free_voices -= 1;
+        }
+    }
+}
+
+// Voice allocation doesn't need an audio device, so unlike `tests` these run everywhere.
+#[cfg(test)]
+mod voice_tests {
+    use super::*;
+
+    #[test]
+    pub fn test_choose_voices() {
+        let mut world = World::new();
+        let voice = |world: &mut World, priority, audibility, is_virtual| Voice {
+            entity: world.spawn(()),
+            priority,
+            audibility,
+            is_virtual,
+            should_be_virtual: true,
+            position: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+        };
+        let loud = voice(&mut world, 0, 1., true);
+        let quiet = voice(&mut world, 0, 0.5, false);
+        let quieter = voice(&mut world, 0, 0.45, true);
+        let important = voice(&mut world, 1, 0.1, true);
+        let inaudible = voice(&mut world, 2, 0.001, false);
+        let mut voices = vec![quieter, loud, inaudible, quiet, important];
+
+        // The important sound goes first, however quiet it is, then the loudest sounds.
+        choose_voices(&mut voices, 3, 0.01);
+        let chosen = voices
+            .iter()
+            .filter(|v| !v.should_be_virtual)
+            .map(|v| v.entity)
+            .collect::<Vec<_>>();
+        assert_eq!(chosen, [important.entity, loud.entity, quiet.entity]);
+
+        // A sound that's already playing keeps its voice over a slightly louder virtual one..
+        let mut voices = vec![
+            voice(&mut world, 0, 0.5, true),
+            voice(&mut world, 0, 0.45, false),
+        ];
+        choose_voices(&mut voices, 1, 0.01);
+        assert!(!voices[0].should_be_virtual);
+        assert!(!voices[0].is_virtual);
+
+        // ..but not a much louder one.
+        let mut voices = vec![
+            voice(&mut world, 0, 0.9, true),
+            voice(&mut world, 0, 0.45, false),
+        ];
+        choose_voices(&mut voices, 1, 0.01);
+        assert!(!voices[0].should_be_virtual);
+        assert!(voices[0].is_virtual);
+    }
+
+    #[test]
+    pub fn test_advance_virtual_voice() {
+        let frames = oddio::Frames::from_slice(100, &[0.; 10]);
+        let mut sound_emitter = SoundEmitter::new(frames);
+        sound_emitter.virtual_voice = Some(VirtualVoice {
+            position: 0.,
+            paused: true,
+        });
+
+        // Paused virtual voices stay where they are..
+        advance_virtual_voice(&mut sound_emitter);
+        assert_eq!(sound_emitter.current_state(), SoundState::Paused);
+        assert_eq!(sound_emitter.virtual_voice.unwrap().position, 0.);
+
+        // ..playing ones move on, and stop at the end of the sound.
+        sound_emitter.virtual_voice.as_mut().unwrap().paused = false;
+        advance_virtual_voice(&mut sound_emitter);
+        assert_eq!(sound_emitter.current_state(), SoundState::Playing);
+        assert_eq!(
+            sound_emitter.virtual_voice.unwrap().position,
+            DELTA_TIME as f64
+        );
+        for _ in 0..10 {
+            advance_virtual_voice(&mut sound_emitter);
+        }
+        assert_eq!(sound_emitter.current_state(), SoundState::Stopped);
+    }
+
+    #[test]
+    pub fn test_find_occluded() {
+        use crate::util::na_vector_from_glam;
+        use rapier3d::prelude::ColliderBuilder;
+
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let frames = oddio::Frames::from_slice(100, &[0.; 10]);
+        let playing = || {
+            let mut sound_emitter = SoundEmitter::new(frames.clone());
+            sound_emitter.virtual_voice = Some(VirtualVoice {
+                position: 0.,
+                paused: false,
+            });
+            sound_emitter
+        };
+        let at = |x: f32| GlobalTransform(glam::Affine3A::from_translation(Vec3::new(x, 0., -5.)));
+
+        // A wall between the listener and the sound straight ahead, but not the one off to the side.
+        let wall = world.spawn(());
+        physics_context.colliders.insert(
+            ColliderBuilder::cuboid(1., 1., 0.1)
+                .translation(na_vector_from_glam(Vec3::new(0., 0., -2.)))
+                .user_data(wall.to_bits().get() as _),
+        );
+        physics_context.update();
+        let behind_wall = world.spawn((playing(), at(0.)));
+        let clear = world.spawn((playing(), at(10.)));
+        let stopped = world.spawn((SoundEmitter::new(frames.clone()), at(0.)));
+
+        let occluded = find_occluded(&world, &physics_context, Vec3::ZERO);
+        assert_eq!(occluded, vec![behind_wall]);
+        assert!(!occluded.contains(&clear));
+        assert!(!occluded.contains(&stopped));
+
+        // Sounds are muffled gradually.
+        let mut sound_emitter = playing();
+        fade_occlusion(&mut sound_emitter, 1.);
+        assert!(sound_emitter.occlusion > 0. && sound_emitter.occlusion < 1.);
+        for _ in 0..100 {
+            fade_occlusion(&mut sound_emitter, 1.);
+        }
+        assert_eq!(sound_emitter.occlusion, 1.);
     }
 }
 
@@ -188,7 +471,13 @@ mod tests {
         );
         physics_context.update();
         xr_context.end_frame().unwrap();
-        audio_system_inner(world, audio_context, xr_context);
+        audio_system_inner(
+            world,
+            audio_context,
+            xr_context,
+            physics_context,
+            &mut Default::default(),
+        );
     }
 
     fn update_xr(xr_context: &mut XrContext) {
@@ -0,0 +1,168 @@
+use glam::{Affine3A, Quat, Vec3};
+use hecs::World;
+
+use crate::{
+    captions::Captions,
+    components::{CaptionPanel, GlobalTransform, LocalTransform, UIPanel, Visible},
+    contexts::physics_context::DELTA_TIME,
+    Engine,
+};
+
+/// How close to the player's gaze a following panel has to get before it stops following, as a fraction of
+/// `CaptionPanel::follow_angle`
+const SETTLE_FRACTION: f32 = 0.1;
+
+/// Captions system
+/// Moves on to the next caption once the current one has been read, then walks through each `CaptionPanel` in the
+/// World, shows the current caption in its `UIPanel` and keeps it in front of the player.
+/// Should be run after `update_global_transform_with_parent_system`, so the HMD's transform is up to date.
+pub fn captions_system(engine: &mut Engine) {
+    let global_from_hmd = match engine.world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    captions_system_inner(
+        &mut engine.world,
+        &mut engine.captions,
+        &global_from_hmd,
+        DELTA_TIME,
+    );
+}
+
+pub fn captions_system_inner(
+    world: &mut World,
+    captions: &mut Captions,
+    global_from_hmd: &Affine3A,
+    delta_time: f32,
+) {
+    captions.update(delta_time);
+    let text = captions.text();
+
+    // Panels that need to be hidden or shown again. We can't do this while iterating through the query.
+    let mut visibility_changes = Vec::new();
+
+    for (entity, (ui_panel, caption_panel, local_transform, global_transform, visible)) in world
+        .query_mut::<(
+            &mut UIPanel,
+            &mut CaptionPanel,
+            &mut LocalTransform,
+            &mut GlobalTransform,
+            Option<&Visible>,
+        )>()
+    {
+        let is_visible = visible.is_some();
+        let text = match &text {
+            Some(text) => text,
+            None => {
+                if is_visible {
+                    visibility_changes.push((entity, false));
+                }
+                continue;
+            }
+        };
+
+        // A panel that's just appeared starts right in front of the player.
+        if !is_visible {
+            visibility_changes.push((entity, true));
+            caption_panel.following = true;
+            local_transform.translation = caption_target(global_from_hmd, caption_panel);
+        }
+        ui_panel.text = text.clone();
+
+        local_transform.translation = follow(
+            global_from_hmd,
+            local_transform.translation,
+            caption_panel,
+            delta_time,
+        );
+        local_transform.rotation = face_viewer(
+            global_from_hmd.translation.into(),
+            local_transform.translation,
+        );
+        *global_transform = (*local_transform).into();
+    }
+
+    for (entity, visible) in visibility_changes {
+        if visible {
+            world.insert_one(entity, Visible {}).unwrap();
+        } else {
+            world.remove_one::<Visible>(entity).unwrap();
+        }
+    }
+}
+
+/// Where the panel would like to be: in front of the player and below their eye line, ignoring their head's pitch
+/// and roll so it doesn't bob about as they look up and down.
+fn caption_target(global_from_hmd: &Affine3A, caption_panel: &CaptionPanel) -> Vec3 {
+    let viewer: Vec3 = global_from_hmd.translation.into();
+    let forward = global_from_hmd.transform_vector3(Vec3::NEG_Z);
+    let forward = Vec3::new(forward.x, 0., forward.z)
+        .try_normalize()
+        .unwrap_or(Vec3::NEG_Z);
+    viewer + forward * caption_panel.distance - Vec3::Y * caption_panel.drop
+}
+
+/// Move the panel towards its target once the player has turned far enough away from it, until it's back in front
+/// of them.
+fn follow(
+    global_from_hmd: &Affine3A,
+    current: Vec3,
+    caption_panel: &mut CaptionPanel,
+    delta_time: f32,
+) -> Vec3 {
+    let viewer: Vec3 = global_from_hmd.translation.into();
+    let target = caption_target(global_from_hmd, caption_panel);
+    let angle = (current - viewer).angle_between(target - viewer);
+    if !angle.is_finite() {
+        return target;
+    }
+
+    if angle > caption_panel.follow_angle {
+        caption_panel.following = true;
+    } else if angle < caption_panel.follow_angle * SETTLE_FRACTION {
+        caption_panel.following = false;
+    }
+    if !caption_panel.following {
+        return current;
+    }
+
+    let t = 1. - (-caption_panel.follow_speed * delta_time).exp();
+    current.lerp(target, t)
+}
+
+/// Turn the panel about the vertical axis so its front faces the viewer.
+fn face_viewer(viewer: Vec3, panel: Vec3) -> Quat {
+    let to_viewer = viewer - panel;
+    Quat::from_rotation_y(to_viewer.x.atan2(to_viewer.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_caption_panel_follows_lazily() {
+        let mut caption_panel = CaptionPanel::default();
+        let global_from_hmd = Affine3A::from_translation([0., 1.6, 0.].into());
+
+        // Straight ahead and below the eye line, facing the viewer.
+        let target = caption_target(&global_from_hmd, &caption_panel);
+        assert_relative_eq!(target, Vec3::new(0., 1.3, -1.2));
+        assert_relative_eq!(
+            face_viewer(global_from_hmd.translation.into(), target),
+            Quat::IDENTITY
+        );
+
+        // Glancing a little to the side doesn't move the panel..
+        caption_panel.following = false;
+        let glance = global_from_hmd * Affine3A::from_rotation_y(10_f32.to_radians());
+        assert_eq!(follow(&glance, target, &mut caption_panel, 0.1), target);
+
+        // ..but turning away does.
+        let turn = global_from_hmd * Affine3A::from_rotation_y(60_f32.to_radians());
+        let moved = follow(&turn, target, &mut caption_panel, 0.1);
+        assert!(caption_panel.following);
+        assert!(moved.x < target.x);
+    }
+}
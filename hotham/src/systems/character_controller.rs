@@ -0,0 +1,339 @@
+use glam::{Affine3A, Quat, Vec2, Vec3};
+use hecs::{Entity, World};
+use rapier3d::parry::shape::Capsule;
+
+use crate::{
+    components::{CharacterController, GlobalTransform, LocalTransform},
+    contexts::{
+        physics_context::{CastFilter, RayHit},
+        PhysicsContext,
+    },
+    Engine,
+};
+
+/// How far the capsule is kept from anything it touches, so the next cast doesn't start inside it
+const SKIN: f32 = 0.01;
+
+/// How many times a move can slide along what it runs into
+const MAX_SLIDES: usize = 3;
+
+/// Character controller system
+/// Walks each entity with a [`CharacterController`] in the direction the player's looking with the left thumbstick,
+/// stepping up ledges, sliding along walls and falling when there's nothing underneath it.
+/// Should be run after `physics_system` and before `update_global_transform_system`.
+pub fn character_controller_system(engine: &mut Engine) {
+    let global_from_hmd = match engine.world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    let thumbstick = engine.input_context.left.thumbstick_xy();
+    let delta_time = engine.time_context.delta_time();
+    let before = player_position(&engine.world);
+    character_controller_system_inner(
+        &mut engine.world,
+        &engine.physics_context,
+        thumbstick,
+        &global_from_hmd,
+        delta_time,
+    );
+
+    // Walking and falling are artificial motion, so narrow the view while they're happening.
+    if let (Some(before), Some(after)) = (before, player_position(&engine.world)) {
+        if delta_time > 0. {
+            let velocity = (after - before) / delta_time;
+            engine.effects_context.drive_vignette(velocity, Vec3::ZERO);
+        }
+    }
+}
+
+/// Where the first entity with a [`CharacterController`] is, if there is one
+fn player_position(world: &World) -> Option<Vec3> {
+    world
+        .query::<(&CharacterController, &GlobalTransform)>()
+        .iter()
+        .next()
+        .map(|(_, (_, global_transform))| global_transform.0.translation.into())
+}
+
+pub fn character_controller_system_inner(
+    world: &mut World,
+    physics_context: &PhysicsContext,
+    thumbstick: Vec2,
+    global_from_hmd: &Affine3A,
+    delta_time: f32,
+) {
+    // Walk the way the player's looking, ignoring how far up or down, so looking at the floor doesn't slow them down.
+    let look = global_from_hmd.transform_vector3(Vec3::NEG_Z);
+    let forward = Vec3::new(look.x, 0., look.z).normalize_or_zero();
+    let right = Vec3::new(-forward.z, 0., forward.x);
+    let walk = (right * thumbstick.x + forward * thumbstick.y).clamp_length_max(1.);
+    let head: Vec3 = global_from_hmd.translation.into();
+
+    // The world can't be changed while it's being searched for collisions, so work out every move first.
+    let moves = world
+        .query::<(&CharacterController, &GlobalTransform)>()
+        .iter()
+        .map(|(entity, (controller, global_transform))| {
+            // The player stands under their head, wherever they are in the play area.
+            let feet = Vec3::new(head.x, global_transform.0.translation.y, head.z);
+            let mut controller = *controller;
+            let translation = walk * controller.speed * delta_time;
+            let moved_to = move_character(
+                physics_context,
+                world,
+                entity,
+                &mut controller,
+                feet,
+                translation,
+                delta_time,
+            );
+            (entity, controller, moved_to - feet)
+        })
+        .collect::<Vec<_>>();
+
+    for (entity, controller, translation) in moves {
+        let (character_controller, local_transform, global_transform) = world
+            .query_one_mut::<(
+                &mut CharacterController,
+                &mut LocalTransform,
+                &mut GlobalTransform,
+            )>(entity)
+            .unwrap();
+        *character_controller = controller;
+        local_transform.translation += translation;
+        global_transform.0.translation += translation.into();
+    }
+}
+
+/// Move a character standing at `feet` by `walk`, then onto the ground or down with gravity, returning where its feet
+/// end up. Updates whether it's grounded and how fast it's falling.
+fn move_character(
+    physics_context: &PhysicsContext,
+    world: &World,
+    entity: Entity,
+    controller: &mut CharacterController,
+    feet: Vec3,
+    walk: Vec3,
+    delta_time: f32,
+) -> Vec3 {
+    let half_height = (controller.height * 0.5).max(controller.radius);
+    let capsule = Capsule::new_y(half_height - controller.radius, controller.radius);
+    let ignore = [entity];
+    let filter = CastFilter {
+        groups: controller.collision_filter,
+        ignore: &ignore,
+        ..Default::default()
+    };
+    let sweep = |center: Vec3, translation: Vec3| {
+        physics_context.cast_shape(world, &capsule, center, Quat::IDENTITY, translation, filter)
+    };
+    let max_slope_cos = controller.max_slope.cos();
+    let mut center = feet + Vec3::Y * half_height;
+
+    // Lift the capsule over any ledge it can step onto, as far as there's room above it..
+    let lift = if controller.grounded {
+        let lift = sweep(center, Vec3::Y * controller.step_offset)
+            .map_or(controller.step_offset, |hit| (hit.distance - SKIN).max(0.));
+        center.y += lift;
+        lift
+    } else {
+        0.
+    };
+
+    // ..walk, sliding along anything in the way..
+    center = slide(&sweep, center, walk, max_slope_cos);
+
+    // ..then put it back down, onto the ground if there's some close enough underneath, or let it fall.
+    if controller.grounded {
+        controller.vertical_velocity = 0.;
+    } else {
+        controller.vertical_velocity += physics_context.gravity.y * delta_time;
+    }
+    let fall = (-controller.vertical_velocity * delta_time).max(0.);
+    let snap = if controller.grounded {
+        controller.step_offset
+    } else {
+        0.
+    };
+    let down = Vec3::NEG_Y * (lift + snap + fall);
+    match sweep(center, down) {
+        Some(hit) if is_ground(physics_context, world, filter, center, &hit, max_slope_cos) => {
+            center.y -= (hit.distance - SKIN).max(0.);
+            controller.grounded = true;
+            controller.vertical_velocity = 0.;
+        }
+        // Slopes that are too steep to stand on are slid down.
+        Some(_) => {
+            center = slide(&sweep, center, Vec3::NEG_Y * (lift + fall), max_slope_cos);
+            controller.grounded = false;
+        }
+        None => {
+            center.y -= lift + fall;
+            controller.grounded = false;
+        }
+    }
+
+    center - Vec3::Y * half_height
+}
+
+/// Move the capsule at `center` by `translation`, sliding along whatever it runs into. Slopes steeper than the one
+/// `max_slope_cos` is the cosine of are treated as walls, so they can't be walked up.
+fn slide(
+    sweep: &impl Fn(Vec3, Vec3) -> Option<RayHit>,
+    mut center: Vec3,
+    mut translation: Vec3,
+    max_slope_cos: f32,
+) -> Vec3 {
+    for _ in 0..MAX_SLIDES {
+        let hit = match sweep(center, translation) {
+            Some(hit) => hit,
+            None => return center + translation,
+        };
+
+        // Stop just short of what was hit..
+        let travelled = (hit.distance - SKIN).max(0.) / translation.length();
+        center += translation * travelled;
+        translation *= 1. - travelled;
+
+        // ..and take away the part of the move that goes into it.
+        let mut normal = facing(hit.normal, translation);
+        if normal.y < max_slope_cos && translation.y >= 0. {
+            normal = Vec3::new(normal.x, 0., normal.z).normalize_or_zero();
+        }
+        translation -= normal * translation.dot(normal).min(0.);
+        if translation.length_squared() < SKIN * SKIN * 0.01 {
+            break;
+        }
+    }
+    center
+}
+
+/// Can a capsule at `center` stand on what it `hit` below it?
+fn is_ground(
+    physics_context: &PhysicsContext,
+    world: &World,
+    filter: CastFilter,
+    center: Vec3,
+    hit: &RayHit,
+    max_slope_cos: f32,
+) -> bool {
+    if facing(hit.normal, Vec3::NEG_Y).y >= max_slope_cos {
+        return true;
+    }
+
+    // The capsule's round bottom touches the edges of steps at an angle, so look at what's just past the edge.
+    let outward = Vec3::new(hit.point.x - center.x, 0., hit.point.z - center.z).normalize_or_zero();
+    let origin = Vec3::new(hit.point.x, center.y, hit.point.z) + outward * SKIN;
+    physics_context
+        .cast_ray(
+            world,
+            origin,
+            Vec3::NEG_Y,
+            center.y - hit.point.y + SKIN * 2.,
+            filter,
+        )
+        .map_or(false, |ground| ground.normal.y >= max_slope_cos)
+}
+
+/// `normal`, flipped if need be to face against `translation`
+fn facing(normal: Vec3, translation: Vec3) -> Vec3 {
+    if normal.dot(translation) > 0. {
+        -normal
+    } else {
+        normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    use crate::{
+        components::Collider, contexts::physics_context::DELTA_TIME,
+        systems::physics::physics_system_inner,
+    };
+    use rapier3d::prelude::SharedShape;
+
+    #[test]
+    pub fn test_character_controller() {
+        // Walk forward over a low step, up to a wall..
+        let (mut world, physics_context, player) = setup(0.2);
+        tick(&mut world, &physics_context, player, Vec2::ZERO, 60);
+        let controller = *world.get::<&CharacterController>(player).unwrap();
+        assert!(controller.grounded);
+        assert_relative_eq!(translation(&world, player).y, SKIN, epsilon = 0.005);
+
+        tick(&mut world, &physics_context, player, Vec2::Y, 72 * 4);
+        let position = translation(&world, player);
+        assert!(world.get::<&CharacterController>(player).unwrap().grounded);
+        assert_relative_eq!(position.y, 0.2 + SKIN, epsilon = 0.005);
+        assert_relative_eq!(position.z, -4.9 + 0.25 + SKIN, epsilon = 0.005);
+
+        // ..but a ledge that's too high can't be stepped onto.
+        let (mut world, physics_context, player) = setup(0.5);
+        tick(&mut world, &physics_context, player, Vec2::Y, 72 * 4);
+        let position = translation(&world, player);
+        assert_relative_eq!(position.y, SKIN, epsilon = 0.005);
+        assert_relative_eq!(position.z, -2. + 0.25 + SKIN, epsilon = 0.005);
+    }
+
+    fn setup(ledge_height: f32) -> (World, PhysicsContext, Entity) {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let mut add_box = |half_extents: Vec3, center: Vec3| {
+            world.spawn((
+                Collider::new(SharedShape::cuboid(
+                    half_extents.x,
+                    half_extents.y,
+                    half_extents.z,
+                )),
+                GlobalTransform(Affine3A::from_translation(center)),
+            ));
+        };
+
+        // A floor, a ledge starting two metres in front of the player and a wall at the back of the ledge.
+        add_box(Vec3::new(10., 0.1, 10.), Vec3::NEG_Y * 0.1);
+        add_box(
+            Vec3::new(5., ledge_height * 0.5, 1.5),
+            Vec3::new(0., ledge_height * 0.5, -3.5),
+        );
+        add_box(Vec3::new(5., 2., 0.1), Vec3::new(0., 2., -5.));
+        physics_system_inner(&mut physics_context, &mut world);
+
+        let player = world.spawn((
+            CharacterController::default(),
+            LocalTransform {
+                translation: Vec3::Y * 0.5,
+                ..Default::default()
+            },
+            GlobalTransform(Affine3A::from_translation(Vec3::Y * 0.5)),
+        ));
+        (world, physics_context, player)
+    }
+
+    fn tick(
+        world: &mut World,
+        physics_context: &PhysicsContext,
+        player: Entity,
+        thumbstick: Vec2,
+        frames: usize,
+    ) {
+        for _ in 0..frames {
+            // The player's head is above the middle of the play area, looking forward.
+            let global_from_hmd =
+                Affine3A::from_translation(translation(world, player) + Vec3::Y * 1.7);
+            character_controller_system_inner(
+                world,
+                physics_context,
+                thumbstick,
+                &global_from_hmd,
+                DELTA_TIME,
+            );
+        }
+    }
+
+    fn translation(world: &World, entity: Entity) -> Vec3 {
+        world.get::<&LocalTransform>(entity).unwrap().translation
+    }
+}
@@ -0,0 +1,307 @@
+use glam::{Affine3A, Vec2, Vec3};
+use hecs::World;
+
+use crate::{
+    components::{
+        hand::Handedness,
+        stage::{get_global_from_stage, set_global_from_stage},
+        ComfortLocomotion, GlobalTransform, TurnMode,
+    },
+    contexts::{
+        effects_context::FadeEvent,
+        physics_context::{CastFilter, PhysicsContext},
+        EffectsContext,
+    },
+    Engine,
+};
+
+/// How far forward the thumbstick has to be pushed to start aiming a teleport
+const AIM_THRESHOLD: f32 = 0.6;
+
+/// How far to the side the thumbstick has to be pushed to snap turn
+const SNAP_THRESHOLD: f32 = 0.7;
+
+/// How close to the middle the thumbstick has to come back to before it can snap turn or teleport again
+const RELEASE_THRESHOLD: f32 = 0.3;
+
+/// How far to the side the thumbstick has to be pushed before smooth turning starts
+const SMOOTH_DEAD_ZONE: f32 = 0.2;
+
+/// How much time each segment of the teleport arc covers, in seconds
+const ARC_STEP: f32 = 0.03;
+
+/// How many segments the teleport arc has at most
+const ARC_SEGMENTS: usize = 60;
+
+/// Comfort locomotion system
+/// Teleports and turns the player with the thumbstick, for the [`ComfortLocomotion`] on the stage.
+/// Should be run after `physics_system` and before `update_global_transform_system`, along with `effects_system`.
+pub fn comfort_locomotion_system(engine: &mut Engine) {
+    let handedness = match engine.world.query::<&ComfortLocomotion>().iter().next() {
+        Some((_, locomotion)) => locomotion.handedness,
+        None => return,
+    };
+    let global_from_hmd = match engine.world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    let input_context = &engine.input_context;
+    let (thumbstick, stage_from_aim) = match handedness {
+        Handedness::Left => (
+            input_context.left.thumbstick_xy(),
+            input_context.left.stage_from_aim(),
+        ),
+        Handedness::Right => (
+            input_context.right.thumbstick_xy(),
+            input_context.right.stage_from_aim(),
+        ),
+    };
+    let delta_time = engine.time_context.delta_time();
+    comfort_locomotion_system_inner(
+        &mut engine.world,
+        &engine.physics_context,
+        &mut engine.effects_context,
+        thumbstick,
+        &stage_from_aim,
+        &global_from_hmd,
+        delta_time,
+    );
+}
+
+pub fn comfort_locomotion_system_inner(
+    world: &mut World,
+    physics_context: &PhysicsContext,
+    effects_context: &mut EffectsContext,
+    thumbstick: Vec2,
+    stage_from_aim: &Affine3A,
+    global_from_hmd: &Affine3A,
+    delta_time: f32,
+) {
+    // The world is searched for where the arc lands, so work on a copy of the component.
+    let (entity, mut locomotion) = match world.query::<&ComfortLocomotion>().iter().next() {
+        Some((entity, locomotion)) => (entity, locomotion.clone()),
+        None => return,
+    };
+    let global_from_stage = get_global_from_stage(world);
+    let head: Vec3 = global_from_hmd.translation.into();
+    let mut moved_to = None;
+
+    if let Some(target) = locomotion.pending_teleport {
+        // Wait until the view can't be seen before moving the player, so they don't see the world jump..
+        if effects_context
+            .fade_events_this_frame
+            .contains(&FadeEvent::FadedOut)
+        {
+            // ..then put their feet, under their head, on the target.
+            let feet = Vec3::new(head.x, global_from_stage.translation.y, head.z);
+            let mut teleported = global_from_stage;
+            teleported.translation += (target - feet).into();
+            moved_to = Some(teleported);
+            locomotion.pending_teleport = None;
+            effects_context.fade_in(locomotion.fade_duration);
+        }
+    } else if locomotion.teleport && thumbstick.y > AIM_THRESHOLD {
+        let global_from_aim = global_from_stage * *stage_from_aim;
+        aim(physics_context, world, &mut locomotion, &global_from_aim);
+    } else if !locomotion.arc.is_empty() && thumbstick.y < RELEASE_THRESHOLD {
+        // Letting go of the thumbstick teleports the player to wherever they were aiming.
+        if let Some(target) = locomotion.target {
+            locomotion.pending_teleport = Some(target);
+            effects_context.fade_out(locomotion.fade_duration, Vec3::ZERO);
+        }
+        locomotion.arc.clear();
+        locomotion.target = None;
+    } else if locomotion.arc.is_empty() {
+        let angle = turn_angle(&mut locomotion, thumbstick.x, delta_time);
+        // Smooth turning is artificial motion, so narrow the view while it's happening. Snap turns are over too quickly
+        // to need it.
+        if matches!(locomotion.turn, TurnMode::Smooth { .. }) && angle != 0. && delta_time > 0. {
+            effects_context.drive_vignette(Vec3::ZERO, Vec3::Y * (angle / delta_time));
+        }
+        if angle != 0. {
+            // Turn around the player's head, so it stays where it is.
+            let turned = Affine3A::from_translation(head)
+                * Affine3A::from_rotation_y(angle)
+                * Affine3A::from_translation(-head)
+                * global_from_stage;
+            moved_to = Some(turned);
+        }
+    }
+
+    *world.get::<&mut ComfortLocomotion>(entity).unwrap() = locomotion;
+    if let Some(global_from_stage) = moved_to {
+        set_global_from_stage(world, &global_from_stage);
+    }
+}
+
+/// Follow the teleport arc from the controller until it lands, and work out whether the player can stand there.
+fn aim(
+    physics_context: &PhysicsContext,
+    world: &World,
+    locomotion: &mut ComfortLocomotion,
+    global_from_aim: &Affine3A,
+) {
+    let filter = CastFilter::groups(locomotion.collision_filter);
+    let origin: Vec3 = global_from_aim.translation.into();
+    let velocity =
+        global_from_aim.transform_vector3(Vec3::NEG_Z).normalize() * locomotion.arc_speed;
+    let gravity = physics_context.gravity;
+
+    locomotion.arc.clear();
+    locomotion.arc.push(origin);
+    locomotion.target = None;
+    for segment in 1..=ARC_SEGMENTS {
+        let time = segment as f32 * ARC_STEP;
+        let from = *locomotion.arc.last().unwrap();
+        let to = origin + velocity * time + gravity * (0.5 * time * time);
+        let hit = physics_context.cast_ray(world, from, to - from, from.distance(to), filter);
+        if let Some(hit) = hit {
+            locomotion.arc.push(hit.point);
+            if hit.normal.y >= locomotion.max_slope.cos() {
+                locomotion.target = Some(hit.point);
+            }
+            return;
+        }
+        locomotion.arc.push(to);
+    }
+}
+
+/// How far to turn the player this frame, in radians, with the thumbstick pushed `x` to the side
+fn turn_angle(locomotion: &mut ComfortLocomotion, x: f32, delta_time: f32) -> f32 {
+    match locomotion.turn {
+        TurnMode::Off => 0.,
+        TurnMode::Snap { angle } => {
+            if x.abs() < RELEASE_THRESHOLD {
+                locomotion.turn_ready = true;
+            }
+            if locomotion.turn_ready && x.abs() > SNAP_THRESHOLD {
+                locomotion.turn_ready = false;
+                // Pushing right turns clockwise, looking down.
+                -angle * x.signum()
+            } else {
+                0.
+            }
+        }
+        TurnMode::Smooth { speed } if x.abs() > SMOOTH_DEAD_ZONE => -x * speed * delta_time,
+        TurnMode::Smooth { .. } => 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::Quat;
+    use rapier3d::prelude::SharedShape;
+    use std::f32::consts::FRAC_PI_4;
+
+    use crate::{
+        components::{Collider, LocalTransform, Stage},
+        contexts::physics_context::DELTA_TIME,
+        systems::physics::physics_system_inner,
+    };
+
+    #[test]
+    pub fn test_snap_turn() {
+        let (mut world, physics_context, mut effects_context) = setup();
+        let global_from_hmd = Affine3A::from_translation([1., 1.7, 0.].into());
+        let mut tick = |world: &mut World, x: f32| {
+            comfort_locomotion_system_inner(
+                world,
+                &physics_context,
+                &mut effects_context,
+                Vec2::new(x, 0.),
+                &Affine3A::IDENTITY,
+                &global_from_hmd,
+                DELTA_TIME,
+            );
+            get_global_from_stage(world)
+        };
+
+        // Pushing the thumbstick right turns the player clockwise, around their head..
+        let global_from_stage = tick(&mut world, 1.);
+        let (_, rotation, _) = global_from_stage.to_scale_rotation_translation();
+        assert_relative_eq!(
+            rotation,
+            Quat::from_rotation_y(-FRAC_PI_4),
+            epsilon = 0.0001
+        );
+        assert_relative_eq!(
+            global_from_stage.transform_point3([1., 1.7, 0.].into()),
+            Vec3::new(1., 1.7, 0.),
+            epsilon = 0.0001
+        );
+
+        // ..once, until it's let go of.
+        assert_eq!(tick(&mut world, 1.), global_from_stage);
+        tick(&mut world, 0.);
+        let global_from_stage = tick(&mut world, -1.);
+        let (_, rotation, _) = global_from_stage.to_scale_rotation_translation();
+        assert_relative_eq!(rotation, Quat::IDENTITY, epsilon = 0.0001);
+    }
+
+    #[test]
+    pub fn test_teleport() {
+        let (mut world, physics_context, mut effects_context) = setup();
+        let global_from_hmd = Affine3A::from_translation([0., 1.7, 0.].into());
+        // Aim forward and down from the right hand.
+        let stage_from_aim = Affine3A::from_rotation_translation(
+            Quat::from_rotation_x(-FRAC_PI_4),
+            [0.2, 1., 0.].into(),
+        );
+        let mut tick = |world: &mut World, y: f32| {
+            effects_context.update_fade(DELTA_TIME);
+            comfort_locomotion_system_inner(
+                world,
+                &physics_context,
+                &mut effects_context,
+                Vec2::new(0., y),
+                &stage_from_aim,
+                &global_from_hmd,
+                DELTA_TIME,
+            );
+        };
+
+        // Pushing the thumbstick forward aims at the floor in front of the player..
+        tick(&mut world, 1.);
+        let target = world
+            .query_mut::<&ComfortLocomotion>()
+            .into_iter()
+            .next()
+            .unwrap()
+            .1
+            .target
+            .unwrap();
+        assert_relative_eq!(target.y, 0., epsilon = 0.0001);
+        assert!(target.z < -0.5);
+
+        // ..and letting go of it teleports them there, once the view has faded out.
+        for _ in 0..20 {
+            tick(&mut world, 0.);
+        }
+        let global_from_stage = get_global_from_stage(&world);
+        assert_relative_eq!(
+            Vec3::from(global_from_stage.translation),
+            Vec3::new(target.x, 0., target.z),
+            epsilon = 0.0001
+        );
+        assert_eq!(effects_context.fade_color().w, 0.);
+    }
+
+    fn setup() -> (World, PhysicsContext, EffectsContext) {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        world.spawn((
+            Collider::new(SharedShape::cuboid(10., 0.1, 10.)),
+            GlobalTransform(Affine3A::from_translation(Vec3::NEG_Y * 0.1)),
+        ));
+        physics_system_inner(&mut physics_context, &mut world);
+        world.spawn((
+            Stage,
+            ComfortLocomotion::default(),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+        ));
+        (world, physics_context, EffectsContext::default())
+    }
+}
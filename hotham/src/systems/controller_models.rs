@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+use hecs::{Entity, World};
+
+use crate::{
+    asset_importer::{add_model_to_world, load_models_from_glb},
+    components::{
+        hand::Handedness, stage, ControllerModel, GlobalTransform, LocalTransform, Visible,
+    },
+    contexts::{InputContext, RenderContext, VulkanContext, XrContext},
+    Engine,
+};
+
+/// Controller models system
+/// Moves each entity with a [`ControllerModel`] to where its controller is, so the model under it matches what the
+/// player is holding.
+pub fn controller_models_system(engine: &mut Engine) {
+    controller_models_system_inner(&mut engine.world, &engine.input_context);
+}
+
+pub fn controller_models_system_inner(world: &mut World, input_context: &InputContext) {
+    let global_from_stage = stage::get_global_from_stage(world);
+
+    for (_, (controller_model, local_transform, global_transform)) in world
+        .query::<(&ControllerModel, &mut LocalTransform, &mut GlobalTransform)>()
+        .iter()
+    {
+        let stage_from_grip = match controller_model.handedness {
+            Handedness::Left => input_context.left.stage_from_grip(),
+            Handedness::Right => input_context.right.stage_from_grip(),
+        };
+        let global_from_local = global_from_stage * stage_from_grip;
+        local_transform.update_from_affine(&global_from_local);
+        global_transform.0 = global_from_local;
+    }
+}
+
+/// Spawn a model of the controller on `handedness`'s side that follows it around. The model comes from the runtime if
+/// it has one, so it matches the player's hardware, and is the built-in hand model if not. Returns the entity with the
+/// [`ControllerModel`], which the model's entities are children of.
+pub fn add_controller_model(
+    xr_context: &XrContext,
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+    handedness: Handedness,
+    world: &mut World,
+) -> Result<Entity> {
+    let runtime_model = xr_context.controller_model(handedness).unwrap_or_else(|e| {
+        println!(
+            "[HOTHAM_CONTROLLER_MODELS] Unable to load {:?} controller model from the runtime: {:?}",
+            handedness, e
+        );
+        None
+    });
+    let from_runtime = runtime_model.is_some();
+    let glb = runtime_model.unwrap_or_else(|| fallback_model(handedness).to_vec());
+    let models = load_models_from_glb(&[&glb], vulkan_context, render_context)?;
+    if models.is_empty() {
+        return Err(anyhow!("The {:?} controller model is empty", handedness));
+    }
+
+    let controller_entity = world.spawn((
+        ControllerModel {
+            handedness,
+            from_runtime,
+        },
+        LocalTransform::default(),
+        GlobalTransform::default(),
+        Visible {},
+    ));
+    for name in models.keys() {
+        add_model_to_world(name, &models, world, Some(controller_entity));
+    }
+
+    Ok(controller_entity)
+}
+
+/// The model shown when the runtime doesn't have one of the controller
+fn fallback_model(handedness: Handedness) -> &'static [u8] {
+    match handedness {
+        Handedness::Left => include_bytes!("../../data/left_hand.glb"),
+        Handedness::Right => include_bytes!("../../data/right_hand.glb"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_controller_models_system() {
+        let mut world = World::new();
+        let input_context = InputContext::testing();
+        let controller = world.spawn((
+            ControllerModel {
+                handedness: Handedness::Left,
+                from_runtime: true,
+            },
+            LocalTransform::default(),
+            GlobalTransform::default(),
+        ));
+
+        controller_models_system_inner(&mut world, &input_context);
+
+        // The model should be wherever the controller is.
+        let local_transform = world.get::<&LocalTransform>(controller).unwrap();
+        assert_relative_eq!(
+            local_transform.translation,
+            input_context.left.stage_from_grip().translation.into()
+        );
+        let global_transform = world.get::<&GlobalTransform>(controller).unwrap();
+        assert_relative_eq!(global_transform.0, input_context.left.stage_from_grip());
+    }
+}
@@ -0,0 +1,28 @@
+use hecs::World;
+
+use crate::{
+    components::{DebugPanel, UIPanel},
+    frame_pacing::FramePacingStats,
+    memory_stats::MemoryStats,
+    Engine,
+};
+
+/// Debug panel system
+/// Walks through each `DebugPanel` in the World and shows the latest `MemoryStats` and `FramePacingStats` in its
+/// `UIPanel`
+pub fn debug_panel_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let memory_stats = &engine.memory_stats;
+    let frame_pacing = engine.frame_pacing.stats();
+    debug_panel_system_inner(world, memory_stats, frame_pacing);
+}
+
+pub fn debug_panel_system_inner(
+    world: &mut World,
+    memory_stats: &MemoryStats,
+    frame_pacing: &FramePacingStats,
+) {
+    for (_, (ui_panel, _)) in world.query_mut::<(&mut UIPanel, &DebugPanel)>() {
+        ui_panel.text = format!("{}{}", memory_stats, frame_pacing);
+    }
+}
@@ -0,0 +1,288 @@
+use glam::{Affine3A, Vec3, Vec3A};
+use hecs::{Entity, World};
+
+use crate::{
+    components::{
+        distance_grab::Pull, hand::Handedness, physics::BodyType, stage, DistanceGrab,
+        DistanceGrabTarget, GlobalTransform, Grabbable, Hand, LocalTransform, RigidBody,
+    },
+    contexts::{
+        physics_context::{CastFilter, DELTA_TIME, HAND_COLLISION_GROUP},
+        InputContext, PhysicsContext,
+    },
+    util::lerp_slerp,
+    Engine,
+};
+
+/// Distance grab system
+/// Allows hands with a [`DistanceGrab`] component to pull [`Grabbable`] objects towards them from afar.
+/// Should be run after `hands_system` and before `grabbing_system`.
+pub fn distance_grab_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let input_context = &engine.input_context;
+    let physics_context = &engine.physics_context;
+    distance_grab_system_inner(world, input_context, physics_context);
+}
+
+pub fn distance_grab_system_inner(
+    world: &mut World,
+    input_context: &InputContext,
+    physics_context: &PhysicsContext,
+) {
+    let global_from_stage = stage::get_global_from_stage(world);
+
+    // Objects that need to have their transforms updated or their body types changed.
+    // We can't modify them while iterating through the hands.
+    let mut pulled = Vec::new();
+    let mut released = Vec::new();
+    let mut targets = Vec::new();
+
+    for (hand_entity, (hand, distance_grab, hand_transform)) in world
+        .query::<(&mut Hand, &mut DistanceGrab, &GlobalTransform)>()
+        .iter()
+    {
+        let (stage_from_aim, grip_just_pressed) = match hand.handedness {
+            Handedness::Left => (
+                input_context.left.stage_from_aim(),
+                input_context.left.grip_button_just_pressed(),
+            ),
+            Handedness::Right => (
+                input_context.right.stage_from_aim(),
+                input_context.right.grip_button_just_pressed(),
+            ),
+        };
+
+        // If we're already holding something, there's nothing to do.
+        if hand.grabbed_entity.is_some() {
+            distance_grab.target = None;
+            distance_grab.pull = None;
+            continue;
+        }
+
+        if let Some(mut pull) = distance_grab.pull.take() {
+            // Letting go of the grip cancels the pull.
+            if hand.grip_value <= 0.1 {
+                released.push(pull.entity);
+                continue;
+            }
+
+            pull.elapsed += DELTA_TIME;
+            let t = (pull.elapsed / distance_grab.pull_duration).min(1.0);
+            let global_from_pulled = pull_curve(
+                &pull.global_from_start,
+                &hand_transform.0,
+                distance_grab.arc_height,
+                t,
+            );
+            pulled.push((pull.entity, global_from_pulled));
+
+            // Once it's arrived, hand it over to the hand.
+            if t >= 1.0 {
+                hand.grabbed_entity = Some(pull.entity);
+            } else {
+                distance_grab.pull = Some(pull);
+            }
+            continue;
+        }
+
+        // Find out what we're pointing at.
+        let global_from_aim = global_from_stage * stage_from_aim;
+        distance_grab.target = find_target(
+            world,
+            physics_context,
+            &global_from_aim,
+            distance_grab.max_distance,
+        );
+
+        if let Some(target) = distance_grab.target {
+            targets.push((target, hand_entity));
+
+            if grip_just_pressed {
+                let global_from_start = world
+                    .get::<&GlobalTransform>(target)
+                    .map(|g| g.0)
+                    .unwrap_or(Affine3A::IDENTITY);
+                distance_grab.pull = Some(Pull {
+                    entity: target,
+                    global_from_start,
+                    elapsed: 0.,
+                });
+                distance_grab.target = None;
+            }
+        }
+    }
+
+    // Move anything that's being pulled, making sure the physics simulation doesn't fight us.
+    for (entity, global_from_pulled) in pulled {
+        if let Ok(mut rigid_body) = world.get::<&mut RigidBody>(entity) {
+            rigid_body.body_type = BodyType::KinematicPositionBased;
+        }
+        if let Ok(mut local_transform) = world.get::<&mut LocalTransform>(entity) {
+            local_transform.update_rotation_translation_from_affine(&global_from_pulled);
+            if let Ok(mut global_transform) = world.get::<&mut GlobalTransform>(entity) {
+                *global_transform = (*local_transform).into();
+            }
+        }
+    }
+
+    // Drop anything that was let go of mid-flight.
+    for entity in released {
+        if let Ok(mut rigid_body) = world.get::<&mut RigidBody>(entity) {
+            rigid_body.body_type = BodyType::Dynamic;
+        }
+    }
+
+    // Finally, update the highlighted targets.
+    let stale_targets = world
+        .query::<&DistanceGrabTarget>()
+        .iter()
+        .filter(|(entity, _)| !targets.iter().any(|(t, _)| t == entity))
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    for entity in stale_targets {
+        let _ = world.remove_one::<DistanceGrabTarget>(entity);
+    }
+    for (target, hand) in targets {
+        let _ = world.insert_one(target, DistanceGrabTarget { hand });
+    }
+}
+
+/// Cast a ray along the aim pose and return the first object hit, if it can be grabbed.
+fn find_target(
+    world: &World,
+    physics_context: &PhysicsContext,
+    global_from_aim: &Affine3A,
+    max_distance: f32,
+) -> Option<Entity> {
+    let (_, rotation, translation) = global_from_aim.to_scale_rotation_translation();
+
+    // Ignore the hands and anything that can't be touched, but let walls and other objects block the ray.
+    let entity = physics_context
+        .cast_ray(
+            world,
+            translation,
+            rotation * Vec3::NEG_Z,
+            max_distance,
+            CastFilter::groups(!HAND_COLLISION_GROUP),
+        )?
+        .entity;
+    world.get::<&Grabbable>(entity).ok()?;
+    Some(entity)
+}
+
+/// Where a pulled object should be `t` of the way through its flight from `global_from_start` to `global_from_hand`.
+fn pull_curve(
+    global_from_start: &Affine3A,
+    global_from_hand: &Affine3A,
+    arc_height: f32,
+    t: f32,
+) -> Affine3A {
+    // Ease in and out, so the object doesn't jerk into motion or slam into the hand.
+    let eased = t * t * (3.0 - 2.0 * t);
+    let mut global_from_pulled = lerp_slerp(global_from_start, global_from_hand, eased);
+
+    // Lift the object into an arc, peaking halfway through the flight.
+    global_from_pulled.translation += Vec3A::Y * (4.0 * arc_height * t * (1.0 - t));
+    global_from_pulled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_pull_to_hand() {
+        let (mut world, input_context, physics_context) = setup();
+        let (hand, pulled_entity) = add_pull(&mut world);
+
+        // Hold the grip down until the pull completes.
+        world.get::<&mut Hand>(hand).unwrap().grip_value = 1.0;
+        for _ in 0..30 {
+            tick(&mut world, &input_context, &physics_context);
+        }
+
+        let hand_component = world.get::<&Hand>(hand).unwrap();
+        assert_eq!(hand_component.grabbed_entity, Some(pulled_entity));
+
+        let local_transform = world.get::<&LocalTransform>(pulled_entity).unwrap();
+        assert_relative_eq!(
+            local_transform.translation,
+            Vec3::new(-0.2, 1.4, -0.5),
+            epsilon = 1e-6
+        );
+
+        let rigid_body = world.get::<&RigidBody>(pulled_entity).unwrap();
+        assert_eq!(rigid_body.body_type, BodyType::KinematicPositionBased);
+    }
+
+    #[test]
+    pub fn test_cancel_pull() {
+        let (mut world, input_context, physics_context) = setup();
+        let (hand, pulled_entity) = add_pull(&mut world);
+
+        // The grip isn't held, so the pull should be cancelled straight away.
+        tick(&mut world, &input_context, &physics_context);
+
+        let hand_component = world.get::<&Hand>(hand).unwrap();
+        assert_eq!(hand_component.grabbed_entity, None);
+        assert!(!world.get::<&DistanceGrab>(hand).unwrap().is_pulling());
+
+        let rigid_body = world.get::<&RigidBody>(pulled_entity).unwrap();
+        assert_eq!(rigid_body.body_type, BodyType::Dynamic);
+    }
+
+    #[test]
+    pub fn test_pull_curve() {
+        let start = Affine3A::from_translation([0., 0., -5.].into());
+        let hand = Affine3A::IDENTITY;
+
+        assert_relative_eq!(
+            pull_curve(&start, &hand, 1., 0.).translation,
+            start.translation
+        );
+        assert_relative_eq!(
+            pull_curve(&start, &hand, 1., 1.).translation,
+            hand.translation
+        );
+
+        // Halfway through, the object should be at the top of its arc.
+        let halfway = pull_curve(&start, &hand, 1., 0.5).translation;
+        assert_relative_eq!(halfway, Vec3A::new(0., 1., -2.5));
+    }
+
+    fn setup() -> (World, InputContext, PhysicsContext) {
+        (
+            World::new(),
+            InputContext::testing(),
+            PhysicsContext::default(),
+        )
+    }
+
+    fn add_pull(world: &mut World) -> (Entity, Entity) {
+        let global_from_start = Affine3A::from_translation([0., 1., -4.].into());
+        let pulled_entity = world.spawn((
+            Grabbable {},
+            RigidBody::default(),
+            LocalTransform::default(),
+            GlobalTransform(global_from_start),
+        ));
+
+        let distance_grab = DistanceGrab {
+            pull: Some(Pull {
+                entity: pulled_entity,
+                global_from_start,
+                elapsed: 0.,
+            }),
+            ..Default::default()
+        };
+        let hand_transform = GlobalTransform(Affine3A::from_translation([-0.2, 1.4, -0.5].into()));
+        let hand = world.spawn((Hand::left(), distance_grab, hand_transform));
+
+        (hand, pulled_entity)
+    }
+
+    fn tick(world: &mut World, input_context: &InputContext, physics_context: &PhysicsContext) {
+        distance_grab_system_inner(world, input_context, physics_context);
+    }
+}
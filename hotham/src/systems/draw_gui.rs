@@ -286,10 +286,11 @@ mod tests {
         let swapchain = SwapchainInfo {
             images: vec![image.handle],
             resolution,
+            format: COLOR_FORMAT,
         };
 
         let mut render_context =
-            RenderContext::new_from_swapchain_info(&vulkan_context, &swapchain).unwrap();
+            RenderContext::new_from_swapchain_info(&vulkan_context, &swapchain, true).unwrap();
         let gui_context = GuiContext::new(&vulkan_context);
 
         let gltf_data: Vec<&[u8]> = vec![include_bytes!(
@@ -0,0 +1,23 @@
+use crate::{
+    contexts::{physics_context::DELTA_TIME, EffectsContext, RenderContext},
+    rendering::vignette::VignetteData,
+    Engine,
+};
+
+/// Effects system
+/// Walks through any active effects, eg. shakes, fades and the comfort vignette, and applies them to the view for this
+/// frame. Should be run after anything that drives the vignette, like `character_controller_system`.
+pub fn effects_system(engine: &mut Engine) {
+    effects_system_inner(&mut engine.effects_context, &mut engine.render_context)
+}
+
+pub fn effects_system_inner(
+    effects_context: &mut EffectsContext,
+    render_context: &mut RenderContext,
+) {
+    render_context.content_offset = effects_context.update_shake(DELTA_TIME);
+    effects_context.update_fade(DELTA_TIME);
+    render_context.fade_color = effects_context.fade_color();
+    let vignette = effects_context.update_vignette(DELTA_TIME);
+    render_context.vignette = VignetteData::new(&effects_context.vignette_settings, vignette);
+}
@@ -0,0 +1,109 @@
+use glam::Vec4;
+use hecs::World;
+
+use crate::{
+    components::{AnimatedProperty, Expressions},
+    contexts::RenderContext,
+    systems::animation::apply_property,
+    Engine,
+};
+
+/// Expressions system
+/// Blends the material colors changed by each avatar's [`Expressions`] by how much each expression is showing.
+pub fn expressions_system(engine: &mut Engine) {
+    expressions_system_inner(&mut engine.world, &mut engine.render_context);
+}
+
+pub fn expressions_system_inner(world: &mut World, render_context: &mut RenderContext) {
+    for (property, value) in blend_material_colors(world) {
+        apply_property(render_context, property, value);
+    }
+}
+
+/// Several expressions can change the same color, so add up how far each one moves it from its base color.
+fn blend_material_colors(world: &mut World) -> Vec<(AnimatedProperty, Vec4)> {
+    let mut colors: Vec<(AnimatedProperty, Vec4)> = Vec::new();
+
+    for (_, expressions) in world.query_mut::<&Expressions>() {
+        for expression in &expressions.expressions {
+            let weight = expression.effective_weight();
+            for bind in &expression.material_color_binds {
+                let offset = (bind.target - bind.base) * weight;
+                match colors
+                    .iter_mut()
+                    .find(|(property, _)| *property == bind.property)
+                {
+                    Some((_, color)) => *color += offset,
+                    None => colors.push((bind.property, bind.base + offset)),
+                }
+            }
+        }
+    }
+
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::expressions::{Expression, MaterialColorBind};
+
+    #[test]
+    pub fn test_blend_material_colors() {
+        let mut world = World::new();
+        let blush = MaterialColorBind {
+            property: AnimatedProperty::BaseColorFactor(1),
+            base: Vec4::ONE,
+            target: Vec4::new(1., 0., 0., 1.),
+        };
+        let glow = MaterialColorBind {
+            property: AnimatedProperty::EmissiveFactor(1),
+            base: Vec4::ZERO,
+            target: Vec4::new(0., 0., 1., 1.),
+        };
+        let mut expressions = Expressions {
+            expressions: vec![
+                Expression {
+                    name: "blush".to_string(),
+                    weight: 0.5,
+                    material_color_binds: vec![blush],
+                    ..Default::default()
+                },
+                Expression {
+                    name: "angry".to_string(),
+                    weight: 0.5,
+                    material_color_binds: vec![blush, glow],
+                    ..Default::default()
+                },
+                Expression {
+                    name: "surprised".to_string(),
+                    weight: 0.4,
+                    is_binary: true,
+                    material_color_binds: vec![glow],
+                    ..Default::default()
+                },
+            ],
+        };
+        expressions.set_weight("nonexistent", 1.);
+        let entity = world.spawn((expressions,));
+
+        // Two half strength blushes make one full blush, and the binary expression is still off.
+        assert_eq!(
+            blend_material_colors(&mut world),
+            [
+                (blush.property, Vec4::new(1., 0., 0., 1.)),
+                (glow.property, Vec4::new(0., 0., 0.5, 0.5)),
+            ]
+        );
+
+        // Push the binary expression past half way and it snaps on.
+        world
+            .get::<&mut Expressions>(entity)
+            .unwrap()
+            .set_weight("surprised", 0.6);
+        assert_eq!(
+            blend_material_colors(&mut world)[1],
+            (glow.property, Vec4::new(0., 0., 1.5, 1.5))
+        );
+    }
+}
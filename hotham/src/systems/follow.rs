@@ -0,0 +1,216 @@
+use glam::{Affine3A, Quat, Vec3};
+use hecs::World;
+
+use crate::{
+    components::{
+        hand::Handedness, stage::get_global_from_stage, FollowTarget, Follower, GlobalTransform,
+        LocalTransform,
+    },
+    Engine,
+};
+
+/// How close to where it should be a following entity has to get before it stops following, as a fraction of
+/// `Follower::dead_zone`
+const SETTLE_FRACTION: f32 = 0.1;
+
+/// Follow system
+/// Walks through each [`Follower`] in the World and moves it after its target: the player's head, one of their hands
+/// or another entity.
+/// Should be run after `update_global_transform_with_parent_system`, so the targets' transforms are up to date.
+pub fn follow_system(engine: &mut Engine) {
+    let global_from_hmd = match engine.world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    let global_from_stage = get_global_from_stage(&engine.world);
+    let global_from_grips = [
+        global_from_stage * engine.input_context.left.stage_from_grip(),
+        global_from_stage * engine.input_context.right.stage_from_grip(),
+    ];
+    let delta_time = engine.time_context.delta_time();
+    follow_system_inner(
+        &mut engine.world,
+        &global_from_hmd,
+        &global_from_grips,
+        delta_time,
+    );
+}
+
+pub fn follow_system_inner(
+    world: &mut World,
+    global_from_hmd: &Affine3A,
+    global_from_grips: &[Affine3A; 2],
+    delta_time: f32,
+) {
+    // Entities being followed can't be looked up while the followers are being moved, so find them first.
+    let global_from_targets = world
+        .query::<&Follower>()
+        .iter()
+        .map(|(_, follower)| match follower.target {
+            FollowTarget::Head => Some(*global_from_hmd),
+            FollowTarget::Hand(Handedness::Left) => Some(global_from_grips[0]),
+            FollowTarget::Hand(Handedness::Right) => Some(global_from_grips[1]),
+            FollowTarget::Entity(target) => world.get::<&GlobalTransform>(target).ok().map(|t| t.0),
+        })
+        .collect::<Vec<_>>();
+
+    let viewer: Vec3 = global_from_hmd.translation.into();
+    for ((_, (follower, local_transform, global_transform)), global_from_target) in world
+        .query_mut::<(&mut Follower, &mut LocalTransform, &mut GlobalTransform)>()
+        .into_iter()
+        .zip(global_from_targets)
+    {
+        // Followers whose target has gone stay where they are.
+        let global_from_target = match global_from_target {
+            Some(global_from_target) => anchor(&global_from_target, follower.level),
+            None => continue,
+        };
+
+        // A follower that's just been added starts where it should be.
+        let target = global_from_target.transform_point3(follower.offset);
+        if !follower.placed {
+            follower.placed = true;
+            local_transform.translation = target;
+        }
+
+        let origin: Vec3 = global_from_target.translation.into();
+        local_transform.translation = follow(
+            follower,
+            origin,
+            local_transform.translation,
+            target,
+            delta_time,
+        );
+        local_transform.rotation = if follower.face_player {
+            face_viewer(viewer, local_transform.translation)
+        } else {
+            global_from_target.to_scale_rotation_translation().1
+        };
+        *global_transform = (*local_transform).into();
+    }
+}
+
+/// The target's transform without any scale, and without its pitch and roll if the follower is to stay `level`.
+fn anchor(global_from_target: &Affine3A, level: bool) -> Affine3A {
+    let (_, rotation, translation) = global_from_target.to_scale_rotation_translation();
+    if !level {
+        return Affine3A::from_rotation_translation(rotation, translation);
+    }
+
+    let forward = rotation * Vec3::NEG_Z;
+    let yaw = if forward.x == 0. && forward.z == 0. {
+        0.
+    } else {
+        (-forward.x).atan2(-forward.z)
+    };
+    Affine3A::from_rotation_translation(Quat::from_rotation_y(yaw), translation)
+}
+
+/// Move `current` towards `target` once it's further than the dead zone from it, as seen from `origin`, until it's
+/// caught up.
+fn follow(
+    follower: &mut Follower,
+    origin: Vec3,
+    current: Vec3,
+    target: Vec3,
+    delta_time: f32,
+) -> Vec3 {
+    let angle = (current - origin).angle_between(target - origin);
+    if !angle.is_finite() {
+        return target;
+    }
+
+    if angle > follower.dead_zone {
+        follower.following = true;
+    } else if angle <= follower.dead_zone * SETTLE_FRACTION {
+        follower.following = false;
+    }
+    if !follower.following && follower.dead_zone > 0. {
+        return current;
+    }
+
+    let t = 1. - (-follower.speed * delta_time).exp();
+    current.lerp(target, t)
+}
+
+/// Turn the follower about the vertical axis so its front faces the viewer.
+fn face_viewer(viewer: Vec3, follower: Vec3) -> Quat {
+    let to_viewer = viewer - follower;
+    Quat::from_rotation_y(to_viewer.x.atan2(to_viewer.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f32::consts::FRAC_PI_2;
+
+    use crate::contexts::physics_context::DELTA_TIME;
+
+    #[test]
+    pub fn test_follow_lazily() {
+        let mut world = World::new();
+        let offset = Vec3::new(0., -0.3, -1.);
+        let follower = world.spawn((
+            Follower::lazy(FollowTarget::Head, offset),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+        ));
+        let grips = [Affine3A::IDENTITY; 2];
+        let head = Vec3::Y * 1.7;
+        let translation =
+            |world: &World| world.get::<&LocalTransform>(follower).unwrap().translation;
+
+        // The follower starts in front of the player..
+        let looking_ahead = Affine3A::from_translation(head);
+        follow_system_inner(&mut world, &looking_ahead, &grips, DELTA_TIME);
+        assert_relative_eq!(translation(&world), head + offset);
+
+        // ..and stays put when they glance a little to the side..
+        let glancing = Affine3A::from_rotation_translation(Quat::from_rotation_y(0.2), head);
+        for _ in 0..72 {
+            follow_system_inner(&mut world, &glancing, &grips, DELTA_TIME);
+        }
+        assert_relative_eq!(translation(&world), head + offset);
+
+        // ..but catches up once they turn away, facing them.
+        let turned = Affine3A::from_rotation_translation(Quat::from_rotation_y(FRAC_PI_2), head);
+        follow_system_inner(&mut world, &turned, &grips, DELTA_TIME);
+        assert!(translation(&world).x > -0.5 && translation(&world).z < -0.5);
+        for _ in 0..72 * 3 {
+            follow_system_inner(&mut world, &turned, &grips, DELTA_TIME);
+        }
+        let expected = Vec3::new(-1., 1.4, 0.);
+        assert_relative_eq!(translation(&world), expected, epsilon = 0.05);
+        let rotation = world.get::<&LocalTransform>(follower).unwrap().rotation;
+        assert_relative_eq!(rotation * Vec3::Z, Vec3::X, epsilon = 0.05);
+    }
+
+    #[test]
+    pub fn test_follow_locked_to_hand() {
+        let mut world = World::new();
+        let offset = Vec3::new(0., 0.1, 0.);
+        let follower = world.spawn((
+            Follower::locked(FollowTarget::Hand(Handedness::Right), offset),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+        ));
+        let global_from_hmd = Affine3A::from_translation(Vec3::Y * 1.7);
+
+        // A locked follower keeps up with its target every frame.
+        for x in [0.2, 0.4] {
+            let right_grip = Affine3A::from_rotation_translation(
+                Quat::from_rotation_z(FRAC_PI_2),
+                [x, 1., -0.3].into(),
+            );
+            let grips = [Affine3A::IDENTITY, right_grip];
+            follow_system_inner(&mut world, &global_from_hmd, &grips, DELTA_TIME);
+            let global_transform = world.get::<&GlobalTransform>(follower).unwrap().0;
+            assert_relative_eq!(
+                Vec3::from(global_transform.translation),
+                Vec3::new(x - 0.1, 1., -0.3),
+                epsilon = 0.0001
+            );
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use hecs::World;
 
 use crate::{
-    components::{physics::BodyType, Collider, Grabbable, Hand, RigidBody},
+    components::{physics::BodyType, Collider, Grabbable, Hand, PoseFilter, RigidBody},
     Engine,
 };
 
@@ -29,6 +29,11 @@ fn grabbing_system_inner(world: &mut World) {
                         rigid_body.body_type = BodyType::KinematicPositionBased;
                     }
 
+                    // If what we're grabbing smooths its pose, start again from where it is now.
+                    if let Ok(mut pose_filter) = world.get::<&mut PoseFilter>(*other_entity) {
+                        pose_filter.reset();
+                    }
+
                     // Store a reference to the grabbed entity
                     hand.grabbed_entity.replace(*other_entity);
 
@@ -73,9 +78,8 @@ mod tests {
 
         // Fully gripped hand
         let hand = Hand {
-            handedness: Handedness::Left,
             grip_value: 1.0,
-            grabbed_entity: None,
+            ..Hand::new(Handedness::Left)
         };
 
         // Collider
@@ -0,0 +1,137 @@
+use glam::{Affine3A, Quat, Vec3};
+use hecs::World;
+
+use crate::{
+    components::{
+        stage, AnimationController, GhostHand, GlobalTransform, GuidanceArrow, LocalTransform,
+        Visible,
+    },
+    contexts::physics_context::DELTA_TIME,
+    Engine,
+};
+
+/// Guidance system
+/// Walks through each `GuidanceArrow` in the World, points it at its target and hides it once the target is in view,
+/// then moves each `GhostHand` on through its motion.
+/// Should be run after `update_global_transform_with_parent_system`, so the HMD's and targets' transforms are up to
+/// date.
+pub fn guidance_system(engine: &mut Engine) {
+    let global_from_hmd = match engine.world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    guidance_system_inner(&mut engine.world, &global_from_hmd, DELTA_TIME);
+}
+
+pub fn guidance_system_inner(world: &mut World, global_from_hmd: &Affine3A, delta_time: f32) {
+    update_arrows(world, global_from_hmd);
+    update_ghost_hands(world, delta_time);
+}
+
+fn update_arrows(world: &mut World, global_from_hmd: &Affine3A) {
+    // Where each arrow's target is. We can't look these up while iterating through the query.
+    let targets: Vec<_> = world
+        .query::<&GuidanceArrow>()
+        .iter()
+        .map(|(entity, arrow)| {
+            let target = arrow
+                .target
+                .and_then(|target| world.get::<&GlobalTransform>(target).ok())
+                .map(|transform| Vec3::from(transform.0.translation));
+            (entity, target)
+        })
+        .collect();
+
+    let viewer: Vec3 = global_from_hmd.translation.into();
+    let forward = global_from_hmd.transform_vector3(Vec3::NEG_Z);
+
+    for (entity, target) in targets {
+        let arrow = *world.get::<&GuidanceArrow>(entity).unwrap();
+        let in_view =
+            target.map(|target| (target - viewer).angle_between(forward) < arrow.hide_angle);
+        let target = match (target, in_view) {
+            (Some(target), Some(false)) => target,
+            _ => {
+                let _ = world.remove_one::<Visible>(entity);
+                continue;
+            }
+        };
+
+        let translation =
+            global_from_hmd.transform_point3(Vec3::new(0., -arrow.drop, -arrow.distance));
+        let rotation = (target - translation)
+            .try_normalize()
+            .map_or(Quat::IDENTITY, |direction| {
+                Quat::from_rotation_arc(Vec3::NEG_Z, direction)
+            });
+
+        let (local_transform, global_transform) = world
+            .query_one_mut::<(&mut LocalTransform, &mut GlobalTransform)>(entity)
+            .unwrap();
+        local_transform.translation = translation;
+        local_transform.rotation = rotation;
+        *global_transform = (*local_transform).into();
+        if world.get::<&Visible>(entity).is_err() {
+            world.insert_one(entity, Visible {}).unwrap();
+        }
+    }
+}
+
+fn update_ghost_hands(world: &mut World, delta_time: f32) {
+    let global_from_stage = stage::get_global_from_stage(world);
+
+    for (_, (ghost_hand, animation_controller, local_transform, global_transform)) in world
+        .query_mut::<(
+            &mut GhostHand,
+            Option<&mut AnimationController>,
+            &mut LocalTransform,
+            &mut GlobalTransform,
+        )>()
+    {
+        let pose = match ghost_hand.advance(delta_time) {
+            Some(pose) => pose,
+            None => continue,
+        };
+
+        let global_from_grip =
+            global_from_stage * ghost_hand.stage_from_motion * pose.stage_from_grip;
+        local_transform.update_from_affine(&global_from_grip);
+        global_transform.0 = global_from_grip;
+        if let Some(animation_controller) = animation_controller {
+            animation_controller.blend_amount = pose.finger_curl;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_guidance_arrow() {
+        let mut world = World::new();
+        let target = world.spawn((GlobalTransform(Affine3A::from_translation(
+            [5., 1.6, 0.].into(),
+        )),));
+        let arrow = world.spawn((
+            GuidanceArrow::new(target),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+        ));
+
+        // The target is off to the right, so the arrow shows and points at it.
+        let global_from_hmd = Affine3A::from_translation([0., 1.6, 0.].into());
+        guidance_system_inner(&mut world, &global_from_hmd, 0.1);
+        assert!(world.get::<&Visible>(arrow).is_ok());
+        let local_transform = *world.get::<&LocalTransform>(arrow).unwrap();
+        assert_relative_eq!(local_transform.translation, Vec3::new(0., 1.45, -0.6));
+        let direction = local_transform.rotation * Vec3::NEG_Z;
+        assert!(direction.x > 0.9);
+
+        // Once the player turns to face it, the arrow is hidden.
+        let global_from_hmd = global_from_hmd * Affine3A::from_rotation_y(-90_f32.to_radians());
+        guidance_system_inner(&mut world, &global_from_hmd, 0.1);
+        assert!(world.get::<&Visible>(arrow).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use glam::{Affine3A, Vec2, Vec3};
+use hecs::{Entity, World};
+
+use crate::{
+    components::{
+        hand::Handedness, stage::get_global_from_stage, GlobalTransform, HandMenu,
+        HandMenuActivation, HandMenuEvent, LocalTransform, Visible,
+    },
+    contexts::HapticContext,
+    Engine,
+};
+
+/// How far the middle of the menu is from the wrist, towards the player's head, in metres
+const MENU_DISTANCE: f32 = 0.12;
+
+/// The cosine of how far the palm can be turned from the player's face for the menu to open..
+const PALM_OPEN_COS: f32 = 0.7;
+
+/// ..and how far it has to be turned away again for it to close
+const PALM_CLOSE_COS: f32 = 0.4;
+
+/// How far the thumbstick has to be pushed to flick towards an item
+const FLICK_THRESHOLD: f32 = 0.7;
+
+/// How close to the middle the thumbstick has to come back to pick the item it was flicked towards
+const RELEASE_THRESHOLD: f32 = 0.3;
+
+/// How close to the middle of the menu, and how far outside its ring of icons, it can be pointed at, as fractions of
+/// `HandMenu::radius`
+const POINTING_RANGE: (f32, f32) = (0.4, 1.6);
+
+/// How strongly the controller buzzes when an item is pointed at..
+const HOVER_HAPTICS: f32 = 0.1;
+
+/// ..and when one is picked
+const SELECT_HAPTICS: f32 = 0.5;
+
+/// The controller state `hand_menu_system` needs, in global space. Each array has the left hand first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HandMenuInput {
+    /// Where each controller's grip is
+    pub global_from_grips: [Affine3A; 2],
+    /// Where each controller is pointing
+    pub global_from_aims: [Affine3A; 2],
+    /// Where each thumbstick is
+    pub thumbsticks: [Vec2; 2],
+    /// Was each trigger pressed this frame?
+    pub triggers_pressed: [bool; 2],
+    /// Was the menu button pressed this frame?
+    pub menu_button_pressed: bool,
+}
+
+/// Hand menu system
+/// Opens and closes each [`HandMenu`], works out which of its items is being pointed or flicked at, records what
+/// happened in its `events_this_frame` and lays out its icons around the wrist.
+/// Should be run after `update_global_transform_with_parent_system`, so the HMD's transform is up to date.
+pub fn hand_menu_system(engine: &mut Engine) {
+    let global_from_hmd = match engine.world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    let global_from_stage = get_global_from_stage(&engine.world);
+    let (left, right) = (&engine.input_context.left, &engine.input_context.right);
+    let input = HandMenuInput {
+        global_from_grips: [
+            global_from_stage * left.stage_from_grip(),
+            global_from_stage * right.stage_from_grip(),
+        ],
+        global_from_aims: [
+            global_from_stage * left.stage_from_aim(),
+            global_from_stage * right.stage_from_aim(),
+        ],
+        thumbsticks: [left.thumbstick_xy(), right.thumbstick_xy()],
+        triggers_pressed: [
+            left.trigger_button_just_pressed(),
+            right.trigger_button_just_pressed(),
+        ],
+        menu_button_pressed: left.menu_button_just_pressed(),
+    };
+    hand_menu_system_inner(
+        &mut engine.world,
+        &input,
+        &global_from_hmd,
+        &mut engine.haptic_context,
+    );
+}
+
+pub fn hand_menu_system_inner(
+    world: &mut World,
+    input: &HandMenuInput,
+    global_from_hmd: &Affine3A,
+    haptic_context: &mut HapticContext,
+) {
+    let head: Vec3 = global_from_hmd.translation.into();
+
+    // Icons can't be added or moved while the menus are being updated, so work out where each menu is first.
+    let mut menus = Vec::new();
+    for (entity, menu) in world.query_mut::<&mut HandMenu>() {
+        menu.events_this_frame.clear();
+        let (hand, other_hand, other_handedness) = match menu.handedness {
+            Handedness::Left => (0, 1, Handedness::Right),
+            Handedness::Right => (1, 0, Handedness::Left),
+        };
+        let global_from_grip = input.global_from_grips[hand];
+        let wrist: Vec3 = global_from_grip.translation.into();
+        let to_head = (head - wrist).normalize_or_zero();
+
+        let open = match menu.activation {
+            HandMenuActivation::LookAtPalm => {
+                let facing = palm_normal(menu.handedness, &global_from_grip).dot(to_head);
+                facing
+                    > if menu.open {
+                        PALM_CLOSE_COS
+                    } else {
+                        PALM_OPEN_COS
+                    }
+            }
+            HandMenuActivation::MenuButton => menu.open != input.menu_button_pressed,
+        };
+        if open != menu.open {
+            menu.open = open;
+            menu.events_this_frame.push(if open {
+                HandMenuEvent::Opened
+            } else {
+                HandMenuEvent::Closed
+            });
+        }
+        if !menu.open || menu.items.is_empty() {
+            menu.hovered = None;
+            menu.flicked = None;
+            menus.push((entity, None));
+            continue;
+        }
+
+        // The menu floats over the wrist, facing the player.
+        let mut menu_transform = LocalTransform {
+            translation: wrist + to_head * MENU_DISTANCE,
+            ..Default::default()
+        };
+        menu_transform.look_at(menu_transform.translation * 2. - head, Vec3::Y);
+        let global_from_menu = menu_transform.to_affine();
+
+        // Flicking the thumbstick towards an item picks it once the thumbstick's let go of..
+        let item_count = menu.items.len();
+        let thumbstick = input.thumbsticks[hand];
+        if thumbstick.length() > FLICK_THRESHOLD {
+            menu.flicked = Some(item_at(thumbstick, item_count));
+        } else if thumbstick.length() < RELEASE_THRESHOLD {
+            if let Some(index) = menu.flicked.take() {
+                menu.events_this_frame.push(HandMenuEvent::Selected(index));
+                haptic_context.request_haptic_feedback(SELECT_HAPTICS, menu.handedness);
+            }
+        }
+
+        // ..and pointing at one with the other hand picks it when the trigger's pulled.
+        let pointed_at = pointed_at(
+            &global_from_menu,
+            &input.global_from_aims[other_hand],
+            menu.radius,
+            item_count,
+        );
+        if let (Some(index), true) = (pointed_at, input.triggers_pressed[other_hand]) {
+            menu.events_this_frame.push(HandMenuEvent::Selected(index));
+            haptic_context.request_haptic_feedback(SELECT_HAPTICS, other_handedness);
+        }
+
+        let hovered = menu.flicked.or(pointed_at);
+        if hovered.is_some() && hovered != menu.hovered {
+            let handedness = if menu.flicked.is_some() {
+                menu.handedness
+            } else {
+                other_handedness
+            };
+            haptic_context.request_haptic_feedback(HOVER_HAPTICS, handedness);
+        }
+        menu.hovered = hovered;
+        menus.push((entity, Some(global_from_menu)));
+    }
+
+    for (entity, global_from_menu) in menus {
+        update_icons(world, entity, global_from_menu);
+    }
+}
+
+/// Make sure the menu has an icon for each item, then lay them out around the menu, or hide them if it's closed.
+fn update_icons(world: &mut World, entity: Entity, global_from_menu: Option<Affine3A>) {
+    let menu = world.get::<&HandMenu>(entity).unwrap().clone();
+    let mut icons = menu.icons;
+    icons.retain(|icon| world.contains(*icon));
+    while icons.len() < menu.items.len() {
+        icons.push(world.spawn((LocalTransform::default(), GlobalTransform::default())));
+    }
+    for icon in icons.drain(menu.items.len()..) {
+        let _ = world.despawn(icon);
+    }
+
+    for (index, (icon, item)) in icons.iter().zip(&menu.items).enumerate() {
+        let global_from_menu = match global_from_menu {
+            Some(global_from_menu) => global_from_menu,
+            None => {
+                let _ = world.remove_one::<Visible>(*icon);
+                continue;
+            }
+        };
+        let scale = if menu.hovered == Some(index) {
+            menu.hover_scale
+        } else {
+            1.
+        };
+        let global_from_icon = global_from_menu
+            * Affine3A::from_scale_rotation_translation(
+                Vec3::splat(scale),
+                Default::default(),
+                item_position(index, menu.items.len(), menu.radius).extend(0.),
+            );
+        world
+            .insert(
+                *icon,
+                (
+                    item.icon,
+                    LocalTransform::from(global_from_icon),
+                    GlobalTransform(global_from_icon),
+                    Visible {},
+                ),
+            )
+            .unwrap();
+    }
+
+    // The menu's own entity is moved too, if it has a transform, so the app can give it a background.
+    if let Ok((local_transform, global_transform)) =
+        world.query_one_mut::<(&mut LocalTransform, &mut GlobalTransform)>(entity)
+    {
+        if let Some(global_from_menu) = global_from_menu {
+            local_transform.update_from_affine(&global_from_menu);
+            global_transform.0 = global_from_menu;
+        }
+    }
+    world.get::<&mut HandMenu>(entity).unwrap().icons = icons;
+}
+
+/// The direction the palm faces. The grip's X axis points out of the left palm, and into the right one.
+fn palm_normal(handedness: Handedness, global_from_grip: &Affine3A) -> Vec3 {
+    let x = global_from_grip
+        .transform_vector3(Vec3::X)
+        .normalize_or_zero();
+    match handedness {
+        Handedness::Left => x,
+        Handedness::Right => -x,
+    }
+}
+
+/// Where the item at `index` of `count` is in the menu, clockwise from the top
+fn item_position(index: usize, count: usize, radius: f32) -> Vec2 {
+    let angle = FRAC_PI_2 - index as f32 * TAU / count as f32;
+    Vec2::new(angle.cos(), angle.sin()) * radius
+}
+
+/// Which of `count` items is closest to `direction` from the middle of the menu
+fn item_at(direction: Vec2, count: usize) -> usize {
+    let angle = direction.y.atan2(direction.x);
+    let index = ((FRAC_PI_2 - angle) / (TAU / count as f32)).round() as i32;
+    index.rem_euclid(count as i32) as usize
+}
+
+/// Which item, if any, the controller at `global_from_aim` is pointing at
+fn pointed_at(
+    global_from_menu: &Affine3A,
+    global_from_aim: &Affine3A,
+    radius: f32,
+    count: usize,
+) -> Option<usize> {
+    // Find where the ray hits the front of the menu..
+    let menu_from_global = global_from_menu.inverse();
+    let origin = menu_from_global.transform_point3(global_from_aim.translation.into());
+    let direction =
+        menu_from_global.transform_vector3(global_from_aim.transform_vector3(Vec3::NEG_Z));
+    if origin.z <= 0. || direction.z >= 0. {
+        return None;
+    }
+    let hit = (origin + direction * (-origin.z / direction.z)).truncate();
+
+    // ..and which item that's closest to, if it's near the ring of icons.
+    let (near, far) = POINTING_RANGE;
+    let distance = hit.length();
+    if distance < radius * near || distance > radius * far {
+        return None;
+    }
+    Some(item_at(hit, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Quat;
+
+    use crate::components::{HandMenuItem, Sprite};
+
+    #[test]
+    pub fn test_item_at() {
+        // Items go clockwise from the top.
+        assert_eq!(item_at(Vec2::Y, 4), 0);
+        assert_eq!(item_at(Vec2::X, 4), 1);
+        assert_eq!(item_at(Vec2::NEG_Y, 4), 2);
+        assert_eq!(item_at(Vec2::NEG_X, 4), 3);
+        assert_eq!(item_at(Vec2::new(-0.1, 1.), 4), 0);
+        for index in 0..6 {
+            assert_eq!(item_at(item_position(index, 6, 0.1), 6), index);
+        }
+    }
+
+    #[test]
+    pub fn test_hand_menu() {
+        let mut world = World::new();
+        let items = ["teleport", "settings", "inventory", "quit"]
+            .map(|name| HandMenuItem::new(name, Sprite::default()))
+            .to_vec();
+        let menu = world.spawn((HandMenu::new(Handedness::Left, items),));
+        let mut haptic_context = HapticContext::default();
+        let head = Vec3::new(0., 1.7, 0.);
+        let global_from_hmd = Affine3A::from_translation(head);
+
+        // Turning the left palm towards the player's face opens the menu..
+        let wrist = Vec3::new(0., 1.3, -0.3);
+        let to_head = (head - wrist).normalize();
+        let palm_up =
+            Affine3A::from_rotation_translation(Quat::from_rotation_arc(Vec3::X, to_head), wrist);
+        let mut input = HandMenuInput {
+            global_from_grips: [palm_up, Affine3A::IDENTITY],
+            global_from_aims: [Affine3A::IDENTITY; 2],
+            thumbsticks: [Vec2::ZERO; 2],
+            triggers_pressed: [false; 2],
+            menu_button_pressed: false,
+        };
+        let mut tick = |world: &mut World, input: &HandMenuInput| {
+            hand_menu_system_inner(world, input, &global_from_hmd, &mut haptic_context);
+            world.get::<&HandMenu>(menu).unwrap().clone()
+        };
+        let hand_menu = tick(&mut world, &input);
+        assert_eq!(hand_menu.events_this_frame, [HandMenuEvent::Opened]);
+        assert_eq!(hand_menu.icons.len(), 4);
+        for icon in &hand_menu.icons {
+            assert!(world.get::<&Visible>(*icon).is_ok());
+            assert!(world.get::<&Sprite>(*icon).is_ok());
+        }
+
+        // ..flicking the thumbstick right picks the item on the right when it's let go of..
+        input.thumbsticks[0] = Vec2::X;
+        let hand_menu = tick(&mut world, &input);
+        assert_eq!(hand_menu.hovered, Some(1));
+        assert!(hand_menu.events_this_frame.is_empty());
+        input.thumbsticks[0] = Vec2::ZERO;
+        let hand_menu = tick(&mut world, &input);
+        assert_eq!(hand_menu.events_this_frame, [HandMenuEvent::Selected(1)]);
+
+        // ..pointing at the item on the left with the other hand and pulling the trigger picks that..
+        let centre = wrist + to_head * MENU_DISTANCE;
+        let left_item = centre - Vec3::X * hand_menu.radius;
+        input.global_from_aims[1] = Affine3A::from_rotation_translation(
+            Quat::from_rotation_arc(Vec3::NEG_Z, -to_head),
+            left_item + to_head * 0.3,
+        );
+        let hand_menu = tick(&mut world, &input);
+        assert_eq!(hand_menu.hovered, Some(3));
+        assert!(hand_menu.events_this_frame.is_empty());
+        input.triggers_pressed[1] = true;
+        let hand_menu = tick(&mut world, &input);
+        assert_eq!(hand_menu.events_this_frame, [HandMenuEvent::Selected(3)]);
+        let icon_scale = world
+            .get::<&LocalTransform>(hand_menu.icons[3])
+            .unwrap()
+            .scale;
+        assert!(icon_scale.x > 1.);
+
+        // ..and turning it away again closes it.
+        input.triggers_pressed[1] = false;
+        input.global_from_grips[0] = Affine3A::from_translation(wrist);
+        let hand_menu = tick(&mut world, &input);
+        assert_eq!(hand_menu.events_this_frame, [HandMenuEvent::Closed]);
+        assert_eq!(hand_menu.hovered, None);
+        for icon in &hand_menu.icons {
+            assert!(world.get::<&Visible>(*icon).is_err());
+        }
+    }
+}
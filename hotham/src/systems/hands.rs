@@ -1,13 +1,17 @@
 use crate::{
     asset_importer::add_model_to_world,
     components::{
-        global_transform::GlobalTransform, hand::Handedness, local_transform::LocalTransform,
-        stage, AnimationController, Collider, Hand,
+        global_transform::GlobalTransform,
+        hand::Handedness,
+        local_transform::LocalTransform,
+        pose_filter::{predict_pose, PoseFilter},
+        stage, AnimationController, Collider, Hand, Parent, Visible,
     },
-    contexts::{physics_context::HAND_COLLISION_GROUP, InputContext},
+    contexts::{physics_context::HAND_COLLISION_GROUP, HandTrackingContext, InputContext},
     Engine,
 };
-use hecs::World;
+use glam::Vec3;
+use hecs::{Entity, World};
 use rapier3d::prelude::{ActiveCollisionTypes, SharedShape};
 
 /// Hands system
@@ -15,32 +19,94 @@ use rapier3d::prelude::{ActiveCollisionTypes, SharedShape};
 pub fn hands_system(engine: &mut Engine) {
     let world = &mut engine.world;
     let input_context = &mut engine.input_context;
-    hands_system_inner(world, input_context);
+    let hand_tracking_context = &engine.hand_tracking_context;
+    let delta_time = engine.time_context.delta_time();
+    hands_system_inner(world, input_context, hand_tracking_context, delta_time);
 }
 
-pub fn hands_system_inner(world: &mut World, input_context: &InputContext) {
+#[allow(clippy::type_complexity)]
+pub fn hands_system_inner(
+    world: &mut World,
+    input_context: &InputContext,
+    hand_tracking_context: &HandTrackingContext,
+    delta_time: f32,
+) {
     // Get the position
     let global_from_stage = stage::get_global_from_stage(world);
 
-    for (_, (hand, animation_controller, local_transform, global_transform)) in world
+    // Hands that need to be hidden or shown again. We can't do this while iterating through the query.
+    let mut visibility_changes = Vec::new();
+
+    for (
+        hand_entity,
+        (hand, animation_controller, local_transform, global_transform, pose_filter),
+    ) in world
         .query::<(
             &mut Hand,
             &mut AnimationController,
             &mut LocalTransform,
             &mut GlobalTransform,
+            Option<&mut PoseFilter>,
         )>()
         .iter()
     {
-        // Get the position of the hand in stage space.
-        let (stage_from_grip, grip_value) = match hand.handedness {
-            Handedness::Left => (
-                input_context.left.stage_from_grip(),
-                input_context.left.grip_analog(),
-            ),
-            Handedness::Right => (
-                input_context.right.stage_from_grip(),
-                input_context.right.grip_analog(),
-            ),
+        // Get the position and velocity of the hand in stage space.
+        let (stage_from_grip, grip_value, trigger_value, linear_velocity, angular_velocity) =
+            match hand.handedness {
+                Handedness::Left => (
+                    input_context.left.stage_from_grip(),
+                    input_context.left.grip_analog(),
+                    input_context.left.trigger_analog(),
+                    input_context.left.linear_velocity(),
+                    input_context.left.angular_velocity(),
+                ),
+                Handedness::Right => (
+                    input_context.right.stage_from_grip(),
+                    input_context.right.grip_analog(),
+                    input_context.right.trigger_analog(),
+                    input_context.right.linear_velocity(),
+                    input_context.right.angular_velocity(),
+                ),
+            };
+
+        // Tracked hands take over from the controllers whenever the runtime can see them.
+        let tracked_hand = hand
+            .tracked
+            .then(|| hand_tracking_context.hand(hand.handedness))
+            .flatten();
+        let (stage_from_grip, grip_value, trigger_value, linear_velocity, angular_velocity) =
+            match tracked_hand {
+                Some(tracked_hand) => (
+                    tracked_hand.stage_from_palm(),
+                    tracked_hand.grip_strength(),
+                    tracked_hand.pinch_strength(),
+                    Vec3::ZERO,
+                    Vec3::ZERO,
+                ),
+                None => (
+                    stage_from_grip,
+                    grip_value,
+                    trigger_value,
+                    linear_velocity,
+                    angular_velocity,
+                ),
+            };
+
+        // Smooth the pose in stage space, so that moving the stage around isn't smoothed as well. Each filter is given
+        // the raw pose, so nothing is predicted or smoothed twice.
+        let raw_stage_from_grip = stage_from_grip;
+        let apply_filter = |pose_filter: &mut PoseFilter| {
+            let predicted = predict_pose(
+                &raw_stage_from_grip,
+                linear_velocity,
+                angular_velocity,
+                pose_filter.prediction_time,
+            );
+            pose_filter.filter(&predicted, delta_time)
+        };
+        let stage_from_grip = match pose_filter {
+            Some(pose_filter) => apply_filter(pose_filter),
+            None => raw_stage_from_grip,
         };
 
         // Get global transform
@@ -51,19 +117,78 @@ pub fn hands_system_inner(world: &mut World, input_context: &InputContext) {
         global_transform.0 = global_from_local;
 
         // If we've grabbed something, update its transform, being careful to preserve its scale.
+        // The grabbed entity may want its own smoothing instead of the hand's.
         if let Some(grabbed_entity) = hand.grabbed_entity {
+            let global_from_grabbed = match world.get::<&mut PoseFilter>(grabbed_entity) {
+                Ok(mut pose_filter) => global_from_stage * apply_filter(&mut pose_filter),
+                Err(_) => global_from_local,
+            };
+
             let mut local_transform = world.get::<&mut LocalTransform>(grabbed_entity).unwrap();
-            local_transform.update_rotation_translation_from_affine(&global_from_local);
+            local_transform.update_rotation_translation_from_affine(&global_from_grabbed);
 
             let mut global_transform = world.get::<&mut GlobalTransform>(grabbed_entity).unwrap();
             *global_transform = (*local_transform).into();
         }
 
-        // Apply grip value to hand
+        // Apply grip and trigger values to hand
         hand.grip_value = grip_value;
+        hand.trigger_value = trigger_value;
 
         // Apply to AnimationController
-        animation_controller.blend_amount = grip_value;
+        animation_controller.blend_amount = hand.finger_curl();
+
+        // Hide the hand if it's holding something and has asked to be hidden, or show it again if it's let go.
+        let should_hide = hand.hide_when_grabbing && hand.grabbed_entity.is_some();
+        if should_hide != hand.hidden {
+            visibility_changes.push((hand_entity, should_hide));
+        }
+    }
+
+    for (hand_entity, hide) in visibility_changes {
+        if hide {
+            hide_hand(world, hand_entity);
+        } else {
+            show_hand(world, hand_entity);
+        }
+    }
+}
+
+/// Make the hand and all of its descendants invisible, remembering which ones were visible.
+fn hide_hand(world: &mut World, hand_entity: Entity) {
+    let mut entities = vec![hand_entity];
+    let mut index = 0;
+    while index < entities.len() {
+        let parent = entities[index];
+        entities.extend(
+            world
+                .query::<&Parent>()
+                .iter()
+                .filter(|(_, p)| p.0 == parent)
+                .map(|(child, _)| child),
+        );
+        index += 1;
+    }
+
+    let hidden_entities = entities
+        .into_iter()
+        .filter(|e| world.remove_one::<Visible>(*e).is_ok())
+        .collect();
+    let mut hand = world.get::<&mut Hand>(hand_entity).unwrap();
+    hand.hidden = true;
+    hand.hidden_entities = hidden_entities;
+}
+
+/// Show anything that was hidden by [`hide_hand`].
+fn show_hand(world: &mut World, hand_entity: Entity) {
+    let hidden_entities = {
+        let mut hand = world.get::<&mut Hand>(hand_entity).unwrap();
+        hand.hidden = false;
+        std::mem::take(&mut hand.hidden_entities)
+    };
+    for entity in hidden_entities {
+        // The entity may have been despawned in the meantime.
+        let _ = world.insert_one(entity, Visible {});
     }
 }
 
@@ -106,10 +231,12 @@ pub fn add_hand(
 mod tests {
     use super::*;
     use approx::assert_relative_eq;
-    use glam::Vec3;
-    use hecs::Entity;
+    use glam::{Affine3A, Vec3};
 
-    use crate::components::{LocalTransform, RigidBody};
+    use crate::{
+        components::{LocalTransform, RigidBody},
+        contexts::physics_context::DELTA_TIME,
+    };
 
     #[test]
     pub fn test_hands_system() {
@@ -151,6 +278,105 @@ mod tests {
         assert_relative_eq!(local_transform.scale, expected_scale);
     }
 
+    #[test]
+    pub fn test_hide_when_grabbing() {
+        let (mut world, input_context) = setup();
+        let grabbed_entity = world.spawn((LocalTransform::default(), GlobalTransform::default()));
+        let hand = add_hand_to_world(&mut world, Some(grabbed_entity));
+        world.insert_one(hand, Visible {}).unwrap();
+        let child = world.spawn((Parent(hand), Visible {}));
+        world.get::<&mut Hand>(hand).unwrap().hide_when_grabbing = true;
+
+        // The hand and its children should be hidden while it's holding something..
+        tick(&mut world, &input_context);
+        assert!(world.get::<&Visible>(hand).is_err());
+        assert!(world.get::<&Visible>(child).is_err());
+
+        // ..and shown again when it lets go.
+        world.get::<&mut Hand>(hand).unwrap().grabbed_entity = None;
+        tick(&mut world, &input_context);
+        assert!(world.get::<&Visible>(hand).is_ok());
+        assert!(world.get::<&Visible>(child).is_ok());
+    }
+
+    #[test]
+    pub fn test_hide_invisible_hand() {
+        let (mut world, input_context) = setup();
+        let grabbed_entity = world.spawn((LocalTransform::default(), GlobalTransform::default()));
+        let hand = add_hand_to_world(&mut world, Some(grabbed_entity));
+        world.get::<&mut Hand>(hand).unwrap().hide_when_grabbing = true;
+
+        // A hand with nothing visible is still hidden, so it isn't walked through again on the next frame..
+        tick(&mut world, &input_context);
+        {
+            let hand = world.get::<&Hand>(hand).unwrap();
+            assert!(hand.hidden);
+            assert!(hand.hidden_entities.is_empty());
+        }
+
+        // ..even if something visible is added to it in the meantime.
+        let child = world.spawn((Parent(hand), Visible {}));
+        tick(&mut world, &input_context);
+        assert!(world.get::<&Hand>(hand).unwrap().hidden);
+        assert!(world.get::<&Visible>(child).is_ok());
+
+        // Letting go shows the hand again.
+        world.get::<&mut Hand>(hand).unwrap().grabbed_entity = None;
+        tick(&mut world, &input_context);
+        assert!(!world.get::<&Hand>(hand).unwrap().hidden);
+    }
+
+    #[test]
+    pub fn test_pose_filters() {
+        let (mut world, input_context) = setup();
+        let raw_stage_from_grip = input_context.left.stage_from_grip();
+
+        // Give the hand and the object it's holding filters that have both already seen a pose at the origin, with
+        // the object's filter much heavier than the hand's.
+        let mut hand_filter = PoseFilter::new(1.0, 0.0);
+        let mut grabbed_filter = PoseFilter::new(0.1, 0.0);
+        hand_filter.filter(&Affine3A::IDENTITY, DELTA_TIME);
+        grabbed_filter.filter(&Affine3A::IDENTITY, DELTA_TIME);
+
+        let grabbed_entity = world.spawn((
+            LocalTransform::default(),
+            GlobalTransform::default(),
+            grabbed_filter.clone(),
+        ));
+        let hand = add_hand_to_world(&mut world, Some(grabbed_entity));
+        world.insert_one(hand, hand_filter.clone()).unwrap();
+
+        // Use a different delta time to the one the filters were primed with, to make sure it's the one that's used.
+        let delta_time = DELTA_TIME * 2.;
+        hands_system_inner(&mut world, &input_context, &Default::default(), delta_time);
+
+        // Each should have been filtered once, from the raw pose, rather than the object being filtered again from
+        // the hand's filtered pose.
+        let expected_hand = hand_filter.filter(&raw_stage_from_grip, delta_time);
+        let expected_grabbed = grabbed_filter.filter(&raw_stage_from_grip, delta_time);
+        assert!(
+            expected_hand
+                .translation
+                .distance(expected_grabbed.translation)
+                > 0.01
+        );
+
+        let hand_transform = world.get::<&LocalTransform>(hand).unwrap();
+        assert_relative_eq!(hand_transform.translation, expected_hand.translation.into());
+        assert!(
+            hand_transform
+                .translation
+                .distance(raw_stage_from_grip.translation.into())
+                > 0.01
+        );
+
+        let grabbed_transform = world.get::<&LocalTransform>(grabbed_entity).unwrap();
+        assert_relative_eq!(
+            grabbed_transform.translation,
+            expected_grabbed.translation.into()
+        );
+    }
+
     // HELPER FUNCTIONS
     fn setup() -> (World, InputContext) {
         let world = World::new();
@@ -159,7 +385,7 @@ mod tests {
     }
 
     fn tick(world: &mut World, input_context: &InputContext) {
-        hands_system_inner(world, input_context);
+        hands_system_inner(world, input_context, &Default::default(), DELTA_TIME);
     }
 
     fn add_hand_to_world(world: &mut World, grabbed_entity: Option<Entity>) -> Entity {
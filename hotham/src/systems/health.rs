@@ -0,0 +1,166 @@
+use hecs::World;
+
+use crate::{
+    components::{
+        health::{Damage, DamageOnContact, Health},
+        Projectile,
+    },
+    contexts::PhysicsContext,
+    Engine,
+};
+
+/// Health system
+/// Clears each [`Health`]'s `damage_this_frame`, then damages anything with [`Health`] that was hit by something with
+/// [`DamageOnContact`] - either touching it in the physics simulation, or as a [`Projectile`].
+///
+/// Should be run after `physics_system` and `projectile_system`, and before any of the application's own systems that
+/// apply or react to damage.
+pub fn health_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let physics_context = &engine.physics_context;
+    health_system_inner(world, physics_context);
+}
+
+pub fn health_system_inner(world: &mut World, physics_context: &PhysicsContext) {
+    for (_, health) in world.query_mut::<&mut Health>() {
+        health.damage_this_frame.clear();
+    }
+
+    for impact in &physics_context.surface_impacts {
+        let pairs = [
+            (impact.entity_a, impact.entity_b),
+            (impact.entity_b, impact.entity_a),
+        ];
+        for (source, target) in pairs {
+            let damage_on_contact = match world.get::<&DamageOnContact>(source) {
+                Ok(damage_on_contact) => *damage_on_contact,
+                Err(_) => continue,
+            };
+            if impact.speed < damage_on_contact.min_speed {
+                continue;
+            }
+            if let Ok(mut health) = world.get::<&mut Health>(target) {
+                health.apply_damage(Damage {
+                    amount: damage_on_contact.amount,
+                    source: Some(source),
+                    point: Some(impact.point),
+                });
+            }
+        }
+    }
+
+    for (source, (projectile, damage_on_contact)) in
+        world.query::<(&Projectile, &DamageOnContact)>().iter()
+    {
+        let hit = match projectile.hit {
+            Some(hit) => hit,
+            None => continue,
+        };
+        if let Ok(mut health) = world.get::<&mut Health>(hit.entity) {
+            health.apply_damage(Damage {
+                amount: damage_on_contact.amount,
+                source: Some(source),
+                point: Some(hit.point),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Affine3A, Vec3};
+    use rapier3d::prelude::SharedShape;
+
+    use crate::{
+        components::{
+            physics::{BodyType, RigidBody},
+            Collider, GlobalTransform, LocalTransform,
+        },
+        systems::{physics::physics_system_inner, projectile::projectile_system_inner},
+    };
+
+    #[test]
+    pub fn test_damage_on_contact() {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let target = world.spawn((
+            Collider::new(SharedShape::cuboid(1., 0.1, 1.)),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+            Health::new(10.),
+        ));
+        let rock = world.spawn((
+            Collider::new(SharedShape::ball(0.1)),
+            RigidBody {
+                body_type: BodyType::Dynamic,
+                linear_velocity: Vec3::new(0., -5., 0.),
+                ..Default::default()
+            },
+            LocalTransform::default(),
+            GlobalTransform(Affine3A::from_translation(Vec3::new(0., 0.5, 0.))),
+            DamageOnContact {
+                amount: 4.,
+                min_speed: 1.,
+            },
+        ));
+
+        let mut damage = Vec::new();
+        for _ in 0..20 {
+            physics_system_inner(&mut physics_context, &mut world);
+            health_system_inner(&mut world, &physics_context);
+            damage.extend(
+                world
+                    .get::<&Health>(target)
+                    .unwrap()
+                    .damage_this_frame
+                    .clone(),
+            );
+        }
+
+        // The rock should only hurt the target once, as it lands.
+        assert_eq!(damage.len(), 1);
+        assert_eq!(damage[0].amount, 4.);
+        assert_eq!(damage[0].source, Some(rock));
+        assert!(damage[0].point.unwrap().y > 0.);
+        assert_eq!(world.get::<&Health>(target).unwrap().current, 6.);
+    }
+
+    #[test]
+    pub fn test_projectile_damage() {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let target = world.spawn((
+            Collider::new(SharedShape::ball(0.5)),
+            LocalTransform::default(),
+            GlobalTransform(Affine3A::from_translation(Vec3::new(0., 0., -5.))),
+            Health::new(10.),
+        ));
+        physics_system_inner(&mut physics_context, &mut world);
+
+        let mut deaths = 0;
+        for _ in 0..3 {
+            world.spawn((
+                Projectile {
+                    gravity: Vec3::ZERO,
+                    ..Projectile::new(Vec3::new(0., 0., -100.))
+                },
+                DamageOnContact::new(4.),
+                LocalTransform::default(),
+            ));
+            for _ in 0..5 {
+                projectile_system_inner(&mut world, &physics_context);
+                health_system_inner(&mut world, &physics_context);
+                if world.get::<&Health>(target).unwrap().just_died() {
+                    deaths += 1;
+                }
+            }
+        }
+
+        // The third projectile kills the target, and it stays dead.
+        let health = world.get::<&Health>(target).unwrap();
+        assert_eq!(health.current, 0.);
+        assert!(health.is_dead());
+        assert_eq!(deaths, 1);
+    }
+}
@@ -0,0 +1,89 @@
+use hecs::World;
+
+use crate::{
+    components::HttpResponses,
+    contexts::{HttpContext, WebSocket},
+    Engine,
+};
+
+/// HTTP system
+/// Delivers the responses to requests sent with `HttpContext`: to the `HttpResponses` component of the entity each
+/// request was sent for, or to `HttpContext::responses`. Responses are only kept for a frame.
+///
+/// It also collects what's happened on each `WebSocket` component into its `events_this_frame`.
+pub fn http_system(engine: &mut Engine) {
+    http_system_inner(&mut engine.world, &mut engine.http_context);
+}
+
+pub fn http_system_inner(world: &mut World, http_context: &mut HttpContext) {
+    for (_, responses) in world.query_mut::<&mut HttpResponses>() {
+        responses.responses_this_frame.clear();
+    }
+    http_context.responses.clear();
+
+    for (_, websocket) in world.query_mut::<&mut WebSocket>() {
+        websocket.update();
+    }
+
+    for (entity, response) in http_context.take_completed() {
+        let entity = match entity {
+            Some(entity) => entity,
+            None => {
+                http_context.responses.push(response);
+                continue;
+            }
+        };
+        if let Ok(mut responses) = world.get::<&mut HttpResponses>(entity) {
+            responses.responses_this_frame.push(response);
+            continue;
+        }
+        // The entity may have been despawned while the request was in flight.
+        let _ = world.insert_one(
+            entity,
+            HttpResponses {
+                responses_this_frame: vec![response],
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    use crate::contexts::http_context::HttpRequest;
+
+    #[test]
+    pub fn test_http_system() {
+        let mut world = World::new();
+        let mut http_context = HttpContext::default();
+        let entity = world.spawn(());
+
+        // A request that can't be sent still gets a response, so whoever sent it isn't left waiting.
+        let request_id = http_context.send_for(entity, HttpRequest::get("not a url"));
+        let started = Instant::now();
+        while http_context.in_flight() > 0 && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+            http_system_inner(&mut world, &mut http_context);
+            if world.get::<&HttpResponses>(entity).is_ok() {
+                break;
+            }
+        }
+
+        let responses = world.get::<&HttpResponses>(entity).unwrap();
+        let response = &responses.responses_this_frame[0];
+        assert_eq!(response.request_id, request_id);
+        assert!(!response.is_success());
+        assert!(response.error.is_some());
+        drop(responses);
+
+        // Responses are only kept for a frame.
+        http_system_inner(&mut world, &mut http_context);
+        assert!(world
+            .get::<&HttpResponses>(entity)
+            .unwrap()
+            .responses_this_frame
+            .is_empty());
+    }
+}
@@ -0,0 +1,468 @@
+use glam::{EulerRot, Quat, Vec3, Vec4};
+use hecs::{Entity, EntityRef, World};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    components::{
+        AnimationController, AnimationTarget, Collider, DistanceGrab, DistanceGrabTarget,
+        GlobalTransform, Grabbable, Hand, Info, Joint, LocalTransform, LogPanel, Mesh, Panel,
+        Parent, Pointer, PoseFilter, RigidBody, Root, Skin, Socket, SoundEmitter, Stage, UIPanel,
+        Visible, HMD,
+    },
+    contexts::{
+        inspector_context::{InspectorRequest, InspectorResponse},
+        InspectorContext,
+    },
+    id_arena::Arena,
+    rendering::{light::Light, material::Material, mesh_data::MeshData},
+    Engine,
+};
+
+/// Checks if an entity has a particular component.
+type HasComponent = fn(&EntityRef) -> bool;
+
+/// The components the inspector knows the names of.
+const COMPONENTS: &[(&str, HasComponent)] = &[
+    ("AnimationController", |e| e.has::<AnimationController>()),
+    ("AnimationTarget", |e| e.has::<AnimationTarget>()),
+    ("Collider", |e| e.has::<Collider>()),
+    ("DistanceGrab", |e| e.has::<DistanceGrab>()),
+    ("DistanceGrabTarget", |e| e.has::<DistanceGrabTarget>()),
+    ("GlobalTransform", |e| e.has::<GlobalTransform>()),
+    ("Grabbable", |e| e.has::<Grabbable>()),
+    ("Hand", |e| e.has::<Hand>()),
+    ("HMD", |e| e.has::<HMD>()),
+    ("Info", |e| e.has::<Info>()),
+    ("Joint", |e| e.has::<Joint>()),
+    ("LocalTransform", |e| e.has::<LocalTransform>()),
+    ("LogPanel", |e| e.has::<LogPanel>()),
+    ("Mesh", |e| e.has::<Mesh>()),
+    ("Panel", |e| e.has::<Panel>()),
+    ("Parent", |e| e.has::<Parent>()),
+    ("Pointer", |e| e.has::<Pointer>()),
+    ("PoseFilter", |e| e.has::<PoseFilter>()),
+    ("RigidBody", |e| e.has::<RigidBody>()),
+    ("Root", |e| e.has::<Root>()),
+    ("Skin", |e| e.has::<Skin>()),
+    ("Socket", |e| e.has::<Socket>()),
+    ("SoundEmitter", |e| e.has::<SoundEmitter>()),
+    ("Stage", |e| e.has::<Stage>()),
+    ("UIPanel", |e| e.has::<UIPanel>()),
+    ("Visible", |e| e.has::<Visible>()),
+];
+
+/// A [`LocalTransform`], with its rotation as Euler angles in degrees, which are much easier to edit by hand.
+#[derive(Debug, Clone, Serialize)]
+struct TransformJson {
+    translation: Vec3,
+    /// Rotation around the X, Y and Z axes in degrees, applied in YXZ order
+    rotation: Vec3,
+    scale: Vec3,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct TransformEdit {
+    translation: Option<Vec3>,
+    rotation: Option<Vec3>,
+    scale: Option<Vec3>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MaterialJson {
+    id: usize,
+    base_color_factor: Vec4,
+    metallic_factor: f32,
+    roughness_factor: f32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct MaterialEdit {
+    base_color_factor: Option<Vec4>,
+    metallic_factor: Option<f32>,
+    roughness_factor: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LightJson {
+    index: usize,
+    #[serde(flatten)]
+    light: Light,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct LightEdit {
+    direction: Option<Vec3>,
+    range: Option<f32>,
+    color: Option<Vec3>,
+    intensity: Option<f32>,
+    position: Option<Vec3>,
+}
+
+/// Inspector system
+/// Walks through each request from the desktop inspector UI and answers it, applying any edits to the `World`,
+/// materials or lights. Edits to a [`LocalTransform`] show up in the headset on the same frame, as long as this runs
+/// before the transform systems.
+pub fn inspector_system(engine: &mut Engine) {
+    let inspector_context = match &engine.inspector_context {
+        Some(inspector_context) => inspector_context,
+        None => return,
+    };
+    let world = &mut engine.world;
+    let resources = &mut engine.render_context.resources;
+    let materials = unsafe { resources.materials_buffer.as_slice_mut() };
+    let lights = &mut engine.render_context.scene_data.lights;
+
+    inspector_system_inner(
+        inspector_context,
+        world,
+        &resources.mesh_data,
+        materials,
+        lights,
+    );
+}
+
+pub fn inspector_system_inner(
+    inspector_context: &InspectorContext,
+    world: &mut World,
+    mesh_data: &Arena<MeshData>,
+    materials: &mut [Material],
+    lights: &mut [Light],
+) {
+    while let Some(request) = inspector_context.next_request() {
+        let response = handle_request(&request, world, mesh_data, materials, lights);
+        request.respond(response);
+    }
+}
+
+fn handle_request(
+    request: &InspectorRequest,
+    world: &mut World,
+    mesh_data: &Arena<MeshData>,
+    materials: &mut [Material],
+    lights: &mut [Light],
+) -> InspectorResponse {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let segments = path
+        .trim_start_matches("/api/")
+        .trim_end_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["entities"]) => Ok(list_entities(world)),
+        ("GET", ["entities", id]) => parse_entity(world, id)
+            .map(|entity| entity_details(world, entity, mesh_data, materials)),
+        ("POST", ["entities", id]) => parse_entity(world, id).and_then(|entity| {
+            edit_transform(world, entity, parse_body(&request.body)?)?;
+            Ok(entity_details(world, entity, mesh_data, materials))
+        }),
+        ("POST", ["materials", id]) => parse_index(id, materials.len()).and_then(|id| {
+            edit_material(&mut materials[id], parse_body(&request.body)?);
+            Ok(json!(material_json(id, &materials[id])))
+        }),
+        ("GET", ["lights"]) => Ok(list_lights(lights)),
+        ("POST", ["lights", index]) => parse_index(index, lights.len()).and_then(|index| {
+            edit_light(&mut lights[index], parse_body(&request.body)?);
+            Ok(list_lights(lights))
+        }),
+        _ => Err(InspectorResponse::error(
+            404,
+            format!("No such endpoint: {} {}", request.method, path),
+        )),
+    };
+
+    match result {
+        Ok(body) => InspectorResponse::ok(body.to_string()),
+        Err(response) => response,
+    }
+}
+
+fn list_entities(world: &World) -> serde_json::Value {
+    let entities = world
+        .iter()
+        .map(|entity_ref| entity_summary(&entity_ref))
+        .collect::<Vec<_>>();
+    json!(entities)
+}
+
+fn entity_summary(entity_ref: &EntityRef) -> serde_json::Value {
+    let components = COMPONENTS
+        .iter()
+        .filter(|(_, has)| has(entity_ref))
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    json!({
+        "id": entity_ref.entity().to_bits(),
+        "name": entity_ref.get::<&Info>().map(|info| info.name.clone()),
+        "components": components,
+    })
+}
+
+fn entity_details(
+    world: &World,
+    entity: Entity,
+    mesh_data: &Arena<MeshData>,
+    materials: &[Material],
+) -> serde_json::Value {
+    let entity_ref = world.entity(entity).unwrap();
+    let mut details = entity_summary(&entity_ref);
+
+    let local_transform = entity_ref.get::<&LocalTransform>().map(|local_transform| {
+        let (y, x, z) = local_transform.rotation.to_euler(EulerRot::YXZ);
+        TransformJson {
+            translation: local_transform.translation,
+            rotation: Vec3::new(x, y, z) * 180. / std::f32::consts::PI,
+            scale: local_transform.scale,
+        }
+    });
+
+    // The materials used by each of this entity's primitives, without repeats.
+    let mut material_ids = Vec::new();
+    if let Some(mesh) = entity_ref.get::<&Mesh>() {
+        if let Some(mesh_data) = mesh_data.get(mesh.handle) {
+            for primitive in &mesh_data.primitives {
+                let id = primitive.material_id as usize;
+                if id < materials.len() && !material_ids.contains(&id) {
+                    material_ids.push(id);
+                }
+            }
+        }
+    }
+    let materials = material_ids
+        .into_iter()
+        .map(|id| material_json(id, &materials[id]))
+        .collect::<Vec<_>>();
+
+    details["local_transform"] = json!(local_transform);
+    details["materials"] = json!(materials);
+    details
+}
+
+fn edit_transform(
+    world: &mut World,
+    entity: Entity,
+    edit: TransformEdit,
+) -> Result<(), InspectorResponse> {
+    let mut local_transform = world
+        .get::<&mut LocalTransform>(entity)
+        .map_err(|_| InspectorResponse::error(400, "This entity has no LocalTransform"))?;
+
+    if let Some(translation) = edit.translation {
+        local_transform.translation = translation;
+    }
+    if let Some(rotation) = edit.rotation {
+        let rotation = rotation * std::f32::consts::PI / 180.;
+        local_transform.rotation =
+            Quat::from_euler(EulerRot::YXZ, rotation.y, rotation.x, rotation.z);
+    }
+    if let Some(scale) = edit.scale {
+        local_transform.scale = scale;
+    }
+
+    Ok(())
+}
+
+fn material_json(id: usize, material: &Material) -> MaterialJson {
+    MaterialJson {
+        id,
+        base_color_factor: material.base_color_factor,
+        metallic_factor: material.metallic_factor,
+        roughness_factor: material.roughness_factor,
+    }
+}
+
+fn edit_material(material: &mut Material, edit: MaterialEdit) {
+    if let Some(base_color_factor) = edit.base_color_factor {
+        material.base_color_factor = base_color_factor;
+    }
+    if let Some(metallic_factor) = edit.metallic_factor {
+        material.metallic_factor = metallic_factor;
+    }
+    if let Some(roughness_factor) = edit.roughness_factor {
+        material.roughness_factor = roughness_factor;
+    }
+}
+
+fn list_lights(lights: &[Light]) -> serde_json::Value {
+    let lights = lights
+        .iter()
+        .enumerate()
+        .map(|(index, light)| LightJson {
+            index,
+            light: *light,
+        })
+        .collect::<Vec<_>>();
+    json!(lights)
+}
+
+fn edit_light(light: &mut Light, edit: LightEdit) {
+    if let Some(direction) = edit.direction {
+        light.direction = direction;
+    }
+    if let Some(range) = edit.range {
+        light.range = range;
+    }
+    if let Some(color) = edit.color {
+        light.color = color;
+    }
+    if let Some(intensity) = edit.intensity {
+        light.intensity = intensity;
+    }
+    if let Some(position) = edit.position {
+        light.position = position;
+    }
+}
+
+fn parse_entity(world: &World, id: &str) -> Result<Entity, InspectorResponse> {
+    id.parse()
+        .ok()
+        .and_then(Entity::from_bits)
+        .filter(|entity| world.contains(*entity))
+        .ok_or_else(|| InspectorResponse::error(404, format!("No such entity: {}", id)))
+}
+
+fn parse_index(index: &str, len: usize) -> Result<usize, InspectorResponse> {
+    index
+        .parse()
+        .ok()
+        .filter(|index| *index < len)
+        .ok_or_else(|| InspectorResponse::error(404, format!("No such index: {}", index)))
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, InspectorResponse> {
+    serde_json::from_slice(body).map_err(|e| InspectorResponse::error(400, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{Info, LocalTransform},
+        rendering::{light::LIGHT_TYPE_POINT, primitive::Primitive},
+    };
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_inspector_requests() {
+        let mut world = World::new();
+        let mut mesh_data = Arena::new();
+        let mut materials = vec![Material::default(), Material::default()];
+        let mut lights = vec![Light::default(); 2];
+
+        let handle = mesh_data.alloc(MeshData {
+            primitives: vec![
+                Primitive {
+                    material_id: 1,
+                    ..Default::default()
+                },
+                Primitive {
+                    material_id: 1,
+                    ..Default::default()
+                },
+            ],
+        });
+        let helmet = world.spawn((
+            Info {
+                name: "Helmet".to_string(),
+                node_id: 0,
+            },
+            LocalTransform::default(),
+            Mesh { handle },
+        ));
+
+        let mut request = |method: &str, path: &str, body: &str| {
+            let (respond, _) = crossbeam::channel::bounded(1);
+            let request = InspectorRequest {
+                method: method.to_string(),
+                path: path.to_string(),
+                body: body.as_bytes().to_vec(),
+                respond,
+            };
+            let response = handle_request(
+                &request,
+                &mut world,
+                &mesh_data,
+                &mut materials,
+                &mut lights,
+            );
+            let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            (response.status, body)
+        };
+
+        // Entities should be listed with their names and components..
+        let (status, entities) = request("GET", "/api/entities", "");
+        assert_eq!(status, 200);
+        assert_eq!(entities[0]["id"], helmet.to_bits().get());
+        assert_eq!(entities[0]["name"], "Helmet");
+        assert_eq!(
+            entities[0]["components"],
+            json!(["Info", "LocalTransform", "Mesh"])
+        );
+
+        // ..and their transforms should be editable, with rotations in degrees.
+        let path = format!("/api/entities/{}", helmet.to_bits());
+        let (status, details) = request(
+            "POST",
+            &path,
+            r#"{"translation": [1, 2, 3], "rotation": [0, 90, 0]}"#,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(
+            details["local_transform"]["translation"],
+            json!([1., 2., 3.])
+        );
+        assert_eq!(details["materials"].as_array().unwrap().len(), 1);
+        assert_eq!(details["materials"][0]["id"], 1);
+
+        let (status, _) = request("POST", &path, "not json");
+        assert_eq!(status, 400);
+
+        let local_transform = world.get::<&LocalTransform>(helmet).unwrap();
+        assert_eq!(local_transform.translation, Vec3::new(1., 2., 3.));
+        assert_eq!(local_transform.scale, Vec3::ONE);
+        assert_relative_eq!(
+            local_transform.rotation,
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)
+        );
+    }
+
+    #[test]
+    pub fn test_inspector_materials_and_lights() {
+        let mut world = World::new();
+        let mesh_data = Arena::new();
+        let mut materials = vec![Material::default()];
+        let mut lights = vec![Light::new_point(Vec3::ZERO, 10., 1., Vec3::ONE)];
+
+        let mut request = |method: &str, path: &str, body: &str| {
+            let (respond, _) = crossbeam::channel::bounded(1);
+            let request = InspectorRequest {
+                method: method.to_string(),
+                path: path.to_string(),
+                body: body.as_bytes().to_vec(),
+                respond,
+            };
+            handle_request(
+                &request,
+                &mut world,
+                &mesh_data,
+                &mut materials,
+                &mut lights,
+            )
+            .status
+        };
+
+        assert_eq!(
+            request("POST", "/api/materials/0", r#"{"roughness_factor": 0.25}"#),
+            200
+        );
+        assert_eq!(request("POST", "/api/lights/0", r#"{"intensity": 5}"#), 200);
+        assert_eq!(request("POST", "/api/lights/1", r#"{"intensity": 5}"#), 404);
+        assert_eq!(request("DELETE", "/api/lights/0", ""), 404);
+        assert_eq!(request("GET", "/api/entities/12345", ""), 404);
+
+        assert_eq!(materials[0].roughness_factor, 0.25);
+        assert_eq!(lights[0].intensity, 5.);
+        assert_eq!(lights[0].light_type, LIGHT_TYPE_POINT);
+    }
+}
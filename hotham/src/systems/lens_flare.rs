@@ -0,0 +1,164 @@
+use glam::{Affine3A, Vec3};
+use hecs::World;
+use rapier3d::prelude::{QueryFilter, Ray};
+
+use crate::{
+    components::{GlobalTransform, LensFlare},
+    contexts::{physics_context::DELTA_TIME, PhysicsContext},
+    rendering::light::{Light, LIGHT_TYPE_DIRECTIONAL, LIGHT_TYPE_NONE, LIGHT_TYPE_SPOT},
+    util::na_vector_from_glam,
+    Engine,
+};
+
+/// How far to look for anything blocking a directional light, in metres.
+const DIRECTIONAL_OCCLUSION_DISTANCE: f32 = 1000.;
+
+/// Lens flare system
+/// Casts a ray from the viewer to each [`LensFlare`]'s light, and fades the flare out if anything is in the way.
+/// Should be run after `physics_system`.
+pub fn lens_flare_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let physics_context = &engine.physics_context;
+    let lights = &engine.render_context.scene_data.lights;
+    let global_from_hmd = match world.get::<&GlobalTransform>(engine.hmd_entity) {
+        Ok(transform) => transform.0,
+        Err(_) => return,
+    };
+    lens_flare_system_inner(world, physics_context, lights, &global_from_hmd);
+}
+
+pub fn lens_flare_system_inner(
+    world: &mut World,
+    physics_context: &PhysicsContext,
+    lights: &[Light],
+    global_from_hmd: &Affine3A,
+) {
+    let viewer = Vec3::from(global_from_hmd.translation);
+
+    for (_, lens_flare) in world.query_mut::<&mut LensFlare>() {
+        let target = lights
+            .get(lens_flare.light_index)
+            .map_or(0., |light| light_visibility(physics_context, light, viewer));
+
+        // Fade rather than pop, so flares don't flicker as things pass in front of the light.
+        let step = if lens_flare.fade_time > 0. {
+            DELTA_TIME / lens_flare.fade_time
+        } else {
+            1.
+        };
+        lens_flare.visibility += (target - lens_flare.visibility).clamp(-step, step);
+    }
+}
+
+/// How much of `light` can be seen from `viewer`, from 0 to 1.
+fn light_visibility(physics_context: &PhysicsContext, light: &Light, viewer: Vec3) -> f32 {
+    let (to_light, distance) = match light.light_type {
+        LIGHT_TYPE_NONE => return 0.,
+        LIGHT_TYPE_DIRECTIONAL => (-light.direction.normalize(), DIRECTIONAL_OCCLUSION_DISTANCE),
+        _ => {
+            let offset = light.position - viewer;
+            (offset.normalize(), offset.length())
+        }
+    };
+    if !to_light.is_finite() {
+        return 0.;
+    }
+
+    // A spotlight can only be seen from inside its cone.
+    let cone = if light.light_type == LIGHT_TYPE_SPOT {
+        let cos = light.direction.normalize().dot(-to_light);
+        smoothstep(light.outer_cone_cos, light.inner_cone_cos, cos)
+    } else {
+        1.
+    };
+    if cone <= 0. {
+        return 0.;
+    }
+
+    let ray = Ray::new(
+        na_vector_from_glam(viewer).into(),
+        na_vector_from_glam(to_light),
+    );
+    let blocked = physics_context
+        .query_pipeline
+        .cast_ray(
+            &physics_context.rigid_bodies,
+            &physics_context.colliders,
+            &ray,
+            distance,
+            true,
+            QueryFilter::new().exclude_sensors(),
+        )
+        .is_some();
+
+    if blocked {
+        0.
+    } else {
+        cone
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x >= edge1 { 1. } else { 0. };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rapier3d::prelude::{ColliderBuilder, RigidBodyBuilder};
+
+    #[test]
+    pub fn test_lens_flare_occlusion() {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let lights = [
+            Light::new_point(Vec3::new(0., 1., -5.), 10., 1., Vec3::ONE),
+            Light::new_spotlight(Vec3::Z, 10., 1., Vec3::ONE, Vec3::new(0., 1., 5.), 0.1, 0.2),
+            Light::none(),
+        ];
+        let point = world.spawn((LensFlare::new(0),));
+        let spot = world.spawn((LensFlare::new(1),));
+        let none = world.spawn((LensFlare::new(2),));
+        let missing = world.spawn((LensFlare::new(7),));
+        let global_from_hmd = Affine3A::from_translation(Vec3::new(0., 1., 0.));
+
+        // With a clear view, the point light's flare should fade in..
+        let tick = |world: &mut World, physics_context: &PhysicsContext| {
+            for _ in 0..10 {
+                lens_flare_system_inner(world, physics_context, &lights, &global_from_hmd);
+            }
+        };
+        tick(&mut world, &physics_context);
+        let visibility =
+            |world: &World, entity| world.get::<&LensFlare>(entity).unwrap().visibility;
+        assert_eq!(visibility(&world, point), 1.);
+
+        // ..but the spotlight is facing away from the viewer, and the others have no light.
+        assert_eq!(visibility(&world, spot), 0.);
+        assert_eq!(visibility(&world, none), 0.);
+        assert_eq!(visibility(&world, missing), 0.);
+
+        // Put a wall between the viewer and the point light, and its flare should fade out.
+        let body = physics_context.rigid_bodies.insert(
+            RigidBodyBuilder::fixed()
+                .translation(na_vector_from_glam(Vec3::new(0., 1., -2.)))
+                .build(),
+        );
+        physics_context.colliders.insert_with_parent(
+            ColliderBuilder::cuboid(1., 1., 0.1).build(),
+            body,
+            &mut physics_context.rigid_bodies,
+        );
+        physics_context.update();
+
+        lens_flare_system_inner(&mut world, &physics_context, &lights, &global_from_hmd);
+        let fading = visibility(&world, point);
+        assert!(fading > 0. && fading < 1.);
+        tick(&mut world, &physics_context);
+        assert_eq!(visibility(&world, point), 0.);
+    }
+}
@@ -0,0 +1,20 @@
+use hecs::World;
+
+use crate::{
+    asset_importer::loader::LoadingProgress,
+    components::{LoadingPanel, UIPanel},
+    Engine,
+};
+
+/// Loading panel system
+/// Walks through each `LoadingPanel` in the World and shows `progress` in its `UIPanel`
+pub fn loading_panel_system(engine: &mut Engine, progress: &LoadingProgress) {
+    loading_panel_system_inner(&mut engine.world, progress);
+}
+
+pub fn loading_panel_system_inner(world: &mut World, progress: &LoadingProgress) {
+    for (_, (ui_panel, _)) in world.query_mut::<(&mut UIPanel, &LoadingPanel)>() {
+        ui_panel.text = format!("Loading.. ({}/{})", progress.imported, progress.total);
+        ui_panel.progress = Some(progress.fraction());
+    }
+}
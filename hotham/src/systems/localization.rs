@@ -0,0 +1,37 @@
+use hecs::World;
+
+use crate::{
+    components::{LocalizedText, UIPanel},
+    localization::Localization,
+    Engine,
+};
+
+/// Localization system
+/// Walks through each `UIPanel` in the World and
+/// - gives it any fallback fonts that have been added since it was last drawn
+/// - shows its `LocalizedText`, if it has one, in the current language
+pub fn localization_system(engine: &mut Engine) {
+    localization_system_inner(&mut engine.world, &engine.localization);
+}
+
+pub fn localization_system_inner(world: &mut World, localization: &Localization) {
+    for (_, (ui_panel, localized_text)) in
+        world.query_mut::<(&mut UIPanel, Option<&LocalizedText>)>()
+    {
+        if ui_panel.fonts_version != localization.fonts_version() {
+            ui_panel
+                .egui_context
+                .set_fonts(localization.font_definitions());
+            ui_panel.fonts_version = localization.fonts_version();
+        }
+
+        if let Some(localized_text) = localized_text {
+            let args: Vec<(&str, &str)> = localized_text
+                .args
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect();
+            ui_panel.text = localization.format(&localized_text.key, &args);
+        }
+    }
+}
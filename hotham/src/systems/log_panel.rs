@@ -0,0 +1,71 @@
+use hecs::World;
+use log::Level;
+
+use crate::{
+    components::{log_panel::LogPanel, UIPanel},
+    logging::LogHistory,
+    Engine,
+};
+
+/// Log panel system
+/// Walks through each `LogPanel` in the World and shows the most recent log entries in its `UIPanel`
+pub fn log_panel_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let log_history = &engine.log_history;
+    log_panel_system_inner(world, log_history);
+}
+
+pub fn log_panel_system_inner(world: &mut World, log_history: &LogHistory) {
+    for (_, (ui_panel, log_panel)) in world.query_mut::<(&mut UIPanel, &LogPanel)>() {
+        ui_panel.text = log_panel_text(log_history, log_panel.level, log_panel.lines);
+    }
+}
+
+fn log_panel_text(log_history: &LogHistory, level: Level, lines: usize) -> String {
+    let entries = log_history.recent(level, lines);
+    if entries.is_empty() {
+        return format!("No log entries at {} or above", level);
+    }
+
+    entries
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LogEntry;
+
+    #[test]
+    pub fn test_log_panel_text() {
+        let log_history = LogHistory::new(10);
+        assert_eq!(
+            log_panel_text(&log_history, Level::Warn, 2),
+            "No log entries at WARN or above"
+        );
+
+        for (level, message) in [
+            (Level::Error, "Lost tracking"),
+            (Level::Info, "Loaded models"),
+            (Level::Warn, "Frame took 20ms"),
+        ] {
+            log_history.push(LogEntry {
+                level,
+                target: "hotham".to_string(),
+                message: message.to_string(),
+            });
+        }
+
+        assert_eq!(
+            log_panel_text(&log_history, Level::Warn, 2),
+            "ERROR hotham - Lost tracking\nWARN  hotham - Frame took 20ms"
+        );
+        assert_eq!(
+            log_panel_text(&log_history, Level::Info, 1),
+            "WARN  hotham - Frame took 20ms"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use hecs::World;
+
+use crate::{
+    contexts::{physics_context::DELTA_TIME, InputContext, LuaContext},
+    scripting::buttons_pressed,
+    Engine,
+};
+
+/// Lua scripting system
+/// Walks through each entity with a `Script` and runs its Lua script for this frame
+pub fn lua_scripting_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let lua_context = &mut engine.lua_context;
+    let input_context = &engine.input_context;
+
+    lua_scripting_system_inner(world, lua_context, input_context);
+}
+
+pub fn lua_scripting_system_inner(
+    world: &mut World,
+    lua_context: &mut LuaContext,
+    input_context: &InputContext,
+) {
+    let buttons_pressed = buttons_pressed(input_context);
+    lua_context.update(world, &buttons_pressed, DELTA_TIME);
+}
@@ -0,0 +1,15 @@
+use crate::Engine;
+
+/// Memory stats system
+/// Refreshes the engine's `MemoryStats` about once a second
+pub fn memory_stats_system(engine: &mut Engine) {
+    if !engine.memory_stats.needs_refresh() {
+        return;
+    }
+
+    engine.memory_stats.refresh(
+        &engine.world,
+        &engine.render_context.resources,
+        &engine.vulkan_context,
+    );
+}
@@ -1,27 +1,89 @@
 #![allow(missing_docs)]
+pub mod analytics;
 pub mod animation;
 pub mod audio;
+pub mod captions;
+pub mod character_controller;
+pub mod comfort_locomotion;
+pub mod controller_models;
 pub mod debug;
+pub mod debug_panel;
+pub mod distance_grab;
 pub mod draw_gui;
+pub mod effects;
+pub mod expressions;
+pub mod follow;
 pub mod grabbing;
+pub mod guidance;
+pub mod hand_menu;
 pub mod hands;
 pub mod haptics;
+pub mod health;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "inspector")]
+pub mod inspector;
+pub mod lens_flare;
+pub mod loading_panel;
+pub mod localization;
+pub mod log_panel;
+#[cfg(feature = "lua-scripting")]
+pub mod lua_scripting;
+pub mod memory_stats;
+pub mod motion;
 pub mod physics;
 pub mod pointers;
+pub mod projectile;
 pub mod rendering;
+#[cfg(feature = "wasm-scripting")]
+pub mod scripting;
 pub mod skinning;
+pub mod sockets;
+pub mod spectator;
+pub mod spring_bone;
 pub mod update_global_transform;
 pub mod update_global_transform_with_parent;
 
+pub use analytics::analytics_system;
 pub use animation::animation_system;
 pub use audio::audio_system;
+pub use captions::captions_system;
+pub use character_controller::character_controller_system;
+pub use comfort_locomotion::comfort_locomotion_system;
+pub use controller_models::controller_models_system;
+pub use debug_panel::debug_panel_system;
+pub use distance_grab::distance_grab_system;
 pub use draw_gui::draw_gui_system;
+pub use effects::effects_system;
+pub use expressions::expressions_system;
+pub use follow::follow_system;
 pub use grabbing::grabbing_system;
+pub use guidance::guidance_system;
+pub use hand_menu::hand_menu_system;
 pub use hands::hands_system;
 pub use haptics::haptics_system;
+pub use health::health_system;
+#[cfg(feature = "http")]
+pub use http::http_system;
+#[cfg(feature = "inspector")]
+pub use inspector::inspector_system;
+pub use lens_flare::lens_flare_system;
+pub use loading_panel::loading_panel_system;
+pub use localization::localization_system;
+pub use log_panel::log_panel_system;
+#[cfg(feature = "lua-scripting")]
+pub use lua_scripting::lua_scripting_system;
+pub use memory_stats::memory_stats_system;
+pub use motion::motion_system;
 pub use physics::physics_system;
 pub use pointers::pointers_system;
+pub use projectile::projectile_system;
 pub use rendering::rendering_system;
+#[cfg(feature = "wasm-scripting")]
+pub use scripting::scripting_system;
 pub use skinning::skinning_system;
+pub use sockets::sockets_system;
+pub use spectator::spectator_system;
+pub use spring_bone::spring_bone_system;
 pub use update_global_transform::update_global_transform_system;
 pub use update_global_transform_with_parent::update_global_transform_with_parent_system;
@@ -0,0 +1,97 @@
+use glam::Quat;
+use hecs::World;
+
+use crate::{
+    components::{AngularVelocity, LinearAcceleration, LinearVelocity, LocalTransform, RigidBody},
+    contexts::TimeContext,
+    Engine,
+};
+
+/// Motion system
+/// Moves entities with a [`LinearVelocity`] or [`AngularVelocity`] by however much time passed in the last frame, so
+/// they move at the same speed no matter the headset's refresh rate. A [`LinearAcceleration`] speeds up the entity's
+/// [`LinearVelocity`] first. Entities with a [`RigidBody`] are left to the physics simulation.
+///
+/// Should be run before `update_global_transform_system`.
+pub fn motion_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let time_context = &engine.time_context;
+    motion_system_inner(world, time_context);
+}
+
+pub fn motion_system_inner(world: &mut World, time_context: &TimeContext) {
+    let delta_time = time_context.delta_time();
+
+    for (_, (local_transform, linear_velocity, linear_acceleration)) in world
+        .query_mut::<(
+            &mut LocalTransform,
+            &mut LinearVelocity,
+            Option<&LinearAcceleration>,
+        )>()
+        .without::<&RigidBody>()
+    {
+        if let Some(linear_acceleration) = linear_acceleration {
+            linear_velocity.0 += linear_acceleration.0 * delta_time;
+        }
+        local_transform.translation += linear_velocity.0 * delta_time;
+    }
+
+    for (_, (local_transform, angular_velocity)) in world
+        .query_mut::<(&mut LocalTransform, &AngularVelocity)>()
+        .without::<&RigidBody>()
+    {
+        let rotation = Quat::from_scaled_axis(angular_velocity.0 * delta_time);
+        local_transform.rotation = (rotation * local_transform.rotation).normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    pub fn test_motion_is_frame_rate_independent() {
+        // Move the same entities for a second at 72Hz and at 120Hz..
+        let positions = [72, 120].map(|refresh_rate| {
+            let mut world = World::new();
+            let mut time_context = TimeContext::default();
+            let entity = world.spawn((
+                LocalTransform::default(),
+                LinearVelocity(Vec3::X),
+                AngularVelocity(Vec3::Y * std::f32::consts::FRAC_PI_2),
+            ));
+            let falling = world.spawn((
+                LocalTransform::default(),
+                LinearVelocity::default(),
+                LinearAcceleration(Vec3::Y * -10.),
+            ));
+            let physics = world.spawn((
+                LocalTransform::default(),
+                LinearVelocity(Vec3::X),
+                RigidBody::default(),
+            ));
+
+            for _ in 0..refresh_rate {
+                time_context.advance(1. / refresh_rate as f32);
+                motion_system_inner(&mut world, &time_context);
+            }
+
+            let local_transform = *world.get::<&LocalTransform>(entity).unwrap();
+            assert!(local_transform.translation.abs_diff_eq(Vec3::X, 1e-4));
+            let facing = local_transform.rotation * -Vec3::Z;
+            assert!(facing.abs_diff_eq(-Vec3::X, 1e-4));
+
+            // Entities in the physics simulation are left alone.
+            let physics = world.get::<&LocalTransform>(physics).unwrap();
+            assert_eq!(physics.translation, Vec3::ZERO);
+
+            let falling = world.get::<&LocalTransform>(falling).unwrap();
+            falling.translation.y
+        });
+
+        // ..and anything accelerating should end up in about the same place, too.
+        assert!((positions[0] - positions[1]).abs() < 0.1);
+        assert!((positions[0] + 5.).abs() < 0.1);
+    }
+}
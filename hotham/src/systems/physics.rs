@@ -4,7 +4,7 @@ use rapier3d::prelude::{ActiveEvents, ColliderBuilder, InteractionGroups, RigidB
 use crate::{
     components::{
         physics::Impulse,
-        physics::{AdditionalMass, BodyType, RigidBody, Teleport},
+        physics::{AdditionalMass, BodyType, Disabled, PhysicalMaterial, RigidBody, Teleport},
         Collider, GlobalTransform, LocalTransform, Parent,
     },
     contexts::physics_context,
@@ -45,8 +45,11 @@ pub fn physics_system(engine: &mut Engine) {
     physics_system_inner(&mut engine.physics_context, &mut engine.world);
 }
 
-pub(crate) fn physics_system_inner(physics_context: &mut PhysicsContext, world: &mut hecs::World) {
-    // First, see if there are any rigid-bodies or colliders in the world that don't currently have a handle in rapier.
+pub fn physics_system_inner(physics_context: &mut PhysicsContext, world: &mut hecs::World) {
+    // Take anything that's been disabled out of the simulation.
+    remove_disabled(physics_context, world);
+
+    // Then, see if there are any rigid-bodies or colliders in the world that don't currently have a handle in rapier.
     create_handles(physics_context, world);
 
     // Next, update any game controlled rigid bodies.
@@ -55,6 +58,9 @@ pub(crate) fn physics_system_inner(physics_context: &mut PhysicsContext, world:
     // Next, update the physics simulation.
     physics_context.update();
 
+    // Find out what hit what, and what they're made of.
+    physics_context.update_collision_events(world);
+
     // Now update any physics controlled rigid bodies.
     update_world_from_physics(physics_context, world);
 }
@@ -70,6 +76,7 @@ fn create_rigid_bodies(world: &mut hecs::World, physics_context: &mut PhysicsCon
     for (entity, (r, parent, global_transform)) in world
         .query::<(&RigidBody, Option<&Parent>, &GlobalTransform)>()
         .without::<&RigidBodyHandle>()
+        .without::<&Disabled>()
         .iter()
     {
         if r.body_type == BodyType::Dynamic && parent.is_some() {
@@ -99,6 +106,7 @@ fn create_colliders(world: &mut hecs::World, physics_context: &mut PhysicsContex
     for (entity, (c, rigid_body_handle)) in world
         .query::<(&mut Collider, Option<&RigidBodyHandle>)>()
         .without::<&ColliderHandle>()
+        .without::<&Disabled>()
         .iter()
     {
         let mut collider = ColliderBuilder::new(c.shape.clone())
@@ -139,6 +147,40 @@ fn create_colliders(world: &mut hecs::World, physics_context: &mut PhysicsContex
     command_buffer.run_on(world);
 }
 
+fn remove_disabled(physics_context: &mut PhysicsContext, world: &mut hecs::World) {
+    let mut command_buffer = hecs::CommandBuffer::new();
+
+    for (entity, (collider_handle, collider)) in world
+        .query::<(&ColliderHandle, &mut Collider)>()
+        .with::<&Disabled>()
+        .iter()
+    {
+        collider.collisions_this_frame.clear();
+        physics_context.colliders.remove(
+            collider_handle.0,
+            &mut physics_context.island_manager,
+            &mut physics_context.rigid_bodies,
+            true,
+        );
+        command_buffer.remove_one::<ColliderHandle>(entity);
+    }
+
+    for (entity, rigid_body_handle) in world.query::<&RigidBodyHandle>().with::<&Disabled>().iter()
+    {
+        physics_context.rigid_bodies.remove(
+            rigid_body_handle.0,
+            &mut physics_context.island_manager,
+            &mut physics_context.colliders,
+            &mut physics_context.impulse_joints,
+            &mut physics_context.multibody_joints,
+            true,
+        );
+        command_buffer.remove_one::<RigidBodyHandle>(entity);
+    }
+
+    command_buffer.run_on(world);
+}
+
 fn update_physics_from_world(physics_context: &mut PhysicsContext, world: &mut hecs::World) {
     update_rigid_bodies_from_world(physics_context, world);
     update_colliders_from_world(physics_context, world);
@@ -241,14 +283,16 @@ fn update_rigid_bodies_from_world(physics_context: &mut PhysicsContext, world: &
 }
 
 fn update_colliders_from_world(physics_context: &mut PhysicsContext, world: &mut hecs::World) {
-    for (_, (collider_component, collider_handle, global_transform, rigid_body)) in world
-        .query_mut::<(
-            &Collider,
-            &ColliderHandle,
-            &GlobalTransform,
-            Option<&RigidBody>,
-        )>()
-    {
+    for (
+        _,
+        (collider_component, collider_handle, global_transform, rigid_body, physical_material),
+    ) in world.query_mut::<(
+        &Collider,
+        &ColliderHandle,
+        &GlobalTransform,
+        Option<&RigidBody>,
+        Option<&PhysicalMaterial>,
+    )>() {
         let collider = &mut physics_context.colliders[collider_handle.0];
 
         // Only update position of colliders that don't have a rigid-body attached
@@ -273,9 +317,16 @@ fn update_colliders_from_world(physics_context: &mut PhysicsContext, world: &mut
         collider.set_shape(shape.clone());
         collider.set_collision_groups(InteractionGroups::new(*collision_groups, *collision_filter));
         collider.set_mass(*mass);
-        collider.set_restitution(*restitution);
         collider.set_active_collision_types(*active_collision_types);
         collider.set_translation_wrt_parent(na_vector_from_glam(*offset_from_parent));
+
+        // A physical material overrides the collider's own restitution.
+        let physical_material = physical_material.copied().unwrap_or(PhysicalMaterial {
+            restitution: *restitution,
+            ..Default::default()
+        });
+        collider.set_friction(physical_material.friction);
+        collider.set_restitution(physical_material.restitution);
     }
 }
 
@@ -327,15 +378,21 @@ fn update_collisions(physics_context: &PhysicsContext, world: &hecs::World) {
 mod tests {
     use approx::{assert_relative_eq, assert_relative_ne};
     use glam::{Affine3A, Quat, Vec3};
-    use rapier3d::prelude::ActiveCollisionTypes;
+    use rapier3d::prelude::{ActiveCollisionTypes, Ball};
 
     use crate::{
         components::{
             physics::Impulse,
-            physics::{AdditionalMass, BodyType, RigidBody, Teleport},
+            physics::{
+                AdditionalMass, BodyType, PhysicalMaterial, RigidBody, SharedShape, Surface,
+                Teleport,
+            },
             Collider, GlobalTransform, LocalTransform,
         },
-        contexts::PhysicsContext,
+        contexts::{
+            physics_context::{CastFilter, PANEL_COLLISION_GROUP},
+            PhysicsContext,
+        },
         systems::physics::{ColliderHandle, RigidBodyHandle},
     };
 
@@ -557,4 +614,186 @@ mod tests {
         let a_collider = world.get::<&mut Collider>(a).unwrap();
         assert!(a_collider.collisions_this_frame.contains(&b));
     }
+
+    #[test]
+    /// Test that physical materials set friction and restitution, and tell us what surfaces are hit.
+    pub fn test_physical_material() {
+        let mut world = hecs::World::default();
+        let mut physics_context = PhysicsContext::default();
+
+        let ground = world.spawn((
+            Collider::new(SharedShape::cuboid(5., 0.1, 5.)),
+            PhysicalMaterial::new(Surface::Grass),
+            GlobalTransform::default(),
+        ));
+        let ball_transform =
+            LocalTransform::from_rotation_translation(Quat::IDENTITY, [0., 0.5, 0.].into());
+        let ball = world.spawn((
+            Collider::new(SharedShape::ball(0.2)),
+            RigidBody {
+                linear_velocity: -Vec3::Y * 5.,
+                ..Default::default()
+            },
+            ball_transform,
+            GlobalTransform::from(ball_transform),
+        ));
+
+        physics_system_inner(&mut physics_context, &mut world);
+
+        // The ground's material should be used, and the ball should get the defaults.
+        let collider = |world: &hecs::World, entity| {
+            &physics_context.colliders[world.get::<&ColliderHandle>(entity).unwrap().0]
+        };
+        assert_eq!(collider(&world, ground).friction(), 0.7);
+        assert_eq!(collider(&world, ground).restitution(), 0.05);
+        assert_eq!(collider(&world, ball).friction(), 0.5);
+
+        // There's grass under the ground, but nothing beside it.
+        let hit = physics_context
+            .surface_below(&world, Vec3::new(1., 2., 1.), 10.)
+            .unwrap();
+        assert_eq!(hit.entity, ground);
+        assert_eq!(hit.surface, Surface::Grass);
+        assert_relative_eq!(hit.point, Vec3::new(1., 0.1, 1.));
+        assert_relative_eq!(hit.normal, Vec3::Y);
+        assert_relative_eq!(hit.distance, 1.9);
+        assert!(physics_context
+            .surface_below(&world, Vec3::new(10., 2., 0.), 10.)
+            .is_none());
+
+        // Drop the ball, and we should hear about it hitting the grass.
+        let mut impacts = Vec::new();
+        for _ in 0..10 {
+            physics_system_inner(&mut physics_context, &mut world);
+            impacts.extend(physics_context.surface_impacts.iter().copied());
+        }
+        assert_eq!(impacts.len(), 1);
+        let impact = impacts[0];
+        let surfaces = [
+            (impact.entity_a, impact.surface_a),
+            (impact.entity_b, impact.surface_b),
+        ];
+        assert!(surfaces.contains(&(ground, Surface::Grass)));
+        assert!(surfaces.contains(&(ball, Surface::Default)));
+        assert_relative_eq!(impact.point.y, 0.1, epsilon = 0.05);
+        assert!(impact.speed > 4.);
+    }
+
+    #[test]
+    /// Test that rays and shapes find the entities they hit, filtered by collision group.
+    pub fn test_casts() {
+        let mut world = hecs::World::default();
+        let mut physics_context = PhysicsContext::default();
+
+        let box_at = |z: f32| GlobalTransform(Affine3A::from_translation([0., 0., z].into()));
+        let sensor = world.spawn((
+            Collider {
+                shape: SharedShape::cuboid(0.5, 0.5, 0.1),
+                sensor: true,
+                ..Default::default()
+            },
+            box_at(-1.),
+        ));
+        let wall = world.spawn((
+            Collider::new(SharedShape::cuboid(0.5, 0.5, 0.1)),
+            box_at(-2.),
+        ));
+        let panel = world.spawn((
+            Collider {
+                shape: SharedShape::cuboid(0.5, 0.5, 0.1),
+                collision_groups: PANEL_COLLISION_GROUP,
+                collision_filter: PANEL_COLLISION_GROUP,
+                ..Default::default()
+            },
+            box_at(-4.),
+        ));
+        physics_system_inner(&mut physics_context, &mut world);
+
+        // Sensors are skipped, so the wall is hit first..
+        let hit = physics_context
+            .cast_ray(&world, Vec3::ZERO, -Vec3::Z, 10., Default::default())
+            .unwrap();
+        assert_eq!(hit.entity, wall);
+        assert_relative_eq!(hit.point, Vec3::new(0., 0., -1.9));
+        assert_relative_eq!(hit.normal, Vec3::Z);
+        assert_relative_eq!(hit.distance, 1.9);
+
+        // ..unless they're asked for, or the wall is in the wrong group or ignored.
+        let with_sensors = CastFilter {
+            sensors: true,
+            ..Default::default()
+        };
+        let hit = physics_context.cast_ray(&world, Vec3::ZERO, -Vec3::Z, 10., with_sensors);
+        assert_eq!(hit.unwrap().entity, sensor);
+        let panels = CastFilter::groups(PANEL_COLLISION_GROUP);
+        let hit = physics_context.cast_ray(&world, Vec3::ZERO, -Vec3::Z, 10., panels);
+        assert_eq!(hit.unwrap().entity, panel);
+        let ignore_wall = CastFilter {
+            ignore: &[wall],
+            ..Default::default()
+        };
+        let hit = physics_context.cast_ray(&world, Vec3::ZERO, -Vec3::Z, 10., ignore_wall);
+        assert_eq!(hit.unwrap().entity, panel);
+
+        // Every hit along the ray can be found, nearest first.
+        let hits =
+            physics_context.intersections_with_ray(&world, Vec3::ZERO, -Vec3::Z, 10., with_sensors);
+        let entities = hits.iter().map(|hit| hit.entity).collect::<Vec<_>>();
+        assert_eq!(entities, [sensor, wall, panel]);
+
+        // A shape stops as soon as it touches something.
+        let hit = physics_context
+            .cast_shape(
+                &world,
+                &Ball::new(0.4),
+                Vec3::ZERO,
+                Quat::IDENTITY,
+                -Vec3::Z * 10.,
+                Default::default(),
+            )
+            .unwrap();
+        assert_eq!(hit.entity, wall);
+        assert_relative_eq!(hit.distance, 1.5, epsilon = 0.001);
+    }
+
+    #[test]
+    /// Test that collisions are reported with the entities that collided.
+    pub fn test_collision_events() {
+        let mut physics_context = PhysicsContext::default();
+        let mut world = hecs::World::default();
+        let sensor = || Collider {
+            sensor: true,
+            active_collision_types: ActiveCollisionTypes::FIXED_FIXED,
+            ..Default::default()
+        };
+        let a = world.spawn((sensor(), GlobalTransform::default()));
+        let b = world.spawn((sensor(), GlobalTransform::default()));
+        let c = world.spawn((
+            sensor(),
+            GlobalTransform(Affine3A::from_translation([10., 0., 0.].into())),
+        ));
+
+        // a and b start overlapping..
+        physics_system_inner(&mut physics_context, &mut world);
+        let events = physics_context.collision_events.iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].started && events[0].sensor);
+        assert_eq!(events[0].other(a), Some(b));
+        assert_eq!(events[0].other(b), Some(a));
+        assert_eq!(physics_context.collision_events.involving(c).count(), 0);
+
+        // ..nothing changes..
+        physics_system_inner(&mut physics_context, &mut world);
+        assert!(physics_context.collision_events.is_empty());
+
+        // ..then b moves away, and they stop.
+        world.get::<&mut GlobalTransform>(b).unwrap().0 =
+            Affine3A::from_translation([5., 0., 0.].into());
+        physics_system_inner(&mut physics_context, &mut world);
+        let events = physics_context.collision_events.drain().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].started);
+        assert_eq!(events[0].other(a), Some(b));
+        assert!(physics_context.collision_events.is_empty());
+    }
 }
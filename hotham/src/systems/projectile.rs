@@ -0,0 +1,205 @@
+use glam::{Quat, Vec3};
+use hecs::{CommandBuffer, World};
+
+use crate::{
+    components::{
+        projectile::{Projectile, Tracer},
+        LocalTransform,
+    },
+    contexts::{physics_context::DELTA_TIME, PhysicsContext},
+    Engine,
+};
+
+/// Projectile system
+/// Moves each [`Projectile`] along its path, sweeping it through the physics simulation so it can't pass through
+/// anything, and turns it to face where it's going. Projectiles that hit something have `hit` set, and are despawned
+/// the next frame, as are projectiles and [`Tracer`]s that have run out of time.
+///
+/// Should be run after `physics_system` and before `update_global_transform_system`.
+pub fn projectile_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let physics_context = &engine.physics_context;
+    projectile_system_inner(world, physics_context);
+}
+
+pub fn projectile_system_inner(world: &mut World, physics_context: &PhysicsContext) {
+    let mut command_buffer = CommandBuffer::new();
+
+    for (entity, (projectile, local_transform)) in world
+        .query::<(&mut Projectile, &mut LocalTransform)>()
+        .iter()
+    {
+        // The application has had a frame to react to the hit.
+        if projectile.hit.is_some() || projectile.lifetime <= 0. {
+            command_buffer.despawn(entity);
+            continue;
+        }
+
+        let start = local_transform.translation;
+        let step =
+            projectile.velocity * DELTA_TIME + 0.5 * projectile.gravity * DELTA_TIME * DELTA_TIME;
+        let ignore = projectile
+            .shooter
+            .into_iter()
+            .chain(Some(entity))
+            .collect::<Vec<_>>();
+
+        match physics_context.sweep(world, start, step, projectile.radius, &ignore) {
+            Some(hit) => {
+                local_transform.translation = start + step.normalize() * hit.distance;
+                projectile.hit = Some(hit);
+            }
+            None => {
+                local_transform.translation = start + step;
+                projectile.velocity += projectile.gravity * DELTA_TIME;
+                projectile.lifetime -= DELTA_TIME;
+            }
+        }
+
+        if let Some(direction) = projectile.velocity.try_normalize() {
+            local_transform.rotation = Quat::from_rotation_arc(Vec3::NEG_Z, direction);
+        }
+    }
+
+    for (entity, tracer) in world.query::<&mut Tracer>().iter() {
+        tracer.lifetime -= DELTA_TIME;
+        if tracer.lifetime <= 0. {
+            command_buffer.despawn(entity);
+        }
+    }
+
+    command_buffer.run_on(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{Affine3A, Vec4};
+    use hecs::Entity;
+    use rapier3d::prelude::SharedShape;
+
+    use crate::{
+        components::{
+            physics::{PhysicalMaterial, Surface},
+            projectile::add_tracer_to_world,
+            Collider, GlobalTransform,
+        },
+        systems::physics::physics_system_inner,
+    };
+
+    #[test]
+    pub fn test_projectile_hit() {
+        let (mut world, mut physics_context, wall) = setup();
+
+        // The projectile moves much further than the wall is thick each frame, but should hit it anyway.
+        let projectile = world.spawn((
+            Projectile {
+                gravity: Vec3::ZERO,
+                ..Projectile::new(Vec3::new(0., 0., -1000.))
+            },
+            LocalTransform::default(),
+        ));
+        for _ in 0..2 {
+            physics_system_inner(&mut physics_context, &mut world);
+            projectile_system_inner(&mut world, &physics_context);
+        }
+
+        {
+            let projectile = world.get::<&Projectile>(projectile).unwrap();
+            let hit = projectile.hit.unwrap();
+            assert_eq!(hit.entity, wall);
+            assert_eq!(hit.surface, Surface::Metal);
+            assert_relative_eq!(hit.normal, Vec3::Z);
+        }
+        let translation = world
+            .get::<&LocalTransform>(projectile)
+            .unwrap()
+            .translation;
+        assert_relative_eq!(translation, Vec3::new(0., 0., -19.995), epsilon = 0.001);
+
+        // The projectile should be gone once the application has seen the hit.
+        projectile_system_inner(&mut world, &physics_context);
+        assert!(!world.contains(projectile));
+    }
+
+    #[test]
+    pub fn test_projectile_gravity_and_lifetime() {
+        let (mut world, physics_context, _) = setup();
+
+        let projectile = world.spawn((
+            Projectile {
+                gravity: Vec3::new(0., -10., 0.),
+                lifetime: 1. - DELTA_TIME / 2.,
+                ..Projectile::new(Vec3::X)
+            },
+            LocalTransform::default(),
+        ));
+        for _ in 0..72 {
+            projectile_system_inner(&mut world, &physics_context);
+        }
+
+        // After a second, the projectile should have followed a parabola and be facing down its path..
+        let local_transform = *world.get::<&LocalTransform>(projectile).unwrap();
+        assert_relative_eq!(
+            local_transform.translation,
+            Vec3::new(1., -5., 0.),
+            epsilon = 0.001
+        );
+        let facing = local_transform.rotation * Vec3::NEG_Z;
+        assert_relative_eq!(facing, Vec3::new(1., -10., 0.).normalize(), epsilon = 0.001);
+
+        // ..and then run out of time.
+        projectile_system_inner(&mut world, &physics_context);
+        assert!(!world.contains(projectile));
+    }
+
+    #[test]
+    pub fn test_hitscan() {
+        let (mut world, mut physics_context, wall) = setup();
+        let shooter = world.spawn((
+            Collider::new(SharedShape::ball(0.5)),
+            LocalTransform::default(),
+            GlobalTransform::default(),
+        ));
+        physics_system_inner(&mut physics_context, &mut world);
+
+        // The shot starts inside the shooter, but should go straight through them.
+        let hit = physics_context
+            .hitscan(&world, Vec3::ZERO, -Vec3::Z, 100., Some(shooter))
+            .unwrap();
+        assert_eq!(hit.entity, wall);
+        assert_relative_eq!(hit.distance, 19.995, epsilon = 0.001);
+        assert!(physics_context
+            .hitscan(&world, Vec3::ZERO, -Vec3::Z, 10., Some(shooter))
+            .is_none());
+
+        let tracer = add_tracer_to_world(Vec3::ZERO, hit.point, Vec4::ONE, 0.1, &mut world);
+        let global_transform = world.get::<&GlobalTransform>(tracer).unwrap().0;
+        assert_relative_eq!(
+            global_transform.transform_point3(Vec3::new(0., hit.distance / 2., 0.)),
+            hit.point,
+            epsilon = 0.001
+        );
+        for _ in 0..8 {
+            projectile_system_inner(&mut world, &physics_context);
+        }
+        assert!(!world.contains(tracer));
+    }
+
+    fn setup() -> (World, PhysicsContext, Entity) {
+        let mut world = World::new();
+        let mut physics_context = PhysicsContext::default();
+        let wall = world.spawn((
+            Collider::new(SharedShape::cuboid(10., 10., 0.005)),
+            PhysicalMaterial::new(Surface::Metal),
+            LocalTransform {
+                translation: Vec3::new(0., 0., -20.),
+                ..Default::default()
+            },
+            GlobalTransform(Affine3A::from_translation(Vec3::new(0., 0., -20.))),
+        ));
+        physics_system_inner(&mut physics_context, &mut world);
+        (world, physics_context, wall)
+    }
+}
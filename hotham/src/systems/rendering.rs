@@ -1,15 +1,32 @@
+use std::collections::HashMap;
+
 use crate::{
-    components::{skin::NO_SKIN, stage, GlobalTransform, Mesh, Skin, Visible},
+    components::{
+        skin::NO_SKIN, stage, AmbientProbe, CrowdMember, FogVolume, GlobalTransform, Grabbable,
+        Instanced, LensFlare, Mesh, Skin, Sprite, SpriteLayer, Visible,
+    },
     contexts::VulkanContext,
     contexts::{
-        render_context::{Instance, InstancedPrimitive},
+        render_context::{DrawBatch, InstancedPrimitive},
         RenderContext,
     },
-    rendering::resources::{DrawData, PrimitiveCullData},
+    rendering::{
+        accessibility::OutlineInstance,
+        crowd::CrowdRenderer,
+        fog::FogVolumeData,
+        instancing::InstancedDraws,
+        lens_flare::LensFlareData,
+        light::Light,
+        light_probes::LightProbeGrid,
+        mesh_data::MeshData,
+        resources::{DrawData, PrimitiveCullData},
+        sprite::{build_sprite_batches, SpriteBatch, SpriteData, MAX_SPRITES},
+    },
     Engine,
 };
-use glam::Affine3A;
-use hecs::{With, World};
+use glam::{Affine3A, Vec3};
+use hecs::{With, Without, World};
+use id_arena::Arena;
 use openxr as xr;
 
 /// Rendering system
@@ -60,7 +77,6 @@ pub(crate) fn rendering_system_inner(
 /// # Safety
 ///
 /// Must be called at the start of the process or after [`end`]
-#[allow(clippy::type_complexity)]
 pub unsafe fn begin(
     world: &mut World,
     vulkan_context: &VulkanContext,
@@ -68,13 +84,6 @@ pub unsafe fn begin(
     views: &[xr::View],
     swapchain_image_index: usize,
 ) {
-    // First, we need to walk through each entity that contains a mesh, collect its primitives
-    // and create a list of instances, indexed by primitive ID.
-    //
-    // We use primitive.index_buffer_offset as our primitive ID as it is guaranteed to be unique between
-    // primitives.
-    let meshes = &render_context.resources.mesh_data;
-
     // Create transformations to globally oriented stage space
     let global_from_stage = stage::get_global_from_stage(world);
 
@@ -84,67 +93,90 @@ pub unsafe fn begin(
 
     let gos_from_stage: Affine3A = gos_from_global * global_from_stage;
 
-    for (_, (mesh, global_transform, skin)) in
-        world.query_mut::<With<(&Mesh, &GlobalTransform, Option<&Skin>), &Visible>>()
-    {
-        let mesh = meshes.get(mesh.handle).unwrap();
-        let skin_id = skin.map(|s| s.id).unwrap_or(NO_SKIN);
-        for primitive in &mesh.primitives {
-            let key = primitive.index_buffer_offset;
-
-            // Create a transform from this primitive's local space into gos space.
-            let gos_from_local = gos_from_global * global_transform.0;
-
-            render_context
-                .primitive_map
-                .entry(key)
-                .or_insert(InstancedPrimitive {
-                    primitive: primitive.clone(),
-                    instances: Default::default(),
-                })
-                .instances
-                .push(Instance {
-                    gos_from_local,
-                    bounding_sphere: primitive.get_bounding_sphere_in_gos(&gos_from_local),
-                    skin_id,
-                });
-        }
-    }
+    // Effects like shakes move the world content, but never the cameras.
+    let gos_from_global =
+        Affine3A::from_translation(render_context.content_offset) * gos_from_global;
 
-    // Next organize this data into a layout that's easily consumed by the compute shader.
-    // ORDER IS IMPORTANT HERE! The final buffer should look something like:
-    //
-    // primitive_a
-    // primitive_a
-    // primitive_c
-    // primitive_b
-    // primitive_b
-    // primitive_e
-    // primitive_e
-    //
-    // ..etc. The most important thing is that each instances are grouped by their primitive.
-    let frame = &mut render_context.frames[render_context.frame_index];
-    let cull_data = &mut frame.primitive_cull_data_buffer;
-    cull_data.clear();
+    // First, we need to walk through each entity that contains a mesh, collect its primitives
+    // and create a list of instances, indexed by primitive ID.
+    gather_instances(
+        world,
+        &render_context.resources.mesh_data,
+        &gos_from_global,
+        render_context.light_probes.as_ref(),
+        &mut render_context.primitive_map,
+    );
 
-    for instanced_primitive in render_context.primitive_map.values() {
-        let primitive = &instanced_primitive.primitive;
-        for (instance, i) in instanced_primitive.instances.iter().zip(0u32..) {
-            cull_data.push(&PrimitiveCullData {
-                bounding_sphere: instance.bounding_sphere,
-                index_instance: i,
-                primitive_id: primitive.index_buffer_offset,
-                visible: false,
-            });
-        }
-    }
+    // Next organize this data into a layout that's easily consumed by the compute shader, then copy
+    // it into the GPU buffer in one go.
+    let cull_data = &mut render_context.cull_data_scratch;
+    build_cull_data(&render_context.primitive_map, cull_data);
+    let frame = &mut render_context.frames[render_context.frame_index];
+    frame.primitive_cull_data_buffer.overwrite(cull_data);
 
     // This is the VERY LATEST we can possibly update our views, as the compute shader will need them.
     render_context.update_scene_data(views, &gos_from_global, &gos_from_stage);
+    render_context.prepare_picking(&gos_from_global);
 
     // Execute the culling shader on the GPU.
     render_context.cull_objects(vulkan_context);
 
+    // Light any fog volumes, before they're drawn over the world.
+    if render_context.volumetric_fog.is_some() {
+        gather_fog_volumes(
+            world,
+            &gos_from_global,
+            &mut render_context.fog_volume_scratch,
+        );
+        render_context.draw_fog(vulkan_context);
+    }
+
+    // Draw the shadow maps the lights need this frame, before anything is shaded with them.
+    render_context.draw_shadows(vulkan_context, &gos_from_global);
+
+    // Find the lens flares to draw over the world at the end of the render pass.
+    gather_lens_flares(
+        world,
+        &render_context.scene_data.lights,
+        &gos_from_global,
+        &mut render_context.lens_flare_scratch,
+    );
+
+    // ..and the sprites, sorted from the point between the eyes.
+    let camera_position = render_context.scene_data.camera_position;
+    gather_sprites(
+        world,
+        &gos_from_global,
+        ((camera_position[0] + camera_position[1]) * 0.5).truncate(),
+        &mut render_context.sprite_scratch,
+        &mut render_context.sprite_batches,
+    );
+
+    // ..and the interactables to outline, if they're being outlined.
+    if render_context.accessibility.outline_interactables {
+        gather_outlines(
+            world,
+            &render_context.resources.mesh_data,
+            &gos_from_global,
+            &mut render_context.outline_scratch,
+        );
+    }
+
+    // Animate any crowds, which have to be ready before the vertex shader runs.
+    gather_crowd_members(world, &gos_from_global, &mut render_context.crowds);
+    render_context.animate_crowds(vulkan_context);
+
+    // Run the app's own compute passes, which can feed anything drawn after them.
+    render_context.run_compute_passes(vulkan_context);
+
+    // ..and the instanced meshes, which are drawn along with them.
+    gather_instanced_meshes(
+        world,
+        &render_context.resources.mesh_data,
+        &gos_from_global,
+        &mut render_context.instancing.draws,
+    );
+
     // Begin the render pass, bind descriptor sets.
     render_context.begin_pbr_render_pass(vulkan_context, swapchain_image_index);
 }
@@ -157,84 +189,59 @@ pub unsafe fn begin(
 ///
 /// Must be between [`begin`] and [`end`]
 pub unsafe fn draw_world(vulkan_context: &VulkanContext, render_context: &mut RenderContext) {
-    // Parse through the cull buffer and record commands. This is a bit complex.
     let device = &vulkan_context.device;
     let frame = &mut render_context.frames[render_context.frame_index];
     let command_buffer = frame.command_buffer;
-    let draw_data_buffer = &mut frame.draw_data_buffer;
-    draw_data_buffer.clear();
-
-    let mut instance_offset = 0;
-    let mut current_primitive_id = u32::MAX;
-    let mut instance_count = 0;
-    let cull_data = frame.primitive_cull_data_buffer.as_slice();
-
-    for cull_result in cull_data {
-        // If we haven't yet set our primitive ID, set it now.
-        if current_primitive_id == u32::MAX {
-            current_primitive_id = cull_result.primitive_id;
-        }
-
-        // We're finished with this primitive. Record the command and increase our offset.
-        if cull_result.primitive_id != current_primitive_id {
-            // Don't record commands for primitives which have no instances, eg. have been culled.
-            if instance_count > 0 {
-                let primitive = &render_context
-                    .primitive_map
-                    .get(&current_primitive_id)
-                    .unwrap()
-                    .primitive;
-                device.cmd_draw_indexed(
-                    command_buffer,
-                    primitive.indices_count,
-                    instance_count,
-                    primitive.index_buffer_offset,
-                    primitive.vertex_buffer_offset as _,
-                    instance_offset,
-                );
-            }
-
-            current_primitive_id = cull_result.primitive_id;
-            instance_offset += instance_count;
-            instance_count = 0;
-        }
 
-        // If this primitive is visible, increase the instance count and record its draw data.
-        if cull_result.visible {
-            let instanced_primitive = render_context
-                .primitive_map
-                .get(&cull_result.primitive_id)
-                .unwrap();
-            let instance = &instanced_primitive.instances[cull_result.index_instance as usize];
-            let draw_data = DrawData {
-                gos_from_local: instance.gos_from_local.into(),
-                local_from_gos: instance.gos_from_local.inverse().into(),
-                material_id: instanced_primitive.primitive.material_id,
-                skin_id: instance.skin_id,
-            };
-            draw_data_buffer.push(&draw_data);
-            instance_count += 1;
-        }
-    }
+    // Parse through the cull results and build up the draw data for each visible instance, then copy it
+    // into the GPU buffer in one go.
+    build_draw_data(
+        &render_context.primitive_map,
+        frame.primitive_cull_data_buffer.as_slice(),
+        &mut render_context.draw_data_scratch,
+        &mut render_context.draw_batches,
+    );
+    frame
+        .draw_data_buffer
+        .overwrite(&render_context.draw_data_scratch);
 
-    // Finally, record the last primitive. This is counterintuitive at first glance, but the loop above only
-    // records a command when the primitive has changed. If we don't do this, the last primitive will never
-    // be drawn.
-    if instance_count > 0 {
+    // Now record one instanced draw command per batch.
+    for batch in &render_context.draw_batches {
         let primitive = &render_context
             .primitive_map
-            .get(&current_primitive_id)
+            .get(&batch.primitive_id)
             .unwrap()
             .primitive;
         device.cmd_draw_indexed(
             command_buffer,
             primitive.indices_count,
-            instance_count,
+            batch.instance_count,
             primitive.index_buffer_offset,
             primitive.vertex_buffer_offset as _,
-            instance_offset,
+            batch.instance_offset,
         );
     }
+
+    // Draw the crowds and instanced meshes, then put the PBR pipeline back for anything drawn after the world.
+    let shared_sets = [
+        render_context.descriptors.sets[render_context.frame_index],
+        render_context.descriptors.fog_set,
+    ];
+    let drew_crowds = render_context.crowds.draw(
+        device,
+        command_buffer,
+        render_context.frame_index,
+        shared_sets,
+    );
+    let drew_instances = render_context.instancing.draw(
+        device,
+        command_buffer,
+        render_context.frame_index,
+        shared_sets,
+    );
+    if drew_crowds || drew_instances {
+        render_context.bind_pbr_pipeline(device, command_buffer);
+    }
 }
 
 /// Finish drawing
@@ -243,11 +250,256 @@ pub unsafe fn draw_world(vulkan_context: &VulkanContext, render_context: &mut Re
 ///
 /// Must be called after `begin`
 pub fn end(vulkan_context: &VulkanContext, render_context: &mut RenderContext) {
-    // OK. We're all done!
-    render_context.primitive_map.clear();
+    // OK. We're all done! Clear out the instances, but keep their allocations around for the next frame.
+    for instanced_primitive in render_context.primitive_map.values_mut() {
+        instanced_primitive.clear();
+    }
     render_context.end_pbr_render_pass(vulkan_context);
 }
 
+/// Walk through each visible entity with a [`Mesh`] and add an instance of each of its primitives to
+/// `primitive_map`, keyed by primitive ID. Entities with an [`AmbientProbe`] are lit by `light_probes`, if given.
+/// [`Instanced`] entities are left out, as they're drawn by [`gather_instanced_meshes`] instead.
+///
+/// We use primitive.index_buffer_offset as our primitive ID as it is guaranteed to be unique between
+/// primitives.
+#[allow(clippy::type_complexity)]
+pub fn gather_instances(
+    world: &mut World,
+    meshes: &Arena<MeshData>,
+    gos_from_global: &Affine3A,
+    light_probes: Option<&LightProbeGrid>,
+    primitive_map: &mut HashMap<u32, InstancedPrimitive>,
+) {
+    for (entity, (mesh, global_transform, skin, ambient_probe)) in world.query_mut::<Without<
+        With<
+            (
+                &Mesh,
+                &GlobalTransform,
+                Option<&Skin>,
+                Option<&AmbientProbe>,
+            ),
+            &Visible,
+        >,
+        &Instanced,
+    >>() {
+        let mesh = meshes.get(mesh.handle).unwrap();
+        let skin_id = skin.map(|s| s.id).unwrap_or(NO_SKIN);
+
+        // Blend the level's light probes at the entity's position, if it wants them.
+        let ambient_probe = light_probes
+            .filter(|_| ambient_probe.is_some())
+            .map(|grid| grid.sample(global_transform.0.translation.into()));
+
+        // Create a transform from this mesh's local space into gos space. This is shared by all its primitives.
+        let gos_from_local = *gos_from_global * global_transform.0;
+
+        for primitive in &mesh.primitives {
+            primitive_map
+                .entry(primitive.index_buffer_offset)
+                .or_insert_with(|| InstancedPrimitive::new(primitive.clone()))
+                .push_entity_instance(entity.id(), gos_from_local, skin_id, ambient_probe.as_ref());
+        }
+    }
+
+    // Forget primitives that weren't drawn this frame, eg. because their meshes were despawned or unloaded, so they
+    // don't pile up and get walked through every frame.
+    primitive_map.retain(|_, instanced_primitive| !instanced_primitive.gos_from_local.is_empty());
+}
+
+/// Walk through each visible [`Instanced`] entity and add a copy of its [`Mesh`] at each of its transforms to `draws`,
+/// in globally oriented stage space.
+pub fn gather_instanced_meshes(
+    world: &mut World,
+    meshes: &Arena<MeshData>,
+    gos_from_global: &Affine3A,
+    draws: &mut InstancedDraws,
+) {
+    draws.clear();
+    for (_, (instanced, mesh, global_transform)) in
+        world.query_mut::<With<(&Instanced, &Mesh, &GlobalTransform), &Visible>>()
+    {
+        let mesh = meshes.get(mesh.handle).unwrap();
+        let gos_from_local = *gos_from_global * global_transform.0;
+        draws.push(&mesh.primitives, &gos_from_local, &instanced.transforms);
+    }
+}
+
+/// Walk through each visible [`Grabbable`] and add its primitives to `outlines`, in globally oriented stage space.
+pub(crate) fn gather_outlines(
+    world: &mut World,
+    meshes: &Arena<MeshData>,
+    gos_from_global: &Affine3A,
+    outlines: &mut Vec<OutlineInstance>,
+) {
+    outlines.clear();
+    for (_, (mesh, global_transform, skin)) in
+        world.query_mut::<With<(&Mesh, &GlobalTransform, Option<&Skin>), (&Visible, &Grabbable)>>()
+    {
+        let mesh = meshes.get(mesh.handle).unwrap();
+        let gos_from_local = (*gos_from_global * global_transform.0).into();
+        let skin_id = skin.map(|s| s.id).unwrap_or(NO_SKIN);
+        for primitive in &mesh.primitives {
+            outlines.push(OutlineInstance {
+                gos_from_local,
+                skin_id,
+                index_buffer_offset: primitive.index_buffer_offset,
+                indices_count: primitive.indices_count,
+                vertex_buffer_offset: primitive.vertex_buffer_offset,
+            });
+        }
+    }
+}
+
+/// Walk through each visible [`CrowdMember`] and add it to its crowd in `crowds`, in globally oriented stage space.
+pub fn gather_crowd_members(
+    world: &mut World,
+    gos_from_global: &Affine3A,
+    crowds: &mut CrowdRenderer,
+) {
+    crowds.clear_members();
+    for (_, (member, global_transform)) in
+        world.query_mut::<With<(&CrowdMember, &GlobalTransform), &Visible>>()
+    {
+        let gos_from_local = *gos_from_global * global_transform.0;
+        crowds.push_member(member, &gos_from_local);
+    }
+}
+
+/// Walk through each [`FogVolume`] and add it to `fog_volumes`, in globally oriented stage space.
+pub fn gather_fog_volumes(
+    world: &mut World,
+    gos_from_global: &Affine3A,
+    fog_volumes: &mut Vec<FogVolumeData>,
+) {
+    fog_volumes.clear();
+    for (_, (fog_volume, global_transform)) in world.query_mut::<(&FogVolume, &GlobalTransform)>() {
+        let gos_from_local = *gos_from_global * global_transform.0;
+        fog_volumes.push(FogVolumeData::new(fog_volume, &gos_from_local));
+    }
+}
+
+/// Walk through each visible [`LensFlare`] and add it to `lens_flares`, in globally oriented stage space.
+pub fn gather_lens_flares(
+    world: &mut World,
+    lights: &[Light],
+    gos_from_global: &Affine3A,
+    lens_flares: &mut Vec<LensFlareData>,
+) {
+    lens_flares.clear();
+    for (_, (lens_flare, _)) in world.query_mut::<(&LensFlare, &Visible)>() {
+        if let Some(light) = lights.get(lens_flare.light_index) {
+            lens_flares.extend(LensFlareData::new(lens_flare, light, gos_from_global));
+        }
+    }
+}
+
+/// Walk through each visible [`Sprite`] and add it to `sprites`, in globally oriented stage space, then split them into
+/// `batches`. Each layer's sprites are sorted from back to front as seen from `viewer`, so they blend properly.
+pub fn gather_sprites(
+    world: &mut World,
+    gos_from_global: &Affine3A,
+    viewer: Vec3,
+    sprites: &mut Vec<SpriteData>,
+    batches: &mut Vec<SpriteBatch>,
+) {
+    sprites.clear();
+    batches.clear();
+    for layer in [SpriteLayer::World, SpriteLayer::HeadLocked] {
+        let first_instance = sprites.len();
+        for (_, (sprite, global_transform)) in
+            world.query_mut::<With<(&Sprite, &GlobalTransform), &Visible>>()
+        {
+            if sprite.layer == layer && sprites.len() < MAX_SPRITES {
+                let gos_from_local = *gos_from_global * global_transform.0;
+                sprites.push(SpriteData::new(sprite, &gos_from_local));
+            }
+        }
+
+        let layer_sprites = &mut sprites[first_instance..];
+        layer_sprites.sort_by(|a, b| b.distance(viewer).total_cmp(&a.distance(viewer)));
+        build_sprite_batches(layer, first_instance, layer_sprites, batches);
+    }
+}
+
+/// Lay out the instances in `primitive_map` for the culling shader.
+///
+/// ORDER IS IMPORTANT HERE! The final buffer should look something like:
+///
+/// ```text
+/// primitive_a
+/// primitive_a
+/// primitive_c
+/// primitive_b
+/// primitive_b
+/// primitive_e
+/// primitive_e
+/// ```
+///
+/// ..etc. The most important thing is that each instances are grouped by their primitive.
+pub fn build_cull_data(
+    primitive_map: &HashMap<u32, InstancedPrimitive>,
+    cull_data: &mut Vec<PrimitiveCullData>,
+) {
+    cull_data.clear();
+
+    for instanced_primitive in primitive_map.values() {
+        let primitive_id = instanced_primitive.primitive.index_buffer_offset;
+        cull_data.extend(instanced_primitive.bounding_spheres.iter().zip(0u32..).map(
+            |(bounding_sphere, index_instance)| PrimitiveCullData {
+                bounding_sphere: *bounding_sphere,
+                index_instance,
+                primitive_id,
+                visible: false,
+            },
+        ));
+    }
+}
+
+/// Build the [`DrawData`] for every visible instance in `cull_results`, and the [`DrawBatch`]es
+/// required to draw them.
+///
+/// Relies on `cull_results` being grouped by primitive, as produced by [`build_cull_data`].
+pub fn build_draw_data(
+    primitive_map: &HashMap<u32, InstancedPrimitive>,
+    cull_results: &[PrimitiveCullData],
+    draw_data: &mut Vec<DrawData>,
+    draw_batches: &mut Vec<DrawBatch>,
+) {
+    draw_data.clear();
+    draw_batches.clear();
+
+    let mut remaining = cull_results;
+    while let Some(first) = remaining.first() {
+        // Find the run of results belonging to this primitive.
+        let primitive_id = first.primitive_id;
+        let run_length = remaining
+            .iter()
+            .position(|r| r.primitive_id != primitive_id)
+            .unwrap_or(remaining.len());
+        let (run, rest) = remaining.split_at(run_length);
+        remaining = rest;
+
+        let instanced_primitive = primitive_map.get(&primitive_id).unwrap();
+        let instance_offset = draw_data.len() as u32;
+        draw_data.extend(
+            run.iter()
+                .filter(|r| r.visible)
+                .map(|r| instanced_primitive.draw_data(r.index_instance as usize)),
+        );
+        let instance_count = draw_data.len() as u32 - instance_offset;
+
+        // Don't record batches for primitives which have no instances, eg. have been culled.
+        if instance_count > 0 {
+            draw_batches.push(DrawBatch {
+                primitive_id,
+                instance_offset,
+                instance_count,
+            });
+        }
+    }
+}
+
 #[cfg(target_os = "windows")]
 #[cfg(test)]
 mod tests {
@@ -258,7 +510,7 @@ mod tests {
         asset_importer,
         components::{stage::Stage, LocalTransform},
         contexts::RenderContext,
-        rendering::{image::Image, light::Light, scene_data},
+        rendering::{image::Image, light::Light, primitive::Primitive, scene_data},
         systems::{
             update_global_transform::update_global_transform_system_inner,
             update_global_transform_with_parent::update_global_transform_with_parent_system_inner,
@@ -267,6 +519,70 @@ mod tests {
     };
     use glam::{Quat, Vec3};
 
+    #[test]
+    pub fn test_gather_instances() {
+        let mut meshes = Arena::new();
+        let primitive = |index_buffer_offset| Primitive {
+            index_buffer_offset,
+            ..Default::default()
+        };
+        let crab = meshes.alloc(MeshData::new(vec![primitive(0)]));
+        let rock = meshes.alloc(MeshData::new(vec![primitive(3)]));
+        let mut world = World::new();
+        world.spawn((
+            Mesh { handle: crab },
+            GlobalTransform::default(),
+            Visible {},
+        ));
+        let rock_entity = world.spawn((
+            Mesh { handle: rock },
+            GlobalTransform::default(),
+            Visible {},
+        ));
+        let mut primitive_map = HashMap::default();
+
+        gather_instances(
+            &mut world,
+            &meshes,
+            &Affine3A::IDENTITY,
+            None,
+            &mut primitive_map,
+        );
+        assert_eq!(primitive_map.len(), 2);
+
+        // Primitives that aren't drawn any more are forgotten.
+        primitive_map
+            .values_mut()
+            .for_each(InstancedPrimitive::clear);
+        world.despawn(rock_entity).unwrap();
+        gather_instances(
+            &mut world,
+            &meshes,
+            &Affine3A::IDENTITY,
+            None,
+            &mut primitive_map,
+        );
+        assert_eq!(primitive_map.len(), 1);
+        assert_eq!(primitive_map[&0].gos_from_local.len(), 1);
+    }
+
+    #[test]
+    pub fn test_gather_lens_flares() {
+        let mut world = World::new();
+        let lights = [Light::new_point(Vec3::ZERO, 10., 1., Vec3::ONE)];
+        let lens_flare = LensFlare {
+            visibility: 1.,
+            ..LensFlare::new(0)
+        };
+        world.spawn((lens_flare, Visible {}));
+        // Flares on entities that aren't visible, eg. because they've been hidden, aren't drawn.
+        world.spawn((lens_flare,));
+        let mut lens_flares = Vec::new();
+
+        gather_lens_flares(&mut world, &lights, &Affine3A::IDENTITY, &mut lens_flares);
+        assert_eq!(lens_flares.len(), 1);
+    }
+
     #[test]
     pub fn test_rendering_normal_tangent() {
         let (mut render_context, vulkan_context, image) = RenderContext::testing_with_image();
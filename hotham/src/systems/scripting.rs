@@ -0,0 +1,27 @@
+use hecs::World;
+
+use crate::{
+    contexts::{physics_context::DELTA_TIME, InputContext, ScriptContext},
+    scripting::buttons_pressed,
+    Engine,
+};
+
+/// Scripting system
+/// Reloads any scripts that have changed on disk, then runs every script attached to an entity
+pub fn scripting_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    let script_context = &mut engine.script_context;
+    let input_context = &engine.input_context;
+
+    scripting_system_inner(world, script_context, input_context);
+}
+
+pub fn scripting_system_inner(
+    world: &mut World,
+    script_context: &mut ScriptContext,
+    input_context: &InputContext,
+) {
+    script_context.reload_changed_files();
+    let buttons_pressed = buttons_pressed(input_context);
+    script_context.update(world, &buttons_pressed, DELTA_TIME);
+}
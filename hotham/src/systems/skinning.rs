@@ -1,3 +1,4 @@
+use glam::Mat4;
 use hecs::World;
 use render_context::RenderContext;
 
@@ -19,18 +20,28 @@ fn skinning_system_inner(world: &mut World, render_context: &mut RenderContext)
     for (_, (skin, global_transform)) in world.query::<(&Skin, &GlobalTransform)>().iter() {
         let buffer = unsafe { render_context.resources.skins_buffer.as_slice_mut() };
         let joint_matrices = &mut buffer[skin.id as usize];
-        let local_from_global = global_transform.0.inverse();
-
-        for (n, (joint, joint_from_mesh)) in skin
-            .joints
-            .iter()
-            .zip(skin.inverse_bind_matrices.iter())
-            .enumerate()
-        {
-            let global_from_joint = world.get::<&GlobalTransform>(*joint).unwrap().0;
-            let local_from_mesh = local_from_global * global_from_joint * *joint_from_mesh;
-            joint_matrices[n] = local_from_mesh.into();
-        }
+        update_joint_matrices(world, skin, global_transform, joint_matrices);
+    }
+}
+
+/// Build the joint matrices for a single skin, writing them into `joint_matrices`.
+pub fn update_joint_matrices(
+    world: &World,
+    skin: &Skin,
+    global_transform: &GlobalTransform,
+    joint_matrices: &mut [Mat4],
+) {
+    let local_from_global = global_transform.0.inverse();
+
+    for (n, (joint, joint_from_mesh)) in skin
+        .joints
+        .iter()
+        .zip(skin.inverse_bind_matrices.iter())
+        .enumerate()
+    {
+        let global_from_joint = world.get::<&GlobalTransform>(*joint).unwrap().0;
+        let local_from_mesh = local_from_global * global_from_joint * *joint_from_mesh;
+        joint_matrices[n] = local_from_mesh.into();
     }
 }
 
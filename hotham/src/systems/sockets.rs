@@ -0,0 +1,270 @@
+use hecs::World;
+use rapier3d::parry::query::PointQuery;
+
+use crate::{
+    components::{
+        physics::BodyType,
+        socket::{SocketEvent, SocketTag},
+        GlobalTransform, Grabbable, Hand, LocalTransform, Parent, RigidBody, Socket,
+    },
+    util::{isometry_from_affine, na_vector_from_glam},
+    Engine,
+};
+
+/// Sockets system
+/// Snaps released `Grabbable`s into any `Socket` they are inside, and removes them again when they're grabbed.
+/// Welded objects are parented to their socket rather than held with a physics joint.
+/// Should be run after `grabbing_system`.
+pub fn sockets_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    sockets_system_inner(world);
+}
+
+pub fn sockets_system_inner(world: &mut World) {
+    // Find out what's being held.
+    let held_entities = world
+        .query::<&Hand>()
+        .iter()
+        .filter_map(|(_, hand)| hand.grabbed_entity)
+        .collect::<Vec<_>>();
+
+    // ..and what's already in a socket.
+    let mut occupants = world
+        .query::<&Socket>()
+        .iter()
+        .filter_map(|(_, socket)| socket.occupant)
+        .collect::<Vec<_>>();
+
+    let mut attached = Vec::new();
+    let mut detached = Vec::new();
+
+    for (socket_entity, (socket, socket_transform)) in
+        world.query::<(&mut Socket, &GlobalTransform)>().iter()
+    {
+        socket.events_this_frame.clear();
+        socket.hovering = None;
+
+        // If the occupant has been grabbed or despawned, it's no longer in the socket.
+        if let Some(occupant) = socket.occupant {
+            if held_entities.contains(&occupant) || !world.contains(occupant) {
+                socket.occupant = None;
+                socket
+                    .events_this_frame
+                    .push(SocketEvent::Detached(occupant));
+                detached.push((occupant, socket.weld));
+            }
+        }
+
+        if socket.occupant.is_some() {
+            continue;
+        }
+
+        // Look for a compatible object inside the socket's zone.
+        let socket_position = isometry_from_affine(&socket_transform.0);
+        for (entity, (global_transform, tag)) in world
+            .query::<(&GlobalTransform, Option<&SocketTag>)>()
+            .with::<&Grabbable>()
+            .iter()
+        {
+            if !socket.accepts(tag) || occupants.contains(&entity) {
+                continue;
+            }
+
+            let (_, _, translation) = global_transform.to_scale_rotation_translation();
+            if !socket
+                .shape
+                .contains_point(&socket_position, &na_vector_from_glam(translation).into())
+            {
+                continue;
+            }
+
+            // Held objects are only hovering - they'll be snapped in when they're let go.
+            if held_entities.contains(&entity) {
+                socket.hovering = Some(entity);
+                continue;
+            }
+
+            socket.occupant = Some(entity);
+            socket.events_this_frame.push(SocketEvent::Attached(entity));
+            occupants.push(entity);
+            attached.push((
+                entity,
+                socket_entity,
+                socket_transform.0 * socket.socket_from_snapped,
+                socket.socket_from_snapped,
+                socket.weld,
+            ));
+            break;
+        }
+    }
+
+    for (entity, welded) in detached {
+        if !world.contains(entity) {
+            continue;
+        }
+
+        // Leave the object where it is now, but no longer attached to the socket.
+        if welded {
+            let _ = world.remove_one::<Parent>(entity);
+            if let Ok(global_transform) = world.get::<&GlobalTransform>(entity).map(|g| g.0) {
+                if let Ok(mut local_transform) = world.get::<&mut LocalTransform>(entity) {
+                    local_transform.update_rotation_translation_from_affine(&global_transform);
+                }
+            }
+        }
+    }
+
+    for (entity, socket_entity, global_from_snapped, socket_from_snapped, weld) in attached {
+        // Make sure the physics simulation doesn't move the object out of the socket.
+        if let Ok(mut rigid_body) = world.get::<&mut RigidBody>(entity) {
+            rigid_body.body_type = BodyType::KinematicPositionBased;
+        }
+
+        let local_from_snapped = if weld {
+            socket_from_snapped
+        } else {
+            global_from_snapped
+        };
+
+        if let Ok(mut local_transform) = world.get::<&mut LocalTransform>(entity) {
+            local_transform.update_rotation_translation_from_affine(&local_from_snapped);
+        }
+        if let Ok(mut global_transform) = world.get::<&mut GlobalTransform>(entity) {
+            global_transform.0 = global_from_snapped;
+        }
+        if weld {
+            world.insert_one(entity, Parent(socket_entity)).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{Affine3A, Vec3};
+    use hecs::Entity;
+    use rapier3d::prelude::SharedShape;
+
+    #[test]
+    pub fn test_snap_on_release() {
+        let mut world = World::new();
+        let socket_entity = add_socket(&mut world, None, false);
+        let object = add_object(&mut world, [0.1, 1.0, -1.0].into(), None);
+        let hand = world.spawn((Hand {
+            grabbed_entity: Some(object),
+            ..Hand::left()
+        },));
+
+        // While held, the object should only hover.
+        tick(&mut world);
+        {
+            let socket = world.get::<&Socket>(socket_entity).unwrap();
+            assert_eq!(socket.hovering, Some(object));
+            assert_eq!(socket.occupant, None);
+        }
+
+        // Once released, it should snap into place.
+        world.get::<&mut Hand>(hand).unwrap().grabbed_entity = None;
+        tick(&mut world);
+        {
+            let socket = world.get::<&Socket>(socket_entity).unwrap();
+            assert_eq!(socket.occupant, Some(object));
+            assert_eq!(
+                socket.events_this_frame,
+                vec![SocketEvent::Attached(object)]
+            );
+        }
+        let local_transform = world.get::<&LocalTransform>(object).unwrap();
+        assert_relative_eq!(local_transform.translation, Vec3::new(0., 1., -1.));
+        let rigid_body = world.get::<&RigidBody>(object).unwrap();
+        assert_eq!(rigid_body.body_type, BodyType::KinematicPositionBased);
+    }
+
+    #[test]
+    pub fn test_weld_and_detach() {
+        let mut world = World::new();
+        let socket_entity = add_socket(&mut world, None, true);
+        let object = add_object(&mut world, [0.1, 1.0, -1.0].into(), None);
+
+        tick(&mut world);
+        assert_eq!(
+            *world.get::<&Parent>(object).unwrap(),
+            Parent(socket_entity)
+        );
+        assert_relative_eq!(
+            world.get::<&LocalTransform>(object).unwrap().translation,
+            Vec3::ZERO
+        );
+
+        // Grabbing the object should remove it from the socket.
+        world.spawn((Hand {
+            grabbed_entity: Some(object),
+            ..Hand::left()
+        },));
+        tick(&mut world);
+        let socket = world.get::<&Socket>(socket_entity).unwrap();
+        assert_eq!(socket.occupant, None);
+        assert_eq!(
+            socket.events_this_frame,
+            vec![SocketEvent::Detached(object)]
+        );
+        assert!(world.get::<&Parent>(object).is_err());
+        assert_relative_eq!(
+            world.get::<&LocalTransform>(object).unwrap().translation,
+            Vec3::new(0., 1., -1.)
+        );
+    }
+
+    #[test]
+    pub fn test_filter_tag() {
+        let mut world = World::new();
+        let socket_entity = add_socket(&mut world, Some("key"), false);
+        add_object(&mut world, [0.1, 1.0, -1.0].into(), Some("sword"));
+
+        tick(&mut world);
+        assert_eq!(world.get::<&Socket>(socket_entity).unwrap().occupant, None);
+
+        let key = add_object(&mut world, [0.1, 1.0, -1.0].into(), Some("key"));
+        tick(&mut world);
+        assert_eq!(
+            world.get::<&Socket>(socket_entity).unwrap().occupant,
+            Some(key)
+        );
+    }
+
+    fn add_socket(world: &mut World, filter_tag: Option<&str>, weld: bool) -> Entity {
+        let socket = Socket {
+            filter_tag: filter_tag.map(|t| t.to_string()),
+            weld,
+            ..Socket::new(SharedShape::ball(0.2))
+        };
+        world.spawn((
+            socket,
+            GlobalTransform(Affine3A::from_translation([0., 1., -1.].into())),
+        ))
+    }
+
+    fn add_object(world: &mut World, translation: Vec3, tag: Option<&str>) -> Entity {
+        let local_transform = LocalTransform {
+            translation,
+            ..Default::default()
+        };
+        let entity = world.spawn((
+            Grabbable {},
+            RigidBody::default(),
+            local_transform,
+            GlobalTransform::from(local_transform),
+        ));
+        if let Some(tag) = tag {
+            world
+                .insert_one(entity, SocketTag(tag.to_string()))
+                .unwrap();
+        }
+        entity
+    }
+
+    fn tick(world: &mut World) {
+        sockets_system_inner(world);
+    }
+}
@@ -0,0 +1,45 @@
+use glam::Affine3A;
+use hecs::World;
+
+use crate::{
+    contexts::{physics_context::DELTA_TIME, RenderContext, VulkanContext},
+    rendering::spectator::SpectatorView,
+    Engine,
+};
+
+/// Spectator system
+/// Walks the spectator camera, if there is one, after the player's head and renders what it sees.
+///
+/// Call this after the `rendering_system`, but before `Engine::finish`.
+pub fn spectator_system(engine: &mut Engine) {
+    let spectator_view = match &mut engine.spectator_view {
+        Some(spectator_view) => spectator_view,
+        None => return,
+    };
+
+    spectator_system_inner(
+        &mut engine.world,
+        &engine.vulkan_context,
+        &mut engine.render_context,
+        engine.input_context.hmd.hmd_in_stage(),
+        spectator_view,
+    );
+}
+
+pub fn spectator_system_inner(
+    world: &mut World,
+    vulkan_context: &VulkanContext,
+    render_context: &mut RenderContext,
+    hmd_in_stage: Affine3A,
+    spectator_view: &mut SpectatorView,
+) {
+    unsafe {
+        spectator_view.render(
+            world,
+            vulkan_context,
+            render_context,
+            hmd_in_stage,
+            DELTA_TIME,
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use glam::{Affine3A, Quat, Vec3};
+use hecs::{Entity, World};
+
+use crate::{
+    components::{
+        spring_bone::SpringBoneTail, GlobalTransform, LocalTransform, Parent, SpringBone,
+    },
+    contexts::physics_context::DELTA_TIME,
+    Engine,
+};
+
+/// Spring bone system
+/// Swings each [`SpringBone`] chain towards where its momentum, stiffness and gravity take it, then updates the
+/// global transforms of the chain and everything attached to it. Should be run after `animation_system` and
+/// `update_global_transform_with_parent_system`, but before `skinning_system`.
+pub fn spring_bone_system(engine: &mut Engine) {
+    let world = &mut engine.world;
+    spring_bone_system_inner(world);
+}
+
+pub fn spring_bone_system_inner(world: &mut World) {
+    let mut hierarchy: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for (entity, parent) in world.query_mut::<&Parent>() {
+        hierarchy.entry(parent.0).or_default().push(entity);
+    }
+
+    for (_, spring_bone) in world.query::<&mut SpringBone>().iter() {
+        simulate_chain(world, spring_bone, &hierarchy);
+    }
+}
+
+fn simulate_chain(
+    world: &World,
+    spring_bone: &mut SpringBone,
+    hierarchy: &HashMap<Entity, Vec<Entity>>,
+) {
+    if spring_bone.joints.len() < 2 {
+        return;
+    }
+
+    // The chain has changed since it was last simulated, so start again from its pose.
+    if spring_bone.tails.len() >= spring_bone.joints.len() {
+        spring_bone.tails.clear();
+    }
+
+    let spheres = spring_bone
+        .colliders
+        .iter()
+        .filter_map(|collider| {
+            let global_from_collider = world.get::<&GlobalTransform>(collider.entity).ok()?.0;
+            Some((
+                global_from_collider.transform_point3(collider.offset),
+                collider.radius + spring_bone.hit_radius,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let gravity = spring_bone.gravity_dir.normalize_or_zero() * spring_bone.gravity_power;
+
+    let mut global_from_parent = world
+        .get::<&Parent>(spring_bone.joints[0])
+        .ok()
+        .and_then(|parent| world.get::<&GlobalTransform>(parent.0).ok().map(|g| g.0))
+        .unwrap_or(Affine3A::IDENTITY);
+
+    for (i, pair) in spring_bone.joints.windows(2).enumerate() {
+        let (joint, child) = (pair[0], pair[1]);
+        let (parent_from_joint, joint_from_tail) = match (
+            world.get::<&LocalTransform>(joint),
+            world.get::<&LocalTransform>(child),
+        ) {
+            (Ok(joint), Ok(child)) => (joint.to_affine(), child.translation),
+            _ => return,
+        };
+
+        // Where the joint would be without the spring, eg. from animation.
+        let global_from_joint = global_from_parent * parent_from_joint;
+        let head = Vec3::from(global_from_joint.translation);
+        let rest_tail = global_from_joint.transform_vector3(joint_from_tail);
+        let length = rest_tail.length();
+        let rest_direction = rest_tail.normalize_or_zero();
+
+        if spring_bone.tails.len() == i {
+            spring_bone.tails.push(SpringBoneTail {
+                current: head + rest_tail,
+                previous: head + rest_tail,
+            });
+        }
+        let tail = &mut spring_bone.tails[i];
+
+        // Verlet integration, as VRM does it: keep some of the last frame's velocity, then pull the tail back towards
+        // its pose and down with gravity.
+        let velocity = (tail.current - tail.previous) * (1. - spring_bone.damping);
+        let next = tail.current
+            + velocity
+            + rest_direction * spring_bone.stiffness * DELTA_TIME
+            + gravity * DELTA_TIME;
+        let mut next = constrain_length(head, next, length, rest_direction);
+
+        for (centre, radius) in &spheres {
+            let offset = next - *centre;
+            let distance = offset.length();
+            if distance < *radius && distance > 0. {
+                next = constrain_length(
+                    head,
+                    *centre + offset * (*radius / distance),
+                    length,
+                    rest_direction,
+                );
+            }
+        }
+
+        tail.previous = tail.current;
+        tail.current = next;
+
+        // Swing the joint around its head, so it points at its new tail.
+        let swing = Quat::from_rotation_arc(rest_direction, (next - head).normalize_or_zero());
+        let simulated = if swing.is_finite() {
+            Affine3A::from_translation(head)
+                * Affine3A::from_quat(swing)
+                * Affine3A::from_translation(-head)
+                * global_from_joint
+        } else {
+            global_from_joint
+        };
+        world.get::<&mut GlobalTransform>(joint).unwrap().0 = simulated;
+
+        // Anything else attached to the joint has to move with it.
+        for attached in hierarchy.get(&joint).into_iter().flatten() {
+            if *attached != child {
+                update_global_transforms_recursively(&simulated, *attached, hierarchy, world);
+            }
+        }
+
+        global_from_parent = simulated;
+    }
+
+    let tip = *spring_bone.joints.last().unwrap();
+    update_global_transforms_recursively(&global_from_parent, tip, hierarchy, world);
+}
+
+/// Move `tail` so it's `length` away from `head`, keeping its direction.
+fn constrain_length(head: Vec3, tail: Vec3, length: f32, fallback_direction: Vec3) -> Vec3 {
+    let direction = (tail - head).try_normalize().unwrap_or(fallback_direction);
+    head + direction * length
+}
+
+fn update_global_transforms_recursively(
+    global_from_parent: &Affine3A,
+    entity: Entity,
+    hierarchy: &HashMap<Entity, Vec<Entity>>,
+    world: &World,
+) {
+    let global_from_entity = match world.get::<&LocalTransform>(entity) {
+        Ok(local_transform) => *global_from_parent * local_transform.to_affine(),
+        Err(_) => return,
+    };
+    if let Ok(mut global_transform) = world.get::<&mut GlobalTransform>(entity) {
+        global_transform.0 = global_from_entity;
+    }
+
+    for child in hierarchy.get(&entity).into_iter().flatten() {
+        update_global_transforms_recursively(&global_from_entity, *child, hierarchy, world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::spring_bone::SpringBoneCollider;
+    use approx::assert_relative_eq;
+
+    #[test]
+    pub fn test_spring_bone_gravity() {
+        let mut world = World::new();
+        let (root, tip, hat) = spawn_chain(&mut world);
+        world
+            .insert_one(
+                root,
+                SpringBone {
+                    stiffness: 0.,
+                    gravity_power: 1.,
+                    ..SpringBone::new(vec![root, tip])
+                },
+            )
+            .unwrap();
+
+        // The chain starts out sticking straight out along X, and should swing down and settle under gravity..
+        for _ in 0..1000 {
+            spring_bone_system_inner(&mut world);
+        }
+        let tip_position = global_position(&world, tip);
+        assert_relative_eq!(tip_position, Vec3::new(0., -1., 0.), epsilon = 0.01);
+
+        // ..and anything attached to the tip should come with it.
+        let hat_position = global_position(&world, hat);
+        assert_relative_eq!(hat_position, Vec3::new(0., -2., 0.), epsilon = 0.02);
+
+        // The chain should never stretch.
+        let tails = &world.get::<&SpringBone>(root).unwrap().tails;
+        assert_relative_eq!(tails[0].current.length(), 1., epsilon = 0.0001);
+    }
+
+    #[test]
+    pub fn test_spring_bone_stiffness() {
+        let mut world = World::new();
+        let (root, tip, _) = spawn_chain(&mut world);
+        world
+            .insert_one(
+                root,
+                SpringBone {
+                    stiffness: 4.,
+                    gravity_power: 1.,
+                    ..SpringBone::new(vec![root, tip])
+                },
+            )
+            .unwrap();
+
+        // Stiffness should hold the chain up against gravity, mostly.
+        for _ in 0..1000 {
+            spring_bone_system_inner(&mut world);
+        }
+        let tip_position = global_position(&world, tip);
+        assert!(tip_position.x > 0.9);
+        assert!(tip_position.y < 0.);
+    }
+
+    #[test]
+    pub fn test_spring_bone_collider() {
+        let mut world = World::new();
+        let (root, tip, _) = spawn_chain(&mut world);
+        let head = world.spawn((
+            LocalTransform::default(),
+            GlobalTransform(Affine3A::from_translation(Vec3::new(0., -1., 0.))),
+        ));
+        world
+            .insert_one(
+                root,
+                SpringBone {
+                    stiffness: 0.,
+                    gravity_power: 1.,
+                    hit_radius: 0.1,
+                    colliders: vec![SpringBoneCollider {
+                        entity: head,
+                        offset: Vec3::ZERO,
+                        radius: 0.5,
+                    }],
+                    ..SpringBone::new(vec![root, tip])
+                },
+            )
+            .unwrap();
+
+        // The tip shouldn't be able to swing through the sphere. Keeping the chain's length means it can end up a
+        // little way inside, but never hanging straight through it.
+        for _ in 0..1000 {
+            spring_bone_system_inner(&mut world);
+            let tip_position = global_position(&world, tip);
+            assert!(tip_position.distance(Vec3::new(0., -1., 0.)) > 0.59);
+        }
+    }
+
+    fn spawn_chain(world: &mut World) -> (Entity, Entity, Entity) {
+        let root = world.spawn((LocalTransform::default(), GlobalTransform::default()));
+        let tip = world.spawn((
+            LocalTransform {
+                translation: Vec3::X,
+                ..Default::default()
+            },
+            GlobalTransform::default(),
+            Parent(root),
+        ));
+        let hat = world.spawn((
+            LocalTransform {
+                translation: Vec3::X,
+                ..Default::default()
+            },
+            GlobalTransform::default(),
+            Parent(tip),
+        ));
+        (root, tip, hat)
+    }
+
+    fn global_position(world: &World, entity: Entity) -> Vec3 {
+        world
+            .get::<&GlobalTransform>(entity)
+            .unwrap()
+            .0
+            .translation
+            .into()
+    }
+}
@@ -11,7 +11,7 @@ pub fn update_global_transform_system(engine: &mut Engine) {
     update_global_transform_system_inner(world);
 }
 
-pub(crate) fn update_global_transform_system_inner(world: &mut World) {
+pub fn update_global_transform_system_inner(world: &mut World) {
     for (_, (local_transform, global_transform)) in
         world.query_mut::<(&LocalTransform, &mut GlobalTransform)>()
     {
@@ -15,7 +15,7 @@ pub fn update_global_transform_with_parent_system(engine: &mut Engine) {
     update_global_transform_with_parent_system_inner(world);
 }
 
-pub(crate) fn update_global_transform_with_parent_system_inner(world: &mut World) {
+pub fn update_global_transform_with_parent_system_inner(world: &mut World) {
     // Build hierarchy
     let mut hierarchy: HashMap<Entity, Vec<Entity>> = HashMap::new();
     for (entity, parent) in world.query_mut::<&Parent>() {
@@ -123,14 +123,13 @@ pub fn na_vector_from_glam(v: Vec3) -> Vector3<f32> {
 }
 
 #[inline]
-/// Convert a [`glam::Vec3`] into a [`rapier3d::na::Vector3`]
+/// Convert a [`rapier3d::na::Vector3`] into a [`glam::Vec3`]
 pub fn glam_vec_from_na(v: &Vector3<f32>) -> glam::Vec3 {
     [v.x, v.y, v.z].into()
 }
 
 #[cfg(test)]
 use crate::rendering::legacy_buffer::Buffer;
-#[cfg(test)]
 use ash::vk;
 #[cfg(test)]
 use std::marker::PhantomData;
@@ -196,51 +195,25 @@ pub(crate) fn get_asset_from_path(path: &str) -> Result<Vec<u8>> {
     Ok(asset.get_buffer()?.to_vec())
 }
 
+/// On desktop, assets are read from the `assets` directory in the current directory, which is where `cargo apk`
+/// packages them from with `[package.metadata.android] assets = "assets"`.
+#[cfg(not(target_os = "android"))]
+pub(crate) fn get_asset_from_path(path: &str) -> Result<Vec<u8>> {
+    let path = std::path::Path::new("assets").join(path);
+    std::fs::read(&path).map_err(|e| anyhow::anyhow!("Can't open: {:?} - {}", path, e))
+}
+
 #[cfg(test)]
 pub(crate) unsafe fn save_image_to_disk(
     vulkan_context: &VulkanContext,
     image: crate::rendering::image::Image,
     name: &str,
 ) -> Result<(), String> {
-    use crate::rendering::buffer::Buffer;
-    use image::{codecs::jpeg::JpegEncoder, DynamicImage, RgbaImage};
-
-    let resolution = image.extent;
-    let size = (resolution.height * resolution.width * 4) as usize;
-    let mut buffer = Buffer::new(&vulkan_context, vk::BufferUsageFlags::TRANSFER_DST, size);
+    use image::{codecs::jpeg::JpegEncoder, DynamicImage};
 
-    vulkan_context.device.device_wait_idle().unwrap();
-
-    vulkan_context.transition_image_layout(
-        image.handle,
-        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-        1,
-        1,
-    );
-    vulkan_context.copy_image_to_buffer(
-        &image,
-        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-        buffer.buffer,
-    );
-
-    // We have to set the buffer's length manually as `copy_image_to_buffer` doesn't.
-    buffer.len = size;
-
-    vulkan_context.device.device_wait_idle().unwrap();
-    let image_bytes = buffer.as_slice().to_vec();
-    assert_eq!(image_bytes.len(), size);
-    let image_from_vulkan = DynamicImage::ImageRgba8(
-        RgbaImage::from_raw(resolution.width, resolution.height, image_bytes).unwrap(),
-    );
+    let image_from_vulkan = DynamicImage::ImageRgba8(read_image_from_gpu(vulkan_context, &image));
     let known_good_path = format!("../test_assets/render_{}_known_good.jpg", name);
-    if env::var("UPDATE_IMAGES").map_or(false, |s| {
-        s.eq_ignore_ascii_case("true")
-            || s.eq_ignore_ascii_case("t")
-            || s.eq_ignore_ascii_case("yes")
-            || s.eq_ignore_ascii_case("y")
-            || s == "1"
-    }) {
+    if should_update_images() {
         let output_path = std::path::Path::new(&known_good_path);
         let mut file = std::fs::File::create(output_path).unwrap();
         let mut jpeg_encoder = JpegEncoder::new(&mut file);
@@ -268,6 +241,69 @@ pub(crate) unsafe fn save_image_to_disk(
     Ok(())
 }
 
+/// Should tests overwrite their known good images? Set `UPDATE_IMAGES=1` to update them.
+#[cfg(test)]
+pub(crate) fn should_update_images() -> bool {
+    env::var("UPDATE_IMAGES").map_or(false, |s| {
+        s.eq_ignore_ascii_case("true")
+            || s.eq_ignore_ascii_case("t")
+            || s.eq_ignore_ascii_case("yes")
+            || s.eq_ignore_ascii_case("y")
+            || s == "1"
+    })
+}
+
+/// Copy the contents of the first layer of a colour attachment back from the GPU. Waits for the GPU to be idle.
+pub(crate) unsafe fn read_image_from_gpu(
+    vulkan_context: &crate::contexts::VulkanContext,
+    image: &crate::rendering::image::Image,
+) -> image::RgbaImage {
+    use crate::rendering::buffer::Buffer;
+
+    let resolution = image.extent;
+    let size = (resolution.height * resolution.width * 4) as usize;
+    let mut buffer = Buffer::new(&vulkan_context, vk::BufferUsageFlags::TRANSFER_DST, size);
+
+    vulkan_context.device.device_wait_idle().unwrap();
+
+    vulkan_context.transition_image_layout(
+        image.handle,
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        1,
+        1,
+    );
+    vulkan_context.copy_image_to_buffer(
+        image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        buffer.buffer,
+    );
+
+    // We have to set the buffer's length manually as `copy_image_to_buffer` doesn't.
+    buffer.len = size;
+
+    vulkan_context.device.device_wait_idle().unwrap();
+    let mut image_bytes = buffer.as_slice().to_vec();
+    assert_eq!(image_bytes.len(), size);
+    if is_bgra(image.format) {
+        swap_red_and_blue(&mut image_bytes);
+    }
+    image::RgbaImage::from_raw(resolution.width, resolution.height, image_bytes).unwrap()
+}
+
+/// Are the color channels of `format` stored blue first?
+pub(crate) fn is_bgra(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM
+    )
+}
+
+/// Convert 8-bit RGBA pixels to BGRA, or back again.
+pub(crate) fn swap_red_and_blue(pixels: &mut [u8]) {
+    pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+}
+
 fn hash_file(file_path: &str) -> anyhow::Result<u64, ()> {
     use std::hash::Hasher;
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -295,6 +331,17 @@ pub(crate) fn end_renderdoc(renderdoc: &mut RenderDoc<renderdoc::V141>) {
     let _ = renderdoc.end_frame_capture(std::ptr::null(), std::ptr::null());
 }
 
+/// Get the rotation that points the forward (-Z) axis from `eye` towards `target`, keeping the up (+Y) axis as close
+/// to `up` as possible.
+///
+/// Returns `None` if `eye` and `target` are in the same place, or the direction to the target is parallel with `up`.
+pub fn look_at_rotation(eye: Vec3, target: Vec3, up: Vec3) -> Option<Quat> {
+    let forward = (target - eye).try_normalize()?;
+    let right = forward.cross(up).try_normalize()?;
+    let up = right.cross(forward);
+    Some(Quat::from_mat3(&glam::Mat3::from_cols(right, up, -forward)))
+}
+
 /// Interpolate between two affine transforms
 pub fn lerp_slerp(a: &Affine3A, b: &Affine3A, s: f32) -> Affine3A {
     let (a_scale, a_rotation, a_translation) = a.to_scale_rotation_translation();